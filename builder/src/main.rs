@@ -65,6 +65,9 @@ enum Commands {
     Test,
     /// Build everything.
     Build,
+    /// Build the WebAssembly preview used by the Client; this requires
+    /// `wasm-pack`.
+    Wasm,
     /// Change the version for the client, server, and extensions.
     ChangeVersion {
         /// The new version number, such as "0.1.1".
@@ -326,6 +329,7 @@ fn run_install(dev: bool) -> Result<(), Box<dyn std::error::Error>> {
         run_cmd!(
             cargo install --locked cargo-outdated;
             cargo install cargo-sort;
+            cargo install wasm-pack;
         )?;
     }
     Ok(())
@@ -404,6 +408,14 @@ fn run_build() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn run_wasm() -> Result<(), Box<dyn std::error::Error>> {
+    // The output directory is relative to the lexer's directory.
+    run_cmd!(
+        wasm-pack build lexer --target web --out-dir ../../client/static/wasm -- --features wasm;
+    )?;
+    Ok(())
+}
+
 fn run_change_version(new_version: &String) -> Result<(), Box<dyn std::error::Error>> {
     let replacement_string = format!("${{1}}{new_version}${{2}}");
     search_and_replace_file(
@@ -474,6 +486,7 @@ impl Cli {
             Commands::Update => run_update(),
             Commands::Test => run_test(),
             Commands::Build => run_build(),
+            Commands::Wasm => run_wasm(),
             Commands::ChangeVersion { new_version } => run_change_version(new_version),
             Commands::Prerelease => run_prerelease(),
             Commands::Postrelease { target, .. } => run_postrelease(target),
@@ -24,6 +24,11 @@
 // I can't get Mocha to work with ESBuild, so I import it using a script tag.
 import { assert } from "chai";
 import { exportedForTesting, page_init } from "./CodeChatEditor.mjs";
+import {
+    apply_doc_block_diffs,
+    apply_string_diffs,
+} from "./CodeMirror-integration.mjs";
+import { clean_pasted_html } from "./tinymce-config.mjs";
 
 // Re-export everything that [CodeChatEditor.mts](CodeChatEditor.mts) exports.
 // Otherwise, including [CodeChatEditor.mts](CodeChatEditor.mts) elsewhere would
@@ -118,6 +123,124 @@ window.CodeChatEditor_test = () => {
         });
     });
 
+    suite("CodeMirror-integration.mts", function () {
+        suite("apply_string_diffs", function () {
+            test("Apply changes using character offsets", function () {
+                assert.equal(
+                    apply_string_diffs("a😀b c", [
+                        { from: 1, to: 2, insert: "x" },
+                        { from: 3, to: 3, insert: "!" },
+                    ]),
+                    "axb! c",
+                );
+                assert.equal(apply_string_diffs("abc", []), "abc");
+            });
+        });
+
+        suite("apply_doc_block_diffs", function () {
+            test("Update, move, and delete doc blocks", function () {
+                assert.deepEqual(
+                    apply_doc_block_diffs(
+                        [
+                            [0, 1, "", "#", "a b"],
+                            [5, 6, "", "#", "c"],
+                            [9, 10, "", "#", "d"],
+                        ],
+                        [
+                            {
+                                Update: [
+                                    0,
+                                    {
+                                        from: 0,
+                                        to: 1,
+                                        indent: null,
+                                        delimiter: "//",
+                                        contents: [
+                                            { from: 2, to: 3, insert: "z" },
+                                        ],
+                                    },
+                                ],
+                            },
+                            // This moves a doc block to where a deleted doc
+                            // block started.
+                            {
+                                Update: [
+                                    5,
+                                    {
+                                        from: 9,
+                                        to: 10,
+                                        indent: null,
+                                        delimiter: null,
+                                        contents: [],
+                                    },
+                                ],
+                            },
+                            { Delete: 9 },
+                        ],
+                    ),
+                    [
+                        [0, 1, "", "//", "a z"],
+                        [9, 10, "", "#", "c"],
+                    ],
+                );
+            });
+
+            test("Replace all doc blocks", function () {
+                assert.deepEqual(
+                    apply_doc_block_diffs(
+                        [[0, 1, "", "#", "a"]],
+                        [
+                            { Delete: 0 },
+                            { Add: [3, 4, "", "#", "n"] },
+                            { Add: [0, 1, "", "#", "m"] },
+                        ],
+                    ),
+                    [
+                        [0, 1, "", "#", "m"],
+                        [3, 4, "", "#", "n"],
+                    ],
+                );
+            });
+        });
+    });
+
+    suite("tinymce-config.mts", function () {
+        suite("clean_pasted_html", function () {
+            test("Leave clean HTML unchanged", function () {
+                const html = "<p>Some <em>text</em> and <code>code</code>.</p>";
+                assert.equal(clean_pasted_html(html), html);
+            });
+
+            test("Clean inline HTML", function () {
+                assert.equal(
+                    clean_pasted_html(
+                        '<p>A <span style="color: red">red <b>bold</b></span> <font face="Arial">word</font>.</p>',
+                    ),
+                    "<p>A red <strong>bold</strong> word.</p>",
+                );
+                assert.equal(
+                    clean_pasted_html(
+                        '<span class="CodeChat-citation Mso" style="x" data-cite="a">[@a]</span>',
+                    ),
+                    '<span class="CodeChat-citation" data-cite="a">[@a]</span>',
+                );
+            });
+
+            test("Clean tables and keep custom elements", function () {
+                assert.equal(
+                    clean_pasted_html(
+                        '<table style="border: 1px" class="MsoTable"><tbody><tr><td colspan="2" width="50%"><font>Cell</font></td></tr></tbody></table><graphviz-graph graph="digraph {}"></graphviz-graph>',
+                    ),
+                    '<table><tbody><tr><td colspan="2" width="50%">Cell</td></tr></tbody></table><graphviz-graph graph="digraph {}"></graphviz-graph>',
+                );
+                assert.equal(
+                    clean_pasted_html('<img src="a.png" alt="A" style="x">'),
+                    '<img src="a.png" alt="A">',
+                );
+            });
+        });
+    });
+
     // Avoid an infinite loop of tests calling this again.
     delete window.CodeChatEditor_test;
     mocha.run();
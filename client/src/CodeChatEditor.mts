@@ -50,9 +50,14 @@ import "./wc-mermaid/wc-mermaid";
 
 // #### Local
 import {
+    apply_fold_ranges,
+    CodeMirror_apply_diff,
     CodeMirror_load,
     CodeMirror_save,
+    get_scroll_position,
     mathJaxTypeset,
+    scroll_to_position,
+    toggle_focus,
 } from "./CodeMirror-integration.mjs";
 import "./EditorComponents.mjs";
 import "./graphviz-webcomponent-setup.mts";
@@ -103,8 +108,19 @@ declare global {
         CodeChatEditor: {
             // Called by the Client Framework.
             open_lp: (all_source: CodeChatForWeb) => Promise<void>;
+            apply_diff: (diff: CodeMirrorDiff) => string | null;
+            scroll_to: (scroll_position: ScrollPosition) => void;
+            fold_to: (ranges: FoldRange[]) => void;
             on_save: (_only_if_dirty: boolean) => Promise<void>;
+            show_diagnostics: (diagnostics: Diagnostic[]) => void;
+            show_saved_state: (state: SaveState) => void;
+            insert_html: (html: string) => void;
+            apply_preferences: (preferences: Preferences) => void;
+            apply_capabilities: (capabilities: Capabilities) => void;
             allow_navigation: boolean;
+            // Set by the Client Framework when this document is shown in an
+            // additional view; otherwise, the Framework provides the path.
+            file_path?: string;
         };
         CodeChatEditor_test: any;
         MathJax: any;
@@ -120,17 +136,26 @@ let autosaveTimeoutId: null | number = null;
 // True to enable autosave.
 let autosaveEnabled = true;
 
+// The time, in ms, to wait after the last change before autosaving.
+let autosaveDelay = 1000;
+
 // Store the lexer info for the currently-loaded language.
 //
 // <a id="current_metadata"></a>This mirrors the data provided by the server --
 // see [SourceFileMetadata](../../server/src/webserver.rs#SourceFileMetadata).
 let current_metadata: {
     mode: string;
+    // For a Jupyter notebook, the notebook this file was translated from.
+    notebook?: string;
 };
 
 // True if the document is dirty (needs saving).
 let is_dirty = false;
 
+// The contents the Server embedded in this page, as JSON, until the Framework
+// provides them in an `Update`; see `open_lp`.
+let embedded_contents: string | undefined;
+
 // ### Markdown to HTML conversion
 //
 // Instantiate [turndown](https://github.com/mixmark-io/turndown) for HTML to
@@ -146,6 +171,158 @@ const turndownService = new TurndownService({
 // to enable conversions for tables, task lists, and strikethroughs.
 turndownService.use(gfm);
 
+// Translate admonitions (see
+// [admonitions.rs](../../server/src/processing/admonitions.rs)) back to the
+// syntax they were written in. The title is part of the admonition's opening
+// line, so drop the title paragraph from the contents.
+turndownService.addRule("admonition-title", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "P" &&
+        node.classList.contains("CodeChat-admonition-title") &&
+        (node.parentNode as HTMLElement | null)?.classList.contains(
+            "CodeChat-admonition",
+        ) === true,
+    replacement: () => "",
+});
+turndownService.addRule("admonition", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "DIV" &&
+        node.classList.contains("CodeChat-admonition"),
+    replacement: (content: string, node: HTMLElement) => {
+        const kind = node.getAttribute("data-kind") ?? "note";
+        const contents = content.trim();
+        if (node.getAttribute("data-syntax") === "gfm") {
+            const body = contents
+                .split("\n")
+                .map((line) => (line ? `> ${line}` : ">"))
+                .join("\n");
+            return `\n\n> [!${kind.toUpperCase()}]\n${body}\n\n`;
+        }
+        // Only include the title if it differs from the default title, which
+        // is the capitalized kind.
+        const title =
+            node
+                .querySelector(":scope > p.CodeChat-admonition-title")
+                ?.textContent?.trim() ?? "";
+        const default_title = kind.charAt(0).toUpperCase() + kind.slice(1);
+        const title_suffix = title && title !== default_title ? ` ${title}` : "";
+        return `\n\n:::${kind}${title_suffix}\n\n${contents}\n\n:::\n\n`;
+    },
+});
+
+// Restore conditional blocks (see
+// [conditions.rs](../../server/src/processing/conditions.rs)), including those
+// whose condition fails, to their fenced syntax.
+turndownService.addRule("conditional", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "DIV" && node.classList.contains("CodeChat-if"),
+    replacement: (content: string, node: HTMLElement) =>
+        `\n\n:::if ${node.getAttribute("data-condition")}\n\n${content.trim()}\n\n:::\n\n`,
+});
+
+// Restore citations (see
+// [citations.rs](../../server/src/processing/citations.rs)) to their original
+// text. The server generates the references section, so discard it.
+turndownService.addRule("citation", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "SPAN" &&
+        node.classList.contains("CodeChat-citation"),
+    replacement: (_content: string, node: HTMLElement) =>
+        `[${node.getAttribute("data-cite")}]`,
+});
+turndownService.addRule("references", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "DIV" &&
+        node.classList.contains("CodeChat-references"),
+    replacement: () => "",
+});
+
+// Preserve explicit heading IDs (see
+// [headings.rs](../../server/src/processing/headings.rs)); IDs generated by
+// the server are marked with a `data-codechat-slug` attribute, so they aren't
+// saved. Anchors for redirected IDs are likewise generated by the server.
+turndownService.addRule("heading-id", {
+    filter: (node: HTMLElement) =>
+        /^H[1-6]$/.test(node.nodeName) &&
+        node.id !== "" &&
+        !node.hasAttribute("data-codechat-slug"),
+    replacement: (content: string, node: HTMLElement) =>
+        `\n\n${"#".repeat(Number(node.nodeName.charAt(1)))} ${content} {#${node.id}}\n\n`,
+});
+turndownService.addRule("anchor-redirect", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "A" &&
+        node.classList.contains("CodeChat-anchor-redirect"),
+    replacement: () => "",
+});
+
+// Restore labels and references to figures, tables, and equations (see
+// [numbering.rs](../../server/src/processing/numbering.rs)) to their original
+// text.
+turndownService.addRule("label", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "SPAN" && node.classList.contains("CodeChat-label"),
+    replacement: (_content: string, node: HTMLElement) =>
+        `{#${node.getAttribute("data-label")}}`,
+});
+turndownService.addRule("label-ref", {
+    filter: (node: HTMLElement) =>
+        (node.nodeName === "A" || node.nodeName === "SPAN") &&
+        node.classList.contains("CodeChat-ref"),
+    replacement: (_content: string, node: HTMLElement) =>
+        `\\ref{${node.getAttribute("data-ref")}}`,
+});
+
+// Restore emoji (see [emoji.rs](../../server/src/processing/emoji.rs)) to the
+// shortcodes they were written as.
+turndownService.addRule("emoji", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "SPAN" && node.classList.contains("CodeChat-emoji"),
+    replacement: (_content: string, node: HTMLElement) =>
+        `:${node.getAttribute("data-shortcode")}:`,
+});
+
+// Restore macros (see [macros.rs](../../server/src/processing/macros.rs)) to
+// their names, rather than saving their current values.
+turndownService.addRule("macro", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "SPAN" && node.classList.contains("CodeChat-macro"),
+    replacement: (_content: string, node: HTMLElement) =>
+        `{{${node.getAttribute("data-macro")}}}`,
+});
+
+// Restore rendered fences, such as diagrams (see
+// [fences.rs](../../server/src/processing/fences.rs)), to their source.
+turndownService.addRule("fence", {
+    filter: (node: HTMLElement) =>
+        node.nodeName === "DIV" && node.classList.contains("CodeChat-fence"),
+    replacement: (_content: string, node: HTMLElement) => {
+        const source = node.getAttribute("data-source") ?? "";
+        const fence = source.includes("```") ? "~~~~" : "```";
+        const newline = source.endsWith("\n") ? "" : "\n";
+        return `\n\n${fence}${node.getAttribute("data-language")}\n${source}${newline}${fence}\n\n`;
+    },
+});
+
+// Restore embedded audio and video (see
+// [media.rs](../../server/src/processing/media.rs)) to the image syntax they
+// were written in. After strict sanitization, only the player's fallback link
+// remains, which is restored in the same way.
+turndownService.addRule("media", {
+    filter: (node: HTMLElement) =>
+        ["AUDIO", "VIDEO", "A"].includes(node.nodeName) &&
+        node.classList.contains("CodeChat-media"),
+    replacement: (_content: string, node: HTMLElement) => {
+        const link =
+            node.nodeName === "A"
+                ? node
+                : node.querySelector(":scope > a.CodeChat-media");
+        const title = node.getAttribute("title");
+        const title_suffix = title ? ` "${title}"` : "";
+        return `![${link?.textContent ?? ""}](${link?.getAttribute("href") ?? ""}${title_suffix})`;
+    },
+});
+
 // ## Page initialization
 //
 // Load the dynamic content into the static page.
@@ -162,17 +339,123 @@ export const page_init = () => {
             "navigate",
             on_navigate,
         );
+        // Jump to the definition of an identifier in inline code.
+        document.addEventListener("click", on_click_code, true);
+        // Open links beside the current file in the IDE.
+        document.addEventListener("click", on_click_link, true);
+        // Perform the actions assigned to keyboard shortcuts.
+        keymap = JSON.parse(
+            document.getElementById("CodeChat-keymap")?.textContent ?? "{}",
+        );
+        document.addEventListener("keydown", on_keydown, true);
 
         window.CodeChatEditor = {
             open_lp,
+            apply_diff,
+            scroll_to,
+            fold_to,
             on_save,
+            show_diagnostics,
+            show_saved_state,
+            insert_html,
+            apply_preferences,
+            apply_capabilities,
             allow_navigation: false,
         };
+
+        // Show the contents embedded in this page, rather than waiting for the
+        // Framework to send them.
+        const embedded_json = document.getElementById(
+            "CodeChat-initial-contents",
+        )?.textContent;
+        if (embedded_json) {
+            const all_source = JSON.parse(embedded_json) as CodeChatForWeb;
+            embedded_contents = JSON.stringify(all_source);
+            _open_lp(all_source);
+        }
     });
 };
 
+// Apply the user's preferences, which the Client Framework provides.
+const apply_preferences = (preferences: Preferences) => {
+    if (preferences.theme) {
+        for (const class_name of [...document.body.classList]) {
+            if (class_name.startsWith("CodeChat-theme-")) {
+                document.body.classList.remove(class_name);
+            }
+        }
+        document.body.classList.add(`CodeChat-theme-${preferences.theme}`);
+    }
+    if (preferences.font_size) {
+        document.body.style.fontSize = `${preferences.font_size}px`;
+    }
+    if (preferences.autosave_delay) {
+        autosaveDelay = preferences.autosave_delay;
+    }
+};
+
+// Show only the optional features enabled by the Server.
+const apply_capabilities = (capabilities: Capabilities) => {
+    const spellcheck = capabilities.spellcheck ? "true" : "false";
+    document
+        .getElementById("TinyMCE-inst")
+        ?.setAttribute("spellcheck", spellcheck);
+    tinymce.activeEditor?.getBody().setAttribute("spellcheck", spellcheck);
+};
+
+// ### Keyboard shortcuts
+//
+// The Server provides the
+// [keymap](../../server/src/webserver/keymap.rs), which assigns a shortcut,
+// such as `Mod-s`, to each action.
+let keymap: Record<string, string> = {};
+
+// The actions which shortcuts perform.
+const keymap_actions: Record<string, () => void> = {
+    save: () => save(false),
+    save_all: () => save(true),
+    export_pdf: () => export_pdf(false),
+    export_project_pdf: () => export_pdf(true),
+    toggle_focus,
+};
+
+// Return true if the provided shortcut matches this keyboard event. `Mod` is
+// Cmd on a Mac and Ctrl elsewhere.
+const shortcut_matches = (shortcut: string, event: KeyboardEvent) => {
+    const parts = shortcut.split("-");
+    const key = parts.pop()!;
+    const modifiers = new Set(parts.map((part) => part.toLowerCase()));
+    const is_mac = navigator.platform.startsWith("Mac");
+    return (
+        key !== "" &&
+        event.key.toLowerCase() === key.toLowerCase() &&
+        event.ctrlKey ===
+            (modifiers.has("ctrl") || (!is_mac && modifiers.has("mod"))) &&
+        event.metaKey ===
+            (modifiers.has("meta") || (is_mac && modifiers.has("mod"))) &&
+        event.altKey === modifiers.has("alt") &&
+        event.shiftKey === modifiers.has("shift")
+    );
+};
+
+// Perform the action assigned to a shortcut.
+const on_keydown = (event: KeyboardEvent) => {
+    for (const [action, shortcut] of Object.entries(keymap)) {
+        const perform = keymap_actions[action];
+        if (perform !== undefined && shortcut_matches(shortcut, event)) {
+            event.preventDefault();
+            perform();
+            return;
+        }
+    }
+};
+
 export const set_is_dirty = (value: boolean = true) => {
     is_dirty = value;
+    // Edits are unsaved until the IDE or File Watcher reports otherwise.
+    if (value) {
+        show_saved_state("Dirty");
+    }
 };
 
 // This is copied from
@@ -189,14 +472,56 @@ const on_dom_content_loaded = (on_load_func: () => void) => {
 
 // ## File handling
 //
+// Show the problems the Server found in this file in a panel over the editor,
+// replacing any previously shown. An empty list removes them.
+const show_diagnostics = (diagnostics: Diagnostic[]) => {
+    document.getElementById("CodeChat-diagnostics")?.remove();
+    if (diagnostics.length === 0) {
+        return;
+    }
+    const ul = document.createElement("ul");
+    ul.id = "CodeChat-diagnostics";
+    for (const diagnostic of diagnostics) {
+        const { start, end } = diagnostic.range;
+        const li = document.createElement("li");
+        li.className = `CodeChat-diagnostic-${diagnostic.severity.toLowerCase()}`;
+        // Use `textContent` so that the message isn't interpreted as HTML.
+        const lines =
+            end - start > 1 ? `Lines ${start}-${end - 1}` : `Line ${start}`;
+        li.textContent = `${lines}: ${diagnostic.message} (${diagnostic.source})`;
+        ul.appendChild(li);
+    }
+    document.body.appendChild(ul);
+};
+
+// Show whether this file has unsaved changes, as reported by the IDE or the
+// File Watcher, by marking its name.
+const show_saved_state = (state: SaveState) => {
+    const filename = document.getElementById("CodeChat-filename");
+    if (filename !== null) {
+        filename.classList.toggle("CodeChat-dirty", state === "Dirty");
+        filename.title = state === "Dirty" ? "Unsaved changes" : "Saved";
+    }
+};
+
 // True if this is a CodeChat Editor document (not a source file).
 const is_doc_only = () => {
     return current_metadata["mode"] === "markdown";
 };
 
-// Wait for the DOM to load before opening the file.
-const open_lp = async (all_source: CodeChatForWeb) =>
-    on_dom_content_loaded(() => _open_lp(all_source));
+// Wait for the DOM to load before opening the file. The Framework's first
+// `Update` usually repeats the contents embedded in this page, which are
+// already shown; skip these, so that edits made since then aren't lost.
+const open_lp = async (all_source: CodeChatForWeb) => {
+    if (embedded_contents !== undefined) {
+        const is_embedded = JSON.stringify(all_source) === embedded_contents;
+        embedded_contents = undefined;
+        if (is_embedded) {
+            return;
+        }
+    }
+    return on_dom_content_loaded(() => _open_lp(all_source));
+};
 
 // This function is called on page load to "load" a file. Before this point, the
 // server has already lexed the source file into code and doc blocks; this
@@ -229,6 +554,9 @@ const _open_lp = async (
     // Disable autosave when updating the document.
     autosaveEnabled = false;
     clearAutosaveTimer();
+    // Problems reported for the previous contents no longer apply; the Server
+    // sends any for these contents after this update.
+    show_diagnostics([]);
     // Before calling any MathJax, make sure it's fully loaded.
     await window.MathJax.startup.promise;
     // Per the
@@ -257,7 +585,7 @@ const _open_lp = async (
                     // [editor core events list](https://www.tiny.cloud/docs/tinymce/6/events/#editor-core-events)
                     // includes the `Dirty` event.
                     editor.on("Dirty", (_event: Event) => {
-                        is_dirty = true;
+                        set_is_dirty();
                         startAutosaveTimer();
                     });
                 },
@@ -286,9 +614,60 @@ const _open_lp = async (
     }
 };
 
+// Apply the changes the Server sent to the contents it last sent, returning an
+// error message if this document no longer shows these contents. A document
+// shown only as Markdown is reformatted by its editor, so it needs the new
+// contents instead.
+const apply_diff = (diff: CodeMirrorDiff) => {
+    if (is_dirty) {
+        return "This document has unsaved changes.";
+    }
+    if (is_doc_only() || !CodeMirror_apply_diff(diff)) {
+        return "This document can't apply changes.";
+    }
+    // The contents embedded in this page are now out of date.
+    embedded_contents = undefined;
+    // As with an `Update`, the Server sends any problems in these contents
+    // next.
+    show_diagnostics([]);
+    return null;
+};
+
+// Scroll to the provided position, which the Server sends when the IDE
+// scrolls. A document shown only as Markdown has no blocks to scroll to.
+const scroll_to = (scroll_position: ScrollPosition) => {
+    if (!is_doc_only()) {
+        scroll_to_position(
+            document.getElementById("CodeChat-body")!,
+            scroll_position,
+        );
+    }
+};
+
+// Fold the provided regions, which the Server sends when the IDE folds or
+// unfolds. Like scrolling, this needs blocks.
+const fold_to = (ranges: FoldRange[]) => {
+    if (!is_doc_only()) {
+        apply_fold_ranges(ranges);
+    }
+};
+
+// Tell the IDE that the user folded or unfolded a region.
+export const send_fold_ranges = (ranges: FoldRange[]) => {
+    parent.window.CodeChatEditorFramework.webSocketComm.send_message({
+        FoldRanges: {
+            // The Framework will fill in this value, unless this is an
+            // additional view.
+            file_path: window.CodeChatEditor.file_path ?? "",
+            ranges,
+        },
+    });
+};
+
 const save_lp = async () => {
     /// @ts-expect-error
     let source: CodeChatForWeb["source"] = {};
+    let scroll_position: ScrollPosition | undefined = undefined;
     if (is_doc_only()) {
         // Untypeset all math before saving the document.
         const codechat_body = document.getElementById(
@@ -311,16 +690,20 @@ const save_lp = async () => {
     } else {
         source = CodeMirror_save();
         await codechat_html_to_markdown(source);
+        scroll_position = get_scroll_position(
+            document.getElementById("CodeChat-body")!,
+        );
     }
 
     let update: UpdateMessageContents = {
-        // The Framework will fill in this value.
-        file_path: "",
+        // The Framework will fill in this value, unless this is an additional
+        // view.
+        file_path: window.CodeChatEditor.file_path ?? "",
         contents: {
             metadata: current_metadata,
             source,
         },
-        scroll_position: undefined,
+        scroll_position,
         cursor_position: undefined,
     };
     return update;
@@ -343,7 +726,6 @@ const on_save = async (only_if_dirty: boolean = false) => {
     // <a id="save"></a>Save the provided contents back to the filesystem, by
     // sending an update message over the websocket.
     const webSocketComm = parent.window.CodeChatEditorFramework.webSocketComm;
-    console.log("Sent Update - saving document.");
     await new Promise(async (resolve) => {
         webSocketComm.send_message({ Update: await save_lp() }, () =>
             resolve(0),
@@ -352,6 +734,48 @@ const on_save = async (only_if_dirty: boolean = false) => {
     is_dirty = false;
 };
 
+// Send any unsaved changes, then ask the IDE to save this file to disk or, if
+// `all`, every file it has open.
+const save = async (all: boolean) => {
+    await on_save();
+    parent.window.CodeChatEditorFramework.webSocketComm.send_message(
+        all
+            ? { SaveAll: null }
+            : // The Server names the current file, unless this is an
+              // additional view.
+              { Save: window.CodeChatEditor.file_path ?? null },
+    );
+};
+
+// Export the current document, or its whole project, to a PDF, saving it
+// first so that the PDF includes any edits.
+const export_pdf = async (project: boolean) => {
+    await on_save();
+    parent.window.CodeChatEditorFramework.webSocketComm.export_pdf(
+        project,
+        (result) =>
+            window.alert(
+                "path" in result
+                    ? `Exported to ${result.path}.`
+                    : `Unable to export: ${result.error}`,
+            ),
+    );
+};
+
+// Return the source of a doc block written in reStructuredText (see
+// [rst.rs](../../server/src/processing/rst.rs)) or AsciiDoc (see
+// [asciidoc.rs](../../server/src/processing/asciidoc.rs)), or `undefined` if
+// this doc block's HTML was rendered from Markdown.
+const markup_source = (html: string) => {
+    const template = document.createElement("template");
+    template.innerHTML = html;
+    const div = template.content.firstElementChild;
+    return div?.classList.contains("CodeChat-rst") ||
+        div?.classList.contains("CodeChat-asciidoc")
+        ? (div.getAttribute("data-source") ?? "")
+        : undefined;
+};
+
 const codechat_html_to_markdown = async (source: any) => {
     // Join all the doc blocks, then convert them to Markdown, then split them
     // back.
@@ -379,6 +803,12 @@ const codechat_html_to_markdown = async (source: any) => {
     // indent - delimiter length - 1 space that always follows the delimiter.
     // Use a minimum width of 40 characters.
     for (const [index, doc_block] of source.doc_blocks.entries()) {
+        // Save doc blocks written in reStructuredText or AsciiDoc unchanged.
+        const markup = markup_source(doc_block[4]);
+        if (markup !== undefined) {
+            doc_block[4] = markup || "\n";
+            continue;
+        }
         const dbm = doc_blocks_markdown[index];
         doc_block[4] =
             (await prettier_markdown(
@@ -407,10 +837,7 @@ export const startAutosaveTimer = () => {
     // timeout...
     clearAutosaveTimer();
     // ...then start another timeout which saves the document when it expires.
-    autosaveTimeoutId = window.setTimeout(() => {
-        console.log("Autosaving.");
-        on_save();
-    }, 1000);
+    autosaveTimeoutId = window.setTimeout(() => on_save(), autosaveDelay);
 };
 
 const clearAutosaveTimer = () => {
@@ -475,7 +902,6 @@ const on_navigate = (navigateEvent: NavigateEvent) => {
 
     // Intercept this navigation so we can save the document first.
     navigateEvent.intercept();
-    console.log("CodeChat Editor: saving document before navigation.");
     on_save(true).then((_value) => {
         // Avoid recursion!
         /// @ts-ignore
@@ -486,6 +912,69 @@ const on_navigate = (navigateEvent: NavigateEvent) => {
     });
 };
 
+// When the user Ctrl+clicks (Cmd+clicks on a Mac) inline code in a doc block,
+// such as `` `parse_args` ``, ask the IDE to show its definition.
+const on_click_code = (event: MouseEvent) => {
+    if (!(event.ctrlKey || event.metaKey)) {
+        return;
+    }
+    const code = (event.target as HTMLElement).closest?.("code");
+    // Ignore code blocks, which contain code rather than a name.
+    if (code === null || code === undefined || code.closest("pre") !== null) {
+        return;
+    }
+    const identifier = code.textContent?.trim();
+    if (!identifier) {
+        return;
+    }
+    event.preventDefault();
+    parent.window.CodeChatEditorFramework.webSocketComm.goto_definition(
+        identifier,
+    );
+};
+
+// When the user Alt+clicks a link to another file, ask the IDE to show it
+// beside the current file, as VSCode does when Alt+clicking a file in its
+// Explorer.
+const on_click_link = (event: MouseEvent) => {
+    if (!event.altKey) {
+        return;
+    }
+    const link = (event.target as HTMLElement).closest?.("a");
+    if (link === null || link === undefined || link.href === "") {
+        return;
+    }
+    const url = new URL(link.href);
+    // Let the browser handle links outside the Server and links within this
+    // document.
+    if (
+        url.host !== window.location.host ||
+        (url.pathname === window.location.pathname && url.hash !== "")
+    ) {
+        return;
+    }
+    event.preventDefault();
+    on_save(true).then((_value) => {
+        window.CodeChatEditor.allow_navigation = true;
+        parent.window.CodeChatEditorFramework.webSocketComm.current_file(
+            url,
+            "Beside",
+        );
+    });
+};
+
+// Insert HTML, such as a snippet provided by the Server, at the cursor in the
+// doc block being edited.
+const insert_html = (html: string) => {
+    if (tinymce.activeEditor === null) {
+        console.log("CodeChat Editor: no doc block to insert into.");
+        return;
+    }
+    tinymce.activeEditor.insertContent(html);
+    set_is_dirty();
+    startAutosaveTimer();
+};
+
 // ## Testing
 //
 // A great and simple idea taken from
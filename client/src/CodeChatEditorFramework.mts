@@ -29,6 +29,9 @@
 // #### Third-party
 import ReconnectingWebSocket from "./ReconnectingWebSocket.cjs";
 
+// #### Local
+import { preview } from "./preview.mjs";
+
 // ## Websocket
 //
 // This code communicates with the CodeChat Editor Server via its websocket
@@ -43,14 +46,97 @@ interface EditorMessage {
     message: EditorMessageContents;
 }
 
-type ResultType = { Ok: "Void" } | { Err: string };
+type ResultType =
+    | { Ok: "Void" }
+    | { Ok: { OpenFiles: string[] } }
+    | { Ok: { Outline: OutlineEntry[] } }
+    | { Ok: { Snippets: string[] } }
+    | { Ok: { Snippet: Snippet } }
+    | { Ok: { Preferences: Preferences } }
+    | { Ok: { Exported: string } }
+    | { Err: string };
 
 interface EditorMessageContents {
     Update?: UpdateMessageContents;
-    CurrentFile?: string;
+    UpdateDiff?: UpdateDiffContents;
+    CurrentFile?: CurrentFileContents;
     Load?: string;
     Result?: ResultType;
     RequestClose?: null;
+    Diagnostics?: Diagnostic[];
+    SetFileLanguage?: SetFileLanguageContents;
+    ListOpenFiles?: null;
+    SwitchFile?: string;
+    OpenView?: string;
+    CloseView?: string;
+    Outline?: string;
+    GotoDefinition?: GotoDefinitionContents;
+    OpenUrl?: string;
+    Save?: string | null;
+    SaveAll?: null;
+    Export?: ExportContents;
+    ListSnippets?: string;
+    InsertSnippet?: InsertSnippetContents;
+    Preferences?: Preferences | null;
+    FoldRanges?: FoldRangesContents;
+    SavedState?: SavedStateContents;
+    Capabilities?: Capabilities;
+}
+
+// See
+// [CurrentFileContents](../../server/src/webserver.rs#CurrentFileContents).
+interface CurrentFileContents {
+    file_path: string;
+    view_target: ViewTarget;
+}
+
+// See [ViewTarget](../../server/src/webserver.rs#ViewTarget).
+type ViewTarget = "Active" | "Beside" | { Group: number };
+
+// See [ExportContents](../../server/src/webserver.rs#ExportContents).
+interface ExportContents {
+    file_path: string;
+    // True to export the current document's whole project.
+    project: boolean;
+}
+
+// See
+// [InsertSnippetContents](../../server/src/webserver.rs#InsertSnippetContents).
+interface InsertSnippetContents {
+    file_path: string;
+    name: string;
+}
+
+// See [Snippet](../../server/src/processing/snippets.rs#Snippet).
+interface Snippet {
+    markdown: string;
+    source: string;
+    html: string;
+}
+
+// See
+// [GotoDefinitionContents](../../server/src/webserver.rs#GotoDefinitionContents).
+interface GotoDefinitionContents {
+    file_path: string;
+    identifier: string;
+    // The Client always sends `null`; the Server fills this in.
+    definition: null;
+}
+
+// See [OutlineEntry](../../server/src/processing/outline.rs#OutlineEntry).
+interface OutlineEntry {
+    level: number;
+    title: string;
+    line: number;
+}
+
+// See
+// [SetFileLanguageContents](../../server/src/webserver.rs#SetFileLanguageContents).
+interface SetFileLanguageContents {
+    file_path: string;
+    // The name of the lexer to use, or `null` to return to the lexer chosen by
+    // the file's extension and contents.
+    language: string | null;
 }
 
 // The max length of a message to show in the console.
@@ -73,16 +159,35 @@ class WebSocketComm {
         number,
         {
             timer_id: number;
-            callback: () => void;
+            callback: (result: ResultType) => void;
         }
     > = {};
     // True when the iframe is loading, so that an `Update` should be postponed
     // until the page load is finished. Otherwise, the page is fully loaded, so
     // the `Update` may be applied immediately.
     onloading = false;
+    // The user's preferences, provided by the Server.
+    preferences: Preferences = {};
+    // The optional features enabled for this connection, provided by the
+    // Server.
+    capabilities: Capabilities | undefined = undefined;
     // The current filename of the file being edited. This is provided by the
     // IDE and passed back to it, but not otherwise used by the Framework.
     current_filename: string | undefined = undefined;
+    // For each file, the ID of the message which sent the contents it shows;
+    // an `UpdateDiff` applies only to these contents. A file whose contents
+    // the Client sent has none.
+    base_ids: Record<string, number> = {};
+    // An additional view, showing a second file alongside the current file.
+    // Its `file_path` is set by the first `Update` received while it loads.
+    view:
+        | {
+              url: URL;
+              iframe: HTMLIFrameElement;
+              onloading: boolean;
+              file_path: string | undefined;
+          }
+        | undefined = undefined;
 
     constructor(ws_url: string) {
         // The `ReconnectingWebSocket` doesn't provide ALL the `WebSocket`
@@ -93,6 +198,8 @@ class WebSocketComm {
         // Identify this client on connection.
         this.ws.onopen = () => {
             console.log(`CodeChat Client: websocket to CodeChat Server open.`);
+            // Load the user's preferences.
+            this.send_message({ Preferences: null }, this.on_preferences);
         };
 
         // Provide logging to help track down errors.
@@ -125,6 +232,40 @@ class WebSocketComm {
                 case "Update":
                     // Load this data in.
                     const current_update = value as UpdateMessageContents;
+                    // Send updates for the file in the additional view to it.
+                    const view = this.view;
+                    if (
+                        view !== undefined &&
+                        (view.file_path === current_update.file_path ||
+                            (view.file_path === undefined &&
+                                view.onloading &&
+                                current_update.file_path !==
+                                    this.current_filename))
+                    ) {
+                        view.file_path = current_update.file_path;
+                        const view_contents = current_update.contents;
+                        if (view_contents !== null && view_contents !== undefined) {
+                            this.base_ids[view.file_path] = id;
+                            const open_view = () => {
+                                const cce = view.iframe.contentWindow!.CodeChatEditor;
+                                // Saves from this view must name its file.
+                                cce.file_path = view.file_path;
+                                cce.open_lp(view_contents);
+                                cce.apply_preferences(this.preferences);
+                                if (this.capabilities !== undefined) {
+                                    cce.apply_capabilities(this.capabilities);
+                                }
+                                view.onloading = false;
+                            };
+                            if (view.onloading) {
+                                view.iframe.onload = open_view;
+                            } else {
+                                open_view();
+                            }
+                        }
+                        this.send_result(id, null);
+                        break;
+                    }
                     // Check or update the `current_filename`.
                     if (this.current_filename === undefined) {
                         this.current_filename = current_update.file_path;
@@ -136,23 +277,46 @@ class WebSocketComm {
                     }
                     let result = null;
                     const contents = current_update.contents;
+                    const scroll_position = current_update.scroll_position;
+                    // Scroll to the IDE's position, once the contents are
+                    // shown.
+                    const scroll = () => {
+                        if (
+                            scroll_position !== null &&
+                            scroll_position !== undefined
+                        ) {
+                            root_iframe!.contentWindow!.CodeChatEditor.scroll_to(
+                                scroll_position,
+                            );
+                        }
+                    };
                     if (contents !== null && contents !== undefined) {
+                        this.base_ids[current_update.file_path] = id;
                         // If the page is still loading, wait until the load
                         // completed before updating the editable contents.
                         if (this.onloading) {
-                            root_iframe!.onload = () => {
-                                root_iframe!.contentWindow!.CodeChatEditor.open_lp(
+                            root_iframe!.onload = async () => {
+                                await root_iframe!.contentWindow!.CodeChatEditor.open_lp(
                                     contents,
                                 );
+                                this.apply_preferences();
                                 this.onloading = false;
+                                scroll();
                             };
                         } else {
                             root_iframe!.contentWindow!.CodeChatEditor.open_lp(
                                 contents,
-                            );
+                            ).then(scroll);
+                            this.apply_preferences();
                         }
+                    } else if (
+                        scroll_position !== null &&
+                        scroll_position !== undefined &&
+                        !this.onloading
+                    ) {
+                        scroll();
                     } else {
-                        // TODO: handle scroll/cursor updates.
+                        // TODO: handle cursor updates.
                         result = `Unhandled Update message: ${current_update}`;
                         console.log(result);
                     }
@@ -160,8 +324,54 @@ class WebSocketComm {
                     this.send_result(id, result);
                     break;
 
+                case "UpdateDiff":
+                    // Apply these changes in whichever view shows this file,
+                    // if it still shows the contents they change. Otherwise,
+                    // the Server sends the new contents in an `Update`.
+                    const update_diff = value as UpdateDiffContents;
+                    const diff_view = this.view;
+                    let diff_cce: typeof window.CodeChatEditor | undefined;
+                    if (
+                        diff_view !== undefined &&
+                        diff_view.file_path === update_diff.file_path
+                    ) {
+                        if (!diff_view.onloading) {
+                            diff_cce =
+                                diff_view.iframe.contentWindow?.CodeChatEditor;
+                        }
+                    } else if (
+                        update_diff.file_path === this.current_filename &&
+                        !this.onloading
+                    ) {
+                        diff_cce = root_iframe?.contentWindow?.CodeChatEditor;
+                    }
+                    let diff_result: string | null =
+                        `${update_diff.file_path} doesn't show the contents these changes apply to.`;
+                    if (
+                        diff_cce !== undefined &&
+                        this.base_ids[update_diff.file_path] ===
+                            update_diff.base_id
+                    ) {
+                        diff_result = diff_cce.apply_diff(update_diff.diff);
+                    }
+                    if (diff_result === null) {
+                        this.base_ids[update_diff.file_path] = id;
+                        const scroll_position = update_diff.scroll_position;
+                        if (
+                            scroll_position !== null &&
+                            scroll_position !== undefined
+                        ) {
+                            diff_cce!.scroll_to(scroll_position);
+                        }
+                    }
+                    this.send_result(id, diff_result);
+                    break;
+
                 case "CurrentFile":
-                    const current_file = value as string;
+                    // The Client shows only one file, so it ignores the
+                    // `view_target`.
+                    const current_file = (value as CurrentFileContents)
+                        .file_path;
                     // If the page is still loading, then don't save. Otherwise,
                     // save the editor contents if necessary.
                     let cce = root_iframe?.contentWindow?.CodeChatEditor;
@@ -194,20 +404,109 @@ class WebSocketComm {
                     });
                     break;
 
+                case "RequestClose":
+                    // Save any unsaved data, then tell the IDE that it's safe
+                    // to close.
+                    const closing_cce =
+                        root_iframe?.contentWindow?.CodeChatEditor;
+                    const saved =
+                        closing_cce !== undefined && !this.onloading
+                            ? closing_cce.on_save(true)
+                            : Promise.resolve();
+                    saved.then(() => {
+                        if (closing_cce !== undefined) {
+                            closing_cce.allow_navigation = true;
+                        }
+                        this.send_result(id, null);
+                    });
+                    break;
+
+                case "Diagnostics":
+                    const diagnostics = value as Diagnostic[];
+                    // This follows an `Update`; if the page is still loading,
+                    // show these once that `Update` is applied.
+                    if (this.onloading) {
+                        const onload = root_iframe!.onload;
+                        root_iframe!.onload = (event: Event) => {
+                            onload?.call(root_iframe!, event);
+                            root_iframe!.contentWindow!.CodeChatEditor.show_diagnostics(
+                                diagnostics,
+                            );
+                        };
+                    } else {
+                        root_iframe!.contentWindow!.CodeChatEditor.show_diagnostics(
+                            diagnostics,
+                        );
+                    }
+                    this.send_result(id, null);
+                    break;
+
+                case "FoldRanges":
+                    const fold_ranges = value as FoldRangesContents;
+                    // Fold the additional view if it shows this file.
+                    const fold_view = this.view;
+                    if (
+                        fold_view !== undefined &&
+                        fold_view.file_path === fold_ranges.file_path
+                    ) {
+                        if (!fold_view.onloading) {
+                            fold_view.iframe.contentWindow!.CodeChatEditor.fold_to(
+                                fold_ranges.ranges,
+                            );
+                        }
+                    } else if (this.onloading) {
+                        // Like `Diagnostics`, this follows an `Update`; fold
+                        // once that `Update` is applied.
+                        const onload = root_iframe!.onload;
+                        root_iframe!.onload = async (event: Event) => {
+                            await onload?.call(root_iframe!, event);
+                            root_iframe!.contentWindow!.CodeChatEditor.fold_to(
+                                fold_ranges.ranges,
+                            );
+                        };
+                    } else {
+                        root_iframe!.contentWindow!.CodeChatEditor.fold_to(
+                            fold_ranges.ranges,
+                        );
+                    }
+                    this.send_result(id, null);
+                    break;
+
+                case "SavedState":
+                    const saved_state = value as SavedStateContents;
+                    // Show this in whichever view shows this file.
+                    const saved_view = this.view;
+                    const saved_cce =
+                        saved_view !== undefined &&
+                        saved_view.file_path === saved_state.file_path
+                            ? saved_view.iframe.contentWindow?.CodeChatEditor
+                            : saved_state.file_path === this.current_filename
+                              ? root_iframe?.contentWindow?.CodeChatEditor
+                              : undefined;
+                    saved_cce?.show_saved_state(saved_state.state);
+                    this.send_result(id, null);
+                    break;
+
+                case "Capabilities":
+                    this.capabilities = value as Capabilities;
+                    this.apply_preferences();
+                    this.send_result(id, null);
+                    break;
+
                 case "Result":
                     // Cancel the timer for this message and remove it from
                     // `pending_messages`.
+                    const result_contents = value as ResultType;
                     const pending_message = this.pending_messages[id];
                     if (pending_message !== undefined) {
                         const { timer_id, callback } =
                             this.pending_messages[id];
                         clearTimeout(timer_id);
-                        callback();
+                        callback(result_contents);
                         delete this.pending_messages[id];
                     }
 
                     // Report if this was an error.
-                    const result_contents = value as ResultType;
                     if ("Err" in result_contents) {
                         console.log(
                             `Error in message ${id}: ${result_contents.Err}.`,
@@ -249,15 +548,31 @@ class WebSocketComm {
     // Send a message expecting a result to the server.
     send_message = (
         message: EditorMessageContents,
-        callback: () => void = () => 0,
+        callback: (result: ResultType) => void = () => 0,
     ) => {
         const id = this.ws_id;
         this.ws_id += 3;
-        // Add in the current filename to the message, if it's an `Update`.
-        if (message.Update !== undefined) {
+        // Add in the current filename to the message, if it's an `Update`
+        // without one. (An `Update` from an additional view provides its
+        // filename.)
+        if (message.Update !== undefined && message.Update.file_path === "") {
             console.assert(this.current_filename !== undefined);
             message.Update.file_path = this.current_filename!;
         }
+        // The Server can't send changes to contents it didn't send.
+        if (
+            message.Update?.contents !== null &&
+            message.Update?.contents !== undefined
+        ) {
+            delete this.base_ids[message.Update.file_path];
+        }
+        if (
+            message.FoldRanges !== undefined &&
+            message.FoldRanges.file_path === ""
+        ) {
+            console.assert(this.current_filename !== undefined);
+            message.FoldRanges.file_path = this.current_filename!;
+        }
         console.log(
             `Sent message ${id}, ${JSON.stringify(message).substring(0, MAX_MESSAGE_LENGTH)}`,
         );
@@ -272,19 +587,229 @@ class WebSocketComm {
         };
     };
 
-    current_file = (url: URL) => {
+    // Load the document at `url`. If it's served by the Server, the IDE shows
+    // this file in the group given by `view_target`.
+    current_file = (url: URL, view_target: ViewTarget = "Active") => {
         // If this points to the Server, then tell the IDE to load a new file.
         if (url.host === window.location.host) {
-            this.send_message({ CurrentFile: url.toString() }, () => {
+            const current_file = { file_path: url.toString(), view_target };
+            this.send_message({ CurrentFile: current_file }, () => {
                 this.set_root_iframe_src(url.toString());
             });
         } else {
-            this.set_root_iframe_src(url.toString());
+            // Show external pages below a navigation bar; see
+            // [external.rs](../../server/src/webserver/external.rs). Its home
+            // button returns to the current page.
+            const params = new URLSearchParams({ url: url.toString() });
+            if (root_iframe!.src !== "") {
+                params.set("home", root_iframe!.src);
+            }
+            this.set_root_iframe_src(`${base_path}/ext?${params}`);
         }
         // Read the `current_filename` from the next `Update` message.
         this.current_filename = undefined;
     };
 
+    // Select the lexer used for the current file. The Server then reloads the
+    // file using this lexer.
+    set_file_language = (language: string | null) => {
+        console.assert(this.current_filename !== undefined);
+        this.send_message({
+            SetFileLanguage: { file_path: this.current_filename!, language },
+        });
+    };
+
+    // Update the user's preferences with each preference provided, saving
+    // them for future sessions.
+    set_preferences = (preferences: Preferences) => {
+        this.send_message({ Preferences: preferences }, this.on_preferences);
+    };
+
+    // Store then apply the preferences provided by the Server.
+    on_preferences = (result: ResultType) => {
+        if (
+            "Ok" in result &&
+            result.Ok !== "Void" &&
+            "Preferences" in result.Ok
+        ) {
+            this.preferences = result.Ok.Preferences;
+            this.apply_preferences();
+        }
+    };
+
+    // Apply the user's preferences, along with the enabled features, to the
+    // current document.
+    apply_preferences = () => {
+        const cce = root_iframe?.contentWindow?.CodeChatEditor;
+        cce?.apply_preferences(this.preferences);
+        if (this.capabilities !== undefined) {
+            cce?.apply_capabilities(this.capabilities);
+        }
+    };
+
+    // Provide the paths of the documents opened during this connection, from
+    // least to most recently used; for example, to show these as tabs.
+    list_open_files = (callback: (file_paths: string[]) => void) => {
+        this.send_message({ ListOpenFiles: null }, (result) => {
+            if (
+                "Ok" in result &&
+                result.Ok !== "Void" &&
+                "OpenFiles" in result.Ok
+            ) {
+                callback(result.Ok.OpenFiles);
+            }
+        });
+    };
+
+    // Provide the headings in the current document, in order; for example, to
+    // show a table of contents.
+    outline = (callback: (entries: OutlineEntry[]) => void) => {
+        console.assert(this.current_filename !== undefined);
+        this.send_message({ Outline: this.current_filename! }, (result) => {
+            if (
+                "Ok" in result &&
+                result.Ok !== "Void" &&
+                "Outline" in result.Ok
+            ) {
+                callback(result.Ok.Outline);
+            }
+        });
+    };
+
+    // Show the definition of an identifier named in the current document in
+    // the IDE.
+    goto_definition = (identifier: string) => {
+        console.assert(this.current_filename !== undefined);
+        this.send_message({
+            GotoDefinition: {
+                file_path: this.current_filename!,
+                identifier,
+                definition: null,
+            },
+        });
+    };
+
+    // Open the provided URL outside the Client, in the system's web browser.
+    open_url = (url: string) => {
+        this.send_message({ OpenUrl: url });
+    };
+
+    // Provide the names of the snippets available in the current document.
+    list_snippets = (callback: (names: string[]) => void) => {
+        console.assert(this.current_filename !== undefined);
+        this.send_message(
+            { ListSnippets: this.current_filename! },
+            (result) => {
+                if (
+                    "Ok" in result &&
+                    result.Ok !== "Void" &&
+                    "Snippets" in result.Ok
+                ) {
+                    callback(result.Ok.Snippets);
+                }
+            },
+        );
+    };
+
+    // Insert the named snippet at the cursor in the current document.
+    insert_snippet = (name: string) => {
+        console.assert(this.current_filename !== undefined);
+        this.send_message(
+            {
+                InsertSnippet: {
+                    file_path: this.current_filename!,
+                    name,
+                },
+            },
+            (result) => {
+                if (
+                    "Ok" in result &&
+                    result.Ok !== "Void" &&
+                    "Snippet" in result.Ok
+                ) {
+                    root_iframe?.contentWindow?.CodeChatEditor.insert_html(
+                        result.Ok.Snippet.html,
+                    );
+                }
+            },
+        );
+    };
+
+    // Export the current document, or its whole project, to a PDF, then
+    // provide the path to this PDF or an error message.
+    export_pdf = (
+        project: boolean,
+        callback: (result: { path: string } | { error: string }) => void,
+    ) => {
+        console.assert(this.current_filename !== undefined);
+        this.send_message(
+            { Export: { file_path: this.current_filename!, project } },
+            (result) => {
+                if ("Err" in result) {
+                    callback({ error: result.Err });
+                } else if (result.Ok !== "Void" && "Exported" in result.Ok) {
+                    callback({ path: result.Ok.Exported });
+                }
+            },
+        );
+    };
+
+    // Switch to an open document. The Server sends its most recent contents,
+    // avoiding a reload; if these aren't available, load it from `url`
+    // instead.
+    switch_file = (file_path: string, url: URL) => {
+        // Save the current document before leaving it.
+        const cce = root_iframe?.contentWindow?.CodeChatEditor;
+        const promise =
+            cce !== undefined ? cce.on_save(true) : Promise.resolve();
+        promise.then((_) => {
+            // Accept the `Update` containing this document's contents.
+            this.current_filename = file_path;
+            this.send_message({ SwitchFile: file_path }, (result) => {
+                if ("Err" in result) {
+                    this.current_file(url);
+                }
+            });
+        });
+    };
+
+    // Show the file at `url` in an additional view, beside the current file.
+    open_view = (url: URL) => {
+        this.close_view();
+        this.send_message({ OpenView: url.toString() }, (result) => {
+            if ("Err" in result) {
+                return;
+            }
+            const iframe = document.createElement("iframe");
+            iframe.id = "CodeChat-view-iframe";
+            iframe.style.cssText =
+                "width:50%; height:100vh; border:none; border-left:1px solid gray;";
+            root_iframe!.style.width = "50%";
+            document.body.style.display = "flex";
+            document.body.appendChild(iframe);
+            this.view = { url, iframe, onloading: true, file_path: undefined };
+            iframe.src = url.toString();
+        });
+    };
+
+    // Close the additional view, if it's open.
+    close_view = () => {
+        const view = this.view;
+        if (view === undefined) {
+            return;
+        }
+        this.view = undefined;
+        const cce = view.iframe.contentWindow?.CodeChatEditor;
+        const promise =
+            cce !== undefined ? cce.on_save(true) : Promise.resolve();
+        promise.then((_) => {
+            this.send_message({ CloseView: view.url.toString() });
+            view.iframe.remove();
+            root_iframe!.style.width = "100%";
+            document.body.style.display = "";
+        });
+    };
+
     // Send a result (a response to a message from the server) back to the
     // server.
     send_result = (id: number, result: string | null = null) => {
@@ -308,34 +833,56 @@ let root_iframe: HTMLIFrameElement | undefined;
 // True when in test mode.
 let testMode = false;
 
+// The path below which a reverse proxy serves the Server, such as `/codechat`;
+// empty if there's no proxy. See
+// [base\_path.rs](../../server/src/webserver/base_path.rs).
+let base_path = "";
+
 // Load the dynamic content into the static page.
 export const page_init = (
-    // The pathname for the websocket to use. The remainder of the URL is
-    // derived from the hosting page's URL. See the
+    // The pathname for the websocket to use, including the base path. The
+    // remainder of the URL is derived from the hosting page's URL. See the
     // [Location docs](https://developer.mozilla.org/en-US/docs/Web/API/Location)
     // for a nice, interactive definition of the components of a URL.
     ws_pathname: string,
     // Test mode flag
     testMode_: boolean,
+    // The base path.
+    base_path_: string,
 ) => {
     testMode = testMode_;
+    base_path = base_path_;
     on_dom_content_loaded(async () => {
         // If the hosting page uses HTTPS, then use a secure websocket (WSS
         // protocol); otherwise, use an insecure websocket (WS).
         const protocol = window.location.protocol === "http:" ? "ws:" : "wss:";
         // Build a websocket address based on the URL of the current page.
         webSocketComm = new WebSocketComm(
-            `${protocol}//${window.location.host}/${ws_pathname}`,
+            `${protocol}//${window.location.host}${ws_pathname}`,
         );
         root_iframe = document.getElementById(
             "CodeChat-iframe",
         )! as HTMLIFrameElement;
         window.CodeChatEditorFramework = {
             webSocketComm,
+            preview_source,
         };
     });
 };
 
+// Show a preview of the provided source file, with the given extension, while
+// its `Update` from the Server is pending. Return false if no preview is
+// available.
+const preview_source = async (source: string, ext: string) => {
+    const contents = await preview(source, ext);
+    const cce = root_iframe?.contentWindow?.CodeChatEditor;
+    if (contents === null || cce === undefined) {
+        return false;
+    }
+    await cce.open_lp(contents);
+    return true;
+};
+
 // This is copied from
 // [MDN](https://developer.mozilla.org/en-US/docs/Web/API/Document/DOMContentLoaded_event#checking_whether_loading_is_already_complete).
 const on_dom_content_loaded = (on_load_func: () => void) => {
@@ -353,6 +900,7 @@ declare global {
     interface Window {
         CodeChatEditorFramework: {
             webSocketComm: WebSocketComm;
+            preview_source: (source: string, ext: string) => Promise<boolean>;
         };
         CodeChatEditor_test: any;
     }
@@ -49,6 +49,7 @@
 // ### Third-party
 import { basicSetup } from "codemirror";
 import {
+    BlockInfo,
     EditorView,
     Decoration,
     DecorationSet,
@@ -66,6 +67,12 @@ import {
     EditorSelection,
     Transaction,
 } from "@codemirror/state";
+import {
+    foldEffect,
+    foldedRanges,
+    foldService,
+    unfoldEffect,
+} from "@codemirror/language";
 import { cpp } from "@codemirror/lang-cpp";
 import { css } from "@codemirror/lang-css";
 import { go } from "@codemirror/lang-go";
@@ -78,7 +85,11 @@ import { rust } from "@codemirror/lang-rust";
 import { Editor, init, tinymce } from "./tinymce-config.mjs";
 
 // ### Local
-import { set_is_dirty, startAutosaveTimer } from "./CodeChatEditor.mjs";
+import {
+    send_fold_ranges,
+    set_is_dirty,
+    startAutosaveTimer,
+} from "./CodeChatEditor.mjs";
 
 // ## Globals
 let current_view: EditorView;
@@ -199,6 +210,12 @@ const docBlockField = StateField.define<DecorationSet>({
                     ],
                 });
             }
+
+            // Replace all doc blocks, such as after applying changes sent by
+            // the Server.
+            else if (effect.is(setDocBlocks)) {
+                doc_blocks = doc_blocks_from_json(effect.value);
+            }
         return doc_blocks;
     },
 
@@ -226,22 +243,22 @@ const docBlockField = StateField.define<DecorationSet>({
 
     // For loading a file from the server back into the editor, use
     // [fromJSON](https://codemirror.net/docs/ref/#state.StateField^define^config.fromJSON).
-    fromJSON: (json: any, state: EditorState) =>
-        Decoration.set(
-            json.map(([from, to, indent, delimiter, contents]: DocBlockJSON) =>
-                Decoration.replace({
-                    widget: new DocBlockWidget(
-                        indent,
-                        delimiter,
-                        contents,
-                        null,
-                    ),
-                    block: true,
-                }).range(from, to),
-            ),
-        ),
+    fromJSON: (json: any, state: EditorState) => doc_blocks_from_json(json),
 });
 
+// Create a set of doc blocks from their JSON representation. CodeMirror reuses
+// the DOM of a doc block whose widget is unchanged.
+const doc_blocks_from_json = (json: DocBlockJSON[]) =>
+    Decoration.set(
+        json.map(([from, to, indent, delimiter, contents]: DocBlockJSON) =>
+            Decoration.replace({
+                widget: new DocBlockWidget(indent, delimiter, contents, null),
+                block: true,
+            }).range(from, to),
+        ),
+        true,
+    );
+
 const CodeMirror_JSON_fields = { doc_blocks: docBlockField };
 
 // Per the [docs](https://codemirror.net/docs/ref/#state.StateEffect^define),
@@ -287,6 +304,10 @@ export const updateDocBlock = StateEffect.define<{
     }),
 });
 
+// Replace all doc blocks with the provided doc blocks, whose locations refer to
+// the document after the transaction's changes.
+export const setDocBlocks = StateEffect.define<DocBlockJSON[]>();
+
 // Create a [widget](https://codemirror.net/docs/ref/#view.WidgetType) which
 // contains a doc block.
 class DocBlockWidget extends WidgetType {
@@ -418,6 +439,27 @@ const event_is_in_doc_block = (event: Event): boolean | HTMLDivElement => {
     return false;
 };
 
+// Move the focus from a doc block to the code, or from the code to the doc
+// block following the cursor (or, if there's none, the last doc block).
+export const toggle_focus = () => {
+    if (document.activeElement?.closest(".CodeChat-doc")) {
+        current_view.focus();
+        return;
+    }
+    const head = current_view.state.selection.main.head;
+    const doc_blocks = [
+        ...current_view.dom.querySelectorAll(".CodeChat-doc"),
+    ] as HTMLDivElement[];
+    const doc_block =
+        doc_blocks.find((doc_block) => current_view.posAtDOM(doc_block) >= head) ??
+        doc_blocks.at(-1);
+    (
+        doc_block?.querySelector(".CodeChat-doc-contents") as
+            | HTMLDivElement
+            | undefined
+    )?.focus();
+};
+
 // Called when a doc block is dirty.
 const on_dirty = (
     // The div that's dirty. It must be a child of the doc block div.
@@ -669,6 +711,13 @@ const autosaveExtension = EditorView.updateListener.of(
     // [ViewUpdate](https://codemirror.net/docs/ref/#view.ViewUpdate) which
     // describes a change being made to the document.
     (v: ViewUpdate) => {
+        // Changes sent by the Server don't need to be saved.
+        if (
+            v.transactions.length &&
+            v.transactions.every((tr) => tr.annotation(Transaction.remote))
+        ) {
+            return;
+        }
         // The
         // [docChanged](https://codemirror.net/docs/ref/#view.ViewUpdate.docChanged)
         // flag is the relevant part of this change description. However, this
@@ -696,6 +745,82 @@ const autosaveExtension = EditorView.updateListener.of(
     },
 );
 
+// ## Folding
+//
+// Allow folding a doc block which starts on the provided line, leaving its
+// first line visible. The language parser provides folds for code.
+const docBlockFoldService = foldService.of(
+    (state: EditorState, lineStart: number, lineEnd: number) => {
+        let range: { from: number; to: number } | null = null;
+        state
+            .field(docBlockField)
+            .between(lineStart, lineEnd, (from: number, to: number) => {
+                if (from === lineStart && to > lineEnd) {
+                    range = { from: lineEnd, to };
+                }
+                return false;
+            });
+        return range;
+    },
+);
+
+// Tell the IDE when the user folds or unfolds a region, so it can do the
+// same. Folds applied by `apply_fold_ranges` came from the IDE, so they
+// aren't sent back.
+const foldExtension = EditorView.updateListener.of((v: ViewUpdate) => {
+    const folds_changed = v.transactions.some(
+        (tr) =>
+            !tr.annotation(Transaction.remote) &&
+            tr.effects.some(
+                (effect) => effect.is(foldEffect) || effect.is(unfoldEffect),
+            ),
+    );
+    if (folds_changed) {
+        send_fold_ranges(get_fold_ranges(v.state));
+    }
+});
+
+// Return the folded regions of the provided state as lines, numbered from 0.
+// Each line of the document is a line of the file, so these are the same
+// lines the IDE folds.
+const get_fold_ranges = (state: EditorState): FoldRange[] => {
+    const doc = state.doc;
+    const ranges: FoldRange[] = [];
+    foldedRanges(state).between(0, doc.length, (from: number, to: number) => {
+        ranges.push({
+            start: doc.lineAt(from).number - 1,
+            end: doc.lineAt(to).number - 1,
+        });
+    });
+    return ranges;
+};
+
+// Replace the current folds with the provided regions, which the IDE sent.
+// Each region hides its lines after the first.
+export const apply_fold_ranges = (ranges: FoldRange[]) => {
+    const state = current_view.state;
+    const doc = state.doc;
+    const effects: StateEffect<{ from: number; to: number }>[] = [];
+    foldedRanges(state).between(0, doc.length, (from: number, to: number) => {
+        effects.push(unfoldEffect.of({ from, to }));
+    });
+    for (const { start, end } of ranges) {
+        if (start >= end || end >= doc.lines) {
+            continue;
+        }
+        effects.push(
+            foldEffect.of({
+                from: doc.line(start + 1).to,
+                to: doc.line(end + 1).to,
+            }),
+        );
+    }
+    current_view.dispatch({
+        effects,
+        annotations: Transaction.remote.of(true),
+    });
+};
+
 // Given source code in a CodeMirror-friendly JSON format, load it into the
 // provided div.
 export const CodeMirror_load = async (
@@ -765,9 +890,29 @@ export const CodeMirror_load = async (
             break;
 
         // Languages without a parser.
+        case "dockerfile":
+            parser = python();
+            break;
+        case "gas":
+        case "gas_arm":
+        case "gas_semicolon":
+            parser = cpp();
+            break;
+        case "ini":
+            parser = python();
+            break;
         case "json5":
             parser = json();
             break;
+        case "kconfig":
+            parser = python();
+            break;
+        case "linker_script":
+            parser = cpp();
+            break;
+        case "makefile":
+            parser = python();
+            break;
         case "matlab":
             parser = python();
             break;
@@ -804,6 +949,8 @@ export const CodeMirror_load = async (
                 basicSetup,
                 EditorView.lineWrapping,
                 autosaveExtension,
+                docBlockFoldService,
+                foldExtension,
                 ...extensions,
             ],
         },
@@ -832,6 +979,89 @@ export const CodeMirror_load = async (
     )[0];
 };
 
+// Return the lines occupied by each code and doc block, in order, as
+// `[first line, line after the last]` numbered from 0. This matches
+// [block\_lines](../../server/src/webserver/scroll.rs#block_lines).
+const block_lines = (): [number, number][] => {
+    const doc = current_view.state.doc;
+    const ranges: [number, number][] = [];
+    let line = 0;
+    current_view.state
+        .field(docBlockField)
+        .between(0, doc.length, (from: number, to: number) => {
+            const start = doc.lineAt(from).number - 1;
+            const end = doc.lineAt(to).number;
+            if (start > line) {
+                ranges.push([line, start]);
+            }
+            ranges.push([start, end]);
+            line = end;
+        });
+    if (doc.lines > line) {
+        ranges.push([line, doc.lines]);
+    }
+    return ranges;
+};
+
+// Return the first line of the provided line block, numbered from 0, and the
+// number of lines it contains; a doc block is a single line block.
+const line_block_lines = (line_block: BlockInfo): [number, number] => {
+    const doc = current_view.state.doc;
+    const start = doc.lineAt(line_block.from).number - 1;
+    return [start, doc.lineAt(line_block.to).number - start];
+};
+
+// Return the position of the top of the provided scrolling element, which
+// contains the editor, as a block and the fraction of the way through it.
+export const get_scroll_position = (scroller: HTMLElement): ScrollPosition => {
+    const top =
+        scroller.getBoundingClientRect().top - current_view.documentTop;
+    const line_block = current_view.lineBlockAtHeight(Math.max(top, 0));
+    const [line_block_start, line_block_count] = line_block_lines(line_block);
+    const fraction = Math.min(
+        Math.max((top - line_block.top) / line_block.height, 0),
+        1,
+    );
+    const line = line_block_start + fraction * line_block_count;
+    const ranges = block_lines();
+    const block = Math.max(
+        ranges.findIndex(([_, end]) => line < end),
+        0,
+    );
+    const [start, end] = ranges[block] ?? [0, 1];
+    return {
+        block,
+        offset: Math.min(Math.max((line - start) / (end - start), 0), 1),
+    };
+};
+
+// Scroll the provided element, which contains the editor, so that the
+// provided position is at its top. The Server always sends a block.
+export const scroll_to_position = (
+    scroller: HTMLElement,
+    position: ScrollPosition,
+) => {
+    if (typeof position !== "object" || !("block" in position)) {
+        return;
+    }
+    const range = block_lines()[position.block];
+    if (range === undefined) {
+        return;
+    }
+    const [start, end] = range;
+    const doc = current_view.state.doc;
+    const line = start + position.offset * (end - start);
+    const line_block = current_view.lineBlockAt(
+        doc.line(Math.min(Math.floor(line), doc.lines - 1) + 1).from,
+    );
+    const [line_block_start, line_block_count] = line_block_lines(line_block);
+    const top =
+        line_block.top +
+        ((line - line_block_start) / line_block_count) * line_block.height;
+    scroller.scrollTop +=
+        current_view.documentTop + top - scroller.getBoundingClientRect().top;
+};
+
 // Return the JSON data to save from the current CodeMirror-based document.
 export const CodeMirror_save = () => {
     // This is the data to write — the source code. First, transform the HTML
@@ -841,3 +1071,98 @@ export const CodeMirror_save = () => {
 
     return source;
 };
+
+// Apply the changes the Server sent to the current document, rather than
+// reloading it, keeping the cursor, undo history, and the editors of unchanged
+// doc blocks. Return false if there's no document to change.
+export const CodeMirror_apply_diff = (diff: CodeMirrorDiff) => {
+    if (current_view === undefined) {
+        return false;
+    }
+    const state = current_view.state;
+    current_view.dispatch({
+        changes: to_utf16_changes(state.doc.toString(), diff.doc),
+        effects: setDocBlocks.of(
+            apply_doc_block_diffs(
+                state.toJSON(CodeMirror_JSON_fields).doc_blocks,
+                diff.doc_blocks,
+            ),
+        ),
+        annotations: Transaction.remote.of(true),
+    });
+    return true;
+};
+
+// Convert the offsets of the provided changes to `s` from characters (code
+// points), which the Server sends, to UTF-16 code units, which JavaScript
+// uses. The changes must be in order.
+const to_utf16_changes = (s: string, diffs: StringDiff[]) => {
+    let chars = 0;
+    let offset = 0;
+    const to_offset = (char_offset: number) => {
+        while (chars < char_offset && offset < s.length) {
+            offset += s.codePointAt(offset)! > 0xffff ? 2 : 1;
+            chars++;
+        }
+        return offset;
+    };
+    return diffs.map(({ from, to, insert }) => ({
+        from: to_offset(from),
+        to: to_offset(to),
+        insert,
+    }));
+};
+
+// Apply the provided changes to `s`.
+export const apply_string_diffs = (s: string, diffs: StringDiff[]) => {
+    let result = "";
+    let last = 0;
+    for (const { from, to, insert } of to_utf16_changes(s, diffs)) {
+        result += s.slice(last, from) + insert;
+        last = to;
+    }
+    return result + s.slice(last);
+};
+
+// Apply the provided changes to `doc_blocks`, returning the new doc blocks in
+// order. Each change identifies a doc block by where it starts before any of
+// these changes.
+export const apply_doc_block_diffs = (
+    doc_blocks: DocBlockJSON[],
+    transactions: CodeMirrorDocBlockTransaction[],
+) => {
+    const changes = new Map<number, CodeMirrorDocBlockTransaction>();
+    const added: DocBlockJSON[] = [];
+    for (const transaction of transactions) {
+        if ("Add" in transaction) {
+            added.push(transaction.Add);
+        } else if ("Update" in transaction) {
+            changes.set(transaction.Update[0], transaction);
+        } else {
+            changes.set(transaction.Delete, transaction);
+        }
+    }
+    return doc_blocks
+        .flatMap((doc_block): DocBlockJSON[] => {
+            const change = changes.get(doc_block[0]);
+            if (change === undefined) {
+                return [doc_block];
+            }
+            if (!("Update" in change)) {
+                return [];
+            }
+            const [_, update] = change.Update;
+            const [, , indent, delimiter, contents] = doc_block;
+            return [
+                [
+                    update.from,
+                    update.to,
+                    update.indent ?? indent,
+                    update.delimiter ?? delimiter,
+                    apply_string_diffs(contents, update.contents),
+                ],
+            ];
+        })
+        .concat(added)
+        .sort((a, b) => a[0] - b[0]);
+};
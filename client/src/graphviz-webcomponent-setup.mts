@@ -24,6 +24,11 @@
 // graphviz webcomponent; see the
 // [ESBuild docs](https://esbuild.github.io/content-types/#real-esm-imports).
 (window as any).graphvizWebComponent = {
-    rendererUrl: "/static/graphviz-webcomponent/renderer.min.js",
+    // Find the renderer relative to this bundle in `static/bundled/`, so that
+    // it loads when the Server is behind a reverse proxy.
+    rendererUrl: new URL(
+        "../graphviz-webcomponent/renderer.min.js",
+        import.meta.url,
+    ).href,
     delayWorkerLoading: true,
 };
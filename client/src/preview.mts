@@ -0,0 +1,57 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+//
+// # `preview.mts` -- translate source in the browser
+//
+// This loads the WebAssembly build of the lexer (see `server/lexer/src/wasm.rs`,
+// built by the builder's `wasm` command), which translates a source file to
+// the format the Server provides. The result is an approximate preview: the
+// Server's translation should replace it once available.
+
+// The location of the WebAssembly module's JavaScript wrapper. This is a
+// variable, so that esbuild doesn't try to bundle it. It's relative to this
+// bundle in `static/bundled/`, so that it loads when the Server is behind a
+// reverse proxy.
+const wasm_url = new URL("../wasm/codechat_editor_lexer.js", import.meta.url)
+    .href;
+
+// The loaded module; `null` if it's not available.
+let wasm_module: Promise<any | null> | undefined;
+
+const load_wasm = () => {
+    if (wasm_module === undefined) {
+        wasm_module = import(wasm_url)
+            .then(async (module) => {
+                await module.default();
+                return module;
+            })
+            // Without the module, the Server translates the file instead.
+            .catch(() => null);
+    }
+    return wasm_module;
+};
+
+// Translate the provided source file, with the given extension (such as `py`),
+// to the format the Server provides. Return `null` if the WebAssembly module
+// isn't available or the file's language is unknown.
+export const preview = async (
+    source: string,
+    ext: string,
+): Promise<CodeChatForWeb | null> => {
+    const module = await load_wasm();
+    const json: string | undefined = module?.preview(source, ext);
+    return json === undefined ? null : JSON.parse(json);
+};
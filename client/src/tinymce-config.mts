@@ -71,7 +71,172 @@ import "tinymce/plugins/visualchars/index.js";
 /// import './plugins/powerpaste/plugin';
 /// import './plugins/powerpaste/js/wordimport';
 
-// Initialize TinyMCE.
+// ## Clean up pasted HTML
+//
+// Rich text pasted from a word processor or a web page brings along
+// presentational markup -- `style` attributes, `<font>` tags, and piles of
+// `<span>`s -- which the HTML to Markdown conversion leaves as raw HTML in the
+// resulting Markdown, cluttering the source file's comments. Only pasted HTML
+// is cleaned; HTML the author writes in a doc block is saved unchanged.
+//
+// Tags kept as is.
+const ALLOWED_TAGS = new Set([
+    "a",
+    "abbr",
+    "blockquote",
+    "br",
+    "caption",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+    "var",
+]);
+
+// Presentational tags and their Markdown-equivalent replacements.
+const REPLACED_TAGS: { [name: string]: string } = {
+    b: "strong",
+    i: "em",
+    strike: "del",
+    tt: "code",
+};
+
+// Attributes kept as is, in addition to `data-*` attributes.
+const ALLOWED_ATTRIBUTES = new Set([
+    "alt",
+    "colspan",
+    "height",
+    "href",
+    "id",
+    "lang",
+    "open",
+    "reversed",
+    "rowspan",
+    "src",
+    "start",
+    "title",
+    "type",
+    "width",
+]);
+
+// Prefixes of the classes kept; the CodeChat Editor and syntax highlighting
+// rely on these.
+const ALLOWED_CLASS_PREFIXES = ["CodeChat-", "language-"];
+
+// Normalize pasted HTML:
+//
+// - Presentational tags with a Markdown equivalent are replaced by it; for
+//   example, `<b>` becomes `<strong>`.
+// - Tags not in an allow-list are removed, keeping their contents. Custom
+//   elements (whose names contain a dash), which render diagrams, are kept.
+// - Attributes not in an allow-list are removed. Only classes used by the
+//   CodeChat Editor itself are kept.
+// - A `<span>` left without any attributes is removed, keeping its contents.
+export const clean_pasted_html = (html: string): string => {
+    const template = document.createElement("template");
+    template.innerHTML = html;
+    clean_children(template.content);
+    return template.innerHTML;
+};
+
+// Clean each child of the provided node.
+const clean_children = (node: Node) => {
+    // Cleaning may replace a child with its children; copy the list first.
+    for (const child of Array.from(node.childNodes)) {
+        if (child instanceof Element) {
+            clean_element(child);
+        }
+    }
+};
+
+// Clean an element and its descendants.
+const clean_element = (element: Element) => {
+    clean_children(element);
+    const name = element.localName;
+    if (name.includes("-")) {
+        return;
+    }
+    const replacement = REPLACED_TAGS[name];
+    if (replacement === undefined && !ALLOWED_TAGS.has(name)) {
+        // Drop this tag, but keep its contents.
+        element.replaceWith(...Array.from(element.childNodes));
+        return;
+    }
+
+    for (const attribute of Array.from(element.attributes)) {
+        const attribute_name = attribute.name.toLowerCase();
+        if (attribute_name === "class") {
+            const classes = attribute.value
+                .split(/\s+/)
+                .filter((class_) =>
+                    ALLOWED_CLASS_PREFIXES.some((prefix) =>
+                        class_.startsWith(prefix),
+                    ),
+                );
+            if (classes.length > 0) {
+                element.setAttribute("class", classes.join(" "));
+            } else {
+                element.removeAttribute(attribute.name);
+            }
+        } else if (
+            !attribute_name.startsWith("data-") &&
+            !ALLOWED_ATTRIBUTES.has(attribute_name)
+        ) {
+            element.removeAttribute(attribute.name);
+        }
+    }
+
+    if (name === "span" && element.attributes.length === 0) {
+        element.replaceWith(...Array.from(element.childNodes));
+    } else if (replacement !== undefined) {
+        const replaced = document.createElement(replacement);
+        for (const attribute of Array.from(element.attributes)) {
+            replaced.setAttribute(attribute.name, attribute.value);
+        }
+        replaced.append(...Array.from(element.childNodes));
+        element.replaceWith(replaced);
+    }
+};
+
+// ## Initialize TinyMCE
 export const init = async (
     // Provide editor options; don't set \`\`plugins\`\` or \`\`skin\`\`, since
     // these must be accompanied by the correct imports.
@@ -123,6 +288,17 @@ export const init = async (
             // See
             // [License key](https://www.tiny.cloud/docs/tinymce/latest/license-key).
             license_key: "gpl",
+            // Clean up rich text pasted from other applications; see
+            // [paste\_preprocess](https://www.tiny.cloud/docs/tinymce/latest/copy-and-paste/#paste_preprocess).
+            // Content copied from within the editor is already clean.
+            paste_preprocess: (
+                _editor: Editor,
+                args: { content: string; internal: boolean },
+            ) => {
+                if (!args.internal) {
+                    args.content = clean_pasted_html(args.content);
+                }
+            },
 
             // Settings for plugins
             //
@@ -19,7 +19,7 @@
 // The server passes this to the client to load a file. See
 // [LexedSourceFile](../../server/src/webserver.rs#LexedSourceFile).
 type CodeChatForWeb = {
-    metadata: { mode: string };
+    metadata: { mode: string; notebook?: string };
     source: {
         doc: string;
         doc_blocks: DocBlockJSON[];
@@ -45,7 +45,110 @@ interface UpdateMessageContents {
     file_path: string;
     contents: CodeChatForWeb | undefined;
     cursor_position: number | undefined;
-    scroll_position: number | undefined;
+    scroll_position: ScrollPosition | undefined;
+}
+
+// Changes to the contents of a file the Client shows. See
+// [UpdateDiffContents](../../server/src/webserver.rs#UpdateDiffContents).
+interface UpdateDiffContents {
+    file_path: string;
+    // The ID of the message which sent the contents these changes apply to.
+    base_id: number;
+    diff: CodeMirrorDiff;
+    scroll_position: ScrollPosition | undefined;
+}
+
+// See [CodeMirrorDiff](../../server/src/processing.rs#CodeMirrorDiff). Offsets
+// are in characters (code points), rather than UTF-16 code units.
+interface CodeMirrorDiff {
+    doc: StringDiff[];
+    doc_blocks: CodeMirrorDocBlockTransaction[];
+}
+
+// Replace the characters `from..to` of a string with `insert`.
+interface StringDiff {
+    from: number;
+    to: number;
+    insert: string;
+}
+
+// A change to one doc block, which is identified by where it starts. See
+// [CodeMirrorDocBlockTransaction](../../server/src/processing.rs#CodeMirrorDocBlockTransaction).
+type CodeMirrorDocBlockTransaction =
+    | { Add: DocBlockJSON }
+    | { Update: [number, CodeMirrorDocBlockUpdate] }
+    | { Delete: number };
+
+// The new location of a doc block, plus the changes to it; a `null` indent or
+// delimiter is unchanged.
+interface CodeMirrorDocBlockUpdate {
+    from: number;
+    to: number;
+    indent: string | null;
+    delimiter: string | null;
+    contents: StringDiff[];
+}
+
+// The position of the top of the view of a file. See
+// [ScrollPosition](../../server/src/webserver/scroll.rs#ScrollPosition).
+type ScrollPosition =
+    // Sent by the Client: a fraction `offset` of the way through the code or
+    // doc block numbered `block`.
+    | { block: number; offset: number }
+    // Sent by the IDE: a line of the file, numbered from 0.
+    | { line: number }
+    // Sent by older IDEs and Clients: from 0 (the top) to 1 (the bottom).
+    | number;
+
+// The folded regions of a file. See
+// [FoldRangesContents](../../server/src/webserver.rs#FoldRangesContents).
+interface FoldRangesContents {
+    file_path: string;
+    ranges: FoldRange[];
+}
+
+// A folded region: its first and last lines, numbered from 0.
+interface FoldRange {
+    start: number;
+    end: number;
+}
+
+// Whether a file has unsaved changes. See
+// [SavedStateContents](../../server/src/webserver.rs#SavedStateContents).
+interface SavedStateContents {
+    file_path: string;
+    state: SaveState;
+}
+
+type SaveState = "Clean" | "Dirty";
+
+// The user's preferences; a missing preference uses the default. See
+// [Preferences](../../server/src/webserver/preferences.rs#Preferences).
+interface Preferences {
+    theme?: string | null;
+    font_size?: number | null;
+    autosave_delay?: number | null;
+    last_project?: string | null;
+    keymap?: Record<string, string>;
+}
+
+// The optional features enabled for a connection. See
+// [Capabilities](../../server/src/webserver/capabilities.rs#Capabilities).
+interface Capabilities {
+    spellcheck: boolean;
+    run_code: boolean;
+    comments: boolean;
+    collaboration: boolean;
+}
+
+// A non-fatal problem found in the current file. See
+// [Diagnostic](../../server/src/processing/diagnostics.rs#Diagnostic).
+interface Diagnostic {
+    severity: "Error" | "Warning" | "Information";
+    // The lines containing the problem, numbered from 1; `end` is exclusive.
+    range: { start: number; end: number };
+    message: string;
+    source: string;
 }
 
 // These modules keep TypeScript from complaining about missing type definitions
@@ -25,6 +25,9 @@
 // ### Node.js packages
 import assert from "assert";
 import child_process from "node:child_process";
+import fs from "node:fs";
+import os from "node:os";
+import path from "node:path";
 
 // ### Third-party packages
 import escape from "escape-html";
@@ -71,9 +74,18 @@ const pending_messages: Record<
     number,
     {
         timer_id: NodeJS.Timeout;
-        callback: (succeeded: boolean) => void;
+        callback: (succeeded: boolean, result?: MessageResult) => void;
     }
 > = {};
+// The Output channel showing log messages from the Server; see `Log`.
+let log_output_channel: vscode.OutputChannel | undefined;
+// The status bar item showing the Server's state, and the problems it found in
+// doc blocks; see `Event`.
+let status_bar_item: vscode.StatusBarItem | undefined;
+let diagnostic_collection: vscode.DiagnosticCollection | undefined;
+// The Server's state, as reported by `Event` messages.
+let translating = 0;
+let client_connected = false;
 // The text editor containing the current file.
 let current_editor: vscode.TextEditor | undefined;
 // True to ignore the next change event, which is produced by applying an
@@ -82,6 +94,12 @@ let ignore_text_document_change = false;
 // True to ignore the next active editor change event, since a `CurrentFile`
 // message from the Client caused this change.
 let ignore_active_editor_change = false;
+// The folded regions of the current file last sent to or received from the
+// Server, as JSON; see `FoldRanges`.
+let fold_ranges_json = "[]";
+// Whether each file had unsaved changes when last reported to the Server; see
+// `SavedState`.
+const saved_states = new Map<string, SaveState>();
 
 // ### Message types
 //
@@ -105,11 +123,57 @@ interface UpdateMessageContents {
     file_path: string;
     contents: CodeChatForWeb | undefined;
     cursor_position: number | undefined;
-    scroll_position: number | undefined;
+    scroll_position: ScrollPosition | undefined;
 }
 
+// See [ScrollPosition](../../../server/src/webserver/scroll.rs#ScrollPosition).
+// The IDE sends and receives a line.
+type ScrollPosition =
+    | { block: number; offset: number }
+    | { line: number }
+    | number;
+
 interface ResultOkTypes {
-    LoadFile: string | null;
+    LoadFile?: string | null;
+    Outline?: OutlineEntry[];
+    Snippets?: string[];
+    Snippet?: Snippet;
+}
+
+// See
+// [FoldRangesContents](../../../server/src/webserver.rs#FoldRangesContents).
+interface FoldRangesContents {
+    file_path: string;
+    ranges: FoldRange[];
+}
+
+// A folded region: its first and last lines, numbered from 0.
+interface FoldRange {
+    start: number;
+    end: number;
+}
+
+// See
+// [SavedStateContents](../../../server/src/webserver.rs#SavedStateContents).
+interface SavedStateContents {
+    file_path: string;
+    state: SaveState;
+}
+
+type SaveState = "Clean" | "Dirty";
+
+// See [Snippet](../../../server/src/processing/snippets.rs#Snippet).
+interface Snippet {
+    markdown: string;
+    source: string;
+    html: string;
+}
+
+// See [OutlineEntry](../../../server/src/processing/outline.rs#OutlineEntry).
+interface OutlineEntry {
+    level: number;
+    title: string;
+    line: number;
 }
 
 interface MessageResult {
@@ -117,14 +181,74 @@ interface MessageResult {
     Err?: string;
 }
 
+// See [LogRecord](../../../server/src/webserver/log_forward.rs#LogRecord).
+interface LogRecord {
+    level: string;
+    target: string;
+    message: string;
+}
+
+// See [ServerEvent](../../../server/src/webserver/events.rs#ServerEvent).
+interface ServerEvent {
+    Translating?: number;
+    ClientConnected?: boolean;
+    Diagnostics?: { file_path: string; diagnostics: Diagnostic[] };
+}
+
+// See [Diagnostic](../../../server/src/processing/diagnostics.rs#Diagnostic).
+interface Diagnostic {
+    severity: "Error" | "Warning" | "Information";
+    range: { start: number; end: number };
+    message: string;
+    source: string;
+}
+
+// See [WorkspaceRoot](../../../server/src/processing/workspace.rs#WorkspaceRoot).
+interface WorkspaceRoot {
+    path: string;
+    trusted: boolean;
+}
+
 interface JointMessageContents {
     Update?: UpdateMessageContents;
-    CurrentFile?: string | undefined;
+    CurrentFile?: CurrentFileContents;
     Opened?: IdeType;
     RequestClose?: null;
     LoadFile?: string;
     ClientHtml?: string;
     Result?: MessageResult;
+    Outline?: string;
+    GotoDefinition?: GotoDefinitionContents;
+    OpenUrl?: string;
+    ListSnippets?: string;
+    InsertSnippet?: { file_path: string; name: string };
+    FoldRanges?: FoldRangesContents;
+    SavedState?: SavedStateContents;
+    Save?: string | null;
+    SaveAll?: null;
+    SubscribeLogs?: string;
+    WorkspaceRoots?: WorkspaceRoot[];
+    Log?: LogRecord;
+    SubscribeEvents?: boolean;
+    Event?: ServerEvent;
+}
+
+// See
+// [CurrentFileContents](../../../server/src/webserver.rs#CurrentFileContents).
+interface CurrentFileContents {
+    file_path: string;
+    view_target: ViewTarget;
+}
+
+// See [ViewTarget](../../../server/src/webserver.rs#ViewTarget).
+type ViewTarget = "Active" | "Beside" | { Group: number };
+
+// See
+// [GotoDefinitionContents](../../../server/src/webserver.rs#GotoDefinitionContents).
+interface GotoDefinitionContents {
+    file_path: string;
+    identifier: string;
+    definition: { file_path: string; line: number } | null;
 }
 
 interface JointMessage {
@@ -142,6 +266,10 @@ export const activate = (context: vscode.ExtensionContext) => {
             "extension.codeChatEditorDeactivate",
             deactivate
         ),
+        vscode.commands.registerCommand(
+            "extension.codeChatEditorInsertSnippet",
+            insert_snippet
+        ),
         vscode.commands.registerCommand(
             "extension.codeChatEditorActivate",
             async () => {
@@ -155,6 +283,9 @@ export const activate = (context: vscode.ExtensionContext) => {
                     // `event <https://code.visualstudio.com/docs/extensionAPI/vscode-api#Event>`\_.
                     context.subscriptions.push(
                         vscode.workspace.onDidChangeTextDocument((event) => {
+                            // Any change, including one from the Client, may
+                            // leave the file unsaved.
+                            send_saved_state(event.document);
                             // VSCode sends empty change events -- ignore these.
                             if (event.contentChanges.length === 0) {
                                 return;
@@ -176,6 +307,13 @@ export const activate = (context: vscode.ExtensionContext) => {
                         })
                     );
 
+                    // Tell the Client when a file is saved.
+                    context.subscriptions.push(
+                        vscode.workspace.onDidSaveTextDocument((document) =>
+                            send_saved_state(document)
+                        )
+                    );
+
                     // Render when the active editor changes.
                     context.subscriptions.push(
                         vscode.window.onDidChangeActiveTextEditor((_event) => {
@@ -186,6 +324,33 @@ export const activate = (context: vscode.ExtensionContext) => {
                             current_file();
                         })
                     );
+
+                    // Tell the Client when folding changes the lines shown.
+                    context.subscriptions.push(
+                        vscode.window.onDidChangeTextEditorVisibleRanges(
+                            (event) => send_fold_ranges(event.textEditor)
+                        )
+                    );
+
+                    // Show the headings in each file's doc blocks in the
+                    // outline view, using the outline found by the Server.
+                    context.subscriptions.push(
+                        vscode.languages.registerDocumentSymbolProvider(
+                            { scheme: "file" },
+                            { provideDocumentSymbols: get_outline }
+                        )
+                    );
+
+                    // Tell the Server when the workspace's folders or their
+                    // trust change.
+                    context.subscriptions.push(
+                        vscode.workspace.onDidChangeWorkspaceFolders(
+                            (_event) => workspace_roots()
+                        ),
+                        vscode.workspace.onDidGrantWorkspaceTrust(() =>
+                            workspace_roots()
+                        )
+                    );
                 }
 
                 // Get the CodeChat Client's location from the VSCode
@@ -311,7 +476,8 @@ export const activate = (context: vscode.ExtensionContext) => {
 
                     // Connect to the CodeChat Editor Server.
                     websocket = new WebSocket(
-                        `ws://localhost:${get_port()}/vsc/ws-ide/${Math.random()}`
+                        `ws://localhost:${get_port()}/vsc/ws-ide/${Math.random()}${get_instructor_query()}`,
+                        { headers: get_local_secret_headers() }
                     );
 
                     let was_error: boolean = false;
@@ -361,6 +527,11 @@ export const activate = (context: vscode.ExtensionContext) => {
                                     CodeChatEditorClientLocation.html,
                             },
                         });
+                        // Show the Server's log messages in an Output channel.
+                        send_message({ SubscribeLogs: get_log_level() });
+                        // Show the Server's state in the status bar.
+                        send_message({ SubscribeEvents: true });
+                        workspace_roots();
                         // For the external browser, we can immediately send the
                         // `CurrentFile` message. For the WebView, we must first
                         // wait to receive the HTML for the WebView (the
@@ -422,19 +593,112 @@ export const activate = (context: vscode.ExtensionContext) => {
                                     );
                                     vscode.workspace.applyEdit(wse);
                                 }
+                                // Show the line at the top of the Client's
+                                // view.
+                                const scroll_position =
+                                    current_update.scroll_position;
+                                if (
+                                    typeof scroll_position === "object" &&
+                                    scroll_position !== null &&
+                                    "line" in scroll_position
+                                ) {
+                                    const line = Math.floor(
+                                        scroll_position.line
+                                    );
+                                    for (const editor of vscode.window
+                                        .visibleTextEditors) {
+                                        if (editor.document === doc) {
+                                            editor.revealRange(
+                                                new vscode.Range(line, 0, line, 0),
+                                                vscode.TextEditorRevealType.AtTop
+                                            );
+                                        }
+                                    }
+                                }
                                 send_result(id);
                                 break;
                             }
 
+                            case "FoldRanges": {
+                                // VSCode folds only in the active editor.
+                                const fold_ranges = value as FoldRangesContents;
+                                const ate = vscode.window.activeTextEditor;
+                                if (
+                                    ate?.document.fileName ===
+                                    fold_ranges.file_path
+                                ) {
+                                    // Don't send these folds back.
+                                    fold_ranges_json = JSON.stringify(
+                                        fold_ranges.ranges
+                                    );
+                                    vscode.commands
+                                        .executeCommand("editor.unfoldAll")
+                                        .then(() =>
+                                            vscode.commands.executeCommand(
+                                                "editor.fold",
+                                                {
+                                                    levels: 1,
+                                                    selectionLines:
+                                                        fold_ranges.ranges.map(
+                                                            (range) =>
+                                                                range.start
+                                                        ),
+                                                }
+                                            )
+                                        )
+                                        .then(() => send_result(id));
+                                } else {
+                                    send_result(id);
+                                }
+                                break;
+                            }
+
+                            case "Save": {
+                                // The Server always names the file.
+                                const doc = get_document(value as string);
+                                if (doc === undefined) {
+                                    send_result(id, {
+                                        Err: "No open document for this file.",
+                                    });
+                                    break;
+                                }
+                                doc.save().then((saved) =>
+                                    send_result(
+                                        id,
+                                        saved
+                                            ? { Ok: "Void" }
+                                            : { Err: "Unable to save this file." }
+                                    )
+                                );
+                                break;
+                            }
+
+                            case "SaveAll": {
+                                vscode.workspace
+                                    .saveAll(false)
+                                    .then((saved) =>
+                                        send_result(
+                                            id,
+                                            saved
+                                                ? { Ok: "Void" }
+                                                : { Err: "Unable to save all files." }
+                                        )
+                                    );
+                                break;
+                            }
+
                             case "CurrentFile": {
-                                const current_file = value as string;
+                                const current_file =
+                                    value as CurrentFileContents;
                                 vscode.workspace
-                                    .openTextDocument(current_file)
+                                    .openTextDocument(current_file.file_path)
                                     .then((document) => {
                                         ignore_active_editor_change = true;
                                         vscode.window.showTextDocument(
                                             document,
-                                            current_editor?.viewColumn
+                                            view_column(
+                                                current_file.view_target
+                                            )
                                         );
                                         send_result(id);
                                     });
@@ -444,18 +708,18 @@ export const activate = (context: vscode.ExtensionContext) => {
                             case "Result": {
                                 // Cancel the timer for this message and remove
                                 // it from `pending_messages`.
+                                const result_contents = value as MessageResult;
                                 const pending_message = pending_messages[id];
                                 if (pending_message !== undefined) {
                                     const { timer_id, callback } =
                                         pending_messages[id];
                                     clearTimeout(timer_id);
                                     // eslint-disable-next-line n/no-callback-literal
-                                    callback(true);
+                                    callback(true, result_contents);
                                     delete pending_messages[id];
                                 }
 
                                 // Report if this was an error.
-                                const result_contents = value as MessageResult;
                                 if ("Err" in result_contents) {
                                     const msg = `Error in message ${id}: ${result_contents.Err}.`;
                                     console.log(msg);
@@ -482,6 +746,75 @@ export const activate = (context: vscode.ExtensionContext) => {
                                 break;
                             }
 
+                            case "GotoDefinition": {
+                                const goto_definition =
+                                    value as GotoDefinitionContents;
+                                find_definition(goto_definition).then(
+                                    (location) => {
+                                        if (location === undefined) {
+                                            send_result(id, {
+                                                Err: `No definition found for ${goto_definition.identifier}.`,
+                                            });
+                                            return;
+                                        }
+                                        vscode.window.showTextDocument(
+                                            location.uri,
+                                            {
+                                                selection: location.range,
+                                                viewColumn:
+                                                    current_editor?.viewColumn,
+                                            }
+                                        );
+                                        send_result(id);
+                                    }
+                                );
+                                break;
+                            }
+
+                            case "OpenUrl": {
+                                // The Server only forwards `http` and `https`
+                                // URLs; check again, since `openExternal` also
+                                // runs commands given a `command:` URI.
+                                const uri = vscode.Uri.parse(value as string);
+                                if (
+                                    uri.scheme !== "http" &&
+                                    uri.scheme !== "https"
+                                ) {
+                                    send_result(id, {
+                                        Err: `Only http and https URLs may be opened, not ${uri.scheme} URLs.`,
+                                    });
+                                    break;
+                                }
+                                vscode.env.openExternal(uri).then((opened) =>
+                                    send_result(
+                                        id,
+                                        opened
+                                            ? undefined
+                                            : { Err: `Unable to open ${uri}.` }
+                                    )
+                                );
+                                break;
+                            }
+
+                            case "Log": {
+                                const record = value as LogRecord;
+                                log_output_channel ??=
+                                    vscode.window.createOutputChannel(
+                                        "CodeChat Editor Server"
+                                    );
+                                log_output_channel.appendLine(
+                                    `${new Date().toISOString()} ${record.level} ${record.target} - ${record.message}`
+                                );
+                                send_result(id);
+                                break;
+                            }
+
+                            case "Event": {
+                                show_event(value as ServerEvent);
+                                send_result(id);
+                                break;
+                            }
+
                             case "ClientHtml": {
                                 const client_html = value as string;
                                 assert(webview_panel !== undefined);
@@ -516,8 +849,16 @@ export const activate = (context: vscode.ExtensionContext) => {
 // On deactivation, close everything down.
 export const deactivate = async () => {
     console.log("CodeChat extension: deactivating.");
+    // Don't lose edits made in the Client.
+    await send_message_request_close();
     await stop_client();
     webview_panel?.dispose();
+    log_output_channel?.dispose();
+    log_output_channel = undefined;
+    status_bar_item?.dispose();
+    status_bar_item = undefined;
+    diagnostic_collection?.dispose();
+    diagnostic_collection = undefined;
     console.log("CodeChat extension: deactivated.");
 }
 
@@ -526,7 +867,7 @@ export const deactivate = async () => {
 // Send a message expecting a result to the server.
 const send_message = (
     message: JointMessageContents,
-    callback: (succeeded: boolean) => void = (_) => 0
+    callback: (succeeded: boolean, result?: MessageResult) => void = (_) => 0
 ) => {
     const id = message_id;
     message_id += 3;
@@ -547,6 +888,136 @@ const send_message = (
     };
 };
 
+// Ask the Client to save any unsaved data then close. The returned promise
+// resolves to true once the Client saved this data, or to false if it didn't
+// respond. Closing the Client then requires stopping it; see `stop_client`.
+const send_message_request_close = (): Promise<boolean> =>
+    new Promise((resolve) => {
+        // Without a connected Client, there's nothing to save.
+        if (websocket === undefined || !client_connected) {
+            resolve(false);
+            return;
+        }
+        send_message({ RequestClose: null }, (succeeded, result) =>
+            resolve(succeeded && result?.Err === undefined)
+        );
+    });
+
+// Request the outline of a document from the Server, then convert it to
+// symbols, nesting each heading under the preceding heading of a lower level.
+const get_outline = (
+    document: vscode.TextDocument
+): Promise<vscode.DocumentSymbol[]> =>
+    new Promise((resolve) => {
+        if (websocket === undefined) {
+            resolve([]);
+            return;
+        }
+        send_message({ Outline: document.fileName }, (_, result) => {
+            const ok = result?.Ok;
+            const entries = typeof ok === "object" ? ok.Outline ?? [] : [];
+            const symbols: vscode.DocumentSymbol[] = [];
+            // The most recent symbol at each level.
+            const parents: [number, vscode.DocumentSymbol][] = [];
+            for (const entry of entries) {
+                const range = document.lineAt(
+                    Math.min(entry.line, document.lineCount) - 1
+                ).range;
+                const symbol = new vscode.DocumentSymbol(
+                    entry.title,
+                    "",
+                    vscode.SymbolKind.String,
+                    range,
+                    range
+                );
+                while (
+                    parents.length > 0 &&
+                    parents[parents.length - 1][0] >= entry.level
+                ) {
+                    parents.pop();
+                }
+                (parents.length > 0
+                    ? parents[parents.length - 1][1].children
+                    : symbols
+                ).push(symbol);
+                parents.push([entry.level, symbol]);
+            }
+            resolve(symbols);
+        });
+    });
+
+// Send the root folders of the workspace to the Server. VSCode trusts either
+// all of a workspace's folders or none of them.
+const workspace_roots = () => {
+    if (websocket === undefined) {
+        return;
+    }
+    send_message({
+        WorkspaceRoots: (vscode.workspace.workspaceFolders ?? [])
+            .filter((folder) => folder.uri.scheme === "file")
+            .map((folder) => ({
+                path: folder.uri.fsPath,
+                trusted: vscode.workspace.isTrusted,
+            })),
+    });
+};
+
+// Let the user choose one of the snippets provided by the Server, then insert
+// it as a doc block before the line containing the cursor.
+const insert_snippet = () => {
+    // When the CodeChat Editor panel has the focus, there's no active text
+    // editor; use the file it shows.
+    const editor = vscode.window.activeTextEditor ?? current_editor;
+    if (websocket === undefined || editor === undefined) {
+        vscode.window.showInformationMessage(
+            "Enable the CodeChat Editor, then open a file to insert a snippet."
+        );
+        return;
+    }
+    const file_path = editor.document.fileName;
+    send_message({ ListSnippets: file_path }, async (_, result) => {
+        const ok = result?.Ok;
+        const names = typeof ok === "object" ? ok.Snippets ?? [] : [];
+        const name = await vscode.window.showQuickPick(names, {
+            placeHolder: "Select a snippet to insert",
+        });
+        if (name === undefined) {
+            return;
+        }
+        send_message({ InsertSnippet: { file_path, name } }, (_, result) => {
+            const ok = result?.Ok;
+            if (typeof ok === "object" && ok.Snippet !== undefined) {
+                const source = ok.Snippet.source;
+                editor.edit((edit_builder) =>
+                    edit_builder.insert(
+                        new vscode.Position(editor.selection.active.line, 0),
+                        source
+                    )
+                );
+            }
+        });
+    });
+};
+
+// Find the location of a definition: either the one found by the Server, or
+// else the first symbol with this name provided by the language servers.
+const find_definition = async (
+    goto_definition: GotoDefinitionContents
+): Promise<vscode.Location | undefined> => {
+    const definition = goto_definition.definition;
+    if (definition !== null) {
+        return new vscode.Location(
+            vscode.Uri.file(definition.file_path),
+            new vscode.Position(definition.line - 1, 0)
+        );
+    }
+    const symbols = await vscode.commands.executeCommand<
+        vscode.SymbolInformation[] | undefined
+    >("vscode.executeWorkspaceSymbolProvider", goto_definition.identifier);
+    return symbols?.find((symbol) => symbol.name === goto_definition.identifier)
+        ?.location;
+};
+
 // Report an error from the server.
 const report_server_timeout = (message_id: number) => {
     // Invoke the callback with an error.
@@ -600,7 +1071,9 @@ const start_render = () => {
                             },
                         },
                         cursor_position: undefined,
-                        scroll_position: undefined,
+                        scroll_position: {
+                            line: ate.visibleRanges[0]?.start.line ?? 0,
+                        },
                     },
                 });
             }
@@ -608,19 +1081,90 @@ const start_render = () => {
     }
 }
 
+// Tell the Client whether the provided document has unsaved changes, if this
+// changed since it was last told.
+const send_saved_state = (document: vscode.TextDocument) => {
+    const state: SaveState = document.isDirty ? "Dirty" : "Clean";
+    if (!can_render() || saved_states.get(document.fileName) === state) {
+        return;
+    }
+    saved_states.set(document.fileName, state);
+    send_message({
+        SavedState: {
+            file_path: document.fileName,
+            state,
+        },
+    });
+};
+
+// Send the folded regions of the provided editor, if it's the active editor
+// and these changed. VSCode doesn't provide its folds; instead, each gap
+// between the ranges of lines it shows is a fold. Since it provides only the
+// lines on the screen, keep the folds sent earlier which are outside these.
+const send_fold_ranges = (editor: vscode.TextEditor) => {
+    const visible_ranges = editor.visibleRanges;
+    if (
+        !can_render() ||
+        editor !== vscode.window.activeTextEditor ||
+        visible_ranges.length === 0
+    ) {
+        return;
+    }
+    const first_line = visible_ranges[0].start.line;
+    const last_line = visible_ranges[visible_ranges.length - 1].end.line;
+    const ranges = (JSON.parse(fold_ranges_json) as FoldRange[]).filter(
+        (range) => range.end < first_line || range.start > last_line
+    );
+    for (let index = 0; index < visible_ranges.length - 1; index++) {
+        ranges.push({
+            start: visible_ranges[index].end.line,
+            end: visible_ranges[index + 1].start.line - 1,
+        });
+    }
+    ranges.sort((a, b) => a.start - b.start);
+    const json = JSON.stringify(ranges);
+    if (json !== fold_ranges_json) {
+        fold_ranges_json = json;
+        send_message({
+            FoldRanges: {
+                file_path: editor.document.fileName,
+                ranges,
+            },
+        });
+    }
+};
+
 const current_file = () => {
     // Only send a new current file is there's a change.
     const ate = vscode.window.activeTextEditor;
     if (can_render() && ate !== current_editor) {
         current_editor = ate;
+        // The Server sends the folds of the new file.
+        fold_ranges_json = "[]";
         send_message(
             {
-                CurrentFile: ate!.document.fileName,
+                CurrentFile: {
+                    file_path: ate!.document.fileName,
+                    view_target: "Active",
+                },
             },
         );
     }
 };
 
+// Return the view column in which to show a file sent by the Server.
+const view_column = (
+    view_target: ViewTarget
+): vscode.ViewColumn | undefined => {
+    if (view_target === "Beside") {
+        return vscode.ViewColumn.Beside;
+    } else if (typeof view_target === "object") {
+        return view_target.Group as vscode.ViewColumn;
+    }
+    // Otherwise, replace the file shown in the current editor's column.
+    return current_editor?.viewColumn;
+};
+
 // Gracefully shut down the render client if possible. Shut down the client as
 // well.
 const stop_client = async () => {
@@ -648,8 +1192,64 @@ const stop_client = async () => {
         );
     }
     current_editor = undefined;
+    // The Server's state is no longer known.
+    status_bar_item?.dispose();
+    status_bar_item = undefined;
+    translating = 0;
+    client_connected = false;
 }
 
+// Show a change in the Server's state.
+const show_event = (event: ServerEvent) => {
+    if (event.Diagnostics !== undefined) {
+        const { file_path, diagnostics } = event.Diagnostics;
+        diagnostic_collection ??=
+            vscode.languages.createDiagnosticCollection("CodeChat Editor");
+        diagnostic_collection.set(
+            vscode.Uri.file(file_path),
+            diagnostics.map((diagnostic) => {
+                // The Server numbers lines from 1, with an exclusive end.
+                const d = new vscode.Diagnostic(
+                    new vscode.Range(
+                        diagnostic.range.start - 1,
+                        0,
+                        Math.max(
+                            diagnostic.range.end - 2,
+                            diagnostic.range.start - 1
+                        ),
+                        Number.MAX_SAFE_INTEGER
+                    ),
+                    diagnostic.message,
+                    vscode.DiagnosticSeverity[diagnostic.severity]
+                );
+                d.source = diagnostic.source;
+                return d;
+            })
+        );
+        return;
+    }
+    translating = event.Translating ?? translating;
+    client_connected = event.ClientConnected ?? client_connected;
+    if (status_bar_item === undefined) {
+        status_bar_item = vscode.window.createStatusBarItem(
+            vscode.StatusBarAlignment.Left
+        );
+        status_bar_item.command = "extension.codeChatEditorActivate";
+        status_bar_item.show();
+    }
+    if (translating > 0) {
+        status_bar_item.text = "$(sync~spin) CodeChat";
+        status_bar_item.tooltip = `CodeChat Editor: translating ${translating} file(s)`;
+    } else if (client_connected) {
+        status_bar_item.text = "$(check) CodeChat";
+        status_bar_item.tooltip = "CodeChat Editor: connected";
+    } else {
+        status_bar_item.text = "$(debug-disconnect) CodeChat";
+        status_bar_item.tooltip =
+            "CodeChat Editor: the Client isn't connected";
+    }
+};
+
 // Provide an error message in the panel if possible.
 const show_error = (message: string) => {
     if (webview_panel !== undefined) {
@@ -698,6 +1298,48 @@ const get_port = (): number => {
     return port;
 };
 
+// A Server started with `--tunnel` or `--base-path` only accepts programs on
+// its machine which provide its local secret, which it writes to a file only
+// this user may read. This mirrors `local_secret_path` in
+// [tunnel.rs](../../../server/src/tunnel.rs).
+const get_local_secret_headers = (): Record<string, string> => {
+    const config_dir =
+        process.platform === "win32"
+            ? process.env.APPDATA ?? ""
+            : process.platform === "darwin"
+              ? path.join(os.homedir(), "Library", "Application Support")
+              : process.env.XDG_CONFIG_HOME || path.join(os.homedir(), ".config");
+    try {
+        const secret = fs.readFileSync(
+            path.join(config_dir, "codechat-editor", `local-secret-${get_port()}`),
+            "utf8"
+        );
+        return { "X-CodeChat-Local-Secret": secret.trim() };
+    } catch {
+        // Other Servers don't require the secret.
+        return {};
+    }
+};
+
+// In classroom mode, identify this IDE as the instructor's by providing the
+// instructor token, if the user set one.
+const get_instructor_query = (): string => {
+    const token = vscode.workspace
+        .getConfiguration("CodeChatEditor.Server")
+        .get("InstructorToken");
+    assert(typeof token === "string");
+    return token === "" ? "" : `?instructor=${encodeURIComponent(token)}`;
+};
+
+// The least severe Server log messages to show.
+const get_log_level = (): string => {
+    const level = vscode.workspace
+        .getConfiguration("CodeChatEditor.Server")
+        .get("LogLevel");
+    assert(typeof level === "string");
+    return level;
+};
+
 const run_server = (args: string[]) => {
     // Get the command from the VSCode configuration.
     let codechat_editor_server_command = vscode.workspace
@@ -14,20 +14,30 @@
 // the CodeChat Editor. If not, see
 // [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
 mod pest_parser;
-/// # `lexer.rs` -- Lex source code into code and doc blocks
+/// # `lib.rs` -- Lex source code into code and doc blocks
+///
+/// This crate, `codechat-editor-lexer`, provides the lexer used by the
+/// CodeChat Editor, so that other documentation tools can split source code
+/// into code blocks and doc blocks (comments containing documentation) in the
+/// same way. Its stable API consists of `language_by_name` and
+/// `language_for_extension`, which find a language, and `lex`, which lexes
+/// source code in that language.
 // ## Submodule definitions
 pub mod supported_languages;
+#[cfg(feature = "tree-sitter")]
+mod tree_sitter_lexer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 // ## Imports
 //
 // ### Standard library
-#[cfg(feature = "lexer_explain")]
-use std::cmp::min;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
 // ### Third-party
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::Serialize;
 
 // ### Local
 use supported_languages::get_language_lexer_vec;
@@ -134,6 +144,15 @@ enum SpecialCase {
     /// [block comments](https://www.mathworks.com/help/matlab/matlab_prog/comments.html)
     /// must start and end on a blank line.
     Matlab,
+    /// In a [Makefile](https://www.gnu.org/software/make/manual/make.html#Recipe-Syntax),
+    /// lines beginning with a tab are recipes, which are passed to the shell;
+    /// a comment there isn't a Makefile comment, so it's never a doc block.
+    Makefile,
+    /// YAML [block scalars](https://yaml.org/spec/1.2.2/#81-block-scalar-styles)
+    /// end at the first line which isn't indented more than the line
+    /// introducing them. Quoted scalars only begin where a value may begin,
+    /// so that the apostrophe in `title: It's here` doesn't start a string.
+    Yaml,
 }
 
 /// Define a language by providing everything this lexer needs in order to split
@@ -146,7 +165,9 @@ pub struct LanguageLexer {
     pub lexer_name: Arc<String>,
     /// An array of file extensions for this language. They *do not* begin with
     /// a period, such as `rs`. This is the typical way that the CodeChat Editor
-    /// uses to determine which lexer to use for a given source file.
+    /// uses to determine which lexer to use for a given source file. Files
+    /// which are identified by their name, such as `Makefile`, list the full
+    /// file name here instead.
     ext_arr: Vec<Arc<String>>,
     /// An array of strings which specify inline comment delimiters. Empty if
     /// this language doesn't provide inline comments.
@@ -224,6 +245,21 @@ enum RegexDelimType {
         String,
     ),
     TemplateLiteral,
+    YamlBlockScalar,
+}
+
+impl RegexDelimType {
+    /// Describe the construct this delimiter begins.
+    fn description(&self) -> &'static str {
+        match self {
+            RegexDelimType::InlineComment => "an inline comment",
+            RegexDelimType::BlockComment(_) => "a block comment",
+            RegexDelimType::String(_) => "a string",
+            RegexDelimType::Heredoc(..) => "a heredoc",
+            RegexDelimType::TemplateLiteral => "a template literal",
+            RegexDelimType::YamlBlockScalar => "a YAML block scalar",
+        }
+    }
 }
 
 /// ### Code/doc blocks
@@ -231,6 +267,8 @@ enum RegexDelimType {
 #[derive(PartialEq)]
 // To allow printing with `println!`.
 #[derive(Debug)]
+// To allow keeping the previous blocks for incremental lexing.
+#[derive(Clone)]
 pub struct DocBlock {
     /// The whitespace characters which created the indent for this doc block.
     pub indent: String,
@@ -250,6 +288,8 @@ pub struct DocBlock {
 #[derive(PartialEq)]
 // To allow printing with `println!`.
 #[derive(Debug)]
+// To allow keeping the previous blocks for incremental lexing.
+#[derive(Clone)]
 pub enum CodeDocBlock {
     CodeBlock(
         // This contains the code defining this code block.
@@ -258,6 +298,70 @@ pub enum CodeDocBlock {
     DocBlock(DocBlock),
 }
 
+/// ### Incremental lexing
+///
+/// A change to source code: replace the bytes `from..to` with `insert`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceEdit {
+    pub from: usize,
+    pub to: usize,
+    pub insert: String,
+}
+
+/// ### Lexer fallbacks
+///
+/// The kind of construct whose closing delimiter the lexer couldn't find.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConstructKind {
+    BlockComment,
+    /// A string, template literal, or heredoc.
+    String,
+}
+
+/// A construct whose closing delimiter is missing. Rather than guess where it
+/// ends, the lexer treats the rest of the file, starting with this construct,
+/// as code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnterminatedConstruct {
+    pub kind: ConstructKind,
+    /// The (1-based) line containing the opening delimiter.
+    pub line: usize,
+    /// The opening delimiter.
+    pub delimiter: String,
+}
+
+/// ### Lexer trace
+///
+/// The kind of step the lexer took.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum LexerTraceKind {
+    /// The opening delimiter of a comment, string, or similar construct.
+    Token,
+    /// A string, heredoc, or similar construct, which can't contain comments.
+    String,
+    /// A comment which is a doc block.
+    DocBlock,
+    /// A comment which isn't a doc block; the message explains why.
+    Comment,
+    /// A construct whose closing delimiter wasn't found.
+    Unterminated,
+    /// This language is lexed by a parser, which doesn't provide a trace.
+    Parser,
+}
+
+/// One step taken by the lexer, explaining how it classified part of the
+/// source code.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LexerTraceEvent {
+    pub kind: LexerTraceKind,
+    /// The byte range of the source code this step refers to. Since the lexer
+    /// first replaces `\r\n` and `\r` with `\n`, this refers to the source
+    /// code after this replacement.
+    pub range: Range<usize>,
+    /// A description of this step.
+    pub message: String,
+}
+
 // ## Globals
 //
 // Create constant regexes needed by the lexer, following the
@@ -286,8 +390,25 @@ lazy_static! {
         "`"),
     ).unwrap();
 
+    /// Match the indentation of a line of YAML, including the indicators of any
+    /// sequence entries it begins.
+    static ref YAML_INDENT_REGEX: Regex = Regex::new("^ *(?:- +)*").unwrap();
+
     /// A vector of all supported languages.
     pub static ref LEXERS: LanguageLexersCompiled = compile_lexers(get_language_lexer_vec());
+
+    /// Content which suggests a file should be lexed by the named lexer. These
+    /// distinguish between lexers which share a file extension.
+    static ref CONTENT_HINTS: Vec<(&'static str, Regex)> = vec![
+        // Verilog and SystemVerilog both use `.v`/`.sv`.
+        ("verilog", Regex::new(r"(?m)^\s*(endmodule|always(_ff|_comb)?|assign|wire|reg|logic)\b").unwrap()),
+        // [V](https://vlang.io/) also uses `.v`; V functions begin with `fn`.
+        ("", Regex::new(r"(?m)^\s*(pub\s+)?fn\s+\w+\s*\(|:=").unwrap()),
+        // GNU assembler for ARM: an `@` comment or ARM-specific directives.
+        ("gas_arm", Regex::new(r"(?m)^\s*@|^\s*\.(syntax|thumb|arm|cpu\s+cortex)\b").unwrap()),
+        // GNU assembler for AVR or MSP430: a `;` comment.
+        ("gas_semicolon", Regex::new(r"(?m)^\s*;").unwrap()),
+    ];
 }
 
 // Support C# verbatim string literals, which end with a `"`; a `""` inserts a
@@ -498,7 +619,8 @@ fn build_lexer_regex(
     }
 
     match language_lexer.special_case {
-        SpecialCase::None => (),
+        // Makefile recipes are handled when lexing inline comments.
+        SpecialCase::None | SpecialCase::Makefile => (),
         // A C# verbatim string has asymmetric opening and closing delimiters,
         // making it a special case.
         SpecialCase::CSharpVerbatimStringLiteral => regex_builder(
@@ -519,6 +641,33 @@ fn build_lexer_regex(
             // parse -- or the closing backtick (which must be unescaped).
             regex_builder(&vec!["`".to_string()], RegexDelimType::TemplateLiteral);
         }
+        SpecialCase::Yaml => {
+            // A block scalar is introduced by a `|` or `>` indicator (with
+            // optional chomping and indentation indicators, then an optional
+            // comment) which ends a mapping value or sequence entry. Include
+            // the newline, so that the search for its end begins on the next
+            // line. Since each regex becomes a group of the overall regex,
+            // these contain only non-capturing groups.
+            regex_strings_arr
+                .push(r"(?m:[:\-][ \t]+[|>][0-9+\-]*[ \t]*(?:#.*)?(?:\n|$))".to_string());
+            regex_group_map.push(RegexDelimType::YamlBlockScalar);
+            // A quoted scalar begins a line, a mapping value, a sequence entry,
+            // or an entry in a flow collection. See
+            // [double-quoted style](https://yaml.org/spec/1.2.2/#double-quoted-style);
+            // newlines are allowed.
+            regex_strings_arr.push(r#"(?m:(?:^|[:?,\[{\-])[ \t]*")"#.to_string());
+            regex_group_map.push(RegexDelimType::String(
+                Regex::new(r#"^(?:[^"\\]|\\[\s\S])*""#).unwrap(),
+            ));
+            // See
+            // [single-quoted style](https://yaml.org/spec/1.2.2/#single-quoted-style):
+            // a single quote is escaped by repeating it, as in
+            // `'That''s unusual.'`
+            regex_strings_arr.push(r"(?m:(?:^|[:?,\[{\-])[ \t]*')".to_string());
+            regex_group_map.push(RegexDelimType::String(
+                Regex::new("^(?:[^']|'')*'").unwrap(),
+            ));
+        }
         SpecialCase::Matlab => {
             // MATLAB supports block comments, when the comment delimiters
             // appear alone on the line (also preceding and following whitespace
@@ -593,6 +742,28 @@ fn build_lexer_regex(
     }
 }
 
+// ## Choose a lexer
+//
+// Given the lexers which support a file's extension, choose the one whose
+// content hints match the file's contents. If none match, use the first lexer.
+pub fn choose_lexer<'a>(
+    lexers: &'a [Arc<LanguageLexerCompiled>],
+    file_contents: &str,
+) -> Option<&'a LanguageLexerCompiled> {
+    if lexers.len() > 1 {
+        for llc in lexers {
+            let lexer_name = llc.language_lexer.lexer_name.as_str();
+            if CONTENT_HINTS
+                .iter()
+                .any(|(name, hint)| *name == lexer_name && hint.is_match(file_contents))
+            {
+                return Some(llc);
+            }
+        }
+    }
+    lexers.first().map(|llc| &**llc)
+}
+
 // ## Compile lexers
 pub fn compile_lexers(language_lexer_arr: Vec<LanguageLexer>) -> LanguageLexersCompiled {
     let mut language_lexers_compiled = LanguageLexersCompiled {
@@ -630,6 +801,37 @@ pub fn compile_lexers(language_lexer_arr: Vec<LanguageLexer>) -> LanguageLexersC
     language_lexers_compiled
 }
 
+// ## Stable API
+//
+// Other tools should use these functions, rather than the lexer's internals,
+// which change as the CodeChat Editor does.
+/// Return the language with the provided name, such as `python` or `rust`.
+pub fn language_by_name(name: &str) -> Option<&'static LanguageLexerCompiled> {
+    // The map's keys are an `Arc<String>`, which can't be borrowed as a `str`.
+    let name = name.to_string();
+    LEXERS.map_mode_to_lexer.get(&name).map(|llc| llc.as_ref())
+}
+
+/// Return the language of a file with the provided extension (without the
+/// leading period, such as `rs`) or name (for files such as `Makefile`). When
+/// several languages share an extension, the file's contents choose between
+/// them.
+pub fn language_for_extension(
+    ext: &str,
+    file_contents: &str,
+) -> Option<&'static LanguageLexerCompiled> {
+    let ext = ext.to_string();
+    LEXERS
+        .map_ext_to_lexer_vec
+        .get(&ext)
+        .and_then(|lexers| choose_lexer(lexers, file_contents))
+}
+
+/// Split the provided source code into code blocks and doc blocks.
+pub fn lex(source_code: &str, language: &LanguageLexerCompiled) -> Vec<CodeDocBlock> {
+    source_lexer(source_code, language)
+}
+
 /// ## Source lexer
 ///
 /// This lexer categorizes source code into code blocks or doc blocks.
@@ -643,6 +845,268 @@ pub fn source_lexer(
     language_lexer_compiled: &LanguageLexerCompiled,
     // The return value is an array of code and doc blocks.
 ) -> Vec<CodeDocBlock> {
+    source_lexer_with_unterminated(source_code, language_lexer_compiled).0
+}
+
+// True if this language is lexed using a tree-sitter grammar.
+#[cfg(feature = "tree-sitter")]
+fn uses_tree_sitter(language_lexer: &LanguageLexer) -> bool {
+    tree_sitter_lexer::grammar(&language_lexer.lexer_name).is_some()
+}
+
+#[cfg(not(feature = "tree-sitter"))]
+fn uses_tree_sitter(_language_lexer: &LanguageLexer) -> bool {
+    false
+}
+
+// Like `source_lexer`, but also return the constructs whose closing delimiter
+// wasn't found, so that the user can be told why the rest of the file was
+// treated as code.
+pub fn source_lexer_with_unterminated(
+    // The source code to lex.
+    source_code: &str,
+    // A description of the language, used to lex the `source_code`.
+    language_lexer_compiled: &LanguageLexerCompiled,
+) -> (Vec<CodeDocBlock>, Vec<UnterminatedConstruct>) {
+    source_lexer_traced(source_code, language_lexer_compiled, &mut None)
+}
+
+/// ## Incremental lexer
+///
+/// Lex the source code produced by applying `edit` to `previous_source`, given
+/// `previous`, the code and doc blocks which `previous_source` lexed to. Only
+/// the blocks which `edit` affects are lexed again, which is much faster for
+/// large files.
+///
+/// This relies on every boundary between blocks lying at the start of a line,
+/// where the lexer is outside any string or comment. Therefore, lexing resumes
+/// at the last boundary before the edit, then continues through the first
+/// unchanged boundary after the edit at which lexing ends outside every
+/// construct; the blocks following this boundary are unchanged. Lexers which
+/// use a parser, support nested block comments, or look ahead past the end of
+/// a line to find the end of a construct (YAML's block scalars) lex the entire
+/// file instead.
+pub fn source_lexer_incremental(
+    // The source code which `previous` was lexed from, with normalized line
+    // endings.
+    previous_source: &str,
+    previous: Vec<CodeDocBlock>,
+    // The change to `previous_source`; its offsets must lie on character
+    // boundaries.
+    edit: &SourceEdit,
+    // A description of the language, used to lex the source code.
+    language_lexer_compiled: &LanguageLexerCompiled,
+) -> Vec<CodeDocBlock> {
+    let source_code = [
+        &previous_source[..edit.from],
+        &edit.insert,
+        &previous_source[edit.to..],
+    ]
+    .concat();
+    let language_lexer = &language_lexer_compiled.language_lexer;
+    if language_lexer.parser.is_some()
+        || uses_tree_sitter(language_lexer)
+        || matches!(language_lexer.special_case, SpecialCase::Yaml)
+        || language_lexer
+            .block_comment_delim_arr
+            .iter()
+            .any(|block_comment_delim| block_comment_delim.is_nestable)
+        || previous_source.contains('\r')
+        || edit.insert.contains('\r')
+    {
+        return source_lexer(&source_code, language_lexer_compiled);
+    }
+
+    // Find the line on which each previous block begins. Doc blocks contain as
+    // many newlines as the source code they came from.
+    let mut block_lines = Vec::with_capacity(previous.len());
+    let mut line = 0;
+    for code_doc_block in &previous {
+        block_lines.push(line);
+        line += match code_doc_block {
+            CodeDocBlock::CodeBlock(code) => code.matches('\n').count(),
+            CodeDocBlock::DocBlock(doc_block) => doc_block.contents.matches('\n').count(),
+        };
+    }
+    // If the previous blocks didn't come from this source, start over.
+    if line != previous_source.matches('\n').count() {
+        return source_lexer(&source_code, language_lexer_compiled);
+    }
+    // The byte offset of the start of each line of the previous source.
+    let previous_line_starts: Vec<usize> = [0]
+        .into_iter()
+        .chain(
+            previous_source
+                .match_indices('\n')
+                .map(|(index, _)| index + 1),
+        )
+        .collect();
+
+    // Resume lexing at the last block beginning on or before the line
+    // containing the start of the edit.
+    let edit_line = previous_source[..edit.from].matches('\n').count();
+    let first_block = block_lines
+        .partition_point(|&line| line <= edit_line)
+        .max(1)
+        - 1;
+    let resume_index = block_lines
+        .get(first_block)
+        .map_or(0, |&line| previous_line_starts[line]);
+
+    // Blocks beginning after the line containing the end of the edit are
+    // candidates for unchanged blocks. Find where each begins in the new
+    // source.
+    let edit_end_line = previous_source[..edit.to].matches('\n').count();
+    let shift = |index: usize| index - edit.to + edit.from + edit.insert.len();
+    let mut candidates = (first_block + 1..previous.len())
+        .filter(|&block| block_lines[block] > edit_end_line)
+        .map(|block| (block, shift(previous_line_starts[block_lines[block]])));
+
+    // Lex from the resume point to a candidate, stopping at the first for
+    // which lexing ends outside every construct. If none works, lex to the end
+    // of the file.
+    let (relexed, unchanged_block) = loop {
+        match candidates.next() {
+            Some((block, stop_index)) => {
+                let (relexed, unterminated) = source_lexer_with_unterminated(
+                    &source_code[resume_index..stop_index],
+                    language_lexer_compiled,
+                );
+                if unterminated.is_empty() {
+                    break (relexed, block);
+                }
+            }
+            None => {
+                break (
+                    source_lexer(&source_code[resume_index..], language_lexer_compiled),
+                    previous.len(),
+                )
+            }
+        }
+    };
+
+    // Join the unchanged blocks before the edit, the blocks lexed again, and
+    // the unchanged blocks after it, merging blocks of the same type as the
+    // lexer does.
+    let mut previous = previous;
+    let unchanged_after = previous.split_off(unchanged_block);
+    previous.truncate(first_block);
+    let mut code_doc_blocks = previous;
+    for code_doc_block in relexed.into_iter().chain(unchanged_after) {
+        push_merged(&mut code_doc_blocks, code_doc_block);
+    }
+    code_doc_blocks
+}
+
+// Append `code_doc_block`, merging it with the last block if both are code or
+// both are doc blocks with the same indent and delimiter.
+fn push_merged(code_doc_blocks: &mut Vec<CodeDocBlock>, code_doc_block: CodeDocBlock) {
+    match (code_doc_blocks.last_mut(), code_doc_block) {
+        (Some(CodeDocBlock::CodeBlock(last_code)), CodeDocBlock::CodeBlock(code)) => {
+            last_code.push_str(&code);
+        }
+        (Some(CodeDocBlock::DocBlock(last_doc_block)), CodeDocBlock::DocBlock(doc_block))
+            if last_doc_block.indent == doc_block.indent
+                && last_doc_block.delimiter == doc_block.delimiter =>
+        {
+            last_doc_block.contents += &doc_block.contents;
+            last_doc_block.lines += doc_block.lines;
+        }
+        (_, code_doc_block) => code_doc_blocks.push(code_doc_block),
+    }
+}
+
+// Like `source_lexer`, but also return a trace of each step the lexer took,
+// explaining why each comment was (or wasn't) classified as a doc block.
+pub fn source_lexer_explained(
+    // The source code to lex.
+    source_code: &str,
+    // A description of the language, used to lex the `source_code`.
+    language_lexer_compiled: &LanguageLexerCompiled,
+) -> (Vec<CodeDocBlock>, Vec<LexerTraceEvent>) {
+    let mut trace = Some(Vec::new());
+    let (code_doc_block_vec, _) =
+        source_lexer_traced(source_code, language_lexer_compiled, &mut trace);
+    (code_doc_block_vec, trace.unwrap_or_default())
+}
+
+// Record a step in the lexer's trace, if there is one. The message is only
+// built when tracing.
+fn explain(
+    trace: &mut Option<Vec<LexerTraceEvent>>,
+    kind: LexerTraceKind,
+    range: Range<usize>,
+    message: impl FnOnce() -> String,
+) {
+    if let Some(trace) = trace {
+        trace.push(LexerTraceEvent {
+            kind,
+            range,
+            message: message(),
+        });
+    }
+}
+
+// Record whether the comment in `range` is a doc block; if not, the reason
+// completes the sentence "This comment isn't a doc block, since...".
+fn explain_comment(
+    trace: &mut Option<Vec<LexerTraceEvent>>,
+    range: Range<usize>,
+    not_doc_block_reason: Option<&str>,
+) {
+    match not_doc_block_reason {
+        None => explain(trace, LexerTraceKind::DocBlock, range, || {
+            "This comment is a doc block.".to_string()
+        }),
+        Some(reason) => explain(trace, LexerTraceKind::Comment, range, || {
+            format!("This comment isn't a doc block, since {reason}.")
+        }),
+    }
+}
+
+// The lexer, optionally recording a trace of its steps in `trace`.
+fn source_lexer_traced(
+    // The source code to lex.
+    source_code: &str,
+    // A description of the language, used to lex the `source_code`.
+    language_lexer_compiled: &LanguageLexerCompiled,
+    // If this is `Some`, record each step taken here.
+    trace: &mut Option<Vec<LexerTraceEvent>>,
+) -> (Vec<CodeDocBlock>, Vec<UnterminatedConstruct>) {
+    if let Some(parser) = language_lexer_compiled.language_lexer.parser {
+        explain(trace, LexerTraceKind::Parser, 0..source_code.len(), || {
+            format!(
+                "The {} lexer uses a parser, which doesn't provide a trace.",
+                language_lexer_compiled.language_lexer.lexer_name
+            )
+        });
+        return (parser(source_code), Vec::new());
+    }
+    // Lex a language with a tree-sitter grammar by hiding everything except its
+    // comments from the regex lexer; see `tree_sitter_lexer.rs`.
+    #[cfg(feature = "tree-sitter")]
+    if let Some(masked_source) =
+        tree_sitter_lexer::mask_code(source_code, &language_lexer_compiled.language_lexer)
+    {
+        let (classified_source, unterminated) =
+            regex_source_lexer(&masked_source, language_lexer_compiled, trace);
+        return (
+            tree_sitter_lexer::unmask(source_code, classified_source),
+            unterminated,
+        );
+    }
+    regex_source_lexer(source_code, language_lexer_compiled, trace)
+}
+
+// Lex source code using the regexes in `language_lexer_compiled`.
+fn regex_source_lexer(
+    // The source code to lex.
+    source_code: &str,
+    // A description of the language, used to lex the `source_code`.
+    language_lexer_compiled: &LanguageLexerCompiled,
+    // If this is `Some`, record each step taken here.
+    trace: &mut Option<Vec<LexerTraceEvent>>,
+) -> (Vec<CodeDocBlock>, Vec<UnterminatedConstruct>) {
     // Rather than attempt to lex the entire language, this lexer's only goal is
     // to categorize all the source code into code blocks or doc blocks. To do
     // it, it only needs to:
@@ -676,16 +1140,13 @@ pub fn source_lexer(
     // makes heavy use of regexes -- read the previous link thoroughly.**
     //
     // To better explain the operation of the lexer, see the
-    // [lexer walkthrough](lexer/lexer-walkthrough.md).
+    // [lexer walkthrough](lexer-walkthrough.md).
     //
     // ### Helper function
     //
     // Provide a method to intelligently append to the code/doc block vec. Empty
     // appends are ignored; appends of the same type append to `contents`
     // instead of creating a new entry.
-    if let Some(parser) = language_lexer_compiled.language_lexer.parser {
-        return parser(source_code);
-    }
     let mut classified_source: Vec<CodeDocBlock> = Vec::new();
     let mut append_code_doc_block = |indent: &str, delimiter: &str, contents: &str| {
         // Don't append empty entries.
@@ -748,6 +1209,11 @@ pub fn source_lexer(
     let source_code = source_code.replace("\r\n", "\n").replace('\r', "\n");
     // This index marks the start of code that hasn't been lexed.
     let mut source_code_unlexed_index: usize = 0;
+    // Constructs whose closing delimiter wasn't found.
+    let mut unterminated = Vec::new();
+    // Given the index of a construct's opening delimiter, return the (1-based)
+    // line it's on.
+    let line_of = |index: usize| source_code[..index].matches('\n').count() + 1;
     // Ths index marks the start of code that belongs to the current code block.
     // The current code block is always defined as
     // `source_code[current_code_block_index..source_code_unlexed_index]`.
@@ -755,11 +1221,6 @@ pub fn source_lexer(
 
     // Main loop: lex the provided source code.
     while source_code_unlexed_index < source_code.len() {
-        #[cfg(feature = "lexer_explain")]
-        println!(
-            "Searching the following source_code using the pattern {:?}:\n'{}'\n\nThe current code block is '{}'\n",
-            language_lexer_compiled.next_token, &source_code[source_code_unlexed_index..], &source_code[current_code_block_index..source_code_unlexed_index]
-        );
         // #### Find the next token
         //
         // Look for the next special case. Per the earlier discussion, this
@@ -785,12 +1246,15 @@ pub fn source_lexer(
             // current code block, since per the assumptions this is code.
             source_code_unlexed_index += classify_match.get(matching_group_index).unwrap().start();
 
-            #[cfg(feature = "lexer_explain")]
-            println!(
-                "Matched the string {} in group {}. The current_code_block is now\n'{}'\n",
-                matching_group_str,
-                matching_group_index,
-                &source_code[current_code_block_index..source_code_unlexed_index]
+            // In the map, index 0 refers to group 1 (since group 0 matches are
+            // skipped). Adjust the index for this.
+            let regex_delim_type = &language_lexer_compiled.map[matching_group_index - 1];
+            let construct = regex_delim_type.description();
+            explain(
+                trace,
+                LexerTraceKind::Token,
+                source_code_unlexed_index..source_code_unlexed_index + matching_group_str.len(),
+                || format!("`{matching_group_str}` begins {construct}."),
             );
 
             // This helper function moves code from unlexed source code to the
@@ -800,34 +1264,31 @@ pub fn source_lexer(
                                    // match will be appended to the current code
                                    // block.
                                    |closing_regex: &Regex| {
-                #[cfg(feature = "lexer_explain")]
-                println!("Searching for the end of this token using the pattern '{:?}'.", closing_regex);
-
+                let opening_index = source_code_unlexed_index;
                 // Add the opening delimiter to the code.
                 source_code_unlexed_index += matching_group_str.len();
                 // Find the closing delimiter.
                 if let Some(closing_match) = closing_regex.find(&source_code[source_code_unlexed_index..]) {
-                    #[cfg(feature = "lexer_explain")]
-                    println!("Found; adding source_code up to and including this token to current_code_block.");
-
                     // Include this in code.
                     source_code_unlexed_index += closing_match.end();
+                    explain(trace, LexerTraceKind::String, opening_index..source_code_unlexed_index, || {
+                        format!("This is {construct}, which is code; it can't contain comments.")
+                    });
                 } else {
-                    #[cfg(feature = "lexer_explain")]
-                    println!("Not found; adding all the source_code to current_code_block.");
-
                     // Then the rest of the code is a string.
                     source_code_unlexed_index = source_code.len();
+                    explain(trace, LexerTraceKind::Unterminated, opening_index..source_code_unlexed_index, || {
+                        format!("This is {construct} which is never closed, so the rest of the file is code.")
+                    });
+                    unterminated.push(UnterminatedConstruct {
+                        kind: ConstructKind::String,
+                        line: line_of(opening_index),
+                        delimiter: matching_group_str.to_string(),
+                    });
                 }
-                #[cfg(feature = "lexer_explain")]
-                println!("The current_code_block is now\n\
-                    '{}'\n", &source_code[current_code_block_index..source_code_unlexed_index]);
-
             };
 
-            // In the map, index 0 refers to group 1 (since group 0 matches are
-            // skipped). Adjust the index for this.
-            match &language_lexer_compiled.map[matching_group_index - 1] {
+            match regex_delim_type {
                 // #### Inline comment
                 RegexDelimType::InlineComment => {
                     // **First**, find the end of this comment: a newline.
@@ -870,14 +1331,6 @@ pub fn source_lexer(
                     let full_comment =
                         &source_code[full_comment_start_index..source_code_unlexed_index];
 
-                    #[cfg(feature = "lexer_explain")]
-                    println!(
-                        "This is an inline comment. Source code before the line containing this comment is:\n'{}'\n\
-                        The text preceding this comment is: '{}'.\n\
-                        The comment is: '{}'\n",
-                        code_lines_before_comment, comment_line_prefix, full_comment
-                    );
-
                     // **Next**, determine if this comment is a doc block.
                     // Criteria for doc blocks for an inline comment:
                     //
@@ -890,24 +1343,41 @@ pub fn source_lexer(
                     //         newline or the end of the file.
                     //
                     // With this last line located, apply the doc block
-                    // criteria.
-                    let ws_only = WHITESPACE_ONLY_REGEX.is_match(comment_line_prefix);
+                    // criteria, noting the first one this comment fails.
                     let has_space_after_comment = full_comment.starts_with(' ');
-                    // Criteria 1 -- the whitespace matched.
-                    if ws_only &&
-                        // TODO: generalize this to specific lines that are
-                        // never doc blocks.
-                        full_comment != " prettier-ignore\n"
-                        && (
-                            // Criteria 2.1
-                            has_space_after_comment ||
-                            // Criteria 2.2a
-                            (full_comment == "\n" ||
-                            // Criteria 2.2b -- end of file means the comment is
-                            // empty.
-                            full_comment.is_empty())
-                        )
-                    {
+                    let is_makefile_recipe = matches!(
+                        language_lexer_compiled.language_lexer.special_case,
+                        SpecialCase::Makefile
+                    ) && comment_line_prefix.starts_with('\t');
+                    let not_doc_block_reason =
+                        if !WHITESPACE_ONLY_REGEX.is_match(comment_line_prefix) {
+                            // Criteria 1 failed.
+                            Some("code precedes it on the same line")
+                        } else if is_makefile_recipe {
+                            // A comment in a Makefile recipe belongs to the shell.
+                            Some("it's part of a Makefile recipe, which is passed to the shell")
+                        } else if full_comment == " prettier-ignore\n" {
+                            // TODO: generalize this to specific lines that are
+                            // never doc blocks.
+                            Some("it's a `prettier-ignore` directive")
+                        } else if has_space_after_comment
+                            || full_comment == "\n"
+                            || full_comment.is_empty()
+                        {
+                            // Criteria 2 succeeded; for criteria 2.2, the end
+                            // of the file means the comment is empty.
+                            None
+                        } else {
+                            // Criteria 2 failed.
+                            Some("its delimiter isn't followed by a space")
+                        };
+                    explain_comment(
+                        trace,
+                        full_comment_start_index - matching_group_str.len()
+                            ..source_code_unlexed_index,
+                        not_doc_block_reason,
+                    );
+                    if not_doc_block_reason.is_none() {
                         // This is a doc block. Transition from the preceding
                         // code block to this doc block.
                         append_code_doc_block("", "", code_lines_before_comment);
@@ -923,15 +1393,6 @@ pub fn source_lexer(
                         let contents = &full_comment[if has_space_after_comment { 1 } else { 0 }..];
                         append_code_doc_block(comment_line_prefix, matching_group_str, contents);
 
-                        #[cfg(feature = "lexer_explain")]
-                        println!(
-                            "This is a doc block. Possibly added the preceding code block\n\
-                            '{}'.\n\
-                            Added a doc block with indent = '{}', delimiter = '{}', and contents =\n\
-                            '{}'.\n",
-                            current_code_block, comment_line_prefix, matching_group_str, contents
-                        );
-
                         // We've now stored the current code block (which was
                         // classified as a doc block) in `classified_lines`.
                         // Make the current code block empty by moving its index
@@ -946,20 +1407,12 @@ pub fn source_lexer(
 
                 // #### Block comment
                 RegexDelimType::BlockComment(comment_delim_regex) => 'block_comment: {
-                    #[cfg(feature = "lexer_explain")]
-                    println!("Block Comment Found.");
-
+                    let opening_index = source_code_unlexed_index;
                     // Determine the location of the beginning of this block
                     // comment's content.
                     let mut comment_start_index =
                         source_code_unlexed_index + matching_group_str.len();
 
-                    #[cfg(feature = "lexer_explain")]
-                    println!(
-                        "The opening delimiter is '{}', and the closing delimiter regex is '{}'.",
-                        matching_group_str, comment_delim_regex
-                    );
-
                     // For nested comments, only treat the innermost comment as
                     // a potential doc block; everything else is treated as
                     // code. The rationale:
@@ -1002,17 +1455,9 @@ pub fn source_lexer(
                     while nesting_depth != 0 && loop_count < 10 {
                         loop_count += 1;
                         // Get the index of the next block comment delimiter.
-                        #[cfg(feature = "lexer_explain")]
-                        println!(
-                            "Looking for a block comment delimiter in '{}'.",
-                            &source_code[comment_start_index
-                                ..min(comment_start_index + 30, source_code.len())]
-                        );
                         let delimiter_captures_wrapped =
                             comment_delim_regex.captures(&source_code[comment_start_index..]);
                         if delimiter_captures_wrapped.is_none() {
-                            #[cfg(feature = "lexer_explain")]
-                            println!("The closing comment delimiter wasn't found.");
                             // If there's no closing delimiter, this is not a
                             // doc block; it's a syntax error. The safe route is
                             // to assume the rest of the contents are code,
@@ -1020,7 +1465,20 @@ pub fn source_lexer(
                             // comments by cleaning up HTML tags, word-wrapping,
                             // etc. which would be a disaster if this was
                             // applied to code.
+                            unterminated.push(UnterminatedConstruct {
+                                kind: ConstructKind::BlockComment,
+                                line: line_of(opening_index),
+                                delimiter: matching_group_str.to_string(),
+                            });
                             source_code_unlexed_index = source_code.len();
+                            explain(
+                                trace,
+                                LexerTraceKind::Unterminated,
+                                opening_index..source_code_unlexed_index,
+                                || {
+                                    "This block comment is never closed, so the rest of the file is code.".to_string()
+                                },
+                            );
                             // Exit the block comment processing code here.
                             break 'block_comment;
                         }
@@ -1053,20 +1511,17 @@ pub fn source_lexer(
                             nesting_depth += 1;
                             // Mark all previous text as code, then continue the
                             // loop.
-                            #[cfg(feature = "lexer_explain")]
-                            println!(
-                                "opening_delimiter.start() = {}, opening_delimiter.len() = {}",
-                                opening_delimiter.start(),
-                                opening_delimiter.len()
-                            );
                             source_code_unlexed_index +=
                                 comment_start_index + opening_delimiter.start();
                             comment_start_index =
                                 source_code_unlexed_index + opening_delimiter.len();
-                            #[cfg(feature = "lexer_explain")]
-                            println!(
-                                "Found a nested opening block comment delimiter. Nesting depth: {}",
-                                &nesting_depth
+                            explain(
+                                trace,
+                                LexerTraceKind::Comment,
+                                comment_start_index - opening_delimiter.len()..comment_start_index,
+                                || {
+                                    format!("This begins a nested comment (nesting depth {nesting_depth}); only the innermost comment may be a doc block.")
+                                },
                             );
                             continue;
                         } else {
@@ -1086,8 +1541,15 @@ pub fn source_lexer(
                                     + closing_delimiter_match.start()
                                     + closing_delimiter_match.len();
                                 last_delimiter_was_opening = false;
-                                #[cfg(feature = "lexer_explain")]
-                                println!("Found a non-innermost closing block comment delimiter. Nesting depth: {}", &nesting_depth);
+                                explain(
+                                    trace,
+                                    LexerTraceKind::Comment,
+                                    comment_start_index + closing_delimiter_match.start()
+                                        ..comment_start_index + closing_delimiter_match.end(),
+                                    || {
+                                        format!("This closes a comment which contains nested comments (nesting depth {nesting_depth}), so it's code.")
+                                    },
+                                );
                                 continue;
                             }
 
@@ -1104,14 +1566,6 @@ pub fn source_lexer(
                             let comment_body =
                                 &source_code[comment_start_index..closing_delimiter_start_index];
 
-                            #[cfg(feature = "lexer_explain")]
-                            println!(
-                                "The comment body is\n\
-                            '{}'.\n\
-                            The closing delimiter is '{}'.",
-                                comment_body,
-                                closing_delimiter_match.as_str()
-                            );
                             // Find the first \\n after the closing delimiter.
                             // If there is a newline after the closing
                             // delimiter, set
@@ -1132,12 +1586,6 @@ pub fn source_lexer(
                                 [closing_delimiter_end_index
                                     ..newline_or_eof_after_closing_delimiter_index];
 
-                            #[cfg(feature = "lexer_explain")]
-                            println!(
-                                "The post-comment line is '{}'.",
-                                post_closing_delimiter_line
-                            );
-
                             // Set the `current_code_block` to contain preceding
                             // code (which might be multiple lines) until the
                             // block comment delimiter. Split this on newlines,
@@ -1157,19 +1605,14 @@ pub fn source_lexer(
                             let code_lines_before_comment = &current_code_block
                                 [..current_code_block.len() - comment_line_prefix.len()];
 
+                            // The comment begins after the current code
+                            // block.
+                            let comment_index = source_code_unlexed_index;
                             // Move to the next block of source code to be
                             // lexed.
                             source_code_unlexed_index =
                                 newline_or_eof_after_closing_delimiter_index;
 
-                            #[cfg(feature = "lexer_explain")]
-                            println!(
-                                "current_code_block is '{}'\n\
-                            comment_line_prefix is '{}'\n\
-                            code_lines_before_comment is '{}'",
-                                current_code_block, comment_line_prefix, code_lines_before_comment
-                            );
-
                             // Next, determine if this is a doc block. Criteria
                             // for doc blocks for a block comment:
                             //
@@ -1187,10 +1630,25 @@ pub fn source_lexer(
                             //     whitespace); moving that whitespace around
                             //     seems like a better alternative than deleting
                             //     it.
-                            if (comment_body.starts_with(' ') || comment_body.starts_with('\n'))
-                                && WHITESPACE_ONLY_REGEX.is_match(comment_line_prefix)
-                                && WHITESPACE_ONLY_REGEX.is_match(post_closing_delimiter_line)
+                            //
+                            // Note the first criteria this comment fails.
+                            let not_doc_block_reason = if !(comment_body.starts_with(' ')
+                                || comment_body.starts_with('\n'))
                             {
+                                Some("its opening delimiter isn't followed by a space or newline")
+                            } else if !WHITESPACE_ONLY_REGEX.is_match(comment_line_prefix) {
+                                Some("code precedes it on the same line")
+                            } else if !WHITESPACE_ONLY_REGEX.is_match(post_closing_delimiter_line) {
+                                Some("code follows it on the same line")
+                            } else {
+                                None
+                            };
+                            explain_comment(
+                                trace,
+                                comment_index..closing_delimiter_end_index,
+                                not_doc_block_reason,
+                            );
+                            if not_doc_block_reason.is_none() {
                                 // Put the `code_lines_before_comment` into the
                                 // code block.
                                 append_code_doc_block("", "", code_lines_before_comment);
@@ -1339,10 +1797,6 @@ pub fn source_lexer(
                                 // Add this doc block:
                                 append_code_doc_block(indent, delimiter, dedented_contents);
 
-                                // print the doc block
-                                #[cfg(feature = "lexer_explain")]
-                                println!("Appending a doc block with indent '{}', delimiter '{}', and contents '{}'.", &comment_line_prefix, matching_group_str, contents);
-
                                 // advance `current_code_block_index` to
                                 // `source_code_unlexed_index`, since we've
                                 // moved everything in the current code block
@@ -1362,22 +1816,49 @@ pub fn source_lexer(
                 }
 
                 // #### String-like syntax
-                RegexDelimType::String(closing_regex) => {
-                    #[cfg(feature = "lexer_explain")]
-                    print!("This is a string. ");
-                    append_code(closing_regex)
-                }
+                RegexDelimType::String(closing_regex) => append_code(closing_regex),
 
                 RegexDelimType::TemplateLiteral => {
-                    #[cfg(feature = "lexer_explain")]
-                    print!("This is a template literal. ");
                     append_code(&TEMPLATE_LITERAL_CLOSING_REGEX);
                 }
 
-                RegexDelimType::Heredoc(stop_prefix, stop_suffix) => {
-                    #[cfg(feature = "lexer_explain")]
-                    print!("This is a heredoc. ");
+                RegexDelimType::YamlBlockScalar => {
+                    // Find the indentation of the node containing this block
+                    // scalar. For a mapping value in a sequence entry, such as
+                    // `- key: |`, this is the indentation of the key.
+                    let opening_index = source_code_unlexed_index;
+                    let line_start = source_code[..opening_index]
+                        .rfind('\n')
+                        .map_or(0, |index| index + 1);
+                    let line = &source_code[line_start..];
+                    let indent = if matching_group_str.starts_with(':') {
+                        YAML_INDENT_REGEX.find(line).unwrap().end()
+                    } else {
+                        line.len() - line.trim_start_matches(' ').len()
+                    };
+                    source_code_unlexed_index += matching_group_str.len();
+                    // The block scalar ends at the first non-blank line which
+                    // isn't indented more than this, or at the end of the file.
+                    let closing_regex = Regex::new(&format!("(?m)^ {{0,{indent}}}[^ \n]")).unwrap();
+                    source_code_unlexed_index += closing_regex
+                        .find(&source_code[source_code_unlexed_index..])
+                        .map_or(
+                            source_code.len() - source_code_unlexed_index,
+                            |closing_match| closing_match.start(),
+                        );
+                    explain(
+                        trace,
+                        LexerTraceKind::String,
+                        opening_index..source_code_unlexed_index,
+                        || {
+                            format!(
+                                "This is {construct}, which is code; it can't contain comments."
+                            )
+                        },
+                    );
+                }
 
+                RegexDelimType::Heredoc(stop_prefix, stop_suffix) => {
                     // Get the string from the source code which (along with the
                     // stop prefix/suffix) defines the end of the heredoc.
                     let heredoc_string = &classify_match[language_lexer_compiled.map.len() + 1];
@@ -1401,7 +1882,7 @@ pub fn source_lexer(
     // Any leftover code is source code.
     append_code_doc_block("", "", &source_code[current_code_block_index..]);
 
-    classified_source
+    (classified_source, unterminated)
 }
 
 // ## Tests
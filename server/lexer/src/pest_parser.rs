@@ -36,7 +36,7 @@
 #[macro_export]
 macro_rules! make_parse_to_code_doc_blocks {
     ($parser: ty) => {
-        pub fn parse_to_code_doc_blocks(input: &str) -> Vec<$crate::lexer::CodeDocBlock> {
+        pub fn parse_to_code_doc_blocks(input: &str) -> Vec<$crate::CodeDocBlock> {
             // While Pest has no problem working with all types of line endings,
             // CodeMirror converts all line endings to `\n` then indexes strings
             // based on that assumption. Normalize line endings to `\n` so that
@@ -105,7 +105,7 @@ macro_rules! make_parse_to_code_doc_blocks {
 
                         //println!("Inline comment: {whitespace}{comment:#?}");
                         let lines = comment.lines().count();
-                        $crate::lexer::CodeDocBlock::DocBlock($crate::lexer::DocBlock {
+                        $crate::CodeDocBlock::DocBlock($crate::DocBlock {
                             indent: whitespace.to_string(),
                             delimiter: inline_comment_delim.as_str().to_string(),
                             contents: comment.to_string(),
@@ -185,7 +185,7 @@ macro_rules! make_parse_to_code_doc_blocks {
                         // Transform this to a doc block.
                         //println!("Block comment: {pre_whitespace}{full_comment:#?}");
                         let lines = full_comment.lines().count();
-                        $crate::lexer::CodeDocBlock::DocBlock($crate::lexer::DocBlock {
+                        $crate::CodeDocBlock::DocBlock($crate::DocBlock {
                             indent: pre_whitespace.to_string(),
                             delimiter: "/*".to_string(),
                             contents: full_comment.to_string(),
@@ -194,7 +194,7 @@ macro_rules! make_parse_to_code_doc_blocks {
                     }
                     Rule::code_block => {
                         //println!("Code block: {:#?}", &block.as_str());
-                        $crate::lexer::CodeDocBlock::CodeBlock(block.as_str().to_string())
+                        $crate::CodeDocBlock::CodeBlock(block.as_str().to_string())
                     }
                     _ => unreachable!(),
                 })
@@ -241,8 +241,8 @@ pub mod c {
     use pest_derive::Parser;
 
     #[derive(Parser)]
-    #[grammar = "lexer/pest/shared.pest"]
-    #[grammar = "lexer/pest/c.pest"]
+    #[grammar = "pest/shared.pest"]
+    #[grammar = "pest/c.pest"]
     struct ThisParser;
     make_parse_to_code_doc_blocks!(ThisParser);
     make_parse_block_comment!(ThisParser);
@@ -253,8 +253,8 @@ pub mod python {
     use pest_derive::Parser;
 
     #[derive(Parser)]
-    #[grammar = "lexer/pest/shared.pest"]
-    #[grammar = "lexer/pest/python.pest"]
+    #[grammar = "pest/shared.pest"]
+    #[grammar = "pest/python.pest"]
     struct ThisParser;
     make_parse_to_code_doc_blocks!(ThisParser);
     make_parse_block_comment!(ThisParser);
@@ -266,7 +266,7 @@ mod test {
     use indoc::indoc;
 
     use super::{c, python};
-    use crate::lexer::{CodeDocBlock, DocBlock};
+    use crate::{CodeDocBlock, DocBlock};
 
     #[test]
     fn test_pest_c_1() {
@@ -203,6 +203,68 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             SpecialCase::None,
             None,
         ),
+        // ### [Dockerfile](https://docs.docker.com/reference/dockerfile/)
+        make_language_lexer(
+            "dockerfile",
+            &["dockerfile", "Dockerfile", "Containerfile"],
+            // Only a `#` at the beginning of a line begins a comment; since a
+            // comment after an instruction isn't a doc block, this distinction
+            // doesn't matter.
+            &["#"],
+            &[],
+            &[],
+            // Lines in a
+            // [here-document](https://docs.docker.com/reference/dockerfile/#here-documents),
+            // such as `RUN <<EOF`, are passed to the instruction, so a `#`
+            // there isn't a comment. The lexer escapes the prefix and suffix,
+            // so only the unquoted `<<EOF` form is recognized.
+            make_heredoc_delim("<<", "\\w+", "", "", ""),
+            SpecialCase::None,
+            None,
+        ),
+        // ### GNU assembler
+        //
+        // The character which begins a comment depends on the target
+        // architecture; see
+        // [Machine Dependencies](https://sourceware.org/binutils/docs/as/Machine-Dependent.html).
+        // All architectures support C-style block comments; see
+        // [Comments](https://sourceware.org/binutils/docs/as/Comments.html).
+        // Since the extensions are the same for every architecture, content
+        // hints in the [lexer](../lexer.rs) choose between these.
+        //
+        // x86, RISC-V, and MIPS use `#`.
+        make_language_lexer(
+            "gas",
+            &["s", "S", "asm"],
+            &["#"],
+            &[make_block_comment_delim("/*", "*/", false)],
+            &[make_string_delimiter_spec("\"", "\\", NewlineSupport::None)],
+            None,
+            SpecialCase::None,
+            None,
+        ),
+        // ARM uses `@`; AArch64 uses `//`.
+        make_language_lexer(
+            "gas_arm",
+            &["s", "S", "asm"],
+            &["@", "//"],
+            &[make_block_comment_delim("/*", "*/", false)],
+            &[make_string_delimiter_spec("\"", "\\", NewlineSupport::None)],
+            None,
+            SpecialCase::None,
+            None,
+        ),
+        // AVR and MSP430 use `;`.
+        make_language_lexer(
+            "gas_semicolon",
+            &["s", "S", "asm"],
+            &[";"],
+            &[make_block_comment_delim("/*", "*/", false)],
+            &[make_string_delimiter_spec("\"", "\\", NewlineSupport::None)],
+            None,
+            SpecialCase::None,
+            None,
+        ),
         // ### Go
         make_language_lexer(
             "golang",
@@ -235,6 +297,18 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             SpecialCase::None,
             None,
         ),
+        // ### INI
+        make_language_lexer(
+            "ini",
+            &["ini", "cfg", ".editorconfig", ".gitconfig"],
+            // INI files have no standard; most parsers accept both of these.
+            &["#", ";"],
+            &[],
+            &[],
+            None,
+            SpecialCase::None,
+            None,
+        ),
         // ### Java/Kotlin
         make_language_lexer(
             "java",
@@ -305,6 +379,48 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             SpecialCase::None,
             None,
         ),
+        // ### [Kconfig](https://docs.kernel.org/kbuild/kconfig-language.html)
+        make_language_lexer(
+            "kconfig",
+            // TODO: a line of `help` text which begins with `#` is part of the
+            // help text, but is treated as a comment.
+            &["Kconfig"],
+            &["#"],
+            &[],
+            &[
+                make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
+                make_string_delimiter_spec("'", "\\", NewlineSupport::None),
+            ],
+            None,
+            SpecialCase::None,
+            None,
+        ),
+        // ### GNU linker scripts
+        make_language_lexer(
+            "linker_script",
+            &["ld", "lds"],
+            // See
+            // [Script Format](https://sourceware.org/binutils/docs/ld/Script-Format.html):
+            // only block comments are supported.
+            &[],
+            &[make_block_comment_delim("/*", "*/", false)],
+            &[make_string_delimiter_spec("\"", "", NewlineSupport::None)],
+            None,
+            SpecialCase::None,
+            None,
+        ),
+        // ### [Makefile](https://www.gnu.org/software/make/manual/make.html)
+        make_language_lexer(
+            "makefile",
+            &["mk", "mak", "Makefile", "makefile", "GNUmakefile"],
+            &["#"],
+            &[],
+            // Make has no strings; quotes are passed through to the shell.
+            &[],
+            None,
+            SpecialCase::Makefile,
+            None,
+        ),
         // ### MATLAB
         make_language_lexer(
             "matlab",
@@ -436,8 +552,9 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
                 make_string_delimiter_spec("\"\"\"", "\\", NewlineSupport::Unescaped),
                 // Basic strings
                 make_string_delimiter_spec("\"", "\\", NewlineSupport::None),
-                // Literal strings
-                make_string_delimiter_spec("'", "\\", NewlineSupport::Escaped),
+                // Literal strings have no escapes and can't contain
+                // newlines.
+                make_string_delimiter_spec("'", "", NewlineSupport::None),
             ],
             None,
             SpecialCase::None,
@@ -515,25 +632,11 @@ pub fn get_language_lexer_vec() -> Vec<LanguageLexer> {
             &["yaml", "yml"],
             &["#"],
             &[],
-            &[
-                // See
-                // [double-quoted style](https://yaml.org/spec/1.2.2/#double-quoted-style).
-                // Something I don't understand and will probably ignore:
-                // "Single- and double-quoted scalars are restricted to a single
-                // line when contained inside an implicit key."
-                make_string_delimiter_spec("\"", "\\", NewlineSupport::Unescaped),
-                // See
-                // [single-quoted style](https://yaml.org/spec/1.2.2/#single-quoted-style).
-                // Single-quoted strings escape a single quote by repeating it
-                // twice: `'That''s unusual.'` Rather than try to parse this,
-                // treat it as two back-to-back strings: `'That'` and
-                // `'s unusual.'` We don't care about getting the correct value
-                // for strings; the only purpose is to avoid interpreting string
-                // contents as inline or block comments.
-                make_string_delimiter_spec("'", "", NewlineSupport::Unescaped),
-            ],
+            // Since quoted scalars can only begin in some places, these are
+            // handled as a special case, along with block scalars.
+            &[],
             None,
-            SpecialCase::None,
+            SpecialCase::Yaml,
             None,
         ),
         // ### Markdown
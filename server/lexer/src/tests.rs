@@ -17,12 +17,21 @@
 /// # `test.rs` -- Unit tests for the lexer
 // ## Imports
 use super::supported_languages::get_language_lexer_vec;
-use super::{compile_lexers, source_lexer, CodeDocBlock, DocBlock};
-use crate::test_utils::stringit;
+use super::{
+    choose_lexer, compile_lexers, language_by_name, language_for_extension, lex, source_lexer,
+    source_lexer_explained, source_lexer_incremental, source_lexer_with_unterminated, CodeDocBlock,
+    ConstructKind, DocBlock, LexerTraceKind, SourceEdit, UnterminatedConstruct,
+};
 use indoc::indoc;
 
 // ## Utilities
 //
+// Clippy warns about `&"css".to_string()`, even though `get` requires a
+// `&String`; this avoids the warning.
+fn stringit(s: &str) -> String {
+    s.to_string()
+}
+
 // Provide a compact way to create a `CodeDocBlock`.
 fn build_doc_block(indent: &str, delimiter: &str, contents: &str) -> CodeDocBlock {
     CodeDocBlock::DocBlock(DocBlock {
@@ -718,6 +727,314 @@ fn test_toml() {
             build_doc_block("", "#", "Test 1\"")
         ]
     );
+    // Literal strings don't have escapes.
+    assert_eq!(
+        source_lexer("a = 'C:\\'\n# Test", toml),
+        [
+            build_code_block("a = 'C:\\'\n"),
+            build_doc_block("", "#", "Test")
+        ]
+    );
+}
+
+#[test]
+fn test_yaml() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let yaml = llc.map_mode_to_lexer.get(&stringit("yaml")).unwrap();
+
+    assert_eq!(
+        source_lexer(
+            indoc!(
+                "
+                # Doc 1
+                script: |
+                  # Not a comment
+                  echo hi
+                # Doc 2
+                items:
+                  - key: >-
+                      # Text
+                    other: 1
+                  # Doc 3
+                title: It's here
+                # Doc 4
+                quote: 'That''s # not a comment'
+                "
+            ),
+            yaml
+        ),
+        [
+            build_doc_block("", "#", "Doc 1\n"),
+            build_code_block("script: |\n  # Not a comment\n  echo hi\n"),
+            build_doc_block("", "#", "Doc 2\n"),
+            build_code_block("items:\n  - key: >-\n      # Text\n    other: 1\n"),
+            build_doc_block("  ", "#", "Doc 3\n"),
+            build_code_block("title: It's here\n"),
+            build_doc_block("", "#", "Doc 4\n"),
+            build_code_block("quote: 'That''s # not a comment'\n"),
+        ]
+    );
+    // A block scalar may end the file.
+    assert_eq!(
+        source_lexer("a: |\n  # Text", yaml),
+        [build_code_block("a: |\n  # Text")]
+    );
+}
+
+#[test]
+fn test_gas() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let gas_arm = llc.map_mode_to_lexer.get(&stringit("gas_arm")).unwrap();
+
+    // ARM assembly supports both `@` and `//` comments, along with C-style
+    // block comments.
+    assert_eq!(
+        source_lexer(
+            "@ Doc 1\nmov r0, #1 @ Code\n// Doc 2\n/* Doc 3 */\n",
+            gas_arm
+        ),
+        [
+            build_doc_block("", "@", "Doc 1\n"),
+            build_code_block("mov r0, #1 @ Code\n"),
+            build_doc_block("", "//", "Doc 2\n"),
+            build_doc_block("", "/*", "Doc 3\n"),
+        ]
+    );
+
+    // Linker scripts only have block comments.
+    let linker_script = llc
+        .map_mode_to_lexer
+        .get(&stringit("linker_script"))
+        .unwrap();
+    assert_eq!(
+        source_lexer("/* Doc */\nENTRY(_start)\n", linker_script),
+        [
+            build_doc_block("", "/*", "Doc\n"),
+            build_code_block("ENTRY(_start)\n"),
+        ]
+    );
+}
+
+#[test]
+fn test_makefile() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let makefile = llc.map_mode_to_lexer.get(&stringit("makefile")).unwrap();
+
+    // A comment in a recipe belongs to the shell, so it isn't a doc block.
+    assert_eq!(
+        source_lexer("# Doc\nall:\n\t# Shell\n  # Indented\n", makefile),
+        [
+            build_doc_block("", "#", "Doc\n"),
+            build_code_block("all:\n\t# Shell\n"),
+            build_doc_block("  ", "#", "Indented\n"),
+        ]
+    );
+}
+
+#[test]
+fn test_dockerfile() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let dockerfile = llc.map_mode_to_lexer.get(&stringit("dockerfile")).unwrap();
+
+    // Comments in a here-document are passed to the instruction.
+    assert_eq!(
+        source_lexer("# Doc\nRUN <<EOF\n# Shell\nEOF\n", dockerfile),
+        [
+            build_doc_block("", "#", "Doc\n"),
+            build_code_block("RUN <<EOF\n# Shell\nEOF\n"),
+        ]
+    );
+}
+
+// ### Lexer fallback tests
+#[test]
+fn test_unterminated() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let js = llc.map_mode_to_lexer.get(&stringit("javascript")).unwrap();
+
+    // Terminated constructs aren't reported.
+    assert_eq!(
+        source_lexer_with_unterminated("a = `b`;\n/* c */\n", js).1,
+        []
+    );
+
+    // An unterminated block comment makes the rest of the file code.
+    assert_eq!(
+        source_lexer_with_unterminated("// Doc\na = 1;\n/* Oops\n// More doc\n", js),
+        (
+            vec![
+                build_doc_block("", "//", "Doc\n"),
+                build_code_block("a = 1;\n/* Oops\n// More doc\n")
+            ],
+            vec![UnterminatedConstruct {
+                kind: ConstructKind::BlockComment,
+                line: 3,
+                delimiter: "/*".to_string()
+            }]
+        )
+    );
+
+    // Likewise, an unterminated template literal.
+    assert_eq!(
+        source_lexer_with_unterminated("a = 1;\nb = `c\n// Doc\n", js).1,
+        [UnterminatedConstruct {
+            kind: ConstructKind::String,
+            line: 2,
+            delimiter: "`".to_string()
+        }]
+    );
+}
+
+// ### Incremental lexer tests
+#[test]
+fn test_incremental() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let js = llc.map_mode_to_lexer.get(&stringit("javascript")).unwrap();
+    let source = "// Doc 1\n// more\na = `b\n// c`;\n\n/* Doc 2\n   more */\nd = 1; // e\n  // Doc 3\nf();\n";
+
+    // Check that incremental lexing matches lexing the entire file.
+    let check = |from: usize, to: usize, insert: &str| {
+        let edit = SourceEdit {
+            from,
+            to,
+            insert: insert.to_string(),
+        };
+        let source_code = [&source[..from], insert, &source[to..]].concat();
+        assert_eq!(
+            source_lexer_incremental(source, source_lexer(source, js), &edit, js),
+            source_lexer(&source_code, js),
+            "Edit {edit:?}"
+        );
+    };
+
+    // Try inserting and deleting at every position, including edits which
+    // open or close comments and template literals.
+    for from in 0..=source.len() {
+        for insert in ["x", "\n", "// ", "/* ", " */", "`", "\n// z\n"] {
+            check(from, from, insert);
+        }
+        for to in from + 1..=source.len().min(from + 12) {
+            check(from, to, "");
+        }
+    }
+    // Replace most of the file.
+    check(3, source.len() - 3, "q\n/* r */\n");
+}
+
+// ### Tree-sitter lexer tests
+#[cfg(feature = "tree-sitter")]
+#[test]
+fn test_tree_sitter() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    let rust = llc.map_mode_to_lexer.get(&stringit("rust")).unwrap();
+    let js = llc.map_mode_to_lexer.get(&stringit("javascript")).unwrap();
+    let sh = llc.map_mode_to_lexer.get(&stringit("sh")).unwrap();
+
+    // The regex lexer mistakes a character literal of `'"'` for the start of a
+    // string.
+    assert_eq!(
+        source_lexer("let c = '\"';\n// Doc\n", rust),
+        [
+            build_code_block("let c = '\"';\n"),
+            build_doc_block("", "//", "Doc\n")
+        ]
+    );
+    // Likewise, it mistakes the `/*` in a regex literal for a comment.
+    assert_eq!(
+        source_lexer("let r = /\\/*/;\n// Doc\n", js),
+        [
+            build_code_block("let r = /\\/*/;\n"),
+            build_doc_block("", "//", "Doc\n")
+        ]
+    );
+    // Code, including multi-byte characters, is restored after masking.
+    assert_eq!(
+        source_lexer("cat <<EOF\n# Not a comment é\nEOF\n  # Doc\necho é\n", sh),
+        [
+            build_code_block("cat <<EOF\n# Not a comment é\nEOF\n"),
+            build_doc_block("  ", "#", "Doc\n"),
+            build_code_block("echo é\n")
+        ]
+    );
+    // Code which doesn't parse is lexed by the regex lexer.
+    assert_eq!(
+        source_lexer("let c = '\"\n// Not doc\n", rust),
+        [build_code_block("let c = '\"\n// Not doc\n")]
+    );
+}
+
+// ### Lexer trace tests
+#[test]
+fn test_explained() {
+    let llc = compile_lexers(get_language_lexer_vec());
+    // Use a language without a tree-sitter grammar, so that the regex lexer
+    // finds its strings.
+    let js = llc.map_mode_to_lexer.get(&stringit("typescript")).unwrap();
+
+    let (code_doc_blocks, trace) =
+        source_lexer_explained("a = 1; // Code\n// Doc\n/*No space */\ns = \"//\";\n", js);
+    // Tracing doesn't change the results.
+    assert_eq!(
+        code_doc_blocks,
+        source_lexer("a = 1; // Code\n// Doc\n/*No space */\ns = \"//\";\n", js)
+    );
+    assert_eq!(
+        trace
+            .iter()
+            .map(|event| (event.kind, event.range.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (LexerTraceKind::Token, 7..9),
+            (LexerTraceKind::Comment, 7..15),
+            (LexerTraceKind::Token, 15..17),
+            (LexerTraceKind::DocBlock, 15..22),
+            (LexerTraceKind::Token, 22..24),
+            (LexerTraceKind::Comment, 22..35),
+            (LexerTraceKind::Token, 40..41),
+            (LexerTraceKind::String, 40..44),
+        ]
+    );
+    assert_eq!(trace[0].message, "`//` begins an inline comment.");
+    assert_eq!(
+        trace[1].message,
+        "This comment isn't a doc block, since code precedes it on the same line."
+    );
+    assert_eq!(
+        trace[5].message,
+        "This comment isn't a doc block, since its opening delimiter isn't followed by a space or newline."
+    );
+
+    // Languages lexed by a parser don't provide a trace.
+    let python = llc.map_mode_to_lexer.get(&stringit("python")).unwrap();
+    assert_eq!(
+        source_lexer_explained("# Doc\n", python).1[0].kind,
+        LexerTraceKind::Parser
+    );
+}
+
+// ### Stable API tests
+#[test]
+fn test_lex() {
+    let python = language_by_name("python").unwrap();
+    assert_eq!(python.language_lexer.lexer_name.as_str(), "python");
+    assert!(language_by_name("unknown").is_none());
+    assert_eq!(
+        language_for_extension("v", "fn main() {\n}\n")
+            .unwrap()
+            .language_lexer
+            .lexer_name
+            .as_str(),
+        ""
+    );
+    assert!(language_for_extension("unknown", "").is_none());
+
+    assert_eq!(
+        lex("# Doc\na = 1\n", python),
+        [
+            build_doc_block("", "#", "Doc\n"),
+            build_code_block("a = 1\n")
+        ]
+    );
 }
 
 // ### Compiler tests
@@ -740,4 +1057,17 @@ fn test_compiler() {
             .as_str(),
         "verilog"
     );
+
+    // The contents of an assembly file select the architecture's lexer.
+    let s_ext_lexer_arr = llc.map_ext_to_lexer_vec.get(&stringit("s")).unwrap();
+    let lexer_name = |file_contents: &str| {
+        choose_lexer(s_ext_lexer_arr, file_contents)
+            .unwrap()
+            .language_lexer
+            .lexer_name
+            .to_string()
+    };
+    assert_eq!(lexer_name("# x86\nmovl $1, %eax\n"), "gas");
+    assert_eq!(lexer_name(".syntax unified\nmov r0, #1\n"), "gas_arm");
+    assert_eq!(lexer_name("; AVR\nldi r16, 1\n"), "gas_semicolon");
 }
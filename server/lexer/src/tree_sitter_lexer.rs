@@ -0,0 +1,149 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `tree_sitter_lexer.rs` -- Find comments using tree-sitter grammars
+///
+/// The regex lexer finds comments by skipping over strings and string-like
+/// syntax; it can be fooled by syntax its regexes don't describe, such as a
+/// Rust character literal of `'"'`, a JavaScript regex literal of `/\/*/`, or
+/// a shell heredoc in a command substitution. When built with the
+/// `tree-sitter` feature, languages with a grammar listed in `grammar` are
+/// parsed by [tree-sitter](https://tree-sitter.github.io/) instead, which finds
+/// their comments exactly.
+///
+/// This doesn't replace the regex lexer's rules for which comments are doc
+/// blocks. Instead, `mask_code` replaces everything except comments and
+/// whitespace with `x`, so that the regex lexer finds only the comments the
+/// parser found; since this preserves the location of every byte, `unmask`
+/// then restores the original code in the code blocks it produced. Source
+/// code which doesn't parse is lexed by the regex lexer alone.
+// ## Imports
+//
+// ### Standard library
+use std::ops::Range;
+
+// ### Third-party
+use tree_sitter::{Language, Parser, Tree};
+
+// ### Local
+use crate::{CodeDocBlock, LanguageLexer};
+
+// ## Code
+/// Return the tree-sitter grammar used to lex the language with the provided
+/// lexer name, or `None` to use the regex lexer.
+pub fn grammar(lexer_name: &str) -> Option<Language> {
+    Some(
+        match lexer_name {
+            "javascript" => tree_sitter_javascript::LANGUAGE,
+            "rust" => tree_sitter_rust::LANGUAGE,
+            "sh" => tree_sitter_bash::LANGUAGE,
+            _ => return None,
+        }
+        .into(),
+    )
+}
+
+/// Return the provided source code with everything except its comments and
+/// whitespace replaced by `x`, or `None` if this language has no grammar or
+/// the source code doesn't parse.
+pub fn mask_code(source_code: &str, language_lexer: &LanguageLexer) -> Option<String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&grammar(&language_lexer.lexer_name)?)
+        .ok()?;
+    let tree = parser.parse(source_code, None)?;
+    if tree.root_node().has_error() {
+        return None;
+    }
+    let mut masked = source_code.as_bytes().to_vec();
+    let mut index = 0;
+    for comment in comment_ranges(&tree) {
+        // Keep only comments which the regex lexer recognizes, since it would
+        // mistake the contents of others, such as a JavaScript HTML comment,
+        // for code.
+        let comment_text = &source_code[comment.clone()];
+        let is_known_comment = language_lexer
+            .inline_comment_delim_arr
+            .iter()
+            .any(|delim| comment_text.starts_with(delim.as_str()))
+            || language_lexer
+                .block_comment_delim_arr
+                .iter()
+                .any(|delim| comment_text.starts_with(delim.opening.as_str()));
+        if is_known_comment {
+            mask(&mut masked[index..comment.start]);
+            index = comment.end;
+        }
+    }
+    mask(&mut masked[index..]);
+    // Each byte of a multi-byte character outside a comment was replaced, so
+    // this remains valid UTF-8.
+    Some(String::from_utf8(masked).unwrap())
+}
+
+/// Replace the code in blocks produced by lexing the result of `mask_code`
+/// with the provided (original) source code.
+pub fn unmask(source_code: &str, classified_source: Vec<CodeDocBlock>) -> Vec<CodeDocBlock> {
+    let mut index = 0;
+    classified_source
+        .into_iter()
+        .map(|code_doc_block| match code_doc_block {
+            CodeDocBlock::CodeBlock(code) => {
+                let start = index;
+                index += code.len();
+                CodeDocBlock::CodeBlock(source_code[start..index].to_string())
+            }
+            // Doc blocks contain only comments and whitespace, which weren't
+            // masked. Skip the lines they came from.
+            CodeDocBlock::DocBlock(doc_block) => {
+                for _ in 0..doc_block.lines {
+                    index = source_code[index..]
+                        .find('\n')
+                        .map_or(source_code.len(), |newline| index + newline + 1);
+                }
+                CodeDocBlock::DocBlock(doc_block)
+            }
+        })
+        .collect()
+}
+
+// Return the location of each comment in the provided tree, in order.
+fn comment_ranges(tree: &Tree) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        // Don't look inside comments, since some grammars parse their contents
+        // (such as Rust's doc comment markers).
+        if cursor.node().kind().ends_with("comment") {
+            ranges.push(cursor.node().byte_range());
+        } else if cursor.goto_first_child() {
+            continue;
+        }
+        // Move to the next node, returning to parents with no more children.
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    ranges
+}
+
+// Replace everything except whitespace with `x`.
+fn mask(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut().filter(|byte| !byte.is_ascii_whitespace()) {
+        *byte = b'x';
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `wasm.rs` -- Preview a file's translation in the browser
+///
+/// When built with the `wasm` feature (see the builder's `wasm` command), this
+/// module provides a WebAssembly function which translates a source file into
+/// the same structure the Server sends to the Client. The Client uses this to
+/// show an edit immediately, while waiting for the Server's translation.
+///
+/// This is only a preview: the Server's processing of doc blocks (diagrams,
+/// transclusion, citations, macros, and so on) needs the file system and the
+/// project's configuration, so it isn't available here. Each doc block is
+/// instead translated by a plain Markdown renderer. The Client replaces the
+/// preview when the Server's translation arrives.
+// ## Imports
+//
+// ### Standard library
+use std::cmp::max;
+
+// ### Third-party
+use pulldown_cmark::{html, Options, Parser};
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+// ### Local
+use crate::{language_for_extension, lex, CodeDocBlock};
+
+// ## Code
+/// Translate the provided source file, with the given extension (without the
+/// leading period), to JSON in the `CodeChatForWeb` format. Return `null` if
+/// the file's language is unknown.
+#[wasm_bindgen]
+pub fn preview(source_code: &str, ext: &str) -> Option<String> {
+    let language = language_for_extension(ext, source_code)?;
+    let mode = language.language_lexer.lexer_name.as_str();
+    let (doc, doc_blocks) = if mode == "markdown" {
+        (markdown_to_html(source_code), Vec::new())
+    } else {
+        let mut doc = String::new();
+        let mut doc_blocks = Vec::new();
+        for code_doc_block in lex(source_code, language) {
+            match code_doc_block {
+                CodeDocBlock::CodeBlock(code) => doc.push_str(&code),
+                CodeDocBlock::DocBlock(doc_block) => {
+                    // Use the same layout as the Server; see
+                    // `source_to_codechat_for_web`.
+                    let len = doc.chars().count();
+                    doc_blocks.push(json!([
+                        len,
+                        len + max(doc_block.lines, 1) - 1,
+                        doc_block.indent,
+                        doc_block.delimiter,
+                        markdown_to_html(&doc_block.contents),
+                    ]));
+                    doc.push_str(&"\n".repeat(doc_block.lines));
+                }
+            }
+        }
+        (doc, doc_blocks)
+    };
+    Some(
+        json!({
+            "metadata": { "mode": mode },
+            "source": { "doc": doc, "doc_blocks": doc_blocks },
+        })
+        .to_string(),
+    )
+}
+
+// Translate Markdown to HTML, using the extensions the Server enables by
+// default.
+fn markdown_to_html(markdown: &str) -> String {
+    let mut options = Options::all();
+    options.remove(Options::ENABLE_SMART_PUNCTUATION);
+    options.remove(Options::ENABLE_MATH);
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, Parser::new_ext(markdown, options));
+    html_output
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::preview;
+
+    #[test]
+    fn test_preview() {
+        let preview_json: Value =
+            serde_json::from_str(&preview("# A *doc* block.\na = 1\n", "py").unwrap()).unwrap();
+        assert_eq!(
+            preview_json,
+            json!({
+                "metadata": { "mode": "python" },
+                "source": {
+                    "doc": "\na = 1\n",
+                    "doc_blocks": [[0, 0, "", "#", "<p>A <em>doc</em> block.</p>\n"]],
+                },
+            })
+        );
+        assert_eq!(preview("", "no-such-extension"), None);
+    }
+}
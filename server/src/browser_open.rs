@@ -0,0 +1,173 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `browser_open.rs` -- Open the Server's pages in a web browser
+///
+/// Opening a web browser depends on where the Server runs:
+///
+/// - The `BROWSER` environment variable, if set, names the programs to try,
+///   separated by `:` (`;` on Windows), as
+///   [Python does](https://docs.python.org/3/library/webbrowser.html). In each,
+///   `%s` is replaced by the URL; otherwise, the URL is added as the last
+///   argument.
+/// - In [GitHub Codespaces](https://github.com/features/codespaces), the
+///   browser runs on another machine, so it must use the URL which Codespaces
+///   forwards to the Server's port. Codespaces also sets `BROWSER` to a program
+///   which opens the user's browser.
+/// - In the [Windows Subsystem for Linux](https://learn.microsoft.com/windows/wsl/),
+///   the browser runs in Windows; `wslview`, if installed, or else Windows'
+///   `explorer.exe` opens it.
+/// - Otherwise, the OS's default browser opens the URL.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    env, fs,
+    process::{Command, Stdio},
+};
+
+// ### Third-party
+use log::warn;
+
+// ### Local
+use crate::webserver::IP_ADDRESS;
+
+// ## Code
+/// Return the URL a browser uses to load `path` from the Server listening on
+/// `port`.
+pub fn server_url(port: u16, path: &str) -> String {
+    server_url_with_env(port, path, |name| env::var(name).ok())
+}
+
+/// Open the provided URL in a web browser.
+pub fn open_url(url: &str) -> Result<(), String> {
+    if let Ok(browser) = env::var("BROWSER") {
+        for args in browser_commands(&browser, url) {
+            match spawn(&args) {
+                Ok(()) => return Ok(()),
+                Err(err) => warn!("Unable to open a browser using {args:?}: {err}"),
+            }
+        }
+    }
+    if is_wsl() {
+        for args in [
+            vec!["wslview".to_string(), url.to_string()],
+            vec!["explorer.exe".to_string(), url.to_string()],
+        ] {
+            if spawn(&args).is_ok() {
+                return Ok(());
+            }
+        }
+    }
+    open::that_detached(url).map_err(|err| format!("Unable to open web browser: {err}"))
+}
+
+// Implement `server_url`, looking up environment variables using `var`.
+fn server_url_with_env(port: u16, path: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    // Codespaces forwards each port to a subdomain of its forwarding domain.
+    if var("CODESPACES").as_deref() == Some("true") {
+        if let (Some(codespace_name), Some(domain)) = (
+            var("CODESPACE_NAME"),
+            var("GITHUB_CODESPACES_PORT_FORWARDING_DOMAIN"),
+        ) {
+            return format!("https://{codespace_name}-{port}.{domain}{path}");
+        }
+    }
+    format!("http://{IP_ADDRESS}:{port}{path}")
+}
+
+// Return the commands, each a list of arguments, given by the `BROWSER`
+// environment variable to open `url`.
+fn browser_commands(browser: &str, url: &str) -> Vec<Vec<String>> {
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    browser
+        .split(separator)
+        .map(|command| {
+            let mut args: Vec<String> = command
+                .split_whitespace()
+                .map(|arg| arg.replace("%s", url))
+                .collect();
+            if !command.contains("%s") {
+                args.push(url.to_string());
+            }
+            args
+        })
+        // Skip empty entries, which contain only the URL.
+        .filter(|args| args.len() > 1)
+        .collect()
+}
+
+// Return true if running in the Windows Subsystem for Linux.
+fn is_wsl() -> bool {
+    cfg!(target_os = "linux")
+        && (env::var_os("WSL_DISTRO_NAME").is_some()
+            || fs::read_to_string("/proc/sys/kernel/osrelease")
+                .is_ok_and(|release| release.to_lowercase().contains("microsoft")))
+}
+
+// Start the provided command without waiting for it to finish.
+fn spawn(args: &[String]) -> Result<(), String> {
+    let (program, args) = args.split_first().ok_or("No command provided.")?;
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{browser_commands, server_url_with_env};
+
+    #[test]
+    fn test_server_url() {
+        assert_eq!(
+            server_url_with_env(8080, "/vsc/cf/1", |_| None),
+            "http://127.0.0.1:8080/vsc/cf/1"
+        );
+        let codespaces = |name: &str| match name {
+            "CODESPACES" => Some("true".to_string()),
+            "CODESPACE_NAME" => Some("fluffy-space-abc123".to_string()),
+            "GITHUB_CODESPACES_PORT_FORWARDING_DOMAIN" => Some("app.github.dev".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            server_url_with_env(8080, "/vsc/cf/1", codespaces),
+            "https://fluffy-space-abc123-8080.app.github.dev/vsc/cf/1"
+        );
+        // Without a forwarding domain, use the local URL.
+        assert_eq!(
+            server_url_with_env(8080, "/", |name| (name == "CODESPACES")
+                .then(|| "true".to_string())),
+            "http://127.0.0.1:8080/"
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_browser_commands() {
+        let url = "http://127.0.0.1:8080/";
+        assert_eq!(browser_commands("firefox", url), vec![vec!["firefox", url]]);
+        assert_eq!(
+            browser_commands("lynx -dump %s:w3m::", url),
+            vec![vec!["lynx", "-dump", url], vec!["w3m", url]]
+        );
+        assert!(browser_commands("", url).is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `coverage.rs` -- Measure how much of a project is documented
+///
+/// The `coverage` command reports, for each file linked to by a project's
+/// table of contents and for the project as a whole:
+///
+/// - The number of lines in doc blocks and the number of (non-blank) lines of
+///   code, along with the ratio of the two.
+/// - The public symbols, such as functions and types, which lack docs: a doc
+///   block ending on the line before the symbol, or before its attributes or
+///   decorators.
+///
+/// It also lists the files which contain code but no doc blocks. Public
+/// symbols are found by matching each line against patterns for common
+/// declarations in C#, Go, Java, JavaScript, Python, Rust, Swift, and
+/// TypeScript; other languages report no symbols.
+///
+/// The report is JSON. For a badge, the command instead produces the JSON
+/// expected by a [Shields.io endpoint](https://shields.io/badges/endpoint-badge)
+/// showing the percentage of public symbols which are documented (or, for a
+/// project with no public symbols, the percentage of lines which are doc
+/// blocks).
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+
+// ### Local
+use crate::processing::{
+    doc_blocks_with_lines, find_file_lexer, find_path_to_toc, project_config::toc_files,
+    project_config_for_file,
+};
+
+// ## Globals
+lazy_static! {
+    /// Patterns matching the declaration of a public symbol, indexed by the
+    /// name of a lexer. The first group which matches is the symbol's name.
+    static ref PUBLIC_SYMBOL_REGEXES: Vec<(&'static str, Regex)> = [
+        (
+            "rust",
+            r#"^\s*pub\s+(?:(?:async|const|unsafe|extern\s+"[^"]*")\s+)*(?:fn|struct|enum|trait|type|const|static|mod|union)\s+([A-Za-z_]\w*)"#,
+        ),
+        // Names beginning with an underscore are private by convention.
+        ("python", r"^\s*(?:async\s+)?(?:def|class)\s+([A-Za-z]\w*)"),
+        (
+            "javascript",
+            r"^\s*export\s+(?:default\s+)?(?:async\s+)?(?:function\*?|class|const|let|var)\s+([A-Za-z_$][\w$]*)",
+        ),
+        (
+            "typescript",
+            r"^\s*export\s+(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function\*?|class|const|let|var|interface|type|enum)\s+([A-Za-z_$][\w$]*)",
+        ),
+        // Exported names begin with a capital letter.
+        (
+            "golang",
+            r"^(?:func\s+(?:\([^)]*\)\s*)?|type\s+|var\s+|const\s+)([A-Z]\w*)",
+        ),
+        (
+            "java",
+            r"^\s*public\s+(?:(?:static|final|abstract|sealed|synchronized)\s+)*(?:(?:class|interface|enum|record|@interface)\s+([A-Za-z_]\w*)|[\w<>\[\],.? ]+?\s+([A-Za-z_]\w*)\s*\()",
+        ),
+        (
+            "csharp",
+            r"^\s*public\s+(?:(?:static|sealed|abstract|partial|async|override|virtual|readonly|unsafe)\s+)*(?:(?:class|interface|enum|record|struct)\s+([A-Za-z_]\w*)|[\w<>\[\],.? ]+?\s+([A-Za-z_]\w*)\s*[({])",
+        ),
+        (
+            "swift",
+            r"^\s*(?:public|open)\s+(?:\w+\s+)*?(?:func|class|struct|enum|protocol|var|let|typealias)\s+([A-Za-z_]\w*)",
+        ),
+    ]
+    .into_iter()
+    .map(|(lexer_name, regex)| (lexer_name, Regex::new(regex).unwrap()))
+    .collect();
+}
+
+// ## Data structures
+/// The formats a coverage report may be written in.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum CoverageFormat {
+    /// The full report, as JSON.
+    Json,
+    /// A Shields.io endpoint badge, as JSON.
+    Badge,
+}
+
+/// A public symbol lacking docs.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    /// The line declaring the symbol, numbered from 1.
+    pub line: usize,
+}
+
+/// The documentation coverage of one file.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct FileCoverage {
+    /// The file's path, relative to the project's root.
+    pub path: String,
+    pub doc_lines: usize,
+    pub code_lines: usize,
+    /// `doc_lines / code_lines`, or `None` if there's no code.
+    pub doc_to_code_ratio: Option<f64>,
+    pub public_symbols: usize,
+    pub undocumented_symbols: Vec<Symbol>,
+}
+
+/// The documentation coverage of a project.
+#[derive(Debug, Default, Serialize)]
+pub struct Coverage {
+    pub doc_lines: usize,
+    pub code_lines: usize,
+    pub doc_to_code_ratio: Option<f64>,
+    pub public_symbols: usize,
+    pub documented_symbols: usize,
+    /// The files containing code but no doc blocks.
+    pub files_without_docs: Vec<String>,
+    pub files: Vec<FileCoverage>,
+}
+
+// ## Code
+/// Measure the documentation coverage of the project whose table of contents
+/// is `toc_path`.
+pub fn project_coverage(toc_path: &Path) -> Result<Coverage, String> {
+    let toc_path = toc_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to find {toc_path:?}: {err}."))?;
+    let project_root = toc_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut files = Vec::new();
+    for file_path in toc_files(&toc_path)? {
+        // The TOC isn't part of the project's documentation.
+        if file_path == toc_path {
+            continue;
+        }
+        // Skip files the CodeChat Editor doesn't support, such as images.
+        let Ok(file_contents) = fs::read_to_string(&file_path) else {
+            continue;
+        };
+        let relative_path = file_path
+            .strip_prefix(&project_root)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| PathBuf::from(&file_path));
+        if let Some(file_coverage) = file_coverage(
+            &file_contents,
+            &file_path,
+            &relative_path.to_string_lossy().replace('\\', "/"),
+        ) {
+            files.push(file_coverage);
+        }
+    }
+    Ok(summarize(files))
+}
+
+/// Measure the documentation coverage of one file, reported as `path`. Return
+/// `None` if the CodeChat Editor doesn't support this file.
+pub fn file_coverage(file_contents: &str, file_path: &Path, path: &str) -> Option<FileCoverage> {
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let lexer = find_file_lexer(file_contents, file_path, &project_config).ok()?;
+    let doc_blocks = doc_blocks_with_lines(file_contents, lexer);
+
+    // Find the lines (numbered from 1) in doc blocks, and the last line of
+    // each doc block.
+    let mut doc_lines = HashSet::new();
+    let mut doc_block_ends = HashSet::new();
+    for (start_line, doc_block) in &doc_blocks {
+        let lines = doc_block.lines.max(1);
+        doc_lines.extend(*start_line..start_line + lines);
+        doc_block_ends.insert(start_line + lines - 1);
+    }
+    let lines: Vec<&str> = file_contents.lines().collect();
+    let code_lines = lines
+        .iter()
+        .enumerate()
+        .filter(|(index, line)| !doc_lines.contains(&(index + 1)) && !line.trim().is_empty())
+        .count();
+
+    let symbol_regex = PUBLIC_SYMBOL_REGEXES
+        .iter()
+        .find(|(lexer_name, _)| *lexer_name == lexer.language_lexer.lexer_name.as_str())
+        .map(|(_, regex)| regex);
+    let mut public_symbols = 0;
+    let mut undocumented_symbols = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        if doc_lines.contains(&line_number) {
+            continue;
+        }
+        let Some(name) = symbol_regex
+            .and_then(|regex| regex.captures(line))
+            .and_then(|captures| captures.iter().skip(1).flatten().next())
+        else {
+            continue;
+        };
+        public_symbols += 1;
+        if !is_documented(&lines, line_number, &doc_block_ends) {
+            undocumented_symbols.push(Symbol {
+                name: name.as_str().to_string(),
+                line: line_number,
+            });
+        }
+    }
+
+    Some(FileCoverage {
+        path: path.to_string(),
+        doc_lines: doc_lines.len(),
+        code_lines,
+        doc_to_code_ratio: ratio(doc_lines.len(), code_lines),
+        public_symbols,
+        undocumented_symbols,
+    })
+}
+
+/// Return the badge for a coverage report, as the JSON expected by a
+/// Shields.io endpoint.
+pub fn coverage_badge(coverage: &Coverage) -> String {
+    let percent = (100 * coverage.documented_symbols)
+        .checked_div(coverage.public_symbols)
+        .unwrap_or_else(|| {
+            let total = coverage.doc_lines + coverage.code_lines;
+            (100 * coverage.doc_lines).checked_div(total).unwrap_or(100)
+        });
+    let color = match percent {
+        90.. => "brightgreen",
+        75.. => "green",
+        50.. => "yellow",
+        _ => "red",
+    };
+    json!({
+        "schemaVersion": 1,
+        "label": "doc coverage",
+        "message": format!("{percent}%"),
+        "color": color,
+    })
+    .to_string()
+}
+
+// Combine the coverage of each file into the project's coverage.
+fn summarize(files: Vec<FileCoverage>) -> Coverage {
+    let doc_lines = files.iter().map(|file| file.doc_lines).sum();
+    let code_lines = files.iter().map(|file| file.code_lines).sum();
+    let public_symbols = files.iter().map(|file| file.public_symbols).sum();
+    let undocumented_symbols: usize = files
+        .iter()
+        .map(|file| file.undocumented_symbols.len())
+        .sum();
+    Coverage {
+        doc_lines,
+        code_lines,
+        doc_to_code_ratio: ratio(doc_lines, code_lines),
+        public_symbols,
+        documented_symbols: public_symbols - undocumented_symbols,
+        files_without_docs: files
+            .iter()
+            .filter(|file| file.doc_lines == 0 && file.code_lines > 0)
+            .map(|file| file.path.clone())
+            .collect(),
+        files,
+    }
+}
+
+// Return true if a doc block ends on the line before `line_number`, skipping
+// any attributes (`#[...]`, `[...]`) or decorators (`@...`) between them.
+fn is_documented(lines: &[&str], line_number: usize, doc_block_ends: &HashSet<usize>) -> bool {
+    let mut previous = line_number - 1;
+    while previous > 0 && !doc_block_ends.contains(&previous) {
+        let line = lines[previous - 1].trim_start();
+        if !(line.starts_with("#[") || line.starts_with('@') || line.starts_with('[')) {
+            return false;
+        }
+        previous -= 1;
+    }
+    previous > 0
+}
+
+fn ratio(doc_lines: usize, code_lines: usize) -> Option<f64> {
+    (code_lines > 0).then(|| doc_lines as f64 / code_lines as f64)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use assert_fs::TempDir;
+    use indoc::indoc;
+
+    use super::{coverage_badge, file_coverage, project_coverage, Coverage, Symbol};
+
+    #[test]
+    fn test_file_coverage() {
+        let coverage = file_coverage(
+            indoc!(
+                "
+                // Documented.
+                #[derive(Debug)]
+                pub struct A;
+
+                pub fn undocumented() {}
+                // Private functions don't need docs.
+                fn private() {}
+                "
+            ),
+            Path::new("a.rs"),
+            "a.rs",
+        )
+        .unwrap();
+        assert_eq!(coverage.doc_lines, 2);
+        assert_eq!(coverage.code_lines, 4);
+        assert_eq!(coverage.doc_to_code_ratio, Some(0.5));
+        assert_eq!(coverage.public_symbols, 2);
+        assert_eq!(
+            coverage.undocumented_symbols,
+            vec![Symbol {
+                name: "undocumented".to_string(),
+                line: 5
+            }]
+        );
+
+        let coverage = file_coverage(
+            "@decorator\ndef f():\n    pass\n\nclass _Private:\n    pass\n",
+            Path::new("a.py"),
+            "a.py",
+        )
+        .unwrap();
+        assert_eq!(coverage.public_symbols, 1);
+        assert_eq!(coverage.undocumented_symbols.len(), 1);
+
+        // Unsupported files have no coverage.
+        assert!(file_coverage("data", Path::new("a.unknown"), "a.unknown").is_none());
+    }
+
+    #[test]
+    fn test_project_coverage() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("toc.md"),
+            "[Intro](intro.md)\n[Code](code.py)\n[Bare](bare.py)\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("intro.md"), "# Intro\n").unwrap();
+        fs::write(
+            temp_dir.path().join("code.py"),
+            "# Documented.\ndef f():\n    pass\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("bare.py"), "def g():\n    pass\n").unwrap();
+        let coverage = project_coverage(&temp_dir.path().join("toc.md")).unwrap();
+        assert_eq!(coverage.files.len(), 3);
+        assert_eq!(coverage.doc_lines, 2);
+        assert_eq!(coverage.code_lines, 4);
+        assert_eq!(coverage.public_symbols, 2);
+        assert_eq!(coverage.documented_symbols, 1);
+        assert_eq!(coverage.files_without_docs, vec!["bare.py".to_string()]);
+        assert_eq!(
+            coverage_badge(&coverage),
+            r#"{"color":"yellow","label":"doc coverage","message":"50%","schemaVersion":1}"#
+        );
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_coverage_badge() {
+        // Without public symbols, use the share of lines which are docs.
+        let coverage = Coverage {
+            doc_lines: 9,
+            code_lines: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            coverage_badge(&coverage),
+            r#"{"color":"brightgreen","label":"doc coverage","message":"90%","schemaVersion":1}"#
+        );
+        assert!(coverage_badge(&Coverage::default()).contains("100%"));
+    }
+}
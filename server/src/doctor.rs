@@ -0,0 +1,294 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `doctor.rs` -- Diagnose problems running the CodeChat Editor
+///
+/// The `doctor` command checks the environment the Server runs in, reporting
+/// each problem found along with how to fix it:
+///
+/// - The files installed with the Server, such as the Client's static files,
+///   are present.
+/// - The port the Server uses is available, or in use by a CodeChat Editor
+///   Server.
+/// - A program which opens a web browser is available.
+/// - The VSCode extension, if installed, matches the Server's version.
+/// - The project directory is writable, since the CodeChat Editor saves files,
+///   caches, and anchors there.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+// ### Local
+use crate::webserver::{check_installed_files, ping_server, IP_ADDRESS};
+
+// ## Globals
+/// The VSCode extension's identifier.
+const VSCODE_EXTENSION_ID: &str = "codechat.codechat-editor-client";
+
+// ## Data structures
+/// The outcome of a check.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Ok,
+    /// Something may not work.
+    Warning,
+    /// Something won't work.
+    Error,
+}
+
+/// The result of checking one part of the environment.
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    /// What was checked.
+    pub name: &'static str,
+    pub status: Status,
+    /// What was found.
+    pub message: String,
+    /// How to fix a problem which was found.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: String) -> Self {
+        Check {
+            name,
+            status: Status::Ok,
+            message,
+            fix: None,
+        }
+    }
+
+    fn problem(name: &'static str, status: Status, message: String, fix: String) -> Self {
+        Check {
+            name,
+            status,
+            message,
+            fix: Some(fix),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+        };
+        write!(f, "[{status}] {}: {}", self.name, self.message)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n    Fix: {fix}")?;
+        }
+        Ok(())
+    }
+}
+
+// ## Code
+/// Run all checks, for a Server using `port` to edit the project in
+/// `project_dir`.
+pub fn run_checks(port: u16, project_dir: &Path) -> Vec<Check> {
+    vec![
+        check_files(),
+        check_port(port),
+        check_browser(),
+        check_vscode_extension(),
+        check_project_writable(project_dir),
+    ]
+}
+
+fn check_files() -> Check {
+    const NAME: &str = "Installed files";
+    match check_installed_files() {
+        Ok(()) => Check::ok(NAME, "All files are present.".to_string()),
+        Err(err) => Check::problem(
+            NAME,
+            Status::Error,
+            err,
+            "Reinstall the CodeChat Editor Server. When developing, run `./bt build` in the `server/` directory.".to_string(),
+        ),
+    }
+}
+
+fn check_port(port: u16) -> Check {
+    const NAME: &str = "Port";
+    if TcpListener::bind((IP_ADDRESS, port)).is_ok() {
+        return Check::ok(NAME, format!("Port {port} is available."));
+    }
+    // See if the CodeChat Editor Server is using this port, as in `Start`.
+    let is_server = ping_server(port, 1).is_ok();
+    if is_server {
+        Check::ok(
+            NAME,
+            format!("A CodeChat Editor Server is already running on port {port}."),
+        )
+    } else {
+        Check::problem(
+            NAME,
+            Status::Error,
+            format!("Port {port} is in use by another program."),
+            "Stop the program using this port, or choose another port using `--port`; in VSCode, also change the CodeChat Editor Server Port setting.".to_string(),
+        )
+    }
+}
+
+fn check_browser() -> Check {
+    const NAME: &str = "Web browser";
+    // See [`browser_open`](browser_open.rs).
+    if let Ok(browser) = env::var("BROWSER") {
+        return Check::ok(
+            NAME,
+            format!("Browsers are opened using the BROWSER environment variable, {browser}."),
+        );
+    }
+    let programs: Vec<_> = open::commands("http://localhost")
+        .iter()
+        .map(|command| command.get_program().to_os_string())
+        .collect();
+    match programs.iter().find_map(|program| find_program(program)) {
+        Some(path) => Check::ok(
+            NAME,
+            format!("Browsers can be opened using {}.", path.display()),
+        ),
+        None => Check::problem(
+            NAME,
+            Status::Warning,
+            "No program to open a web browser was found.".to_string(),
+            "Install a web browser (on Linux, also install `xdg-utils`), or open the URL the Server prints manually. In VSCode, set the Client Location setting to show the Client in a VSCode panel.".to_string(),
+        ),
+    }
+}
+
+fn check_vscode_extension() -> Check {
+    const NAME: &str = "VSCode extension";
+    let code = if cfg!(windows) { "code.cmd" } else { "code" };
+    let output = match Command::new(code)
+        .args(["--list-extensions", "--show-versions"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            return Check::ok(
+                NAME,
+                "VSCode's `code` command wasn't found; skipping this check.".to_string(),
+            )
+        }
+    };
+    let server_version = env!("CARGO_PKG_VERSION");
+    match extension_version(&String::from_utf8_lossy(&output.stdout)) {
+        None => Check::ok(NAME, "The extension isn't installed.".to_string()),
+        Some(version) if version == server_version => {
+            Check::ok(NAME, format!("Version {version} matches the Server."))
+        }
+        Some(version) => Check::problem(
+            NAME,
+            Status::Warning,
+            format!("The extension's version {version} doesn't match the Server's version {server_version}."),
+            "Update the extension and the Server to the same version. The extension uses the Server it includes unless its Server Command setting is set.".to_string(),
+        ),
+    }
+}
+
+// Return the version of the CodeChat Editor extension in the output of `code
+// --list-extensions --show-versions`, which lists one `id@version` per line.
+fn extension_version(extensions: &str) -> Option<String> {
+    extensions.lines().find_map(|line| {
+        let (id, version) = line.trim().split_once('@')?;
+        id.eq_ignore_ascii_case(VSCODE_EXTENSION_ID)
+            .then(|| version.to_string())
+    })
+}
+
+fn check_project_writable(project_dir: &Path) -> Check {
+    const NAME: &str = "Project";
+    let test_path = project_dir.join(format!(".codechat-doctor-{}", process::id()));
+    match fs::write(&test_path, "") {
+        Ok(()) => {
+            let _ = fs::remove_file(&test_path);
+            Check::ok(NAME, format!("{} is writable.", project_dir.display()))
+        }
+        Err(err) => Check::problem(
+            NAME,
+            Status::Error,
+            format!("Unable to write to {}: {err}.", project_dir.display()),
+            "Change this directory's permissions, or edit a project you can write to; the CodeChat Editor saves files, caches, and anchors in the project.".to_string(),
+        ),
+    }
+}
+
+// Return the path to the provided program, looking in the `PATH` if it's not
+// a path.
+fn find_program(program: &OsStr) -> Option<PathBuf> {
+    let program = Path::new(program);
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        let path = dir.join(program);
+        if path.is_file() {
+            return Some(path);
+        }
+        let path = path.with_extension("exe");
+        (cfg!(windows) && path.is_file()).then_some(path)
+    })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, fs};
+
+    use assert_fs::TempDir;
+
+    use super::{check_project_writable, extension_version, find_program, Status};
+
+    #[test]
+    fn test_extension_version() {
+        assert_eq!(
+            extension_version("ms-python.python@2024.1.0\nCodeChat.codechat-editor-client@0.1.6\n"),
+            Some("0.1.6".to_string())
+        );
+        assert_eq!(extension_version("ms-python.python@2024.1.0\n"), None);
+        assert_eq!(extension_version(""), None);
+    }
+
+    #[test]
+    fn test_check_project_writable() {
+        let temp_dir = TempDir::new().unwrap();
+        let check = check_project_writable(temp_dir.path());
+        assert_eq!(check.status, Status::Ok);
+        // The test file is removed.
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        let check = check_project_writable(&temp_dir.path().join("missing"));
+        assert_eq!(check.status, Status::Error);
+        assert!(check.fix.is_some());
+        temp_dir.close().unwrap();
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_find_program() {
+        assert!(find_program(OsStr::new("sh")).is_some());
+        assert!(find_program(OsStr::new("/bin/sh")).is_some());
+        assert!(find_program(OsStr::new("no-such-program-codechat")).is_none());
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `export.rs` -- Export a project to a single document
+///
+/// An export combines every file linked to by a project's table of contents,
+/// in the order they're linked, into one HTML document: doc blocks become their
+/// rendered HTML, while code blocks become `<pre><code>` elements. This HTML is
+/// then converted to the requested format by [Pandoc](https://pandoc.org/),
+/// which must be installed. Pandoc maps the document's structure (headings,
+/// lists, tables, code, etc.) to the styles of the output format; for DOCX, a
+/// project may provide a reference document which defines these styles.
+/// Alternatively, an HTML export writes a web page which may be read offline;
+/// see `offline.rs`, while a PDF export prints the HTML using Chromium; see
+/// `pdf.rs`.
+// ## Submodules
+mod offline;
+mod pdf;
+
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+// ### Third-party
+use clap::ValueEnum;
+use indoc::formatdoc;
+
+// ### Local
+use crate::processing::{
+    escape::{escape, EscapeContext},
+    project_config::{load_project_config, toc_files, RenderTarget},
+    quiz::move_quiz_answers,
+    source_to_codechat_for_web_string, CodeChatForWeb, TranslationResultsString,
+};
+
+// ## Data structures
+/// The formats a project may be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// A Microsoft Word document.
+    Docx,
+    /// A directory containing a web page, which may be installed as a
+    /// progressive web app and read offline.
+    Html,
+    /// A PDF, printed by Chromium or Chrome.
+    Pdf,
+}
+
+// ## Code
+/// Export the project whose table of contents is `toc_path` to `output_path`,
+/// in the provided format.
+pub fn export_project(
+    toc_path: &Path,
+    output_path: &Path,
+    format: ExportFormat,
+) -> Result<(), String> {
+    match format {
+        ExportFormat::Docx => export_docx(toc_path, output_path),
+        ExportFormat::Html => offline::export_offline(toc_path, output_path),
+        ExportFormat::Pdf => pdf::export_pdf(toc_path, output_path),
+    }
+}
+
+/// Export a single file to `output_path`, in the provided format. Only a whole
+/// project may be exported to HTML.
+pub fn export_file(
+    file_path: &Path,
+    output_path: &Path,
+    format: ExportFormat,
+) -> Result<(), String> {
+    match format {
+        ExportFormat::Pdf => pdf::export_file_pdf(file_path, output_path),
+        _ => Err(format!(
+            "A single file may only be exported to PDF, not {format:?}."
+        )),
+    }
+}
+
+// Export a project to a DOCX file using Pandoc.
+fn export_docx(toc_path: &Path, output_path: &Path) -> Result<(), String> {
+    let project_root = toc_path.parent().unwrap_or_else(|| Path::new(""));
+    let project_config = load_project_config(project_root)?;
+    let html = project_to_html(toc_path)?;
+
+    let mut pandoc_args = vec![
+        "--from".to_string(),
+        "html".to_string(),
+        "--to".to_string(),
+        "docx".to_string(),
+        "--output".to_string(),
+        output_path.to_string_lossy().to_string(),
+        // Find images and other resources relative to the project's root.
+        "--resource-path".to_string(),
+        project_root.to_string_lossy().to_string(),
+    ];
+    if let Some(reference_doc) = &project_config.export.docx_reference_doc {
+        pandoc_args.push("--reference-doc".to_string());
+        pandoc_args.push(
+            project_root
+                .join(reference_doc)
+                .to_string_lossy()
+                .to_string(),
+        );
+    }
+    run_pandoc(&pandoc_args, &html)
+}
+
+/// Combine all files in the project whose table of contents is `toc_path`
+/// into a single HTML document.
+pub fn project_to_html(toc_path: &Path) -> Result<String, String> {
+    let (title, body) = project_body(toc_path)?;
+    Ok(html_document(&title, "", &body))
+}
+
+// Return the title of the project whose table of contents is `toc_path`,
+// along with the HTML of all its files.
+fn project_body(toc_path: &Path) -> Result<(String, String), String> {
+    let toc_path = toc_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to find {toc_path:?}: {err}."))?;
+    let mut body = String::new();
+    for file_path in toc_files(&toc_path)? {
+        // Don't include the TOC in the export.
+        if file_path == toc_path {
+            continue;
+        }
+        body.push_str(&file_to_html(&file_path)?);
+    }
+    let body = move_quiz_answers(&body);
+    let title = toc_path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok((title, body))
+}
+
+// Wrap `body` in an HTML document; `head` contains any additional elements
+// for its `<head>`, each on its own line.
+fn html_document(title: &str, head: &str, body: &str) -> String {
+    formatdoc!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="utf-8">
+                <title>{}</title>
+        {head}    </head>
+            <body>
+        {body}
+            </body>
+        </html>
+        "#,
+        escape(title, EscapeContext::Text)
+    )
+}
+
+/// Translate one file in a project to HTML, wrapped in a `<section>`.
+pub fn file_to_html(file_path: &Path) -> Result<String, String> {
+    let file_contents = fs::read_to_string(file_path)
+        .map_err(|err| format!("Unable to read {file_path:?}: {err}."))?;
+    let html = match source_to_codechat_for_web_string(
+        &file_contents,
+        file_path,
+        false,
+        RenderTarget::Export,
+    )
+    .0
+    {
+        TranslationResultsString::CodeChat(codechat_for_web) => {
+            codechat_for_web_to_html(&codechat_for_web)
+        }
+        // Include files the CodeChat Editor doesn't support as plain text.
+        TranslationResultsString::Unknown => code_to_html(&file_contents, None),
+        TranslationResultsString::Err(err) => {
+            return Err(format!("Unable to translate {file_path:?}: {err}"))
+        }
+        TranslationResultsString::Toc(html) => html,
+    };
+    Ok(format!(
+        "<section class=\"CodeChat-export-file\" data-path=\"{}\">\n{html}</section>\n",
+        escape(&file_path.to_string_lossy(), EscapeContext::Attribute)
+    ))
+}
+
+/// Interleave the code and doc blocks of a translated file as HTML.
+pub fn codechat_for_web_to_html(codechat_for_web: &CodeChatForWeb) -> String {
+    let source = &codechat_for_web.source;
+    let mode = &codechat_for_web.metadata.mode;
+    // Document-only files are already HTML.
+    if mode == "markdown" {
+        return source.doc.clone();
+    }
+
+    let doc: Vec<char> = source.doc.chars().collect();
+    let mut html = String::new();
+    let mut index = 0;
+    for (from, to, _indent, _delimiter, contents) in &source.doc_blocks {
+        html.push_str(&code_to_html(
+            &doc[index..*from].iter().collect::<String>(),
+            Some(mode),
+        ));
+        html.push_str(contents);
+        // Skip the newlines which stand in for this doc block in the code.
+        // A doc block of zero or one lines occupies the same range, so stop at
+        // the first character which isn't a newline.
+        index = *from;
+        while index <= *to && index < doc.len() && doc[index] == '\n' {
+            index += 1;
+        }
+    }
+    html.push_str(&code_to_html(
+        &doc[index.min(doc.len())..].iter().collect::<String>(),
+        Some(mode),
+    ));
+    html
+}
+
+/// Format a block of code as HTML, omitting code which is only whitespace.
+fn code_to_html(code: &str, mode: Option<&str>) -> String {
+    if code.trim().is_empty() {
+        return String::new();
+    }
+    let class = match mode {
+        Some(mode) => format!(
+            " class=\"language-{}\"",
+            escape(mode, EscapeContext::Attribute)
+        ),
+        None => String::new(),
+    };
+    format!(
+        "<pre><code{class}>{}</code></pre>\n",
+        escape(code.trim_end_matches('\n'), EscapeContext::Code)
+    )
+}
+
+/// Run Pandoc with the provided arguments, passing it `input` on stdin.
+fn run_pandoc(args: &[String], input: &str) -> Result<(), String> {
+    let mut process = Command::new("pandoc")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Unable to run pandoc; is it installed? {err}"))?;
+    process
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .map_err(|err| format!("Unable to send the document to pandoc: {err}"))?;
+    let output = process
+        .wait_with_output()
+        .map_err(|err| format!("Error running pandoc: {err}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "pandoc failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{codechat_for_web_to_html, project_to_html};
+    use crate::prep_test_dir;
+    use crate::processing::{CodeChatForWeb, CodeMirror, SourceFileMetadata};
+
+    #[test]
+    fn test_codechat_for_web_to_html() {
+        let codechat_for_web = CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "python".to_string(),
+                notebook: None,
+            },
+            source: CodeMirror {
+                doc: "a = 1 < 2\n\n\nb = 2\n\n".to_string(),
+                doc_blocks: vec![
+                    (
+                        10,
+                        11,
+                        "".to_string(),
+                        "#".to_string(),
+                        "<p>Two</p>\n".to_string(),
+                    ),
+                    (
+                        18,
+                        18,
+                        "".to_string(),
+                        "#".to_string(),
+                        "<p>End</p>\n".to_string(),
+                    ),
+                ],
+            },
+        };
+        assert_eq!(
+            codechat_for_web_to_html(&codechat_for_web),
+            "<pre><code class=\"language-python\">a = 1 &lt; 2</code></pre>\n<p>Two</p>\n<pre><code class=\"language-python\">b = 2</code></pre>\n<p>End</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_project_to_html() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let html = project_to_html(&test_dir.join("toc.md")).unwrap();
+        // Files appear in the order given by the TOC.
+        let intro_index = html.find("<h1 id=\"intro\"").unwrap();
+        let code_index = html.find("<p>A doc block.</p>").unwrap();
+        assert!(intro_index < code_index);
+        assert!(html.contains("<pre><code class=\"language-python\">x = 1</code></pre>"));
+        // The TOC itself isn't included.
+        assert!(!html.contains("<a href=\"intro.md\">"));
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,515 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `offline.rs` -- Export a project as a web page which works offline
+///
+/// An HTML export writes a directory containing the project's combined
+/// document (`index.html`), its table of contents (`toc.html`, whose links
+/// lead to each file's section of `index.html`), and the images and other
+/// files these pages show. It also writes a
+/// [web app manifest](https://developer.mozilla.org/en-US/docs/Web/Progressive_web_apps/Manifest)
+/// and a
+/// [service worker](https://developer.mozilla.org/en-US/docs/Web/API/Service_Worker_API)
+/// which caches all these files when the export is first viewed, so that it may
+/// be installed as a progressive web app and read offline. Browsers only run
+/// service workers for pages served over HTTPS or from `localhost`; an export
+/// opened directly from disk still works, but only while its files are
+/// available.
+///
+/// To help search engines and sites which preview links, each page describes
+/// itself using the text of its first paragraph, along with
+/// [OpenGraph](https://ogp.me/) properties. If the project's configuration
+/// provides the URL the export is published at, each page also names its
+/// canonical URL, and the export includes a
+/// [sitemap](https://www.sitemaps.org/protocol.html). The configuration may
+/// also provide an analytics snippet, which is added to each page of the export
+/// (but never to the Client).
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+// ### Third-party
+use indoc::indoc;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_json::json;
+
+// ### Local
+use super::{file_to_html, html_document, project_body};
+use crate::processing::{
+    escape::{escape, unescape, EscapeContext},
+    project_config::{load_project_config, toc_files},
+};
+
+// ## Globals
+/// The name of the web app manifest.
+const MANIFEST_NAME: &str = "manifest.webmanifest";
+
+/// The name of the service worker.
+const SERVICE_WORKER_NAME: &str = "service-worker.js";
+
+/// The name of the sitemap.
+const SITEMAP_NAME: &str = "sitemap.xml";
+
+/// The longest description of a page, in characters. Search engines typically
+/// show no more than this.
+const MAX_DESCRIPTION_LENGTH: usize = 160;
+
+/// Elements added to the `<head>` of each page, which link to the manifest and
+/// register the service worker.
+const OFFLINE_HEAD: &str = concat!(
+    "        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n",
+    "        <link rel=\"manifest\" href=\"manifest.webmanifest\">\n",
+    "        <script>\n",
+    "            if (\"serviceWorker\" in navigator) {\n",
+    "                navigator.serviceWorker.register(\"service-worker.js\");\n",
+    "            }\n",
+    "        </script>\n",
+);
+
+/// The service worker, which follows definitions of `CACHE_VERSION` and
+/// `PRECACHE_URLS`.
+const SERVICE_WORKER_BODY: &str = indoc!(
+    r#"
+    // Name this export's cache using its scope, so that several exports served
+    // from the same site keep separate caches.
+    const CACHE_PREFIX = `codechat-export-${self.registration.scope}-`;
+    const CACHE_NAME = CACHE_PREFIX + CACHE_VERSION;
+
+    // Cache all of the export's files when installed.
+    self.addEventListener("install", (event) => {
+        event.waitUntil(
+            caches
+                .open(CACHE_NAME)
+                .then((cache) => cache.addAll(PRECACHE_URLS))
+                .then(() => self.skipWaiting()),
+        );
+    });
+
+    // Remove the caches of earlier versions of this export.
+    self.addEventListener("activate", (event) => {
+        event.waitUntil(
+            caches
+                .keys()
+                .then((names) =>
+                    Promise.all(
+                        names
+                            .filter(
+                                (name) =>
+                                    name.startsWith(CACHE_PREFIX) &&
+                                    name !== CACHE_NAME,
+                            )
+                            .map((name) => caches.delete(name)),
+                    ),
+                )
+                .then(() => self.clients.claim()),
+        );
+    });
+
+    // Serve cached files, falling back to the network.
+    self.addEventListener("fetch", (event) => {
+        event.respondWith(
+            caches
+                .match(event.request)
+                .then((response) => response ?? fetch(event.request)),
+        );
+    });
+    "#
+);
+
+lazy_static! {
+    /// Match the start of each file's section in the combined document.
+    static ref SECTION_REGEX: Regex =
+        Regex::new(r#"<section class="CodeChat-export-file""#).unwrap();
+    /// Match the URL of a link.
+    static ref HREF_REGEX: Regex = Regex::new(r#"\shref="([^"]*)""#).unwrap();
+    /// Match the URL of an embedded file, such as an image.
+    static ref SRC_REGEX: Regex = Regex::new(r#"\ssrc="([^"]*)""#).unwrap();
+    /// Match a paragraph, capturing its contents.
+    static ref PARAGRAPH_REGEX: Regex = Regex::new(r"(?s)<p(?:\s[^>]*)?>(.*?)</p>").unwrap();
+    /// Match an HTML tag.
+    static ref TAG_REGEX: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+// ## Code
+/// Export the project whose table of contents is `toc_path` to the directory
+/// `output_dir`, as a web page which may be read offline.
+pub fn export_offline(toc_path: &Path, output_dir: &Path) -> Result<(), String> {
+    let toc_path = toc_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to find {toc_path:?}: {err}."))?;
+    let project_root = toc_path.parent().unwrap_or_else(|| Path::new(""));
+    let project_config = load_project_config(project_root)?;
+    // Ensure the site's URL ends with a slash, so page names may be appended.
+    let site_url = project_config
+        .export
+        .site_url
+        .as_deref()
+        .map(|site_url| format!("{}/", site_url.trim_end_matches('/')));
+    let (title, body) = project_body(&toc_path)?;
+    // Give each file's section an ID, so the table of contents can link to it.
+    let mut section_index = 0;
+    let body = SECTION_REGEX.replace_all(&body, |captures: &Captures| {
+        section_index += 1;
+        format!("{} id=\"{}\"", &captures[0], section_id(section_index))
+    });
+    let toc_html = link_toc(&file_to_html(&toc_path)?, &toc_path)?;
+
+    fs::create_dir_all(output_dir)
+        .map_err(|err| format!("Unable to create {output_dir:?}: {err}."))?;
+    let mut precache_urls = vec![
+        "index.html".to_string(),
+        "toc.html".to_string(),
+        MANIFEST_NAME.to_string(),
+    ];
+    let mut hasher = DefaultHasher::new();
+    // The table of contents may lack a paragraph; if so, describe it using
+    // the combined document's description.
+    let index_description = description(&body);
+    for (name, page_body) in [("index.html", &*body), ("toc.html", toc_html.as_str())] {
+        copy_assets(page_body, project_root, output_dir, &mut precache_urls)?;
+        let head = format!(
+            "{OFFLINE_HEAD}{}{}",
+            metadata_head(
+                &title,
+                description(page_body).or(index_description.clone()),
+                site_url
+                    .as_deref()
+                    .map(|site_url| format!("{site_url}{name}")),
+            ),
+            analytics_head(project_config.export.analytics.as_deref())
+        );
+        let page = html_document(&title, &head, page_body);
+        page.hash(&mut hasher);
+        write_file(&output_dir.join(name), &page)?;
+    }
+
+    let manifest = json!({
+        "name": title,
+        "short_name": title,
+        "start_url": "index.html",
+        "display": "standalone",
+        "background_color": "#ffffff",
+        "theme_color": "#ffffff",
+    });
+    write_file(
+        &output_dir.join(MANIFEST_NAME),
+        &serde_json::to_string_pretty(&manifest).unwrap(),
+    )?;
+
+    // Change the cache's name whenever the export changes, so that the
+    // service worker caches the new files.
+    for url in &precache_urls {
+        url.hash(&mut hasher);
+        if let Ok(contents) = fs::read(output_dir.join(asset_path(url))) {
+            contents.hash(&mut hasher);
+        }
+    }
+    let service_worker = format!(
+        "const CACHE_VERSION = \"{:016x}\";\nconst PRECACHE_URLS = {};\n\n{SERVICE_WORKER_BODY}",
+        hasher.finish(),
+        serde_json::to_string(&precache_urls).unwrap()
+    );
+    write_file(&output_dir.join(SERVICE_WORKER_NAME), &service_worker)?;
+
+    // Sitemaps require absolute URLs.
+    match site_url {
+        Some(site_url) => write_file(
+            &output_dir.join(SITEMAP_NAME),
+            &sitemap(&[
+                format!("{site_url}index.html"),
+                format!("{site_url}toc.html"),
+            ]),
+        ),
+        None => Ok(()),
+    }
+}
+
+// Return the elements for a page's `<head>` which describe it to search
+// engines and to sites which preview links.
+fn metadata_head(title: &str, description: Option<String>, url: Option<String>) -> String {
+    let mut head = format!(
+        "        <meta property=\"og:title\" content=\"{}\">\n        <meta property=\"og:type\" content=\"website\">\n",
+        escape(title, EscapeContext::Attribute)
+    );
+    if let Some(description) = description {
+        let description = escape(&description, EscapeContext::Attribute);
+        head.push_str(&format!(
+            "        <meta name=\"description\" content=\"{description}\">\n        <meta property=\"og:description\" content=\"{description}\">\n"
+        ));
+    }
+    if let Some(url) = url {
+        let url = escape(&url, EscapeContext::Attribute);
+        head.push_str(&format!(
+            "        <link rel=\"canonical\" href=\"{url}\">\n        <meta property=\"og:url\" content=\"{url}\">\n"
+        ));
+    }
+    head
+}
+
+// Return the analytics snippet, if provided, on lines of its own.
+fn analytics_head(analytics: Option<&str>) -> String {
+    match analytics.map(str::trim) {
+        Some(analytics) if !analytics.is_empty() => format!("        {analytics}\n"),
+        _ => "".to_string(),
+    }
+}
+
+// Return the text of the first paragraph in `html` which contains text,
+// shortened to at most `MAX_DESCRIPTION_LENGTH` characters.
+fn description(html: &str) -> Option<String> {
+    let text = PARAGRAPH_REGEX
+        .captures_iter(html)
+        .map(|captures| {
+            unescape(
+                &TAG_REGEX.replace_all(&captures[1], ""),
+                EscapeContext::Text,
+            )
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+        })
+        .find(|text| !text.is_empty())?;
+    if text.chars().count() <= MAX_DESCRIPTION_LENGTH {
+        return Some(text);
+    }
+    // Shorten the text at a word boundary, leaving room for an ellipsis.
+    let mut short_text: String = text.chars().take(MAX_DESCRIPTION_LENGTH - 1).collect();
+    if let Some(index) = short_text.rfind(' ') {
+        short_text.truncate(index);
+    }
+    short_text.push('…');
+    Some(short_text)
+}
+
+// Return a sitemap listing the provided URLs.
+fn sitemap(urls: &[String]) -> String {
+    let mut sitemap = concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"
+    )
+    .to_string();
+    for url in urls {
+        sitemap.push_str(&format!(
+            "    <url><loc>{}</loc></url>\n",
+            escape(url, EscapeContext::Text)
+        ));
+    }
+    sitemap.push_str("</urlset>\n");
+    sitemap
+}
+
+// Return the ID of the section for the file at `index` (starting from 1) in
+// the table of contents.
+fn section_id(index: usize) -> String {
+    format!("CodeChat-file-{index}")
+}
+
+// Point links in the table of contents to files in the project to the
+// corresponding section of `index.html`.
+fn link_toc(toc_html: &str, toc_path: &Path) -> Result<String, String> {
+    // Sections are numbered in the order of these files, omitting the table of
+    // contents.
+    let files: Vec<_> = toc_files(toc_path)?
+        .into_iter()
+        .filter(|file_path| file_path != toc_path)
+        .collect();
+    let toc_dir = toc_path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(HREF_REGEX
+        .replace_all(toc_html, |captures: &Captures| {
+            let Some(url) = local_url(&captures[1]) else {
+                return captures[0].to_string();
+            };
+            let Ok(file_path) = toc_dir.join(asset_path(&url)).canonicalize() else {
+                return captures[0].to_string();
+            };
+            match files.iter().position(|path| *path == file_path) {
+                Some(index) => format!(" href=\"index.html#{}\"", section_id(index + 1)),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned())
+}
+
+// Copy the files embedded in `html` which are in the project to the
+// corresponding location in `output_dir`, adding their URLs to `urls`.
+fn copy_assets(
+    html: &str,
+    project_root: &Path,
+    output_dir: &Path,
+    urls: &mut Vec<String>,
+) -> Result<(), String> {
+    for captures in SRC_REGEX.captures_iter(html) {
+        let Some(url) = local_url(&captures[1]) else {
+            continue;
+        };
+        if urls.contains(&url) {
+            continue;
+        }
+        let relative_path = asset_path(&url);
+        // Skip missing files, along with files outside the project.
+        let Ok(source_path) = project_root.join(&relative_path).canonicalize() else {
+            continue;
+        };
+        if !source_path.starts_with(project_root) || !source_path.is_file() {
+            continue;
+        }
+        let dest_path = output_dir.join(&relative_path);
+        if let Some(parent) = dest_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Unable to create {parent:?}: {err}."))?;
+        }
+        fs::copy(&source_path, &dest_path)
+            .map_err(|err| format!("Unable to copy {source_path:?} to {dest_path:?}: {err}."))?;
+        urls.push(url);
+    }
+    Ok(())
+}
+
+// Given the (HTML-escaped) value of a URL attribute, return the URL without
+// any query or fragment, or `None` if it doesn't refer to a file relative to
+// the page.
+fn local_url(attribute: &str) -> Option<String> {
+    let url = unescape(attribute, EscapeContext::Attribute);
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    // Skip URLs with a scheme, such as `https:` or `data:`, along with paths
+    // relative to the site's root.
+    if url.is_empty() || url.contains(':') || url.starts_with('/') {
+        None
+    } else {
+        Some(url.to_string())
+    }
+}
+
+// Return the path of the file named by a relative URL.
+fn asset_path(url: &str) -> String {
+    // URLs may percent-encode characters, such as spaces.
+    urlencoding::decode(url)
+        .map(|path| path.into_owned())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+// Write `contents` to `path`.
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|err| format!("Unable to write {path:?}: {err}."))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{description, export_offline, local_url, MAX_DESCRIPTION_LENGTH};
+    use crate::prep_test_dir;
+
+    #[test]
+    fn test_local_url() {
+        assert_eq!(
+            local_url("images/logo.svg?v=1#top"),
+            Some("images/logo.svg".to_string())
+        );
+        assert_eq!(local_url("https://example.com/logo.svg"), None);
+        assert_eq!(local_url("data:image/png;base64,AAAA"), None);
+        assert_eq!(local_url("/logo.svg"), None);
+        assert_eq!(local_url("#intro"), None);
+        // Character references in the attribute are replaced.
+        assert_eq!(
+            local_url("it&#39;s &amp; &quot;more&quot;.png"),
+            Some("it's & \"more\".png".to_string())
+        );
+    }
+
+    #[test]
+    fn test_description() {
+        assert_eq!(
+            description(
+                "<p><img src=\"a.png\"></p>\n<p class=\"a\">A <em>doc</em>\nblock &amp; more.</p>"
+            ),
+            Some("A doc block & more.".to_string())
+        );
+        assert_eq!(description("<pre><code>x = 1</code></pre>"), None);
+        // Long paragraphs are shortened at a word boundary.
+        let long_description = description(&format!("<p>{}</p>", "word ".repeat(100))).unwrap();
+        assert!(long_description.chars().count() <= MAX_DESCRIPTION_LENGTH);
+        assert!(long_description.ends_with("word…"));
+    }
+
+    #[test]
+    fn test_export_offline() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let output_dir = temp_dir.path().join("output");
+        export_offline(&test_dir.join("toc.md"), &output_dir).unwrap();
+
+        let index = fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("<link rel=\"manifest\" href=\"manifest.webmanifest\">"));
+        assert!(index.contains("id=\"CodeChat-file-1\""));
+        assert!(index.contains("id=\"CodeChat-file-2\""));
+        // The TOC links to each file's section.
+        let toc = fs::read_to_string(output_dir.join("toc.html")).unwrap();
+        assert!(toc.contains("href=\"index.html#CodeChat-file-1\""));
+        assert!(toc.contains("href=\"index.html#CodeChat-file-2\""));
+        // Images are copied and cached.
+        assert!(output_dir.join("images/logo.svg").is_file());
+        let service_worker = fs::read_to_string(output_dir.join("service-worker.js")).unwrap();
+        assert!(service_worker.contains(
+            "const PRECACHE_URLS = [\"index.html\",\"toc.html\",\"manifest.webmanifest\",\"images/logo.svg\"];"
+        ));
+        let manifest: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(output_dir.join("manifest.webmanifest")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["start_url"], "index.html");
+        // Each page describes itself, using the table of contents' first
+        // paragraph or, since it has none, the document's.
+        assert!(index.contains("<meta property=\"og:title\" content=\"test_export_offline\">"));
+        assert!(index.contains("<meta name=\"description\" content=\"A doc block.\">"));
+        assert!(toc.contains("<meta property=\"og:description\" content=\"A doc block.\">"));
+        // Without the site's URL, there's no sitemap or canonical URL.
+        assert!(!index.contains("rel=\"canonical\""));
+        assert!(!output_dir.join("sitemap.xml").exists());
+
+        // Provide the site's URL and an analytics snippet.
+        fs::write(
+            test_dir.join("codechat.json"),
+            r#"{"export": {"site_url": "https://example.com/docs", "analytics": "<script src=\"https://stats.example.com/count.js\"></script>"}}"#,
+        )
+        .unwrap();
+        export_offline(&test_dir.join("toc.md"), &output_dir).unwrap();
+        let index = fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(
+            index.contains("<link rel=\"canonical\" href=\"https://example.com/docs/index.html\">")
+        );
+        assert!(index.contains(
+            "<meta property=\"og:url\" content=\"https://example.com/docs/index.html\">"
+        ));
+        for name in ["index.html", "toc.html"] {
+            assert!(fs::read_to_string(output_dir.join(name)).unwrap().contains(
+                "        <script src=\"https://stats.example.com/count.js\"></script>\n    </head>"
+            ));
+        }
+        let sitemap = fs::read_to_string(output_dir.join("sitemap.xml")).unwrap();
+        assert!(sitemap.contains("<url><loc>https://example.com/docs/index.html</loc></url>"));
+        assert!(sitemap.contains("<url><loc>https://example.com/docs/toc.html</loc></url>"));
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `pdf.rs` -- Export a project or file as a PDF
+///
+/// A PDF export prints the rendered HTML of a project (or of a single file)
+/// using a headless [Chromium](https://www.chromium.org/) or Chrome, which must
+/// be installed; a project's configuration may name the command which runs it.
+/// Since Chromium renders the same HTML as the Client, diagrams, math, and
+/// other rendered doc block contents look as they do when editing. The HTML is
+/// written to a temporary file whose `<base>` is the directory its links are
+/// relative to, so that images and other resources load from the project.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::Path,
+    process::{self, Command},
+};
+
+// ### Third-party
+use url::Url;
+
+// ### Local
+use super::{file_to_html, html_document, project_body};
+use crate::processing::{
+    escape::{escape, EscapeContext},
+    find_path_to_toc,
+    project_config::{load_project_config, ProjectConfig},
+    project_config_for_file,
+};
+
+// ## Globals
+/// The commands tried, in order, to run Chromium when the project's
+/// configuration doesn't name one.
+const CHROMIUM_COMMANDS: [&str; 4] = [
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+];
+
+// ## Code
+/// Export the project whose table of contents is `toc_path` to a PDF.
+pub fn export_pdf(toc_path: &Path, output_path: &Path) -> Result<(), String> {
+    let project_root = toc_path.parent().unwrap_or_else(|| Path::new(""));
+    let project_config = load_project_config(project_root)?;
+    let (title, body) = project_body(toc_path)?;
+    html_to_pdf(&title, &body, project_root, &project_config, output_path)
+}
+
+/// Export a single file to a PDF. Its project, if any, provides the
+/// configuration.
+pub fn export_file_pdf(file_path: &Path, output_path: &Path) -> Result<(), String> {
+    let file_dir = file_path.parent().unwrap_or_else(|| Path::new(""));
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let title = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Links in a file are relative to its directory.
+    html_to_pdf(
+        &title,
+        &file_to_html(file_path)?,
+        file_dir,
+        &project_config,
+        output_path,
+    )
+}
+
+// Print an HTML document with the provided title and body to a PDF at
+// `output_path`, loading resources relative to `base_dir`.
+fn html_to_pdf(
+    title: &str,
+    body: &str,
+    base_dir: &Path,
+    project_config: &ProjectConfig,
+    output_path: &Path,
+) -> Result<(), String> {
+    let base_dir = base_dir
+        .canonicalize()
+        .map_err(|err| format!("Unable to find {base_dir:?}: {err}."))?;
+    let base_url = Url::from_directory_path(&base_dir)
+        .map_err(|_| format!("Unable to convert {base_dir:?} to a URL."))?;
+    let head = format!(
+        "        <base href=\"{}\">\n",
+        escape(base_url.as_str(), EscapeContext::Attribute)
+    );
+    let html = html_document(title, &head, body);
+
+    let html_path = env::temp_dir().join(format!("codechat-export-{}.html", process::id()));
+    fs::write(&html_path, html).map_err(|err| format!("Unable to write {html_path:?}: {err}."))?;
+    let result = print_to_pdf(&html_path, project_config, output_path);
+    // The temporary file is no longer needed, even if printing failed.
+    let _ = fs::remove_file(&html_path);
+    result
+}
+
+// Run Chromium to print the HTML file at `html_path` to `output_path`.
+fn print_to_pdf(
+    html_path: &Path,
+    project_config: &ProjectConfig,
+    output_path: &Path,
+) -> Result<(), String> {
+    let html_url = Url::from_file_path(html_path)
+        .map_err(|_| format!("Unable to convert {html_path:?} to a URL."))?;
+    let args = [
+        "--headless".to_string(),
+        "--disable-gpu".to_string(),
+        "--no-pdf-header-footer".to_string(),
+        format!("--print-to-pdf={}", output_path.to_string_lossy()),
+        html_url.to_string(),
+    ];
+    let commands: Vec<&str> = match &project_config.export.chromium {
+        Some(command) => vec![command.as_str()],
+        None => CHROMIUM_COMMANDS.to_vec(),
+    };
+    for command in &commands {
+        let output = match Command::new(command).args(&args).output() {
+            Ok(output) => output,
+            // Try the next command if this one isn't installed.
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(format!("Unable to run {command}: {err}")),
+        };
+        return if output.status.success() && output_path.is_file() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{command} failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        };
+    }
+    Err(format!(
+        "Unable to find Chromium; is it installed? Tried {}.",
+        commands.join(", ")
+    ))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::print_to_pdf;
+    use crate::processing::project_config::ProjectConfig;
+
+    #[test]
+    fn test_print_to_pdf() {
+        let mut project_config = ProjectConfig::default();
+        project_config.export.chromium = Some("codechat-no-such-chromium".to_string());
+        // A missing Chromium is reported, naming the command tried.
+        assert_eq!(
+            print_to_pdf(
+                Path::new("/tmp/export.html"),
+                &project_config,
+                Path::new("/tmp/export.pdf")
+            ),
+            Err(
+                "Unable to find Chromium; is it installed? Tried codechat-no-such-chromium."
+                    .to_string()
+            )
+        );
+    }
+}
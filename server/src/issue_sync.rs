@@ -0,0 +1,446 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `issue_sync.rs` -- Link task list items to an issue tracker
+///
+/// The `sync-tasks` command links the task list items (`- [ ] text`) in the
+/// doc blocks of a project's files to issues in an issue tracker:
+///
+/// - When asked to, it creates an issue for each unchecked task not yet linked
+///   to one, then appends a link to this issue to the task, such as
+///   `- [ ] Handle errors. [#12](https://github.com/owner/repo/issues/12)`.
+/// - It checks each unchecked task linked to an issue which is now closed.
+///
+/// Each issue tracker implements the `IssueTracker` trait; GitHub is the only
+/// one provided. This integration has its own configuration: the `issues`
+/// object of the project's `codechat.json`, which the rest of the CodeChat
+/// Editor ignores. For example:
+///
+/// ```json
+/// { "issues": { "github": "owner/repo", "labels": ["docs"] } }
+/// ```
+// ## Submodules
+pub mod github;
+
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+
+// ### Local
+use crate::processing::{
+    doc_blocks_with_lines, find_file_lexer, find_path_to_toc,
+    project_config::{toc_files, PROJECT_CONFIG_FILE_NAME},
+    project_config_for_file,
+};
+
+// ## Globals
+lazy_static! {
+    /// Match a task list item, capturing its checkbox and its text. The text
+    /// omits the end of a block comment.
+    static ref TASK_REGEX: Regex =
+        Regex::new(r"(?:^|\s)[-*+]\s+\[([ xX])\]\s+(.*?)\s*(?:\*/|-->)?\s*$").unwrap();
+    /// Match a link to an issue at the end of a task's text, capturing the
+    /// issue's number.
+    static ref ISSUE_LINK_REGEX: Regex = Regex::new(r"\[#(\d+)\]\([^)\s]*\)$").unwrap();
+}
+
+// ## Data structures
+/// The `issues` object of a project's `codechat.json`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct IssueSyncConfig {
+    /// The GitHub repository containing the issues, such as
+    /// `bjones1/CodeChat_Editor`.
+    pub github: Option<String>,
+    /// Labels applied to the issues created.
+    pub labels: Vec<String>,
+}
+
+// The parts of `codechat.json` used by this integration.
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    issues: Option<IssueSyncConfig>,
+}
+
+/// An issue created by an issue tracker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub number: u64,
+    /// The URL of the issue's web page.
+    pub url: String,
+}
+
+/// An issue tracker which tasks may be linked to.
+pub trait IssueTracker {
+    /// Create an issue, returning it.
+    fn create_issue(&self, title: &str, body: &str) -> Result<Issue, String>;
+    /// Return true if the issue `number` is closed.
+    fn is_closed(&self, number: u64) -> Result<bool, String>;
+}
+
+/// How a task was changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskAction {
+    /// An issue was created for the task, and the task was linked to it.
+    Created,
+    /// The task's issue was closed, so the task was checked.
+    Checked,
+}
+
+/// A change made to a task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskChange {
+    /// The path, relative to the project's root, to the file containing the
+    /// task.
+    pub path: String,
+    /// The task's line, numbered from 1.
+    pub line: usize,
+    pub issue: u64,
+    pub action: TaskAction,
+}
+
+impl fmt::Display for TaskChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            TaskAction::Created => write!(
+                f,
+                "{}:{}: created issue #{}.",
+                self.path, self.line, self.issue
+            ),
+            TaskAction::Checked => write!(
+                f,
+                "{}:{}: checked, since issue #{} is closed.",
+                self.path, self.line, self.issue
+            ),
+        }
+    }
+}
+
+// ## Code
+/// Load this integration's configuration from the project rooted at
+/// `project_root`. A project without one produces an error.
+pub fn load_issue_sync_config(project_root: &Path) -> Result<IssueSyncConfig, String> {
+    let config_path = project_root.join(PROJECT_CONFIG_FILE_NAME);
+    let config_str = fs::read_to_string(&config_path)
+        .map_err(|err| format!("Unable to read {config_path:?}: {err}."))?;
+    let config_file: ConfigFile = serde_json::from_str(&config_str)
+        .map_err(|err| format!("Unable to parse {config_path:?}: {err}."))?;
+    config_file
+        .issues
+        .ok_or_else(|| format!("{config_path:?} lacks an \"issues\" object."))
+}
+
+/// Create the issue tracker selected by the configuration, authenticating with
+/// `token`.
+pub fn issue_tracker(
+    config: &IssueSyncConfig,
+    token: String,
+) -> Result<Box<dyn IssueTracker>, String> {
+    match &config.github {
+        Some(repository) => Ok(Box::new(github::GitHubIssues::new(
+            repository.clone(),
+            config.labels.clone(),
+            token,
+        ))),
+        None => Err("The \"issues\" configuration doesn't name an issue tracker.".to_string()),
+    }
+}
+
+/// Synchronize the tasks in each file linked to by the project's table of
+/// contents with `tracker`, creating issues for unlinked tasks if `create` is
+/// true. Return the changes made, which have been written to these files.
+pub fn sync_project_tasks(
+    toc_path: &Path,
+    tracker: &dyn IssueTracker,
+    create: bool,
+) -> Result<Vec<TaskChange>, String> {
+    let toc_path = toc_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to find {toc_path:?}: {err}."))?;
+    let project_root = toc_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut changes = Vec::new();
+    for file_path in toc_files(&toc_path)? {
+        // Skip files the CodeChat Editor doesn't support, such as images.
+        let Ok(file_contents) = fs::read_to_string(&file_path) else {
+            continue;
+        };
+        let relative_path = file_path
+            .strip_prefix(&project_root)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| PathBuf::from(&file_path));
+        let (new_contents, file_changes) = sync_tasks(
+            &file_contents,
+            &file_path,
+            &relative_path.to_string_lossy().replace('\\', "/"),
+            tracker,
+            create,
+        );
+        // Save the changes made before any error, since their issues exist.
+        if new_contents != file_contents {
+            fs::write(&file_path, &new_contents)
+                .map_err(|err| format!("Unable to write {file_path:?}: {err}."))?;
+        }
+        changes.extend(file_changes?);
+    }
+    Ok(changes)
+}
+
+/// Synchronize the tasks in the doc blocks of one file, reported as `path`,
+/// with `tracker`. Return the file's updated contents, along with the changes
+/// made or the first error encountered; the contents include the changes made
+/// before this error.
+pub fn sync_tasks(
+    file_contents: &str,
+    file_path: &Path,
+    path: &str,
+    tracker: &dyn IssueTracker,
+    create: bool,
+) -> (String, Result<Vec<TaskChange>, String>) {
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let Ok(lexer) = find_file_lexer(file_contents, file_path, &project_config) else {
+        return (file_contents.to_string(), Ok(Vec::new()));
+    };
+    let mut doc_lines = HashSet::new();
+    for (start_line, doc_block) in doc_blocks_with_lines(file_contents, lexer) {
+        doc_lines.extend(start_line..start_line + doc_block.lines.max(1));
+    }
+
+    let mut new_contents = String::with_capacity(file_contents.len());
+    let mut changes = Vec::new();
+    let mut error = None;
+    for (index, line) in file_contents.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        if error.is_some() || !doc_lines.contains(&line_number) {
+            new_contents.push_str(line);
+            continue;
+        }
+        match sync_task(line, path, line_number, tracker, create) {
+            Ok(Some((new_line, change))) => {
+                new_contents.push_str(&new_line);
+                changes.push(change);
+            }
+            Ok(None) => new_contents.push_str(line),
+            Err(err) => {
+                error = Some(format!("{path}:{line_number}: {err}"));
+                new_contents.push_str(line);
+            }
+        }
+    }
+    let result = match error {
+        Some(err) => Err(err),
+        None => Ok(changes),
+    };
+    (new_contents, result)
+}
+
+// Synchronize the task, if any, on this line of a doc block. Return the updated
+// line and the change made, or `None` if nothing changed.
+fn sync_task(
+    line: &str,
+    path: &str,
+    line_number: usize,
+    tracker: &dyn IssueTracker,
+    create: bool,
+) -> Result<Option<(String, TaskChange)>, String> {
+    let Some(captures) = TASK_REGEX.captures(line) else {
+        return Ok(None);
+    };
+    let (checkbox, text) = (captures.get(1).unwrap(), captures.get(2).unwrap());
+    // Checked tasks need no changes.
+    if checkbox.as_str() != " " {
+        return Ok(None);
+    }
+    let change = |issue, action| TaskChange {
+        path: path.to_string(),
+        line: line_number,
+        issue,
+        action,
+    };
+    match ISSUE_LINK_REGEX.captures(text.as_str()) {
+        Some(link) => {
+            let number: u64 = link[1]
+                .parse()
+                .map_err(|_| "Invalid issue number.".to_string())?;
+            if !tracker.is_closed(number)? {
+                return Ok(None);
+            }
+            let new_line = format!("{}x{}", &line[..checkbox.start()], &line[checkbox.end()..]);
+            Ok(Some((new_line, change(number, TaskAction::Checked))))
+        }
+        None if create && !text.as_str().is_empty() => {
+            let issue = tracker.create_issue(
+                text.as_str(),
+                &format!("Created from the task on line {line_number} of `{path}`."),
+            )?;
+            let new_line = format!(
+                "{} [#{}]({}){}",
+                &line[..text.end()],
+                issue.number,
+                issue.url,
+                &line[text.end()..]
+            );
+            Ok(Some((new_line, change(issue.number, TaskAction::Created))))
+        }
+        None => Ok(None),
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, path::Path};
+
+    use assert_fs::TempDir;
+    use indoc::indoc;
+
+    use super::{
+        load_issue_sync_config, sync_tasks, Issue, IssueSyncConfig, IssueTracker, TaskAction,
+        TaskChange,
+    };
+
+    // An issue tracker where issue 1 is closed and issue 2 is open.
+    #[derive(Default)]
+    struct MockTracker {
+        created: RefCell<Vec<String>>,
+    }
+
+    impl IssueTracker for MockTracker {
+        fn create_issue(&self, title: &str, _body: &str) -> Result<Issue, String> {
+            let mut created = self.created.borrow_mut();
+            created.push(title.to_string());
+            let number = 10 + created.len() as u64;
+            Ok(Issue {
+                number,
+                url: format!("https://example.com/issues/{number}"),
+            })
+        }
+
+        fn is_closed(&self, number: u64) -> Result<bool, String> {
+            match number {
+                1 => Ok(true),
+                2 => Ok(false),
+                _ => Err(format!("No issue {number}.")),
+            }
+        }
+    }
+
+    fn change(line: usize, issue: u64, action: TaskAction) -> TaskChange {
+        TaskChange {
+            path: "a.py".to_string(),
+            line,
+            issue,
+            action,
+        }
+    }
+
+    #[test]
+    fn test_sync_tasks() {
+        let file_contents = indoc!(
+            "
+            # - [ ] Done. [#1](https://example.com/issues/1)
+            # - [ ] Not done. [#2](https://example.com/issues/2)
+            # - [x] Already checked.
+            # - [ ] New.
+            s = '- [ ] Not a doc block.'
+            "
+        );
+        let tracker = MockTracker::default();
+
+        // Without `create`, only closed issues are reflected.
+        let (new_contents, changes) =
+            sync_tasks(file_contents, Path::new("a.py"), "a.py", &tracker, false);
+        assert_eq!(changes, Ok(vec![change(1, 1, TaskAction::Checked)]));
+        assert!(new_contents.starts_with("# - [x] Done. [#1]"));
+        assert!(tracker.created.borrow().is_empty());
+
+        // With `create`, unlinked tasks receive an issue.
+        let (new_contents, changes) =
+            sync_tasks(&new_contents, Path::new("a.py"), "a.py", &tracker, true);
+        assert_eq!(changes, Ok(vec![change(4, 11, TaskAction::Created)]));
+        assert_eq!(
+            new_contents,
+            indoc!(
+                "
+                # - [x] Done. [#1](https://example.com/issues/1)
+                # - [ ] Not done. [#2](https://example.com/issues/2)
+                # - [x] Already checked.
+                # - [ ] New. [#11](https://example.com/issues/11)
+                s = '- [ ] Not a doc block.'
+                "
+            )
+        );
+        assert_eq!(*tracker.created.borrow(), vec!["New."]);
+
+        // Block comments keep their closing delimiter.
+        let (new_contents, changes) = sync_tasks(
+            "/* - [ ] Fix. */\n",
+            Path::new("a.c"),
+            "a.c",
+            &tracker,
+            true,
+        );
+        assert!(changes.is_ok());
+        assert_eq!(
+            new_contents,
+            "/* - [ ] Fix. [#12](https://example.com/issues/12) */\n"
+        );
+
+        // An error stops the synchronization, keeping the earlier changes.
+        let (new_contents, changes) = sync_tasks(
+            "# - [ ] A. [#1](x)\n# - [ ] B. [#3](x)\n# - [ ] C. [#1](x)\n",
+            Path::new("a.py"),
+            "a.py",
+            &tracker,
+            false,
+        );
+        assert_eq!(changes, Err("a.py:2: No issue 3.".to_string()));
+        assert_eq!(
+            new_contents,
+            "# - [x] A. [#1](x)\n# - [ ] B. [#3](x)\n# - [ ] C. [#1](x)\n"
+        );
+    }
+
+    #[test]
+    fn test_load_issue_sync_config() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(load_issue_sync_config(temp_dir.path()).is_err());
+        fs::write(temp_dir.path().join("codechat.json"), "{}").unwrap();
+        assert!(load_issue_sync_config(temp_dir.path()).is_err());
+        fs::write(
+            temp_dir.path().join("codechat.json"),
+            r#"{"anchors": true, "issues": {"github": "owner/repo"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            load_issue_sync_config(temp_dir.path()),
+            Ok(IssueSyncConfig {
+                github: Some("owner/repo".to_string()),
+                labels: Vec::new(),
+            })
+        );
+        temp_dir.close().unwrap();
+    }
+}
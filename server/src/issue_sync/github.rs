@@ -0,0 +1,103 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `github.rs` -- GitHub issues
+///
+/// Use the [GitHub REST API](https://docs.github.com/en/rest/issues/issues) to
+/// create and query issues.
+// ## Imports
+//
+// ### Third-party
+use serde::Deserialize;
+use serde_json::json;
+
+// ### Local
+use super::{Issue, IssueTracker};
+
+// ## Data structures
+/// The issues of a GitHub repository.
+pub struct GitHubIssues {
+    /// The repository, such as `bjones1/CodeChat_Editor`.
+    repository: String,
+    /// Labels applied to the issues created.
+    labels: Vec<String>,
+    /// A token which may read and write the repository's issues.
+    token: String,
+}
+
+// The parts of an issue returned by GitHub used here.
+#[derive(Deserialize)]
+struct GitHubIssue {
+    number: u64,
+    html_url: String,
+    state: String,
+}
+
+impl GitHubIssues {
+    pub fn new(repository: String, labels: Vec<String>, token: String) -> Self {
+        GitHubIssues {
+            repository,
+            labels,
+            token,
+        }
+    }
+
+    // Add the headers GitHub expects to a request, then send it, returning the
+    // issue in the response.
+    fn send(&self, request: minreq::Request) -> Result<GitHubIssue, String> {
+        let response = request
+            .with_header("Accept", "application/vnd.github+json")
+            .with_header("Authorization", format!("Bearer {}", self.token))
+            .with_header("User-Agent", "CodeChat-Editor")
+            .with_header("X-GitHub-Api-Version", "2022-11-28")
+            .with_timeout(30)
+            .send()
+            .map_err(|err| format!("Unable to reach GitHub: {err}"))?;
+        if !(200..300).contains(&response.status_code) {
+            return Err(format!(
+                "GitHub rejected the request: {}, status code = {}",
+                response.as_str().unwrap_or("Non-text body"),
+                response.status_code
+            ));
+        }
+        response
+            .json()
+            .map_err(|err| format!("Unable to parse GitHub's response: {err}"))
+    }
+}
+
+impl IssueTracker for GitHubIssues {
+    fn create_issue(&self, title: &str, body: &str) -> Result<Issue, String> {
+        let request = minreq::post(format!(
+            "https://api.github.com/repos/{}/issues",
+            self.repository
+        ))
+        .with_json(&json!({ "title": title, "body": body, "labels": self.labels }))
+        .map_err(|err| format!("Unable to encode the issue: {err}"))?;
+        let issue = self.send(request)?;
+        Ok(Issue {
+            number: issue.number,
+            url: issue.html_url,
+        })
+    }
+
+    fn is_closed(&self, number: u64) -> Result<bool, String> {
+        let issue = self.send(minreq::get(format!(
+            "https://api.github.com/repos/{}/issues/{number}",
+            self.repository
+        )))?;
+        Ok(issue.state == "closed")
+    }
+}
@@ -1,4 +1,3 @@
-pub mod capture;
 /// Copyright (C) 2023 Bryan A. Jones.
 ///
 /// This file is part of the CodeChat Editor. The CodeChat Editor is free
@@ -19,9 +18,24 @@ pub mod capture;
 ///
 /// TODO: Add the ability to use
 /// [plugins](https://zicklag.github.io/rust-tutorials/rust-plugins.html).
-pub mod lexer;
+pub mod browser_open;
+pub mod capture;
+pub mod coverage;
+pub mod doctor;
+pub mod export;
+pub mod issue_sync;
+pub mod mobile;
+pub mod perf;
+pub mod preview;
 pub mod processing;
+pub mod review;
+pub mod scaffold;
+pub mod slides;
+pub mod tangle;
+pub mod tunnel;
 pub mod webserver;
+// The lexer is a separate crate; keep its original path.
+pub use codechat_editor_lexer as lexer;
 
 #[cfg(test)]
 pub mod test_utils;
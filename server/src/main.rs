@@ -18,8 +18,9 @@
 //
 // ### Standard library
 use std::{
-    env,
+    env, fs,
     io::Read,
+    path::PathBuf,
     process::{Command, Stdio},
     time::SystemTime,
 };
@@ -31,7 +32,31 @@ use clap::{Parser, Subcommand};
 use log::LevelFilter;
 
 // ### Local
-use code_chat_editor::webserver::{self, IP_ADDRESS};
+use code_chat_editor::{
+    browser_open::open_url,
+    coverage::{coverage_badge, project_coverage, CoverageFormat},
+    doctor::{run_checks, Status},
+    export::{export_file, export_project, ExportFormat},
+    issue_sync::{issue_tracker, load_issue_sync_config, sync_project_tasks},
+    preview::preview_file,
+    processing::{
+        cache::{set_memory_budget, DEFAULT_MEMORY_BUDGET},
+        conditions::set_conditions,
+        diagnostics::check_file,
+        encryption::set_encryption,
+        filter::{filter, FilterFormat},
+    },
+    review::{post_review, review_doc_blocks},
+    scaffold::{scaffold, user_templates_dir, ScaffoldOptions},
+    tangle::tangle_project,
+    tunnel::{
+        read_local_secret, run_tunnel, set_tunnel, ssh_args, tunnel_url, LOCAL_SECRET_HEADER,
+    },
+    webserver::{
+        self, Feature, IframeSettings, OutsideProjectPolicy, TlsSettings,
+        DEFAULT_LOAD_FILE_RETRIES, IP_ADDRESS,
+    },
+};
 
 // ## Data structures
 //
@@ -68,11 +93,221 @@ enum Commands {
         /// Control logging verbosity.
         #[arg(short, long)]
         log: Option<LevelFilter>,
+        /// The memory, in megabytes, used to cache translated files.
+        #[arg(long, default_value_t = DEFAULT_MEMORY_BUDGET / (1024 * 1024))]
+        cache_memory: usize,
+        /// How many times to resend a request for a file which the IDE
+        /// doesn't answer before reading it from disk.
+        #[arg(long, default_value_t = DEFAULT_LOAD_FILE_RETRIES)]
+        load_file_retries: u32,
+        /// Enable classroom mode: a connection which provides this token
+        /// becomes the instructor, whose position students follow. Requires
+        /// `--share-lan`, so that students can reach the Server.
+        #[arg(long, requires = "share_lan")]
+        instructor_token: Option<String>,
+        /// Encrypt cached translations saved to disk, using a key stored in
+        /// the OS keyring.
+        #[arg(long)]
+        encrypt_cache: bool,
+        /// Also let other devices on the local network view the rendered
+        /// docs, using the URL (and QR code) printed when the Server starts.
+        #[arg(long)]
+        share_lan: bool,
+        /// Disable an optional feature of the Client; repeat to disable
+        /// several.
+        #[arg(long, value_enum)]
+        disable_feature: Vec<Feature>,
+        /// Also serve HTTPS, including HTTP/2, using the certificate chain in
+        /// this PEM file.
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// The PEM file containing the private key of the HTTPS certificate.
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// The port for HTTPS connections.
+        #[arg(long, default_value_t = 8443)]
+        tls_port: u16,
+        /// The `sandbox` attribute of the iframes containing the Client, such
+        /// as `"allow-scripts allow-same-origin allow-popups"`; by default,
+        /// the iframes aren't sandboxed.
+        #[arg(long)]
+        iframe_sandbox: Option<String>,
+        /// The `allow` attribute (permissions policy) of the iframes
+        /// containing the Client, such as `clipboard-write`.
+        #[arg(long)]
+        iframe_allow: Option<String>,
+        /// Let the Client load images and pages from this host, such as
+        /// `example.com`, through the Server, for hosts such as VSCode's
+        /// webview which block them; repeat to allow several hosts.
+        #[arg(long)]
+        proxy_allow: Vec<String>,
+        /// Let this source, such as `https://example.com`, show the Client in
+        /// a frame; repeat to allow several. By default, only the Server and
+        /// VSCode's webview may.
+        #[arg(long)]
+        frame_ancestor: Vec<String>,
+        /// How the Client opens a file outside the current project, such as
+        /// one named by a link in a doc block.
+        #[arg(long, value_enum, default_value_t = OutsideProjectPolicy::NewProject)]
+        outside_project: OutsideProjectPolicy,
+        /// The path below which a reverse proxy, such as nginx, serves the
+        /// Server, such as `/codechat`; the proxy removes it from each
+        /// request. Browsers must then first open the URL printed when the
+        /// Server starts, which contains a one-time token.
+        #[arg(long, default_value = "")]
+        base_path: String,
+        /// Expect browsers to reach the Server through an SSH tunnel (see the
+        /// `tunnel` command); they must first open the URL printed when the
+        /// Server starts, which contains a one-time token.
+        #[arg(long)]
+        tunnel: bool,
     },
     /// Start the webserver in a child process then exit.
     Start,
     /// Stop the webserver child process.
     Stop,
+    /// Print a new one-time token for a browser reaching a Server started
+    /// with `serve --tunnel` or `serve --base-path`, such as a second browser
+    /// or one whose session expired.
+    Token,
+    /// Export a project, or a single file as a PDF, to a single document.
+    Export {
+        /// The project's directory, the path to its `toc.md`, or, for a PDF,
+        /// the path to a single file.
+        project: PathBuf,
+        /// The file to write the exported document to; for HTML, the
+        /// directory to write its files to.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The format of the exported document.
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Docx)]
+        format: ExportFormat,
+        /// Set a condition, such as `audience=instructor`, which selects the
+        /// conditional blocks to include, overriding the project's
+        /// configuration. May be repeated.
+        #[arg(long = "condition", value_parser = parse_condition)]
+        conditions: Vec<(String, String)>,
+    },
+    /// Generate source files from the named code chunks in a project's doc
+    /// blocks.
+    Tangle {
+        /// The project's directory, or the path to its `toc.md`.
+        project: PathBuf,
+        /// The directory to write the generated files to; by default, the
+        /// project's directory.
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Link the task list items in a project's doc blocks to issues, checking
+    /// tasks whose issues are closed. Configure this with the `issues` object
+    /// of the project's `codechat.json`; the `GITHUB_TOKEN` environment
+    /// variable provides access to GitHub.
+    SyncTasks {
+        /// The project's directory, or the path to its `toc.md`.
+        project: PathBuf,
+        /// Create an issue for each unchecked task which isn't linked to one.
+        #[arg(long)]
+        create: bool,
+    },
+    /// Report how much of a project is documented.
+    Coverage {
+        /// The project's directory, or the path to its `toc.md`.
+        project: PathBuf,
+        /// The format of the report.
+        #[arg(short, long, value_enum, default_value_t = CoverageFormat::Json)]
+        format: CoverageFormat,
+    },
+    /// Create a new project from a template.
+    New {
+        /// The template to use, such as `python`.
+        template: String,
+        /// The directory to create the project in, which must not exist or be
+        /// empty.
+        dir: PathBuf,
+        /// The copyright holder, added to the header of each source file.
+        #[arg(long)]
+        author: Option<String>,
+        /// The project's license, as an SPDX identifier such as `MIT`, added
+        /// to the header of each source file.
+        #[arg(long)]
+        license: Option<String>,
+    },
+    /// Check for problems running the CodeChat Editor, explaining how to fix
+    /// them.
+    Doctor {
+        /// The project directory to check.
+        #[arg(default_value = ".")]
+        project: PathBuf,
+    },
+    /// Print (or run) the `ssh` command which lets a browser on this machine
+    /// reach a Server on a remote machine.
+    Tunnel {
+        /// The remote machine, as passed to `ssh`, such as `user@example.com`.
+        host: String,
+        /// The port on this machine to forward; by default, the Server's port.
+        #[arg(long)]
+        local_port: Option<u16>,
+        /// Run the `ssh` command, then verify the tunnel reaches the Server.
+        #[arg(long)]
+        spawn: bool,
+        /// The one-time token printed by the Server, started with
+        /// `serve --tunnel`, to include in the URL to open.
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Compare the doc blocks changed between two git revisions, producing a
+    /// pull request review with a comment on each. Print the review as JSON,
+    /// or post it to a pull request on GitHub.
+    ReviewDocs {
+        /// The old revision, such as `main`.
+        base: String,
+        /// The new revision.
+        #[arg(default_value = "HEAD")]
+        head: String,
+        /// A directory in the git repository to review.
+        #[arg(long, default_value = ".")]
+        repo_dir: PathBuf,
+        /// Post the review to this GitHub repository, such as
+        /// `bjones1/CodeChat_Editor`, using the token in the `GITHUB_TOKEN`
+        /// environment variable.
+        #[arg(long, requires = "pr")]
+        github: Option<String>,
+        /// The number of the pull request to post the review to.
+        #[arg(long, requires = "github")]
+        pr: Option<u64>,
+    },
+    /// Show a read-only preview of one file in a web browser, without
+    /// starting the Server.
+    Preview {
+        /// The file to preview.
+        file: PathBuf,
+        /// Write the preview to this file instead of a temporary file.
+        #[arg(short, long, conflicts_with = "stdout")]
+        output: Option<PathBuf>,
+        /// Write the preview's HTML to stdout instead of opening a browser.
+        #[arg(long)]
+        stdout: bool,
+    },
+    /// Translate source code read from stdin to Markdown, or the reverse,
+    /// writing the result to stdout.
+    Translate {
+        /// The format to read.
+        #[arg(long, value_enum)]
+        from: FilterFormat,
+        /// The format to write.
+        #[arg(long, value_enum)]
+        to: FilterFormat,
+        /// The language of the source code, such as `python`, or its file
+        /// extension, such as `py`.
+        #[arg(short, long)]
+        lang: String,
+    },
+    /// Check the doc blocks of the provided files for common problems.
+    LintDocs {
+        /// The files to check.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
 }
 
 // ## Code
@@ -82,7 +317,25 @@ enum Commands {
 impl Cli {
     fn run(self) -> Result<(), Box<dyn std::error::Error>> {
         match &self.command {
-            Commands::Serve { log } => {
+            Commands::Serve {
+                log,
+                cache_memory,
+                load_file_retries,
+                instructor_token,
+                encrypt_cache,
+                share_lan,
+                disable_feature,
+                tls_cert,
+                tls_key,
+                tls_port,
+                iframe_sandbox,
+                iframe_allow,
+                proxy_allow,
+                frame_ancestor,
+                outside_project,
+                base_path,
+                tunnel,
+            } => {
                 #[cfg(debug_assertions)]
                 if let Some(TestMode::Sleep) = self.test_mode {
                     // For testing, don't start the server at all.
@@ -90,6 +343,28 @@ impl Cli {
                     return Ok(());
                 }
                 webserver::configure_logger(log.unwrap_or(LevelFilter::Info));
+                set_memory_budget(cache_memory * 1024 * 1024);
+                set_encryption(*encrypt_cache);
+                webserver::set_load_file_retries(*load_file_retries);
+                webserver::set_instructor_token(instructor_token.clone());
+                webserver::set_share_lan(*share_lan);
+                webserver::set_disabled_features(disable_feature.clone());
+                webserver::set_tls(tls_cert.clone().zip(tls_key.clone()).map(
+                    |(cert_path, key_path)| TlsSettings {
+                        port: *tls_port,
+                        cert_path,
+                        key_path,
+                    },
+                ));
+                webserver::set_iframe_settings(IframeSettings {
+                    sandbox: iframe_sandbox.clone(),
+                    allow: iframe_allow.clone(),
+                });
+                webserver::set_proxy_hosts(proxy_allow.clone());
+                webserver::set_frame_ancestors(frame_ancestor.clone());
+                webserver::set_outside_project_policy(*outside_project);
+                webserver::set_base_path(base_path);
+                set_tunnel(*tunnel);
                 webserver::main(self.port).unwrap();
             }
             Commands::Start => {
@@ -131,25 +406,12 @@ impl Cli {
                 let now = SystemTime::now();
                 loop {
                     // Look for a ping/pong response from the server.
-                    match minreq::get(format!("http://{IP_ADDRESS}:{}/ping", self.port))
-                        .with_timeout(3)
-                        .send()
-                    {
-                        Ok(response) => {
-                            let status_code = response.status_code;
-                            let body = response.as_str().unwrap_or("Non-text body");
-                            if status_code == 200 && body == "pong" {
-                                println!("Server started.");
-                                return Ok(());
-                            } else {
-                                eprintln!(
-                                    "Unexpected response from server: {body}, status code = {status_code}"
-                                );
-                            }
-                        }
-                        Err(err) => {
-                            eprintln!("Failed to start server: {err}");
+                    match webserver::ping_server(self.port, 3) {
+                        Ok(()) => {
+                            println!("Server started.");
+                            return Ok(());
                         }
+                        Err(err) => eprintln!("Failed to start server: {err}"),
                     }
 
                     // Check if the server has exited or failed to start.
@@ -187,10 +449,7 @@ impl Cli {
                 println!("Stopping server...");
                 // TODO: Use https://crates.io/crates/sysinfo to find the server
                 // process and kill it if it doesn't respond to a stop request.
-                return match minreq::get(format!("http://{IP_ADDRESS}:{}/stop", self.port))
-                    .with_timeout(3)
-                    .send()
-                {
+                return match local_request(self.port, "/stop").send() {
                     Err(err) => Err(format!("Failed to stop server: {err}").into()),
                     Ok(response) => {
                         let status_code = response.status_code;
@@ -207,12 +466,214 @@ impl Cli {
                     }
                 };
             }
+            Commands::Token => {
+                let response = local_request(self.port, "/tunnel-token")
+                    .send()
+                    .map_err(|err| format!("Failed to reach the server: {err}"))?;
+                let body = response.as_str().unwrap_or("Non-text body");
+                if response.status_code != 200 {
+                    return Err(format!(
+                        "Unable to mint a token; was the server started with --tunnel or --base-path? Status code = {}",
+                        response.status_code
+                    )
+                    .into());
+                }
+                println!("Browsers reaching this Server through a tunnel or proxy may now open {body}; this token works only once.");
+            }
+            Commands::Export {
+                project,
+                output,
+                format,
+                conditions,
+            } => {
+                set_conditions(conditions.iter().cloned().collect());
+                if project.is_dir() {
+                    export_project(&project.join("toc.md"), output, *format)?;
+                } else if project.file_name().is_some_and(|name| name == "toc.md") {
+                    export_project(project, output, *format)?;
+                } else {
+                    export_file(project, output, *format)?;
+                }
+                println!("Exported to {}.", output.display());
+            }
+            Commands::Tangle {
+                project,
+                output_dir,
+            } => {
+                let toc_path = if project.is_dir() {
+                    project.join("toc.md")
+                } else {
+                    project.clone()
+                };
+                let output_dir = match output_dir {
+                    Some(output_dir) => output_dir.clone(),
+                    None => toc_path.parent().map(PathBuf::from).unwrap_or_default(),
+                };
+                for file_path in tangle_project(&toc_path, &output_dir)? {
+                    println!("Wrote {}.", file_path.display());
+                }
+            }
+            Commands::SyncTasks { project, create } => {
+                let toc_path = if project.is_dir() {
+                    project.join("toc.md")
+                } else {
+                    project.clone()
+                };
+                let project_root = toc_path.parent().map(PathBuf::from).unwrap_or_default();
+                let config = load_issue_sync_config(&project_root)?;
+                let token = env::var("GITHUB_TOKEN")
+                    .map_err(|_| "Set GITHUB_TOKEN to synchronize tasks with GitHub.")?;
+                let tracker = issue_tracker(&config, token)?;
+                for change in sync_project_tasks(&toc_path, tracker.as_ref(), *create)? {
+                    println!("{change}");
+                }
+            }
+            Commands::Coverage { project, format } => {
+                let toc_path = if project.is_dir() {
+                    project.join("toc.md")
+                } else {
+                    project.clone()
+                };
+                let coverage = project_coverage(&toc_path)?;
+                match format {
+                    CoverageFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&coverage)?)
+                    }
+                    CoverageFormat::Badge => println!("{}", coverage_badge(&coverage)),
+                }
+            }
+            Commands::New {
+                template,
+                dir,
+                author,
+                license,
+            } => {
+                let options = ScaffoldOptions {
+                    author: author.clone(),
+                    license: license.clone(),
+                };
+                for path in scaffold(template, dir, &options, user_templates_dir().as_deref())? {
+                    println!("Created {}.", path.display());
+                }
+            }
+            Commands::Doctor { project } => {
+                let checks = run_checks(self.port, project);
+                for check in &checks {
+                    println!("{check}");
+                }
+                if checks.iter().any(|check| check.status == Status::Error) {
+                    return Err("Problems were found.".into());
+                }
+            }
+            Commands::Tunnel {
+                host,
+                local_port,
+                spawn,
+                token,
+            } => {
+                let local_port = local_port.unwrap_or(self.port);
+                if *spawn {
+                    run_tunnel(host, local_port, self.port, token.as_deref())?;
+                } else {
+                    println!("{}", ssh_args(host, local_port, self.port).join(" "));
+                    println!(
+                        "While this runs, open {} in a browser.",
+                        tunnel_url(local_port, token.as_deref())
+                    );
+                }
+            }
+            Commands::ReviewDocs {
+                base,
+                head,
+                repo_dir,
+                github,
+                pr,
+            } => {
+                let review = review_doc_blocks(repo_dir, base, head)?;
+                match (github, pr) {
+                    (Some(github), Some(pr)) => {
+                        let token = env::var("GITHUB_TOKEN")
+                            .map_err(|_| "Set GITHUB_TOKEN to post a review to GitHub.")?;
+                        post_review(github, *pr, &review, &token)?;
+                        println!("{}", review.body);
+                    }
+                    _ => println!("{}", serde_json::to_string_pretty(&review)?),
+                }
+            }
+            Commands::Preview {
+                file,
+                output,
+                stdout,
+            } => {
+                let html = preview_file(file)?;
+                if *stdout {
+                    print!("{html}");
+                } else {
+                    let output_path = match output {
+                        Some(output) => output.clone(),
+                        None => env::temp_dir().join(format!(
+                            "codechat-preview-{}.html",
+                            file.file_stem().unwrap_or_default().to_string_lossy()
+                        )),
+                    };
+                    fs::write(&output_path, html).map_err(|err| {
+                        format!("Unable to write {}: {err}", output_path.display())
+                    })?;
+                    open_url(&output_path.to_string_lossy())?;
+                }
+            }
+            Commands::Translate { from, to, lang } => {
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut input)
+                    .map_err(|err| format!("Unable to read stdin: {err}"))?;
+                print!("{}", filter(&input, *from, *to, lang)?);
+            }
+            Commands::LintDocs { paths } => {
+                let mut problem_count = 0;
+                for path in paths {
+                    let file_contents = fs::read_to_string(path)
+                        .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+                    for diagnostic in check_file(&file_contents, path)? {
+                        println!(
+                            "{}:{}: {:?} [{}] {}",
+                            path.display(),
+                            diagnostic.range.start,
+                            diagnostic.severity,
+                            diagnostic.source,
+                            diagnostic.message
+                        );
+                        problem_count += 1;
+                    }
+                }
+                if problem_count > 0 {
+                    return Err(format!("Found {problem_count} problem(s).").into());
+                }
+            }
         }
 
         Ok(())
     }
 }
 
+// Parse a condition given as `name=value`.
+// Return a request for `path` from the Server on `port`, providing the local
+// secret if the Server requires it.
+fn local_request(port: u16, path: &str) -> minreq::Request {
+    let request = minreq::get(format!("http://{IP_ADDRESS}:{port}{path}")).with_timeout(3);
+    match read_local_secret(port) {
+        Some(secret) => request.with_header(LOCAL_SECRET_HEADER, secret),
+        None => request,
+    }
+}
+
+fn parse_condition(condition: &str) -> Result<(String, String), String> {
+    condition
+        .split_once('=')
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| format!("{condition} isn't of the form name=value."))
+}
+
 #[cfg(not(tarpaulin_include))]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
@@ -0,0 +1,188 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `mobile.rs` -- Show a file on a phone or tablet
+///
+/// The Client's editors and its side-by-side TOC need a large screen and a
+/// keyboard. On a phone or tablet, the Server instead generates a read-only
+/// page which fits a narrow screen: the file's code and doc blocks are
+/// combined as when [exporting](export.rs), the project's TOC is placed in a
+/// drawer which opens from a button at the top of the page, and links are
+/// larger, so they're easier to tap. This page loads no editors and needs no
+/// JavaScript, other than MathJax.
+///
+/// This layout is used when the browser's user agent identifies a mobile
+/// device, or when the URL contains the `mobile` query parameter. Use
+/// `?mobile=0` to show the Client instead on a mobile device.
+// ## Imports
+//
+// ### Third-party
+use indoc::formatdoc;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+// ### Local
+use crate::{
+    export::codechat_for_web_to_html,
+    processing::{
+        escape::{escape, EscapeContext},
+        CodeChatForWeb,
+    },
+};
+
+// ## Globals
+lazy_static! {
+    /// Match the `href` of a link.
+    static ref HREF_REGEX: Regex = Regex::new(r#"\bhref="([^"]*)""#).unwrap();
+}
+
+/// Parts of a user agent which identify a mobile device. Per
+/// [MDN](https://developer.mozilla.org/en-US/docs/Web/HTTP/Browser_detection_using_the_user_agent#mobile_device_detection),
+/// most mobile browsers include `Mobi`; Android and iPad tablets may not.
+const MOBILE_USER_AGENTS: [&str; 4] = ["Mobi", "Android", "iPad", "iPhone"];
+
+/// The styles for the mobile layout.
+const MOBILE_STYLE: &str = r#"
+body.CodeChat-mobile { margin: 0; padding: 0; overflow-wrap: break-word; }
+#CodeChat-mobile-top { position: sticky; top: 0; z-index: 1; display: flex; align-items: center; gap: 0.5em; padding: 0 0.5em; background: inherit; border-bottom: 1px solid #ccc; }
+#CodeChat-mobile-top h1 { flex: 1; margin: 0; font-size: 1.1em; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+#CodeChat-mobile-toc > summary { display: inline-block; min-width: 44px; min-height: 44px; line-height: 44px; text-align: center; font-size: 1.5em; cursor: pointer; list-style: none; }
+#CodeChat-mobile-toc > summary::-webkit-details-marker { display: none; }
+#CodeChat-mobile-toc[open] > .CodeChat-TOC { position: fixed; top: 45px; left: 0; bottom: 0; width: min(85vw, 20rem); overflow-y: auto; padding: 0 1em; background: inherit; border-right: 1px solid #ccc; box-shadow: 2px 0 8px rgba(0, 0, 0, 0.3); }
+#CodeChat-body { height: auto; overflow: visible; padding: 0 1em; }
+#CodeChat-body pre { overflow-x: auto; }
+#CodeChat-body img, #CodeChat-body video { max-width: 100%; height: auto; }
+.CodeChat-TOC a { display: block; min-height: 44px; line-height: 44px; }
+#CodeChat-body a { padding: 0.5em 0; }
+"#;
+
+// ## Code
+/// Return true if the provided user agent identifies a mobile device.
+pub fn is_mobile_user_agent(user_agent: &str) -> bool {
+    MOBILE_USER_AGENTS
+        .iter()
+        .any(|mobile| user_agent.contains(mobile))
+}
+
+/// Return a read-only web page which shows the provided file on a mobile
+/// device. `title` is the page's title; `head` is additional HTML to place in
+/// the page's `<head>`. `toc` provides the HTML of the project's TOC, if this
+/// file is part of a project, along with the URL of the TOC's directory
+/// relative to this file, such as `../`.
+pub fn codechat_for_web_to_mobile(
+    codechat_for_web: &CodeChatForWeb,
+    title: &str,
+    head: &str,
+    toc: Option<(&str, &str)>,
+) -> String {
+    let title = escape(title, EscapeContext::Text);
+    let body = codechat_for_web_to_html(codechat_for_web);
+    let toc_drawer = match toc {
+        Some((toc_html, toc_dir)) => formatdoc!(
+            r#"
+            <details id="CodeChat-mobile-toc">
+                <summary aria-label="Table of contents">&#9776;</summary>
+                <div class="CodeChat-TOC" role="navigation" aria-label="Table of contents">
+            {}
+                </div>
+            </details>"#,
+            rebase_links(toc_html, toc_dir)
+        ),
+        None => "".to_string(),
+    };
+    formatdoc!(
+        r##"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <title>{title} - The CodeChat Editor</title>
+                {head}
+                <style>{MOBILE_STYLE}</style>
+            </head>
+            <body class="CodeChat-theme-light CodeChat-mobile">
+                <a class="CodeChat-skip-link" href="#CodeChat-body">Skip to content</a>
+                <header id="CodeChat-mobile-top">
+                    {toc_drawer}
+                    <h1>{title}</h1>
+                </header>
+                <main id="CodeChat-body" class="CodeChat-doc-contents">
+        {body}
+                </main>
+            </body>
+        </html>
+        "##
+    )
+}
+
+// The TOC's links are relative to its directory; make relative links instead
+// relative to the page showing the TOC, which is in `toc_dir`.
+fn rebase_links(html: &str, toc_dir: &str) -> String {
+    if toc_dir.is_empty() {
+        return html.to_string();
+    }
+    HREF_REGEX
+        .replace_all(html, |captures: &Captures| {
+            let href = &captures[1];
+            // Leave URLs with a scheme (such as `https:` or `mailto:`),
+            // absolute paths, and fragments unchanged.
+            let has_scheme = href
+                .split('/')
+                .next()
+                .is_some_and(|part| part.contains(':'));
+            if href.is_empty() || href.starts_with(['/', '#']) || has_scheme {
+                captures[0].to_string()
+            } else {
+                format!("href=\"{toc_dir}{href}\"")
+            }
+        })
+        .into_owned()
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{is_mobile_user_agent, rebase_links};
+
+    #[test]
+    fn test_is_mobile_user_agent() {
+        assert!(is_mobile_user_agent(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1"
+        ));
+        assert!(is_mobile_user_agent(
+            "Mozilla/5.0 (Linux; Android 14; SM-X710) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0 Safari/537.36"
+        ));
+        assert!(!is_mobile_user_agent(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:120.0) Gecko/20100101 Firefox/120.0"
+        ));
+        assert!(!is_mobile_user_agent(""));
+    }
+
+    #[test]
+    fn test_rebase_links() {
+        assert_eq!(
+            rebase_links(
+                r##"<a href="a/b.py">B</a> <a href="https://x.org/">X</a> <a href="/c">C</a> <a href="#d">D</a> <a href="mailto:a@b.org">E</a>"##,
+                "../"
+            ),
+            r##"<a href="../a/b.py">B</a> <a href="https://x.org/">X</a> <a href="/c">C</a> <a href="#d">D</a> <a href="mailto:a@b.org">E</a>"##
+        );
+        assert_eq!(
+            rebase_links(r#"<a href="a.py">A</a>"#, ""),
+            r#"<a href="a.py">A</a>"#
+        );
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `perf.rs` -- Measure how long the Server's hot paths take
+///
+/// When built with the `perf` feature, the Server records how long each
+/// operation takes in a histogram, grouped by the size of the file operated on.
+/// These are available from the `/metrics` endpoint and are logged when the
+/// Server exits. Without this feature, nothing is recorded.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+// ## Data structures
+/// The operations whose duration is recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Lex a file into code and doc blocks.
+    Lex,
+    /// Translate a file to the CodeChat Editor format.
+    Translate,
+    /// Compute the differences between two versions of a file.
+    Diff,
+    /// Wait for a pong in response to a websocket ping.
+    WebsocketRtt,
+}
+
+/// The ranges of file sizes, in bytes, whose durations are recorded together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum SizeBucket {
+    #[serde(rename = "<1kB")]
+    Under1k,
+    #[serde(rename = "<10kB")]
+    Under10k,
+    #[serde(rename = "<100kB")]
+    Under100k,
+    #[serde(rename = "<1MB")]
+    Under1M,
+    #[serde(rename = ">=1MB")]
+    Over1M,
+}
+
+/// A histogram of durations.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Histogram {
+    pub count: u64,
+    pub total_us: u64,
+    pub max_us: u64,
+    /// `buckets[0]` counts durations under 1 µs; `buckets[i]` for `i > 0`
+    /// counts durations of at least 2^(i - 1) µs but under 2^i µs.
+    pub buckets: Vec<u64>,
+}
+
+/// The histogram for each operation and file size.
+pub type Metrics = BTreeMap<Operation, BTreeMap<SizeBucket, Histogram>>;
+
+// ## Globals
+lazy_static! {
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+}
+
+// ## Code
+impl SizeBucket {
+    pub fn of(file_size: usize) -> SizeBucket {
+        match file_size {
+            0..1_000 => SizeBucket::Under1k,
+            1_000..10_000 => SizeBucket::Under10k,
+            10_000..100_000 => SizeBucket::Under100k,
+            100_000..1_000_000 => SizeBucket::Under1M,
+            _ => SizeBucket::Over1M,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn add(&mut self, duration: Duration) {
+        let us = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        // The number of bits needed to represent `us` is the bucket's index.
+        let index = (u64::BITS - us.leading_zeros()) as usize;
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, 0);
+        }
+        self.buckets[index] += 1;
+        self.count += 1;
+        self.total_us = self.total_us.saturating_add(us);
+        self.max_us = self.max_us.max(us);
+    }
+}
+
+/// Record that `operation` on a file of `file_size` bytes took `duration`.
+pub fn record(operation: Operation, file_size: usize, duration: Duration) {
+    if !cfg!(feature = "perf") {
+        return;
+    }
+    METRICS
+        .lock()
+        .unwrap()
+        .entry(operation)
+        .or_default()
+        .entry(SizeBucket::of(file_size))
+        .or_default()
+        .add(duration);
+}
+
+/// Run `f`, recording how long it took as `operation` on a file of `file_size`
+/// bytes.
+pub fn time<T>(operation: Operation, file_size: usize, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(operation, file_size, start.elapsed());
+    result
+}
+
+/// Return everything recorded so far.
+pub fn metrics() -> Metrics {
+    METRICS.lock().unwrap().clone()
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Histogram, SizeBucket};
+
+    #[test]
+    fn test_size_bucket() {
+        assert_eq!(SizeBucket::of(0), SizeBucket::Under1k);
+        assert_eq!(SizeBucket::of(999), SizeBucket::Under1k);
+        assert_eq!(SizeBucket::of(1_000), SizeBucket::Under10k);
+        assert_eq!(SizeBucket::of(999_999), SizeBucket::Under1M);
+        assert_eq!(SizeBucket::of(5_000_000), SizeBucket::Over1M);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        histogram.add(Duration::from_nanos(500));
+        histogram.add(Duration::from_micros(1));
+        histogram.add(Duration::from_micros(3));
+        histogram.add(Duration::from_micros(4));
+        assert_eq!(
+            histogram,
+            Histogram {
+                count: 4,
+                total_us: 8,
+                max_us: 4,
+                buckets: vec![1, 1, 1, 1],
+            }
+        );
+    }
+}
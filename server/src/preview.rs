@@ -0,0 +1,152 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `preview.rs` -- Preview one file without the Server
+///
+/// The `preview` command translates a single file to a read-only web page,
+/// without starting the Server. The file's code and doc blocks are combined as
+/// when [exporting](export.rs). The page is self-contained: its styles are
+/// included in the page, while images and other media the file refers to are
+/// inlined as `data:` URLs, so the page may be saved, mailed, or passed to
+/// other tools.
+// ## Imports
+//
+// ### Standard library
+use std::{fs, path::Path};
+
+// ### Third-party
+use base64::{engine::general_purpose::STANDARD, Engine};
+use indoc::formatdoc;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+// ### Local
+use crate::{
+    export::file_to_html,
+    processing::escape::{escape, EscapeContext},
+};
+
+// ## Globals
+lazy_static! {
+    /// Match the `src` of an image, video, etc.
+    static ref SRC_REGEX: Regex = Regex::new(r#"\bsrc="([^"]*)""#).unwrap();
+}
+
+/// The styles for the preview.
+const PREVIEW_STYLE: &str = r#"
+body { max-width: 50rem; margin: 0 auto; padding: 0 1em; font-family: sans-serif; overflow-wrap: break-word; }
+pre { overflow-x: auto; padding: 0.5em; background: #f6f8fa; border-radius: 4px; }
+img, video { max-width: 100%; height: auto; }
+"#;
+
+// ## Code
+/// Return a self-contained web page showing the provided file.
+pub fn preview_file(file_path: &Path) -> Result<String, String> {
+    let file_path = file_path
+        .canonicalize()
+        .map_err(|err| format!("Unable to find {file_path:?}: {err}."))?;
+    let body = file_to_html(&file_path)?;
+    let body = match file_path.parent() {
+        Some(file_dir) => inline_assets(&body, file_dir),
+        None => body,
+    };
+    let title = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(formatdoc!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <title>{} - The CodeChat Editor</title>
+                <style>{PREVIEW_STYLE}</style>
+            </head>
+            <body>
+        {body}
+            </body>
+        </html>
+        "#,
+        escape(&title, EscapeContext::Text)
+    ))
+}
+
+// Replace each `src` which refers to a local file, relative to `file_dir`,
+// with a `data:` URL containing that file. Leave other URLs, and files which
+// can't be read, unchanged.
+fn inline_assets(html: &str, file_dir: &Path) -> String {
+    SRC_REGEX
+        .replace_all(html, |captures: &Captures| {
+            let src = &captures[1];
+            // Skip URLs with a scheme (such as `https:` or `data:`) and
+            // absolute paths, which refer to the Server.
+            let has_scheme = src.split('/').next().is_some_and(|part| part.contains(':'));
+            if src.is_empty() || src.starts_with(['/', '#']) || has_scheme {
+                return captures[0].to_string();
+            }
+            let decoded = urlencoding::decode(src)
+                .map(|src| src.into_owned())
+                .unwrap_or_else(|_| src.to_string());
+            let asset_path = file_dir.join(decoded.split(['?', '#']).next().unwrap_or(""));
+            match fs::read(&asset_path) {
+                Ok(contents) => format!(
+                    "src=\"data:{};base64,{}\"",
+                    mime_guess::from_path(&asset_path).first_or_octet_stream(),
+                    STANDARD.encode(contents)
+                ),
+                Err(_) => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+
+    use super::{inline_assets, preview_file};
+
+    #[test]
+    fn test_inline_assets() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a b.svg"), "<svg/>").unwrap();
+        assert_eq!(
+            inline_assets(
+                r#"<img src="a%20b.svg"> <img src="https://x.org/c.png"> <img src="/d.png"> <img src="missing.png">"#,
+                temp_dir.path()
+            ),
+            r#"<img src="data:image/svg+xml;base64,PHN2Zy8+"> <img src="https://x.org/c.png"> <img src="/d.png"> <img src="missing.png">"#
+        );
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_preview_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.py");
+        fs::write(&file_path, "# A *doc* block.\na = 1\n").unwrap();
+        let html = preview_file(&file_path).unwrap();
+        assert!(html.contains("<title>test.py - The CodeChat Editor</title>"));
+        assert!(html.contains("<em>doc</em>"));
+        assert!(html.contains("a = 1"));
+        assert!(preview_file(&temp_dir.path().join("missing.py")).is_err());
+        temp_dir.close().unwrap();
+    }
+}
@@ -15,6 +15,41 @@
 /// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
 ///
 /// # `processing.rs` -- Transform source code to its web-editable equivalent and back
+// ## Submodules
+pub mod admonitions;
+pub mod anchors;
+pub mod api;
+pub mod asciidoc;
+pub mod cache;
+pub mod citations;
+pub mod conditions;
+pub mod copy_markdown;
+pub mod definitions;
+pub mod diagnostics;
+pub mod diff;
+pub mod emoji;
+pub mod encryption;
+pub mod escape;
+pub mod fence_scanner;
+pub mod fences;
+pub mod filter;
+pub mod headings;
+pub mod lint;
+pub mod macros;
+pub mod media;
+pub mod notebook;
+pub mod numbering;
+pub mod outline;
+pub mod project_config;
+pub mod quiz;
+pub mod render_cache;
+pub mod rst;
+pub mod sanitize;
+pub mod snippets;
+pub mod todos;
+pub mod transclusion;
+pub mod workspace;
+
 // ## Imports
 //
 // ### Standard library
@@ -28,19 +63,47 @@ use std::ops::Deref;
 use std::rc::{Rc, Weak};
 */
 use std::cmp::max;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 // ### Third-party
 use lazy_static::lazy_static;
+use log::warn;
 use pulldown_cmark::{html, Options, Parser};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::lexer::LEXERS;
 // ### Local
-use crate::lexer::{source_lexer, CodeDocBlock, DocBlock, LanguageLexerCompiled};
+use crate::lexer::LEXERS;
+use crate::lexer::{
+    choose_lexer, source_lexer, source_lexer_incremental, CodeDocBlock, DocBlock,
+    LanguageLexerCompiled, SourceEdit,
+};
+use crate::perf::{self, Operation};
+use admonitions::{fenced_admonitions_to_html, render_alerts};
+use anchors::{load_anchors, reattach, update_anchors};
+use asciidoc::asciidoc_to_html;
+use cache::{cache_key, get_cached, insert_cached, CachedTranslation};
+use citations::{citation_link_callback, Citations};
+use conditions::{conditions_for_file, evaluate_conditionals};
+use diff::{diff_str, DiffGranularity};
+use emoji::expand_shortcodes;
+use fences::{insert_rendered_fences, render_fences};
+use headings::assign_heading_ids;
+use macros::expand_macros;
+use media::embed_media;
+use notebook::{code_doc_blocks_to_notebook, notebook_to_codechat_for_web, NOTEBOOK_EXT};
+use numbering::{chapter_number, number_labels};
+use project_config::{
+    load_project_config, project_root, DocBlockMarkup, MarkdownExtensions, ProjectConfig,
+    RenderTarget, PROJECT_CONFIG_FILE_NAME,
+};
+use rst::rst_to_html;
+use sanitize::sanitize_html;
+use workspace::root_distance;
 
 // ## Data structures
 //
@@ -66,12 +129,14 @@ pub struct CodeChatForWeb {
 }
 
 /// <a id="SourceFileMetadata"></a>Metadata about a source file sent along with
-/// it both to and from the client. TODO: currently, this is too simple to
-/// justify a struct. This allows for future growth -- perhaps the valid types
-/// of comment delimiters?
+/// it both to and from the client.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct SourceFileMetadata {
     pub mode: String,
+    /// For a Jupyter notebook, the notebook this file was translated from; see
+    /// `notebook.rs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notebook: Option<String>,
 }
 
 /// The format used by CodeMirror to serialize/deserialize editor contents.
@@ -85,7 +150,7 @@ pub struct CodeMirror {
 }
 
 /// This defines a doc block for CodeMirror.
-pub type CodeMirrorDocBlocks = Vec<(
+pub type CodeMirrorDocBlock = (
     // From -- the starting character this doc block is anchored to.
     usize,
     // To -- the ending character this doc block is anchored to.
@@ -96,7 +161,55 @@ pub type CodeMirrorDocBlocks = Vec<(
     String,
     // contents
     String,
-)>;
+);
+
+/// All the doc blocks in a CodeMirror document.
+pub type CodeMirrorDocBlocks = Vec<CodeMirrorDocBlock>;
+
+/// The changes which transform one `CodeMirror` document into another.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CodeMirrorDiff {
+    /// Changes to the document's text, in order. Each refers to character
+    /// offsets in the document before any of these changes.
+    pub doc: Vec<StringDiff>,
+    /// Changes to the document's doc blocks. Positions refer to the document
+    /// before the change.
+    pub doc_blocks: Vec<CodeMirrorDocBlockTransaction>,
+}
+
+/// Replace the characters `from..to` of a string with `insert`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StringDiff {
+    pub from: usize,
+    pub to: usize,
+    pub insert: String,
+}
+
+/// The changes to one doc block. Rather than sending the entire contents of a
+/// doc block when only part of it changes, send just the changes.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CodeMirrorDocBlockUpdate {
+    /// The new location of the doc block.
+    pub from: usize,
+    pub to: usize,
+    /// The new indent, if it changed.
+    pub indent: Option<String>,
+    /// The new delimiter, if it changed.
+    pub delimiter: Option<String>,
+    /// The changes to the doc block's contents.
+    pub contents: Vec<StringDiff>,
+}
+
+/// A change to one doc block.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum CodeMirrorDocBlockTransaction {
+    /// Add this doc block.
+    Add(CodeMirrorDocBlock),
+    /// Change the doc block starting at the provided character.
+    Update(usize, CodeMirrorDocBlockUpdate),
+    /// Delete the doc block starting at the provided character.
+    Delete(usize),
+}
 
 /// This enum contains the results of translating a source file to the CodeChat
 /// Editor format.
@@ -136,6 +249,13 @@ pub enum TranslationResultsString {
 lazy_static! {
     /// Match the lexer directive in a source file.
     static ref LEXER_DIRECTIVE: Regex = Regex::new(r"CodeChat Editor lexer: (\w+)").unwrap();
+    /// Match the doc block markup directive in a source file.
+    static ref MARKUP_DIRECTIVE: Regex = Regex::new(r"CodeChat Editor markup: (\w+)").unwrap();
+    /// The lexers selected for specific files by `SetFileLanguage` messages.
+    static ref FILE_LANGUAGES: Mutex<HashMap<PathBuf, String>> = Mutex::new(HashMap::new());
+    /// The most recently lexed large file for each lexer, with the code and doc
+    /// blocks it lexed to; see `lex_source`.
+    static ref LAST_LEXED: Mutex<HashMap<String, (String, Vec<CodeDocBlock>)>> = Mutex::new(HashMap::new());
     /// Match the doc block separator string translated from Markdown to HTML as
     /// itself, or when inside a fenced code block.
     static ref DOC_BLOCK_SEPARATOR_STRING_REGEX: Regex = Regex::new("<CodeChatEditor-separator/>\n|&lt;CodeChatEditor-separator/&gt;\n").unwrap();
@@ -143,25 +263,195 @@ lazy_static! {
 
 const DOC_BLOCK_SEPARATOR_STRING: &str = "\n<CodeChatEditor-separator/>\n\n";
 
+/// The doc block separator as it appears in HTML, and as an HTML comment,
+/// which the sanitizer preserves.
+const DOC_BLOCK_SEPARATOR_HTML: &str = "<CodeChatEditor-separator/>";
+const DOC_BLOCK_SEPARATOR_COMMENT: &str = "<!--CodeChatEditor-separator-->";
+
+/// Files with a line longer than this (in bytes), such as minified JavaScript,
+/// aren't lexed; instead, the entire file is shown as code. Lexing and
+/// rendering these is slow, and they rarely contain doc blocks.
+pub const MAX_LEXED_LINE_LENGTH: usize = 10_000;
+
+/// Files at least this long (in bytes) are lexed incrementally, re-lexing only
+/// what changed since the previous version of the file.
+const MIN_INCREMENTAL_LEX_LENGTH: usize = 50_000;
+
+/// The files or directories which mark the root of a project, from highest to
+/// lowest precedence; see `find_project_root`.
+pub const PROJECT_ROOT_MARKERS: [&str; 3] = ["toc.md", PROJECT_CONFIG_FILE_NAME, ".git"];
+
 // ## Determine if the provided file is part of a project.
+//
+// Return the path to the project's table of contents, relative to the
+// directory containing the provided file.
 pub fn find_path_to_toc(file_path: &Path) -> Option<PathBuf> {
-    // To determine if this source code is part of a project, look for a project
-    // file by searching the current directory, then all its parents, for a file
-    // named `toc.md`.
+    let levels = find_marker(file_path, "toc.md")?;
     let mut path_to_toc = PathBuf::new();
+    for _ in 0..levels {
+        path_to_toc.push("../");
+    }
+    path_to_toc.push("toc.md");
+    Some(path_to_toc)
+}
+
+/// Return the root directory of the project containing the provided file, or
+/// `None` if it's not part of a project. The root is the nearest directory
+/// containing a `toc.md`; if there's none, the nearest containing a project
+/// configuration file; if there's none, the nearest Git repository. Look up
+/// project files, such as the configuration, relative to this root.
+pub fn find_project_root(file_path: &Path) -> Option<PathBuf> {
+    let levels = PROJECT_ROOT_MARKERS
+        .iter()
+        .find_map(|marker| find_marker(file_path, marker))?;
+    file_path.ancestors().nth(levels + 1).map(Path::to_path_buf)
+}
+
+// Search the directory containing the provided file, then all its parents, for
+// `marker`, returning the number of levels above the file's directory at which
+// it was found. Don't search above the root of the IDE's workspace containing
+// this file; see `workspace.rs`.
+fn find_marker(file_path: &Path, marker: &str) -> Option<usize> {
     let mut current_dir = file_path.to_path_buf();
+    let mut levels_to_root = root_distance(file_path);
+    // The number of directories popped from `file_path`.
+    let mut levels = 0;
     loop {
-        let mut project_file = current_dir.clone();
-        project_file.push("toc.md");
-        if project_file.is_file() {
-            path_to_toc.pop();
-            path_to_toc.push("toc.md");
-            return Some(path_to_toc);
+        if current_dir.join(marker).exists() {
+            return Some(levels.max(1) - 1);
         }
-        if !current_dir.pop() {
+        if levels_to_root == Some(0) || !current_dir.pop() {
             return None;
         }
-        path_to_toc.push("../");
+        levels_to_root = levels_to_root.map(|levels| levels - 1);
+        levels += 1;
+    }
+}
+
+// ## Lex source code
+/// Lex the source code produced by applying `diff` (whose offsets are in
+/// characters) to `previous_source`, given `previous`, the code and doc blocks
+/// which `previous_source` lexed to. Only the blocks which `diff` affects are
+/// lexed again.
+pub fn source_lexer_diff(
+    previous_source: &str,
+    previous: Vec<CodeDocBlock>,
+    diff: &StringDiff,
+    lexer: &LanguageLexerCompiled,
+) -> Vec<CodeDocBlock> {
+    let byte_index = |char_index: usize| {
+        previous_source
+            .char_indices()
+            .nth(char_index)
+            .map_or(previous_source.len(), |(index, _)| index)
+    };
+    let edit = SourceEdit {
+        from: byte_index(diff.from),
+        to: byte_index(diff.to),
+        insert: diff.insert.clone(),
+    };
+    source_lexer_incremental(previous_source, previous, &edit, lexer)
+}
+
+// Lex the provided source code. Since an IDE sends the entire file on every
+// change, the next version of a large file is lexed incrementally, using the
+// change between it and the previous version lexed with the same lexer.
+fn lex_source(file_contents: &str, lexer: &LanguageLexerCompiled) -> Vec<CodeDocBlock> {
+    if file_contents.len() < MIN_INCREMENTAL_LEX_LENGTH || file_contents.contains('\r') {
+        return source_lexer(file_contents, lexer);
+    }
+    let lexer_name = lexer.language_lexer.lexer_name.to_string();
+    let previous = LAST_LEXED.lock().unwrap().remove(&lexer_name);
+    let code_doc_blocks = match previous {
+        Some((previous_source, previous)) => source_lexer_diff(
+            &previous_source,
+            previous,
+            &change_between(&previous_source, file_contents),
+            lexer,
+        ),
+        None => source_lexer(file_contents, lexer),
+    };
+    LAST_LEXED.lock().unwrap().insert(
+        lexer_name,
+        (file_contents.to_string(), code_doc_blocks.clone()),
+    );
+    code_doc_blocks
+}
+
+// Return a single change transforming `before` into `after`: replace everything
+// between their common prefix and suffix.
+fn change_between(before: &str, after: &str) -> StringDiff {
+    let before: Vec<char> = before.chars().collect();
+    let after: Vec<char> = after.chars().collect();
+    let prefix = before
+        .iter()
+        .zip(&after)
+        .take_while(|(b, a)| b == a)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(b, a)| b == a)
+        .count();
+    StringDiff {
+        from: prefix,
+        to: before.len() - suffix,
+        insert: after[prefix..after.len() - suffix].iter().collect(),
+    }
+}
+
+// ## Compute the differences between two `CodeMirror` documents
+/// Return the changes which transform `before` into `after`, comparing the
+/// documents' text with the provided granularity.
+pub fn diff_code_mirror(
+    before: &CodeMirror,
+    after: &CodeMirror,
+    granularity: DiffGranularity,
+) -> CodeMirrorDiff {
+    perf::time(Operation::Diff, before.doc.len(), || CodeMirrorDiff {
+        doc: diff_str(&before.doc, &after.doc, granularity),
+        doc_blocks: diff_doc_blocks(&before.doc_blocks, &after.doc_blocks, granularity),
+    })
+}
+
+/// Return the changes which transform the doc blocks in `before` into those in
+/// `after`. When the number of doc blocks is unchanged, update only those which
+/// differ, comparing their contents with the provided granularity; otherwise,
+/// replace them all.
+fn diff_doc_blocks(
+    before: &CodeMirrorDocBlocks,
+    after: &CodeMirrorDocBlocks,
+    granularity: DiffGranularity,
+) -> Vec<CodeMirrorDocBlockTransaction> {
+    if before.len() == after.len() {
+        before
+            .iter()
+            .zip(after)
+            .filter(|(b, a)| b != a)
+            .map(|(b, a)| {
+                CodeMirrorDocBlockTransaction::Update(
+                    b.0,
+                    CodeMirrorDocBlockUpdate {
+                        from: a.0,
+                        to: a.1,
+                        indent: (b.2 != a.2).then(|| a.2.clone()),
+                        delimiter: (b.3 != a.3).then(|| a.3.clone()),
+                        contents: diff_str(&b.4, &a.4, granularity),
+                    },
+                )
+            })
+            .collect()
+    } else {
+        before
+            .iter()
+            .map(|b| CodeMirrorDocBlockTransaction::Delete(b.0))
+            .chain(
+                after
+                    .iter()
+                    .map(|a| CodeMirrorDocBlockTransaction::Add(a.clone())),
+            )
+            .collect()
     }
 }
 
@@ -183,7 +473,10 @@ pub fn codechat_for_web_to_source(
 
     // Convert from `CodeMirror` to a `SortaCodeDocBlocks`.
     let code_doc_block_vec = code_mirror_to_code_doc_blocks(&codechat_for_web.source);
-    code_doc_block_vec_to_source(&code_doc_block_vec, lexer)
+    match &codechat_for_web.metadata.notebook {
+        Some(notebook) => code_doc_blocks_to_notebook(&code_doc_block_vec, notebook),
+        None => code_doc_block_vec_to_source(&code_doc_block_vec, lexer),
+    }
 }
 
 /// Translate from CodeMirror to CodeDocBlocks.
@@ -377,6 +670,156 @@ fn code_doc_block_vec_to_source(
 
 // ## Transform from source code to `CodeChatForWeb`
 //
+// Determine the lexer to use for a file, in order of priority:
+//
+// 1.  A lexer directive in the file's contents.
+// 2.  The lexer selected for this file by a `SetFileLanguage` message.
+// 3.  The lexer the project's configuration selects for the file's extension.
+// 4.  The lexer for the file's extension. If several lexers support this
+//     extension, the file's contents select one.
+//
+// Return `Ok(None)` if no lexer supports this file.
+pub fn find_lexer(
+    file_contents: &str,
+    file_ext: &String,
+    project_config: &ProjectConfig,
+) -> Result<Option<&'static LanguageLexerCompiled>, String> {
+    let lexer_name = match LEXER_DIRECTIVE.captures(file_contents) {
+        Some(captures) => Some(captures[1].to_string()),
+        None => project_config
+            .file_language
+            .clone()
+            .or_else(|| project_config.languages.get(file_ext).cloned()),
+    };
+    match lexer_name {
+        Some(lexer_name) => match LEXERS.map_mode_to_lexer.get(&lexer_name) {
+            Some(v) => Ok(Some(v.as_ref())),
+            None => Err(format!("<p>Unknown lexer type {}.</p>", &lexer_name)),
+        },
+        None => Ok(LEXERS
+            .map_ext_to_lexer_vec
+            .get(file_ext)
+            .and_then(|lexers| choose_lexer(lexers, file_contents))),
+    }
+}
+
+// Find the markup used by the doc blocks of the provided file: either the
+// markup named by a directive in the file or the project's markup.
+fn find_markup(
+    file_contents: &str,
+    project_config: &ProjectConfig,
+) -> Result<DocBlockMarkup, String> {
+    match MARKUP_DIRECTIVE.captures(file_contents) {
+        Some(captures) => {
+            serde_json::from_value(serde_json::Value::String(captures[1].to_string()))
+                .map_err(|_| format!("<p>Unknown doc block markup {}.</p>", &captures[1]))
+        }
+        None => Ok(project_config.doc_block_markup),
+    }
+}
+
+// Like `find_lexer`, but determine the extension from the provided path and
+// report an unsupported file as an error.
+pub fn find_file_lexer(
+    file_contents: &str,
+    file_path: &Path,
+    project_config: &ProjectConfig,
+) -> Result<&'static LanguageLexerCompiled, String> {
+    find_lexer(file_contents, &lexer_ext(file_path), project_config)?
+        .ok_or_else(|| format!("{file_path:?} isn't supported by the CodeChat Editor."))
+}
+
+// Return the extension used to look up the lexer for the provided file. Files
+// such as `Makefile` are identified by their name rather than an extension;
+// lexers list these names as extensions.
+pub fn lexer_ext(file_path: &Path) -> String {
+    if let Some(file_name) = file_path.file_name() {
+        let file_name = file_name.to_string_lossy().to_string();
+        if LEXERS.map_ext_to_lexer_vec.contains_key(&file_name) {
+            return file_name;
+        }
+    }
+    file_path
+        .extension()
+        .unwrap_or_else(|| OsStr::new(""))
+        .to_string_lossy()
+        .to_string()
+}
+
+// Select the lexer used for the provided file, overriding the lexer chosen by
+// its extension and contents; `None` removes this override. This lasts until
+// the Server exits.
+pub fn set_file_language(file_path: &Path, language: Option<String>) -> Result<(), String> {
+    let mut file_languages = FILE_LANGUAGES.lock().unwrap();
+    match language {
+        Some(language) => {
+            if !LEXERS.map_mode_to_lexer.contains_key(&language) {
+                return Err(format!("Unknown lexer type {language}."));
+            }
+            file_languages.insert(file_language_key(file_path), language);
+        }
+        None => {
+            file_languages.remove(&file_language_key(file_path));
+        }
+    }
+    Ok(())
+}
+
+// Since the same file may be referred to by different paths, use its canonical
+// path (if it exists) to look up the lexer selected for it.
+fn file_language_key(file_path: &Path) -> PathBuf {
+    file_path
+        .canonicalize()
+        .unwrap_or_else(|_| file_path.to_path_buf())
+}
+
+// Return the (1-based) line number and length of the first line longer than
+// `MAX_LEXED_LINE_LENGTH`, if there is one.
+pub fn find_long_line(file_contents: &str) -> Option<(usize, usize)> {
+    file_contents
+        .lines()
+        .enumerate()
+        .find(|(_, line)| line.len() > MAX_LEXED_LINE_LENGTH)
+        .map(|(index, line)| (index + 1, line.len()))
+}
+
+// Lex the provided file, returning each doc block paired with the (1-based)
+// line of the file on which it begins. A document-only file is a single doc
+// block; a file with a line too long to lex has no doc blocks.
+pub fn doc_blocks_with_lines(
+    file_contents: &str,
+    lexer: &LanguageLexerCompiled,
+) -> Vec<(usize, DocBlock)> {
+    if lexer.language_lexer.lexer_name.as_str() == "markdown" {
+        return vec![(
+            1,
+            DocBlock {
+                indent: "".to_string(),
+                delimiter: "".to_string(),
+                contents: file_contents.to_string(),
+                lines: file_contents.lines().count(),
+            },
+        )];
+    }
+    if find_long_line(file_contents).is_some() {
+        return Vec::new();
+    }
+    // Walk the code and doc blocks, tracking the line each begins on.
+    let mut line = 1;
+    let mut doc_blocks = Vec::new();
+    for code_doc_block in source_lexer(file_contents, lexer) {
+        match code_doc_block {
+            CodeDocBlock::CodeBlock(code) => line += code.matches('\n').count(),
+            CodeDocBlock::DocBlock(doc_block) => {
+                let lines = doc_block.lines;
+                doc_blocks.push((line, doc_block));
+                line += lines;
+            }
+        }
+    }
+    doc_blocks
+}
+
 // Given the contents of a file, classify it and (for CodeChat Editor files)
 // convert it to the `CodeChatForWeb` format.
 pub fn source_to_codechat_for_web(
@@ -388,58 +831,84 @@ pub fn source_to_codechat_for_web(
     _is_toc: bool,
     // True if this file is part of a project.
     _is_project: bool,
+    // The configuration of the project containing this file, or the default
+    // configuration if this file isn't part of a project.
+    project_config: &ProjectConfig,
 ) -> TranslationResults {
+    // Jupyter notebooks contain JSON; edit their cells instead.
+    if file_ext == NOTEBOOK_EXT {
+        return notebook_to_codechat_for_web(file_contents, project_config);
+    }
+
     // Determine the lexer to use for this file.
-    let lexer_name;
-    // First, search for a lexer directive in the file contents.
-    let lexer = if let Some(captures) = LEXER_DIRECTIVE.captures(file_contents) {
-        lexer_name = captures[1].to_string();
-        match LEXERS.map_mode_to_lexer.get(&lexer_name) {
-            Some(v) => v,
-            None => {
-                return TranslationResults::Err(format!(
-                    "<p>Unknown lexer type {}.</p>",
-                    &lexer_name
-                ))
-            }
-        }
-    } else {
-        // Otherwise, look up the lexer by the file's extension.
-        if let Some(llc) = LEXERS.map_ext_to_lexer_vec.get(file_ext) {
-            llc.first().unwrap()
-        } else {
-            // The file type is unknown; treat it as plain text.
-            return TranslationResults::Unknown;
-        }
+    let lexer = match find_lexer(file_contents, file_ext, project_config) {
+        Ok(Some(lexer)) => lexer,
+        // The file type is unknown; treat it as plain text.
+        Ok(None) => return TranslationResults::Unknown,
+        Err(err) => return TranslationResults::Err(err),
     };
 
     // Transform the provided file into the `CodeChatForWeb` structure.
-    let code_doc_block_arr;
     let codechat_for_web = CodeChatForWeb {
         metadata: SourceFileMetadata {
             mode: lexer.language_lexer.lexer_name.to_string(),
+            notebook: None,
         },
         source: if lexer.language_lexer.lexer_name.as_str() == "markdown" {
             // Document-only files are easy: just encode the contents.
-            let html = markdown_to_html(file_contents);
+            let html = markdown_to_html(
+                &preprocess_markdown(file_contents, project_config),
+                project_config,
+            );
             // TODO: process the HTML.
             CodeMirror {
                 doc: html,
                 doc_blocks: vec![],
             }
+        } else if find_long_line(file_contents).is_some() {
+            // Show a file with a line too long to lex as code.
+            CodeMirror {
+                doc: file_contents.to_string(),
+                doc_blocks: vec![],
+            }
         } else {
-            // This is a source file.
-            //
-            // Create an initially-empty struct; the source code will be
-            // translated to this.
-            let mut code_mirror = CodeMirror {
-                doc: "".to_string(),
-                doc_blocks: Vec::new(),
+            // This is a source file. Lex the code.
+            let code_doc_block_arr = perf::time(Operation::Lex, file_contents.len(), || {
+                lex_source(file_contents, lexer)
+            });
+            // Move doc blocks separated from their code back to it.
+            let code_doc_block_arr = if project_config.file_anchors.is_empty() {
+                code_doc_block_arr
+            } else {
+                reattach(code_doc_block_arr, &project_config.file_anchors).0
+            };
+            let markup = match find_markup(file_contents, project_config) {
+                Ok(markup) => markup,
+                Err(err) => return TranslationResults::Err(err),
             };
+            code_doc_blocks_to_code_mirror(code_doc_block_arr, markup, project_config)
+        },
+    };
 
-            // Lex the code.
-            code_doc_block_arr = source_lexer(file_contents, lexer);
+    TranslationResults::CodeChat(codechat_for_web)
+}
+
+// Translate code and doc blocks to their `CodeMirror` equivalent, transforming
+// doc blocks from the provided markup to HTML.
+fn code_doc_blocks_to_code_mirror(
+    code_doc_block_arr: Vec<CodeDocBlock>,
+    markup: DocBlockMarkup,
+    project_config: &ProjectConfig,
+) -> CodeMirror {
+    // Create an initially-empty struct; the source code will be
+    // translated to this.
+    let mut code_mirror = CodeMirror {
+        doc: "".to_string(),
+        doc_blocks: Vec::new(),
+    };
 
+    let doc_blocks_html: Vec<String> = match markup {
+        DocBlockMarkup::Markdown => {
             // Combine all the doc blocks into a single string, separated by a
             // delimiter. Transform this to markdown, then split the transformed
             // content back into the doc blocks they came from. This is
@@ -455,7 +924,7 @@ pub fn source_to_codechat_for_web(
                 // ...selcting only the doc block contents...
                 .filter_map(|cdb| {
                     if let CodeDocBlock::DocBlock(db) = cdb {
-                        Some(db.contents.as_str())
+                        Some(preprocess_markdown(&db.contents, project_config))
                     } else {
                         None
                     }
@@ -464,52 +933,73 @@ pub fn source_to_codechat_for_web(
                 // string.
                 .collect::<Vec<_>>()
                 .join(DOC_BLOCK_SEPARATOR_STRING);
-            let html = markdown_to_html(&doc_contents);
+            let html = markdown_to_html(&doc_contents, project_config);
             // Now that we have HTML, process it. TODO.
             //
             // After processing by Markdown, the doc block separator string may
             // be (mostly) unchanged; however, if there's an unterminated fenced
             // code block, then HTML entities replaces angle brackets. Match on
             // either case.
-            let mut doc_block_contents_iter = DOC_BLOCK_SEPARATOR_STRING_REGEX.split(&html);
-
-            // Translate each `CodeDocBlock` to its `CodeMirror` equivalent.
-            for code_or_doc_block in code_doc_block_arr {
-                match code_or_doc_block {
-                    CodeDocBlock::CodeBlock(code_string) => code_mirror.doc.push_str(&code_string),
-                    CodeDocBlock::DocBlock(doc_block) => {
-                        // Create the doc block.
-                        //
-                        // Get the length of the string in characters (not
-                        // bytes, which is what `len()` returns).
-                        let len = code_mirror.doc.chars().count();
-                        code_mirror.doc_blocks.push((
-                            // From
-                            len,
-                            // To. Make this one line short, which allows
-                            // CodeMirror to correctly handle inserts at the
-                            // first character of the following code block. Note
-                            // that the last doc block could be zero length, so
-                            // handle this case.
-                            len + max(doc_block.lines, 1) - 1,
-                            doc_block.indent.to_string(),
-                            doc_block.delimiter.to_string(),
-                            // Used the markdown-translated replacement for this
-                            // doc block, rather than the original string.
-                            doc_block_contents_iter.next().unwrap().to_string(),
-                        ));
-                        // Append newlines to the document; the doc block will
-                        // replace these in the editor. This keeps the line
-                        // numbering of non-doc blocks correct.
-                        code_mirror.doc.push_str(&"\n".repeat(doc_block.lines));
-                    }
-                }
-            }
-            code_mirror
-        },
+            DOC_BLOCK_SEPARATOR_STRING_REGEX
+                .split(&html)
+                .map(str::to_string)
+                .collect()
+        }
+        // reStructuredText doc blocks are rendered one at a time.
+        DocBlockMarkup::ReStructuredText => doc_block_contents(&code_doc_block_arr)
+            .iter()
+            .map(|contents| rst_to_html(contents, project_config))
+            .collect(),
+        DocBlockMarkup::AsciiDoc => {
+            asciidoc_to_html(&doc_block_contents(&code_doc_block_arr), project_config)
+        }
     };
+    let mut doc_block_contents_iter = doc_blocks_html.into_iter();
 
-    TranslationResults::CodeChat(codechat_for_web)
+    // Translate each `CodeDocBlock` to its `CodeMirror` equivalent.
+    for code_or_doc_block in code_doc_block_arr {
+        match code_or_doc_block {
+            CodeDocBlock::CodeBlock(code_string) => code_mirror.doc.push_str(&code_string),
+            CodeDocBlock::DocBlock(doc_block) => {
+                // Create the doc block.
+                //
+                // Get the length of the string in characters (not
+                // bytes, which is what `len()` returns).
+                let len = code_mirror.doc.chars().count();
+                code_mirror.doc_blocks.push((
+                    // From
+                    len,
+                    // To. Make this one line short, which allows
+                    // CodeMirror to correctly handle inserts at the
+                    // first character of the following code block. Note
+                    // that the last doc block could be zero length, so
+                    // handle this case.
+                    len + max(doc_block.lines, 1) - 1,
+                    doc_block.indent.to_string(),
+                    doc_block.delimiter.to_string(),
+                    // Used the HTML-translated replacement for this doc
+                    // block, rather than the original string.
+                    doc_block_contents_iter.next().unwrap(),
+                ));
+                // Append newlines to the document; the doc block will
+                // replace these in the editor. This keeps the line
+                // numbering of non-doc blocks correct.
+                code_mirror.doc.push_str(&"\n".repeat(doc_block.lines));
+            }
+        }
+    }
+    code_mirror
+}
+
+// Return the contents of each doc block.
+fn doc_block_contents(code_doc_block_arr: &[CodeDocBlock]) -> Vec<String> {
+    code_doc_block_arr
+        .iter()
+        .filter_map(|cdb| match cdb {
+            CodeDocBlock::DocBlock(db) => Some(db.contents.clone()),
+            CodeDocBlock::CodeBlock(_) => None,
+        })
+        .collect()
 }
 
 // Like `source_to_codechat_for_web`, translate a source file to the CodeChat
@@ -523,21 +1013,41 @@ pub fn source_to_codechat_for_web_string(
     file_path: &Path,
     // True if this file is a TOC.
     is_toc: bool,
+    // Where the resulting HTML will be displayed.
+    render_target: RenderTarget,
 ) -> (TranslationResultsString, Option<PathBuf>) {
     // Determine the file's extension, in order to look up a lexer.
-    let ext = &file_path
-        .extension()
-        .unwrap_or_else(|| OsStr::new(""))
-        .to_string_lossy();
+    let ext = lexer_ext(file_path);
 
     // To determine if this source code is part of a project, look for a project
     // file by searching the current directory, then all its parents, for a file
     // named `toc.md`.
     let path_to_toc = find_path_to_toc(file_path);
     let is_project = path_to_toc.is_some();
+    let mut project_config = project_config_for_file(file_path, path_to_toc.as_deref());
+    project_config.sanitize.target = render_target;
+
+    // Use a cached translation if possible.
+    let project_root = path_to_toc
+        .as_deref()
+        .map(|path_to_toc| project_root(file_path, path_to_toc));
+    let key = cache_key(file_path, file_contents, is_toc, &project_config);
+    if let Some(cached) = get_cached(project_root.as_deref(), key) {
+        return (
+            match cached {
+                CachedTranslation::CodeChat(codechat_for_web) => {
+                    TranslationResultsString::CodeChat(codechat_for_web)
+                }
+                CachedTranslation::Toc(html) => TranslationResultsString::Toc(html),
+            },
+            path_to_toc,
+        );
+    }
 
-    (
-        match source_to_codechat_for_web(file_contents, &ext.to_string(), is_toc, is_project) {
+    let translation_results_string =
+        match perf::time(Operation::Translate, file_contents.len(), || {
+            source_to_codechat_for_web(file_contents, &ext, is_toc, is_project, &project_config)
+        }) {
             TranslationResults::CodeChat(codechat_for_web) => {
                 if is_toc {
                     // For the table of contents sidebar, which is pure
@@ -550,23 +1060,159 @@ pub fn source_to_codechat_for_web_string(
             }
             TranslationResults::Unknown => TranslationResultsString::Unknown,
             TranslationResults::Err(err) => TranslationResultsString::Err(err),
-        },
-        path_to_toc,
-    )
+        };
+    // Record the anchors of a file shown in the Client; see `anchors.rs`.
+    if project_config.anchors
+        && !is_toc
+        && render_target == RenderTarget::Editor
+        && ext != NOTEBOOK_EXT
+    {
+        if let Err(err) = update_anchors(file_path, file_contents, &project_config) {
+            warn!("Unable to record anchors for {file_path:?}: {err}");
+        }
+    }
+    // Cache only successful translations.
+    match &translation_results_string {
+        TranslationResultsString::CodeChat(codechat_for_web) => insert_cached(
+            project_root.as_deref(),
+            key,
+            CachedTranslation::CodeChat(codechat_for_web.clone()),
+        ),
+        TranslationResultsString::Toc(html) => insert_cached(
+            project_root.as_deref(),
+            key,
+            CachedTranslation::Toc(html.clone()),
+        ),
+        _ => (),
+    }
+    (translation_results_string, path_to_toc)
+}
+
+/// Load the configuration of the project containing the provided file, given
+/// the path to the project's TOC (if the file is part of a project with a TOC);
+/// otherwise, the project's root is found using `find_project_root`. If there's
+/// no project or the configuration can't be loaded, use the defaults.
+pub fn project_config_for_file(file_path: &Path, path_to_toc: Option<&Path>) -> ProjectConfig {
+    let root = match path_to_toc {
+        Some(path_to_toc) => Some(project_root(file_path, path_to_toc)),
+        None => find_project_root(file_path),
+    };
+    let mut project_config = match &root {
+        Some(root) => load_project_config(root).unwrap_or_else(|err| {
+            warn!("{err} Using the default project configuration.");
+            ProjectConfig::default()
+        }),
+        None => ProjectConfig::default(),
+    };
+    if let (true, Some(path_to_toc)) = (project_config.numbering.chapter_numbers, path_to_toc) {
+        let toc_path = file_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(path_to_toc);
+        project_config.numbering.chapter = chapter_number(&toc_path, file_path);
+    }
+    project_config.file_language = FILE_LANGUAGES
+        .lock()
+        .unwrap()
+        .get(&file_language_key(file_path))
+        .cloned();
+    if let (true, Some(root)) = (project_config.anchors, &root) {
+        project_config.file_anchors = load_anchors(root, file_path);
+    }
+    project_config.conditions = conditions_for_file(file_path, &project_config.conditions);
+    project_config.file_dir = file_path.parent().map(Path::to_path_buf);
+    project_config.file_path = Some(file_path.to_path_buf());
+    project_config
+}
+
+/// Apply the transformations which must take place before the Markdown
+/// renderer sees a doc block. Each doc block is preprocessed separately, so that
+/// an unterminated construct in one doc block doesn't affect the next.
+fn preprocess_markdown(markdown: &str, project_config: &ProjectConfig) -> String {
+    let markdown = evaluate_conditionals(
+        markdown,
+        &project_config.conditions,
+        project_config.sanitize.target,
+    );
+    if project_config.markdown.admonitions {
+        fenced_admonitions_to_html(&markdown)
+    } else {
+        markdown
+    }
 }
 
 /// Convert markdown to HTML. (This assumes the Markdown defined in the
-/// CommonMark spec.)
-fn markdown_to_html(markdown: &str) -> String {
+/// CommonMark spec, plus the extensions selected by the project's
+/// configuration.)
+fn markdown_to_html(markdown: &str, project_config: &ProjectConfig) -> String {
+    // Only look for citations if the project provides a bibliography.
+    let parser = Parser::new_with_broken_link_callback(
+        markdown,
+        markdown_options(&project_config.markdown),
+        Some(citation_link_callback).filter(|_| project_config.bibliography.is_some()),
+    );
+    let mut citations = Citations::new(&project_config.bibliography_entries);
+    let mut events = number_labels(
+        citations.render(render_alerts(parser)),
+        project_config.numbering.chapter,
+    );
+    events = expand_macros(events, project_config);
+    if project_config.markdown.emoji {
+        events = expand_shortcodes(events, &project_config.shortcodes);
+    }
+    let events = embed_media(events);
+    let (events, rendered_fences) = render_fences(events, project_config);
+    let events = assign_heading_ids(events, &project_config.headings);
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    // Place the references (if any) at the end of the document.
+    html_output.push_str(&citations.references_html());
+    // The sanitizer removes the doc block separators; temporarily replace them
+    // with comments, which it keeps.
+    let html_output = sanitize_html(
+        &html_output.replace(DOC_BLOCK_SEPARATOR_HTML, DOC_BLOCK_SEPARATOR_COMMENT),
+        project_config.sanitize.level(),
+    )
+    .replace(DOC_BLOCK_SEPARATOR_COMMENT, DOC_BLOCK_SEPARATOR_HTML);
+    insert_rendered_fences(&html_output, &rendered_fences)
+}
+
+/// Translate the project's selection of Markdown extensions into the
+/// equivalent pulldown-cmark options.
+fn markdown_options(markdown_extensions: &MarkdownExtensions) -> Options {
     let mut options = Options::all();
     // Turndown (which converts HTML back to Markdown) doesn't support smart
-    // punctuation.
+    // punctuation; MathJax, rather than the Markdown renderer, handles math.
+    // Therefore, these are never enabled.
     options.remove(Options::ENABLE_SMART_PUNCTUATION);
     options.remove(Options::ENABLE_MATH);
-    let parser = Parser::new_ext(markdown, options);
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
-    html_output
+    for (is_enabled, option) in [
+        (markdown_extensions.tables, Options::ENABLE_TABLES),
+        // Old-style footnotes are a variant of footnotes, so disabling
+        // footnotes disables both.
+        (
+            markdown_extensions.footnotes,
+            Options::ENABLE_FOOTNOTES | Options::ENABLE_OLD_FOOTNOTES,
+        ),
+        (markdown_extensions.task_lists, Options::ENABLE_TASKLISTS),
+        (
+            markdown_extensions.strikethrough,
+            Options::ENABLE_STRIKETHROUGH,
+        ),
+        (
+            markdown_extensions.definition_lists,
+            Options::ENABLE_DEFINITION_LIST,
+        ),
+        (
+            markdown_extensions.heading_ids,
+            Options::ENABLE_HEADING_ATTRIBUTES,
+        ),
+        // GitHub-style alerts are the only GFM-specific extension.
+        (markdown_extensions.admonitions, Options::ENABLE_GFM),
+    ] {
+        options.set(option, is_enabled);
+    }
+    options
 }
 
 // Goal: make it easy to update the data structure. We update on every
@@ -782,22 +1428,34 @@ fn html_analyze(
 // ## Tests
 #[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, str::FromStr};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+    };
 
     use predicates::prelude::predicate::str;
 
-    use super::{find_path_to_toc, TranslationResults};
+    use super::citations::{BibEntry, Bibliography};
+    use super::project_config::{DocBlockMarkup, MarkdownExtensions, ProjectConfig, SanitizeLevel};
+    use super::{find_path_to_toc, find_project_root, TranslationResults};
     use super::{CodeChatForWeb, CodeMirror, CodeMirrorDocBlocks, SourceFileMetadata};
     use crate::lexer::{
-        compile_lexers, supported_languages::get_language_lexer_vec, CodeDocBlock, DocBlock,
+        compile_lexers, source_lexer, supported_languages::get_language_lexer_vec, CodeDocBlock,
+        DocBlock,
     };
     use crate::processing::{
-        code_doc_block_vec_to_source, code_mirror_to_code_doc_blocks, codechat_for_web_to_source,
+        change_between, code_doc_block_vec_to_source, code_mirror_to_code_doc_blocks,
+        codechat_for_web_to_source,
+        diff::DiffGranularity,
+        diff_code_mirror, find_lexer, find_markup, lexer_ext, set_file_language, source_lexer_diff,
         source_to_codechat_for_web,
+        workspace::{set_workspace_roots, WorkspaceRoot},
+        CodeMirrorDocBlockTransaction, CodeMirrorDocBlockUpdate, StringDiff, MAX_LEXED_LINE_LENGTH,
     };
     use crate::test_utils::stringit;
 
-    use crate::prep_test_dir;
+    use crate::{cast, prep_test_dir};
 
     // ### Utilities
     fn build_codechat_for_web(
@@ -809,6 +1467,7 @@ mod tests {
         CodeChatForWeb {
             metadata: SourceFileMetadata {
                 mode: mode.to_string(),
+                notebook: None,
             },
             source: CodeMirror {
                 doc: doc.to_string(),
@@ -866,6 +1525,24 @@ mod tests {
             Result::Ok("".to_string())
         );
 
+        // HTML in doc blocks is saved unchanged; the Client cleans up only
+        // pasted HTML.
+        let codechat_for_web = build_codechat_for_web(
+            "python",
+            "\n",
+            vec![build_codemirror_doc_block(
+                0,
+                0,
+                "",
+                "#",
+                "<span style=\"x\">Styled</span>\n",
+            )],
+        );
+        assert_eq!(
+            codechat_for_web_to_source(&codechat_for_web),
+            Result::Ok("# <span style=\"x\">Styled</span>\n".to_string())
+        );
+
         let codechat_for_web = build_codechat_for_web("undefined", "", vec![]);
         assert_eq!(
             codechat_for_web_to_source(&codechat_for_web),
@@ -1217,7 +1894,13 @@ mod tests {
         // A file with an unknown extension and no lexer, which is classified as
         // a text file.
         assert_eq!(
-            source_to_codechat_for_web("", &".xxx".to_string(), false, false),
+            source_to_codechat_for_web(
+                "",
+                &".xxx".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::Unknown
         );
 
@@ -1230,13 +1913,20 @@ mod tests {
                 &".xxx".to_string(),
                 false,
                 false,
+                &ProjectConfig::default(),
             ),
             TranslationResults::Err("<p>Unknown lexer type unknown.</p>".to_string())
         );
 
         // A CodeChat Editor document via filename.
         assert_eq!(
-            source_to_codechat_for_web("", &"md".to_string(), false, false),
+            source_to_codechat_for_web(
+                "",
+                &"md".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web("markdown", "", vec![]))
         );
 
@@ -1247,6 +1937,7 @@ mod tests {
                 &"xxx".to_string(),
                 false,
                 false,
+                &ProjectConfig::default(),
             ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "markdown",
@@ -1255,15 +1946,40 @@ mod tests {
             ))
         );
 
+        // A file with a line too long to lex is shown as code.
+        let long_line = format!("// Doc\n{}\n", "a".repeat(MAX_LEXED_LINE_LENGTH + 1));
+        assert_eq!(
+            source_to_codechat_for_web(
+                &long_line,
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
+            TranslationResults::CodeChat(build_codechat_for_web("javascript", &long_line, vec![]))
+        );
+
         // An empty source file.
         assert_eq!(
-            source_to_codechat_for_web("", &"js".to_string(), false, false),
+            source_to_codechat_for_web(
+                "",
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web("javascript", "", vec![]))
         );
 
         // A zero doc block source file.
         assert_eq!(
-            source_to_codechat_for_web("let a = 1;", &"js".to_string(), false, false),
+            source_to_codechat_for_web(
+                "let a = 1;",
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "let a = 1;",
@@ -1273,7 +1989,13 @@ mod tests {
 
         // One doc block source files.
         assert_eq!(
-            source_to_codechat_for_web("// Test", &"js".to_string(), false, false),
+            source_to_codechat_for_web(
+                "// Test",
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "\n",
@@ -1281,7 +2003,13 @@ mod tests {
             ))
         );
         assert_eq!(
-            source_to_codechat_for_web("let a = 1;\n// Test", &"js".to_string(), false, false,),
+            source_to_codechat_for_web(
+                "let a = 1;\n// Test",
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "let a = 1;\n\n",
@@ -1295,7 +2023,13 @@ mod tests {
             ))
         );
         assert_eq!(
-            source_to_codechat_for_web("// Test\nlet a = 1;", &"js".to_string(), false, false,),
+            source_to_codechat_for_web(
+                "// Test\nlet a = 1;",
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
                 "\nlet a = 1;",
@@ -1311,6 +2045,7 @@ mod tests {
                 &"js".to_string(),
                 false,
                 false,
+                &ProjectConfig::default(),
             ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "javascript",
@@ -1334,7 +2069,13 @@ mod tests {
         // - A doc block in the middle of the file
         // - A doc block with no trailing newline at the end of the file.
         assert_eq!(
-            source_to_codechat_for_web("//\n\n//\n\n//", &"cpp".to_string(), false, false),
+            source_to_codechat_for_web(
+                "//\n\n//\n\n//",
+                &"cpp".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "\n\n\n\n",
@@ -1348,7 +2089,13 @@ mod tests {
 
         // Test Unicode characters in code.
         assert_eq!(
-            source_to_codechat_for_web("; // σ\n//", &"cpp".to_string(), false, false),
+            source_to_codechat_for_web(
+                "; // σ\n//",
+                &"cpp".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "; // σ\n",
@@ -1358,7 +2105,13 @@ mod tests {
 
         // Test Unicode characters in strings.
         assert_eq!(
-            source_to_codechat_for_web("\"σ\";\n//", &"cpp".to_string(), false, false),
+            source_to_codechat_for_web(
+                "\"σ\";\n//",
+                &"cpp".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "\"σ\";\n",
@@ -1368,7 +2121,13 @@ mod tests {
 
         // Test a fenced code block that's unterminated.
         assert_eq!(
-            source_to_codechat_for_web("/* ```\n*/\n//", &"cpp".to_string(), false, false),
+            source_to_codechat_for_web(
+                "/* ```\n*/\n//",
+                &"cpp".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
             TranslationResults::CodeChat(build_codechat_for_web(
                 "c_cpp",
                 "\n\n",
@@ -1380,6 +2139,401 @@ mod tests {
         );
     }
 
+    // ### Tests for Markdown extensions
+    //
+    // For each extension, verify that:
+    //
+    // 1.  Enabling the extension renders its syntax; disabling it doesn't.
+    // 2.  Either way, the rendered HTML still splits into the same doc blocks.
+    // 3.  The extension's syntax survives a round trip from source code to doc
+    //     blocks and back.
+    #[test]
+    fn test_markdown_extensions() {
+        // Each entry is a function to disable an extension, a doc block using
+        // that extension, and a fragment of the HTML it should produce.
+        type ExtensionTest = (fn(&mut MarkdownExtensions), &'static str, &'static str);
+        let extension_tests: [ExtensionTest; 9] = [
+            (
+                |me| me.tables = false,
+                "| a | b |\n| - | - |\n| 1 | 2 |\n",
+                "<table>",
+            ),
+            (
+                |me| me.footnotes = false,
+                "Text[^1].\n\n[^1]: A footnote.\n",
+                "footnote-definition",
+            ),
+            (
+                |me| me.task_lists = false,
+                "- [x] Done\n",
+                r#"type="checkbox""#,
+            ),
+            (|me| me.strikethrough = false, "~~old~~\n", "<del>"),
+            (
+                |me| me.definition_lists = false,
+                "Term\n: Definition\n",
+                "<dl>",
+            ),
+            (
+                |me| me.heading_ids = false,
+                "# Heading {#my-id}\n",
+                r#"<h1 id="my-id">"#,
+            ),
+            (
+                |me| me.admonitions = false,
+                ":::note\nText\n:::\n",
+                r#"data-syntax="fence""#,
+            ),
+            (
+                |me| me.admonitions = false,
+                "> [!NOTE]\n> Text\n",
+                r#"data-syntax="gfm""#,
+            ),
+            (
+                |me| me.emoji = false,
+                "Launch :rocket:\n",
+                r#"data-shortcode="rocket""#,
+            ),
+        ];
+
+        for (disable, markdown, html_fragment) in extension_tests {
+            // Place the Markdown in one doc block, followed by code and a second
+            // doc block.
+            let source = format!(
+                "{}let a = 1;\n// Last\n",
+                markdown
+                    .split_inclusive('\n')
+                    .map(|line| if line == "\n" {
+                        "//\n".to_string()
+                    } else {
+                        format!("// {line}")
+                    })
+                    .collect::<String>()
+            );
+            let mut disabled_extensions = MarkdownExtensions::default();
+            disable(&mut disabled_extensions);
+            for (markdown_extensions, is_enabled) in [
+                (MarkdownExtensions::default(), true),
+                (disabled_extensions, false),
+            ] {
+                let project_config = ProjectConfig {
+                    markdown: markdown_extensions,
+                    ..Default::default()
+                };
+                let codechat_for_web = cast!(
+                    source_to_codechat_for_web(
+                        &source,
+                        &"js".to_string(),
+                        false,
+                        false,
+                        &project_config
+                    ),
+                    TranslationResults::CodeChat
+                );
+                let doc_blocks = &codechat_for_web.source.doc_blocks;
+                assert_eq!(doc_blocks.len(), 2, "{markdown}");
+                assert_eq!(
+                    doc_blocks[0].4.contains(html_fragment),
+                    is_enabled,
+                    "{markdown}"
+                );
+                assert_eq!(doc_blocks[1].4, "<p>Last</p>\n");
+            }
+
+            // Replace the rendered HTML with the original Markdown, as the
+            // Client does, then verify that this produces the original source.
+            let llc = compile_lexers(get_language_lexer_vec());
+            let js_lexer = llc.map_mode_to_lexer.get(&stringit("javascript")).unwrap();
+            assert_eq!(
+                code_doc_block_vec_to_source(&source_lexer(&source, js_lexer), js_lexer).unwrap(),
+                source
+            );
+        }
+    }
+
+    // The Client saves the Markdown that turndown then Prettier produce from
+    // the HTML of a doc block, which may differ from what the author wrote;
+    // HTML without a Markdown equivalent is saved as HTML. Make sure this
+    // Markdown keeps each extension.
+    #[test]
+    fn test_markdown_extensions_from_client() {
+        // Each entry is a doc block using an extension, the Markdown the Client
+        // produces from its HTML, and a fragment of the HTML it should produce.
+        let extension_tests = [
+            (
+                "| a | b |\n| - | - |\n| 1 | 2 |\n",
+                "| a   | b   |\n| --- | --- |\n| 1   | 2   |\n",
+                "<table>",
+            ),
+            (
+                "Text[^1].\n\n[^1]: A footnote.\n",
+                "Text<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup>.\n\n<div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n<p>A footnote.</p>\n</div>\n",
+                "footnote-definition",
+            ),
+            ("- [x] Done\n", "- [x] Done\n", r#"type="checkbox""#),
+            ("~~old~~\n", "~~old~~\n", "<del>"),
+            (
+                "Term\n: Definition\n",
+                "<dl>\n<dt>Term</dt>\n<dd>Definition</dd>\n</dl>\n",
+                "<dl>",
+            ),
+            (
+                "# Heading {#my-id}\n",
+                "# Heading {#my-id}\n",
+                r#"<h1 id="my-id">"#,
+            ),
+            (
+                ":::note Read me\nText\n:::\n",
+                ":::note Read me\n\nText\n\n:::\n",
+                r#"data-syntax="fence""#,
+            ),
+            (
+                "> [!NOTE]\n> Text\n",
+                "> [!NOTE]\n> Text\n",
+                r#"data-syntax="gfm""#,
+            ),
+            (
+                "Launch :rocket:\n",
+                "Launch :rocket:\n",
+                r#"data-shortcode="rocket""#,
+            ),
+        ];
+
+        let to_codechat_for_web = |source: &str| {
+            cast!(
+                source_to_codechat_for_web(
+                    source,
+                    &"js".to_string(),
+                    false,
+                    false,
+                    &ProjectConfig::default()
+                ),
+                TranslationResults::CodeChat
+            )
+        };
+        for (markdown, client_markdown, html_fragment) in extension_tests {
+            let source = format!(
+                "{}let a = 1;\n// Last\n",
+                markdown
+                    .split_inclusive('\n')
+                    .map(|line| if line == "\n" {
+                        "//\n".to_string()
+                    } else {
+                        format!("// {line}")
+                    })
+                    .collect::<String>()
+            );
+            let mut codechat_for_web = to_codechat_for_web(&source);
+            let doc_blocks = &mut codechat_for_web.source.doc_blocks;
+            assert_eq!(doc_blocks.len(), 2, "{markdown}");
+            doc_blocks[0].4 = client_markdown.to_string();
+            doc_blocks[1].4 = "Last\n".to_string();
+            let saved_source = codechat_for_web_to_source(&codechat_for_web).unwrap();
+
+            // Translating the saved source produces the same doc blocks.
+            let codechat_for_web = to_codechat_for_web(&saved_source);
+            let doc_blocks = &codechat_for_web.source.doc_blocks;
+            assert_eq!(doc_blocks.len(), 2, "{saved_source}");
+            assert!(doc_blocks[0].4.contains(html_fragment), "{saved_source}");
+            assert_eq!(doc_blocks[1].4, "<p>Last</p>\n");
+        }
+    }
+
+    #[test]
+    fn test_citations() {
+        let mut bibliography_entries = Bibliography::default();
+        bibliography_entries.0.insert(
+            "knuth84".to_string(),
+            BibEntry {
+                authors: vec![("Knuth".to_string(), "Donald".to_string())],
+                year: Some("1984".to_string()),
+                ..Default::default()
+            },
+        );
+        let source = "// See [@knuth84].\nlet a = 1;\n// Last\n";
+
+        // Without a bibliography, citations are plain text.
+        let codechat_for_web = cast!(
+            source_to_codechat_for_web(
+                source,
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
+            TranslationResults::CodeChat
+        );
+        assert_eq!(
+            codechat_for_web.source.doc_blocks[0].4,
+            "<p>See [@knuth84].</p>\n"
+        );
+
+        // With one, citations link to the references, which are placed at the
+        // end of the last doc block.
+        let project_config = ProjectConfig {
+            bibliography: Some(PathBuf::from("refs.bib")),
+            bibliography_entries,
+            ..Default::default()
+        };
+        let codechat_for_web = cast!(
+            source_to_codechat_for_web(source, &"js".to_string(), false, false, &project_config),
+            TranslationResults::CodeChat
+        );
+        let doc_blocks = &codechat_for_web.source.doc_blocks;
+        assert_eq!(
+            doc_blocks[0].4,
+            "<p>See <span class=\"CodeChat-citation\" data-cite=\"@knuth84\">[<a href=\"#CodeChat-ref-knuth84\">Knuth 1984</a>]</span>.</p>\n"
+        );
+        assert!(doc_blocks[1]
+            .4
+            .starts_with("<p>Last</p>\n<div class=\"CodeChat-references\">"));
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let source = "// A <b onclick=\"f()\">b</b><script>f()</script>\nlet a = 1;\n// Last\n";
+        let mut project_config = ProjectConfig::default();
+        for (sanitize_level, html) in [
+            (SanitizeLevel::Relaxed, "<p>A <b>b</b></p>\n"),
+            (
+                SanitizeLevel::Off,
+                "<p>A <b onclick=\"f()\">b</b><script>f()</script></p>\n",
+            ),
+        ] {
+            project_config.sanitize.editor = sanitize_level;
+            let codechat_for_web = cast!(
+                source_to_codechat_for_web(
+                    source,
+                    &"js".to_string(),
+                    false,
+                    false,
+                    &project_config
+                ),
+                TranslationResults::CodeChat
+            );
+            // Sanitizing doesn't affect the boundaries between doc blocks.
+            let doc_blocks = &codechat_for_web.source.doc_blocks;
+            assert_eq!(doc_blocks.len(), 2);
+            assert_eq!(doc_blocks[0].4, html);
+            assert_eq!(doc_blocks[1].4, "<p>Last</p>\n");
+        }
+    }
+
+    // By default, the Client receives the author's raw HTML unchanged, so an
+    // Update from the Client saves it unchanged.
+    #[test]
+    fn test_sanitize_round_trip() {
+        let raw_html = [
+            "<span style=\"color: red\" onclick=\"f()\">red</span>",
+            "<video controls src=\"a.mp4\"></video>",
+            "<svg width=\"10\"><circle r=\"5\"/></svg>",
+        ];
+        let source = format!(
+            "// A {}\n//\n// {}\n//\n// {}\nlet a = 1;\n// Last\n",
+            raw_html[0], raw_html[1], raw_html[2]
+        );
+        let mut codechat_for_web = cast!(
+            source_to_codechat_for_web(
+                &source,
+                &"js".to_string(),
+                false,
+                false,
+                &ProjectConfig::default()
+            ),
+            TranslationResults::CodeChat
+        );
+        let doc_blocks = &mut codechat_for_web.source.doc_blocks;
+        assert_eq!(doc_blocks.len(), 2);
+        for html in raw_html {
+            assert!(doc_blocks[0].4.contains(html), "{html}");
+        }
+
+        // Replace the rendered HTML with the Markdown the Client produces from
+        // it, which keeps raw HTML as is.
+        doc_blocks[0].4 = format!("A {}\n\n{}\n\n{}\n", raw_html[0], raw_html[1], raw_html[2]);
+        doc_blocks[1].4 = "Last\n".to_string();
+        assert_eq!(codechat_for_web_to_source(&codechat_for_web), Ok(source));
+    }
+
+    #[test]
+    fn test_find_lexer() {
+        let lexer_name = |file_contents: &str, ext: &str, project_config: &ProjectConfig| {
+            find_lexer(file_contents, &ext.to_string(), project_config)
+                .unwrap()
+                .map(|llc| llc.language_lexer.lexer_name.to_string())
+        };
+        let mut project_config = ProjectConfig::default();
+
+        // The extension selects the lexer; when several lexers support an
+        // extension, the contents select between them.
+        assert_eq!(
+            lexer_name("", "py", &project_config),
+            Some("python".to_string())
+        );
+        assert_eq!(lexer_name("", "unknown", &project_config), None);
+        assert_eq!(
+            lexer_name("module a;\nendmodule\n", "v", &project_config),
+            Some("verilog".to_string())
+        );
+        assert_eq!(
+            lexer_name("fn main() {\n}\n", "v", &project_config),
+            Some("".to_string())
+        );
+
+        // The project's configuration overrides the extension.
+        project_config
+            .languages
+            .insert("v".to_string(), "verilog".to_string());
+        assert_eq!(
+            lexer_name("fn main() {\n}\n", "v", &project_config),
+            Some("verilog".to_string())
+        );
+        // A `SetFileLanguage` message overrides the configuration.
+        project_config.file_language = Some("python".to_string());
+        assert_eq!(
+            lexer_name("", "v", &project_config),
+            Some("python".to_string())
+        );
+        // A lexer directive overrides everything.
+        assert_eq!(
+            lexer_name("// CodeChat Editor lexer: rust", "v", &project_config),
+            Some("rust".to_string())
+        );
+        // Unknown lexers are an error.
+        project_config.file_language = Some("unknown".to_string());
+        assert!(find_lexer("", &"v".to_string(), &project_config).is_err());
+        assert!(set_file_language(&PathBuf::from("foo.v"), Some("unknown".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_find_markup() {
+        let mut project_config = ProjectConfig::default();
+        assert_eq!(
+            find_markup("", &project_config),
+            Ok(DocBlockMarkup::Markdown)
+        );
+        project_config.doc_block_markup = DocBlockMarkup::ReStructuredText;
+        assert_eq!(
+            find_markup("", &project_config),
+            Ok(DocBlockMarkup::ReStructuredText)
+        );
+        // A markup directive overrides the project's configuration.
+        assert_eq!(
+            find_markup("# CodeChat Editor markup: asciidoc", &project_config),
+            Ok(DocBlockMarkup::AsciiDoc)
+        );
+        assert!(find_markup("# CodeChat Editor markup: unknown", &project_config).is_err());
+    }
+
+    #[test]
+    fn test_lexer_ext() {
+        assert_eq!(lexer_ext(Path::new("a/foo.py")), "py");
+        // Some files are identified by their name.
+        assert_eq!(lexer_ext(Path::new("a/Makefile")), "Makefile");
+        assert_eq!(lexer_ext(Path::new(".editorconfig")), ".editorconfig");
+        assert_eq!(lexer_ext(Path::new("a/README")), "");
+    }
+
     #[test]
     fn test_find_path_to_toc_1() {
         let (temp_dir, test_dir) = prep_test_dir!();
@@ -1397,7 +2551,146 @@ mod tests {
         let fp = find_path_to_toc(&test_dir.join("3/bar/baz/foo.py"));
         assert_eq!(fp, Some(PathBuf::from_str("../../toc.md").unwrap()));
 
+        // Test 4: the search stops at the root of the workspace containing the
+        // file.
+        let roots = vec![WorkspaceRoot {
+            path: test_dir.join("3/bar"),
+            trusted: true,
+        }];
+        set_workspace_roots("test_find_path_to_toc_1", roots);
+        let fp = find_path_to_toc(&test_dir.join("3/bar/baz/foo.py"));
+        set_workspace_roots("test_find_path_to_toc_1", Vec::new());
+        assert_eq!(fp, None);
+
         // Report any errors produced when removing the temporary directory.
         temp_dir.close().unwrap();
     }
+
+    #[test]
+    fn test_find_project_root() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        // Git won't store a `.git` directory in a fixture, so create these
+        // here.
+        fs::create_dir(test_dir.join("config/sub/.git")).unwrap();
+        fs::create_dir(test_dir.join("git/.git")).unwrap();
+
+        // A `toc.md` takes precedence over a nearer configuration file...
+        assert_eq!(
+            find_project_root(&test_dir.join("toc/sub/foo.py")),
+            Some(test_dir.join("toc"))
+        );
+        // ...which takes precedence over a nearer Git repository.
+        assert_eq!(
+            find_project_root(&test_dir.join("config/sub/foo.py")),
+            Some(test_dir.join("config"))
+        );
+        assert_eq!(
+            find_project_root(&test_dir.join("git/sub/foo.py")),
+            Some(test_dir.join("git"))
+        );
+        // No markers, so no project. (We assume all temp directory parents
+        // lack markers as well.)
+        assert_eq!(find_project_root(&test_dir.join("none/foo.py")), None);
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_diff_code_mirror() {
+        let before = CodeMirror {
+            doc: "\n\n".to_string(),
+            doc_blocks: vec![
+                (0, 0, "".to_string(), "#".to_string(), "one\n".to_string()),
+                (1, 1, "".to_string(), "#".to_string(), "two\n".to_string()),
+            ],
+        };
+        // Changing one doc block sends only the changes to that block.
+        let mut after = before.clone();
+        after.doc_blocks[1].4 = "three\n".to_string();
+        let diff = diff_code_mirror(&before, &after, DiffGranularity::Word);
+        assert_eq!(diff.doc, vec![]);
+        assert_eq!(
+            diff.doc_blocks,
+            vec![CodeMirrorDocBlockTransaction::Update(
+                1,
+                CodeMirrorDocBlockUpdate {
+                    from: 1,
+                    to: 1,
+                    indent: None,
+                    delimiter: None,
+                    contents: vec![StringDiff {
+                        from: 0,
+                        to: 3,
+                        insert: "three".to_string()
+                    }]
+                }
+            )]
+        );
+        // A changed delimiter is sent.
+        let mut after_delim = before.clone();
+        after_delim.doc_blocks[0].3 = "//".to_string();
+        assert_eq!(
+            diff_code_mirror(&before, &after_delim, DiffGranularity::Word).doc_blocks,
+            vec![CodeMirrorDocBlockTransaction::Update(
+                0,
+                CodeMirrorDocBlockUpdate {
+                    from: 0,
+                    to: 0,
+                    indent: None,
+                    delimiter: Some("//".to_string()),
+                    contents: vec![]
+                }
+            )]
+        );
+
+        // Removing a doc block replaces them all.
+        after.doc = "\n".to_string();
+        after.doc_blocks.pop();
+        let diff = diff_code_mirror(&before, &after, DiffGranularity::Word);
+        assert_eq!(
+            diff.doc,
+            vec![StringDiff {
+                from: 1,
+                to: 2,
+                insert: "".to_string()
+            }]
+        );
+        assert_eq!(
+            diff.doc_blocks,
+            vec![
+                CodeMirrorDocBlockTransaction::Delete(0),
+                CodeMirrorDocBlockTransaction::Delete(1),
+                CodeMirrorDocBlockTransaction::Add(after.doc_blocks[0].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_lexer_diff() {
+        let llc = compile_lexers(get_language_lexer_vec());
+        let py_lexer = llc.map_mode_to_lexer.get(&stringit("python")).unwrap();
+        // Character offsets differ from byte offsets after the non-ASCII
+        // characters.
+        let before = "é = 1\n# ü doc\nb = 2\n";
+        for after in [
+            "é = 1\n# ü doc\nb = 3\n",
+            "é = 1\n# ü dóc\n# more\nb = 2\n",
+            "é = 1\nb = 2\n",
+        ] {
+            let diff = change_between(before, after);
+            assert_eq!(
+                source_lexer_diff(before, source_lexer(before, py_lexer), &diff, py_lexer),
+                source_lexer(after, py_lexer)
+            );
+        }
+        assert_eq!(
+            change_between("aéb", "aüüb"),
+            StringDiff {
+                from: 1,
+                to: 2,
+                insert: "üü".to_string()
+            }
+        );
+    }
 }
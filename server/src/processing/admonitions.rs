@@ -0,0 +1,257 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `admonitions.rs` -- Render callouts and admonitions in doc blocks
+///
+/// Two syntaxes are supported:
+///
+/// - GitHub-style alerts, such as `> [!NOTE]`. The Markdown renderer
+///   recognizes these; this module replaces the resulting block quote with an
+///   admonition.
+/// - Fenced admonitions, such as `:::warning Optional title`, ended by a line
+///   containing only `:::`. These are translated to HTML before the Markdown
+///   renderer sees them.
+///
+/// Both produce the same HTML: a `<div class="CodeChat-admonition
+/// CodeChat-admonition-kind">` whose first child is a title paragraph. The
+/// `data-syntax` attribute records the original syntax, allowing the Client to
+/// translate the admonition back to the Markdown it came from.
+// ## Imports
+//
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{BlockQuoteKind, Event, Tag, TagEnd};
+use regex::Regex;
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    fence_scanner::{fence_lines, FenceLine},
+};
+
+// ## Globals
+lazy_static! {
+    /// Match the text following the colons which open a fenced admonition: the
+    /// kind of admonition, then an optional title.
+    static ref ADMONITION_REGEX: Regex = Regex::new(r"^([A-Za-z][\w-]*)[ \t]*(.*)$").unwrap();
+}
+
+// ## Code
+//
+// ### Fenced admonitions
+/// Translate fenced admonitions in the provided Markdown to HTML blocks which
+/// surround the (still Markdown) contents of the admonition. Fences inside
+/// code blocks are ignored; any unterminated admonitions are closed at the end
+/// of the provided Markdown.
+pub fn fenced_admonitions_to_html(markdown: &str) -> String {
+    // Avoid any work in the common case.
+    if !markdown.contains(":::") {
+        return markdown.to_string();
+    }
+
+    let mut html = String::with_capacity(markdown.len());
+    // The number of admonitions which are currently open.
+    let mut open_count = 0;
+    for (line, fence_line) in fence_lines(markdown) {
+        match fence_line {
+            FenceLine::BlockOpen(text) => {
+                let captures = ADMONITION_REGEX.captures(text).unwrap();
+                let kind = captures[1].to_lowercase();
+                let title = &captures[2];
+                open_count += 1;
+                html.push_str(&admonition_open_html(
+                    &kind,
+                    if title.is_empty() {
+                        default_title(&kind)
+                    } else {
+                        escape(title, EscapeContext::Text)
+                    }
+                    .as_str(),
+                    "fence",
+                ));
+                html.push('\n');
+            }
+            FenceLine::BlockClose if open_count > 0 => {
+                open_count -= 1;
+                html.push_str("\n</div>\n\n");
+            }
+            _ => html.push_str(line),
+        }
+    }
+
+    // Close any unterminated admonitions.
+    for _ in 0..open_count {
+        html.push_str("\n\n</div>\n");
+    }
+    html
+}
+
+// ### GitHub-style alerts
+/// Replace the block quotes produced by GitHub-style alerts in the provided
+/// stream of Markdown events with admonitions.
+pub fn render_alerts<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+) -> impl Iterator<Item = Event<'a>> {
+    // For each block quote currently open, true if it's an alert.
+    let mut block_quote_stack: Vec<bool> = Vec::new();
+    events.map(move |event| match event {
+        Event::Start(Tag::BlockQuote(kind)) => {
+            block_quote_stack.push(kind.is_some());
+            match kind {
+                Some(kind) => {
+                    let kind = block_quote_kind_name(kind);
+                    Event::Html(admonition_open_html(kind, &default_title(kind), "gfm").into())
+                }
+                None => Event::Start(Tag::BlockQuote(None)),
+            }
+        }
+        Event::End(TagEnd::BlockQuote { .. }) if block_quote_stack.pop() == Some(true) => {
+            Event::Html("</div>\n".into())
+        }
+        _ => event,
+    })
+}
+
+// ### Support functions
+/// Return the lowercase name for the provided kind of alert.
+fn block_quote_kind_name(kind: BlockQuoteKind) -> &'static str {
+    match kind {
+        BlockQuoteKind::Note => "note",
+        BlockQuoteKind::Tip => "tip",
+        BlockQuoteKind::Important => "important",
+        BlockQuoteKind::Warning => "warning",
+        BlockQuoteKind::Caution => "caution",
+    }
+}
+
+/// Produce the title used when an admonition doesn't provide one: the
+/// capitalized kind of the admonition.
+fn default_title(kind: &str) -> String {
+    let mut chars = kind.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Produce the HTML which opens an admonition. The `title` must already be
+/// HTML-escaped.
+fn admonition_open_html(kind: &str, title: &str, syntax: &str) -> String {
+    format!(
+        "<div class=\"CodeChat-admonition CodeChat-admonition-{kind}\" data-kind=\"{kind}\" data-syntax=\"{syntax}\">\n<p class=\"CodeChat-admonition-title\">{title}</p>\n"
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::{fenced_admonitions_to_html, render_alerts};
+
+    fn render(markdown: &str) -> String {
+        let markdown = fenced_admonitions_to_html(markdown);
+        let parser = Parser::new_ext(&markdown, Options::ENABLE_GFM);
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, render_alerts(parser));
+        html_output
+    }
+
+    #[test]
+    fn test_fenced_admonitions() {
+        assert_eq!(
+            render(indoc!(
+                "
+                :::warning Be <careful>
+                Some *text*.
+                :::
+                "
+            )),
+            indoc!(
+                r#"
+                <div class="CodeChat-admonition CodeChat-admonition-warning" data-kind="warning" data-syntax="fence">
+                <p class="CodeChat-admonition-title">Be &lt;careful&gt;</p>
+                <p>Some <em>text</em>.</p>
+                </div>
+                "#
+            )
+        );
+
+        // The default title is the kind; nested admonitions work; unterminated
+        // admonitions are closed.
+        assert_eq!(
+            render(indoc!(
+                "
+                ::: Note
+                Outer.
+
+                :::tip
+                Inner.
+                :::
+                "
+            )),
+            indoc!(
+                r#"
+                <div class="CodeChat-admonition CodeChat-admonition-note" data-kind="note" data-syntax="fence">
+                <p class="CodeChat-admonition-title">Note</p>
+                <p>Outer.</p>
+                <div class="CodeChat-admonition CodeChat-admonition-tip" data-kind="tip" data-syntax="fence">
+                <p class="CodeChat-admonition-title">Tip</p>
+                <p>Inner.</p>
+                </div>
+                </div>
+                "#
+            )
+        );
+
+        // Fences inside code blocks are ignored, as are unmatched closing
+        // fences.
+        let markdown = indoc!(
+            "
+            ```
+            :::note
+            ```
+            :::
+            "
+        );
+        assert_eq!(fenced_admonitions_to_html(markdown), markdown);
+    }
+
+    #[test]
+    fn test_alerts() {
+        assert_eq!(
+            render(indoc!(
+                "
+                > [!IMPORTANT]
+                > Read this.
+
+                > A quote.
+                "
+            )),
+            indoc!(
+                r#"
+                <div class="CodeChat-admonition CodeChat-admonition-important" data-kind="important" data-syntax="gfm">
+                <p class="CodeChat-admonition-title">Important</p>
+                <p>Read this.</p>
+                </div>
+                <blockquote>
+                <p>A quote.</p>
+                </blockquote>
+                "#
+            )
+        );
+    }
+}
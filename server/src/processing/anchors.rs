@@ -0,0 +1,474 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `anchors.rs` -- Anchor doc blocks to the code they describe
+///
+/// A doc block usually describes the code which follows it. Changes made
+/// outside the CodeChat Editor, such as merging a branch which moves a
+/// function, may separate the two. When a project enables anchoring, the
+/// Server records the first line of code following each doc block -- its
+/// signature, such as `def parse_args():` -- in the project's
+/// `.codechat/anchors.json`. When the Server next translates the file, a doc
+/// block no longer followed by its signature is moved back to the line
+/// containing this signature. If the signature is missing or appears more than
+/// once, the doc block stays put and a warning is reported instead.
+///
+/// The anchors of a file are recorded when the Client saves it, and after a
+/// translation which resolved every anchor (including the first translation,
+/// when there are no anchors to resolve).
+// ## Imports
+//
+// ### Standard library
+use std::{collections::BTreeMap, fs, path::Path, sync::Mutex};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{
+    diagnostics::{Diagnostic, Severity},
+    find_file_lexer, find_long_line, find_path_to_toc, find_project_root,
+    project_config::ProjectConfig,
+    project_config_for_file,
+};
+use crate::lexer::{source_lexer, CodeDocBlock, DocBlock, LanguageLexerCompiled};
+
+// ## Globals
+/// The file, relative to a project's root, which stores its anchors.
+pub const ANCHORS_FILE_NAME: &str = ".codechat/anchors.json";
+
+lazy_static! {
+    // Serialize updates to the anchors file, since files may be translated on
+    // several threads at once.
+    static ref ANCHORS_FILE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+// ## Data structures
+/// The code a doc block describes.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Anchor {
+    /// The first non-blank line of the doc block, which identifies it.
+    pub doc_block: String,
+    /// The first non-blank line of code following the doc block, without its
+    /// indent.
+    pub signature: String,
+}
+
+// A source file, split into lines of code and doc blocks, so that doc blocks
+// may be moved between lines of code.
+enum Item {
+    // One line of code, including its newline.
+    Code(String),
+    // A doc block, along with a warning if its anchor wasn't resolved.
+    Doc(DocBlock, Option<String>),
+}
+
+// ## Code
+/// Return the anchors of the doc blocks in `code_doc_blocks`. Doc blocks which
+/// aren't followed by code have no anchor.
+pub fn find_anchors(code_doc_blocks: &[CodeDocBlock]) -> Vec<Anchor> {
+    code_doc_blocks
+        .iter()
+        .zip(code_doc_blocks.iter().skip(1))
+        .filter_map(|pair| match pair {
+            (CodeDocBlock::DocBlock(doc_block), CodeDocBlock::CodeBlock(code)) => Some(Anchor {
+                doc_block: first_line(&doc_block.contents)?,
+                signature: first_line(code)?,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// Return the first non-blank line of `text`, without surrounding whitespace.
+fn first_line(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+/// Move each doc block which is no longer followed by its signature back to
+/// this signature. Returns the resulting code and doc blocks, along with the
+/// line (numbered from 1) of and a warning for each doc block whose anchor
+/// couldn't be resolved.
+pub fn reattach(
+    code_doc_blocks: Vec<CodeDocBlock>,
+    anchors: &[Anchor],
+) -> (Vec<CodeDocBlock>, Vec<(usize, String)>) {
+    let mut items = Vec::new();
+    for code_doc_block in code_doc_blocks {
+        match code_doc_block {
+            CodeDocBlock::CodeBlock(code) => items.extend(
+                code.split_inclusive('\n')
+                    .map(|line| Item::Code(line.to_string())),
+            ),
+            CodeDocBlock::DocBlock(doc_block) => items.push(Item::Doc(doc_block, None)),
+        }
+    }
+
+    for anchor in anchors {
+        // Find the doc block; if it was edited or removed, its anchor no
+        // longer applies.
+        let Some(doc_index) = items.iter().position(|item| match item {
+            Item::Doc(doc_block, _) => {
+                first_line(&doc_block.contents).as_ref() == Some(&anchor.doc_block)
+            }
+            Item::Code(_) => false,
+        }) else {
+            continue;
+        };
+        // See if the signature still follows it.
+        let following_code = items[doc_index + 1..]
+            .iter()
+            .map_while(|item| match item {
+                Item::Code(line) => Some(line.trim()),
+                Item::Doc(..) => None,
+            })
+            .find(|line| !line.is_empty());
+        if following_code == Some(anchor.signature.as_str()) {
+            continue;
+        }
+        let signature_indexes: Vec<usize> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| match item {
+                Item::Code(line) if line.trim() == anchor.signature => Some(index),
+                _ => None,
+            })
+            .collect();
+        match signature_indexes[..] {
+            // Move the doc block to just before its signature, matching the
+            // signature's indent.
+            [signature_index] => {
+                let Item::Doc(mut doc_block, _) = items.remove(doc_index) else {
+                    unreachable!();
+                };
+                let signature_index = if signature_index > doc_index {
+                    signature_index - 1
+                } else {
+                    signature_index
+                };
+                if let Item::Code(line) = &items[signature_index] {
+                    doc_block.indent = line[..line.len() - line.trim_start().len()].to_string();
+                }
+                items.insert(signature_index, Item::Doc(doc_block, None));
+            }
+            [] => {
+                if let Item::Doc(_, warning) = &mut items[doc_index] {
+                    *warning = Some(format!(
+                        "This doc block describes `{}`, which is no longer in this file.",
+                        anchor.signature
+                    ));
+                }
+            }
+            _ => {
+                if let Item::Doc(_, warning) = &mut items[doc_index] {
+                    *warning = Some(format!(
+                        "This doc block describes `{}`, which now appears more than once in this file; the doc block wasn't moved.",
+                        anchor.signature
+                    ));
+                }
+            }
+        }
+    }
+
+    // Join the lines of code back into code blocks.
+    let mut code_doc_blocks = Vec::new();
+    let mut warnings = Vec::new();
+    let mut line = 1;
+    for item in items {
+        match item {
+            Item::Code(code) => {
+                line += 1;
+                if let Some(CodeDocBlock::CodeBlock(code_block)) = code_doc_blocks.last_mut() {
+                    code_block.push_str(&code);
+                } else {
+                    code_doc_blocks.push(CodeDocBlock::CodeBlock(code));
+                }
+            }
+            Item::Doc(doc_block, warning) => {
+                if let Some(warning) = warning {
+                    warnings.push((line, warning));
+                }
+                line += doc_block.lines;
+                code_doc_blocks.push(CodeDocBlock::DocBlock(doc_block));
+            }
+        }
+    }
+    (code_doc_blocks, warnings)
+}
+
+/// Report each doc block in the provided file whose anchor can't be resolved.
+pub fn anchor_diagnostics(
+    file_contents: &str,
+    lexer: &LanguageLexerCompiled,
+    anchors: &[Anchor],
+) -> Vec<Diagnostic> {
+    reattach(source_lexer(file_contents, lexer), anchors)
+        .1
+        .into_iter()
+        .map(|(line, warning)| Diagnostic::on_line(Severity::Warning, line, warning, "anchor"))
+        .collect()
+}
+
+/// Return the anchors recorded for the provided file, part of the project at
+/// `project_root`.
+pub fn load_anchors(project_root: &Path, file_path: &Path) -> Vec<Anchor> {
+    let _lock = ANCHORS_FILE_LOCK.lock().unwrap();
+    read_anchors_file(project_root)
+        .remove(&anchors_key(project_root, file_path))
+        .unwrap_or_default()
+}
+
+/// Record the anchors of the provided file, if its project enables anchoring.
+/// Call this when the Client saves the file.
+pub fn record_anchors(file_path: &Path, file_contents: &str) -> Result<(), String> {
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    if !project_config.anchors {
+        return Ok(());
+    }
+    save_anchors(file_path, file_contents, &project_config, &[])
+}
+
+/// After translating the provided file, record its anchors if every anchor
+/// was resolved.
+pub fn update_anchors(
+    file_path: &Path,
+    file_contents: &str,
+    project_config: &ProjectConfig,
+) -> Result<(), String> {
+    save_anchors(
+        file_path,
+        file_contents,
+        project_config,
+        &project_config.file_anchors,
+    )
+}
+
+// Resolve the provided anchors in the provided file; if they're all resolved,
+// record the anchors of the result.
+fn save_anchors(
+    file_path: &Path,
+    file_contents: &str,
+    project_config: &ProjectConfig,
+    anchors: &[Anchor],
+) -> Result<(), String> {
+    let Some(project_root) = find_project_root(file_path) else {
+        return Ok(());
+    };
+    let lexer = find_file_lexer(file_contents, file_path, project_config)?;
+    // Only source files have code to anchor doc blocks to.
+    if lexer.language_lexer.lexer_name.as_str() == "markdown"
+        || find_long_line(file_contents).is_some()
+    {
+        return Ok(());
+    }
+    let (code_doc_blocks, warnings) = reattach(source_lexer(file_contents, lexer), anchors);
+    if !warnings.is_empty() {
+        return Ok(());
+    }
+    let new_anchors = find_anchors(&code_doc_blocks);
+
+    let _lock = ANCHORS_FILE_LOCK.lock().unwrap();
+    let mut all_anchors = read_anchors_file(&project_root);
+    let key = anchors_key(&project_root, file_path);
+    if all_anchors.get(&key) == Some(&new_anchors) {
+        return Ok(());
+    }
+    if new_anchors.is_empty() {
+        all_anchors.remove(&key);
+    } else {
+        all_anchors.insert(key, new_anchors);
+    }
+    let anchors_path = project_root.join(ANCHORS_FILE_NAME);
+    if let Some(anchors_dir) = anchors_path.parent() {
+        fs::create_dir_all(anchors_dir)
+            .map_err(|err| format!("Unable to create {anchors_dir:?}: {err}."))?;
+    }
+    let anchors_json = serde_json::to_string_pretty(&all_anchors)
+        .map_err(|err| format!("Unable to serialize anchors: {err}."))?;
+    fs::write(&anchors_path, anchors_json)
+        .map_err(|err| format!("Unable to write {anchors_path:?}: {err}."))
+}
+
+// Read the anchors of all files in a project, indexed by `anchors_key`. A
+// missing or invalid file has no anchors.
+fn read_anchors_file(project_root: &Path) -> BTreeMap<String, Vec<Anchor>> {
+    fs::read_to_string(project_root.join(ANCHORS_FILE_NAME))
+        .ok()
+        .and_then(|anchors_json| serde_json::from_str(&anchors_json).ok())
+        .unwrap_or_default()
+}
+
+// The key for a file's anchors: its path relative to the project's root, using
+// forward slashes.
+fn anchors_key(project_root: &Path, file_path: &Path) -> String {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let file_path = canonical(file_path);
+    let relative_path = file_path
+        .strip_prefix(canonical(project_root))
+        .unwrap_or(&file_path);
+    relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+    use indoc::indoc;
+
+    use super::{find_anchors, reattach, record_anchors, Anchor};
+    use crate::lexer::{language_by_name, source_lexer, CodeDocBlock};
+    use crate::processing::{
+        code_doc_block_vec_to_source, diagnostics::check_file, project_config::RenderTarget,
+        source_to_codechat_for_web_string, TranslationResultsString,
+    };
+
+    fn lex(source: &str) -> Vec<CodeDocBlock> {
+        source_lexer(source, language_by_name("python").unwrap())
+    }
+
+    fn to_source(code_doc_blocks: Vec<CodeDocBlock>) -> String {
+        code_doc_block_vec_to_source(&code_doc_blocks, language_by_name("python").unwrap()).unwrap()
+    }
+
+    fn anchor(doc_block: &str, signature: &str) -> Anchor {
+        Anchor {
+            doc_block: doc_block.to_string(),
+            signature: signature.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_anchors() {
+        assert_eq!(
+            find_anchors(&lex(indoc!(
+                "
+                # Parse the arguments.
+                def parse_args():
+                    # Done.
+                    return 1
+                # Nothing follows this.
+                "
+            ))),
+            vec![
+                anchor("Parse the arguments.", "def parse_args():"),
+                anchor("Done.", "return 1")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reattach() {
+        let anchors = vec![anchor("Run it.", "def run():")];
+        // A doc block still followed by its signature doesn't move.
+        let source = "# Run it.\ndef run():\n    pass\n";
+        let (code_doc_blocks, warnings) = reattach(lex(source), &anchors);
+        assert_eq!(to_source(code_doc_blocks), source);
+        assert!(warnings.is_empty());
+
+        // A doc block separated from its signature moves back to it, taking
+        // the signature's indent.
+        let (code_doc_blocks, warnings) = reattach(
+            lex(indoc!(
+                "
+                # Run it.
+                x = 1
+
+                class A:
+                    def run():
+                        pass
+                "
+            )),
+            &anchors,
+        );
+        assert_eq!(
+            to_source(code_doc_blocks),
+            indoc!(
+                "
+                x = 1
+
+                class A:
+                    # Run it.
+                    def run():
+                        pass
+                "
+            )
+        );
+        assert!(warnings.is_empty());
+
+        // Missing and duplicated signatures produce warnings.
+        let (_, warnings) = reattach(lex("x = 1\n# Run it.\ny = 2\n"), &anchors);
+        assert_eq!(
+            warnings,
+            vec![(
+                2,
+                "This doc block describes `def run():`, which is no longer in this file."
+                    .to_string()
+            )]
+        );
+        let (code_doc_blocks, warnings) =
+            reattach(lex("# Run it.\nx = 1\ndef run():\ndef run():\n"), &anchors);
+        assert_eq!(
+            to_source(code_doc_blocks),
+            "# Run it.\nx = 1\ndef run():\ndef run():\n"
+        );
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_anchoring() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("foo.py");
+        fs::write(temp_dir.path().join("toc.md"), "[foo](foo.py)\n").unwrap();
+        fs::write(
+            temp_dir.path().join("codechat.json"),
+            r#"{"anchors": true}"#,
+        )
+        .unwrap();
+        let original = "# Run it.\ndef run():\n    pass\nx = 1\n";
+        fs::write(&file_path, original).unwrap();
+        record_anchors(&file_path, original).unwrap();
+        assert!(temp_dir.path().join(".codechat/anchors.json").is_file());
+
+        // The code moved, but its doc block didn't. Translation moves the doc
+        // block back to its code.
+        let moved = "# Run it.\nx = 1\ndef run():\n    pass\n";
+        let (translation, _) =
+            source_to_codechat_for_web_string(moved, &file_path, false, RenderTarget::Editor);
+        let TranslationResultsString::CodeChat(codechat_for_web) = translation else {
+            panic!("Expected a CodeChat translation.");
+        };
+        assert_eq!(
+            codechat_for_web.source.doc,
+            "x = 1\n\ndef run():\n    pass\n"
+        );
+        assert_eq!(codechat_for_web.source.doc_blocks[0].0, 6);
+        assert!(check_file(moved, &file_path).unwrap().is_empty());
+
+        // A doc block whose code is gone produces a warning.
+        let diagnostics = check_file("# Run it.\nx = 1\n", &file_path).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source, "anchor");
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `api.rs` -- The stable interface to the CodeChat Editor's processing
+///
+/// Tools built on the CodeChat Editor, such as exporters and CI checks, should
+/// use only the functions and types in this module. The rest of `processing`
+/// may change between releases; this module changes only with a new major
+/// version. It provides:
+///
+/// - `translate` / `translate_file`: source code to its web-editable
+///   equivalent (`CodeChatForWeb`).
+/// - `untranslate`: `CodeChatForWeb` back to source code.
+/// - `diff`: the changes between two translations of the same file.
+/// - `toc`: the table of contents of the project containing a file.
+///
+/// Each reports failures as a `ProcessingError`.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+// ### Local
+use super::{
+    codechat_for_web_to_source, diff_code_mirror, find_path_to_toc, project_config::toc_files,
+    source_to_codechat_for_web_string, TranslationResultsString,
+};
+pub use super::{
+    diff::DiffGranularity, project_config::RenderTarget, CodeChatForWeb, CodeMirror,
+    CodeMirrorDiff, CodeMirrorDocBlock, CodeMirrorDocBlockTransaction, CodeMirrorDocBlockUpdate,
+    SourceFileMetadata, StringDiff,
+};
+use crate::lexer::LEXERS;
+
+// ## Data structures
+/// The ways in which processing may fail.
+#[derive(Debug)]
+pub enum ProcessingError {
+    /// No lexer supports this file.
+    UnsupportedFile(PathBuf),
+    /// This mode (the name of a lexer) doesn't exist.
+    UnknownMode(String),
+    /// The mode of the two translations provided to `diff` differ.
+    ModeMismatch { before: String, after: String },
+    /// The file couldn't be translated; the string explains why.
+    Translation(String),
+    /// A doc block couldn't be turned back into a comment; the string explains
+    /// why.
+    Untranslation(String),
+    /// The table of contents couldn't be read or parsed; the string explains
+    /// why.
+    Toc(String),
+    /// The file couldn't be read.
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for ProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessingError::UnsupportedFile(path) => {
+                write!(f, "{path:?} isn't supported by the CodeChat Editor.")
+            }
+            ProcessingError::UnknownMode(mode) => write!(f, "Unknown mode {mode}."),
+            ProcessingError::ModeMismatch { before, after } => {
+                write!(f, "Unable to compare mode {before} with mode {after}.")
+            }
+            ProcessingError::Translation(msg) => write!(f, "Unable to translate: {msg}"),
+            ProcessingError::Untranslation(msg) => write!(f, "Unable to untranslate: {msg}"),
+            ProcessingError::Toc(msg) => write!(f, "Unable to load the table of contents: {msg}"),
+            ProcessingError::Io { path, source } => write!(f, "Unable to read {path:?}: {source}."),
+        }
+    }
+}
+
+impl Error for ProcessingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProcessingError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The table of contents of a project.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toc {
+    /// The path to the TOC.
+    pub path: PathBuf,
+    /// The TOC, rendered as HTML.
+    pub html: String,
+    /// The files the TOC links to, in order; see `toc_files`.
+    pub files: Vec<PathBuf>,
+}
+
+// ## Code
+/// Translate the provided contents of `file_path` to the web-editable format.
+/// The path selects a lexer and the project (if any) whose configuration
+/// applies; the file isn't read.
+pub fn translate(
+    file_contents: &str,
+    file_path: &Path,
+    render_target: RenderTarget,
+) -> Result<CodeChatForWeb, ProcessingError> {
+    match source_to_codechat_for_web_string(file_contents, file_path, false, render_target).0 {
+        TranslationResultsString::CodeChat(codechat_for_web) => Ok(codechat_for_web),
+        TranslationResultsString::Unknown => {
+            Err(ProcessingError::UnsupportedFile(file_path.to_path_buf()))
+        }
+        TranslationResultsString::Err(msg) => Err(ProcessingError::Translation(msg)),
+        // Only requested when `is_toc` is true.
+        TranslationResultsString::Toc(_) => unreachable!(),
+    }
+}
+
+/// Read then translate the file at `file_path`.
+pub fn translate_file(
+    file_path: &Path,
+    render_target: RenderTarget,
+) -> Result<CodeChatForWeb, ProcessingError> {
+    translate(&read(file_path)?, file_path, render_target)
+}
+
+/// Translate from the web-editable format back to source code.
+pub fn untranslate(codechat_for_web: &CodeChatForWeb) -> Result<String, ProcessingError> {
+    let mode = &codechat_for_web.metadata.mode;
+    if !LEXERS.map_mode_to_lexer.contains_key(mode) {
+        return Err(ProcessingError::UnknownMode(mode.clone()));
+    }
+    codechat_for_web_to_source(codechat_for_web).map_err(ProcessingError::Untranslation)
+}
+
+/// Return the changes which transform translation `before` into `after`,
+/// comparing their text with the provided granularity.
+pub fn diff(
+    before: &CodeChatForWeb,
+    after: &CodeChatForWeb,
+    granularity: DiffGranularity,
+) -> Result<CodeMirrorDiff, ProcessingError> {
+    if before.metadata.mode != after.metadata.mode {
+        return Err(ProcessingError::ModeMismatch {
+            before: before.metadata.mode.clone(),
+            after: after.metadata.mode.clone(),
+        });
+    }
+    Ok(diff_code_mirror(&before.source, &after.source, granularity))
+}
+
+/// Return the table of contents of the project containing `file_path`, or
+/// `None` if this file isn't part of a project.
+pub fn toc(file_path: &Path) -> Result<Option<Toc>, ProcessingError> {
+    let Some(path_to_toc) = find_path_to_toc(file_path) else {
+        return Ok(None);
+    };
+    let path = file_path.parent().unwrap_or(file_path).join(path_to_toc);
+    let html =
+        match source_to_codechat_for_web_string(&read(&path)?, &path, true, RenderTarget::Editor).0
+        {
+            TranslationResultsString::Toc(html) => html,
+            TranslationResultsString::Err(msg) => return Err(ProcessingError::Toc(msg)),
+            _ => return Err(ProcessingError::UnsupportedFile(path)),
+        };
+    let files = toc_files(&path).map_err(ProcessingError::Toc)?;
+    Ok(Some(Toc { path, html, files }))
+}
+
+// Read the file at `path`, reporting failure as a `ProcessingError`.
+fn read(path: &Path) -> Result<String, ProcessingError> {
+    fs::read_to_string(path).map_err(|source| ProcessingError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{
+        diff, toc, translate, translate_file, untranslate, DiffGranularity, ProcessingError,
+    };
+    use crate::prep_test_dir;
+    use crate::processing::project_config::RenderTarget;
+
+    #[test]
+    fn test_round_trip() {
+        let source = "# A doc block.\na = 1\n";
+        let path = Path::new("foo.py");
+        let mut codechat_for_web = translate(source, path, RenderTarget::Editor).unwrap();
+        assert_eq!(codechat_for_web.metadata.mode, "python");
+        // Doc blocks are translated to HTML, which the Client returns as
+        // Markdown.
+        assert_eq!(
+            codechat_for_web.source.doc_blocks[0].4,
+            "<p>A doc block.</p>\n"
+        );
+        codechat_for_web.source.doc_blocks[0].4 = "A doc block.\n".to_string();
+        assert_eq!(untranslate(&codechat_for_web).unwrap(), source);
+
+        // Comparing a translation with itself produces no changes.
+        let d = diff(&codechat_for_web, &codechat_for_web, DiffGranularity::Word).unwrap();
+        assert!(d.doc.is_empty() && d.doc_blocks.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            translate("", Path::new("foo.unknown"), RenderTarget::Editor),
+            Err(ProcessingError::UnsupportedFile(_))
+        ));
+        assert!(matches!(
+            translate_file(Path::new("/does/not/exist.py"), RenderTarget::Editor),
+            Err(ProcessingError::Io { .. })
+        ));
+
+        let py = translate("a = 1\n", Path::new("foo.py"), RenderTarget::Editor).unwrap();
+        let mut bad = py.clone();
+        bad.metadata.mode = "not a mode".to_string();
+        assert!(matches!(
+            untranslate(&bad),
+            Err(ProcessingError::UnknownMode(_))
+        ));
+        assert!(matches!(
+            diff(&py, &bad, DiffGranularity::Word),
+            Err(ProcessingError::ModeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_toc() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+
+        let project_toc = toc(&test_dir.join("foo.py")).unwrap().unwrap();
+        assert!(project_toc.html.contains("foo.py"));
+        assert_eq!(
+            project_toc.files,
+            vec![test_dir.join("foo.py").canonicalize().unwrap()]
+        );
+        assert!(toc(&temp_dir.path().join("none.py")).unwrap().is_none());
+
+        temp_dir.close().unwrap();
+    }
+}
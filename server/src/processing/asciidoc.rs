@@ -0,0 +1,123 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `asciidoc.rs` -- Render doc blocks written in AsciiDoc
+///
+/// Doc blocks may be written in [AsciiDoc](https://asciidoc.org/), selected by
+/// setting `doc_block_markup` to `asciidoc` in a project's configuration or by
+/// placing `CodeChat Editor markup: asciidoc` in a comment in a file. Since
+/// there's no complete AsciiDoc renderer written in Rust, this runs
+/// [Asciidoctor](https://asciidoctor.org/), which must be installed. It runs in
+/// its secure mode, which ignores includes and other access to the
+/// filesystem. Asciidoctor is slow to start, so all the doc blocks in a file
+/// are rendered by a single run.
+///
+/// As for reStructuredText (see `rst.rs`), the Client can't convert HTML back
+/// to AsciiDoc. So, each rendered doc block is read-only and carries its source
+/// in a `data-source` attribute, which the Client saves unchanged.
+// ## Imports
+//
+// ### Third-party
+use log::warn;
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    fences::run_command,
+    project_config::ProjectConfig,
+    sanitize::sanitize_html,
+};
+
+// ## Globals
+/// The command which renders AsciiDoc read from stdin to HTML, without a
+/// header or footer.
+const ASCIIDOCTOR_COMMAND: [&str; 7] = [
+    "asciidoctor",
+    "--no-header-footer",
+    "--safe-mode",
+    "secure",
+    "--out-file",
+    "-",
+    "-",
+];
+
+/// Separates doc blocks in the HTML produced by Asciidoctor. Each doc block is
+/// followed by a passthrough block containing this comment, which Asciidoctor
+/// copies to its output unchanged.
+const SEPARATOR: &str = "<!--CodeChat-asciidoc-separator-->";
+
+// ## Code
+/// Render doc blocks written in AsciiDoc to HTML. If Asciidoctor fails, the doc
+/// blocks are shown as written.
+pub fn asciidoc_to_html(doc_blocks: &[String], project_config: &ProjectConfig) -> Vec<String> {
+    let rendered = render(doc_blocks)
+        .map_err(|err| warn!("Unable to render AsciiDoc: {err}"))
+        .ok();
+    doc_blocks
+        .iter()
+        .enumerate()
+        .map(|(index, asciidoc)| {
+            let html = match &rendered {
+                Some(rendered) => sanitize_html(&rendered[index], project_config.sanitize.level()),
+                None => format!("<pre>{}</pre>", escape(asciidoc, EscapeContext::Pre)),
+            };
+            format!(
+                "<div class=\"CodeChat-asciidoc\" contenteditable=\"false\" data-source=\"{}\">{html}</div>\n",
+                escape(asciidoc, EscapeContext::Attribute)
+            )
+        })
+        .collect()
+}
+
+// Render all the doc blocks by a single run of Asciidoctor, returning the HTML
+// for each.
+fn render(doc_blocks: &[String]) -> Result<Vec<String>, String> {
+    if doc_blocks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let asciidoc = doc_blocks.join(&format!("\n\n++++\n{SEPARATOR}\n++++\n\n"));
+    let command: Vec<String> = ASCIIDOCTOR_COMMAND.iter().map(|s| s.to_string()).collect();
+    let html = run_command(&command, None, &asciidoc)?;
+    let rendered: Vec<String> = html.split(SEPARATOR).map(str::to_string).collect();
+    // An unterminated block in one doc block swallows the separators which
+    // follow it.
+    if rendered.len() != doc_blocks.len() {
+        return Err("a doc block contains an unterminated block.".to_string());
+    }
+    Ok(rendered)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::asciidoc_to_html;
+    use crate::processing::project_config::ProjectConfig;
+
+    #[test]
+    fn test_asciidoc_to_html() {
+        let doc_blocks = vec!["Some _emphasis_.\n".to_string(), "a < b\n".to_string()];
+        let html = asciidoc_to_html(&doc_blocks, &ProjectConfig::default());
+        // Whether or not Asciidoctor is installed, each doc block keeps its
+        // source, so that the Client can save it unchanged.
+        assert_eq!(html.len(), 2);
+        assert!(html[0].starts_with(
+            "<div class=\"CodeChat-asciidoc\" contenteditable=\"false\" data-source=\"Some _emphasis_.\n\">"
+        ));
+        assert!(html[1].starts_with(
+            "<div class=\"CodeChat-asciidoc\" contenteditable=\"false\" data-source=\"a &lt; b\n\">"
+        ));
+        assert!(html[1].ends_with("</div>\n"));
+    }
+}
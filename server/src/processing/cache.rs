@@ -0,0 +1,425 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `cache.rs` -- Cache translated files
+///
+/// Translating a large file to the CodeChat Editor format takes time. This
+/// cache stores each translation, keyed by a hash of everything the
+/// translation depends on: the file's path and contents, whether it's a TOC,
+/// the project's configuration, and the contents of any files it
+/// [quotes](transclusion.rs). Translations of files in a project are
+/// also saved to the project's `.codechat/cache` directory when the Server is
+/// idle, then read from there when they aren't in memory, so that they outlive
+/// the Server.
+///
+/// The translations kept in memory are limited to a memory budget; when adding
+/// a translation exceeds this budget, the least recently used translations are
+/// removed. A removed translation is read again from disk if it was saved
+/// there; otherwise, the file is translated again.
+///
+/// Each cached translation is a file named by its key in hex, containing the
+/// translation as JSON, [encrypted](encryption.rs) if requested. Since the key
+/// includes the Server's version, upgrading the Server ignores old
+/// translations; delete this directory to remove them.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{
+    encryption::{decrypt, encrypt, is_encrypted, is_encryption_enabled},
+    macros::macro_cache_input,
+    project_config::ProjectConfig,
+    transclusion::transcluded_files,
+    CodeChatForWeb,
+};
+
+// ## Data structures
+/// A translation which may be cached.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum CachedTranslation {
+    CodeChat(CodeChatForWeb),
+    Toc(String),
+}
+
+/// A translation in memory.
+struct CacheEntry {
+    translation: CachedTranslation,
+    /// The approximate memory used by this translation, in bytes.
+    size: usize,
+    /// When this translation was last used; see `TranslationCache.uses`.
+    last_use: u64,
+}
+
+#[derive(Default)]
+struct TranslationCache {
+    /// Translations, keyed by `cache_key`.
+    entries: HashMap<u64, CacheEntry>,
+    /// The key of each translation, ordered from least to most recently used.
+    lru: BTreeMap<u64, u64>,
+    /// The number of times a translation was used; this orders `lru`.
+    uses: u64,
+    /// The sum of the size of all entries.
+    size: usize,
+    /// Translations not yet saved to disk: the project root and key of each.
+    unsaved: Vec<(PathBuf, u64)>,
+    /// When a translation was last added to the cache.
+    last_insert: Option<Instant>,
+}
+
+// ## Globals
+/// The directory, relative to a project's root, storing its cached
+/// translations.
+pub const CACHE_DIR: &str = ".codechat/cache";
+
+/// The default memory budget for cached translations, in bytes.
+pub const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+/// The memory budget for cached translations, in bytes.
+static MEMORY_BUDGET: AtomicUsize = AtomicUsize::new(DEFAULT_MEMORY_BUDGET);
+
+lazy_static! {
+    static ref CACHE: Mutex<TranslationCache> = Mutex::new(TranslationCache::default());
+}
+
+// ## Code
+/// Set the memory budget for cached translations, in bytes, removing
+/// translations as needed to meet it.
+pub fn set_memory_budget(budget: usize) {
+    MEMORY_BUDGET.store(budget, Ordering::Relaxed);
+    CACHE.lock().unwrap().evict(budget);
+}
+
+/// Return the key for a translation of the provided file.
+pub fn cache_key(
+    file_path: &Path,
+    file_contents: &str,
+    is_toc: bool,
+    project_config: &ProjectConfig,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    file_path.hash(&mut hasher);
+    file_contents.hash(&mut hasher);
+    is_toc.hash(&mut hasher);
+    // `ProjectConfig` contains floats and maps, which don't implement `Hash`;
+    // its debug representation contains everything.
+    format!("{project_config:?}").hash(&mut hasher);
+    for transcluded_file in transcluded_files(file_path, file_contents) {
+        fs::read(transcluded_file).ok().hash(&mut hasher);
+    }
+    macro_cache_input(file_path, file_contents).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Return the cached translation with the provided key. If it's not in memory
+/// but the file is part of a project, look for it on disk.
+pub fn get_cached(project_root: Option<&Path>, key: u64) -> Option<CachedTranslation> {
+    {
+        let mut cache = CACHE.lock().unwrap();
+        if let Some(translation) = cache.get(key) {
+            return Some(translation);
+        }
+    }
+    let translation = read_translation(project_root?, key)?;
+    CACHE.lock().unwrap().insert(key, translation.clone());
+    Some(translation)
+}
+
+/// Add a translation to the cache. If the file is part of a project, save this
+/// translation to disk later; see `persist_if_idle`.
+pub fn insert_cached(project_root: Option<&Path>, key: u64, translation: CachedTranslation) {
+    let mut cache = CACHE.lock().unwrap();
+    cache.insert(key, translation);
+    if let Some(project_root) = project_root {
+        cache.unsaved.push((project_root.to_path_buf(), key));
+    }
+    cache.last_insert = Some(Instant::now());
+}
+
+/// Save translations not yet saved to disk, provided no translation was added
+/// to the cache for `idle_time`. Translations removed from memory before this
+/// runs aren't saved. Return the number saved.
+pub fn persist_if_idle(idle_time: Duration) -> usize {
+    // Copy what to save, so the lock isn't held while writing files.
+    let unsaved: Vec<_> = {
+        let mut cache = CACHE.lock().unwrap();
+        if cache
+            .last_insert
+            .is_some_and(|last_insert| last_insert.elapsed() < idle_time)
+        {
+            return 0;
+        }
+        let unsaved = std::mem::take(&mut cache.unsaved);
+        unsaved
+            .into_iter()
+            .filter_map(|(project_root, key)| {
+                cache
+                    .entries
+                    .get(&key)
+                    .map(|entry| (project_root, key, entry.translation.clone()))
+            })
+            .collect()
+    };
+
+    let mut saved = 0;
+    for (project_root, key, translation) in unsaved {
+        // Don't re-create a project which no longer exists.
+        if !project_root.is_dir() {
+            continue;
+        }
+        let cache_dir = project_root.join(CACHE_DIR);
+        let result = fs::create_dir_all(&cache_dir)
+            .map_err(|err| err.to_string())
+            .and_then(|_| serde_json::to_vec(&translation).map_err(|err| err.to_string()))
+            .and_then(|json| {
+                if is_encryption_enabled() {
+                    encrypt(&json)
+                } else {
+                    Ok(json)
+                }
+            })
+            .and_then(|data| {
+                fs::write(cache_file(&project_root, key), data).map_err(|err| err.to_string())
+            });
+        match result {
+            Ok(()) => saved += 1,
+            Err(err) => warn!("Unable to save a cached translation to {cache_dir:?}: {err}"),
+        }
+    }
+    saved
+}
+
+impl TranslationCache {
+    // Return the translation with the provided key, marking it as the most
+    // recently used.
+    fn get(&mut self, key: u64) -> Option<CachedTranslation> {
+        self.uses += 1;
+        let entry = self.entries.get_mut(&key)?;
+        self.lru.remove(&entry.last_use);
+        entry.last_use = self.uses;
+        self.lru.insert(self.uses, key);
+        Some(entry.translation.clone())
+    }
+
+    // Add a translation, then remove the least recently used translations
+    // until the cache fits in the memory budget.
+    fn insert(&mut self, key: u64, translation: CachedTranslation) {
+        self.remove(key);
+        self.uses += 1;
+        let size = translation_size(&translation);
+        self.entries.insert(
+            key,
+            CacheEntry {
+                translation,
+                size,
+                last_use: self.uses,
+            },
+        );
+        self.lru.insert(self.uses, key);
+        self.size += size;
+        self.evict(MEMORY_BUDGET.load(Ordering::Relaxed));
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.lru.remove(&entry.last_use);
+            self.size -= entry.size;
+        }
+    }
+
+    // Remove the least recently used translations until the cache fits in
+    // `budget` bytes.
+    fn evict(&mut self, budget: usize) {
+        while self.size > budget {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.size -= entry.size;
+            }
+        }
+    }
+}
+
+// Estimate the memory used by a translation, in bytes.
+fn translation_size(translation: &CachedTranslation) -> usize {
+    match translation {
+        CachedTranslation::CodeChat(codechat_for_web) => {
+            codechat_for_web.metadata.mode.len()
+                + codechat_for_web.source.doc.len()
+                + codechat_for_web
+                    .source
+                    .doc_blocks
+                    .iter()
+                    .map(|doc_block| {
+                        std::mem::size_of_val(doc_block)
+                            + doc_block.2.len()
+                            + doc_block.3.len()
+                            + doc_block.4.len()
+                    })
+                    .sum::<usize>()
+        }
+        CachedTranslation::Toc(html) => html.len(),
+    }
+}
+
+// The path to the file storing the translation with the provided key.
+fn cache_file(project_root: &Path, key: u64) -> PathBuf {
+    project_root
+        .join(CACHE_DIR)
+        .join(format!("{key:016x}.json"))
+}
+
+// Read a translation saved to disk, ignoring any which can't be read or
+// decrypted.
+fn read_translation(project_root: &Path, key: u64) -> Option<CachedTranslation> {
+    let data = fs::read(cache_file(project_root, key)).ok()?;
+    parse_translation(data, is_encryption_enabled())
+}
+
+// Parse a translation saved to disk. When encryption is enabled, ignore an
+// unencrypted translation, since anyone able to write to the cache directory
+// could have replaced an encrypted translation with it.
+fn parse_translation(data: Vec<u8>, is_encryption_enabled: bool) -> Option<CachedTranslation> {
+    let json = if is_encrypted(&data) {
+        decrypt(&data).ok()?
+    } else if is_encryption_enabled {
+        return None;
+    } else {
+        data
+    };
+    serde_json::from_slice(&json).ok()
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use super::{
+        cache_file, cache_key, get_cached, insert_cached, parse_translation, persist_if_idle,
+        read_translation, CachedTranslation, TranslationCache,
+    };
+    use crate::processing::project_config::ProjectConfig;
+
+    #[test]
+    fn test_cache_key() {
+        let project_config = ProjectConfig::default();
+        let key = cache_key(Path::new("a.py"), "a = 1\n", false, &project_config);
+        assert_eq!(
+            key,
+            cache_key(Path::new("a.py"), "a = 1\n", false, &project_config)
+        );
+        assert_ne!(
+            key,
+            cache_key(Path::new("a.py"), "a = 2\n", false, &project_config)
+        );
+        assert_ne!(
+            key,
+            cache_key(Path::new("b.py"), "a = 1\n", false, &project_config)
+        );
+        assert_ne!(
+            key,
+            cache_key(Path::new("a.py"), "a = 1\n", true, &project_config)
+        );
+        let mut other_config = ProjectConfig::default();
+        other_config.markdown.admonitions = !other_config.markdown.admonitions;
+        assert_ne!(
+            key,
+            cache_key(Path::new("a.py"), "a = 1\n", false, &other_config)
+        );
+    }
+
+    #[test]
+    fn test_persist() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let project_root = temp_dir.path();
+        let translation = CachedTranslation::Toc("<p>TOC</p>".to_string());
+        // Use a key other tests won't.
+        let key = cache_key(
+            project_root,
+            "test_persist",
+            true,
+            &ProjectConfig::default(),
+        );
+        insert_cached(Some(project_root), key, translation.clone());
+        assert_eq!(
+            get_cached(Some(project_root), key),
+            Some(translation.clone())
+        );
+
+        // Nothing is saved until the cache is idle.
+        assert_eq!(persist_if_idle(Duration::from_secs(3600)), 0);
+        assert!(persist_if_idle(Duration::ZERO) >= 1);
+        assert!(cache_file(project_root, key).is_file());
+
+        // The saved translation can be read again.
+        assert_eq!(read_translation(project_root, key), Some(translation));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_parse_translation() {
+        let translation = CachedTranslation::Toc("<p>TOC</p>".to_string());
+        let json = serde_json::to_vec(&translation).unwrap();
+        assert_eq!(parse_translation(json.clone(), false), Some(translation));
+        // With encryption enabled, an unencrypted translation is ignored.
+        assert_eq!(parse_translation(json, true), None);
+        assert_eq!(parse_translation(b"not JSON".to_vec(), false), None);
+    }
+
+    #[test]
+    fn test_evict() {
+        let toc = |size| CachedTranslation::Toc("x".repeat(size));
+        let mut cache = TranslationCache::default();
+        // These fit in the default budget; call `evict` to test eviction.
+        cache.insert(1, toc(10));
+        cache.insert(2, toc(10));
+        cache.insert(3, toc(10));
+        assert_eq!(cache.size, 30);
+
+        // Using a translation makes it the most recently used.
+        assert_eq!(cache.get(1), Some(toc(10)));
+        cache.evict(20);
+        assert_eq!(cache.size, 20);
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some() && cache.get(3).is_some());
+
+        // Replacing a translation updates the size.
+        cache.insert(3, toc(5));
+        assert_eq!(cache.size, 15);
+        cache.evict(0);
+        assert_eq!(cache.size, 0);
+        assert!(cache.entries.is_empty() && cache.lru.is_empty());
+    }
+}
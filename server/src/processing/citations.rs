@@ -0,0 +1,607 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `citations.rs` -- Citations and a bibliography for doc blocks
+///
+/// A project may provide a bibliography, in either BibTeX (`.bib`) or CSL-JSON
+/// (`.json`) format. Doc blocks then cite entries in the bibliography using
+/// `[@key]`, or `[@key1; @key2]` for multiple citations. Each citation renders
+/// as an author-year reference linked to an entry in a references section,
+/// which is appended to the end of the document.
+///
+/// The Markdown renderer reports each citation as a
+/// [broken link](https://docs.rs/pulldown-cmark/latest/pulldown_cmark/struct.BrokenLink.html),
+/// since no link reference definition matches it. The broken link callback
+/// below turns these into links with a special URL, which `Citations::render`
+/// then replaces with the HTML for the citation.
+// ## Imports
+//
+// ### Standard library
+use std::{collections::HashMap, fs, path::Path};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{BrokenLink, CowStr, Event, Tag, TagEnd};
+use regex::Regex;
+use serde_json::Value;
+
+// ### Local
+use super::escape::{escape, EscapeContext};
+
+// ## Globals
+lazy_static! {
+    /// Match the text of a citation: one or more `@key`s, separated by
+    /// semicolons.
+    static ref CITATION_REGEX: Regex = Regex::new(r"^\s*@[^\s;@\]]+(\s*;\s*@[^\s;@\]]+)*\s*$").unwrap();
+}
+
+/// The URL given to citations by the broken link callback, followed by the
+/// text of the citation.
+const CITATION_URL_PREFIX: &str = "codechat-citation:";
+
+// ## Data structures
+/// One entry in a bibliography.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BibEntry {
+    /// The authors' names, as `(family name, given names)`.
+    pub authors: Vec<(String, String)>,
+    pub year: Option<String>,
+    pub title: Option<String>,
+    /// The journal, book, proceedings, or publisher containing this work.
+    pub container: Option<String>,
+    pub url: Option<String>,
+}
+
+/// A bibliography, indexed by citation key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bibliography(pub HashMap<String, BibEntry>);
+
+/// Track the citations made while rendering a document.
+pub struct Citations<'b> {
+    bibliography: &'b Bibliography,
+    /// The keys cited, in the order first cited.
+    cited_keys: Vec<String>,
+}
+
+// ## Code
+//
+// ### Loading a bibliography
+/// Load a bibliography from the provided file, choosing the format based on
+/// the file's extension.
+pub fn load_bibliography(path: &Path) -> Result<Bibliography, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(err) => return Err(format!("Unable to read bibliography {path:?}: {err}.")),
+    };
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("bib") => Ok(parse_bibtex(&contents)),
+        Some("json") => parse_csl_json(&contents)
+            .map_err(|err| format!("Unable to parse bibliography {path:?}: {err}")),
+        _ => Err(format!(
+            "Unknown bibliography format for {path:?}; expected a .bib or .json file."
+        )),
+    }
+}
+
+/// Parse a bibliography in
+/// [CSL-JSON](https://citeproc-js.readthedocs.io/en/latest/csl-json/markup.html)
+/// format.
+pub fn parse_csl_json(contents: &str) -> Result<Bibliography, String> {
+    let json: Value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    let Some(items) = json.as_array() else {
+        return Err("expected an array of entries.".to_string());
+    };
+    let mut bibliography = Bibliography::default();
+    for item in items {
+        let Some(id) = item["id"].as_str() else {
+            return Err("an entry is missing its id.".to_string());
+        };
+        let text = |field: &str| item[field].as_str().map(|s| s.to_string());
+        let authors = item["author"]
+            .as_array()
+            .map(|authors| {
+                authors
+                    .iter()
+                    .map(|author| match author["literal"].as_str() {
+                        Some(literal) => (literal.to_string(), String::new()),
+                        None => (
+                            author["family"].as_str().unwrap_or("").to_string(),
+                            author["given"].as_str().unwrap_or("").to_string(),
+                        ),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Dates are stored as `{"date-parts": [[year, month, day]]}`; the year
+        // may be a number or a string.
+        let year = match &item["issued"]["date-parts"][0][0] {
+            Value::Number(n) => Some(n.to_string()),
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        };
+        bibliography.0.insert(
+            id.to_string(),
+            BibEntry {
+                authors,
+                year,
+                title: text("title"),
+                container: text("container-title").or_else(|| text("publisher")),
+                url: text("URL"),
+            },
+        );
+    }
+    Ok(bibliography)
+}
+
+/// Parse a bibliography in BibTeX format. This supports the common subset of
+/// BibTeX: entries whose field values are enclosed in braces or quotes, or are
+/// bare numbers. Entries which can't be parsed, along with `@string`,
+/// `@preamble`, and `@comment` entries, are skipped.
+pub fn parse_bibtex(contents: &str) -> Bibliography {
+    let mut bibliography = Bibliography::default();
+    let mut rest = contents;
+    while let Some(at_index) = rest.find('@') {
+        rest = &rest[at_index + 1..];
+        let Some(open_index) = rest.find(['{', '(']) else {
+            break;
+        };
+        let entry_type = rest[..open_index].trim().to_lowercase();
+        rest = &rest[open_index + 1..];
+        if ["string", "preamble", "comment"].contains(&entry_type.as_str()) {
+            continue;
+        }
+        let Some(comma_index) = rest.find(',') else {
+            break;
+        };
+        let key = rest[..comma_index].trim().to_string();
+        rest = &rest[comma_index + 1..];
+
+        // Parse `name = value` pairs until the end of the entry.
+        let mut fields = HashMap::new();
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            if rest.is_empty() || rest.starts_with(['}', ')']) {
+                break;
+            }
+            let Some(equals_index) = rest.find('=') else {
+                break;
+            };
+            let name = rest[..equals_index].trim().to_lowercase();
+            rest = rest[equals_index + 1..].trim_start();
+            let Some((value, remainder)) = parse_bibtex_value(rest) else {
+                break;
+            };
+            fields.insert(name, value);
+            rest = remainder;
+        }
+
+        let authors = fields
+            .get("author")
+            .map(|authors| authors.split(" and ").map(parse_bibtex_name).collect())
+            .unwrap_or_default();
+        let field = |name: &str| fields.get(name).cloned();
+        bibliography.0.insert(
+            key,
+            BibEntry {
+                authors,
+                year: field("year"),
+                title: field("title"),
+                container: field("journal")
+                    .or_else(|| field("booktitle"))
+                    .or_else(|| field("publisher")),
+                url: field("url"),
+            },
+        );
+    }
+    bibliography
+}
+
+/// Parse one BibTeX field value, returning the value and the remaining text.
+/// Braces used for grouping are removed from the value.
+fn parse_bibtex_value(text: &str) -> Option<(String, &str)> {
+    let (value, remainder) = if let Some(quoted) = text.strip_prefix('"') {
+        let end_index = quoted.find('"')?;
+        (&quoted[..end_index], &quoted[end_index + 1..])
+    } else if text.starts_with('{') {
+        // Find the matching closing brace.
+        let mut depth = 0;
+        let mut end_index = None;
+        for (index, c) in text.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end_index = Some(index);
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        let end_index = end_index?;
+        (&text[1..end_index], &text[end_index + 1..])
+    } else {
+        let end_index = text.find([',', '}', ')']).unwrap_or(text.len());
+        (text[..end_index].trim(), &text[end_index..])
+    };
+    let value = value
+        .replace(['{', '}'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some((value, remainder))
+}
+
+/// Split a BibTeX name, written as either `Family, Given` or `Given Family`,
+/// into its family and given names.
+fn parse_bibtex_name(name: &str) -> (String, String) {
+    let name = name.trim();
+    match name.split_once(',') {
+        Some((family, given)) => (family.trim().to_string(), given.trim().to_string()),
+        None => match name.rsplit_once(' ') {
+            Some((given, family)) => (family.to_string(), given.to_string()),
+            None => (name.to_string(), String::new()),
+        },
+    }
+}
+
+// ### Rendering citations
+/// If the text of a broken link is a citation, provide a URL which marks it as
+/// a citation. Pass this to the Markdown parser as its broken link callback.
+pub fn citation_link_callback<'a>(link: BrokenLink<'a>) -> Option<(CowStr<'a>, CowStr<'a>)> {
+    if CITATION_REGEX.is_match(&link.reference) {
+        Some((
+            format!("{CITATION_URL_PREFIX}{}", link.reference).into(),
+            "".into(),
+        ))
+    } else {
+        None
+    }
+}
+
+impl<'b> Citations<'b> {
+    pub fn new(bibliography: &'b Bibliography) -> Self {
+        Citations {
+            bibliography,
+            cited_keys: Vec::new(),
+        }
+    }
+
+    /// Replace the links produced by `citation_link_callback` in the provided
+    /// stream of Markdown events with the HTML for each citation.
+    pub fn render<'a>(&mut self, events: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+        // True when skipping the text of a citation link.
+        let mut in_citation = false;
+        events
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link { ref dest_url, .. })
+                    if dest_url.starts_with(CITATION_URL_PREFIX) =>
+                {
+                    in_citation = true;
+                    Some(Event::Html(
+                        self.citation_html(&dest_url[CITATION_URL_PREFIX.len()..])
+                            .into(),
+                    ))
+                }
+                Event::End(TagEnd::Link) if in_citation => {
+                    in_citation = false;
+                    None
+                }
+                _ if in_citation => None,
+                _ => Some(event),
+            })
+            .collect()
+    }
+
+    /// Produce the HTML for the provided citation text, such as `@key1;
+    /// @key2`. The original text is stored in the `data-cite` attribute, so
+    /// that the Client can restore it.
+    fn citation_html(&mut self, citation: &str) -> String {
+        let labels: Vec<String> = citation
+            .split(';')
+            .map(|key| {
+                let key = key.trim().trim_start_matches('@');
+                if !self.cited_keys.iter().any(|k| k == key) {
+                    self.cited_keys.push(key.to_string());
+                }
+                match self.bibliography.0.get(key) {
+                    Some(entry) => format!(
+                        "<a href=\"#{}\">{}</a>",
+                        reference_id(key),
+                        escape(&short_label(key, entry), EscapeContext::Text)
+                    ),
+                    None => format!(
+                        "<span class=\"CodeChat-citation-missing\">?{}</span>",
+                        escape(key, EscapeContext::Text)
+                    ),
+                }
+            })
+            .collect();
+        format!(
+            "<span class=\"CodeChat-citation\" data-cite=\"{}\">[{}]</span>",
+            escape(citation.trim(), EscapeContext::Attribute),
+            labels.join("; ")
+        )
+    }
+
+    /// Produce a references section listing every entry cited, or an empty
+    /// string if nothing was cited.
+    pub fn references_html(&self) -> String {
+        let mut entries: Vec<_> = self
+            .cited_keys
+            .iter()
+            .filter_map(|key| self.bibliography.0.get(key).map(|entry| (key, entry)))
+            .collect();
+        if entries.is_empty() {
+            return String::new();
+        }
+        // Sort by the first author's family name, then by year.
+        entries.sort_by_key(|(key, entry)| {
+            (
+                entry
+                    .authors
+                    .first()
+                    .map(|(family, _)| family.to_lowercase())
+                    .unwrap_or_else(|| key.to_lowercase()),
+                entry.year.clone(),
+            )
+        });
+
+        let mut html = "<div class=\"CodeChat-references\">\n<p class=\"CodeChat-references-title\">References</p>\n<ul>\n".to_string();
+        for (key, entry) in entries {
+            let mut parts = Vec::new();
+            let mut authors_year = String::new();
+            if !entry.authors.is_empty() {
+                authors_year.push_str(&escape(
+                    &entry
+                        .authors
+                        .iter()
+                        .map(|(family, given)| {
+                            if given.is_empty() {
+                                family.clone()
+                            } else {
+                                format!("{family}, {given}")
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                    EscapeContext::Text,
+                ));
+            }
+            if let Some(year) = &entry.year {
+                if !authors_year.is_empty() {
+                    authors_year.push(' ');
+                }
+                authors_year.push_str(&format!("({})", escape(year, EscapeContext::Text)));
+            }
+            if !authors_year.is_empty() {
+                parts.push(authors_year);
+            }
+            if let Some(title) = &entry.title {
+                parts.push(format!("<em>{}</em>", escape(title, EscapeContext::Text)));
+            }
+            if let Some(container) = &entry.container {
+                parts.push(escape(container, EscapeContext::Text));
+            }
+            if let Some(url) = &entry.url {
+                // Only link to the web; show any other URL, which could be a
+                // `javascript:` link, as text.
+                parts.push(match link_url(url) {
+                    Some(href) => format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape(&href, EscapeContext::Attribute),
+                        escape(url, EscapeContext::Text)
+                    ),
+                    None => escape(url, EscapeContext::Text),
+                });
+            }
+            html.push_str(&format!(
+                "<li id=\"{}\">{}.</li>\n",
+                reference_id(key),
+                parts.join(". ")
+            ));
+        }
+        html.push_str("</ul>\n</div>\n");
+        html
+    }
+}
+
+// ### Support functions
+/// Produce the short, author-year label for a citation.
+fn short_label(key: &str, entry: &BibEntry) -> String {
+    let authors = match entry.authors.as_slice() {
+        [] => entry.title.clone().unwrap_or_else(|| key.to_string()),
+        [(a, _)] => a.clone(),
+        [(a, _), (b, _)] => format!("{a} and {b}"),
+        [(a, _), ..] => format!("{a} et al."),
+    };
+    match &entry.year {
+        Some(year) => format!("{authors} {year}"),
+        None => authors,
+    }
+}
+
+/// The address to link an entry's URL to, if it's an `http`, `https`, or `doi`
+/// URL; a DOI links to its resolver.
+fn link_url(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once(':')?;
+    match scheme.to_ascii_lowercase().as_str() {
+        "http" | "https" => Some(url.trim().to_string()),
+        "doi" => Some(format!("https://doi.org/{}", rest.trim_start_matches('/'))),
+        _ => None,
+    }
+}
+
+/// The HTML `id` given to an entry in the references section.
+fn reference_id(key: &str) -> String {
+    format!(
+        "CodeChat-ref-{}",
+        key.replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '_', "-")
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::{
+        citation_link_callback, link_url, parse_bibtex, parse_csl_json, BibEntry, Bibliography,
+        Citations,
+    };
+
+    #[test]
+    fn test_parse_bibtex() {
+        let bibliography = parse_bibtex(indoc!(
+            r#"
+            @comment{Ignore this.}
+            @book{knuth84,
+                author = {Knuth, Donald E.},
+                title = {Literate {P}rogramming},
+                year = 1984,
+                publisher = "CSLI",
+            }
+            @article{two,
+                author = {Ada Lovelace and Charles Babbage},
+                journal = {Notes}
+            }
+            "#
+        ));
+        assert_eq!(bibliography.0.len(), 2);
+        assert_eq!(
+            bibliography.0["knuth84"],
+            BibEntry {
+                authors: vec![("Knuth".to_string(), "Donald E.".to_string())],
+                year: Some("1984".to_string()),
+                title: Some("Literate Programming".to_string()),
+                container: Some("CSLI".to_string()),
+                url: None,
+            }
+        );
+        assert_eq!(
+            bibliography.0["two"].authors,
+            vec![
+                ("Lovelace".to_string(), "Ada".to_string()),
+                ("Babbage".to_string(), "Charles".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_csl_json() {
+        let bibliography = parse_csl_json(
+            r#"[{"id": "k", "author": [{"family": "Knuth", "given": "Donald"}, {"literal": "CSLI"}],
+                 "issued": {"date-parts": [[1984, 1]]}, "title": "LP", "URL": "http://a.org"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            bibliography.0["k"],
+            BibEntry {
+                authors: vec![
+                    ("Knuth".to_string(), "Donald".to_string()),
+                    ("CSLI".to_string(), "".to_string())
+                ],
+                year: Some("1984".to_string()),
+                title: Some("LP".to_string()),
+                container: None,
+                url: Some("http://a.org".to_string()),
+            }
+        );
+        assert!(parse_csl_json("{}").is_err());
+        assert!(parse_csl_json(r#"[{"title": "No id"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_render_citations() {
+        let mut bibliography = Bibliography::default();
+        bibliography.0.insert(
+            "k".to_string(),
+            BibEntry {
+                authors: vec![("Knuth".to_string(), "Donald".to_string())],
+                year: Some("1984".to_string()),
+                ..Default::default()
+            },
+        );
+        let mut citations = Citations::new(&bibliography);
+        let parser = Parser::new_with_broken_link_callback(
+            "See [@k; @missing], [a link], and `[@k]`.\n",
+            Options::empty(),
+            Some(citation_link_callback),
+        );
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, citations.render(parser).into_iter());
+        assert_eq!(
+            html_output,
+            "<p>See <span class=\"CodeChat-citation\" data-cite=\"@k; @missing\">[<a href=\"#CodeChat-ref-k\">Knuth 1984</a>; <span class=\"CodeChat-citation-missing\">?missing</span>]</span>, [a link], and <code>[@k]</code>.</p>\n"
+        );
+        assert_eq!(
+            citations.references_html(),
+            indoc!(
+                r#"
+                <div class="CodeChat-references">
+                <p class="CodeChat-references-title">References</p>
+                <ul>
+                <li id="CodeChat-ref-k">Knuth, Donald (1984).</li>
+                </ul>
+                </div>
+                "#
+            )
+        );
+
+        // Nothing cited produces no references.
+        assert_eq!(Citations::new(&bibliography).references_html(), "");
+
+        // Only web URLs are links.
+        for (url, html) in [
+            (
+                "https://a.org/?a=1&b=2",
+                r#"<a href="https://a.org/?a=1&amp;b=2">https://a.org/?a=1&amp;b=2</a>"#,
+            ),
+            (
+                "doi:10.1093/comjnl/27.2.97",
+                r#"<a href="https://doi.org/10.1093/comjnl/27.2.97">doi:10.1093/comjnl/27.2.97</a>"#,
+            ),
+            ("javascript:alert(1)", "javascript:alert(1)."),
+        ] {
+            bibliography.0.get_mut("k").unwrap().url = Some(url.to_string());
+            let mut citations = Citations::new(&bibliography);
+            let parser = Parser::new_with_broken_link_callback(
+                "[@k]",
+                Options::empty(),
+                Some(citation_link_callback),
+            );
+            html::push_html(&mut String::new(), citations.render(parser).into_iter());
+            assert!(citations.references_html().contains(html), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_link_url() {
+        assert_eq!(link_url("http://a.org"), Some("http://a.org".to_string()));
+        assert_eq!(
+            link_url(" HTTPS://a.org "),
+            Some("HTTPS://a.org".to_string())
+        );
+        assert_eq!(
+            link_url("doi:10.1000/182"),
+            Some("https://doi.org/10.1000/182".to_string())
+        );
+        assert_eq!(link_url("javascript:alert(1)"), None);
+        assert_eq!(link_url("data:text/html,x"), None);
+        assert_eq!(link_url("a.org"), None);
+    }
+}
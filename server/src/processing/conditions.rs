@@ -0,0 +1,304 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `conditions.rs` -- Include content for some audiences only
+///
+/// A conditional block, such as:
+///
+/// ```markdown
+/// :::if audience=instructor
+/// The answer is 42.
+/// :::
+/// ```
+///
+/// is only included when its condition holds, so that one source produces
+/// several editions, such as one for students and another for instructors. A
+/// condition is one or more tests separated by spaces, all of which must hold:
+/// `name=value` holds when the condition `name` has the value `value`, while
+/// `name!=value` holds when it doesn't.
+///
+/// The values of conditions come from the `conditions` entry in the project's
+/// configuration, which may be overridden for all files by
+/// `set_conditions` (used by the `export` command) and for one file by
+/// `set_file_conditions` (used for the query parameters of the page showing the
+/// file, such as `?audience=instructor`).
+///
+/// When exporting or serving a read-only page, a block whose condition fails is
+/// removed, while the fences around a block whose condition holds are removed.
+/// The Client must be able
+/// to save every block, so it receives all blocks, each wrapped in an element
+/// recording its condition; blocks whose condition fails are collapsed.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    fence_scanner::{fence_lines, FenceLine},
+    file_language_key,
+    project_config::RenderTarget,
+};
+
+// ## Globals
+lazy_static! {
+    /// Match the text following the colons which open a conditional block,
+    /// capturing its condition.
+    static ref IF_REGEX: Regex = Regex::new(r"^if[ \t]+(.*)$").unwrap();
+    /// Conditions which apply to all files, overriding the project's
+    /// configuration.
+    static ref CONDITIONS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+    /// Conditions which apply to specific files, overriding all others.
+    static ref FILE_CONDITIONS: Mutex<HashMap<PathBuf, BTreeMap<String, String>>> =
+        Mutex::new(HashMap::new());
+}
+
+// ## Code
+//
+// ### Condition values
+/// Override the project's conditions for all files. This lasts until the
+/// Server exits.
+pub fn set_conditions(conditions: BTreeMap<String, String>) {
+    *CONDITIONS.lock().unwrap() = conditions;
+}
+
+/// Override the conditions for the provided file; an empty map removes this
+/// override. This lasts until the Server exits.
+pub fn set_file_conditions(file_path: &Path, conditions: BTreeMap<String, String>) {
+    let mut file_conditions = FILE_CONDITIONS.lock().unwrap();
+    if conditions.is_empty() {
+        file_conditions.remove(&file_language_key(file_path));
+    } else {
+        file_conditions.insert(file_language_key(file_path), conditions);
+    }
+}
+
+/// Return the conditions for the provided file: the project's conditions,
+/// updated by any overrides.
+pub fn conditions_for_file(
+    file_path: &Path,
+    project_conditions: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut conditions = project_conditions.clone();
+    conditions.extend(CONDITIONS.lock().unwrap().clone());
+    if let Some(file_conditions) = FILE_CONDITIONS
+        .lock()
+        .unwrap()
+        .get(&file_language_key(file_path))
+    {
+        conditions.extend(file_conditions.clone());
+    }
+    conditions
+}
+
+// ### Conditional blocks
+/// Evaluate the conditional blocks in the provided Markdown for the given
+/// render target. Fences inside code blocks are ignored; any unterminated
+/// conditional blocks are closed at the end of the provided Markdown.
+pub fn evaluate_conditionals(
+    markdown: &str,
+    conditions: &BTreeMap<String, String>,
+    render_target: RenderTarget,
+) -> String {
+    // Avoid any work in the common case.
+    if !markdown.contains(":::") {
+        return markdown.to_string();
+    }
+
+    // Only the Client receives the blocks whose condition fails.
+    let is_editor = render_target == RenderTarget::Editor;
+    let mut output = String::with_capacity(markdown.len());
+    // For each fenced block which is currently open, whether its condition
+    // holds, or `None` if it's not a conditional block.
+    let mut open_blocks: Vec<Option<bool>> = Vec::new();
+    for (line, fence_line) in fence_lines(markdown) {
+        // Otherwise, omit the contents of blocks whose condition fails.
+        let is_hidden = !is_editor && open_blocks.contains(&Some(false));
+        match fence_line {
+            FenceLine::BlockOpen(text) => {
+                if let Some(captures) = IF_REGEX.captures(text) {
+                    let condition = &captures[1];
+                    let holds = evaluate(condition, conditions);
+                    open_blocks.push(Some(holds));
+                    if is_editor {
+                        output.push_str(&format!(
+                            "<div class=\"CodeChat-if{}\" data-condition=\"{}\">\n\n",
+                            if holds { "" } else { " CodeChat-if-inactive" },
+                            escape(condition, EscapeContext::Attribute)
+                        ));
+                    }
+                    continue;
+                }
+                open_blocks.push(None);
+            }
+            FenceLine::BlockClose => {
+                if let Some(Some(_)) = open_blocks.pop() {
+                    if is_editor {
+                        output.push_str("\n</div>\n\n");
+                    }
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        if !is_hidden {
+            output.push_str(line);
+        }
+    }
+
+    // Close any unterminated conditional blocks.
+    if is_editor {
+        for _ in open_blocks.iter().filter(|block| block.is_some()) {
+            output.push_str("\n\n</div>\n");
+        }
+    }
+    output
+}
+
+// Return true if every test in the condition holds.
+fn evaluate(condition: &str, conditions: &BTreeMap<String, String>) -> bool {
+    condition.split_whitespace().all(|test| {
+        if let Some((name, value)) = test.split_once("!=") {
+            conditions.get(name).map(String::as_str) != Some(value)
+        } else if let Some((name, value)) = test.split_once('=') {
+            conditions.get(name).map(String::as_str) == Some(value)
+        } else {
+            // A bare name holds if the condition is set.
+            conditions.contains_key(test)
+        }
+    })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::Path};
+
+    use indoc::indoc;
+
+    use super::{conditions_for_file, evaluate, evaluate_conditionals, set_file_conditions};
+    use crate::processing::project_config::RenderTarget;
+
+    const MARKDOWN: &str = indoc!(
+        "
+        Everyone.
+        :::if audience=instructor
+        Instructors.
+        :::note
+        A note.
+        :::
+        :::
+        :::if audience!=instructor
+        ```
+        :::
+        ```
+        :::
+        "
+    );
+
+    fn conditions(audience: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([("audience".to_string(), audience.to_string())])
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let conditions = conditions("student");
+        assert!(evaluate("audience=student", &conditions));
+        assert!(!evaluate("audience=instructor", &conditions));
+        assert!(evaluate("audience!=instructor", &conditions));
+        assert!(evaluate("audience", &conditions));
+        assert!(!evaluate("audience=student level=advanced", &conditions));
+        assert!(evaluate("level!=advanced", &conditions));
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(
+            evaluate_conditionals(MARKDOWN, &conditions("instructor"), RenderTarget::Export),
+            "Everyone.\nInstructors.\n:::note\nA note.\n:::\n"
+        );
+        assert_eq!(
+            evaluate_conditionals(MARKDOWN, &conditions("student"), RenderTarget::Export),
+            "Everyone.\n```\n:::\n```\n"
+        );
+        // Read-only pages, such as those shown to students, also omit blocks
+        // whose condition fails.
+        assert_eq!(
+            evaluate_conditionals(MARKDOWN, &conditions("student"), RenderTarget::ReadOnly),
+            "Everyone.\n```\n:::\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_editor() {
+        assert_eq!(
+            evaluate_conditionals(MARKDOWN, &conditions("student"), RenderTarget::Editor),
+            indoc!(
+                r#"
+                Everyone.
+                <div class="CodeChat-if CodeChat-if-inactive" data-condition="audience=instructor">
+
+                Instructors.
+                :::note
+                A note.
+                :::
+
+                </div>
+
+                <div class="CodeChat-if" data-condition="audience!=instructor">
+
+                ```
+                :::
+                ```
+
+                </div>
+
+                "#
+            )
+        );
+        // Unterminated blocks are closed.
+        assert_eq!(
+            evaluate_conditionals(":::if a\nText\n", &BTreeMap::new(), RenderTarget::Editor),
+            "<div class=\"CodeChat-if CodeChat-if-inactive\" data-condition=\"a\">\n\nText\n\n\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_conditions_for_file() {
+        let file_path = Path::new("/no/such/dir/conditions_test.md");
+        let project_conditions = BTreeMap::from([
+            ("audience".to_string(), "student".to_string()),
+            ("level".to_string(), "basic".to_string()),
+        ]);
+        set_file_conditions(file_path, conditions("instructor"));
+        let file_conditions = conditions_for_file(file_path, &project_conditions);
+        assert_eq!(file_conditions["audience"], "instructor");
+        assert_eq!(file_conditions["level"], "basic");
+        set_file_conditions(file_path, BTreeMap::new());
+        assert_eq!(
+            conditions_for_file(file_path, &project_conditions),
+            project_conditions
+        );
+    }
+}
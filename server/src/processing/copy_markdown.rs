@@ -0,0 +1,273 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `copy_markdown.rs` -- Extract doc blocks as clipboard-friendly Markdown
+///
+/// This provides the Markdown source of a file's doc blocks (optionally, only
+/// those in a range of lines), so that the Client can offer "Copy as Markdown"
+/// without translating its HTML back to Markdown. Since the copied Markdown
+/// will be pasted elsewhere, relative links and images are resolved to
+/// absolute `file:` URLs.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+use regex::Regex;
+use url::Url;
+
+// ### Local
+use super::{doc_blocks_with_lines, find_file_lexer, find_path_to_toc, project_config_for_file};
+
+// ## Globals
+lazy_static! {
+    /// Match a URL which begins with a scheme, such as `https:` or `mailto:`.
+    static ref URL_SCHEME_REGEX: Regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap();
+}
+
+// ## Code
+/// Return the Markdown of the doc blocks in the provided file which overlap the
+/// (1-based, inclusive) range of lines from `first_line` to `last_line`. A
+/// missing `first_line` or `last_line` refers to the beginning or end of the
+/// file.
+pub fn doc_blocks_to_markdown(
+    file_contents: &str,
+    file_path: &Path,
+    first_line: Option<usize>,
+    last_line: Option<usize>,
+) -> Result<String, String> {
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let lexer = find_file_lexer(file_contents, file_path, &project_config)?;
+    let first_line = first_line.unwrap_or(1);
+    let last_line = last_line.unwrap_or(usize::MAX);
+    let overlaps =
+        |start: usize, lines: usize| start <= last_line && start + lines.max(1) > first_line;
+
+    let markdown = if lexer.language_lexer.lexer_name.as_str() == "markdown" {
+        // Document-only files are entirely Markdown; select the lines
+        // requested.
+        file_contents
+            .split_inclusive('\n')
+            .enumerate()
+            .filter(|(index, _)| overlaps(index + 1, 1))
+            .map(|(_, line)| line)
+            .collect::<String>()
+    } else {
+        let doc_blocks = doc_blocks_with_lines(file_contents, lexer);
+        let doc_blocks: Vec<&str> = doc_blocks
+            .iter()
+            .filter(|(line, doc_block)| overlaps(*line, doc_block.lines))
+            .map(|(_, doc_block)| doc_block.contents.trim_end())
+            .collect();
+        // Separate doc blocks by a blank line, so they remain separate
+        // paragraphs.
+        let mut markdown = doc_blocks.join("\n\n");
+        if !markdown.is_empty() {
+            markdown.push('\n');
+        }
+        markdown
+    };
+
+    Ok(resolve_relative_links(
+        &markdown,
+        file_path.parent().unwrap_or_else(|| Path::new("")),
+    ))
+}
+
+/// Rewrite the destination of each relative link or image in the provided
+/// Markdown as an absolute `file:` URL, resolved relative to `base_dir`.
+pub fn resolve_relative_links(markdown: &str, base_dir: &Path) -> String {
+    // Gather the byte range and replacement for each destination.
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    let mut offset_iter = Parser::new(markdown).into_offset_iter();
+    for (event, range) in offset_iter.by_ref() {
+        let dest_url = match event {
+            Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            })
+            | Event::Start(Tag::Image {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }) => dest_url,
+            _ => continue,
+        };
+        let span = &markdown[range.clone()];
+        // The destination follows the last `](` in an inline link.
+        let (Some(dest_index), Some(url)) = (span.rfind("]("), resolve_url(&dest_url, base_dir))
+        else {
+            continue;
+        };
+        if let Some(dest_range) = raw_destination(span, dest_index + 2, true) {
+            replacements.push((
+                range.start + dest_range.start..range.start + dest_range.end,
+                url,
+            ));
+        }
+    }
+    // Link reference definitions (`[label]: destination`) provide the
+    // destinations for the remaining links.
+    for (_, link_def) in offset_iter.reference_definitions().iter() {
+        let span = &markdown[link_def.span.clone()];
+        let (Some(dest_index), Some(url)) =
+            (span.find("]:"), resolve_url(&link_def.dest, base_dir))
+        else {
+            continue;
+        };
+        if let Some(dest_range) = raw_destination(span, dest_index + 2, false) {
+            replacements.push((
+                link_def.span.start + dest_range.start..link_def.span.start + dest_range.end,
+                url,
+            ));
+        }
+    }
+
+    // Apply the replacements, from last to first, so that earlier ranges
+    // remain valid.
+    replacements.sort_by_key(|(range, _)| range.start);
+    let mut resolved = markdown.to_string();
+    for (range, url) in replacements.into_iter().rev() {
+        resolved.replace_range(range, &url);
+    }
+    resolved
+}
+
+/// If the provided URL is relative, return it as an absolute `file:` URL,
+/// resolved relative to `base_dir`; otherwise, return `None`.
+fn resolve_url(url: &str, base_dir: &Path) -> Option<String> {
+    if url.is_empty() || url.starts_with(['#', '/']) || URL_SCHEME_REGEX.is_match(url) {
+        return None;
+    }
+    let (path, fragment) = match url.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (url, None),
+    };
+    let path = urlencoding::decode(path).ok()?;
+    // Resolve any `.` or `..` components, since file URLs don't.
+    let mut resolved_path = PathBuf::new();
+    for component in base_dir.join(&*path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved_path.pop();
+            }
+            _ => resolved_path.push(component),
+        }
+    }
+    let mut file_url = Url::from_file_path(resolved_path).ok()?;
+    file_url.set_fragment(fragment);
+    Some(file_url.to_string())
+}
+
+/// Find the byte range of the raw destination which begins after any
+/// whitespace at index `start` of `span`. If `closed_by_paren`, the span ends
+/// with the `)` which closes an inline link.
+fn raw_destination(span: &str, start: usize, closed_by_paren: bool) -> Option<Range<usize>> {
+    let rest = &span[start..];
+    let dest_start = start + (rest.len() - rest.trim_start().len());
+    let rest = &span[dest_start..];
+    // A destination may be enclosed in angle brackets.
+    if rest.starts_with('<') {
+        return rest.find('>').map(|end| dest_start..dest_start + end + 1);
+    }
+    let mut dest_end = rest
+        .find(char::is_whitespace)
+        .map(|end| dest_start + end)
+        .unwrap_or(span.len());
+    if closed_by_paren && dest_end == span.len() {
+        // Omit the closing parenthesis.
+        dest_end -= 1;
+    }
+    (dest_end > dest_start).then_some(dest_start..dest_end)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::{doc_blocks_to_markdown, resolve_relative_links};
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_resolve_relative_links() {
+        let base_dir = Path::new("/a/b");
+        assert_eq!(
+            resolve_relative_links(
+                indoc!(
+                    r#"
+                    [Doc](../c%20d.md#sec "Title") and ![Img](img.png) and
+                    [web](https://a.org), [local](#top), and [ref][1].
+
+                    [1]: <x.py>
+                    "#
+                ),
+                base_dir
+            ),
+            indoc!(
+                r#"
+                [Doc](file:///a/c%20d.md#sec "Title") and ![Img](file:///a/b/img.png) and
+                [web](https://a.org), [local](#top), and [ref][1].
+
+                [1]: file:///a/b/x.py
+                "#
+            )
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_doc_blocks_to_markdown() {
+        let source = indoc!(
+            "
+            # First.
+            a = 1
+            # Second,
+            # [with a link](x.md).
+            b = 2
+            "
+        );
+        let file_path = Path::new("/a/foo.py");
+        assert_eq!(
+            doc_blocks_to_markdown(source, file_path, None, None).unwrap(),
+            "First.\n\nSecond,\n[with a link](file:///a/x.md).\n"
+        );
+        assert_eq!(
+            doc_blocks_to_markdown(source, file_path, Some(2), Some(3)).unwrap(),
+            "Second,\n[with a link](file:///a/x.md).\n"
+        );
+        assert_eq!(
+            doc_blocks_to_markdown(source, file_path, Some(5), None).unwrap(),
+            ""
+        );
+        assert!(doc_blocks_to_markdown(source, Path::new("foo.unknown"), None, None).is_err());
+
+        // Markdown files select lines.
+        assert_eq!(
+            doc_blocks_to_markdown("a\nb\nc\n", Path::new("/a/foo.md"), Some(2), Some(2)).unwrap(),
+            "b\n"
+        );
+    }
+}
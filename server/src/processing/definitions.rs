@@ -0,0 +1,212 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `definitions.rs` -- Find the definition of an identifier
+///
+/// A doc block often names an identifier in the code it describes, such as
+/// `` `parse_args` ``. To jump from this name to its definition, a project may
+/// provide a [ctags](https://ctags.io/) `tags` file, given by the `tags` entry
+/// in its configuration; the Server looks up identifiers in this file.
+/// Otherwise, the IDE finds the definition using its language servers.
+// ## Imports
+//
+// ### Standard library
+use std::{fs, path::Path};
+
+// ### Third-party
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{find_path_to_toc, find_project_root, project_config_for_file};
+
+// ## Data structures
+/// The location of a definition.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Definition {
+    /// The path to the file containing the definition.
+    pub file_path: String,
+    /// The line of this file containing the definition, numbered from 1.
+    pub line: usize,
+}
+
+// ## Code
+/// Find the definition of `identifier`, named in a doc block of the provided
+/// file, using its project's `tags` file. Returns `None` if the project has no
+/// `tags` file or the identifier isn't in it.
+pub fn find_definition(identifier: &str, file_path: &Path) -> Result<Option<Definition>, String> {
+    let Some(project_root) = find_project_root(file_path) else {
+        return Ok(None);
+    };
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let Some(tags) = project_config.tags else {
+        return Ok(None);
+    };
+    let tags_path = project_root.join(tags);
+    let tags_contents = fs::read_to_string(&tags_path)
+        .map_err(|err| format!("Unable to read {tags_path:?}: {err}."))?;
+    Ok(find_in_tags(
+        &tags_contents,
+        tags_path.parent().unwrap_or_else(|| Path::new("")),
+        identifier,
+    ))
+}
+
+/// Find `identifier` in the contents of a `tags` file, whose paths are relative
+/// to `tags_dir`.
+fn find_in_tags(tags_contents: &str, tags_dir: &Path, identifier: &str) -> Option<Definition> {
+    tags_contents
+        .lines()
+        // Skip the `!_TAG_` lines which describe this file.
+        .filter(|line| !line.starts_with('!'))
+        .find_map(|line| {
+            // Each tag is `name<TAB>file<TAB>address;"<TAB>fields`.
+            let mut fields = line.split('\t');
+            if fields.next()? != identifier {
+                return None;
+            }
+            let file_path = tags_dir.join(fields.next()?);
+            let rest: Vec<&str> = fields.collect();
+            let rest = rest.join("\t");
+            let (address, extension_fields) = rest.split_once(";\"").unwrap_or((&rest, ""));
+            let line = address
+                .parse()
+                .ok()
+                // Look for a `line:` field, produced by `ctags --fields=+n`.
+                .or_else(|| {
+                    extension_fields
+                        .split('\t')
+                        .find_map(|field| field.strip_prefix("line:")?.parse().ok())
+                })
+                // Otherwise, search for the line matching the address's pattern.
+                .or_else(|| find_pattern(address, &file_path))
+                .unwrap_or(1);
+            Some(Definition {
+                file_path: file_path.to_string_lossy().to_string(),
+                line,
+            })
+        })
+}
+
+/// Return the line number of the first line of `file_path` matching a tag's
+/// search pattern, such as `/^def foo():$/`.
+fn find_pattern(address: &str, file_path: &Path) -> Option<usize> {
+    let pattern = address
+        .strip_prefix('/')
+        .and_then(|pattern| pattern.strip_suffix('/'))
+        .or_else(|| {
+            address
+                .strip_prefix('?')
+                .and_then(|pattern| pattern.strip_suffix('?'))
+        })?;
+    let (pattern, is_start) = match pattern.strip_prefix('^') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let (pattern, is_end) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let pattern = pattern.replace("\\/", "/").replace("\\\\", "\\");
+    let file_contents = fs::read_to_string(file_path).ok()?;
+    file_contents
+        .lines()
+        .position(|line| match (is_start, is_end) {
+            (true, true) => line == pattern,
+            (true, false) => line.starts_with(&pattern),
+            (false, true) => line.ends_with(&pattern),
+            (false, false) => line.contains(&pattern),
+        })
+        .map(|index| index + 1)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+    use indoc::indoc;
+
+    use super::{find_definition, find_in_tags, Definition};
+
+    #[test]
+    fn test_find_in_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("foo.py"),
+            "import sys\n\ndef parse_args():\n    pass\n",
+        )
+        .unwrap();
+        let tags = indoc!(
+            "
+            !_TAG_FILE_FORMAT\t2\t/extended format/
+            main\tfoo.py\t12;\"\tf
+            parse_args\tfoo.py\t/^def parse_args():$/;\"\tf
+            run\tfoo.py\t/^def run():$/;\"\tf\tline:20
+            missing\tfoo.py\t/^def missing():$/;\"\tf
+            "
+        );
+        let definition = |line| {
+            Some(Definition {
+                file_path: temp_dir.path().join("foo.py").to_string_lossy().to_string(),
+                line,
+            })
+        };
+        // A line number address.
+        assert_eq!(find_in_tags(tags, temp_dir.path(), "main"), definition(12));
+        // A search pattern address.
+        assert_eq!(
+            find_in_tags(tags, temp_dir.path(), "parse_args"),
+            definition(3)
+        );
+        // A `line:` field.
+        assert_eq!(find_in_tags(tags, temp_dir.path(), "run"), definition(20));
+        // A pattern which doesn't match goes to the start of the file.
+        assert_eq!(
+            find_in_tags(tags, temp_dir.path(), "missing"),
+            definition(1)
+        );
+        assert_eq!(find_in_tags(tags, temp_dir.path(), "unknown"), None);
+        assert_eq!(
+            find_in_tags(tags, temp_dir.path(), "!_TAG_FILE_FORMAT"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_definition() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("foo.py");
+        fs::write(&file_path, "def foo():\n    pass\n").unwrap();
+        // Without a project, there's no `tags` file.
+        assert_eq!(find_definition("foo", &file_path), Ok(None));
+
+        // A project without a `tags` entry also uses the IDE.
+        fs::write(temp_dir.path().join("toc.md"), "[foo](foo.py)\n").unwrap();
+        assert_eq!(find_definition("foo", &file_path), Ok(None));
+
+        fs::write(temp_dir.path().join("codechat.json"), r#"{"tags": "tags"}"#).unwrap();
+        assert!(find_definition("foo", &file_path).is_err());
+        fs::write(temp_dir.path().join("tags"), "foo\tfoo.py\t1;\"\tf\n").unwrap();
+        assert_eq!(
+            find_definition("foo", &file_path),
+            Ok(Some(Definition {
+                file_path: temp_dir.path().join("foo.py").to_string_lossy().to_string(),
+                line: 1,
+            }))
+        );
+        assert_eq!(find_definition("bar", &file_path), Ok(None));
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `diagnostics.rs` -- Report non-fatal problems found in a source file
+///
+/// Problems which don't prevent a file from being edited -- a lint warning, HTML
+/// removed by the sanitizer, a malformed exercise, or a comment the lexer
+/// couldn't find the end of -- are reported as a `Diagnostic`, rather than
+/// only to the log. The Server sends these to the Client in a `Diagnostics`
+/// message, which shows them next to the lines they refer to.
+///
+/// Each subsystem names itself as the diagnostic's `source`, so that the Client
+/// and command-line tools can show where a problem came from.
+// ## Imports
+//
+// ### Standard library
+use std::{cmp::max, ops::Range, path::Path};
+
+// ### Third-party
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{
+    anchors::anchor_diagnostics, doc_blocks_with_lines, find_file_lexer, find_long_line,
+    find_path_to_toc, lint::lint_doc_blocks, project_config::SanitizeLevel,
+    project_config_for_file, quiz::find_quiz_errors, sanitize::find_unsafe_html,
+    MAX_LEXED_LINE_LENGTH,
+};
+use crate::lexer::{source_lexer_with_unterminated, ConstructKind, UnterminatedConstruct};
+
+// ## Data structures
+/// How serious a problem is.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum Severity {
+    /// Part of the file can't be displayed as intended.
+    Error,
+    /// A likely mistake.
+    Warning,
+    /// A suggestion.
+    Information,
+}
+
+/// A non-fatal problem found in a source file.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The lines of the source file containing the problem, numbered from 1.
+    /// The end of the range is exclusive.
+    pub range: Range<usize>,
+    /// A description of the problem.
+    pub message: String,
+    /// The subsystem which found this problem, such as `lint/bare-url`.
+    pub source: String,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic for a problem on a single line.
+    pub fn on_line(severity: Severity, line: usize, message: String, source: &str) -> Self {
+        Diagnostic {
+            severity,
+            range: line..line + 1,
+            message,
+            source: source.to_string(),
+        }
+    }
+}
+
+// ## Code
+/// Check the provided file for all the problems the Server knows how to find,
+/// returning them ordered by line.
+pub fn check_file(file_contents: &str, file_path: &Path) -> Result<Vec<Diagnostic>, String> {
+    let path_to_toc = find_path_to_toc(file_path);
+    let project_config = project_config_for_file(file_path, path_to_toc.as_deref());
+    let lexer = find_file_lexer(file_contents, file_path, &project_config)?;
+    // A file with a line too long to lex has no doc blocks to check.
+    if lexer.language_lexer.lexer_name.as_str() != "markdown" {
+        if let Some((line, length)) = find_long_line(file_contents) {
+            return Ok(vec![Diagnostic::on_line(
+                Severity::Information,
+                line,
+                format!("This line is {length} bytes long; since the longest line lexed is {MAX_LEXED_LINE_LENGTH} bytes, this file is shown as code, without doc blocks."),
+                "lexer",
+            )]);
+        }
+    }
+    let doc_blocks = doc_blocks_with_lines(file_contents, lexer);
+
+    let mut diagnostics = lint_doc_blocks(file_contents, &doc_blocks);
+    if lexer.language_lexer.lexer_name.as_str() != "markdown" {
+        let line_count = file_contents.lines().count();
+        diagnostics.extend(
+            source_lexer_with_unterminated(file_contents, lexer)
+                .1
+                .iter()
+                .map(|unterminated| unterminated_diagnostic(unterminated, line_count)),
+        );
+    }
+    if project_config.anchors && lexer.language_lexer.lexer_name.as_str() != "markdown" {
+        diagnostics.extend(anchor_diagnostics(
+            file_contents,
+            lexer,
+            &project_config.file_anchors,
+        ));
+    }
+    // By default, unsafe HTML is kept when editing, but removed from exports
+    // and read-only pages.
+    let sanitize_level = match project_config.sanitize.editor {
+        SanitizeLevel::Off => project_config.sanitize.export,
+        level => level,
+    };
+    for (start_line, doc_block) in &doc_blocks {
+        diagnostics.extend(find_unsafe_html(
+            &doc_block.contents,
+            *start_line,
+            sanitize_level,
+        ));
+        diagnostics.extend(find_quiz_errors(&doc_block.contents, *start_line));
+    }
+    // Sorting is stable, so problems on the same line remain in the order
+    // found.
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+    Ok(diagnostics)
+}
+
+/// Explain the consequences of a construct the lexer couldn't find the end of.
+/// The problem extends from the construct to the end of the file, since all
+/// of that is treated as code.
+fn unterminated_diagnostic(unterminated: &UnterminatedConstruct, line_count: usize) -> Diagnostic {
+    let construct = match unterminated.kind {
+        ConstructKind::BlockComment => "block comment",
+        ConstructKind::String => "string",
+    };
+    Diagnostic {
+        severity: Severity::Error,
+        range: unterminated.line..max(line_count, unterminated.line) + 1,
+        message: format!(
+            "The {construct} opened by `{}` on line {} is never closed, so the rest of the file is treated as code; comments after it aren't shown as doc blocks.",
+            unterminated.delimiter, unterminated.line
+        ),
+        source: "lexer".to_string(),
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::{check_file, Diagnostic, Severity, MAX_LEXED_LINE_LENGTH};
+
+    #[test]
+    fn test_check_file() {
+        let source = indoc!(
+            "
+            # # Title
+            a = 1
+            # <img src=\"a.png\" onerror=\"f()\"> \x20
+            "
+        );
+        assert_eq!(
+            check_file(source, Path::new("foo.py")).unwrap(),
+            vec![
+                Diagnostic::on_line(
+                    Severity::Warning,
+                    3,
+                    "Remove the whitespace at the end of this line.".to_string(),
+                    "lint/trailing-whitespace"
+                ),
+                Diagnostic::on_line(
+                    Severity::Warning,
+                    3,
+                    "Describe this image for screen readers by providing alt text, such as ![A diagram of the parser](parser.png) or <img alt=\"...\">.".to_string(),
+                    "lint/missing-alt"
+                ),
+                Diagnostic::on_line(
+                    Severity::Warning,
+                    3,
+                    "The sanitizer removes unsafe HTML (a script, event handler, or `javascript:` link) from this doc block.".to_string(),
+                    "sanitize"
+                ),
+            ]
+        );
+
+        // Report an unterminated block comment.
+        assert_eq!(
+            check_file("// Doc\n/* Oops\n// More\n", Path::new("foo.js")).unwrap(),
+            vec![Diagnostic {
+                severity: Severity::Error,
+                range: 2..4,
+                message: "The block comment opened by `/*` on line 2 is never closed, so the rest of the file is treated as code; comments after it aren't shown as doc blocks.".to_string(),
+                source: "lexer".to_string(),
+            }]
+        );
+        assert!(check_file("", Path::new("foo.unknown")).is_err());
+
+        // Report a line too long to lex.
+        let source = format!("// Doc\n{}\n", "a".repeat(MAX_LEXED_LINE_LENGTH + 1));
+        assert_eq!(
+            check_file(&source, Path::new("foo.js")).unwrap(),
+            vec![Diagnostic::on_line(
+                Severity::Information,
+                2,
+                format!("This line is {} bytes long; since the longest line lexed is {MAX_LEXED_LINE_LENGTH} bytes, this file is shown as code, without doc blocks.", MAX_LEXED_LINE_LENGTH + 1),
+                "lexer"
+            )]
+        );
+    }
+}
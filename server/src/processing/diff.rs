@@ -0,0 +1,419 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `diff.rs` -- Compute the changes between two strings
+///
+/// This uses
+/// [Myers' diff algorithm](http://www.xmailserver.org/diff2.pdf) to find the
+/// smallest set of changes between two strings. Running this on every
+/// character of a large file is slow, so the strings are first compared line
+/// by line; each changed range of lines is then compared again using the
+/// requested granularity (words or characters). If two strings differ by more
+/// than `MAX_EDIT_DISTANCE` tokens, the comparison gives up and reports
+/// everything between their common prefix and suffix as a single change.
+///
+/// The results are deterministic: the same strings always produce the same
+/// changes.
+// ## Imports
+//
+// ### Standard library
+use std::cmp::min;
+
+// ### Third-party
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::StringDiff;
+
+// ## Data structures
+/// How finely to divide the strings being compared.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffGranularity {
+    /// Replace whole lines.
+    Line,
+    /// Replace words, runs of whitespace, and punctuation.
+    #[default]
+    Word,
+    /// Replace characters.
+    Char,
+}
+
+/// A change, plus its location in bytes in the original string.
+struct Hunk {
+    from_byte: usize,
+    to_byte: usize,
+    diff: StringDiff,
+}
+
+/// The kinds of characters which make up a word-level token.
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Word,
+    Space,
+    Other,
+}
+
+// ## Globals
+/// The largest number of tokens inserted or deleted for which Myers' algorithm
+/// runs. Its memory use grows with the square of this value.
+const MAX_EDIT_DISTANCE: usize = 500;
+
+// ## Code
+/// Return the changes which transform `before` into `after`, as character
+/// offsets into `before`.
+pub fn diff_str(before: &str, after: &str, granularity: DiffGranularity) -> Vec<StringDiff> {
+    if before == after {
+        return Vec::new();
+    }
+    let line_hunks = token_diff(before, after, DiffGranularity::Line);
+    if granularity == DiffGranularity::Line {
+        return line_hunks.into_iter().map(|hunk| hunk.diff).collect();
+    }
+    // Refine each changed range of lines.
+    let mut diffs = Vec::new();
+    for line_hunk in line_hunks {
+        let line_diff = line_hunk.diff;
+        for hunk in token_diff(
+            &before[line_hunk.from_byte..line_hunk.to_byte],
+            &line_diff.insert,
+            granularity,
+        ) {
+            diffs.push(StringDiff {
+                from: line_diff.from + hunk.diff.from,
+                to: line_diff.from + hunk.diff.to,
+                insert: hunk.diff.insert,
+            });
+        }
+    }
+    diffs
+}
+
+/// Apply the changes produced by `diff_str` to `before`.
+pub fn apply_str_diffs(before: &str, diffs: &[StringDiff]) -> String {
+    let before: Vec<char> = before.chars().collect();
+    let mut after = String::new();
+    let mut index = 0;
+    for diff in diffs {
+        after.extend(&before[index..diff.from]);
+        after.push_str(&diff.insert);
+        index = diff.to;
+    }
+    after.extend(&before[index..]);
+    after
+}
+
+/// Divide `s` into tokens of the provided granularity. Joining these tokens
+/// produces `s`.
+fn tokenize(s: &str, granularity: DiffGranularity) -> Vec<&str> {
+    match granularity {
+        DiffGranularity::Line => s.split_inclusive('\n').collect(),
+        DiffGranularity::Char => s
+            .char_indices()
+            .map(|(index, c)| &s[index..index + c.len_utf8()])
+            .collect(),
+        DiffGranularity::Word => {
+            let char_class = |c: char| {
+                if c.is_alphanumeric() || c == '_' {
+                    CharClass::Word
+                } else if c.is_whitespace() && c != '\n' {
+                    CharClass::Space
+                } else {
+                    CharClass::Other
+                }
+            };
+            let mut tokens = Vec::new();
+            let mut start = 0;
+            let mut prev_class = None;
+            for (index, c) in s.char_indices() {
+                let class = char_class(c);
+                // Each punctuation character and newline is a separate token.
+                if (prev_class != Some(class) || class == CharClass::Other) && index > start {
+                    tokens.push(&s[start..index]);
+                    start = index;
+                }
+                prev_class = Some(class);
+            }
+            if start < s.len() {
+                tokens.push(&s[start..]);
+            }
+            tokens
+        }
+    }
+}
+
+/// Return the changes which transform `before` into `after`, comparing tokens
+/// of the provided granularity.
+fn token_diff(before: &str, after: &str, granularity: DiffGranularity) -> Vec<Hunk> {
+    let before_tokens = tokenize(before, granularity);
+    let after_tokens = tokenize(after, granularity);
+
+    // The offset of each token in `before`, in bytes and characters, followed
+    // by the length of `before`.
+    let mut before_bytes = vec![0];
+    let mut before_chars = vec![0];
+    for token in &before_tokens {
+        before_bytes.push(before_bytes.last().unwrap() + token.len());
+        before_chars.push(before_chars.last().unwrap() + token.chars().count());
+    }
+    // The offset of each token in `after`, in bytes.
+    let mut after_bytes = vec![0];
+    for token in &after_tokens {
+        after_bytes.push(after_bytes.last().unwrap() + token.len());
+    }
+
+    // Myers' algorithm needn't examine the common prefix and suffix.
+    let prefix = before_tokens
+        .iter()
+        .zip(&after_tokens)
+        .take_while(|(b, a)| b == a)
+        .count();
+    let suffix = before_tokens[prefix..]
+        .iter()
+        .rev()
+        .zip(after_tokens[prefix..].iter().rev())
+        .take_while(|(b, a)| b == a)
+        .count();
+    let before_middle = &before_tokens[prefix..before_tokens.len() - suffix];
+    let after_middle = &after_tokens[prefix..after_tokens.len() - suffix];
+    // If these are too different, replace everything between the prefix and
+    // suffix.
+    let matches = myers(before_middle, after_middle).unwrap_or_default();
+
+    // Each gap between matching tokens is a change.
+    let mut hunks = Vec::new();
+    let (mut before_index, mut after_index) = (0, 0);
+    for (before_match, after_match) in matches
+        .into_iter()
+        .chain([(before_middle.len(), after_middle.len())])
+    {
+        if before_match > before_index || after_match > after_index {
+            let (from, to) = (prefix + before_index, prefix + before_match);
+            hunks.push(Hunk {
+                from_byte: before_bytes[from],
+                to_byte: before_bytes[to],
+                diff: StringDiff {
+                    from: before_chars[from],
+                    to: before_chars[to],
+                    insert: after
+                        [after_bytes[prefix + after_index]..after_bytes[prefix + after_match]]
+                        .to_string(),
+                },
+            });
+        }
+        before_index = before_match + 1;
+        after_index = after_match + 1;
+    }
+    hunks
+}
+
+/// Use Myers' algorithm to find the longest common subsequence of `a` and `b`,
+/// returning the index into `a` and `b` of each matching element, in order.
+/// Return `None` if more than `MAX_EDIT_DISTANCE` insertions and deletions
+/// separate them.
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max_d = min(a.len() + b.len(), MAX_EDIT_DISTANCE) as isize;
+    // Diagonal `k` is stored at index `k + offset` of `v`, which contains the
+    // furthest `x` reached on each diagonal.
+    let offset = max_d + 1;
+    let mut v = vec![0isize; 2 * max_d as usize + 3];
+    // A copy of `v` before each step, used to recover the path taken.
+    let mut trace = Vec::new();
+    // Move down (an insertion) if this diagonal is the lowest possible, or if
+    // the diagonal above has gone further; otherwise, move right (a deletion).
+    let is_down = |v: &[isize], d: isize, k: isize| {
+        k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize])
+    };
+
+    let mut found = false;
+    'search: for d in 0..=max_d {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if is_down(&v, d, k) {
+                v[(k + 1 + offset) as usize]
+            } else {
+                v[(k - 1 + offset) as usize] + 1
+            };
+            let mut y = x - k;
+            // Follow matching elements.
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                found = true;
+                break 'search;
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    // Walk back from the end, collecting the matching elements along the path.
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if is_down(v, d, k) { k + 1 } else { k - 1 };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    matches.reverse();
+    Some(matches)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{apply_str_diffs, diff_str, myers, tokenize, DiffGranularity, MAX_EDIT_DISTANCE};
+    use crate::processing::StringDiff;
+
+    fn sd(from: usize, to: usize, insert: &str) -> StringDiff {
+        StringDiff {
+            from,
+            to,
+            insert: insert.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("a_1  b.,c\n\nd", DiffGranularity::Word),
+            vec!["a_1", "  ", "b", ".", ",", "c", "\n", "\n", "d"]
+        );
+        assert_eq!(tokenize("a\nb", DiffGranularity::Line), vec!["a\n", "b"]);
+        assert_eq!(tokenize("éa", DiffGranularity::Char), vec!["é", "a"]);
+        assert!(tokenize("", DiffGranularity::Word).is_empty());
+    }
+
+    #[test]
+    fn test_myers() {
+        assert_eq!(myers::<char>(&[], &[]), Some(vec![]));
+        assert_eq!(
+            myers(&['a', 'b', 'c'], &['a', 'c']),
+            Some(vec![(0, 0), (2, 1)])
+        );
+        let a: Vec<_> = "abcabba".chars().collect();
+        let b: Vec<_> = "cbabac".chars().collect();
+        // The longest common subsequence has 4 elements.
+        assert_eq!(myers(&a, &b).unwrap().len(), 4);
+        // Too many changes.
+        let b = vec!['x'; MAX_EDIT_DISTANCE + 1];
+        assert_eq!(myers(&[], &b), None);
+    }
+
+    #[test]
+    fn test_diff_str() {
+        use DiffGranularity::{Char, Line, Word};
+        for granularity in [Line, Word, Char] {
+            assert_eq!(diff_str("abc", "abc", granularity), vec![]);
+            assert_eq!(diff_str("", "a\n", granularity), vec![sd(0, 0, "a\n")]);
+        }
+        assert_eq!(diff_str("abc", "aXc", Char), vec![sd(1, 2, "X")]);
+        assert_eq!(diff_str("abc", "abXc", Char), vec![sd(2, 2, "X")]);
+        assert_eq!(diff_str("abc", "ac", Char), vec![sd(1, 2, "")]);
+        // The prefix and suffix don't overlap.
+        assert_eq!(diff_str("aa", "aaa", Char), vec![sd(2, 2, "a")]);
+        // Offsets are in characters, not bytes.
+        assert_eq!(diff_str("éa", "éb", Char), vec![sd(1, 2, "b")]);
+
+        // Change one word.
+        let before = "one two three\nfour five six\n";
+        let after = "one two three\nfour 5 six\n";
+        assert_eq!(diff_str(before, after, Word), vec![sd(19, 23, "5")]);
+        assert_eq!(
+            diff_str(before, after, Line),
+            vec![sd(14, 28, "four 5 six\n")]
+        );
+        assert_eq!(diff_str(before, after, Char), vec![sd(19, 23, "5")]);
+
+        // Insert and delete lines.
+        let before = "a\nb\nc\nd\n";
+        assert_eq!(
+            diff_str(before, "a\nb\nX\nc\nd\n", Line),
+            vec![sd(4, 4, "X\n")]
+        );
+        assert_eq!(diff_str(before, "a\nc\nd\n", Line), vec![sd(2, 4, "")]);
+
+        // Separate edits produce separate changes.
+        assert_eq!(
+            diff_str(before, "A\nb\nc\nD\n", Word),
+            vec![sd(0, 1, "A"), sd(6, 7, "D")]
+        );
+        assert_eq!(
+            diff_str("x = 1\ny = 2\n", "x = 10\ny = 2\nz = 3\n", Word),
+            vec![sd(4, 5, "10"), sd(12, 12, "z = 3\n")]
+        );
+
+        // Moving a line deletes then inserts it, rather than replacing every
+        // line in between.
+        assert_eq!(
+            diff_str("a\nb\nc\nd\n", "b\nc\nd\na\n", Line),
+            vec![sd(0, 2, ""), sd(8, 8, "a\n")]
+        );
+    }
+
+    #[test]
+    fn test_apply_str_diffs() {
+        let cases = [
+            ("", "abc"),
+            ("abc", ""),
+            (
+                "<p>One <em>two</em> three.</p>\n",
+                "<p>One <em>2</em> three!</p>\n",
+            ),
+            ("é\nb\nc\n", "a\nb\nç\nd\n"),
+        ];
+        for (before, after) in cases {
+            for granularity in [
+                DiffGranularity::Line,
+                DiffGranularity::Word,
+                DiffGranularity::Char,
+            ] {
+                let diffs = diff_str(before, after, granularity);
+                assert_eq!(apply_str_diffs(before, &diffs), after);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_str_fallback() {
+        // Too many changes produce a single change between the common prefix
+        // and suffix.
+        let before = "start\n".to_string() + &"a\n".repeat(MAX_EDIT_DISTANCE) + "end\n";
+        let after = "start\n".to_string() + &"b\n".repeat(MAX_EDIT_DISTANCE) + "end\n";
+        let diffs = diff_str(&before, &after, DiffGranularity::Line);
+        assert_eq!(
+            diffs,
+            vec![sd(
+                6,
+                6 + 2 * MAX_EDIT_DISTANCE,
+                &"b\n".repeat(MAX_EDIT_DISTANCE)
+            )]
+        );
+    }
+}
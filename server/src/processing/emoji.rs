@@ -0,0 +1,203 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `emoji.rs` -- Expand emoji shortcodes
+///
+/// A doc block may contain a shortcode such as `:rocket:`, which is rendered
+/// as the corresponding emoji. Besides the built-in shortcodes below, a project
+/// may define its own (or replace built-in shortcodes) using the `shortcodes`
+/// entry in its configuration. Unknown shortcodes are left unchanged.
+///
+/// Each expanded shortcode is rendered as an HTML element which records its
+/// name, so that the Client can restore the shortcode when saving.
+// ## Imports
+//
+// ### Standard library
+use std::collections::BTreeMap;
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use regex::Regex;
+
+// ### Local
+use super::numbering::merge_text;
+
+// ## Globals
+lazy_static! {
+    /// Match a shortcode, such as `:rocket:`.
+    static ref SHORTCODE_REGEX: Regex = Regex::new(r":([a-z0-9_+-]+):").unwrap();
+}
+
+/// The built-in shortcodes, a commonly-used subset of those supported by
+/// GitHub.
+const BUILT_IN_SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("check", "✔️"),
+    ("construction", "🚧"),
+    ("eyes", "👀"),
+    ("fire", "🔥"),
+    ("heart", "❤️"),
+    ("heavy_check_mark", "✔️"),
+    ("hourglass", "⌛"),
+    ("information_source", "ℹ️"),
+    ("lock", "🔒"),
+    ("memo", "📝"),
+    ("no_entry", "⛔"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rocket", "🚀"),
+    ("smile", "😄"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("tada", "🎉"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("warning", "⚠️"),
+    ("white_check_mark", "✅"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("zap", "⚡"),
+];
+
+// ## Code
+/// Replace the shortcodes in the provided Markdown events with their emoji,
+/// using the project's `shortcodes` in addition to the built-in shortcodes.
+pub fn expand_shortcodes<'a>(
+    events: Vec<Event<'a>>,
+    shortcodes: &BTreeMap<String, String>,
+) -> Vec<Event<'a>> {
+    // Avoid any work in the common case.
+    if !events
+        .iter()
+        .any(|event| matches!(event, Event::Text(text) if text.contains(':')))
+    {
+        return events;
+    }
+
+    let mut output = Vec::with_capacity(events.len());
+    let mut in_code_block = false;
+    for event in merge_text(events) {
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                let mut last_end = 0;
+                let mut search_start = 0;
+                while let Some(captures) = SHORTCODE_REGEX.captures_at(text, search_start) {
+                    let whole = captures.get(0).unwrap();
+                    let name = &captures[1];
+                    let Some(emoji) = find_emoji(name, shortcodes) else {
+                        // The closing colon of an unknown shortcode may open
+                        // the next shortcode, as in `:a:rocket:`.
+                        search_start = whole.end() - 1;
+                        continue;
+                    };
+                    if whole.start() > last_end {
+                        output.push(Event::Text(
+                            text[last_end..whole.start()].to_string().into(),
+                        ));
+                    }
+                    output.push(Event::InlineHtml(
+                        format!(
+                            "<span class=\"CodeChat-emoji\" data-shortcode=\"{name}\">{emoji}</span>"
+                        )
+                        .into(),
+                    ));
+                    last_end = whole.end();
+                    search_start = last_end;
+                }
+                if last_end > 0 {
+                    if last_end < text.len() {
+                        output.push(Event::Text(text[last_end..].to_string().into()));
+                    }
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        output.push(event);
+    }
+    output
+}
+
+/// Return the emoji for the named shortcode, preferring the project's
+/// shortcodes.
+fn find_emoji<'a>(name: &str, shortcodes: &'a BTreeMap<String, String>) -> Option<&'a str> {
+    shortcodes
+        .get(name)
+        .map(|emoji| emoji.as_str())
+        .or_else(|| {
+            BUILT_IN_SHORTCODES
+                .iter()
+                .find(|(built_in_name, _)| *built_in_name == name)
+                .map(|(_, emoji)| *emoji)
+        })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pulldown_cmark::{html, Parser};
+
+    use super::expand_shortcodes;
+
+    fn render(markdown: &str, shortcodes: &BTreeMap<String, String>) -> String {
+        let mut html_output = String::new();
+        html::push_html(
+            &mut html_output,
+            expand_shortcodes(Parser::new(markdown).collect(), shortcodes).into_iter(),
+        );
+        html_output
+    }
+
+    #[test]
+    fn test_expand_shortcodes() {
+        let shortcodes = BTreeMap::new();
+        assert_eq!(
+            render("Ship it :rocket:!", &shortcodes),
+            "<p>Ship it <span class=\"CodeChat-emoji\" data-shortcode=\"rocket\">🚀</span>!</p>\n"
+        );
+        // Unknown shortcodes, times, and code are unchanged.
+        assert_eq!(
+            render("At 10:30:00 :unknown: `:rocket:`", &shortcodes),
+            "<p>At 10:30:00 :unknown: <code>:rocket:</code></p>\n"
+        );
+        assert_eq!(
+            render("```\n:rocket:\n```\n", &shortcodes),
+            "<pre><code>:rocket:\n</code></pre>\n"
+        );
+        // Adjacent shortcodes, with an unknown shortcode before them.
+        assert_eq!(
+            render(":a:tada::x:", &shortcodes),
+            "<p>:a<span class=\"CodeChat-emoji\" data-shortcode=\"tada\">🎉</span><span class=\"CodeChat-emoji\" data-shortcode=\"x\">❌</span></p>\n"
+        );
+
+        // A project may add or replace shortcodes.
+        let shortcodes = BTreeMap::from([
+            ("rocket".to_string(), "🛸".to_string()),
+            ("tm".to_string(), "™".to_string()),
+        ]);
+        assert_eq!(
+            render(":rocket: :tm:", &shortcodes),
+            "<p><span class=\"CodeChat-emoji\" data-shortcode=\"rocket\">🛸</span> <span class=\"CodeChat-emoji\" data-shortcode=\"tm\">™</span></p>\n"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `encryption.rs` -- Encrypt data the Server saves to disk
+///
+/// Doc blocks may contain proprietary information, which the Server copies to
+/// disk when it saves cached translations. On a shared machine, other users
+/// may be able to read these files. Starting the Server with `serve
+/// --encrypt-cache` encrypts this data using ChaCha20-Poly1305, which also
+/// detects tampering.
+///
+/// The key is random, created the first time it's needed, then stored in the
+/// current user's OS keyring (the macOS Keychain, the Windows Credential
+/// Manager, or the Secret Service on Linux), so that each user has their own
+/// key. If the keyring isn't available, nothing is saved, rather than saving
+/// unencrypted data.
+///
+/// Encrypted data begins with a short header identifying it, followed by the
+/// random nonce used to encrypt it, then the ciphertext.
+// ## Imports
+//
+// ### Standard library
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+// ### Third-party
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use keyring::Entry;
+
+// ## Globals
+/// The keyring service storing the key.
+const KEYRING_SERVICE: &str = "CodeChat Editor";
+
+/// The keyring entry, within this service, storing the key.
+const KEYRING_USER: &str = "cache-encryption-key";
+
+/// The header which begins encrypted data.
+const HEADER: &[u8] = b"CodeChat-encrypted-1\n";
+
+/// The length of a nonce, in bytes.
+const NONCE_LENGTH: usize = 12;
+
+/// True if data saved to disk should be encrypted.
+static ENCRYPTION_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The key, once read from (or created in) the keyring.
+static KEY: Mutex<Option<Key>> = Mutex::new(None);
+
+// ## Code
+/// Enable or disable encrypting data saved to disk.
+pub fn set_encryption(enabled: bool) {
+    ENCRYPTION_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Return true if data saved to disk should be encrypted.
+pub fn is_encryption_enabled() -> bool {
+    ENCRYPTION_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Return true if the provided data was encrypted by `encrypt`.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(HEADER)
+}
+
+/// Encrypt the provided data using the current user's key.
+pub fn encrypt(plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    encrypt_with_key(&user_key()?, plaintext)
+}
+
+/// Decrypt data produced by `encrypt` using the current user's key.
+pub fn decrypt(data: &[u8]) -> Result<Vec<u8>, String> {
+    decrypt_with_key(&user_key()?, data)
+}
+
+fn encrypt_with_key(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|err| format!("Unable to encrypt: {err}."))?;
+    let mut data = Vec::with_capacity(HEADER.len() + NONCE_LENGTH + ciphertext.len());
+    data.extend_from_slice(HEADER);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+fn decrypt_with_key(key: &Key, data: &[u8]) -> Result<Vec<u8>, String> {
+    let Some(data) = data.strip_prefix(HEADER) else {
+        return Err("The data isn't encrypted.".to_string());
+    };
+    if data.len() < NONCE_LENGTH {
+        return Err("The encrypted data is truncated.".to_string());
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+    ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            "Unable to decrypt; the data was changed or encrypted by another user.".to_string()
+        })
+}
+
+// Return the current user's key, reading it from the OS keyring, or creating
+// it there if it doesn't exist.
+fn user_key() -> Result<Key, String> {
+    let mut key = KEY.lock().unwrap();
+    if let Some(key) = *key {
+        return Ok(key);
+    }
+    let entry = Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .map_err(|err| format!("Unable to access the OS keyring: {err}."))?;
+    let user_key = match entry.get_secret() {
+        Ok(secret) => {
+            if secret.len() != 32 {
+                return Err(format!(
+                    "The key stored in the OS keyring under \"{KEYRING_SERVICE}\" is invalid."
+                ));
+            }
+            *Key::from_slice(&secret)
+        }
+        Err(keyring::Error::NoEntry) => {
+            let user_key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            entry
+                .set_secret(&user_key)
+                .map_err(|err| format!("Unable to save a key to the OS keyring: {err}."))?;
+            user_key
+        }
+        Err(err) => return Err(format!("Unable to read a key from the OS keyring: {err}.")),
+    };
+    *key = Some(user_key);
+    Ok(user_key)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use chacha20poly1305::{aead::KeyInit, ChaCha20Poly1305, Key};
+
+    use super::{decrypt_with_key, encrypt_with_key, is_encrypted, HEADER};
+
+    #[test]
+    fn test_encryption() {
+        let key = ChaCha20Poly1305::generate_key(&mut chacha20poly1305::aead::OsRng);
+        let data = encrypt_with_key(&key, b"Proprietary doc block").unwrap();
+        assert!(is_encrypted(&data));
+        assert!(!is_encrypted(b"{\"Toc\": \"\"}"));
+        assert_eq!(
+            decrypt_with_key(&key, &data).unwrap(),
+            b"Proprietary doc block"
+        );
+        // Each encryption uses a different nonce.
+        assert_ne!(
+            data,
+            encrypt_with_key(&key, b"Proprietary doc block").unwrap()
+        );
+
+        // Another key, changed data, or truncated data can't be decrypted.
+        let other_key = Key::from([1; 32]);
+        assert!(decrypt_with_key(&other_key, &data).is_err());
+        let mut changed = data.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert!(decrypt_with_key(&key, &changed).is_err());
+        assert!(decrypt_with_key(&key, &data[..HEADER.len() + 4]).is_err());
+        assert!(decrypt_with_key(&key, b"plain").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `escape.rs` -- Escape and unescape text for HTML
+///
+/// All HTML escaping performed by the server goes through this module. The
+/// characters which must be escaped depend on where the text is placed, so
+/// each call names its context:
+///
+/// - `Text`: element content, such as the text of a `<p>`.
+/// - `Attribute`: a quoted attribute value.
+/// - `Code`: the contents of a `<code>` element. Escaping every `&` matters
+///   here: an unescaped entity such as `&lt;` in code would be displayed as
+///   `<`, rather than as written.
+/// - `Pre`: the contents of a `<pre>` element. An HTML parser discards a
+///   newline which immediately follows the `<pre>` tag, so a leading newline
+///   is doubled to preserve it.
+///
+/// For each context, `unescape` reverses `escape`.
+// ## Imports
+//
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+// ## Globals
+lazy_static! {
+    /// Match a character reference: either a named reference or a decimal or
+    /// hexadecimal numeric reference.
+    static ref CHARACTER_REFERENCE_REGEX: Regex =
+        Regex::new(r"&(?:(amp|lt|gt|quot|apos|nbsp)|#([0-9]{1,7})|#[xX]([0-9a-fA-F]{1,6}));").unwrap();
+}
+
+// ## Data structures
+/// The location in an HTML document where escaped text will be placed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EscapeContext {
+    /// Element content.
+    Text,
+    /// A quoted attribute value.
+    Attribute,
+    /// The contents of a `<code>` element.
+    Code,
+    /// The contents of a `<pre>` element.
+    Pre,
+}
+
+// ## Code
+/// Escape the provided text for placement in the provided context.
+pub fn escape(unsafe_text: &str, context: EscapeContext) -> String {
+    let mut escaped = String::with_capacity(unsafe_text.len());
+    if context == EscapeContext::Pre && unsafe_text.starts_with('\n') {
+        escaped.push('\n');
+    }
+    for c in unsafe_text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if context == EscapeContext::Attribute => escaped.push_str("&quot;"),
+            '\'' if context == EscapeContext::Attribute => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Replace the character references in the provided HTML with the characters
+/// they represent, reversing `escape` for the provided context. Unrecognized
+/// or invalid references are left unchanged.
+pub fn unescape(html: &str, context: EscapeContext) -> String {
+    let html = match context {
+        EscapeContext::Pre => html.strip_prefix('\n').unwrap_or(html),
+        _ => html,
+    };
+    CHARACTER_REFERENCE_REGEX
+        .replace_all(html, |captures: &Captures| {
+            let c = if let Some(name) = captures.get(1) {
+                Some(match name.as_str() {
+                    "amp" => '&',
+                    "lt" => '<',
+                    "gt" => '>',
+                    "quot" => '"',
+                    "apos" => '\'',
+                    _ => '\u{a0}',
+                })
+            } else if let Some(decimal) = captures.get(2) {
+                decimal.as_str().parse().ok().and_then(char::from_u32)
+            } else {
+                u32::from_str_radix(&captures[3], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            };
+            match c {
+                Some(c) => c.to_string(),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{escape, unescape, EscapeContext};
+
+    const CONTEXTS: [EscapeContext; 4] = [
+        EscapeContext::Text,
+        EscapeContext::Attribute,
+        EscapeContext::Code,
+        EscapeContext::Pre,
+    ];
+
+    #[test]
+    fn test_escape() {
+        let text = r#"<a href="x">Tom's &amp;</a>"#;
+        assert_eq!(
+            escape(text, EscapeContext::Text),
+            r#"&lt;a href="x"&gt;Tom's &amp;amp;&lt;/a&gt;"#
+        );
+        assert_eq!(
+            escape(text, EscapeContext::Attribute),
+            "&lt;a href=&quot;x&quot;&gt;Tom&#39;s &amp;amp;&lt;/a&gt;"
+        );
+        assert_eq!(
+            escape(text, EscapeContext::Code),
+            escape(text, EscapeContext::Text)
+        );
+        assert_eq!(escape("\na < b", EscapeContext::Pre), "\n\na &lt; b");
+        assert_eq!(escape("\na < b", EscapeContext::Code), "\na &lt; b");
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(
+            unescape(
+                "&lt;&amp;lt;&gt; &quot;&apos;&nbsp;&#65;&#x42;&#X43;",
+                EscapeContext::Text
+            ),
+            "<&lt;> \"'\u{a0}ABC"
+        );
+        // Unknown or invalid references are unchanged.
+        assert_eq!(
+            unescape("&copy; &#xD800; &#; &amp", EscapeContext::Text),
+            "&copy; &#xD800; &#; &amp"
+        );
+        assert_eq!(unescape("\n\na", EscapeContext::Pre), "\na");
+    }
+
+    proptest! {
+        // Escaping, then unescaping, produces the original text in every
+        // context.
+        #[test]
+        fn test_round_trip(text in any::<String>()) {
+            for context in CONTEXTS {
+                prop_assert_eq!(unescape(&escape(&text, context), context), text.clone());
+            }
+        }
+
+        // Escaped text contains no characters which are special in its
+        // context.
+        #[test]
+        fn test_escaped_is_safe(text in any::<String>()) {
+            for context in CONTEXTS {
+                let escaped = escape(&text, context);
+                prop_assert!(!escaped.contains(['<', '>']));
+                if context == EscapeContext::Attribute {
+                    prop_assert!(!escaped.contains(['"', '\'']));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `fence_scanner.rs` -- Find the fences in Markdown
+///
+/// Several transformations look for fenced blocks in Markdown before the
+/// Markdown renderer sees it: [admonitions](admonitions.rs) and
+/// [conditional blocks](conditions.rs) are delimited by `:::` fences, while the
+/// [translate command](filter.rs) looks for code blocks delimited by backtick
+/// or tilde fences. A `:::` fence inside a code block is part of the code, so
+/// each must track code blocks the same way; this module does so for all of
+/// them.
+// ## Imports
+//
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// ## Globals
+lazy_static! {
+    /// Match the opening or closing line of a fenced code block, capturing the
+    /// fence characters and the remainder of the line.
+    static ref CODE_FENCE_REGEX: Regex = Regex::new(r"^ {0,3}(`{3,}|~{3,})(.*)$").unwrap();
+    /// Match the opening line of a `:::` block, such as `:::warning Title`,
+    /// capturing the text following the colons.
+    static ref BLOCK_OPEN_REGEX: Regex = Regex::new(r"^ {0,3}:{3,}[ \t]*([A-Za-z].*?)[ \t]*$").unwrap();
+    /// Match the closing line of a `:::` block.
+    static ref BLOCK_CLOSE_REGEX: Regex = Regex::new(r"^ {0,3}:{3,}[ \t]*$").unwrap();
+}
+
+// ## Data structures
+/// The role of a line of Markdown with respect to fences.
+#[derive(Debug, PartialEq)]
+pub enum FenceLine<'a> {
+    /// A line outside any code block which isn't a fence.
+    Text,
+    /// The opening fence of a code block, containing the first word of its
+    /// info string (usually a language), which may be empty.
+    CodeOpen(&'a str),
+    /// A line inside a code block.
+    Code,
+    /// The closing fence of a code block.
+    CodeClose,
+    /// The opening line of a `:::` block, containing the text following the
+    /// colons, such as `warning Title`.
+    BlockOpen(&'a str),
+    /// A line containing only `:::`.
+    BlockClose,
+}
+
+// ## Code
+/// Return each line of the provided Markdown, including its newline, along
+/// with its role. An unterminated code block continues to the end of the
+/// Markdown.
+pub fn fence_lines(markdown: &str) -> impl Iterator<Item = (&str, FenceLine<'_>)> {
+    // The fence of the code block currently open, if any.
+    let mut code_fence: Option<&str> = None;
+    markdown.split_inclusive('\n').map(move |line| {
+        let trimmed_line = line.trim_end_matches(['\n', '\r']);
+        let fence_line = if let Some(captures) = CODE_FENCE_REGEX.captures(trimmed_line) {
+            let fence = captures.get(1).unwrap().as_str();
+            let rest = captures.get(2).unwrap().as_str();
+            match code_fence {
+                // A closing fence must use the same character and be at least
+                // as long as the opening fence, with no info string.
+                Some(open_fence) => {
+                    if fence.starts_with(open_fence) && rest.trim().is_empty() {
+                        code_fence = None;
+                        FenceLine::CodeClose
+                    } else {
+                        FenceLine::Code
+                    }
+                }
+                None => {
+                    code_fence = Some(fence);
+                    FenceLine::CodeOpen(rest.split_whitespace().next().unwrap_or(""))
+                }
+            }
+        } else if code_fence.is_some() {
+            FenceLine::Code
+        } else if BLOCK_CLOSE_REGEX.is_match(trimmed_line) {
+            FenceLine::BlockClose
+        } else if let Some(captures) = BLOCK_OPEN_REGEX.captures(trimmed_line) {
+            FenceLine::BlockOpen(captures.get(1).unwrap().as_str())
+        } else {
+            FenceLine::Text
+        };
+        (line, fence_line)
+    })
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::{fence_lines, FenceLine};
+
+    #[test]
+    fn test_fence_lines() {
+        let markdown = indoc!(
+            "
+            Text
+            :::warning Be careful
+            ```python
+            :::
+            ```` not a close
+            ~~~
+            ````
+            :::
+            ~~~
+            "
+        );
+        let roles: Vec<_> = fence_lines(markdown).map(|(_, role)| role).collect();
+        assert_eq!(
+            roles,
+            vec![
+                FenceLine::Text,
+                FenceLine::BlockOpen("warning Be careful"),
+                FenceLine::CodeOpen("python"),
+                // Fences inside a code block are code.
+                FenceLine::Code,
+                // A closing fence has no info string.
+                FenceLine::Code,
+                // A closing fence uses the same character.
+                FenceLine::Code,
+                // A closing fence may be longer than the opening fence.
+                FenceLine::CodeClose,
+                FenceLine::BlockClose,
+                // An unterminated code block continues to the end.
+                FenceLine::CodeOpen(""),
+            ]
+        );
+        // Each line keeps its newline.
+        assert_eq!(
+            fence_lines("a\r\nb")
+                .map(|(line, _)| line)
+                .collect::<Vec<_>>(),
+            vec!["a\r\n", "b"]
+        );
+    }
+}
@@ -0,0 +1,527 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `fences.rs` -- Render fenced code blocks as diagrams
+///
+/// A fenced code block whose language has a registered fence renderer, such
+/// as:
+///
+/// ````markdown
+/// ```plantuml
+/// Alice -> Bob: Hello
+/// ```
+/// ````
+///
+/// is rendered by that renderer rather than shown as code. Renderers for
+/// Mermaid (`mermaid`), Graphviz (`dot` or `graphviz`), and PlantUML
+/// (`plantuml`) are built in, along with exercises (`quiz`; see
+/// [quiz.rs](quiz.rs)) and quoted code (`include`; see
+/// [transclusion.rs](transclusion.rs)); `register_fence_renderer` adds others. A project
+/// may also configure renderers, given by its `fences` configuration entry,
+/// which take priority over registered renderers: a built-in renderer under
+/// another name, an external command, or a remote service which renders a
+/// fence to an image.
+///
+/// Rendered fences are cached, since some renderers are slow; see
+/// [render_cache.rs](render_cache.rs).
+///
+/// Each rendered fence is wrapped in an element recording its language and
+/// source, so that the Client can restore the fence when saving. Since the
+/// renderers are trusted, their HTML is inserted after the rendered doc blocks
+/// are sanitized; otherwise, strict sanitization would remove diagrams such as
+/// inline SVG.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use log::warn;
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    project_config::{FenceConfig, ProjectConfig, RenderTarget},
+    quiz::QuizRenderer,
+    render_cache::render_cached,
+    transclusion::{TransclusionRenderer, INCLUDE_LANGUAGE},
+    workspace::is_trusted,
+};
+
+// ## Data structures
+/// Render the contents of fenced code blocks in a specific language.
+pub trait FenceRenderer: Send + Sync {
+    /// Return the HTML for a fence containing `code`, rendered for the project
+    /// and target given by `project_config`.
+    fn render(&self, code: &str, project_config: &ProjectConfig) -> Result<String, String>;
+
+    /// Return the files a fence containing `code` is rendered from, so that
+    /// its cached rendering is discarded when they change; see
+    /// `render_cache.rs`.
+    fn dependencies(&self, _code: &str, _project_config: &ProjectConfig) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Render Mermaid diagrams in the Client, using the `wc-mermaid` element.
+struct MermaidRenderer;
+
+impl FenceRenderer for MermaidRenderer {
+    fn render(&self, code: &str, _project_config: &ProjectConfig) -> Result<String, String> {
+        Ok(format!(
+            "<wc-mermaid>{}</wc-mermaid>",
+            escape(code, EscapeContext::Text)
+        ))
+    }
+}
+
+/// Render Graphviz diagrams in the Client, using the `graphviz-graph` element.
+struct GraphvizRenderer;
+
+impl FenceRenderer for GraphvizRenderer {
+    fn render(&self, code: &str, _project_config: &ProjectConfig) -> Result<String, String> {
+        Ok(format!(
+            "<graphviz-graph graph=\"{}\"></graphviz-graph>",
+            escape(code, EscapeContext::Attribute)
+        ))
+    }
+}
+
+/// Render PlantUML diagrams. When exporting a trusted project which provides a
+/// local PlantUML jar, run it to produce an SVG; otherwise, link to an SVG
+/// produced by the project's PlantUML server.
+struct PlantUmlRenderer;
+
+impl FenceRenderer for PlantUmlRenderer {
+    fn render(&self, code: &str, project_config: &ProjectConfig) -> Result<String, String> {
+        let plantuml = &project_config.plantuml;
+        if let (RenderTarget::Export, Some(jar), true) = (
+            project_config.sanitize.target,
+            &plantuml.jar,
+            is_trusted_project(project_config),
+        ) {
+            let svg = run_command(
+                &[
+                    "java".to_string(),
+                    "-jar".to_string(),
+                    jar.to_string_lossy().to_string(),
+                    "-tsvg".to_string(),
+                    "-pipe".to_string(),
+                ],
+                None,
+                code,
+            )?;
+            // Omit the XML declaration which precedes the SVG.
+            return Ok(svg[svg.find("<svg").unwrap_or(0)..].to_string());
+        }
+        // Use PlantUML's hex encoding, which requires no compression.
+        Ok(image_html(
+            &format!(
+                "{}/svg/~h{}",
+                plantuml.server.trim_end_matches('/'),
+                hex_encode(code)
+            ),
+            "PlantUML diagram",
+        ))
+    }
+}
+
+/// Render a fence by running a command, given by a project's configuration.
+struct CommandRenderer {
+    /// The program to run, followed by its arguments.
+    command: Vec<String>,
+    /// The directory to run it in.
+    current_dir: Option<PathBuf>,
+}
+
+impl FenceRenderer for CommandRenderer {
+    fn render(&self, code: &str, _project_config: &ProjectConfig) -> Result<String, String> {
+        run_command(&self.command, self.current_dir.as_deref(), code)
+    }
+}
+
+/// Render a fence as an image produced by a remote service, given by a URL
+/// template in a project's configuration.
+struct UrlRenderer {
+    url_template: String,
+}
+
+impl FenceRenderer for UrlRenderer {
+    fn render(&self, code: &str, _project_config: &ProjectConfig) -> Result<String, String> {
+        if !self.url_template.contains("{source}") && !self.url_template.contains("{hex}") {
+            return Err(format!(
+                "The URL {} contains neither {{source}} nor {{hex}}.",
+                self.url_template
+            ));
+        }
+        Ok(image_html(
+            &self
+                .url_template
+                .replace("{source}", &urlencoding::encode(code))
+                .replace("{hex}", &hex_encode(code)),
+            "Diagram",
+        ))
+    }
+}
+
+// ## Globals
+/// Incremented when a renderer is registered, so that the keys of cached
+/// fences change; see `render_cache.rs`.
+static RENDERERS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// The fence renderers, indexed by the language given in a fence's info
+    /// string.
+    static ref FENCE_RENDERERS: RwLock<HashMap<String, Arc<dyn FenceRenderer>>> = {
+        let mermaid: Arc<dyn FenceRenderer> = Arc::new(MermaidRenderer);
+        let graphviz: Arc<dyn FenceRenderer> = Arc::new(GraphvizRenderer);
+        let plantuml: Arc<dyn FenceRenderer> = Arc::new(PlantUmlRenderer);
+        let quiz: Arc<dyn FenceRenderer> = Arc::new(QuizRenderer);
+        let include: Arc<dyn FenceRenderer> = Arc::new(TransclusionRenderer);
+        RwLock::new(HashMap::from([
+            ("mermaid".to_string(), mermaid),
+            ("dot".to_string(), graphviz.clone()),
+            ("graphviz".to_string(), graphviz),
+            ("plantuml".to_string(), plantuml),
+            ("quiz".to_string(), quiz),
+            (INCLUDE_LANGUAGE.to_string(), include),
+        ]))
+    };
+}
+
+// ## Code
+/// Render fences in the provided language using `renderer`, replacing any
+/// renderer previously registered for this language.
+pub fn register_fence_renderer(language: &str, renderer: Arc<dyn FenceRenderer>) {
+    FENCE_RENDERERS
+        .write()
+        .unwrap()
+        .insert(language.to_string(), renderer);
+    // Don't reuse fences rendered by the renderer this replaces.
+    RENDERERS_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Replace each fence with a registered renderer in the provided Markdown
+/// events by a placeholder, returning the updated events and the HTML for each
+/// placeholder. After sanitizing, `insert_rendered_fences` replaces the
+/// placeholders with this HTML. A fence which fails to render is left
+/// unchanged.
+pub fn render_fences<'a>(
+    events: Vec<Event<'a>>,
+    project_config: &ProjectConfig,
+) -> (Vec<Event<'a>>, Vec<String>) {
+    let renderers = FENCE_RENDERERS.read().unwrap();
+    let mut output = Vec::with_capacity(events.len());
+    let mut rendered_fences = Vec::new();
+    // The events making up the current fence, and the language and renderer
+    // for this fence.
+    let mut fence: Option<(Vec<Event<'a>>, String, Arc<dyn FenceRenderer>)> = None;
+    // Renderers may use any part of the project's configuration, so include
+    // all of it in the cache key; compute this only when needed.
+    let mut config_key: Option<String> = None;
+    for event in events {
+        if let Some((mut fence_events, language, renderer)) = fence.take() {
+            if !matches!(event, Event::End(TagEnd::CodeBlock)) {
+                fence_events.push(event);
+                fence = Some((fence_events, language, renderer));
+                continue;
+            }
+            let code: String = fence_events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Text(text) => Some(&**text),
+                    _ => None,
+                })
+                .collect();
+            let config_key = config_key.get_or_insert_with(|| format!("{project_config:?}"));
+            match render_cached(
+                &(
+                    RENDERERS_GENERATION.load(Ordering::Relaxed),
+                    &language,
+                    &code,
+                    &*config_key,
+                ),
+                renderer.dependencies(&code, project_config),
+                || renderer.render(&code, project_config),
+            ) {
+                Ok(html) => {
+                    output.push(Event::Html(fence_placeholder(rendered_fences.len()).into()));
+                    rendered_fences.push(format!(
+                        "<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"{}\" data-source=\"{}\">{html}</div>\n",
+                        escape(&language, EscapeContext::Attribute),
+                        escape(&code, EscapeContext::Attribute)
+                    ));
+                }
+                Err(err) => {
+                    warn!("Unable to render a {language} fence: {err}");
+                    output.extend(fence_events);
+                    output.push(event);
+                }
+            }
+            continue;
+        }
+
+        let language_renderer = match &event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let language = info.split_whitespace().next().unwrap_or("");
+                find_renderer(language, project_config, &renderers)
+                    .map(|renderer| (language.to_string(), renderer))
+            }
+            _ => None,
+        };
+        match language_renderer {
+            Some((language, renderer)) => fence = Some((vec![event], language, renderer)),
+            None => output.push(event),
+        }
+    }
+    (output, rendered_fences)
+}
+
+/// Find the renderer for fences in the provided language, preferring the
+/// renderers configured by the project.
+fn find_renderer(
+    language: &str,
+    project_config: &ProjectConfig,
+    renderers: &HashMap<String, Arc<dyn FenceRenderer>>,
+) -> Option<Arc<dyn FenceRenderer>> {
+    match project_config.fences.get(language) {
+        Some(FenceConfig::Builtin(name)) => {
+            let renderer = renderers.get(name).cloned();
+            if renderer.is_none() {
+                warn!("Unknown fence renderer {name} for {language} fences.");
+            }
+            renderer
+        }
+        Some(FenceConfig::Command(command)) => {
+            if !is_trusted_project(project_config) {
+                warn!("Not running the render command for {language} fences in an untrusted workspace.");
+                return None;
+            }
+            Some(Arc::new(CommandRenderer {
+                command: command.clone(),
+                current_dir: project_config.root.clone(),
+            }))
+        }
+        Some(FenceConfig::Url(url_template)) => Some(Arc::new(UrlRenderer {
+            url_template: url_template.clone(),
+        })),
+        None => renderers.get(language).cloned(),
+    }
+}
+
+// Return true if the user trusts the project, allowing commands given by its
+// configuration to run; see `workspace.rs`.
+fn is_trusted_project(project_config: &ProjectConfig) -> bool {
+    project_config.root.as_deref().is_none_or(is_trusted)
+}
+
+/// Replace the placeholders produced by `render_fences` with the HTML of the
+/// fences they stand for.
+pub fn insert_rendered_fences(html: &str, rendered_fences: &[String]) -> String {
+    rendered_fences
+        .iter()
+        .enumerate()
+        .fold(html.to_string(), |html, (index, rendered_fence)| {
+            html.replacen(&fence_placeholder(index), rendered_fence, 1)
+        })
+}
+
+// The placeholder for a rendered fence: a comment, which the sanitizer keeps.
+fn fence_placeholder(index: usize) -> String {
+    format!("<!--CodeChat-fence {index}-->")
+}
+
+// Return the HTML for an image.
+fn image_html(src: &str, alt: &str) -> String {
+    format!(
+        "<img src=\"{}\" alt=\"{alt}\">",
+        escape(src, EscapeContext::Attribute)
+    )
+}
+
+// Encode text as the hex digits of its UTF-8 bytes.
+fn hex_encode(text: &str) -> String {
+    text.bytes().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Run `command` (a program followed by its arguments) in `current_dir`,
+/// passing it `input` on stdin, and return its stdout.
+pub fn run_command(
+    command: &[String],
+    current_dir: Option<&Path>,
+    input: &str,
+) -> Result<String, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "The render command is empty.".to_string())?;
+    let mut process = Command::new(program);
+    process
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(current_dir) = current_dir {
+        process.current_dir(current_dir);
+    }
+    let mut process = process
+        .spawn()
+        .map_err(|err| format!("Unable to run {program}; is it installed? {err}"))?;
+    process
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .map_err(|err| format!("Unable to send input to {program}: {err}"))?;
+    let output = process
+        .wait_with_output()
+        .map_err(|err| format!("Error running {program}: {err}"))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "{program} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use pulldown_cmark::{html, Parser};
+
+    use super::{insert_rendered_fences, register_fence_renderer, render_fences, FenceRenderer};
+    use crate::processing::project_config::{FenceConfig, ProjectConfig};
+
+    struct FailingRenderer;
+
+    impl FenceRenderer for FailingRenderer {
+        fn render(&self, _code: &str, _project_config: &ProjectConfig) -> Result<String, String> {
+            Err("Failed.".to_string())
+        }
+    }
+
+    fn render(markdown: &str) -> String {
+        render_with(markdown, &ProjectConfig::default())
+    }
+
+    fn render_with(markdown: &str, project_config: &ProjectConfig) -> String {
+        let (events, rendered_fences) =
+            render_fences(Parser::new(markdown).collect(), project_config);
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+        insert_rendered_fences(&html_output, &rendered_fences)
+    }
+
+    #[test]
+    fn test_render_fences() {
+        assert_eq!(
+            render("```plantuml\nA -> B\n```\n"),
+            "<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"plantuml\" data-source=\"A -&gt; B\n\"><img src=\"https://www.plantuml.com/plantuml/svg/~h41202d3e20420a\" alt=\"PlantUML diagram\"></div>\n"
+        );
+        assert_eq!(
+            render("```mermaid\ngraph TD; A --> B;\n```\n\n```dot extra\ndigraph { A -> B }\n```\n"),
+            "<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"mermaid\" data-source=\"graph TD; A --&gt; B;\n\"><wc-mermaid>graph TD; A --&gt; B;\n</wc-mermaid></div>\n<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"dot\" data-source=\"digraph { A -&gt; B }\n\"><graphviz-graph graph=\"digraph { A -&gt; B }\n\"></graphviz-graph></div>\n"
+        );
+        // Other fences are unchanged.
+        assert_eq!(
+            render("```python\nx = 1\n```\n"),
+            "<pre><code class=\"language-python\">x = 1\n</code></pre>\n"
+        );
+        // So are fences which fail to render.
+        register_fence_renderer("failing", Arc::new(FailingRenderer));
+        assert_eq!(
+            render("```failing\nx\n```\n"),
+            "<pre><code class=\"language-failing\">x\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_configured_fences() {
+        let project_config = ProjectConfig {
+            fences: BTreeMap::from([
+                (
+                    "uml".to_string(),
+                    FenceConfig::Builtin("plantuml".to_string()),
+                ),
+                (
+                    "vega-lite".to_string(),
+                    FenceConfig::Url("https://example.com/render?spec={source}".to_string()),
+                ),
+                (
+                    "bad-url".to_string(),
+                    FenceConfig::Url("https://example.com/render".to_string()),
+                ),
+                (
+                    "mermaid".to_string(),
+                    FenceConfig::Command(vec!["no-such-program-exists".to_string()]),
+                ),
+                (
+                    "unknown".to_string(),
+                    FenceConfig::Builtin("no-such-renderer".to_string()),
+                ),
+            ]),
+            ..Default::default()
+        };
+        // A built-in renderer under another name.
+        assert_eq!(
+            render_with("```uml\nA -> B\n```\n", &project_config),
+            "<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"uml\" data-source=\"A -&gt; B\n\"><img src=\"https://www.plantuml.com/plantuml/svg/~h41202d3e20420a\" alt=\"PlantUML diagram\"></div>\n"
+        );
+        // A remote service.
+        assert_eq!(
+            render_with("```vega-lite\n{\"mark\": \"bar\"}\n```\n", &project_config),
+            "<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"vega-lite\" data-source=\"{&quot;mark&quot;: &quot;bar&quot;}\n\"><img src=\"https://example.com/render?spec=%7B%22mark%22%3A%20%22bar%22%7D%0A\" alt=\"Diagram\"></div>\n"
+        );
+        // Misconfigured renderers, including a project's renderer which
+        // replaces a built-in renderer, leave the fence unchanged.
+        for language in ["bad-url", "mermaid", "unknown"] {
+            assert_eq!(
+                render_with(&format!("```{language}\nx\n```\n"), &project_config),
+                format!("<pre><code class=\"language-{language}\">x\n</code></pre>\n")
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_fences() {
+        let project_config = ProjectConfig {
+            fences: BTreeMap::from([(
+                "upper".to_string(),
+                FenceConfig::Command(vec!["tr".to_string(), "a-z".to_string(), "A-Z".to_string()]),
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_with("```upper\nabc\n```\n", &project_config),
+            "<div class=\"CodeChat-fence\" contenteditable=\"false\" data-language=\"upper\" data-source=\"abc\n\">ABC\n</div>\n"
+        );
+    }
+}
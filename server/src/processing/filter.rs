@@ -0,0 +1,292 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `filter.rs` -- Translate between source code and Markdown
+///
+/// The `translate` command reads a source file from stdin and writes it to
+/// stdout as a Markdown document, or the reverse, so that shell pipelines and
+/// pre-commit hooks can use the CodeChat Editor's lexer. In the Markdown
+/// document, each doc block becomes its Markdown contents, while each code
+/// block becomes a fenced code block whose info string is the language's name,
+/// such as ` ```python `.
+///
+/// When translating Markdown back to source code, fenced code blocks in the
+/// file's language become code, while all other Markdown becomes doc blocks,
+/// using the language's inline comment delimiter (or, if it has none, its
+/// block comment delimiters). Blank lines before and after doc blocks remain
+/// blank lines in the code. Translating source code to Markdown then back
+/// therefore loses the indent and delimiter of each doc block.
+// ## Imports
+//
+// ### Third-party
+use clap::ValueEnum;
+
+// ### Local
+use super::{
+    code_doc_block_vec_to_source,
+    fence_scanner::{fence_lines, FenceLine},
+    find_lexer,
+    project_config::ProjectConfig,
+};
+use crate::lexer::{language_by_name, source_lexer, CodeDocBlock, DocBlock, LanguageLexerCompiled};
+
+// ## Data structures
+/// The formats the `translate` command reads and writes.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum FilterFormat {
+    /// Source code.
+    Src,
+    /// Markdown.
+    Md,
+}
+
+// ## Code
+/// Translate `input` in the `from` format to the `to` format. `language` is
+/// the name of the source code's language, such as `python`, or a file
+/// extension, such as `py`.
+pub fn filter(
+    input: &str,
+    from: FilterFormat,
+    to: FilterFormat,
+    language: &str,
+) -> Result<String, String> {
+    let lexer = find_language(input, language)?;
+    match (from, to) {
+        (FilterFormat::Src, FilterFormat::Md) => Ok(source_to_markdown(input, lexer)),
+        (FilterFormat::Md, FilterFormat::Src) => markdown_to_source(input, lexer),
+        _ => Ok(input.to_string()),
+    }
+}
+
+// Find the lexer for the provided language name or file extension.
+fn find_language(input: &str, language: &str) -> Result<&'static LanguageLexerCompiled, String> {
+    if let Some(lexer) = language_by_name(language) {
+        return Ok(lexer);
+    }
+    find_lexer(input, &language.to_string(), &ProjectConfig::default())?
+        .ok_or_else(|| format!("Unknown language {language}."))
+}
+
+/// Translate source code to a Markdown document.
+pub fn source_to_markdown(source: &str, lexer: &LanguageLexerCompiled) -> String {
+    let lexer_name = lexer.language_lexer.lexer_name.as_str();
+    // Markdown files are already Markdown.
+    if lexer_name == "markdown" {
+        return source.to_string();
+    }
+    let mut markdown = String::new();
+    for code_doc_block in source_lexer(source, lexer) {
+        match code_doc_block {
+            CodeDocBlock::DocBlock(doc_block) => {
+                markdown.push_str(&doc_block.contents);
+                end_line(&mut markdown);
+            }
+            // Leave blank lines as they are, rather than fencing them.
+            CodeDocBlock::CodeBlock(code) if code.trim().is_empty() => markdown.push_str(&code),
+            // Place blank lines before and after the code outside its fence.
+            CodeDocBlock::CodeBlock(code) => {
+                let lines: Vec<&str> = code.split_inclusive('\n').collect();
+                let is_blank = |line: &&&str| line.trim().is_empty();
+                let leading = lines.iter().take_while(is_blank).count();
+                let trailing = lines[leading..].iter().rev().take_while(is_blank).count();
+                let code = lines[leading..lines.len() - trailing].concat();
+                let fence = fence_for(&code);
+                markdown.push_str(&lines[..leading].concat());
+                markdown.push_str(&format!("{fence}{lexer_name}\n{code}"));
+                end_line(&mut markdown);
+                markdown.push_str(&fence);
+                markdown.push('\n');
+                markdown.push_str(&lines[lines.len() - trailing..].concat());
+            }
+        }
+    }
+    markdown
+}
+
+/// Translate a Markdown document to source code.
+pub fn markdown_to_source(markdown: &str, lexer: &LanguageLexerCompiled) -> Result<String, String> {
+    let language_lexer = &lexer.language_lexer;
+    if language_lexer.lexer_name.as_str() == "markdown" {
+        return Ok(markdown.to_string());
+    }
+    let delimiter = match (
+        language_lexer.inline_comment_delim_arr.first(),
+        language_lexer.block_comment_delim_arr.first(),
+    ) {
+        (Some(inline_delimiter), _) => inline_delimiter.clone(),
+        (None, Some(block_delimiter)) => block_delimiter.opening.clone(),
+        (None, None) => {
+            return Err(format!(
+                "{} has no comments, so it can't contain doc blocks.",
+                language_lexer.lexer_name
+            ))
+        }
+    };
+
+    let mut code_doc_blocks = Vec::new();
+    let mut doc = String::new();
+    // True if the code block currently being processed contains code in this
+    // language.
+    let mut is_code = false;
+    let mut code = String::new();
+    for (line, fence_line) in fence_lines(markdown) {
+        match fence_line {
+            FenceLine::CodeOpen(info) => {
+                is_code = info == language_lexer.lexer_name.as_str();
+                if is_code {
+                    push_doc_block(&mut code_doc_blocks, &mut doc, &delimiter);
+                    continue;
+                }
+            }
+            FenceLine::CodeClose if is_code => {
+                is_code = false;
+                code_doc_blocks.push(CodeDocBlock::CodeBlock(std::mem::take(&mut code)));
+                continue;
+            }
+            _ => (),
+        }
+        if is_code {
+            code.push_str(line);
+        } else {
+            doc.push_str(line);
+        }
+    }
+    // Include any unterminated code block.
+    if !code.is_empty() {
+        code_doc_blocks.push(CodeDocBlock::CodeBlock(code));
+    }
+    push_doc_block(&mut code_doc_blocks, &mut doc, &delimiter);
+    code_doc_block_vec_to_source(&code_doc_blocks, lexer)
+}
+
+// Add the provided Markdown as a doc block, placing any blank lines before and
+// after it in code blocks.
+fn push_doc_block(code_doc_blocks: &mut Vec<CodeDocBlock>, doc: &mut String, delimiter: &str) {
+    let lines: Vec<&str> = doc.split_inclusive('\n').collect();
+    let is_blank = |line: &&&str| line.trim().is_empty();
+    let leading = lines.iter().take_while(is_blank).count();
+    let trailing = lines[leading..].iter().rev().take_while(is_blank).count();
+    let push_blank = |code_doc_blocks: &mut Vec<CodeDocBlock>, blank_lines: &[&str]| {
+        if !blank_lines.is_empty() {
+            code_doc_blocks.push(CodeDocBlock::CodeBlock(blank_lines.concat()));
+        }
+    };
+    push_blank(code_doc_blocks, &lines[..leading]);
+    let contents = lines[leading..lines.len() - trailing].concat();
+    if !contents.is_empty() {
+        let mut contents = contents;
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        code_doc_blocks.push(CodeDocBlock::DocBlock(DocBlock {
+            indent: "".to_string(),
+            delimiter: delimiter.to_string(),
+            contents,
+            lines: 0,
+        }));
+    }
+    push_blank(code_doc_blocks, &lines[lines.len() - trailing..]);
+    doc.clear();
+}
+
+// Return a fence longer than any run of backticks in the provided code.
+fn fence_for(code: &str) -> String {
+    let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+// Make sure the provided text ends with a newline, unless it's empty.
+fn end_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::{fence_for, filter, FilterFormat};
+
+    const PYTHON: &str = indoc!(
+        "
+        # A *doc* block.
+        def f():
+            return 1
+
+        # Another doc block.
+        #
+        # Over several lines.
+        a = f()
+        "
+    );
+
+    const MARKDOWN: &str = indoc!(
+        "
+        A *doc* block.
+        ```python
+        def f():
+            return 1
+        ```
+
+        Another doc block.
+
+        Over several lines.
+        ```python
+        a = f()
+        ```
+        "
+    );
+
+    #[test]
+    fn test_source_to_markdown() {
+        assert_eq!(
+            filter(PYTHON, FilterFormat::Src, FilterFormat::Md, "python").unwrap(),
+            MARKDOWN
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_source() {
+        assert_eq!(
+            filter(MARKDOWN, FilterFormat::Md, FilterFormat::Src, "py").unwrap(),
+            PYTHON
+        );
+        // Code in other languages stays in the doc block.
+        assert_eq!(
+            filter(
+                "Example:\n```js\nf();\n```\n",
+                FilterFormat::Md,
+                FilterFormat::Src,
+                "python"
+            )
+            .unwrap(),
+            "# Example:\n# ```js\n# f();\n# ```\n"
+        );
+        // Use block comments when a language has no inline comments.
+        assert_eq!(
+            filter("Styles.\n", FilterFormat::Md, FilterFormat::Src, "css").unwrap(),
+            "/* Styles. */\n"
+        );
+        assert!(filter("", FilterFormat::Md, FilterFormat::Src, "no-such-language").is_err());
+    }
+
+    #[test]
+    fn test_fence_for() {
+        assert_eq!(fence_for("a = 1\n"), "```");
+        assert_eq!(fence_for("s = '````'\n"), "`````");
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `headings.rs` -- Assign anchors to headings
+///
+/// Every heading receives an `id`, so that links may refer to it. A heading
+/// may specify its `id` explicitly (`## Title {#my-id}`); otherwise, the `id`
+/// is a slug generated from the heading's text. Since a generated `id` changes
+/// when the heading's text changes, prefer explicit IDs for headings which are
+/// the target of links from other files.
+///
+/// Generated IDs are unique within a file: a duplicate slug receives a numeric
+/// suffix (`-1`, `-2`, etc.). Generated IDs are marked with a
+/// `data-codechat-slug` attribute, which tells the Client not to save them as
+/// explicit IDs. Finally, the project's redirect map provides additional,
+/// invisible anchors, so that links to a heading's old `id` still work.
+// ## Imports
+//
+// ### Standard library
+use std::collections::HashSet;
+
+// ### Third-party
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    project_config::{HeadingConfig, SlugStyle},
+};
+
+// ## Code
+/// Assign an `id` to each heading in the provided Markdown events which lacks
+/// one, then add anchors for any redirects to each heading's `id`.
+pub fn assign_heading_ids<'a>(events: Vec<Event<'a>>, config: &HeadingConfig) -> Vec<Event<'a>> {
+    // First, gather the explicit IDs, so that generated IDs won't duplicate
+    // them, and the text of each heading.
+    let mut used_ids = HashSet::new();
+    let mut heading_texts = Vec::new();
+    let mut heading_text: Option<String> = None;
+    for event in &events {
+        match event {
+            Event::Start(Tag::Heading { id, .. }) => {
+                if let Some(id) = id {
+                    used_ids.insert(id.to_string());
+                }
+                heading_text = Some(String::new());
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading_text) = &mut heading_text {
+                    heading_text.push_str(text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                heading_texts.extend(heading_text.take());
+            }
+            _ => (),
+        }
+    }
+
+    // Next, assign IDs.
+    let mut heading_texts = heading_texts.into_iter();
+    let mut output = Vec::with_capacity(events.len());
+    for event in events {
+        let (level, mut id, classes, mut attrs) = match event {
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) => (level, id, classes, attrs),
+            _ => {
+                output.push(event);
+                continue;
+            }
+        };
+        let text = heading_texts.next().unwrap_or_default();
+        if id.is_none() && config.slug_style != SlugStyle::None {
+            let slug = unique_slug(&slugify(&text, config.slug_style), &mut used_ids);
+            id = Some(slug.into());
+            attrs.push(("data-codechat-slug".into(), Some("".into())));
+        }
+        let redirect_anchors: Vec<_> = match &id {
+            Some(id) => config
+                .redirects
+                .iter()
+                .filter(|(_, new_id)| new_id.as_str() == &**id)
+                .map(|(old_id, _)| {
+                    Event::InlineHtml(CowStr::from(format!(
+                        "<a id=\"{}\" class=\"CodeChat-anchor-redirect\"></a>",
+                        escape(old_id, EscapeContext::Attribute)
+                    )))
+                })
+                .collect(),
+            None => vec![],
+        };
+        output.push(Event::Start(Tag::Heading {
+            level,
+            id,
+            classes,
+            attrs,
+        }));
+        output.extend(redirect_anchors);
+    }
+    output
+}
+
+/// Produce a slug from the provided heading text, following GitHub's approach:
+/// lowercase the text, remove punctuation, then replace spaces with dashes.
+fn slugify(text: &str, slug_style: SlugStyle) -> String {
+    let slug: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| {
+            if c.is_whitespace() {
+                Some('-')
+            } else if c == '-'
+                || c == '_'
+                || (c.is_alphanumeric() && (slug_style != SlugStyle::Ascii || c.is_ascii()))
+            {
+                Some(c)
+            } else {
+                None
+            }
+        })
+        .collect();
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Make the provided slug unique by appending a numeric suffix if necessary,
+/// then record it as used.
+fn unique_slug(slug: &str, used_ids: &mut HashSet<String>) -> String {
+    let mut unique = slug.to_string();
+    let mut suffix = 1;
+    while used_ids.contains(&unique) {
+        unique = format!("{slug}-{suffix}");
+        suffix += 1;
+    }
+    used_ids.insert(unique.clone());
+    unique
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::{assign_heading_ids, slugify};
+    use crate::processing::project_config::{HeadingConfig, SlugStyle};
+
+    fn render(markdown: &str, config: &HeadingConfig) -> String {
+        let parser = Parser::new_ext(markdown, Options::ENABLE_HEADING_ATTRIBUTES);
+        let mut html_output = String::new();
+        html::push_html(
+            &mut html_output,
+            assign_heading_ids(parser.collect(), config).into_iter(),
+        );
+        html_output
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, `World`!", SlugStyle::GitHub), "hello-world");
+        assert_eq!(slugify(" a_b - c ", SlugStyle::GitHub), "a_b---c");
+        assert_eq!(slugify("Café", SlugStyle::GitHub), "café");
+        assert_eq!(slugify("Café", SlugStyle::Ascii), "caf");
+        assert_eq!(slugify("!!!", SlugStyle::GitHub), "section");
+    }
+
+    #[test]
+    fn test_assign_heading_ids() {
+        let mut config = HeadingConfig::default();
+        config
+            .redirects
+            .insert("old-intro".to_string(), "intro".to_string());
+        assert_eq!(
+            render(
+                indoc!(
+                    "
+                    # Intro
+                    ## Intro
+                    ## Setup {#intro-1}
+                    ### Details {#intro}
+                    "
+                ),
+                &config
+            ),
+            indoc!(
+                r#"
+                <h1 id="intro-2" data-codechat-slug="">Intro</h1>
+                <h2 id="intro-3" data-codechat-slug="">Intro</h2>
+                <h2 id="intro-1">Setup</h2>
+                <h3 id="intro"><a id="old-intro" class="CodeChat-anchor-redirect"></a>Details</h3>
+                "#
+            )
+        );
+
+        // Disabling slugs leaves headings without an explicit ID unchanged.
+        config.slug_style = SlugStyle::None;
+        assert_eq!(render("# Intro\n", &config), "<h1>Intro</h1>\n");
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `lint.rs` -- Check doc blocks for common problems
+///
+/// Each check (rule) looks for one problem:
+///
+/// - `bare-url`: a URL which isn't a link. Markdown doesn't turn these into
+///   links; instead, use `<https://example.com>`.
+/// - `heading-increment`: a heading more than one level deeper than the
+///   preceding heading, such as a `###` following a `#`.
+/// - `trailing-whitespace`: whitespace at the end of a line.
+/// - `line-length`: a line longer than `MAX_LINE_LENGTH` characters which could
+///   be wrapped; a long line whose only excess is a single word, such as a URL,
+///   is allowed.
+/// - `unclosed-fence`: a fenced code block without a closing fence, which
+///   turns the rest of the doc block into code.
+/// - `missing-alt`: an image without alt text, which screen readers use to
+///   describe it, such as `![](diagram.png)` or `<img src="diagram.png">`.
+///
+/// Each problem is reported as a `Diagnostic` whose source is `lint/` followed
+/// by the rule's name. Lines are numbered from the start of the source file, so
+/// that problems may be shown next to the offending line.
+// ## Imports
+//
+// ### Standard library
+use std::{ops::Range, path::Path};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+
+// ### Local
+use super::{
+    diagnostics::{Diagnostic, Severity},
+    doc_blocks_with_lines, find_file_lexer, find_path_to_toc, project_config_for_file,
+};
+use crate::lexer::DocBlock;
+
+// ## Globals
+/// The longest line allowed. This matches the width the Client wraps doc blocks
+/// to.
+pub const MAX_LINE_LENGTH: usize = 80;
+
+lazy_static! {
+    /// Match a URL.
+    static ref URL_REGEX: Regex = Regex::new(r"\b(?:https?|ftp)://[^\s<>()\[\]]+").unwrap();
+    /// Match an HTML `<img>` tag.
+    static ref IMG_TAG_REGEX: Regex = Regex::new(r"(?i)<img\b[^>]*>").unwrap();
+    /// Match an `alt` attribute.
+    static ref ALT_ATTRIBUTE_REGEX: Regex = Regex::new(r"(?i)\salt\s*=").unwrap();
+}
+
+// ## Code
+/// Lint the doc blocks in the provided file.
+pub fn lint_file(file_contents: &str, file_path: &Path) -> Result<Vec<Diagnostic>, String> {
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let lexer = find_file_lexer(file_contents, file_path, &project_config)?;
+    Ok(lint_doc_blocks(
+        file_contents,
+        &doc_blocks_with_lines(file_contents, lexer),
+    ))
+}
+
+/// Lint the provided doc blocks, each paired with the line it begins on, from
+/// the source file `file_contents`. Return the problems found, ordered by line.
+pub fn lint_doc_blocks(file_contents: &str, doc_blocks: &[(usize, DocBlock)]) -> Vec<Diagnostic> {
+    let source_lines: Vec<&str> = file_contents.lines().collect();
+    let mut diagnostics = Vec::new();
+    let mut heading_level = None;
+    for (start_line, doc_block) in doc_blocks {
+        // Check the source lines containing this doc block.
+        for line in *start_line..start_line + doc_block.lines {
+            if let Some(source_line) = source_lines.get(line - 1) {
+                lint_line(source_line, line, &mut diagnostics);
+            }
+        }
+        lint_markdown(
+            &doc_block.contents,
+            *start_line,
+            &mut heading_level,
+            &mut diagnostics,
+        );
+    }
+    // Sorting is stable, so problems on the same line remain in the order
+    // found.
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+    diagnostics
+}
+
+/// Report a problem found by the provided rule.
+fn lint_diagnostic(line: usize, rule: &str, message: String) -> Diagnostic {
+    Diagnostic::on_line(Severity::Warning, line, message, &format!("lint/{rule}"))
+}
+
+/// Check the whitespace and length of one line of a doc block.
+fn lint_line(source_line: &str, line: usize, diagnostics: &mut Vec<Diagnostic>) {
+    if source_line.ends_with([' ', '\t']) {
+        diagnostics.push(lint_diagnostic(
+            line,
+            "trailing-whitespace",
+            "Remove the whitespace at the end of this line.".to_string(),
+        ));
+    }
+    let length = source_line.chars().count();
+    // Only report lines which could be wrapped: there's whitespace past the
+    // maximum length.
+    if length > MAX_LINE_LENGTH
+        && source_line
+            .trim_end()
+            .chars()
+            .skip(MAX_LINE_LENGTH)
+            .any(char::is_whitespace)
+    {
+        diagnostics.push(lint_diagnostic(
+            line,
+            "line-length",
+            format!("This line is {length} characters long; wrap it to at most {MAX_LINE_LENGTH} characters."),
+        ));
+    }
+}
+
+/// Check the Markdown of one doc block, which begins on `start_line`. The
+/// `heading_level` is the level of the last heading seen in preceding doc
+/// blocks.
+fn lint_markdown(
+    markdown: &str,
+    start_line: usize,
+    heading_level: &mut Option<usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let line_of = |offset: usize| start_line + markdown[..offset].matches('\n').count();
+    // Render as the CodeChat Editor does.
+    let mut options = Options::all();
+    options.remove(Options::ENABLE_SMART_PUNCTUATION | Options::ENABLE_MATH);
+    let mut link_depth = 0;
+    let mut in_code_block = false;
+    // The start of the image being parsed, and its alt text so far.
+    let mut image_start = None;
+    let mut image_alt = String::new();
+    // The Markdown parser may split text into several adjacent events; gather
+    // the source of each run of text outside links and code.
+    let mut text_ranges: Vec<Range<usize>> = Vec::new();
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let level = level as usize;
+                if let Some(previous_level) = *heading_level {
+                    if level > previous_level + 1 {
+                        diagnostics.push(lint_diagnostic(
+                            line_of(range.start),
+                            "heading-increment",
+                            format!("This level {level} heading follows a level {previous_level} heading; increase heading levels by one at a time."),
+                        ));
+                    }
+                }
+                *heading_level = Some(level);
+            }
+            Event::Start(Tag::Image { .. }) => {
+                link_depth += 1;
+                image_start = Some(range.start);
+                image_alt.clear();
+            }
+            Event::End(TagEnd::Image) => {
+                link_depth -= 1;
+                if let Some(start) = image_start.take() {
+                    if image_alt.trim().is_empty() {
+                        diagnostics.push(missing_alt_diagnostic(line_of(start)));
+                    }
+                }
+            }
+            Event::Start(Tag::Link { .. }) => link_depth += 1,
+            Event::End(TagEnd::Link) => link_depth -= 1,
+            Event::Text(ref text) | Event::Code(ref text) if image_start.is_some() => {
+                image_alt.push_str(text)
+            }
+            Event::Html(ref html) | Event::InlineHtml(ref html) => {
+                for img_tag in IMG_TAG_REGEX.find_iter(html) {
+                    if !ALT_ATTRIBUTE_REGEX.is_match(img_tag.as_str()) {
+                        diagnostics.push(missing_alt_diagnostic(line_of(
+                            range.start + img_tag.start(),
+                        )));
+                    }
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                in_code_block = true;
+                if let CodeBlockKind::Fenced(_) = kind {
+                    if !is_fence_closed(&markdown[range.clone()]) {
+                        diagnostics.push(lint_diagnostic(
+                            line_of(range.start),
+                            "unclosed-fence",
+                            "This fenced code block has no closing fence.".to_string(),
+                        ));
+                    }
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(_) if link_depth == 0 && !in_code_block => match text_ranges.last_mut() {
+                Some(last_range) if last_range.end == range.start => last_range.end = range.end,
+                _ => text_ranges.push(range),
+            },
+            _ => (),
+        }
+    }
+
+    // Search the source of the text, rather than the text itself, so that
+    // offsets remain correct.
+    for range in text_ranges {
+        for url in URL_REGEX.find_iter(&markdown[range.clone()]) {
+            // Omit any punctuation which ends the enclosing sentence.
+            let url_str = url
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            diagnostics.push(lint_diagnostic(
+                line_of(range.start + url.start()),
+                "bare-url",
+                format!("Make this URL a link by enclosing it in angle brackets: <{url_str}>."),
+            ));
+        }
+    }
+}
+
+/// Report an image without alt text.
+fn missing_alt_diagnostic(line: usize) -> Diagnostic {
+    lint_diagnostic(
+        line,
+        "missing-alt",
+        "Describe this image for screen readers by providing alt text, such as ![A diagram of the parser](parser.png) or <img alt=\"...\">.".to_string(),
+    )
+}
+
+/// Given the source of a fenced code block, determine if it ends with a
+/// closing fence.
+fn is_fence_closed(code_block: &str) -> bool {
+    let mut lines = code_block.trim_end_matches('\n').lines();
+    let Some(opening) = lines.next().map(str::trim_start) else {
+        return false;
+    };
+    let Some(fence_char) = opening.chars().next() else {
+        return false;
+    };
+    let fence_length = opening.chars().take_while(|c| *c == fence_char).count();
+    // The closing fence must be on a line after the opening fence.
+    let Some(closing) = lines.last().map(str::trim) else {
+        return false;
+    };
+    closing.chars().all(|c| c == fence_char) && closing.chars().count() >= fence_length
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::lint_file;
+    use crate::processing::diagnostics::Diagnostic;
+
+    fn rules_and_lines(diagnostics: Vec<Diagnostic>) -> Vec<(String, usize)> {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.source.trim_start_matches("lint/").to_string(),
+                    diagnostic.range.start,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_file() {
+        let source = indoc!(
+            "
+            # # Title
+            a = 1
+            # ### Skipped a level
+            #
+            # See https://example.com, <https://example.com>, and
+            # [a link](https://example.com). \x20
+            b = 2
+            # ```
+            # https://example.com
+            c = 3
+            # A very long line which goes on and on and on, well past the limit of eighty characters in all.
+            # <https://example.com/a/very/long/url/which/cannot/be/wrapped/at/all/so/it/is/ok>
+            #
+            # ![](a.png) ![A *diagram*](b.png) <img src='c.png'> <img alt='' src='d.png'>
+            "
+        );
+        assert_eq!(
+            rules_and_lines(lint_file(source, Path::new("foo.py")).unwrap()),
+            vec![
+                ("heading-increment".to_string(), 3),
+                ("bare-url".to_string(), 5),
+                ("trailing-whitespace".to_string(), 6),
+                ("unclosed-fence".to_string(), 8),
+                ("line-length".to_string(), 11),
+                ("missing-alt".to_string(), 14),
+                ("missing-alt".to_string(), 14),
+            ]
+        );
+
+        // Markdown files are a single doc block.
+        assert_eq!(
+            rules_and_lines(lint_file("# A\n\n### B\n", Path::new("foo.md")).unwrap()),
+            vec![("heading-increment".to_string(), 3)]
+        );
+        assert!(lint_file("", Path::new("foo.unknown")).is_err());
+    }
+}
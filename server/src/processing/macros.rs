@@ -0,0 +1,423 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `macros.rs` -- Expand macros such as `{{today}}`
+///
+/// A doc block may contain a macro such as `{{today}}`, which is replaced by
+/// its value each time the doc block is rendered or exported. The built-in
+/// macros are:
+///
+/// - `{{today}}`: today's date, such as `2024-12-29`.
+/// - `{{git-last-modified}}`: the date of the last commit which changed the
+///   file containing the doc block.
+/// - `{{version}}`: the most recent git tag reachable from the current commit,
+///   as given by `git describe --tags`, such as `v1.2.0-3-gabc1234`.
+///
+/// A project may define its own macros (or replace built-in macros) using the
+/// `macros` entry in its configuration; `register_macro` adds others. In
+/// addition, `{{var:name}}` is replaced by the variable `name` from the
+/// `variables` entry in the project's configuration, so that values such as
+/// product names and URLs are defined in one place. Unknown macros and
+/// variables, macros in code, and macros which fail to expand are left
+/// unchanged.
+///
+/// Each expanded macro is rendered as an HTML element which records its name,
+/// so that the Client saves the macro, rather than its value.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+// ### Third-party
+use chrono::{DateTime, FixedOffset, Local};
+use git2::{Commit, DescribeOptions, Repository, Sort};
+use lazy_static::lazy_static;
+use log::warn;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use regex::Regex;
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    numbering::merge_text,
+    project_config::ProjectConfig,
+};
+
+// ## Data structures
+/// Provide the value of a macro.
+pub trait Macro: Send + Sync {
+    /// Return the value of this macro in the file given by `project_config`.
+    fn expand(&self, project_config: &ProjectConfig) -> Result<String, String>;
+}
+
+/// Today's date.
+struct TodayMacro;
+
+impl Macro for TodayMacro {
+    fn expand(&self, _project_config: &ProjectConfig) -> Result<String, String> {
+        Ok(Local::now().format("%Y-%m-%d").to_string())
+    }
+}
+
+/// The date of the last commit which changed the file.
+struct GitLastModifiedMacro;
+
+impl Macro for GitLastModifiedMacro {
+    fn expand(&self, project_config: &ProjectConfig) -> Result<String, String> {
+        let file_path = project_config
+            .file_path
+            .as_deref()
+            .ok_or("The file being rendered is unknown.")?;
+        git_last_modified(file_path)
+    }
+}
+
+/// The output of `git describe --tags`.
+struct VersionMacro;
+
+impl Macro for VersionMacro {
+    fn expand(&self, project_config: &ProjectConfig) -> Result<String, String> {
+        let file_dir = project_config
+            .file_dir
+            .as_deref()
+            .ok_or("The file being rendered is unknown.")?;
+        let repo = Repository::discover(file_dir)
+            .map_err(|err| format!("{file_dir:?} isn't in a git repository: {err}"))?;
+        repo.describe(
+            DescribeOptions::new()
+                .describe_tags()
+                .show_commit_oid_as_fallback(true),
+        )
+        .and_then(|describe| describe.format(None))
+        .map_err(|err| format!("Unable to describe the current commit: {err}"))
+    }
+}
+
+// ## Globals
+lazy_static! {
+    /// Match a macro, such as `{{today}}`, or a variable, such as
+    /// `{{var:product_name}}`.
+    static ref MACRO_REGEX: Regex =
+        Regex::new(r"\{\{\s*([a-z][a-z0-9_-]*|var:[A-Za-z_][A-Za-z0-9_.-]*)\s*\}\}").unwrap();
+    /// The registered macros, indexed by name.
+    static ref MACROS: RwLock<HashMap<String, Arc<dyn Macro>>> = {
+        let today: Arc<dyn Macro> = Arc::new(TodayMacro);
+        let git_last_modified: Arc<dyn Macro> = Arc::new(GitLastModifiedMacro);
+        let version: Arc<dyn Macro> = Arc::new(VersionMacro);
+        RwLock::new(HashMap::from([
+            ("today".to_string(), today),
+            ("git-last-modified".to_string(), git_last_modified),
+            ("version".to_string(), version),
+        ]))
+    };
+}
+
+// ## Code
+/// Expand macros named `name` using `macro_`, replacing any macro previously
+/// registered with this name.
+pub fn register_macro(name: &str, macro_: Arc<dyn Macro>) {
+    MACROS.write().unwrap().insert(name.to_string(), macro_);
+}
+
+/// Replace the macros in the provided Markdown events with their values, using
+/// the project's `macros` in addition to the registered macros.
+pub fn expand_macros<'a>(events: Vec<Event<'a>>, project_config: &ProjectConfig) -> Vec<Event<'a>> {
+    // Avoid any work in the common case.
+    if !events
+        .iter()
+        .any(|event| matches!(event, Event::Text(text) if text.contains("{{")))
+    {
+        return events;
+    }
+
+    let macros = MACROS.read().unwrap();
+    let mut output = Vec::with_capacity(events.len());
+    let mut in_code_block = false;
+    for event in merge_text(events) {
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                let mut last_end = 0;
+                for captures in MACRO_REGEX.captures_iter(text) {
+                    let whole = captures.get(0).unwrap();
+                    let name = &captures[1];
+                    let Some(value) = macro_value(name, project_config, &macros) else {
+                        continue;
+                    };
+                    if whole.start() > last_end {
+                        output.push(Event::Text(
+                            text[last_end..whole.start()].to_string().into(),
+                        ));
+                    }
+                    output.push(Event::InlineHtml(
+                        format!(
+                            "<span class=\"CodeChat-macro\" data-macro=\"{name}\">{}</span>",
+                            escape(&value, EscapeContext::Text)
+                        )
+                        .into(),
+                    ));
+                    last_end = whole.end();
+                }
+                if last_end > 0 {
+                    if last_end < text.len() {
+                        output.push(Event::Text(text[last_end..].to_string().into()));
+                    }
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        output.push(event);
+    }
+    output
+}
+
+// Return the value of the named macro or variable, or `None` if it's unknown or
+// fails to expand.
+fn macro_value(
+    name: &str,
+    project_config: &ProjectConfig,
+    macros: &HashMap<String, Arc<dyn Macro>>,
+) -> Option<String> {
+    if let Some(variable) = name.strip_prefix("var:") {
+        let value = project_config.variables.get(variable).cloned();
+        if value.is_none() {
+            warn!("Unknown variable {variable}.");
+        }
+        return value;
+    }
+    if let Some(value) = project_config.macros.get(name) {
+        return Some(value.clone());
+    }
+    match macros.get(name)?.expand(project_config) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Unable to expand {{{{{name}}}}}: {err}");
+            None
+        }
+    }
+}
+
+/// Return a string which changes when the value of a macro in the provided
+/// file might, so that cached renderings of the file are updated. Return
+/// `None` for files without macros.
+pub fn macro_cache_input(file_path: &Path, file_contents: &str) -> Option<String> {
+    if !MACRO_REGEX.is_match(file_contents) {
+        return None;
+    }
+    // The date changes `{{today}}`; a new commit may change the git-based
+    // macros.
+    let head = file_path
+        .parent()
+        .and_then(|dir| Repository::discover(dir).ok())
+        .and_then(|repo| repo.refname_to_id("HEAD").ok())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+    Some(format!("{} {head}", Local::now().date_naive()))
+}
+
+// Return the date of the last commit which changed `file_path`.
+fn git_last_modified(file_path: &Path) -> Result<String, String> {
+    let dir = file_path
+        .parent()
+        .ok_or_else(|| format!("{file_path:?} has no directory."))?;
+    let repo = Repository::discover(dir)
+        .map_err(|err| format!("{file_path:?} isn't in a git repository: {err}"))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| format!("The repository containing {file_path:?} has no working tree."))?;
+    // Git reports the working tree's path with symbolic links resolved, so do
+    // the same for the file's path.
+    let relative_path = file_path
+        .canonicalize()
+        .ok()
+        .and_then(|file_path| {
+            let workdir = workdir.canonicalize().ok()?;
+            Some(file_path.strip_prefix(workdir).ok()?.to_path_buf())
+        })
+        .ok_or_else(|| format!("{file_path:?} isn't in the repository's working tree."))?;
+
+    // Return the ID of the file's blob in a commit, if it exists there.
+    let blob_id = |commit: &Commit| {
+        commit
+            .tree()
+            .ok()?
+            .get_path(&relative_path)
+            .ok()
+            .map(|entry| entry.id())
+    };
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk.push_head().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TIME)
+        .map_err(|err| err.to_string())?;
+    for oid in revwalk {
+        let commit = oid
+            .and_then(|oid| repo.find_commit(oid))
+            .map_err(|err| err.to_string())?;
+        let Some(id) = blob_id(&commit) else {
+            continue;
+        };
+        let is_changed = match commit.parent(0) {
+            Ok(parent) => blob_id(&parent) != Some(id),
+            Err(_) => true,
+        };
+        if is_changed {
+            let time = commit.time();
+            return DateTime::from_timestamp(time.seconds(), 0)
+                .zip(FixedOffset::east_opt(time.offset_minutes() * 60))
+                .map(|(date_time, offset)| {
+                    date_time
+                        .with_timezone(&offset)
+                        .format("%Y-%m-%d")
+                        .to_string()
+                })
+                .ok_or_else(|| "The commit's time is invalid.".to_string());
+        }
+    }
+    Err(format!("{relative_path:?} hasn't been committed."))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+
+    use assert_fs::TempDir;
+    use git2::{Repository, Signature, Time};
+    use pulldown_cmark::{html, Parser};
+
+    use super::{expand_macros, git_last_modified, register_macro, Macro};
+    use crate::processing::project_config::ProjectConfig;
+
+    fn render(markdown: &str, project_config: &ProjectConfig) -> String {
+        let mut html_output = String::new();
+        html::push_html(
+            &mut html_output,
+            expand_macros(Parser::new(markdown).collect(), project_config).into_iter(),
+        );
+        html_output
+    }
+
+    struct AnswerMacro;
+
+    impl Macro for AnswerMacro {
+        fn expand(&self, _project_config: &ProjectConfig) -> Result<String, String> {
+            Ok("42 < 43".to_string())
+        }
+    }
+
+    #[test]
+    fn test_expand_macros() {
+        register_macro("test-answer", Arc::new(AnswerMacro));
+        let project_config = ProjectConfig::default();
+        assert_eq!(
+            render("The answer: {{ test-answer }}.", &project_config),
+            "<p>The answer: <span class=\"CodeChat-macro\" data-macro=\"test-answer\">42 &lt; 43</span>.</p>\n"
+        );
+        // Unknown macros and code are unchanged.
+        assert_eq!(
+            render("{{unknown}} `{{test-answer}}`", &project_config),
+            "<p>{{unknown}} <code>{{test-answer}}</code></p>\n"
+        );
+        assert_eq!(
+            render("```\n{{test-answer}}\n```\n", &project_config),
+            "<pre><code>{{test-answer}}\n</code></pre>\n"
+        );
+        // A macro which fails to expand is unchanged; this file is unknown.
+        assert_eq!(
+            render("{{git-last-modified}}", &project_config),
+            "<p>{{git-last-modified}}</p>\n"
+        );
+        // Today's date looks like a date.
+        let html = render("{{today}}", &project_config);
+        assert!(regex::Regex::new(r">\d{4}-\d{2}-\d{2}<")
+            .unwrap()
+            .is_match(&html));
+
+        // A project may add or replace macros.
+        let project_config = ProjectConfig {
+            macros: BTreeMap::from([
+                ("version".to_string(), "2.0".to_string()),
+                ("test-answer".to_string(), "Unknown".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            render("{{version}} {{test-answer}}", &project_config),
+            "<p><span class=\"CodeChat-macro\" data-macro=\"version\">2.0</span> <span class=\"CodeChat-macro\" data-macro=\"test-answer\">Unknown</span></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let project_config = ProjectConfig {
+            variables: BTreeMap::from([
+                ("product_name".to_string(), "Widget & Co.".to_string()),
+                ("today".to_string(), "Not a macro".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            render("Welcome to {{var:product_name}}!", &project_config),
+            "<p>Welcome to <span class=\"CodeChat-macro\" data-macro=\"var:product_name\">Widget &amp; Co.</span>!</p>\n"
+        );
+        // Variables don't replace macros; unknown variables are unchanged.
+        assert!(!render("{{today}}", &project_config).contains("Not a macro"));
+        assert_eq!(
+            render("{{var:missing}}", &project_config),
+            "<p>{{var:missing}}</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_git_last_modified() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let commit = |path: &str, seconds: i64| {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(path)).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature =
+                Signature::new("Test", "test@example.com", &Time::new(seconds, 0)).unwrap();
+            let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            let parents: Vec<_> = parent.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, path, &tree, &parents)
+                .unwrap();
+        };
+        let a_path = temp_dir.path().join("a.py");
+        fs::write(&a_path, "# A\n").unwrap();
+        // 2023-11-14.
+        commit("a.py", 1_700_000_000);
+        fs::write(temp_dir.path().join("b.py"), "# B\n").unwrap();
+        // 2024-01-01; this doesn't change `a.py`.
+        commit("b.py", 1_704_100_000);
+
+        assert_eq!(git_last_modified(&a_path).unwrap(), "2023-11-14");
+        assert_eq!(
+            git_last_modified(&temp_dir.path().join("b.py")).unwrap(),
+            "2024-01-01"
+        );
+        fs::write(temp_dir.path().join("c.py"), "# C\n").unwrap();
+        assert!(git_last_modified(&temp_dir.path().join("c.py")).is_err());
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `media.rs` -- Embed audio and video
+///
+/// An image in a doc block whose destination is a local audio or video file,
+/// such as `![A walkthrough](walkthrough.mp4)`, is rendered as a `<video>` or
+/// `<audio>` element with playback controls, instead of as a broken image.
+/// The element gives the file's MIME type, so that the browser can skip
+/// formats it can't play. It also contains a link to the file, which browsers
+/// that can't play the file show instead; this link is all that remains after
+/// strict sanitization, which removes media elements.
+///
+/// The Server serves these files directly from the filesystem, with support
+/// for range requests, so that the viewer can seek within a recording.
+// ## Imports
+//
+// ### Standard library
+use std::path::Path;
+
+// ### Third-party
+use mime::Mime;
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+// ### Local
+use super::escape::{escape, EscapeContext};
+
+// ## Code
+/// Return the MIME type of the provided path if it's an audio or video file.
+pub fn media_mime_type(path: &Path) -> Option<Mime> {
+    mime_guess::from_path(path)
+        .first()
+        .filter(|mime_type| mime_type.type_() == mime::AUDIO || mime_type.type_() == mime::VIDEO)
+}
+
+/// Return true if the provided path is an audio or video file.
+pub fn is_media_file(path: &Path) -> bool {
+    media_mime_type(path).is_some()
+}
+
+/// Replace each image in the provided Markdown events whose destination is a
+/// local audio or video file with an embedded player.
+pub fn embed_media<'a>(events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    // The destination, title, and MIME type of the media being embedded, its
+    // alt text, and the nesting depth of images in the alt text.
+    let mut media: Option<(String, String, Mime, String, usize)> = None;
+    for event in events {
+        if let Some((src, title, mime_type, mut alt, depth)) = media.take() {
+            match event {
+                Event::End(TagEnd::Image) if depth == 0 => {
+                    output.push(Event::InlineHtml(
+                        media_html(&src, &title, &mime_type, &alt).into(),
+                    ));
+                }
+                _ => {
+                    let depth = match &event {
+                        Event::Start(Tag::Image { .. }) => depth + 1,
+                        Event::End(TagEnd::Image) => depth - 1,
+                        _ => depth,
+                    };
+                    if let Event::Text(text) | Event::Code(text) = &event {
+                        alt.push_str(text);
+                    }
+                    media = Some((src, title, mime_type, alt, depth));
+                }
+            }
+            continue;
+        }
+
+        match &event {
+            Event::Start(Tag::Image {
+                dest_url, title, ..
+            }) => match local_media_mime_type(dest_url) {
+                Some(mime_type) => {
+                    media = Some((
+                        dest_url.to_string(),
+                        title.to_string(),
+                        mime_type,
+                        String::new(),
+                        0,
+                    ));
+                }
+                None => output.push(event),
+            },
+            _ => output.push(event),
+        }
+    }
+    output
+}
+
+// Return the MIME type of the provided URL if it refers to a local audio or
+// video file.
+fn local_media_mime_type(url: &str) -> Option<Mime> {
+    // Only consider relative or absolute paths, not URLs such as
+    // `https://example.com/a.mp4` or `data:`.
+    if url.starts_with("//")
+        || url
+            .split_once(':')
+            .is_some_and(|(scheme, _)| !scheme.contains('/'))
+    {
+        return None;
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    media_mime_type(Path::new(path))
+}
+
+// Return the HTML which embeds the provided media.
+fn media_html(src: &str, title: &str, mime_type: &Mime, alt: &str) -> String {
+    let element = if mime_type.type_() == mime::AUDIO {
+        "audio"
+    } else {
+        "video"
+    };
+    let src = escape(src, EscapeContext::Attribute);
+    let title = if title.is_empty() {
+        String::new()
+    } else {
+        format!(" title=\"{}\"", escape(title, EscapeContext::Attribute))
+    };
+    format!(
+        "<{element} class=\"CodeChat-media\" controls{title}><source src=\"{src}\" type=\"{}\"><a class=\"CodeChat-media\" href=\"{src}\"{title}>{}</a></{element}>",
+        escape(mime_type.essence_str(), EscapeContext::Attribute),
+        escape(alt, EscapeContext::Text)
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pulldown_cmark::{html, Parser};
+
+    use super::{embed_media, is_media_file};
+
+    fn render(markdown: &str) -> String {
+        let mut html_output = String::new();
+        html::push_html(
+            &mut html_output,
+            embed_media(Parser::new(markdown).collect()).into_iter(),
+        );
+        html_output
+    }
+
+    #[test]
+    fn test_embed_media() {
+        assert_eq!(
+            render("![A *walkthrough*](<walk through.mp4> \"Demo\")"),
+            "<p><video class=\"CodeChat-media\" controls title=\"Demo\"><source src=\"walk through.mp4\" type=\"video/mp4\"><a class=\"CodeChat-media\" href=\"walk through.mp4\" title=\"Demo\">A walkthrough</a></video></p>\n"
+        );
+        assert_eq!(
+            render("![Intro](../media/intro.wav?v=2)"),
+            "<p><audio class=\"CodeChat-media\" controls><source src=\"../media/intro.wav?v=2\" type=\"audio/wav\"><a class=\"CodeChat-media\" href=\"../media/intro.wav?v=2\">Intro</a></audio></p>\n"
+        );
+        assert_eq!(
+            render("![Clip](/clip.webm)"),
+            "<p><video class=\"CodeChat-media\" controls><source src=\"/clip.webm\" type=\"video/webm\"><a class=\"CodeChat-media\" href=\"/clip.webm\">Clip</a></video></p>\n"
+        );
+        // Images, remote media, and links to media are unchanged.
+        assert_eq!(
+            render("![A](a.png) ![B](https://example.com/b.mp4) [C](c.mp4)"),
+            "<p><img src=\"a.png\" alt=\"A\" /> <img src=\"https://example.com/b.mp4\" alt=\"B\" /> <a href=\"c.mp4\">C</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_is_media_file() {
+        assert!(is_media_file(Path::new("a.mp4")));
+        assert!(is_media_file(Path::new("a.webm")));
+        assert!(is_media_file(Path::new("a.wav")));
+        assert!(!is_media_file(Path::new("a.png")));
+        assert!(!is_media_file(Path::new("a.py")));
+    }
+}
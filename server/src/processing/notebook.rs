@@ -0,0 +1,335 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `notebook.rs` -- Edit Jupyter notebooks
+///
+/// A [Jupyter notebook](https://nbformat.readthedocs.io/en/latest/format_description.html)
+/// (an `.ipynb` file) is JSON containing a list of cells. To edit one, its
+/// markdown cells become doc blocks and its code cells become code, in the
+/// language of the notebook's kernel; an empty doc block separates adjacent
+/// code cells. The Client sends the notebook back in the `notebook` field of
+/// the file's metadata, which provides everything except the contents of its
+/// cells when saving. Cells are matched to the original cells in order, so
+/// that each keeps its metadata and outputs; outputs aren't shown. Since empty
+/// doc blocks separate cells, empty markdown cells are dropped.
+// ## Imports
+//
+// ### Standard library
+use std::collections::HashSet;
+
+// ### Third-party
+use serde::Serialize;
+use serde_json::{json, ser::PrettyFormatter, Serializer, Value};
+
+// ### Local
+use super::{
+    code_doc_blocks_to_code_mirror,
+    project_config::{DocBlockMarkup, ProjectConfig},
+    CodeChatForWeb, SourceFileMetadata, TranslationResults,
+};
+use crate::lexer::{CodeDocBlock, DocBlock, LEXERS};
+
+// ## Globals
+/// The extension of a Jupyter notebook.
+pub const NOTEBOOK_EXT: &str = "ipynb";
+
+// ## Code
+/// Translate a Jupyter notebook to the `CodeChatForWeb` format.
+pub fn notebook_to_codechat_for_web(
+    file_contents: &str,
+    project_config: &ProjectConfig,
+) -> TranslationResults {
+    match notebook_to_code_doc_blocks(file_contents) {
+        Ok((mode, code_doc_blocks)) => TranslationResults::CodeChat(CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode,
+                notebook: Some(file_contents.to_string()),
+            },
+            // Markdown cells are always Markdown.
+            source: code_doc_blocks_to_code_mirror(
+                code_doc_blocks,
+                DocBlockMarkup::Markdown,
+                project_config,
+            ),
+        }),
+        Err(err) => TranslationResults::Err(err),
+    }
+}
+
+// Translate a notebook to code and doc blocks, also returning the name of the
+// lexer for its language.
+fn notebook_to_code_doc_blocks(file_contents: &str) -> Result<(String, Vec<CodeDocBlock>), String> {
+    let notebook = parse_notebook(file_contents)?;
+    let lexer_name = notebook_language(&notebook);
+    let lexer = LEXERS
+        .map_mode_to_lexer
+        .get(&lexer_name)
+        .ok_or_else(|| format!("Unknown notebook language {lexer_name}."))?;
+    let delimiter = lexer
+        .language_lexer
+        .inline_comment_delim_arr
+        .first()
+        .cloned()
+        .unwrap_or_default();
+
+    let mut code_doc_blocks = Vec::new();
+    for cell in cells(&notebook)? {
+        let source = cell_source(cell);
+        if cell["cell_type"] == "markdown" {
+            let contents = source + "\n";
+            code_doc_blocks.push(CodeDocBlock::DocBlock(DocBlock {
+                indent: "".to_string(),
+                delimiter: delimiter.clone(),
+                lines: contents.lines().count(),
+                contents,
+            }));
+        } else {
+            if let Some(CodeDocBlock::CodeBlock(_)) = code_doc_blocks.last() {
+                code_doc_blocks.push(CodeDocBlock::DocBlock(DocBlock {
+                    indent: "".to_string(),
+                    delimiter: delimiter.clone(),
+                    contents: "".to_string(),
+                    lines: 1,
+                }));
+            }
+            code_doc_blocks.push(CodeDocBlock::CodeBlock(source + "\n"));
+        }
+    }
+    Ok((lexer_name, code_doc_blocks))
+}
+
+/// Translate code and doc blocks to a Jupyter notebook, taking everything
+/// except the contents of its cells from `notebook`, the notebook they were
+/// translated from.
+pub fn code_doc_blocks_to_notebook(
+    code_doc_blocks: &[CodeDocBlock],
+    notebook: &str,
+) -> Result<String, String> {
+    let mut notebook = parse_notebook(notebook)?;
+    let (markdown_cells, code_cells): (Vec<_>, Vec<_>) = cells(&notebook)?
+        .iter()
+        .cloned()
+        .partition(|cell| cell["cell_type"] == "markdown");
+    let mut markdown_cells = markdown_cells.into_iter();
+    // Raw cells are edited as code, so they're matched with code cells.
+    let mut code_cells = code_cells.into_iter();
+    // Notebook format 4.5 and later require a unique ID for each cell.
+    let uses_ids =
+        notebook["nbformat"].as_u64() == Some(4) && notebook["nbformat_minor"].as_u64() >= Some(5);
+    let mut ids: HashSet<String> = cells(&notebook)?
+        .iter()
+        .filter_map(|cell| cell["id"].as_str().map(str::to_string))
+        .collect();
+
+    let mut new_cells = Vec::new();
+    for code_doc_block in code_doc_blocks {
+        let (source, original_cell) = match code_doc_block {
+            CodeDocBlock::DocBlock(doc_block) => {
+                // Empty doc blocks separate code cells.
+                if doc_block.contents.trim().is_empty() {
+                    continue;
+                }
+                (
+                    doc_block.contents.trim_end_matches('\n'),
+                    markdown_cells.next(),
+                )
+            }
+            CodeDocBlock::CodeBlock(code) => {
+                (code.strip_suffix('\n').unwrap_or(code), code_cells.next())
+            }
+        };
+        let mut cell = match original_cell {
+            Some(cell) => cell,
+            None => {
+                let mut cell = if let CodeDocBlock::DocBlock(_) = code_doc_block {
+                    json!({ "cell_type": "markdown", "metadata": {} })
+                } else {
+                    json!({
+                        "cell_type": "code",
+                        "execution_count": null,
+                        "metadata": {},
+                        "outputs": []
+                    })
+                };
+                if uses_ids {
+                    cell["id"] = Value::String(new_cell_id(&mut ids));
+                }
+                // Jupyter sorts the keys of the cells it writes; `source`,
+                // added below, is the last of these.
+                if let Value::Object(map) = &mut cell {
+                    map.sort_keys();
+                }
+                cell
+            }
+        };
+        cell["source"] = source
+            .split_inclusive('\n')
+            .map(|line| Value::String(line.to_string()))
+            .collect();
+        new_cells.push(cell);
+    }
+    notebook["cells"] = Value::Array(new_cells);
+
+    // Write the notebook as Jupyter does: indented by one space, ending with a
+    // newline.
+    let mut bytes = Vec::new();
+    let mut serializer = Serializer::with_formatter(&mut bytes, PrettyFormatter::with_indent(b" "));
+    notebook
+        .serialize(&mut serializer)
+        .map_err(|err| format!("Unable to write notebook: {err}"))?;
+    Ok(String::from_utf8(bytes).unwrap() + "\n")
+}
+
+fn parse_notebook(notebook: &str) -> Result<Value, String> {
+    serde_json::from_str(notebook).map_err(|err| format!("Unable to parse notebook: {err}"))
+}
+
+fn cells(notebook: &Value) -> Result<&Vec<Value>, String> {
+    notebook["cells"]
+        .as_array()
+        .ok_or_else(|| "Notebook has no cells.".to_string())
+}
+
+// Return the name of the lexer for the notebook's language, given by its
+// kernel; Python is the default.
+fn notebook_language(notebook: &Value) -> String {
+    let metadata = &notebook["metadata"];
+    metadata["kernelspec"]["language"]
+        .as_str()
+        .or_else(|| metadata["language_info"]["name"].as_str())
+        .unwrap_or("python")
+        .to_lowercase()
+}
+
+// A cell's source is either a string or a list of lines.
+fn cell_source(cell: &Value) -> String {
+    match &cell["source"] {
+        Value::String(source) => source.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+// Return an ID not used by any other cell.
+fn new_cell_id(ids: &mut HashSet<String>) -> String {
+    let id = (ids.len()..)
+        .map(|index| format!("codechat-{index}"))
+        .find(|id| !ids.contains(id))
+        .unwrap();
+    ids.insert(id.clone());
+    id
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{code_doc_blocks_to_notebook, notebook_to_code_doc_blocks};
+    use crate::lexer::{CodeDocBlock, DocBlock};
+
+    fn doc_block(contents: &str, lines: usize) -> CodeDocBlock {
+        CodeDocBlock::DocBlock(DocBlock {
+            indent: "".to_string(),
+            delimiter: "#".to_string(),
+            contents: contents.to_string(),
+            lines,
+        })
+    }
+
+    fn code_block(code: &str) -> CodeDocBlock {
+        CodeDocBlock::CodeBlock(code.to_string())
+    }
+
+    #[test]
+    fn test_notebook() {
+        let notebook = json!({
+            "cells": [
+                {
+                    "cell_type": "markdown",
+                    "id": "a",
+                    "metadata": {},
+                    "source": ["# Title\n", "Text"]
+                },
+                {
+                    "cell_type": "code",
+                    "execution_count": 1,
+                    "id": "b",
+                    "metadata": {},
+                    "outputs": [{ "name": "stdout", "output_type": "stream", "text": ["1\n"] }],
+                    "source": ["print(1)"]
+                },
+                {
+                    "cell_type": "code",
+                    "execution_count": null,
+                    "id": "c",
+                    "metadata": {},
+                    "outputs": [],
+                    "source": "x = 1\ny = 2"
+                }
+            ],
+            "metadata": { "kernelspec": { "language": "python", "name": "python3" } },
+            "nbformat": 4,
+            "nbformat_minor": 5
+        })
+        .to_string();
+
+        let (mode, code_doc_blocks) = notebook_to_code_doc_blocks(&notebook).unwrap();
+        assert_eq!(mode, "python");
+        assert_eq!(
+            code_doc_blocks,
+            vec![
+                doc_block("# Title\nText\n", 2),
+                code_block("print(1)\n"),
+                // An empty doc block separates adjacent code cells.
+                doc_block("", 1),
+                code_block("x = 1\ny = 2\n"),
+            ]
+        );
+
+        // Edit the code, then add a markdown cell at the end. Cells keep their
+        // outputs; new cells receive an ID.
+        let edited = code_doc_blocks_to_notebook(
+            &[
+                doc_block("# Title\nText\n", 2),
+                code_block("print(2)\n"),
+                doc_block("\n", 1),
+                code_block("x = 1\ny = 2\n"),
+                doc_block("More\n", 1),
+            ],
+            &notebook,
+        )
+        .unwrap();
+        assert!(edited.ends_with("}\n"));
+        let edited: Value = serde_json::from_str(&edited).unwrap();
+        let cells = edited["cells"].as_array().unwrap();
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells[0]["source"], json!(["# Title\n", "Text"]));
+        assert_eq!(cells[1]["source"], json!(["print(2)"]));
+        assert_eq!(cells[1]["outputs"][0]["text"], json!(["1\n"]));
+        assert_eq!(cells[2]["source"], json!(["x = 1\n", "y = 2"]));
+        assert_eq!(cells[2]["id"], "c");
+        assert_eq!(
+            cells[3],
+            json!({
+                "cell_type": "markdown",
+                "id": "codechat-3",
+                "metadata": {},
+                "source": ["More"]
+            })
+        );
+        assert_eq!(edited["metadata"]["kernelspec"]["name"], "python3");
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `numbering.rs` -- Number figures, tables, and equations
+///
+/// A doc block labels a figure, table, or equation by placing `{#fig:name}`,
+/// `{#tbl:name}`, or `{#eq:name}` in its caption; `\ref{fig:name}` then refers
+/// to it. Labels are numbered in order of appearance within a file, separately
+/// for each kind. When the project enables chapter numbers, each number is
+/// prefixed by the file's position in the project's table of contents, making
+/// numbers unique across the project.
+///
+/// Both labels and references are rendered as HTML elements which record their
+/// original text, so that the Client can restore it.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use regex::{Captures, Regex};
+
+// ### Local
+use super::project_config::toc_files;
+
+// ## Globals
+lazy_static! {
+    /// Match either a label (`{#fig:name}`) or a reference (`\ref{fig:name}`).
+    static ref LABEL_OR_REF_REGEX: Regex = Regex::new(r"\{#((fig|tbl|eq):[\w.-]+)\}|\\ref\{((?:fig|tbl|eq):[\w.-]+)\}").unwrap();
+}
+
+// ## Code
+/// Number the labels in the provided Markdown events, replacing each label and
+/// reference with its number. If provided, prefix each number with the
+/// `chapter`.
+pub fn number_labels<'a>(events: Vec<Event<'a>>, chapter: Option<usize>) -> Vec<Event<'a>> {
+    let events = merge_text(events);
+    // Find the text events containing labels or references, skipping text in
+    // code blocks.
+    let mut in_code_block = false;
+    let labeled_indices: HashSet<usize> = events
+        .iter()
+        .enumerate()
+        .filter_map(|(index, event)| {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                Event::Text(text) if !in_code_block && LABEL_OR_REF_REGEX.is_match(text) => {
+                    return Some(index)
+                }
+                _ => (),
+            }
+            None
+        })
+        .collect();
+    // Avoid any work in the common case.
+    if labeled_indices.is_empty() {
+        return events;
+    }
+
+    // First, number all labels, so that references may precede the label they
+    // refer to.
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut numbers: HashMap<String, String> = HashMap::new();
+    for (index, event) in events.iter().enumerate() {
+        let Event::Text(text) = event else {
+            continue;
+        };
+        if !labeled_indices.contains(&index) {
+            continue;
+        }
+        for captures in LABEL_OR_REF_REGEX.captures_iter(text) {
+            if let (Some(label), Some(kind)) = (captures.get(1), captures.get(2)) {
+                let count = counts.entry(kind.as_str().to_string()).or_insert(0);
+                *count += 1;
+                let number = match chapter {
+                    Some(chapter) => format!("{chapter}.{count}"),
+                    None => count.to_string(),
+                };
+                numbers.entry(label.as_str().to_string()).or_insert(number);
+            }
+        }
+    }
+
+    // Next, replace the labels and references.
+    let mut output = Vec::with_capacity(events.len());
+    for (index, event) in events.into_iter().enumerate() {
+        let Event::Text(text) = &event else {
+            output.push(event);
+            continue;
+        };
+        if !labeled_indices.contains(&index) {
+            output.push(event);
+            continue;
+        }
+        let mut last_end = 0;
+        for captures in LABEL_OR_REF_REGEX.captures_iter(text) {
+            let whole = captures.get(0).unwrap();
+            if whole.start() > last_end {
+                output.push(Event::Text(
+                    text[last_end..whole.start()].to_string().into(),
+                ));
+            }
+            output.push(Event::InlineHtml(
+                label_or_ref_html(&captures, &numbers).into(),
+            ));
+            last_end = whole.end();
+        }
+        if last_end < text.len() {
+            output.push(Event::Text(text[last_end..].to_string().into()));
+        }
+    }
+    output
+}
+
+/// Combine adjacent text events; the Markdown parser may split text which
+/// contains special characters, such as the backslash in `\ref`.
+pub(super) fn merge_text(events: Vec<Event>) -> Vec<Event> {
+    let mut output: Vec<Event> = Vec::with_capacity(events.len());
+    for event in events {
+        if let (Some(Event::Text(previous)), Event::Text(text)) = (output.last_mut(), &event) {
+            *previous = format!("{}{}", &**previous, &**text).into();
+        } else {
+            output.push(event);
+        }
+    }
+    output
+}
+
+/// Produce the HTML for a label or reference.
+fn label_or_ref_html(captures: &Captures, numbers: &HashMap<String, String>) -> String {
+    if let (Some(label), Some(kind)) = (captures.get(1), captures.get(2)) {
+        let label = label.as_str();
+        let number = &numbers[label];
+        let text = match kind.as_str() {
+            "fig" => format!("Figure {number}"),
+            "tbl" => format!("Table {number}"),
+            _ => format!("({number})"),
+        };
+        format!(
+            "<span class=\"CodeChat-label\" id=\"{label}\" data-label=\"{label}\">{text}</span>"
+        )
+    } else {
+        let label = &captures[3];
+        match numbers.get(label) {
+            Some(number) => {
+                let text = match &label[..label.find(':').unwrap()] {
+                    "fig" => format!("Figure {number}"),
+                    "tbl" => format!("Table {number}"),
+                    _ => format!("Equation ({number})"),
+                };
+                format!(
+                    "<a class=\"CodeChat-ref\" href=\"#{label}\" data-ref=\"{label}\">{text}</a>"
+                )
+            }
+            None => format!(
+                "<span class=\"CodeChat-ref CodeChat-ref-missing\" data-ref=\"{label}\">??</span>"
+            ),
+        }
+    }
+}
+
+/// Return the chapter number of the provided file: its (1-based) position
+/// among the files linked to by the project's table of contents, or `None` if
+/// the table of contents doesn't link to it.
+pub fn chapter_number(toc_path: &Path, file_path: &Path) -> Option<usize> {
+    let file_path = file_path.canonicalize().ok()?;
+    toc_files(toc_path)
+        .ok()?
+        .iter()
+        .position(|path| *path == file_path)
+        .map(|index| index + 1)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use pulldown_cmark::{html, Parser};
+
+    use super::{chapter_number, number_labels};
+    use crate::prep_test_dir;
+
+    fn render(markdown: &str, chapter: Option<usize>) -> String {
+        let mut html_output = String::new();
+        html::push_html(
+            &mut html_output,
+            number_labels(Parser::new(markdown).collect(), chapter).into_iter(),
+        );
+        html_output
+    }
+
+    #[test]
+    fn test_number_labels() {
+        assert_eq!(
+            render(
+                indoc!(
+                    r"
+                    See \ref{fig:b}, \ref{eq:a}, and \ref{tbl:none}.
+
+                    {#fig:a} First. {#fig:b} Second. $$x$$ {#eq:a}
+                    "
+                ),
+                None
+            ),
+            indoc!(
+                r##"
+                <p>See <a class="CodeChat-ref" href="#fig:b" data-ref="fig:b">Figure 2</a>, <a class="CodeChat-ref" href="#eq:a" data-ref="eq:a">Equation (1)</a>, and <span class="CodeChat-ref CodeChat-ref-missing" data-ref="tbl:none">??</span>.</p>
+                <p><span class="CodeChat-label" id="fig:a" data-label="fig:a">Figure 1</span> First. <span class="CodeChat-label" id="fig:b" data-label="fig:b">Figure 2</span> Second. $$x$$ <span class="CodeChat-label" id="eq:a" data-label="eq:a">(1)</span></p>
+                "##
+            )
+        );
+
+        // Chapter numbers prefix each number; text without labels is
+        // unchanged.
+        assert_eq!(
+            render("{#tbl:a} A {#other}\n", Some(3)),
+            "<p><span class=\"CodeChat-label\" id=\"tbl:a\" data-label=\"tbl:a\">Table 3.1</span> A {#other}</p>\n"
+        );
+        assert_eq!(
+            render("Plain *text*.\n", None),
+            "<p>Plain <em>text</em>.</p>\n"
+        );
+        // Labels in code blocks are ignored.
+        assert_eq!(
+            render("```\n{#fig:a}\n```\n", None),
+            "<pre><code>{#fig:a}\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_chapter_number() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let toc_path = test_dir.join("toc.md");
+        assert_eq!(
+            chapter_number(&toc_path, &test_dir.join("intro.md")),
+            Some(1)
+        );
+        assert_eq!(
+            chapter_number(&toc_path, &test_dir.join("src/main.py")),
+            Some(2)
+        );
+        assert_eq!(chapter_number(&toc_path, &test_dir.join("other.py")), None);
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `outline.rs` -- Find the headings in a file
+///
+/// The outline of a file lists the headings in its doc blocks, in order, along
+/// with the line of the source file on which each appears. The IDE and the
+/// Client use this to show the structure of a document, without parsing its
+/// doc blocks themselves.
+// ## Imports
+//
+// ### Standard library
+use std::path::Path;
+
+// ### Third-party
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{
+    doc_blocks_with_lines, find_file_lexer, find_path_to_toc, lexer_ext, notebook::NOTEBOOK_EXT,
+    project_config_for_file,
+};
+
+// ## Data structures
+/// One heading in the outline of a file.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OutlineEntry {
+    /// The heading's level, from 1 (`#`) to 6 (`######`).
+    pub level: u8,
+    /// The heading's text, without any formatting.
+    pub title: String,
+    /// The line of the source file containing the heading, numbered from 1.
+    pub line: usize,
+}
+
+// ## Code
+/// Return the headings in the doc blocks of the provided file, in order.
+pub fn outline(file_contents: &str, file_path: &Path) -> Result<Vec<OutlineEntry>, String> {
+    // The lines of a Jupyter notebook's JSON don't correspond to its cells.
+    if lexer_ext(file_path) == NOTEBOOK_EXT {
+        return Ok(vec![]);
+    }
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let lexer = find_file_lexer(file_contents, file_path, &project_config)?;
+    Ok(doc_blocks_with_lines(file_contents, lexer)
+        .iter()
+        .flat_map(|(start_line, doc_block)| markdown_outline(&doc_block.contents, *start_line))
+        .collect())
+}
+
+/// Return the headings in the provided Markdown, which begins on `start_line`.
+fn markdown_outline(markdown: &str, start_line: usize) -> Vec<OutlineEntry> {
+    // Parse as the CodeChat Editor does.
+    let mut options = Options::all();
+    options.remove(Options::ENABLE_SMART_PUNCTUATION | Options::ENABLE_MATH);
+    let mut entries = Vec::new();
+    let mut heading: Option<OutlineEntry> = None;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                heading = Some(OutlineEntry {
+                    level: level as u8,
+                    title: String::new(),
+                    line: start_line + markdown[..range.start].matches('\n').count(),
+                })
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut heading {
+                    heading.title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => entries.extend(heading.take()),
+            _ => (),
+        }
+    }
+    entries
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::{outline, OutlineEntry};
+
+    fn entry(level: u8, title: &str, line: usize) -> OutlineEntry {
+        OutlineEntry {
+            level,
+            title: title.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_outline() {
+        assert_eq!(
+            outline(
+                indoc!(
+                    "
+                    # # Title
+                    #
+                    # Some text.
+                    a = 1
+                    # ## A `code` *section*
+                    b = 2
+                    # Not a heading.
+                    "
+                ),
+                Path::new("foo.py")
+            )
+            .unwrap(),
+            vec![entry(1, "Title", 1), entry(2, "A code section", 5)]
+        );
+        assert_eq!(
+            outline("Intro\n\n## Part\n", Path::new("foo.md")).unwrap(),
+            vec![entry(2, "Part", 3)]
+        );
+        assert!(outline("", Path::new("foo.unknown")).is_err());
+    }
+}
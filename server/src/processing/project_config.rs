@@ -0,0 +1,550 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `project_config.rs` -- Per-project configuration
+///
+/// A project may optionally provide a configuration file, named
+/// `codechat.json`, in the same directory as its `toc.md`. Any missing fields
+/// take on their default values, so an empty object (`{}`) is a valid
+/// configuration; likewise, a project without a configuration file uses the
+/// defaults for every field.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::anchors::Anchor;
+use super::citations::{load_bibliography, Bibliography};
+
+// ## Globals
+/// The name of the project configuration file, which resides in the same
+/// directory as the project's `toc.md`.
+pub const PROJECT_CONFIG_FILE_NAME: &str = "codechat.json";
+
+// ## Data structures
+/// The configuration for a project.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// The markup language doc blocks are written in. A file may select
+    /// another; see `find_markup`.
+    pub doc_block_markup: DocBlockMarkup,
+    /// The Markdown extensions used when rendering doc blocks.
+    pub markdown: MarkdownExtensions,
+    /// How headings receive their anchors.
+    pub headings: HeadingConfig,
+    /// How figures, tables, and equations are numbered.
+    pub numbering: NumberingConfig,
+    /// Options for exporting the project.
+    pub export: ExportConfig,
+    /// How the HTML rendered from doc blocks is sanitized.
+    pub sanitize: SanitizeConfig,
+    /// The path, relative to the project's root, to a bibliography in BibTeX
+    /// (`.bib`) or CSL-JSON (`.json`) format. Doc blocks may cite entries in
+    /// this bibliography.
+    pub bibliography: Option<PathBuf>,
+    /// The contents of the bibliography, loaded by `load_project_config`.
+    #[serde(skip)]
+    pub bibliography_entries: Bibliography,
+    /// A map from a shortcode name, such as `rocket`, to the text it's
+    /// replaced with, such as `🚀`. These add to (or replace) the built-in
+    /// shortcodes. Names may contain lowercase letters, digits, `_`, `+`, and
+    /// `-`. See `emoji.rs`.
+    pub shortcodes: BTreeMap<String, String>,
+    /// A map from a macro name, such as `author`, to the text `{{author}}` is
+    /// replaced with. These add to (or replace) the built-in macros, such as
+    /// `{{today}}`. See `macros.rs`.
+    pub macros: BTreeMap<String, String>,
+    /// A map from a variable name, such as `product_name`, to the text
+    /// `{{var:product_name}}` is replaced with. See `macros.rs`.
+    pub variables: BTreeMap<String, String>,
+    /// A map from a condition name, such as `audience`, to its value, such as
+    /// `student`, which determines the conditional blocks included when
+    /// rendering. See `conditions.rs`.
+    pub conditions: BTreeMap<String, String>,
+    /// TeX macros available to all math in the project, as a map from a
+    /// macro's name (without the leading backslash) to its definition. See
+    /// `TexMacro`.
+    pub tex_macros: BTreeMap<String, TexMacro>,
+    /// How PlantUML diagrams are rendered.
+    pub plantuml: PlantUmlConfig,
+    /// A map from a fence language, such as `wavedrom`, to the renderer for
+    /// fenced code blocks in that language. These add to (or replace) the
+    /// built-in fence renderers. See `fences.rs`.
+    pub fences: BTreeMap<String, FenceConfig>,
+    /// The directory containing this configuration file, set by
+    /// `load_project_config`. Fence render commands run in this directory.
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+    /// The path, relative to the project's root, to a
+    /// [ctags](https://ctags.io/) `tags` file, used to find the definition of
+    /// an identifier named in a doc block. If omitted, the IDE finds
+    /// definitions instead.
+    pub tags: Option<PathBuf>,
+    /// True to anchor each doc block to the code following it, moving a doc
+    /// block separated from this code by changes made outside the CodeChat
+    /// Editor back to it. See `anchors.rs`.
+    pub anchors: bool,
+    /// The anchors recorded for the file being rendered, when `anchors` is
+    /// true. This isn't part of the configuration file; instead, it's
+    /// determined for each file.
+    #[serde(skip)]
+    pub file_anchors: Vec<Anchor>,
+    /// A map from a file extension to the name of the lexer used for files
+    /// with that extension, such as `{"h": "c_cpp"}`. This takes priority over
+    /// the lexer chosen by the file's extension and contents.
+    pub languages: BTreeMap<String, String>,
+    /// A map from a Client action, such as `save`, to its keyboard shortcut,
+    /// such as `Mod-s`. These add to (or replace) the default shortcuts; the
+    /// user's preferences take priority over these. See `keymap.rs`.
+    pub keymap: BTreeMap<String, String>,
+    /// The name of the lexer selected for the file being rendered by a
+    /// `SetFileLanguage` message. This isn't part of the configuration file;
+    /// instead, it's determined for each file.
+    #[serde(skip)]
+    pub file_language: Option<String>,
+    /// The directory containing the file being rendered, which `include`
+    /// fences are relative to. This isn't part of the configuration file;
+    /// instead, it's determined for each file.
+    #[serde(skip)]
+    pub file_dir: Option<PathBuf>,
+    /// The path to the file being rendered, used by macros such as
+    /// `{{git-last-modified}}`. This isn't part of the configuration file;
+    /// instead, it's determined for each file.
+    #[serde(skip)]
+    pub file_path: Option<PathBuf>,
+}
+
+/// The markup language of doc blocks.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum DocBlockMarkup {
+    /// [CommonMark](https://commonmark.org/), plus the extensions selected by
+    /// `markdown`.
+    #[default]
+    #[serde(rename = "markdown")]
+    Markdown,
+    /// [reStructuredText](https://docutils.sourceforge.io/rst.html), as used by
+    /// Sphinx. See `rst.rs`.
+    #[serde(rename = "rst")]
+    ReStructuredText,
+    /// [AsciiDoc](https://asciidoc.org/). See `asciidoc.rs`.
+    #[serde(rename = "asciidoc")]
+    AsciiDoc,
+}
+
+/// Select which Markdown extensions (beyond those defined by the CommonMark
+/// spec) the doc block renderer supports. All are enabled by default.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MarkdownExtensions {
+    /// [GFM tables](https://github.github.com/gfm/#tables-extension-).
+    pub tables: bool,
+    /// Footnotes, such as `[^1]` paired with `[^1]: A footnote.`.
+    pub footnotes: bool,
+    /// [GFM task lists](https://github.github.com/gfm/#task-list-items-extension-).
+    pub task_lists: bool,
+    /// [GFM strikethrough](https://github.github.com/gfm/#strikethrough-extension-).
+    pub strikethrough: bool,
+    /// Definition lists, with a term followed by a line beginning with `:`.
+    pub definition_lists: bool,
+    /// Heading attributes, such as `# Heading {#my-id .my-class}`.
+    pub heading_ids: bool,
+    /// Admonitions, either GitHub-style (`> [!NOTE]`) or fenced (`:::note`).
+    pub admonitions: bool,
+    /// Emoji shortcodes, such as `:rocket:`.
+    pub emoji: bool,
+}
+
+impl Default for MarkdownExtensions {
+    fn default() -> Self {
+        MarkdownExtensions {
+            tables: true,
+            footnotes: true,
+            task_lists: true,
+            strikethrough: true,
+            definition_lists: true,
+            heading_ids: true,
+            admonitions: true,
+            emoji: true,
+        }
+    }
+}
+
+/// Configure the anchors (`id`s) given to headings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HeadingConfig {
+    /// How to generate an `id` for headings which lack an explicit one.
+    pub slug_style: SlugStyle,
+    /// A map from an old heading `id` to its current `id`, so that links to
+    /// the old `id` still work.
+    pub redirects: BTreeMap<String, String>,
+}
+
+/// Configure the numbering of figures, tables, and equations.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct NumberingConfig {
+    /// True to prefix each number with the file's chapter number: its position
+    /// in the project's table of contents.
+    pub chapter_numbers: bool,
+    /// The chapter number of the file being rendered. This isn't part of the
+    /// configuration file; instead, it's determined for each file when
+    /// `chapter_numbers` is true.
+    #[serde(skip)]
+    pub chapter: Option<usize>,
+}
+
+/// Configure how a project is exported.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// The path, relative to the project's root, to a DOCX file whose styles
+    /// are used for DOCX exports.
+    pub docx_reference_doc: Option<PathBuf>,
+    /// The command which runs Chromium or Chrome for PDF exports. By default,
+    /// the first of `chromium`, `chromium-browser`, `google-chrome`, and
+    /// `google-chrome-stable` which is installed.
+    pub chromium: Option<String>,
+    /// The URL an HTML export is published at, such as
+    /// `https://example.com/docs/`. If provided, the export names the
+    /// canonical URL of each page and includes a sitemap.
+    pub site_url: Option<String>,
+    /// HTML, such as a `<script>` element, added to the `<head>` of each page
+    /// of an HTML export; for example, to count visitors. It's never added to
+    /// the Client.
+    pub analytics: Option<String>,
+}
+
+/// Configure the rendering of PlantUML diagrams; see `fences.rs`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PlantUmlConfig {
+    /// The URL of the PlantUML server which renders diagrams.
+    pub server: String,
+    /// The path, relative to the project's root, to a local `plantuml.jar`.
+    /// If provided, exports use it to render diagrams, instead of the server.
+    /// `load_project_config` makes this path absolute.
+    pub jar: Option<PathBuf>,
+}
+
+impl Default for PlantUmlConfig {
+    fn default() -> Self {
+        PlantUmlConfig {
+            server: "https://www.plantuml.com/plantuml".to_string(),
+            jar: None,
+        }
+    }
+}
+
+/// Configure the renderer for fenced code blocks in a specific language.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FenceConfig {
+    /// Use the named built-in renderer, such as `mermaid`.
+    Builtin(String),
+    /// Run the provided program and arguments, passing the fence's contents on
+    /// stdin; its stdout, such as an SVG, is the rendered HTML.
+    Command(Vec<String>),
+    /// Show the image at the provided URL, after replacing `{source}` with the
+    /// URL-encoded contents of the fence or `{hex}` with their hex encoding.
+    Url(String),
+}
+
+/// Configure the sanitization of HTML rendered from doc blocks. Doc blocks may
+/// contain arbitrary HTML, including scripts; while this is acceptable when
+/// editing a trusted local project, exported documents and read-only pages may
+/// be viewed by anyone.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SanitizeConfig {
+    /// The sanitization applied when editing. By default, this is off: the
+    /// Client saves the HTML it shows, so HTML removed by the sanitizer would
+    /// also be removed from the source file.
+    pub editor: SanitizeLevel,
+    /// The sanitization applied to exports and read-only pages.
+    pub export: SanitizeLevel,
+    /// The destination of the HTML being rendered. This isn't part of the
+    /// configuration file; instead, it's determined by the caller.
+    #[serde(skip)]
+    pub target: RenderTarget,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        SanitizeConfig {
+            editor: SanitizeLevel::Off,
+            export: SanitizeLevel::Strict,
+            target: RenderTarget::default(),
+        }
+    }
+}
+
+impl SanitizeConfig {
+    /// Return the sanitization to apply to the current render target.
+    pub fn level(&self) -> SanitizeLevel {
+        match self.target {
+            RenderTarget::Editor => self.editor,
+            RenderTarget::Export | RenderTarget::ReadOnly => self.export,
+        }
+    }
+}
+
+/// The degree to which HTML rendered from doc blocks is sanitized.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SanitizeLevel {
+    /// Allow only the elements and attributes produced by Markdown and the
+    /// CodeChat Editor.
+    #[default]
+    Strict,
+    /// Also allow inline styles and embedded media, such as `<iframe>` and
+    /// `<video>`. Scripts and event handlers are still removed.
+    Relaxed,
+    /// Don't sanitize.
+    Off,
+}
+
+/// The definition of a TeX macro, in the format used by
+/// [MathJax](https://docs.mathjax.org/en/latest/input/tex/macros.html).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum TexMacro {
+    /// A macro without arguments, such as `"\\mathbb{R}"`.
+    Simple(String),
+    /// A macro and its number of arguments, such as `["\\mathbf{#1}", 1]`.
+    WithArguments(String, u8),
+}
+
+/// Where rendered HTML will be displayed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RenderTarget {
+    /// The CodeChat Editor Client.
+    #[default]
+    Editor,
+    /// An exported document.
+    Export,
+    /// A read-only page served to others, such as a student's view of a
+    /// classroom or a past revision of a file.
+    ReadOnly,
+}
+
+/// The ways to generate an `id` from a heading's text.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStyle {
+    /// Follow GitHub's approach: lowercase the text, remove punctuation, then
+    /// replace spaces with dashes.
+    #[default]
+    GitHub,
+    /// Like `GitHub`, but also remove all non-ASCII characters.
+    Ascii,
+    /// Don't generate an `id`.
+    None,
+}
+
+// ## Code
+/// Given the path to a file and the relative path from that file's directory
+/// to the project's TOC (as returned by `find_path_to_toc`), return the path to
+/// the directory containing the TOC -- the root of the project.
+pub fn project_root(file_path: &Path, path_to_toc: &Path) -> PathBuf {
+    let mut root = file_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(path_to_toc);
+    // Remove the `toc.md` from the path.
+    root.pop();
+    root
+}
+
+/// Load the configuration for the project rooted at `project_root`. A missing
+/// configuration file produces the default configuration; a configuration file
+/// which can't be read or parsed produces an error.
+pub fn load_project_config(project_root: &Path) -> Result<ProjectConfig, String> {
+    let config_path = project_root.join(PROJECT_CONFIG_FILE_NAME);
+    if !config_path.is_file() {
+        return Ok(ProjectConfig::default());
+    }
+    let config_str = match fs::read_to_string(&config_path) {
+        Ok(v) => v,
+        Err(err) => return Err(format!("Unable to read {config_path:?}: {err}.")),
+    };
+    let mut project_config: ProjectConfig = serde_json::from_str(&config_str)
+        .map_err(|err| format!("Unable to parse {config_path:?}: {err}."))?;
+    if let Some(bibliography) = &project_config.bibliography {
+        project_config.bibliography_entries = load_bibliography(&project_root.join(bibliography))?;
+    }
+    if let Some(jar) = &project_config.plantuml.jar {
+        project_config.plantuml.jar = Some(project_root.join(jar));
+    }
+    project_config.root = Some(project_root.to_path_buf());
+    Ok(project_config)
+}
+
+/// Return the files linked to by the project's table of contents, in the order
+/// they're first linked to. Each path is canonicalized; links to other sites,
+/// to locations within the table of contents, and to missing files are
+/// omitted.
+pub fn toc_files(toc_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let toc_contents = fs::read_to_string(toc_path)
+        .map_err(|err| format!("Unable to read {toc_path:?}: {err}."))?;
+    let toc_dir = toc_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut files = Vec::new();
+    for event in Parser::new(&toc_contents) {
+        let Event::Start(Tag::Link { dest_url, .. }) = event else {
+            continue;
+        };
+        if dest_url.contains("://") || dest_url.starts_with('#') {
+            continue;
+        }
+        let link_path = dest_url.split('#').next().unwrap_or_default();
+        // Links may percent-encode characters, such as spaces.
+        let link_path = urlencoding::decode(link_path)
+            .map(|path| path.into_owned())
+            .unwrap_or_else(|_| link_path.to_string());
+        let Ok(link_path) = toc_dir.join(link_path).canonicalize() else {
+            continue;
+        };
+        if !files.contains(&link_path) {
+            files.push(link_path);
+        }
+    }
+    Ok(files)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{
+        project_root, DocBlockMarkup, FenceConfig, MarkdownExtensions, ProjectConfig, RenderTarget,
+        SanitizeLevel, SlugStyle, TexMacro,
+    };
+
+    #[test]
+    fn test_parse_project_config() {
+        // An empty configuration produces the defaults.
+        assert_eq!(
+            serde_json::from_str::<ProjectConfig>("{}").unwrap(),
+            ProjectConfig::default()
+        );
+
+        // Unspecified fields keep their default values.
+        assert_eq!(
+            serde_json::from_str::<ProjectConfig>(r#"{"markdown": {"tables": false}}"#).unwrap(),
+            ProjectConfig {
+                markdown: MarkdownExtensions {
+                    tables: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            serde_json::from_str::<ProjectConfig>(r#"{"headings": {"slug_style": "none"}}"#)
+                .unwrap()
+                .headings
+                .slug_style,
+            SlugStyle::None
+        );
+
+        assert_eq!(
+            serde_json::from_str::<ProjectConfig>(r#"{"doc_block_markup": "rst"}"#)
+                .unwrap()
+                .doc_block_markup,
+            DocBlockMarkup::ReStructuredText
+        );
+
+        // The sanitization level depends on the render target.
+        let mut project_config =
+            serde_json::from_str::<ProjectConfig>(r#"{"sanitize": {"editor": "off"}}"#).unwrap();
+        assert_eq!(project_config.sanitize.level(), SanitizeLevel::Off);
+        project_config.sanitize.target = RenderTarget::Export;
+        assert_eq!(project_config.sanitize.level(), SanitizeLevel::Strict);
+
+        // TeX macros may have arguments.
+        assert_eq!(
+            serde_json::from_str::<ProjectConfig>(
+                r#"{"tex_macros": {"R": "\\mathbb{R}", "vec": ["\\mathbf{#1}", 1]}}"#
+            )
+            .unwrap()
+            .tex_macros
+            .into_iter()
+            .collect::<Vec<_>>(),
+            vec![
+                ("R".to_string(), TexMacro::Simple("\\mathbb{R}".to_string())),
+                (
+                    "vec".to_string(),
+                    TexMacro::WithArguments("\\mathbf{#1}".to_string(), 1)
+                ),
+            ]
+        );
+
+        // Each fence renderer is one of several kinds.
+        assert_eq!(
+            serde_json::from_str::<ProjectConfig>(
+                r#"{"fences": {"diagram": {"builtin": "mermaid"}, "wavedrom": {"command": ["wavedrom-cli", "-i", "-"]}, "vega": {"url": "http://localhost/svg?{source}"}}}"#
+            )
+            .unwrap()
+            .fences
+            .into_iter()
+            .collect::<Vec<_>>(),
+            vec![
+                (
+                    "diagram".to_string(),
+                    FenceConfig::Builtin("mermaid".to_string())
+                ),
+                (
+                    "vega".to_string(),
+                    FenceConfig::Url("http://localhost/svg?{source}".to_string())
+                ),
+                (
+                    "wavedrom".to_string(),
+                    FenceConfig::Command(vec![
+                        "wavedrom-cli".to_string(),
+                        "-i".to_string(),
+                        "-".to_string()
+                    ])
+                ),
+            ]
+        );
+
+        // Invalid types are an error.
+        assert!(serde_json::from_str::<ProjectConfig>(r#"{"markdown": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_project_root() {
+        assert_eq!(
+            project_root(Path::new("/a/b/c/foo.py"), Path::new("../../toc.md")),
+            PathBuf::from("/a/b/c/../../")
+        );
+        assert_eq!(
+            project_root(Path::new("/a/foo.py"), Path::new("toc.md")),
+            PathBuf::from("/a/")
+        );
+    }
+}
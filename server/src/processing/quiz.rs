@@ -0,0 +1,440 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `quiz.rs` -- Exercises with multiple-choice answers
+///
+/// A fenced code block whose language is `quiz` contains an exercise: a
+/// question, followed by its choices, then an optional explanation of the
+/// answer.
+///
+/// ````markdown
+/// ```quiz
+/// What does `len([1, 2])` return?
+/// - [ ] 1
+/// - [x] 2
+/// - [ ] An error
+/// > `len` returns the number of items in a list.
+/// ```
+/// ````
+///
+/// The question, choices, and explanation may contain Markdown. Choices marked
+/// with `[x]` are correct; an exercise with several correct choices asks the
+/// reader to select all of them.
+///
+/// In the Client, the reader selects a choice, which is then marked as right
+/// or wrong; the answer is hidden until the reader shows it. This requires no
+/// scripts, only styles. When exporting, exercises are numbered, and their
+/// answers are collected into an appendix at the end of the document.
+/// Read-only pages, such as those shown to students in a classroom, omit the
+/// answers.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{html, CodeBlockKind, Event, Parser, Tag, TagEnd};
+use regex::{Captures, Regex};
+
+// ### Local
+use super::{
+    diagnostics::{Diagnostic, Severity},
+    fences::FenceRenderer,
+    project_config::{ProjectConfig, RenderTarget, SanitizeLevel},
+    sanitize::sanitize_html,
+};
+
+// ## Globals
+lazy_static! {
+    /// Match a choice, such as `- [x] 2`.
+    static ref CHOICE_REGEX: Regex = Regex::new(r"^\s*[-*+]\s+\[([ xX])\]\s+(.*)$").unwrap();
+    /// Match the label of an exported exercise, which is numbered after
+    /// exporting.
+    static ref EXERCISE_LABEL_REGEX: Regex =
+        Regex::new(r#"<strong class="CodeChat-quiz-label">Exercise\.</strong>"#).unwrap();
+    /// Match the answer to an exported exercise.
+    static ref EXPORTED_ANSWER_REGEX: Regex =
+        Regex::new(r#"(?s)<aside class="CodeChat-quiz-answer">(.*?)</aside>\n?"#).unwrap();
+}
+
+// ## Data structures
+/// An exercise, parsed from a `quiz` fence.
+#[derive(Debug, PartialEq)]
+pub struct Quiz {
+    /// The question's Markdown.
+    pub question: String,
+    pub choices: Vec<Choice>,
+    /// The Markdown explaining the answer; this may be empty.
+    pub explanation: String,
+}
+
+/// One of an exercise's choices.
+#[derive(Debug, PartialEq)]
+pub struct Choice {
+    /// The choice's Markdown.
+    pub text: String,
+    pub is_correct: bool,
+}
+
+/// Render `quiz` fences.
+pub struct QuizRenderer;
+
+impl FenceRenderer for QuizRenderer {
+    fn render(&self, code: &str, project_config: &ProjectConfig) -> Result<String, String> {
+        let quiz = parse_quiz(code)?;
+        let level = project_config.sanitize.level();
+        Ok(match project_config.sanitize.target {
+            RenderTarget::Editor => quiz_to_interactive_html(&quiz, code, level),
+            RenderTarget::Export => quiz_to_static_html(&quiz, level, true),
+            RenderTarget::ReadOnly => quiz_to_static_html(&quiz, level, false),
+        })
+    }
+}
+
+// ## Code
+/// Parse the contents of a `quiz` fence, reporting any problems in its
+/// structure.
+pub fn parse_quiz(source: &str) -> Result<Quiz, String> {
+    let mut question: Vec<&str> = Vec::new();
+    let mut choices = Vec::new();
+    let mut explanation: Vec<&str> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        if let Some(captures) = CHOICE_REGEX.captures(line) {
+            if !explanation.is_empty() {
+                return Err(format!(
+                    "Line {line_number} of the quiz: place all choices before the explanation."
+                ));
+            }
+            choices.push(Choice {
+                text: captures[2].trim().to_string(),
+                is_correct: &captures[1] != " ",
+            });
+        } else if let Some(text) = line.trim_start().strip_prefix('>') {
+            if choices.is_empty() {
+                return Err(format!(
+                    "Line {line_number} of the quiz: place the explanation after the choices."
+                ));
+            }
+            explanation.push(text.strip_prefix(' ').unwrap_or(text));
+        } else if choices.is_empty() {
+            question.push(line);
+        } else if line.trim().is_empty() {
+            if !explanation.is_empty() {
+                explanation.push("");
+            }
+        } else {
+            return Err(format!(
+                "Line {line_number} of the quiz: expected a choice (`- [ ]` or `- [x]`) or an explanation (`>`)."
+            ));
+        }
+    }
+
+    let question = question.join("\n").trim().to_string();
+    if question.is_empty() {
+        return Err("The quiz has no question; place it before the choices.".to_string());
+    }
+    if choices.len() < 2 {
+        return Err("The quiz needs at least two choices, such as `- [ ] 1`.".to_string());
+    }
+    if !choices.iter().any(|choice| choice.is_correct) {
+        return Err("The quiz has no correct choice; mark one using `- [x]`.".to_string());
+    }
+    Ok(Quiz {
+        question,
+        choices,
+        explanation: explanation.join("\n").trim().to_string(),
+    })
+}
+
+/// Report problems in the `quiz` fences of the provided doc block's Markdown,
+/// which begins on `start_line` of its source file.
+pub fn find_quiz_errors(markdown: &str, start_line: usize) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    // The line the current `quiz` fence starts on, and its contents.
+    let mut quiz: Option<(usize, String)> = None;
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.split_whitespace().next() == Some("quiz") =>
+            {
+                quiz = Some((
+                    start_line + markdown[..range.start].matches('\n').count(),
+                    String::new(),
+                ));
+            }
+            Event::Text(text) => {
+                if let Some((_, source)) = &mut quiz {
+                    source.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((line, source)) = quiz.take() {
+                    if let Err(err) = parse_quiz(&source) {
+                        diagnostics.push(Diagnostic::on_line(Severity::Error, line, err, "quiz"));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    diagnostics
+}
+
+/// Number the exported exercises in the provided HTML, then move their answers
+/// to an appendix at its end.
+pub fn move_quiz_answers(html: &str) -> String {
+    let mut number = 0;
+    let html = EXERCISE_LABEL_REGEX.replace_all(html, |_: &Captures| {
+        number += 1;
+        format!("<strong class=\"CodeChat-quiz-label\">Exercise {number}.</strong>")
+    });
+    let mut answers = Vec::new();
+    let mut html = EXPORTED_ANSWER_REGEX
+        .replace_all(&html, |captures: &Captures| {
+            answers.push(captures[1].to_string());
+            ""
+        })
+        .to_string();
+    if answers.is_empty() {
+        return html;
+    }
+
+    html.push_str("<section class=\"CodeChat-quiz-answers\">\n<h1>Answers</h1>\n");
+    for (index, answer) in answers.iter().enumerate() {
+        html.push_str(&format!(
+            "<div class=\"CodeChat-quiz-answer\">\n<p><strong>Exercise {}.</strong></p>\n{answer}</div>\n",
+            index + 1
+        ));
+    }
+    html.push_str("</section>\n");
+    html
+}
+
+// Render an exercise for the Client. Styles mark a selected choice as right or
+// wrong, and the answer is hidden in a `<details>` element.
+fn quiz_to_interactive_html(quiz: &Quiz, source: &str, level: SanitizeLevel) -> String {
+    // Give each exercise's inputs a name unique to that exercise, so that
+    // selecting a choice in one exercise doesn't clear another's.
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let name = format!("CodeChat-quiz-{:x}", hasher.finish());
+    let input_type = if quiz
+        .choices
+        .iter()
+        .filter(|choice| choice.is_correct)
+        .count()
+        > 1
+    {
+        "checkbox"
+    } else {
+        "radio"
+    };
+    let choices: String = quiz
+        .choices
+        .iter()
+        .map(|choice| {
+            format!(
+                "<li><label><input type=\"{input_type}\" name=\"{name}\"{}> {}</label></li>\n",
+                if choice.is_correct {
+                    " data-correct=\"\""
+                } else {
+                    ""
+                },
+                markdown_to_inline_html(&choice.text, level)
+            )
+        })
+        .collect();
+    format!(
+        "<div class=\"CodeChat-quiz\">\n<div class=\"CodeChat-quiz-question\">{}</div>\n<ol class=\"CodeChat-quiz-choices\" type=\"A\">\n{choices}</ol>\n<details class=\"CodeChat-quiz-answer\"><summary>Answer</summary>\n{}</details>\n</div>",
+        markdown_to_block_html(&quiz.question, level),
+        answer_html(quiz, level)
+    )
+}
+
+// Render an exercise for export, where `move_quiz_answers` numbers it and
+// moves its answer to the appendix, or for a read-only page, which omits the
+// answer.
+fn quiz_to_static_html(quiz: &Quiz, level: SanitizeLevel, has_answer: bool) -> String {
+    let choices: String = quiz
+        .choices
+        .iter()
+        .map(|choice| {
+            format!(
+                "<li>{}</li>\n",
+                markdown_to_inline_html(&choice.text, level)
+            )
+        })
+        .collect();
+    let answer = if has_answer {
+        format!(
+            "<aside class=\"CodeChat-quiz-answer\">{}</aside>\n",
+            answer_html(quiz, level)
+        )
+    } else {
+        "".to_string()
+    };
+    format!(
+        "<div class=\"CodeChat-quiz\">\n<p><strong class=\"CodeChat-quiz-label\">Exercise.</strong></p>\n{}<ol class=\"CodeChat-quiz-choices\" type=\"A\">\n{choices}</ol>\n{answer}</div>",
+        markdown_to_block_html(&quiz.question, level)
+    )
+}
+
+// Return the HTML giving the letters of the correct choices, followed by the
+// explanation.
+fn answer_html(quiz: &Quiz, level: SanitizeLevel) -> String {
+    let letters: Vec<String> = quiz
+        .choices
+        .iter()
+        .zip('A'..='Z')
+        .filter(|(choice, _)| choice.is_correct)
+        .map(|(_, letter)| letter.to_string())
+        .collect();
+    format!(
+        "<p>{}.</p>\n{}",
+        letters.join(", "),
+        markdown_to_block_html(&quiz.explanation, level)
+    )
+}
+
+// Render Markdown to HTML. Since fences are rendered after the rest of the doc
+// block is sanitized, sanitize this HTML here.
+fn markdown_to_block_html(markdown: &str, level: SanitizeLevel) -> String {
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, Parser::new(markdown));
+    sanitize_html(&html_output, level)
+}
+
+// Render Markdown to HTML, omitting the paragraph which wraps a single line of
+// text.
+fn markdown_to_inline_html(markdown: &str, level: SanitizeLevel) -> String {
+    let html = markdown_to_block_html(markdown, level);
+    html.trim_end()
+        .strip_prefix("<p>")
+        .and_then(|html| html.strip_suffix("</p>"))
+        .filter(|html| !html.contains("<p>"))
+        .map(|html| html.to_string())
+        .unwrap_or(html)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{find_quiz_errors, move_quiz_answers, parse_quiz, Choice, Quiz, QuizRenderer};
+    use crate::processing::{
+        diagnostics::{Diagnostic, Severity},
+        fences::FenceRenderer,
+        project_config::{ProjectConfig, RenderTarget},
+    };
+
+    const SOURCE: &str =
+        "What does `len([1, 2])` return?\n- [ ] 1\n- [x] 2\n\n> `len` counts *items*.\n";
+
+    #[test]
+    fn test_parse_quiz() {
+        assert_eq!(
+            parse_quiz(SOURCE).unwrap(),
+            Quiz {
+                question: "What does `len([1, 2])` return?".to_string(),
+                choices: vec![
+                    Choice {
+                        text: "1".to_string(),
+                        is_correct: false
+                    },
+                    Choice {
+                        text: "2".to_string(),
+                        is_correct: true
+                    },
+                ],
+                explanation: "`len` counts *items*.".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_quiz("- [x] 1\n- [ ] 2\n"),
+            Err("The quiz has no question; place it before the choices.".to_string())
+        );
+        assert_eq!(
+            parse_quiz("Q?\n- [x] 1\n"),
+            Err("The quiz needs at least two choices, such as `- [ ] 1`.".to_string())
+        );
+        assert_eq!(
+            parse_quiz("Q?\n- [ ] 1\n- [ ] 2\n"),
+            Err("The quiz has no correct choice; mark one using `- [x]`.".to_string())
+        );
+        assert_eq!(
+            parse_quiz("Q?\n- [ ] 1\n- [x] 2\nOops\n"),
+            Err("Line 4 of the quiz: expected a choice (`- [ ]` or `- [x]`) or an explanation (`>`).".to_string())
+        );
+        assert_eq!(
+            parse_quiz("Q?\n- [ ] 1\n> Why\n- [x] 2\n"),
+            Err("Line 4 of the quiz: place all choices before the explanation.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_quiz() {
+        let mut project_config = ProjectConfig::default();
+        let html = QuizRenderer.render(SOURCE, &project_config).unwrap();
+        assert!(html.contains("<div class=\"CodeChat-quiz-question\"><p>What does <code>len([1, 2])</code> return?</p>\n</div>"));
+        assert!(html.contains("<input type=\"radio\" name=\"CodeChat-quiz-"));
+        assert!(html.contains("data-correct=\"\"> 2</label>"));
+        assert!(html.contains("<summary>Answer</summary>\n<p>B.</p>\n<p><code>len</code> counts <em>items</em>.</p>\n</details>"));
+        // Several correct choices use checkboxes.
+        assert!(QuizRenderer
+            .render("Q?\n- [x] 1\n- [x] 2\n", &project_config)
+            .unwrap()
+            .contains("<input type=\"checkbox\""));
+
+        // Export two exercises, moving their answers to an appendix.
+        project_config.sanitize.target = RenderTarget::Export;
+        let html = QuizRenderer.render(SOURCE, &project_config).unwrap();
+        assert!(html.contains("<aside class=\"CodeChat-quiz-answer\"><p>B.</p>"));
+        let exported = move_quiz_answers(&format!("{html}\n{html}\n"));
+        assert!(exported.contains("<strong class=\"CodeChat-quiz-label\">Exercise 1.</strong>"));
+        assert!(exported.contains("<strong class=\"CodeChat-quiz-label\">Exercise 2.</strong>"));
+        assert!(!exported.contains("<aside"));
+        assert!(exported.ends_with("<div class=\"CodeChat-quiz-answer\">\n<p><strong>Exercise 2.</strong></p>\n<p>B.</p>\n<p><code>len</code> counts <em>items</em>.</p>\n</div>\n</section>\n"));
+        // HTML without exercises is unchanged.
+        assert_eq!(move_quiz_answers("<p>A</p>\n"), "<p>A</p>\n");
+
+        // Read-only pages don't reveal the answer.
+        project_config.sanitize.target = RenderTarget::ReadOnly;
+        let html = QuizRenderer.render(SOURCE, &project_config).unwrap();
+        assert!(html.contains("<li>2</li>"));
+        assert!(!html.contains("data-correct"));
+        assert!(!html.contains("CodeChat-quiz-answer"));
+        assert!(!html.contains("counts"));
+    }
+
+    #[test]
+    fn test_find_quiz_errors() {
+        assert_eq!(
+            find_quiz_errors(
+                "Text.\n\n```quiz\nQ?\n- [x] 1\n- [ ] 2\n```\n\n```quiz\nQ?\n```\n",
+                3
+            ),
+            vec![Diagnostic::on_line(
+                Severity::Error,
+                11,
+                "The quiz needs at least two choices, such as `- [ ] 1`.".to_string(),
+                "quiz"
+            )]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `render_cache.rs` -- Cache the output of expensive directives
+///
+/// Rendering some directives, such as diagrams drawn by an external command or
+/// code quoted from another file, is slow. Since a file is re-rendered after
+/// each edit, while most of its directives are unchanged, this cache stores
+/// each directive's HTML, keyed by everything it's rendered from: its inputs,
+/// such as a fence's language and contents, along with the files it depends
+/// on and their modification times. A change to any of these produces a
+/// different key, so stale entries are never used; `invalidate_render_cache`
+/// also lets the file watcher discard entries which depend on a changed file.
+///
+/// The cache holds a bounded number of entries, discarding the least recently
+/// inserted when full. Failed renders aren't cached.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+
+// ## Data structures
+/// A rendered directive.
+struct RenderCacheEntry {
+    html: String,
+    /// The files the directive was rendered from.
+    dependencies: Vec<PathBuf>,
+}
+
+/// The rendered directives, along with the order they were inserted in.
+#[derive(Default)]
+struct RenderCache {
+    entries: HashMap<u64, RenderCacheEntry>,
+    order: VecDeque<u64>,
+}
+
+// ## Globals
+/// The maximum number of entries in the cache.
+const MAX_ENTRIES: usize = 1000;
+
+lazy_static! {
+    static ref RENDER_CACHE: Mutex<RenderCache> = Mutex::new(RenderCache::default());
+}
+
+// ## Code
+/// Return the cached HTML for a directive rendered from `inputs` and
+/// `dependencies`, or call `render` to produce it. Only successful renders are
+/// cached.
+pub fn render_cached<T: Hash + ?Sized>(
+    inputs: &T,
+    dependencies: Vec<PathBuf>,
+    render: impl FnOnce() -> Result<String, String>,
+) -> Result<String, String> {
+    let key = cache_key(inputs, &dependencies);
+    if let Some(entry) = RENDER_CACHE.lock().unwrap().entries.get(&key) {
+        return Ok(entry.html.clone());
+    }
+    // Don't hold the lock while rendering, which may be slow.
+    let html = render()?;
+    let mut render_cache = RENDER_CACHE.lock().unwrap();
+    if render_cache
+        .entries
+        .insert(
+            key,
+            RenderCacheEntry {
+                html: html.clone(),
+                dependencies,
+            },
+        )
+        .is_none()
+    {
+        render_cache.order.push_back(key);
+    }
+    while render_cache.order.len() > MAX_ENTRIES {
+        if let Some(oldest) = render_cache.order.pop_front() {
+            render_cache.entries.remove(&oldest);
+        }
+    }
+    Ok(html)
+}
+
+/// Discard the cached directives which depend on `path`, since it changed.
+pub fn invalidate_render_cache(path: &Path) {
+    let mut render_cache = RENDER_CACHE.lock().unwrap();
+    let RenderCache { entries, order } = &mut *render_cache;
+    entries.retain(|_, entry| !entry.dependencies.iter().any(|dep| dep == path));
+    order.retain(|key| entries.contains_key(key));
+}
+
+// Combine the inputs and the current state of the dependencies into a key.
+fn cache_key<T: Hash + ?Sized>(inputs: &T, dependencies: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    inputs.hash(&mut hasher);
+    for dependency in dependencies {
+        dependency.hash(&mut hasher);
+        fs::metadata(dependency)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, fs};
+
+    use assert_fs::TempDir;
+
+    use super::{invalidate_render_cache, render_cached};
+
+    #[test]
+    fn test_render_cached() {
+        let render_count = Cell::new(0);
+        let render = || {
+            render_count.set(render_count.get() + 1);
+            Ok(format!("<p>{}</p>", render_count.get()))
+        };
+        // The first render is cached; later renders use it.
+        let inputs = ("render-cache-test", "code");
+        assert_eq!(
+            render_cached(&inputs, Vec::new(), render).unwrap(),
+            "<p>1</p>"
+        );
+        assert_eq!(
+            render_cached(&inputs, Vec::new(), render).unwrap(),
+            "<p>1</p>"
+        );
+        // Different inputs render again.
+        let other_inputs = ("render-cache-test", "other code");
+        assert_eq!(
+            render_cached(&other_inputs, Vec::new(), render).unwrap(),
+            "<p>2</p>"
+        );
+        // Errors aren't cached.
+        let error_inputs = ("render-cache-test", "error");
+        assert!(render_cached(&error_inputs, Vec::new(), || Err("Oops".to_string())).is_err());
+        assert_eq!(
+            render_cached(&error_inputs, Vec::new(), render).unwrap(),
+            "<p>3</p>"
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let temp_dir = TempDir::new().unwrap();
+        let dependency = temp_dir.path().join("a.rs");
+        fs::write(&dependency, "fn a() {}").unwrap();
+        let render_count = Cell::new(0);
+        let render = || {
+            render_count.set(render_count.get() + 1);
+            Ok(render_count.get().to_string())
+        };
+        let inputs = ("render-cache-test", "include");
+        assert_eq!(
+            render_cached(&inputs, vec![dependency.clone()], render).unwrap(),
+            "1"
+        );
+        assert_eq!(
+            render_cached(&inputs, vec![dependency.clone()], render).unwrap(),
+            "1"
+        );
+        // Invalidating the dependency discards the entry.
+        invalidate_render_cache(&dependency);
+        assert_eq!(
+            render_cached(&inputs, vec![dependency.clone()], render).unwrap(),
+            "2"
+        );
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `rst.rs` -- Render doc blocks written in reStructuredText
+///
+/// Projects documented using [Sphinx](https://www.sphinx-doc.org/) write their
+/// doc blocks in [reStructuredText](https://docutils.sourceforge.io/rst.html)
+/// rather than Markdown. When a project's configuration sets
+/// `doc_block_markup` to `rst`, its doc blocks are rendered by a Rust
+/// reStructuredText parser instead; Sphinx's own roles and directives aren't
+/// supported.
+///
+/// The Client converts the HTML it edits back to Markdown, but there's no such
+/// conversion to reStructuredText. Instead, each rendered doc block is
+/// read-only and carries its source in a `data-source` attribute, which the
+/// Client saves unchanged; edit these doc blocks in the IDE.
+// ## Imports
+//
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    project_config::ProjectConfig,
+    sanitize::sanitize_html,
+};
+
+// ## Code
+/// Render a doc block written in reStructuredText to HTML. A doc block which
+/// doesn't parse is shown as written.
+pub fn rst_to_html(rst: &str, project_config: &ProjectConfig) -> String {
+    let html = match render(rst) {
+        Ok(html) => sanitize_html(&html, project_config.sanitize.level()),
+        Err(_) => format!("<pre>{}</pre>", escape(rst, EscapeContext::Pre)),
+    };
+    format!(
+        "<div class=\"CodeChat-rst\" contenteditable=\"false\" data-source=\"{}\">{html}</div>\n",
+        escape(rst, EscapeContext::Attribute)
+    )
+}
+
+fn render(rst: &str) -> Result<String, String> {
+    let document = rst_parser::parse(rst).map_err(|err| err.to_string())?;
+    let mut html = Vec::new();
+    // Render only the document's body, not a standalone page.
+    rst_renderer::render_html(&document, &mut html, false).map_err(|err| err.to_string())?;
+    String::from_utf8(html).map_err(|err| err.to_string())
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::rst_to_html;
+    use crate::processing::project_config::ProjectConfig;
+
+    #[test]
+    fn test_rst_to_html() {
+        let html = rst_to_html(
+            "Some *emphasis* and ``a < b``.\n",
+            &ProjectConfig::default(),
+        );
+        // The source is kept, so that the Client can save it unchanged.
+        assert!(html.starts_with(
+            "<div class=\"CodeChat-rst\" contenteditable=\"false\" data-source=\"Some *emphasis* and ``a &lt; b``.\n\">"
+        ));
+        assert!(html.ends_with("</div>\n"));
+        assert!(html.contains("<em>emphasis</em>"));
+        assert!(html.contains("a &lt; b"));
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `sanitize.rs` -- Remove unsafe HTML from rendered doc blocks
+///
+/// Markdown allows raw HTML, so a doc block may contain `<script>` tags, event
+/// handlers such as `onclick`, or `javascript:` links. The
+/// [ammonia](https://docs.rs/ammonia) crate removes these from the rendered
+/// HTML, keeping only an allow-list of elements and attributes. Two
+/// allow-lists are provided:
+///
+/// - Strict: the elements and attributes which Markdown and the CodeChat
+///   Editor produce. This is the default for exports and read-only pages.
+/// - Relaxed: strict, plus inline styles and embedded media. It also leaves
+///   links unchanged, so that saving a file doesn't modify them.
+///
+/// By default, HTML shown in the Client isn't sanitized, since the author
+/// trusts their own project and the Client saves the HTML it shows.
+///
+/// Comments are always kept, since the renderer uses them to mark the
+/// boundaries between doc blocks.
+///
+/// Since removed HTML silently disappears from the rendered doc block,
+/// `find_unsafe_html` reports where it occurs in the doc block's Markdown.
+// ## Imports
+//
+// ### Third-party
+use ammonia::Builder;
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::Regex;
+
+// ### Local
+use super::{
+    diagnostics::{Diagnostic, Severity},
+    project_config::SanitizeLevel,
+};
+
+// ## Globals
+/// The elements, and their attributes, which the CodeChat Editor adds to
+/// Markdown.
+const CODECHAT_TAGS: &[(&str, &[&str])] = &[
+    // Task lists.
+    ("input", &["checked", "disabled", "type"]),
+    // Diagrams.
+    ("graphviz-graph", &["graph", "scale"]),
+    ("graphviz-script-editor", &["value", "tab"]),
+    ("graphviz-combined", &["graph", "scale"]),
+    ("wc-mermaid", &[]),
+];
+
+/// The embedded media elements, and their attributes, allowed by the relaxed
+/// sanitizer.
+const MEDIA_TAGS: &[(&str, &[&str])] = &[
+    (
+        "iframe",
+        &[
+            "allow",
+            "allowfullscreen",
+            "frameborder",
+            "height",
+            "src",
+            "width",
+        ],
+    ),
+    (
+        "video",
+        &[
+            "autoplay", "controls", "height", "loop", "muted", "poster", "src", "width",
+        ],
+    ),
+    ("audio", &["autoplay", "controls", "loop", "muted", "src"]),
+    ("source", &["src", "type"]),
+    ("track", &["default", "kind", "label", "src", "srclang"]),
+];
+
+lazy_static! {
+    static ref STRICT: Builder<'static> = strict_builder();
+    static ref RELAXED: Builder<'static> = relaxed_builder();
+    /// Match HTML which both sanitizers remove: a script, an event handler
+    /// attribute, or a `javascript:` URL.
+    static ref UNSAFE_HTML_REGEX: Regex =
+        Regex::new(r"(?i)<script\b|\son[a-z]+\s*=|javascript:").unwrap();
+}
+
+// ## Code
+/// Sanitize the provided HTML at the provided level.
+pub fn sanitize_html(html: &str, level: SanitizeLevel) -> String {
+    match level {
+        SanitizeLevel::Strict => STRICT.clean(html).to_string(),
+        SanitizeLevel::Relaxed => RELAXED.clean(html).to_string(),
+        SanitizeLevel::Off => html.to_string(),
+    }
+}
+
+/// Report the unsafe HTML in the provided doc block's Markdown, which begins on
+/// `start_line` of its source file, that sanitizing at the provided level will
+/// remove.
+pub fn find_unsafe_html(
+    markdown: &str,
+    start_line: usize,
+    level: SanitizeLevel,
+) -> Vec<Diagnostic> {
+    if level == SanitizeLevel::Off {
+        return vec![];
+    }
+    Parser::new(markdown)
+        .into_offset_iter()
+        .filter(|(event, range)| match event {
+            Event::Html(_) | Event::InlineHtml(_) => {
+                UNSAFE_HTML_REGEX.is_match(&markdown[range.clone()])
+            }
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                UNSAFE_HTML_REGEX.is_match(dest_url)
+            }
+            _ => false,
+        })
+        .map(|(_, range)| {
+            Diagnostic::on_line(
+                Severity::Warning,
+                start_line + markdown[..range.start].matches('\n').count(),
+                "The sanitizer removes unsafe HTML (a script, event handler, or `javascript:` link) from this doc block.".to_string(),
+                "sanitize",
+            )
+        })
+        .collect()
+}
+
+/// Build the strict sanitizer: ammonia's defaults, plus the classes, IDs, and
+/// `data-*` attributes used by the CodeChat Editor and its elements.
+fn strict_builder() -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .add_generic_attributes(["class", "id"])
+        .add_generic_attribute_prefixes(["data-"])
+        .strip_comments(false);
+    add_tags(&mut builder, CODECHAT_TAGS);
+    builder
+}
+
+/// Build the relaxed sanitizer.
+fn relaxed_builder() -> Builder<'static> {
+    let mut builder = strict_builder();
+    builder.add_generic_attributes(["style"]).link_rel(None);
+    add_tags(&mut builder, MEDIA_TAGS);
+    builder
+}
+
+/// Allow the provided elements and their attributes.
+fn add_tags(builder: &mut Builder<'static>, tags: &[(&'static str, &[&'static str])]) {
+    for (tag, attributes) in tags {
+        builder
+            .add_tags([*tag])
+            .add_tag_attributes(*tag, attributes.iter().copied());
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{find_unsafe_html, sanitize_html};
+    use crate::processing::project_config::SanitizeLevel;
+
+    #[test]
+    fn test_sanitize_html() {
+        let html = r#"<p class="CodeChat-x" style="color: red" onclick="f()">A<script>f()</script> <a href="javascript:f()">link</a></p>"#;
+        assert_eq!(
+            sanitize_html(html, SanitizeLevel::Strict),
+            r#"<p class="CodeChat-x">A <a rel="noopener noreferrer">link</a></p>"#
+        );
+        assert_eq!(
+            sanitize_html(html, SanitizeLevel::Relaxed),
+            r#"<p class="CodeChat-x" style="color: red">A <a>link</a></p>"#
+        );
+        assert_eq!(sanitize_html(html, SanitizeLevel::Off), html);
+
+        // The CodeChat Editor's own markup is allowed by both levels.
+        let html = r#"<ul><li><input disabled="" type="checkbox"> Done</li></ul><graphviz-graph graph="digraph {}"></graphviz-graph><!--note-->"#;
+        assert_eq!(sanitize_html(html, SanitizeLevel::Strict), html);
+        assert_eq!(sanitize_html(html, SanitizeLevel::Relaxed), html);
+
+        // Media is only allowed by the relaxed level.
+        let html = r#"<video src="a.mp4" controls=""></video>"#;
+        assert_eq!(sanitize_html(html, SanitizeLevel::Strict), "");
+        assert_eq!(sanitize_html(html, SanitizeLevel::Relaxed), html);
+    }
+
+    #[test]
+    fn test_find_unsafe_html() {
+        let markdown = "Safe <b>text</b>.\n\n<script>f()</script>\n\nA [link](javascript:f()) and `<script>`.\n";
+        let lines = |level| {
+            find_unsafe_html(markdown, 10, level)
+                .into_iter()
+                .map(|diagnostic| diagnostic.range.start)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(SanitizeLevel::Relaxed), vec![12, 14]);
+        assert_eq!(lines(SanitizeLevel::Strict), vec![12, 14]);
+        assert_eq!(lines(SanitizeLevel::Off), Vec::<usize>::new());
+    }
+}
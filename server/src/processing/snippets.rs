@@ -0,0 +1,228 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `snippets.rs` -- Templates to insert into doc blocks
+///
+/// A snippet is a template for commonly-used content, such as a figure with a
+/// caption. Besides the built-in snippets below, a project may provide its
+/// own: each Markdown file in the `snippets/` directory at the project's root
+/// is a snippet, named by the file's name without its extension. A project's
+/// snippet replaces a built-in snippet with the same name.
+///
+/// The Server provides each snippet both as source code (a doc block in the
+/// language of the file being edited) for the IDE and as HTML for the Client,
+/// so that both insert the same content.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{
+    code_doc_block_vec_to_source, find_file_lexer, find_path_to_toc, find_project_root,
+    markdown_to_html, preprocess_markdown, project_config_for_file,
+};
+use crate::lexer::{CodeDocBlock, DocBlock, LanguageLexerCompiled};
+
+// ## Globals
+/// The directory, relative to a project's root, containing its snippets.
+pub const SNIPPETS_DIR_NAME: &str = "snippets";
+
+/// The built-in snippets, as (name, Markdown) pairs.
+const BUILT_IN_SNIPPETS: &[(&str, &str)] = &[
+    ("admonition", ":::note Title\nText.\n:::\n"),
+    ("equation", "$$E = mc^2$$ {#eq:name}\n"),
+    ("figure", "![Description](image.svg)\n\n{#fig:name} Caption.\n"),
+    ("mermaid", "<wc-mermaid>graph TD; A --> B;</wc-mermaid>\n"),
+    (
+        "table",
+        "{#tbl:name} Caption.\n\n| Heading | Heading |\n| ------- | ------- |\n| Cell    | Cell    |\n",
+    ),
+];
+
+// ## Data structures
+/// A snippet, ready to insert into a file.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Snippet {
+    /// The snippet's Markdown.
+    pub markdown: String,
+    /// The snippet as a doc block in the file's language, for the IDE to
+    /// insert.
+    pub source: String,
+    /// The snippet rendered to HTML, for the Client to insert.
+    pub html: String,
+}
+
+// ## Code
+/// Return the names of the snippets available when editing the provided file,
+/// sorted alphabetically.
+pub fn snippet_names(file_path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = BUILT_IN_SNIPPETS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    if let Some(snippets_dir) = snippets_dir(file_path) {
+        if let Ok(entries) = fs::read_dir(snippets_dir) {
+            names.extend(entries.filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "md")
+                    .then_some(path.file_stem()?.to_string_lossy().to_string())
+            }));
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Return the snippet with the provided name, prepared for insertion into the
+/// provided file.
+pub fn snippet(name: &str, file_path: &Path) -> Result<Snippet, String> {
+    let project_snippet = snippets_dir(file_path)
+        .map(|snippets_dir| snippets_dir.join(format!("{name}.md")))
+        .filter(|snippet_path| snippet_path.is_file());
+    let markdown = match project_snippet {
+        Some(snippet_path) => fs::read_to_string(&snippet_path)
+            .map_err(|err| format!("Unable to read {snippet_path:?}: {err}."))?,
+        None => BUILT_IN_SNIPPETS
+            .iter()
+            .find(|(built_in_name, _)| *built_in_name == name)
+            .map(|(_, markdown)| markdown.to_string())
+            .ok_or_else(|| format!("Unknown snippet '{name}'."))?,
+    };
+
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let lexer = find_file_lexer("", file_path, &project_config)?;
+    let source = markdown_to_doc_block(&markdown, lexer)?;
+    let html = markdown_to_html(
+        &preprocess_markdown(&markdown, &project_config),
+        &project_config,
+    );
+    Ok(Snippet {
+        markdown,
+        source,
+        html,
+    })
+}
+
+/// Return the provided Markdown as a doc block in the language of `lexer`; for
+/// Markdown, this is the Markdown itself.
+pub fn markdown_to_doc_block(
+    markdown: &str,
+    lexer: &LanguageLexerCompiled,
+) -> Result<String, String> {
+    let language_lexer = &lexer.language_lexer;
+    if language_lexer.lexer_name.as_str() == "markdown" {
+        return Ok(markdown.to_string());
+    }
+    // Prefer inline comments, which are simpler to edit.
+    let delimiter = language_lexer
+        .inline_comment_delim_arr
+        .first()
+        .or_else(|| {
+            language_lexer
+                .block_comment_delim_arr
+                .first()
+                .map(|block_comment| &block_comment.opening)
+        })
+        .ok_or_else(|| format!("{} has no comments.", language_lexer.lexer_name))?;
+    code_doc_block_vec_to_source(
+        &vec![CodeDocBlock::DocBlock(DocBlock {
+            indent: "".to_string(),
+            delimiter: delimiter.to_string(),
+            contents: markdown.to_string(),
+            lines: 0,
+        })],
+        lexer,
+    )
+}
+
+// Return the snippets directory of the project containing the provided file, if
+// it's part of a project.
+fn snippets_dir(file_path: &Path) -> Option<PathBuf> {
+    Some(find_project_root(file_path)?.join(SNIPPETS_DIR_NAME))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use assert_fs::TempDir;
+
+    use super::{snippet, snippet_names};
+
+    #[test]
+    fn test_built_in_snippets() {
+        let file_path = Path::new("foo.py");
+        assert_eq!(
+            snippet_names(file_path),
+            vec!["admonition", "equation", "figure", "mermaid", "table"]
+        );
+        let admonition = snippet("admonition", file_path).unwrap();
+        assert_eq!(admonition.markdown, ":::note Title\nText.\n:::\n");
+        assert_eq!(admonition.source, "# :::note Title\n# Text.\n# :::\n");
+        assert!(admonition.html.contains("Text."));
+
+        // Block comments are used when a language lacks inline comments.
+        assert_eq!(
+            snippet("mermaid", Path::new("foo.css")).unwrap().source,
+            "/* <wc-mermaid>graph TD; A --> B;</wc-mermaid> */\n"
+        );
+        // Markdown files need no comments.
+        assert_eq!(
+            snippet("figure", Path::new("foo.md")).unwrap().source,
+            "![Description](image.svg)\n\n{#fig:name} Caption.\n"
+        );
+        assert!(snippet("unknown", file_path).is_err());
+    }
+
+    #[test]
+    fn test_project_snippets() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("foo.py");
+        fs::write(temp_dir.path().join("toc.md"), "[foo](foo.py)\n").unwrap();
+        fs::create_dir(temp_dir.path().join("snippets")).unwrap();
+        fs::write(temp_dir.path().join("snippets/todo.md"), "**TODO**\n").unwrap();
+        fs::write(temp_dir.path().join("snippets/figure.md"), "A figure.\n").unwrap();
+        fs::write(temp_dir.path().join("snippets/notes.txt"), "Ignored.\n").unwrap();
+
+        assert_eq!(
+            snippet_names(&file_path),
+            vec![
+                "admonition",
+                "equation",
+                "figure",
+                "mermaid",
+                "table",
+                "todo"
+            ]
+        );
+        let todo = snippet("todo", &file_path).unwrap();
+        assert_eq!(todo.source, "# **TODO**\n");
+        assert_eq!(todo.html, "<p><strong>TODO</strong></p>\n");
+        // A project's snippet replaces the built-in snippet.
+        assert_eq!(
+            snippet("figure", &file_path).unwrap().markdown,
+            "A figure.\n"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `todos.rs` -- Find TODO, FIXME, and HACK markers
+///
+/// A marker is one of the words `TODO`, `FIXME`, or `HACK`, in a doc block or
+/// in a comment following code, optionally followed by its owner in
+/// parentheses and a colon: `TODO(alice): handle errors.` The text following
+/// the marker, to the end of the line, describes it.
+///
+/// Markers in code outside of comments, such as in strings, are ignored.
+/// However, only comments which begin on the marker's line are recognized, so
+/// a marker in a later line of a multi-line comment which isn't a doc block is
+/// missed.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::{doc_blocks_with_lines, find_file_lexer, find_path_to_toc, project_config_for_file};
+
+// ## Globals
+lazy_static! {
+    /// Match a marker, capturing the marker, its owner, and its text.
+    static ref MARKER_REGEX: Regex =
+        Regex::new(r"\b(TODO|FIXME|HACK)\b(?:\(([^)]*)\))?:?\s*(.*)").unwrap();
+}
+
+/// Directories skipped when searching a project, since they contain generated
+/// or third-party files.
+const SKIPPED_DIRS: [&str; 3] = ["node_modules", "target", "__pycache__"];
+
+/// Files larger than this, in bytes, are skipped when searching a project; they
+/// are usually generated.
+const MAX_FILE_SIZE: u64 = 1_000_000;
+
+// ## Data structures
+/// One marker.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Todo {
+    /// The path to the file containing the marker.
+    pub file_path: String,
+    /// The line containing the marker, numbered from 1.
+    pub line: usize,
+    /// `TODO`, `FIXME`, or `HACK`.
+    pub marker: String,
+    pub owner: Option<String>,
+    pub text: String,
+}
+
+// ## Code
+/// Return the markers in the provided file, in order. Return an empty list
+/// for files the CodeChat Editor doesn't support.
+pub fn find_todos(file_contents: &str, file_path: &Path) -> Vec<Todo> {
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let Ok(lexer) = find_file_lexer(file_contents, file_path, &project_config) else {
+        return Vec::new();
+    };
+    let mut doc_lines = HashSet::new();
+    for (start_line, doc_block) in doc_blocks_with_lines(file_contents, lexer) {
+        doc_lines.extend(start_line..start_line + doc_block.lines.max(1));
+    }
+    // The delimiters which begin a comment in code.
+    let language_lexer = &lexer.language_lexer;
+    let comment_delims: Vec<&str> = language_lexer
+        .inline_comment_delim_arr
+        .iter()
+        .map(String::as_str)
+        .chain(
+            language_lexer
+                .block_comment_delim_arr
+                .iter()
+                .map(|delim| delim.opening.as_str()),
+        )
+        .collect();
+
+    let mut todos = Vec::new();
+    for (index, line) in file_contents.lines().enumerate() {
+        let line_number = index + 1;
+        // In code, only look in the comment, if there is one.
+        let search = if doc_lines.contains(&line_number) {
+            line
+        } else {
+            match comment_delims
+                .iter()
+                .filter_map(|delim| line.find(delim).map(|start| start + delim.len()))
+                .min()
+            {
+                Some(comment_start) => &line[comment_start..],
+                None => continue,
+            }
+        };
+        if let Some(captures) = MARKER_REGEX.captures(search) {
+            todos.push(Todo {
+                file_path: file_path.to_string_lossy().to_string(),
+                line: line_number,
+                marker: captures[1].to_string(),
+                owner: captures
+                    .get(2)
+                    .map(|owner| owner.as_str().trim().to_string())
+                    .filter(|owner| !owner.is_empty()),
+                text: captures[3]
+                    .trim()
+                    // Omit the end of a block comment.
+                    .trim_end_matches("*/")
+                    .trim_end_matches("-->")
+                    .trim()
+                    .to_string(),
+            });
+        }
+    }
+    todos
+}
+
+/// Return the markers in all files in `dir` and its subdirectories, skipping
+/// hidden files and directories, along with directories of generated or
+/// third-party files.
+pub fn find_project_todos(dir: &Path) -> Vec<Todo> {
+    let mut todos = Vec::new();
+    for file_path in project_files(dir) {
+        if let Ok(file_contents) = fs::read_to_string(&file_path) {
+            todos.extend(find_todos(&file_contents, &file_path));
+        }
+    }
+    todos
+}
+
+// Return the files to search in `dir`, sorted by path.
+fn project_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_ref()) {
+                files.extend(project_files(&entry.path()));
+            }
+        } else if metadata.is_file() && metadata.len() <= MAX_FILE_SIZE {
+            files.push(entry.path());
+        }
+    }
+    files
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use assert_fs::TempDir;
+    use indoc::indoc;
+
+    use super::{find_project_todos, find_todos, Todo};
+
+    fn todo(line: usize, marker: &str, owner: Option<&str>, text: &str) -> Todo {
+        Todo {
+            file_path: "a.py".to_string(),
+            line,
+            marker: marker.to_string(),
+            owner: owner.map(str::to_string),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_todos() {
+        assert_eq!(
+            find_todos(
+                indoc!(
+                    r#"
+                    # TODO(alice): handle errors.
+                    x = 1  # FIXME: off by one
+                    s = "TODO: not a comment"
+                    # Some docs.
+                    #
+                    # HACK works around a bug.
+                    """TODO docstrings are strings."""
+                    "#
+                ),
+                Path::new("a.py")
+            ),
+            vec![
+                todo(1, "TODO", Some("alice"), "handle errors."),
+                todo(2, "FIXME", None, "off by one"),
+                todo(6, "HACK", None, "works around a bug."),
+            ]
+        );
+        // Block comments are recognized; their closing delimiter is omitted.
+        let todos = find_todos("int x; /* TODO(bob) fix */\n", Path::new("a.c"));
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].owner.as_deref(), Some("bob"));
+        assert_eq!(todos[0].text, "fix");
+        // Words containing a marker aren't markers.
+        assert!(find_todos("# TODOS and HACKER\n", Path::new("a.py")).is_empty());
+        // Unsupported files have no markers.
+        assert!(find_todos("TODO", Path::new("a.unknown")).is_empty());
+    }
+
+    #[test]
+    fn test_find_project_todos() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.py"), "# TODO: a\n").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub/b.py"), "# FIXME: b\n").unwrap();
+        for skipped in [".git", "node_modules"] {
+            fs::create_dir(temp_dir.path().join(skipped)).unwrap();
+            fs::write(temp_dir.path().join(skipped).join("c.py"), "# TODO: c\n").unwrap();
+        }
+        let texts: Vec<_> = find_project_todos(temp_dir.path())
+            .into_iter()
+            .map(|todo| todo.text)
+            .collect();
+        assert_eq!(texts, vec!["a", "b"]);
+        temp_dir.close().unwrap();
+    }
+}
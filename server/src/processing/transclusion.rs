@@ -0,0 +1,294 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `transclusion.rs` -- Quote code from other files
+///
+/// A fenced code block whose language is `include` shows part of another file,
+/// read when the doc block is rendered, so that documentation quotes the
+/// current code rather than a copy which drifts out of date:
+///
+/// ````markdown
+/// ```include
+/// ../src/main.rs#setup
+/// ```
+/// ````
+///
+/// The fence contains the path to the file, relative to the file containing
+/// the doc block, followed by either:
+///
+/// - `#name`: the lines between a line containing `BEGIN name` and a line
+///   containing `END name`, typically placed in comments, such as `// BEGIN
+///   setup` and `// END setup`. The marker lines aren't included.
+/// - `:first-last`: lines `first` through `last`, numbered from 1. Omit `last`
+///   to continue to the end of the file; omit `-last` for a single line.
+/// - Nothing, to include the entire file.
+///
+/// The quoted code is read-only; editing the fence changes what's quoted. The
+/// file watcher also watches the quoted files, updating the rendered doc
+/// blocks when they change.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// ### Local
+use super::{
+    escape::{escape, EscapeContext},
+    fences::FenceRenderer,
+    project_config::ProjectConfig,
+};
+
+// ## Globals
+/// The language of a fence which quotes another file.
+pub const INCLUDE_LANGUAGE: &str = "include";
+
+lazy_static! {
+    /// Match the path in an `include` fence, when followed by a line range.
+    static ref LINE_RANGE_REGEX: Regex = Regex::new(r"^(.+):(\d+)(?:-(\d*))?$").unwrap();
+    /// Match the first line of an `include` fence in a source file, capturing
+    /// it. The fence may be in a doc block; skip any comment delimiter
+    /// preceding this line.
+    static ref INCLUDE_FENCE_REGEX: Regex = Regex::new(
+        r"(?m)(?:```|~~~)[ \t]*include[ \t]*\r?\n[ \t]*(?:(?://+|#+|;+|--|%+|\*+)[ \t]*)?(\S[^\r\n]*?)[ \t]*\r?$"
+    )
+    .unwrap();
+}
+
+// ## Data structures
+/// The part of a file to quote.
+#[derive(Debug, PartialEq)]
+enum Selection {
+    All,
+    Region(String),
+    /// The first and last lines, numbered from 1.
+    Lines(usize, Option<usize>),
+}
+
+/// Render `include` fences.
+pub struct TransclusionRenderer;
+
+impl FenceRenderer for TransclusionRenderer {
+    fn render(&self, code: &str, project_config: &ProjectConfig) -> Result<String, String> {
+        let (path, selection) = parse_include(code)?;
+        let file_path = resolve(&path, project_config.file_dir.as_deref());
+        let file_contents = fs::read_to_string(&file_path)
+            .map_err(|err| format!("Unable to read {file_path:?}: {err}."))?;
+        let snippet = select(&file_contents, &selection)?;
+        let language = file_path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(format!(
+            "<figure class=\"CodeChat-transclusion\"><figcaption><code>{}</code></figcaption><pre><code class=\"language-{}\">{}</code></pre></figure>",
+            escape(code.trim(), EscapeContext::Text),
+            escape(&language, EscapeContext::Attribute),
+            escape(&snippet, EscapeContext::Text)
+        ))
+    }
+
+    fn dependencies(&self, code: &str, project_config: &ProjectConfig) -> Vec<PathBuf> {
+        match parse_include(code) {
+            Ok((path, _)) => vec![resolve(&path, project_config.file_dir.as_deref())],
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// ## Code
+/// Return the paths of the files quoted by `include` fences in the provided
+/// file, so that changes to them can be detected.
+pub fn transcluded_files(file_path: &Path, file_contents: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for captures in INCLUDE_FENCE_REGEX.captures_iter(file_contents) {
+        let Ok((path, _)) = parse_include(&captures[1]) else {
+            continue;
+        };
+        let path = resolve(&path, file_path.parent());
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+// Split the contents of an `include` fence into a path and a selection.
+fn parse_include(code: &str) -> Result<(String, Selection), String> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err("The include fence doesn't name a file.".to_string());
+    }
+    if let Some((path, region)) = code.rsplit_once('#') {
+        return Ok((
+            path.to_string(),
+            Selection::Region(region.trim().to_string()),
+        ));
+    }
+    if let Some(captures) = LINE_RANGE_REGEX.captures(code) {
+        let first: usize = captures[2].parse().map_err(|_| "Invalid line number.")?;
+        let last = match captures.get(3) {
+            // A single line.
+            None => Some(first),
+            // To the end of the file.
+            Some(last) if last.as_str().is_empty() => None,
+            Some(last) => Some(last.as_str().parse().map_err(|_| "Invalid line number.")?),
+        };
+        return Ok((captures[1].to_string(), Selection::Lines(first, last)));
+    }
+    Ok((code.to_string(), Selection::All))
+}
+
+// Find a quoted file relative to the directory of the file quoting it.
+fn resolve(path: &str, file_dir: Option<&Path>) -> PathBuf {
+    match file_dir {
+        Some(file_dir) => file_dir.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+// Return the selected part of a file, removing any indent common to all its
+// lines.
+fn select(file_contents: &str, selection: &Selection) -> Result<String, String> {
+    let lines: Vec<&str> = file_contents.lines().collect();
+    let selected: &[&str] = match selection {
+        Selection::All => &lines,
+        Selection::Lines(first, last) => {
+            let last = last.unwrap_or(lines.len());
+            if *first == 0 || *first > last || last > lines.len() {
+                return Err(format!(
+                    "Lines {first}-{last} aren't in the file, which has {} lines.",
+                    lines.len()
+                ));
+            }
+            &lines[first - 1..last]
+        }
+        Selection::Region(name) => {
+            let is_marker = |line: &str, marker: &str| {
+                let mut words = line.split_whitespace();
+                words.any(|word| word == marker) && words.next() == Some(name.as_str())
+            };
+            let begin = lines
+                .iter()
+                .position(|line| is_marker(line, "BEGIN"))
+                .ok_or_else(|| format!("No line contains BEGIN {name}."))?;
+            let length = lines[begin + 1..]
+                .iter()
+                .position(|line| is_marker(line, "END"))
+                .ok_or_else(|| format!("No line following BEGIN {name} contains END {name}."))?;
+            &lines[begin + 1..begin + 1 + length]
+        }
+    };
+    let indent = selected
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    Ok(selected
+        .iter()
+        .map(|line| format!("{}\n", line.get(indent..).unwrap_or("").trim_end()))
+        .collect())
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+
+    use super::{parse_include, select, transcluded_files, Selection, TransclusionRenderer};
+    use crate::processing::{fences::FenceRenderer, project_config::ProjectConfig};
+
+    const FILE: &str =
+        "fn main() {\n    // BEGIN setup\n    let x = 1;\n    let y = 2;\n    // END setup\n}\n";
+
+    #[test]
+    fn test_parse_include() {
+        assert_eq!(
+            parse_include("a.rs#setup\n"),
+            Ok(("a.rs".to_string(), Selection::Region("setup".to_string())))
+        );
+        assert_eq!(
+            parse_include("a.rs:2-4"),
+            Ok(("a.rs".to_string(), Selection::Lines(2, Some(4))))
+        );
+        assert_eq!(
+            parse_include("a.rs:2-"),
+            Ok(("a.rs".to_string(), Selection::Lines(2, None)))
+        );
+        assert_eq!(
+            parse_include("a.rs:2"),
+            Ok(("a.rs".to_string(), Selection::Lines(2, Some(2))))
+        );
+        assert_eq!(
+            parse_include("a.rs"),
+            Ok(("a.rs".to_string(), Selection::All))
+        );
+        assert!(parse_include(" \n").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(
+            select(FILE, &Selection::Region("setup".to_string())).unwrap(),
+            "let x = 1;\nlet y = 2;\n"
+        );
+        assert_eq!(
+            select(FILE, &Selection::Lines(1, Some(2))).unwrap(),
+            "fn main() {\n    // BEGIN setup\n"
+        );
+        assert_eq!(select(FILE, &Selection::Lines(6, None)).unwrap(), "}\n");
+        assert_eq!(select(FILE, &Selection::All).unwrap(), FILE);
+        assert!(select(FILE, &Selection::Region("missing".to_string())).is_err());
+        assert!(select(FILE, &Selection::Lines(0, Some(2))).is_err());
+        assert!(select(FILE, &Selection::Lines(2, Some(10))).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.rs"), FILE).unwrap();
+        let project_config = ProjectConfig {
+            file_dir: Some(temp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(
+            TransclusionRenderer
+                .render("a.rs#setup\n", &project_config)
+                .unwrap(),
+            "<figure class=\"CodeChat-transclusion\"><figcaption><code>a.rs#setup</code></figcaption><pre><code class=\"language-rs\">let x = 1;\nlet y = 2;\n</code></pre></figure>"
+        );
+        assert!(TransclusionRenderer
+            .render("missing.rs", &project_config)
+            .is_err());
+
+        let file_path = temp_dir.path().join("doc.md");
+        assert_eq!(
+            transcluded_files(
+                &file_path,
+                "```include\na.rs#setup\n```\n\n```include\na.rs:1-2\n```\n\n```include\nb.rs\n```\n"
+            ),
+            vec![temp_dir.path().join("a.rs"), temp_dir.path().join("b.rs")]
+        );
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `workspace.rs` -- Track the roots of IDE workspaces and their trust
+///
+/// An IDE's workspace may contain several folders (roots), each of which the
+/// user may or may not trust; the IDE reports these in a `WorkspaceRoots`
+/// message. The Server then:
+///
+/// *   looks for the project (the `toc.md`) containing a file only within the
+///     root containing this file, so that a project never spans several roots;
+/// *   doesn't run commands given by the configuration of a project in an
+///     untrusted root, such as fence render commands or a local PlantUML jar.
+///
+/// Files outside every root, such as those opened from the command line, are
+/// treated as before: their project may be found in any parent directory, and
+/// they're trusted.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::file_language_key;
+
+// ## Data structures
+/// A root folder of an IDE's workspace.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WorkspaceRoot {
+    pub path: PathBuf,
+    /// True if the user trusts the contents of this folder.
+    pub trusted: bool,
+}
+
+// ## Globals
+lazy_static! {
+    /// The roots of each IDE's workspace, indexed by the IDE's connection ID.
+    static ref WORKSPACE_ROOTS: RwLock<HashMap<String, Vec<WorkspaceRoot>>> =
+        RwLock::new(HashMap::new());
+}
+
+// ## Code
+/// Record the roots of the workspace of the IDE with the provided connection
+/// ID, replacing any previously provided; an empty list removes them.
+pub fn set_workspace_roots(connection_id: &str, roots: Vec<WorkspaceRoot>) {
+    let mut workspace_roots = WORKSPACE_ROOTS.write().unwrap();
+    if roots.is_empty() {
+        workspace_roots.remove(connection_id);
+    } else {
+        // Since the same folder may be referred to by different paths, compare
+        // canonical paths.
+        workspace_roots.insert(
+            connection_id.to_string(),
+            roots
+                .into_iter()
+                .map(|root| WorkspaceRoot {
+                    path: file_language_key(&root.path),
+                    trusted: root.trusted,
+                })
+                .collect(),
+        );
+    }
+}
+
+/// Return the number of parent directories between the provided path and the
+/// root of the workspace containing it; this is 0 for the root itself. Return
+/// `None` if no workspace contains this path.
+pub fn root_distance(path: &Path) -> Option<usize> {
+    let path = file_language_key(path);
+    containing_root(&WORKSPACE_ROOTS.read().unwrap(), &path)
+        .and_then(|root| path.strip_prefix(&root.path).ok())
+        .map(|relative_path| relative_path.components().count())
+}
+
+/// Return true if the user trusts the provided path. Paths outside every
+/// workspace are trusted.
+pub fn is_trusted(path: &Path) -> bool {
+    containing_root(&WORKSPACE_ROOTS.read().unwrap(), &file_language_key(path))
+        .is_none_or(|root| root.trusted)
+}
+
+// Return the innermost root containing the provided canonical path, since
+// roots may be nested.
+fn containing_root<'a>(
+    workspace_roots: &'a HashMap<String, Vec<WorkspaceRoot>>,
+    path: &Path,
+) -> Option<&'a WorkspaceRoot> {
+    workspace_roots
+        .values()
+        .flatten()
+        .filter(|root| path.starts_with(&root.path))
+        .max_by_key(|root| root.path.components().count())
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::{containing_root, WorkspaceRoot};
+
+    fn root(path: &str, trusted: bool) -> WorkspaceRoot {
+        WorkspaceRoot {
+            path: PathBuf::from(path),
+            trusted,
+        }
+    }
+
+    #[test]
+    fn test_containing_root() {
+        let workspace_roots = HashMap::from([
+            ("1".to_string(), vec![root("/a", true), root("/b", false)]),
+            ("2".to_string(), vec![root("/a/c", false)]),
+        ]);
+        let find = |path: &str| containing_root(&workspace_roots, &PathBuf::from(path));
+        assert_eq!(find("/a/foo.py"), Some(&root("/a", true)));
+        assert_eq!(find("/b/d/foo.py"), Some(&root("/b", false)));
+        // The innermost root contains a file in nested roots.
+        assert_eq!(find("/a/c/foo.py"), Some(&root("/a/c", false)));
+        // A root contains only paths within it, not those sharing a prefix.
+        assert_eq!(find("/ab/foo.py"), None);
+        assert_eq!(find("/d/foo.py"), None);
+    }
+}
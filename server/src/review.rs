@@ -0,0 +1,458 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `review.rs` -- Review changes to doc blocks as prose
+///
+/// In a pull request's diff, a change to a doc block is buried in comment
+/// delimiters and mixed with code changes. The `review-docs` command instead
+/// compares the doc blocks of each file changed between two git revisions,
+/// then produces one review comment per changed doc block, showing a diff of
+/// just its Markdown.
+///
+/// The result is a pull request review in the format of GitHub's
+/// [create a review](https://docs.github.com/en/rest/pulls/reviews#create-a-review-for-a-pull-request)
+/// API: printed as JSON, for use with other tools, or posted directly to a
+/// pull request.
+///
+/// Doc blocks are matched between the two revisions by their contents; a
+/// changed doc block is one which is replaced by another at the same place.
+/// Comments on added or changed doc blocks refer to their lines in the new
+/// revision, while comments on removed doc blocks refer to their lines in the
+/// old revision.
+// ## Imports
+//
+// ### Standard library
+use std::path::Path;
+
+// ### Third-party
+use git2::{Delta, Oid, Repository};
+use serde::Serialize;
+
+// ### Local
+use crate::{
+    lexer::DocBlock,
+    processing::{
+        diff::{diff_str, DiffGranularity},
+        doc_blocks_with_lines, find_file_lexer, find_path_to_toc, project_config_for_file,
+    },
+};
+
+// ## Data structures
+/// Which version of a file a comment refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+    /// The old revision.
+    Left,
+    /// The new revision.
+    Right,
+}
+
+/// A comment on a range of lines in one file.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReviewComment {
+    /// The file's path, relative to the repository's root.
+    pub path: String,
+    /// The first line of a comment on several lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    /// The last line commented on.
+    pub line: usize,
+    pub side: Side,
+    /// The comment, in Markdown.
+    pub body: String,
+}
+
+/// A review of a pull request, as sent to GitHub.
+#[derive(Debug, Serialize)]
+pub struct Review {
+    /// The commit reviewed, which is the new revision.
+    pub commit_id: String,
+    pub body: String,
+    /// Leave comments without approving or requesting changes.
+    pub event: &'static str,
+    pub comments: Vec<ReviewComment>,
+}
+
+// ## Code
+/// Compare the doc blocks of the files changed from revision `base` to revision
+/// `head` of the git repository containing `repo_dir`, returning a review with
+/// a comment on each changed doc block.
+pub fn review_doc_blocks(repo_dir: &Path, base: &str, head: &str) -> Result<Review, String> {
+    let repo = Repository::discover(repo_dir)
+        .map_err(|err| format!("{repo_dir:?} isn't in a git repository: {err}"))?;
+    let workdir = repo
+        .workdir()
+        .ok_or("The repository has no working tree.")?
+        .to_path_buf();
+    let find_commit = |revision: &str| {
+        repo.revparse_single(revision)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|err| format!("Unknown revision {revision}: {err}"))
+    };
+    let base_commit = find_commit(base)?;
+    let head_commit = find_commit(head)?;
+    let diff = repo
+        .diff_tree_to_tree(
+            Some(&base_commit.tree().map_err(|err| err.to_string())?),
+            Some(&head_commit.tree().map_err(|err| err.to_string())?),
+            None,
+        )
+        .map_err(|err| format!("Unable to compare {base} and {head}: {err}"))?;
+
+    let mut comments = Vec::new();
+    for delta in diff.deltas() {
+        if !matches!(
+            delta.status(),
+            Delta::Added | Delta::Deleted | Delta::Modified
+        ) {
+            continue;
+        }
+        let Some(path) = delta
+            .new_file()
+            .path()
+            .or(delta.old_file().path())
+            .map(Path::to_path_buf)
+        else {
+            continue;
+        };
+        // Use the file's location in the working tree to find its project's
+        // configuration, which determines its language.
+        let file_path = workdir.join(&path);
+        let doc_blocks = |id: Oid| -> Vec<(usize, DocBlock)> {
+            if id.is_zero() {
+                return Vec::new();
+            }
+            let Some(file_contents) = repo
+                .find_blob(id)
+                .ok()
+                .and_then(|blob| String::from_utf8(blob.content().to_vec()).ok())
+            else {
+                return Vec::new();
+            };
+            let project_config =
+                project_config_for_file(&file_path, find_path_to_toc(&file_path).as_deref());
+            match find_file_lexer(&file_contents, &file_path, &project_config) {
+                Ok(lexer) => doc_blocks_with_lines(&file_contents, lexer),
+                // Skip files the CodeChat Editor doesn't support.
+                Err(_) => Vec::new(),
+            }
+        };
+        comments.extend(doc_block_comments(
+            &path.to_string_lossy().replace('\\', "/"),
+            &doc_blocks(delta.old_file().id()),
+            &doc_blocks(delta.new_file().id()),
+        ));
+    }
+
+    let count = comments.len();
+    Ok(Review {
+        commit_id: head_commit.id().to_string(),
+        body: format!(
+            "The CodeChat Editor found {count} changed doc block{} between {base} and {head}.",
+            if count == 1 { "" } else { "s" }
+        ),
+        event: "COMMENT",
+        comments,
+    })
+}
+
+/// Post the provided review to a pull request on GitHub. `repository` is the
+/// repository's owner and name, such as `bjones1/CodeChat_Editor`; `token`
+/// is a GitHub token allowed to write to its pull requests.
+pub fn post_review(
+    repository: &str,
+    pull_request: u64,
+    review: &Review,
+    token: &str,
+) -> Result<(), String> {
+    let response = minreq::post(format!(
+        "https://api.github.com/repos/{repository}/pulls/{pull_request}/reviews"
+    ))
+    .with_header("Accept", "application/vnd.github+json")
+    .with_header("Authorization", format!("Bearer {token}"))
+    .with_header("User-Agent", "CodeChat-Editor")
+    .with_header("X-GitHub-Api-Version", "2022-11-28")
+    .with_json(review)
+    .map_err(|err| format!("Unable to encode the review: {err}"))?
+    .with_timeout(30)
+    .send()
+    .map_err(|err| format!("Unable to reach GitHub: {err}"))?;
+    if (200..300).contains(&response.status_code) {
+        Ok(())
+    } else {
+        Err(format!(
+            "GitHub rejected the review: {}, status code = {}",
+            response.as_str().unwrap_or("Non-text body"),
+            response.status_code
+        ))
+    }
+}
+
+// Compare the doc blocks of two versions of the file `path`, each paired with
+// the line it begins on, returning a comment on each changed doc block.
+fn doc_block_comments(
+    path: &str,
+    before: &[(usize, DocBlock)],
+    after: &[(usize, DocBlock)],
+) -> Vec<ReviewComment> {
+    let comment = |(start_line, doc_block): &(usize, DocBlock), side, body| {
+        let last_line = start_line + doc_block.lines.max(1) - 1;
+        ReviewComment {
+            path: path.to_string(),
+            start_line: (last_line > *start_line).then_some(*start_line),
+            line: last_line,
+            side,
+            body,
+        }
+    };
+    let mut comments = Vec::new();
+    // Walk the runs of unmatched doc blocks between matched ones; pair the
+    // doc blocks in each run as changes, leaving any extras as additions or
+    // removals.
+    let (mut old_index, mut new_index) = (0, 0);
+    let mut matches = matching_doc_blocks(before, after);
+    matches.push((before.len(), after.len()));
+    for (old_match, new_match) in matches {
+        let removed = &before[old_index..old_match];
+        let added = &after[new_index..new_match];
+        for index in 0..removed.len().max(added.len()) {
+            match (removed.get(index), added.get(index)) {
+                (Some(old), Some(new)) => comments.push(comment(
+                    new,
+                    Side::Right,
+                    format!(
+                        "Changed doc block:\n\n{}",
+                        prose_diff(&old.1.contents, &new.1.contents)
+                    ),
+                )),
+                (None, Some(new)) => comments.push(comment(
+                    new,
+                    Side::Right,
+                    format!("Added doc block:\n\n{}", prose_diff("", &new.1.contents)),
+                )),
+                (Some(old), None) => comments.push(comment(
+                    old,
+                    Side::Left,
+                    format!("Removed doc block:\n\n{}", prose_diff(&old.1.contents, "")),
+                )),
+                (None, None) => unreachable!(),
+            }
+        }
+        old_index = old_match + 1;
+        new_index = new_match + 1;
+    }
+    comments
+}
+
+// Return the indexes of the doc blocks whose contents are unchanged, using the
+// longest common subsequence of the two lists of doc blocks.
+fn matching_doc_blocks(
+    before: &[(usize, DocBlock)],
+    after: &[(usize, DocBlock)],
+) -> Vec<(usize, usize)> {
+    // `lengths[i][j]` is the length of the longest common subsequence of
+    // `before[i..]` and `after[j..]`.
+    let mut lengths = vec![vec![0; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lengths[i][j] = if before[i].1.contents == after[j].1.contents {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut matches = Vec::new();
+    while i < before.len() && j < after.len() {
+        if before[i].1.contents == after[j].1.contents {
+            matches.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+// Show the changes from `before` to `after` as a fenced block of a unified
+// diff of their lines.
+fn prose_diff(before: &str, after: &str) -> String {
+    let before_chars: Vec<char> = before.chars().collect();
+    let mut lines = Vec::new();
+    let mut index = 0;
+    let push_lines = |lines: &mut Vec<String>, prefix: char, text: &str| {
+        lines.extend(text.lines().map(|line| format!("{prefix} {line}")));
+    };
+    for diff in diff_str(before, after, DiffGranularity::Line) {
+        let context: String = before_chars[index..diff.from].iter().collect();
+        push_lines(&mut lines, ' ', &context);
+        let removed: String = before_chars[diff.from..diff.to].iter().collect();
+        push_lines(&mut lines, '-', &removed);
+        push_lines(&mut lines, '+', &diff.insert);
+        index = diff.to;
+    }
+    let context: String = before_chars[index..].iter().collect();
+    push_lines(&mut lines, ' ', &context);
+    // Use a fence longer than any run of backticks in the text.
+    let fence = "`".repeat(
+        lines
+            .iter()
+            .map(|line| line.split(|c| c != '`').map(str::len).max().unwrap_or(0))
+            .max()
+            .unwrap_or(0)
+            .max(2)
+            + 1,
+    );
+    format!("{fence}diff\n{}\n{fence}", lines.join("\n"))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use assert_fs::TempDir;
+    use git2::{Repository, Signature};
+
+    use super::{doc_block_comments, prose_diff, review_doc_blocks, ReviewComment, Side};
+    use crate::lexer::DocBlock;
+
+    fn doc_block(line: usize, lines: usize, contents: &str) -> (usize, DocBlock) {
+        (
+            line,
+            DocBlock {
+                indent: "".to_string(),
+                delimiter: "#".to_string(),
+                contents: contents.to_string(),
+                lines,
+            },
+        )
+    }
+
+    #[test]
+    fn test_prose_diff() {
+        assert_eq!(
+            prose_diff("A\nB\nC\n", "A\nX\nC\n"),
+            "```diff\n  A\n- B\n+ X\n  C\n```"
+        );
+        assert_eq!(prose_diff("", "New\n"), "```diff\n+ New\n```");
+        // The fence is longer than any backticks in the text.
+        assert_eq!(prose_diff("", "````\n"), "`````diff\n+ ````\n`````");
+    }
+
+    #[test]
+    fn test_doc_block_comments() {
+        let before = [
+            doc_block(1, 1, "Same\n"),
+            doc_block(3, 2, "Old\ntext\n"),
+            doc_block(6, 1, "Removed\n"),
+            doc_block(8, 1, "End\n"),
+        ];
+        let after = [
+            doc_block(1, 1, "Same\n"),
+            doc_block(3, 2, "New\ntext\n"),
+            doc_block(6, 1, "End\n"),
+            doc_block(9, 1, "Added\n"),
+        ];
+        assert_eq!(
+            doc_block_comments("a.py", &before, &after),
+            vec![
+                ReviewComment {
+                    path: "a.py".to_string(),
+                    start_line: Some(3),
+                    line: 4,
+                    side: Side::Right,
+                    body: "Changed doc block:\n\n```diff\n- Old\n+ New\n  text\n```".to_string(),
+                },
+                ReviewComment {
+                    path: "a.py".to_string(),
+                    start_line: None,
+                    line: 6,
+                    side: Side::Left,
+                    body: "Removed doc block:\n\n```diff\n- Removed\n```".to_string(),
+                },
+                ReviewComment {
+                    path: "a.py".to_string(),
+                    start_line: None,
+                    line: 9,
+                    side: Side::Right,
+                    body: "Added doc block:\n\n```diff\n+ Added\n```".to_string(),
+                },
+            ]
+        );
+        assert!(doc_block_comments("a.py", &before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_review_doc_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let commit = |contents: &str, message: &str| {
+            fs::write(temp_dir.path().join("a.py"), contents).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a.py")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            let parents: Vec<_> = parent.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        };
+        commit("# Intro\na = 1\n# Old text\nb = 2\n", "First");
+        // Changing only code produces no comments.
+        commit("# Intro\na = 10\n# Old text\nb = 2\n", "Second");
+        commit("# Intro\na = 10\n# New text\nb = 2\n", "Third");
+
+        let review = review_doc_blocks(temp_dir.path(), "HEAD~1", "HEAD").unwrap();
+        assert_eq!(
+            review.commit_id,
+            repo.head()
+                .unwrap()
+                .peel_to_commit()
+                .unwrap()
+                .id()
+                .to_string()
+        );
+        assert_eq!(
+            review.comments,
+            vec![ReviewComment {
+                path: "a.py".to_string(),
+                start_line: None,
+                line: 3,
+                side: Side::Right,
+                body: "Changed doc block:\n\n```diff\n- Old text\n+ New text\n```".to_string(),
+            }]
+        );
+        assert!(review_doc_blocks(temp_dir.path(), "HEAD~2", "HEAD~1")
+            .unwrap()
+            .comments
+            .is_empty());
+        assert!(review_doc_blocks(temp_dir.path(), "no-such-branch", "HEAD").is_err());
+        temp_dir.close().unwrap();
+    }
+}
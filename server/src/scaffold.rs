@@ -0,0 +1,434 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `scaffold.rs` -- Create a new project from a template
+///
+/// The `new <template> <dir>` command creates a CodeChat Editor project in
+/// `dir`: a table of contents, a project configuration, a readme, and an
+/// example source file. Each built-in template is named for the language of
+/// its example source file, such as `python`.
+///
+/// A user may add templates, or replace the built-in templates, by creating a
+/// directory named for the template in their templates directory (see
+/// `user_templates_dir`). Every file in this directory is copied to the new
+/// project.
+///
+/// In a template's text files, `{{project_name}}` is replaced by the name of
+/// the new project's directory. If an author or license is provided, a
+/// copyright and license header is added to the start of each source file, as
+/// a doc block; Markdown and JSON files, which lack comments, are unchanged.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ### Third-party
+use chrono::{Datelike, Local};
+
+// ### Local
+use crate::processing::{
+    find_file_lexer, project_config::ProjectConfig, snippets::markdown_to_doc_block,
+};
+
+// ## Globals
+/// The files shared by all built-in templates, as (path, contents) pairs.
+/// `{{example_file}}` is replaced by the name of the template's example source
+/// file.
+const COMMON_FILES: &[(&str, &str)] = &[
+    (
+        "toc.md",
+        "# {{project_name}}\n\n1.  [Readme](README.md)\n2.  [{{example_file}}]({{example_file}})\n",
+    ),
+    (
+        "README.md",
+        "# {{project_name}}\n\nThis is a CodeChat Editor project. Open any of its files in the CodeChat\nEditor to edit it; the [table of contents](toc.md) lists its files. Add each\nnew file to the table of contents.\n",
+    ),
+    (
+        "codechat.json",
+        "{\n    \"markdown\": {\n        \"admonitions\": true\n    }\n}\n",
+    ),
+];
+
+/// The built-in templates, as (name, example source file name, example source
+/// file contents).
+const BUILT_IN_TEMPLATES: &[(&str, &str, &str)] = &[
+    (
+        "c",
+        "main.c",
+        r#"// # {{project_name}}
+//
+// Comments like this one are doc blocks, written in Markdown; the code between
+// them is shown as code.
+#include <stdio.h>
+
+// ## Greetings
+//
+// Print a greeting.
+int main(void) {
+    printf("Hello, world!\n");
+    return 0;
+}
+"#,
+    ),
+    (
+        "cpp",
+        "main.cpp",
+        r#"// # {{project_name}}
+//
+// Comments like this one are doc blocks, written in Markdown; the code between
+// them is shown as code.
+#include <iostream>
+
+// ## Greetings
+//
+// Print a greeting.
+int main() {
+    std::cout << "Hello, world!" << std::endl;
+    return 0;
+}
+"#,
+    ),
+    (
+        "javascript",
+        "main.js",
+        r#"// # {{project_name}}
+//
+// Comments like this one are doc blocks, written in Markdown; the code between
+// them is shown as code.
+//
+// ## Greetings
+//
+// Return a greeting for `name`.
+const greet = (name) => `Hello, ${name}!`;
+
+console.log(greet("world"));
+"#,
+    ),
+    (
+        "python",
+        "main.py",
+        r#"# # {{project_name}}
+#
+# Comments like this one are doc blocks, written in Markdown; the code between
+# them is shown as code.
+#
+# ## Greetings
+#
+# Return a greeting for `name`.
+def greet(name):
+    return f"Hello, {name}!"
+
+
+if __name__ == "__main__":
+    print(greet("world"))
+"#,
+    ),
+    (
+        "rust",
+        "main.rs",
+        r#"// # {{project_name}}
+//
+// Comments like this one are doc blocks, written in Markdown; the code between
+// them is shown as code.
+//
+// ## Greetings
+//
+// Return a greeting for `name`.
+fn greet(name: &str) -> String {
+    format!("Hello, {name}!")
+}
+
+fn main() {
+    println!("{}", greet("world"));
+}
+"#,
+    ),
+];
+
+// ## Data structures
+/// Options which customize a new project.
+#[derive(Debug, Default)]
+pub struct ScaffoldOptions {
+    /// The copyright holder, added to each source file's header.
+    pub author: Option<String>,
+    /// The project's license, as an [SPDX identifier](https://spdx.org/licenses/)
+    /// such as `MIT`, added to each source file's header.
+    pub license: Option<String>,
+}
+
+// ## Code
+/// Return the directory containing the user's templates, if the OS provides a
+/// configuration directory.
+pub fn user_templates_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("codechat-editor").join("templates"))
+}
+
+/// Return the names of the available templates, sorted alphabetically.
+pub fn template_names(user_templates_dir: Option<&Path>) -> Vec<String> {
+    let mut names: Vec<String> = BUILT_IN_TEMPLATES
+        .iter()
+        .map(|(name, _, _)| name.to_string())
+        .collect();
+    if let Some(Ok(entries)) = user_templates_dir.map(fs::read_dir) {
+        names.extend(entries.filter_map(|entry| {
+            let entry = entry.ok()?;
+            entry
+                .path()
+                .is_dir()
+                .then(|| entry.file_name().to_string_lossy().to_string())
+        }));
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Create a new project in `dir` from the template named `template`, looking
+/// for user templates in `user_templates_dir`. `dir` must not exist or be
+/// empty. Return the paths of the files created.
+pub fn scaffold(
+    template: &str,
+    dir: &Path,
+    options: &ScaffoldOptions,
+    user_templates_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>, String> {
+    if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!(
+            "{} isn't empty; choose a new or empty directory.",
+            dir.display()
+        ));
+    }
+    let files = template_files(template, user_templates_dir)?;
+
+    let project_name = dir
+        .canonicalize()
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "New project".to_string());
+    let header = license_header(options);
+    let mut created = Vec::new();
+    for (relative_path, contents) in files {
+        let path = dir.join(&relative_path);
+        let contents = match String::from_utf8(contents) {
+            Ok(text) => {
+                let text = text.replace("{{project_name}}", &project_name);
+                add_header(&header, &relative_path, text).into_bytes()
+            }
+            // Copy binary files, such as images, unchanged.
+            Err(err) => err.into_bytes(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Unable to create {}: {err}.", parent.display()))?;
+        }
+        fs::write(&path, contents)
+            .map_err(|err| format!("Unable to write {}: {err}.", path.display()))?;
+        created.push(path);
+    }
+    Ok(created)
+}
+
+// Return the files of the provided template as (path relative to the project,
+// contents) pairs. A user template replaces a built-in template with the same
+// name.
+fn template_files(
+    template: &str,
+    user_templates_dir: Option<&Path>,
+) -> Result<Vec<(PathBuf, Vec<u8>)>, String> {
+    if let Some(template_dir) = user_templates_dir
+        .map(|user_templates_dir| user_templates_dir.join(template))
+        .filter(|template_dir| template_dir.is_dir())
+    {
+        let mut files = Vec::new();
+        read_dir_files(&template_dir, Path::new(""), &mut files)?;
+        return Ok(files);
+    }
+    let Some((_, example_file, example_contents)) = BUILT_IN_TEMPLATES
+        .iter()
+        .find(|(name, _, _)| *name == template)
+    else {
+        return Err(format!(
+            "Unknown template '{template}'. The available templates are: {}.",
+            template_names(user_templates_dir).join(", ")
+        ));
+    };
+    let mut files: Vec<_> = COMMON_FILES
+        .iter()
+        .map(|(path, contents)| {
+            (
+                PathBuf::from(path),
+                contents
+                    .replace("{{example_file}}", example_file)
+                    .into_bytes(),
+            )
+        })
+        .collect();
+    files.push((
+        PathBuf::from(example_file),
+        example_contents.as_bytes().to_vec(),
+    ));
+    Ok(files)
+}
+
+// Add every file in `dir` and its subdirectories to `files`, with paths
+// relative to the template's root, where `relative_dir` is `dir`'s path.
+fn read_dir_files(
+    dir: &Path,
+    relative_dir: &Path,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| format!("Unable to read {}: {err}.", dir.display()))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| format!("Unable to read {}: {err}.", dir.display()))?;
+        let path = entry.path();
+        let relative_path = relative_dir.join(entry.file_name());
+        if path.is_dir() {
+            read_dir_files(&path, &relative_path, files)?;
+        } else {
+            let contents = fs::read(&path)
+                .map_err(|err| format!("Unable to read {}: {err}.", path.display()))?;
+            files.push((relative_path, contents));
+        }
+    }
+    Ok(())
+}
+
+// Return the copyright and license header, as Markdown, or an empty string if
+// there's no author or license.
+fn license_header(options: &ScaffoldOptions) -> String {
+    let mut paragraphs = Vec::new();
+    if let Some(author) = &options.author {
+        paragraphs.push(format!("Copyright (C) {} {author}.", Local::now().year()));
+    }
+    if let Some(license) = &options.license {
+        paragraphs.push(format!("SPDX-License-Identifier: {license}"));
+    }
+    if paragraphs.is_empty() {
+        String::new()
+    } else {
+        paragraphs.join("\n\n") + "\n"
+    }
+}
+
+// Add the header as a doc block to the start of the provided file, if it's a
+// source file.
+fn add_header(header: &str, path: &Path, contents: String) -> String {
+    // JSON doesn't allow comments, though it's edited using the JSON5 lexer.
+    if header.is_empty() || path.extension().is_some_and(|ext| ext == "json") {
+        return contents;
+    }
+    match find_file_lexer("", path, &ProjectConfig::default()) {
+        Ok(lexer) if lexer.language_lexer.lexer_name.as_str() != "markdown" => {
+            match markdown_to_doc_block(header, lexer) {
+                Ok(header) => header + "\n" + &contents,
+                // This language has no comments.
+                Err(_) => contents,
+            }
+        }
+        // Markdown and other files have no header.
+        _ => contents,
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+    use chrono::{Datelike, Local};
+
+    use super::{scaffold, template_names, ScaffoldOptions};
+
+    #[test]
+    fn test_scaffold() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("my-project");
+        let options = ScaffoldOptions {
+            author: Some("Ada".to_string()),
+            license: Some("MIT".to_string()),
+        };
+        let created = scaffold("python", &dir, &options, None).unwrap();
+        assert_eq!(created.len(), 4);
+        assert_eq!(
+            fs::read_to_string(dir.join("toc.md")).unwrap(),
+            "# my-project\n\n1.  [Readme](README.md)\n2.  [main.py](main.py)\n"
+        );
+        let year = Local::now().year();
+        assert!(fs::read_to_string(dir.join("main.py"))
+            .unwrap()
+            .starts_with(&format!(
+                "# Copyright (C) {year} Ada.\n#\n# SPDX-License-Identifier: MIT\n\n# # my-project\n"
+            )));
+        // Markdown files have no header.
+        assert!(fs::read_to_string(dir.join("README.md"))
+            .unwrap()
+            .starts_with("# my-project\n"));
+
+        // A project can't be created in a non-empty directory.
+        assert!(scaffold("python", &dir, &options, None).is_err());
+        // Unknown templates are an error.
+        let err = scaffold("cobol", &temp_dir.path().join("x"), &options, None).unwrap_err();
+        assert!(err.contains("c, cpp, javascript, python, rust"));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_user_templates() {
+        let temp_dir = TempDir::new().unwrap();
+        let templates_dir = temp_dir.path().join("templates");
+        fs::create_dir_all(templates_dir.join("python/src")).unwrap();
+        fs::create_dir_all(templates_dir.join("notes")).unwrap();
+        fs::write(templates_dir.join("python/toc.md"), "# {{project_name}}\n").unwrap();
+        fs::write(templates_dir.join("python/src/lib.py"), "x = 1\n").unwrap();
+        fs::write(templates_dir.join("python/logo.bin"), [0xff, 0xfe]).unwrap();
+
+        assert_eq!(
+            template_names(Some(&templates_dir)),
+            vec!["c", "cpp", "javascript", "notes", "python", "rust"]
+        );
+
+        // A user template replaces the built-in template.
+        let dir = temp_dir.path().join("proj");
+        let options = ScaffoldOptions {
+            author: None,
+            license: Some("MIT".to_string()),
+        };
+        let mut created = scaffold("python", &dir, &options, Some(&templates_dir)).unwrap();
+        created.sort();
+        assert_eq!(
+            created,
+            vec![
+                dir.join("logo.bin"),
+                dir.join("src/lib.py"),
+                dir.join("toc.md")
+            ]
+        );
+        assert_eq!(fs::read_to_string(dir.join("toc.md")).unwrap(), "# proj\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("src/lib.py")).unwrap(),
+            "# SPDX-License-Identifier: MIT\n\nx = 1\n"
+        );
+        assert_eq!(fs::read(dir.join("logo.bin")).unwrap(), vec![0xff, 0xfe]);
+
+        temp_dir.close().unwrap();
+    }
+}
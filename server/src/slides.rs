@@ -0,0 +1,185 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `slides.rs` -- Show a file as a slide deck
+///
+/// Requesting a file with `?mode=slides` shows it as a deck of slides, so that
+/// a literate source file can double as lecture slides. The file's code and
+/// doc blocks are combined as when [exporting](export.rs), then split into
+/// slides: a thematic break
+/// (`---`) ends a slide, while a level 1 or 2 heading begins a new slide.
+/// Slides which would be empty are omitted.
+///
+/// The deck is a static page generated by the Server; a small script shows
+/// one slide at a time. The arrow keys, space, Page Up/Down, Home, and End
+/// move between slides; the URL's fragment (such as `#3`) gives the current
+/// slide. Printing the deck prints each slide on its own page.
+// ## Imports
+//
+// ### Third-party
+use indoc::formatdoc;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// ### Local
+use crate::{
+    export::codechat_for_web_to_html,
+    processing::{
+        escape::{escape, EscapeContext},
+        CodeChatForWeb,
+    },
+    webserver::inline_script,
+};
+
+// ## Globals
+lazy_static! {
+    /// Match a slide boundary: a thematic break (`<hr>`, including any newline
+    /// which follows it), or the start of a level 1 or 2 heading.
+    static ref SLIDE_BREAK_REGEX: Regex =
+        Regex::new(r"(?i)<hr\s*/?>\n?|<h[12][\s>]").unwrap();
+}
+
+/// The styles for a slide deck.
+const SLIDES_STYLE: &str = r#"
+body.CodeChat-slides { margin: 0; font-size: 150%; }
+.CodeChat-slide { box-sizing: border-box; min-height: 100vh; padding: 4vh 6vw; }
+.CodeChat-slide pre { font-size: 80%; }
+#CodeChat-slide-number { position: fixed; right: 1em; bottom: 1em; opacity: 0.6; }
+@media print {
+    .CodeChat-slide[hidden] { display: block; }
+    .CodeChat-slide { min-height: auto; break-after: page; }
+    #CodeChat-slide-number { display: none; }
+}
+"#;
+
+/// The script which moves between slides.
+const SLIDES_SCRIPT: &str = r#"
+const slides = document.querySelectorAll(".CodeChat-slide");
+let current = 0;
+const show = (index) => {
+    current = Math.max(0, Math.min(slides.length - 1, index));
+    slides.forEach((slide, i) => (slide.hidden = i !== current));
+    document.getElementById("CodeChat-slide-number").textContent =
+        `${current + 1} / ${slides.length}`;
+    history.replaceState(null, "", `#${current + 1}`);
+};
+document.addEventListener("keydown", (event) => {
+    if (["ArrowRight", "ArrowDown", "PageDown", " "].includes(event.key)) {
+        show(current + 1);
+    } else if (["ArrowLeft", "ArrowUp", "PageUp"].includes(event.key)) {
+        show(current - 1);
+    } else if (event.key === "Home") {
+        show(0);
+    } else if (event.key === "End") {
+        show(slides.length - 1);
+    } else {
+        return;
+    }
+    event.preventDefault();
+});
+show((parseInt(location.hash.slice(1)) || 1) - 1);
+"#;
+
+// ## Code
+/// Split the provided HTML into slides.
+pub fn split_slides(html: &str) -> Vec<String> {
+    let mut slides = Vec::new();
+    let mut start = 0;
+    for slide_break in SLIDE_BREAK_REGEX.find_iter(html) {
+        slides.push(&html[start..slide_break.start()]);
+        // A heading belongs to the slide it begins; a thematic break belongs
+        // to neither slide.
+        start = if slide_break.as_str()[..3].eq_ignore_ascii_case("<hr") {
+            slide_break.end()
+        } else {
+            slide_break.start()
+        };
+    }
+    slides.push(&html[start..]);
+    slides
+        .into_iter()
+        .filter(|slide| !slide.trim().is_empty())
+        .map(|slide| slide.to_string())
+        .collect()
+}
+
+/// Return a web page which shows the provided file as a slide deck. `title`
+/// is the page's title; `head` is additional HTML to place in the page's
+/// `<head>`.
+pub fn codechat_for_web_to_slides(
+    codechat_for_web: &CodeChatForWeb,
+    title: &str,
+    head: &str,
+) -> String {
+    let slides: String = split_slides(&codechat_for_web_to_html(codechat_for_web))
+        .into_iter()
+        .map(|slide| format!("<section class=\"CodeChat-slide\">\n{slide}</section>\n"))
+        .collect();
+    formatdoc!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <title>{} - The CodeChat Editor</title>
+                {head}
+                <style>{SLIDES_STYLE}</style>
+            </head>
+            <body class="CodeChat-theme-light CodeChat-slides">
+                <main>
+        {slides}
+                </main>
+                <div id="CodeChat-slide-number" aria-live="polite"></div>
+                {slides_script}
+            </body>
+        </html>
+        "#,
+        escape(title, EscapeContext::Text),
+        slides_script = inline_script("", SLIDES_SCRIPT)
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::split_slides;
+
+    #[test]
+    fn test_split_slides() {
+        assert_eq!(
+            split_slides(
+                "<h1>Intro</h1>\n<p>A</p>\n<hr>\n<p>B</p>\n<h2 id=\"c\">C</h2>\n<h3>D</h3>\n"
+            ),
+            vec![
+                "<h1>Intro</h1>\n<p>A</p>\n",
+                "<p>B</p>\n",
+                "<h2 id=\"c\">C</h2>\n<h3>D</h3>\n"
+            ]
+        );
+        // Empty slides are omitted, including those between a break and a
+        // heading.
+        assert_eq!(
+            split_slides("<hr />\n<p>A</p>\n<hr>\n<h1>B</h1>\n<hr>\n"),
+            vec!["<p>A</p>\n", "<h1>B</h1>\n"]
+        );
+        // Other tags beginning with `h` aren't slide breaks.
+        assert_eq!(
+            split_slides("<header>A</header><html><h4>B</h4>"),
+            vec!["<header>A</header><html><h4>B</h4>"]
+        );
+        assert!(split_slides("").is_empty());
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `tangle.rs` -- Generate source files from named code chunks
+///
+/// Classic literate programming, in the style of
+/// [noweb](https://www.cs.tufts.edu/~nr/noweb/), presents a program in the
+/// order best for explaining it, then "tangles" its pieces into source files.
+/// In a doc block, a fenced code block whose first line is `<<name>>=` defines
+/// a named chunk:
+///
+/// ````markdown
+/// ```python
+/// <<src/hello.py>>=
+/// def main():
+///     <<Greet the user>>
+/// ```
+/// ````
+///
+/// A line containing only `<<name>>` is replaced by the chunk of that name,
+/// indented to match the reference. Defining a chunk again appends to it. Each
+/// chunk which no other chunk refers to is a root: the `tangle` command writes
+/// it to the file it names, relative to the output directory. Chunks are
+/// collected from the files linked to by a project's table of contents, in the
+/// order they're linked.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use regex::Regex;
+
+// ### Local
+use crate::processing::{
+    doc_blocks_with_lines, find_file_lexer, find_path_to_toc, project_config::toc_files,
+    project_config_for_file,
+};
+
+// ## Globals
+lazy_static! {
+    /// Match the first line of a code block which defines a chunk.
+    static ref CHUNK_DEFINITION_REGEX: Regex = Regex::new(r"^<<(.+)>>=\s*$").unwrap();
+    /// Match a line which refers to a chunk, capturing its indent.
+    static ref CHUNK_REFERENCE_REGEX: Regex = Regex::new(r"^(\s*)<<(.+)>>\s*$").unwrap();
+}
+
+// ## Data structures
+/// The chunks of a program, in the order they're first defined.
+#[derive(Debug, Default)]
+pub struct Chunks {
+    names: Vec<String>,
+    bodies: HashMap<String, String>,
+}
+
+impl Chunks {
+    /// Add the chunks defined in the provided Markdown.
+    pub fn add_markdown(&mut self, markdown: &str) {
+        let mut code_block: Option<String> = None;
+        for event in Parser::new(markdown) {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => code_block = Some(String::new()),
+                Event::Text(text) => {
+                    if let Some(code_block) = &mut code_block {
+                        code_block.push_str(&text);
+                    }
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some(code_block) = code_block.take() {
+                        self.add_code_block(&code_block);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Add the chunk defined by this code block, if it defines one.
+    fn add_code_block(&mut self, code_block: &str) {
+        let (first_line, body) = code_block.split_once('\n').unwrap_or((code_block, ""));
+        let Some(captures) = CHUNK_DEFINITION_REGEX.captures(first_line) else {
+            return;
+        };
+        let name = captures[1].trim().to_string();
+        if !self.bodies.contains_key(&name) {
+            self.names.push(name.clone());
+        }
+        self.bodies.entry(name).or_default().push_str(body);
+    }
+
+    /// Return the names of the chunks which no other chunk refers to.
+    pub fn roots(&self) -> Vec<&str> {
+        let referenced: HashSet<&str> = self
+            .bodies
+            .values()
+            .flat_map(|body| body.lines())
+            .filter_map(|line| CHUNK_REFERENCE_REGEX.captures(line))
+            .filter_map(|captures| captures.get(2))
+            .map(|name| name.as_str().trim())
+            .collect();
+        self.names
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !referenced.contains(name))
+            .collect()
+    }
+
+    /// Return the chunk `name`, with all references it contains replaced by
+    /// the chunks they refer to.
+    pub fn expand(&self, name: &str) -> Result<String, String> {
+        let mut expanded = String::new();
+        self.expand_into(name, "", &mut Vec::new(), &mut expanded)?;
+        Ok(expanded)
+    }
+
+    fn expand_into<'a>(
+        &'a self,
+        name: &'a str,
+        indent: &str,
+        // The chunks being expanded, used to detect a chunk which refers to
+        // itself.
+        stack: &mut Vec<&'a str>,
+        expanded: &mut String,
+    ) -> Result<(), String> {
+        let Some((name, body)) = self.bodies.get_key_value(name) else {
+            return Err(match stack.last() {
+                Some(parent) => format!("Chunk <<{parent}>> refers to undefined chunk <<{name}>>."),
+                None => format!("Chunk <<{name}>> isn't defined."),
+            });
+        };
+        if stack.contains(&name.as_str()) {
+            return Err(format!(
+                "Chunk <<{name}>> refers to itself: {} -> {name}.",
+                stack.join(" -> ")
+            ));
+        }
+        stack.push(name);
+        for line in body.lines() {
+            match CHUNK_REFERENCE_REGEX.captures(line) {
+                Some(captures) => self.expand_into(
+                    // Borrow the name from the body, rather than from
+                    // `captures`, so it lives as long as `stack`.
+                    captures.get(2).map_or("", |name| name.as_str().trim()),
+                    &format!("{indent}{}", &captures[1]),
+                    stack,
+                    expanded,
+                )?,
+                None => {
+                    // Don't indent blank lines.
+                    if !line.is_empty() {
+                        expanded.push_str(indent);
+                    }
+                    expanded.push_str(line);
+                    expanded.push('\n');
+                }
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+// ## Code
+/// Tangle the project whose table of contents is `toc_path`, writing each root
+/// chunk to the file it names in `output_dir`. Return the paths of the files
+/// written; files whose contents are unchanged aren't rewritten.
+pub fn tangle_project(toc_path: &Path, output_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut chunks = Chunks::default();
+    for file_path in toc_files(toc_path)? {
+        add_file_chunks(&mut chunks, &file_path)?;
+    }
+    tangle(&chunks, output_dir)
+}
+
+/// Write each root chunk to the file it names in `output_dir`, returning the
+/// paths of the files written.
+pub fn tangle(chunks: &Chunks, output_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut written = Vec::new();
+    for root in chunks.roots() {
+        // Keep the generated files inside the output directory.
+        let root_path = Path::new(root);
+        if !root_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!(
+                "Root chunk <<{root}>> must name a relative path inside the output directory."
+            ));
+        }
+        let contents = chunks.expand(root)?;
+        let file_path = output_dir.join(root_path);
+        if fs::read_to_string(&file_path).is_ok_and(|old_contents| old_contents == contents) {
+            continue;
+        }
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Unable to create {parent:?}: {err}."))?;
+        }
+        fs::write(&file_path, contents)
+            .map_err(|err| format!("Unable to write {file_path:?}: {err}."))?;
+        written.push(file_path);
+    }
+    Ok(written)
+}
+
+// Add the chunks defined in the doc blocks of the provided file. Skip files
+// the CodeChat Editor doesn't support, such as images.
+fn add_file_chunks(chunks: &mut Chunks, file_path: &Path) -> Result<(), String> {
+    let Ok(file_contents) = fs::read_to_string(file_path) else {
+        return Ok(());
+    };
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    let Ok(lexer) = find_file_lexer(&file_contents, file_path, &project_config) else {
+        return Ok(());
+    };
+    for (_, doc_block) in doc_blocks_with_lines(&file_contents, lexer) {
+        chunks.add_markdown(&doc_block.contents);
+    }
+    Ok(())
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+    use indoc::indoc;
+
+    use super::{tangle, tangle_project, Chunks};
+
+    fn chunks(markdown: &str) -> Chunks {
+        let mut chunks = Chunks::default();
+        chunks.add_markdown(markdown);
+        chunks
+    }
+
+    #[test]
+    fn test_expand() {
+        let chunks = chunks(indoc!(
+            "
+            Some text.
+
+            ```python
+            <<hello.py>>=
+            def main():
+                <<Greet>>
+
+            main()
+            ```
+
+            A code block which isn't a chunk:
+
+            ```python
+            print('Ignored')
+            ```
+
+            ```python
+            <<Greet>>=
+            print('Hello')
+            ```
+
+            Chunks may be continued.
+
+            ```python
+            <<Greet>>=
+            print('World')
+            ```
+            "
+        ));
+        assert_eq!(chunks.roots(), vec!["hello.py"]);
+        assert_eq!(
+            chunks.expand("hello.py").unwrap(),
+            "def main():\n    print('Hello')\n    print('World')\n\nmain()\n"
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let chunks = chunks(indoc!(
+            "
+            ```
+            <<a>>=
+            <<b>>
+            ```
+
+            ```
+            <<b>>=
+            <<a>>
+            ```
+
+            ```
+            <<c>>=
+            <<missing>>
+            ```
+            "
+        ));
+        assert_eq!(
+            chunks.expand("a").unwrap_err(),
+            "Chunk <<a>> refers to itself: a -> b -> a."
+        );
+        assert_eq!(
+            chunks.expand("c").unwrap_err(),
+            "Chunk <<c>> refers to undefined chunk <<missing>>."
+        );
+        assert_eq!(
+            chunks.expand("d").unwrap_err(),
+            "Chunk <<d>> isn't defined."
+        );
+    }
+
+    #[test]
+    fn test_tangle() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().join("out");
+        let a_chunks = chunks("```\n<<src/a.txt>>=\nA\n```\n");
+        assert_eq!(
+            tangle(&a_chunks, &output_dir).unwrap(),
+            vec![output_dir.join("src/a.txt")]
+        );
+        assert_eq!(
+            fs::read_to_string(output_dir.join("src/a.txt")).unwrap(),
+            "A\n"
+        );
+        // Unchanged files aren't rewritten.
+        assert!(tangle(&a_chunks, &output_dir).unwrap().is_empty());
+
+        // Roots can't write outside the output directory.
+        assert!(tangle(&chunks("```\n<<../a.txt>>=\nA\n```\n"), &output_dir).is_err());
+        assert!(tangle(&chunks("```\n<</a.txt>>=\nA\n```\n"), &output_dir).is_err());
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_tangle_project() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("toc.md"),
+            "[Intro](intro.md)\n[Code](code.py)\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("intro.md"),
+            "# Intro\n\n```\n<<out.py>>=\n<<Body>>\n```\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("code.py"),
+            "# ```\n# <<Body>>=\n# x = 1\n# ```\nprint('Not tangled')\n",
+        )
+        .unwrap();
+        tangle_project(&temp_dir.path().join("toc.md"), temp_dir.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("out.py")).unwrap(),
+            "x = 1\n"
+        );
+        temp_dir.close().unwrap();
+    }
+}
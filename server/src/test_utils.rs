@@ -32,6 +32,7 @@ use assert_fs::fixture::PathCopy;
 use assert_fs::TempDir;
 use assertables::assert_le;
 use log::Level;
+use regex::Regex;
 
 // ### Local
 use crate::testing_logger;
@@ -192,3 +193,58 @@ pub fn check_logger_errors(num_expected_errors: usize) {
 pub fn stringit(s: &str) -> String {
     s.to_string()
 }
+
+// Check a web page generated by the Server for common accessibility problems,
+// returning a description of each problem found. This looks for:
+//
+// - A language and a title for the page.
+// - Exactly one main landmark, which a skip link may point to.
+// - Images without alt text and frames without a title.
+// - Headings which skip a level, such as an `<h3>` following an `<h1>`.
+pub fn accessibility_problems(html: &str) -> Vec<String> {
+    let mut problems = vec![];
+    if !html.contains("<html lang=\"") {
+        problems.push("The page doesn't provide its language.".to_string());
+    }
+    if !Regex::new(r"<title>[^<]+</title>").unwrap().is_match(html) {
+        problems.push("The page has no title.".to_string());
+    }
+    let main_count = html.matches("<main").count() + html.matches("role=\"main\"").count();
+    if main_count != 1 {
+        problems.push(format!(
+            "The page has {main_count} main landmarks; it should have one."
+        ));
+    }
+    for skip_link in Regex::new(r##"<a class="CodeChat-skip-link" href="#([^"]+)""##)
+        .unwrap()
+        .captures_iter(html)
+    {
+        if !html.contains(&format!("id=\"{}\"", &skip_link[1])) {
+            problems.push(format!(
+                "The skip link's target {} doesn't exist.",
+                &skip_link[1]
+            ));
+        }
+    }
+    for img in Regex::new(r"<img\b[^>]*>").unwrap().find_iter(html) {
+        if !img.as_str().contains(" alt=") {
+            problems.push(format!("{} has no alt text.", img.as_str()));
+        }
+    }
+    for iframe in Regex::new(r"<iframe\b[^>]*>").unwrap().find_iter(html) {
+        if !iframe.as_str().contains(" title=") {
+            problems.push(format!("{} has no title.", iframe.as_str()));
+        }
+    }
+    let mut previous_level = 0;
+    for heading in Regex::new(r"<h([1-6])\b").unwrap().captures_iter(html) {
+        let level: usize = heading[1].parse().unwrap();
+        if level > previous_level + 1 {
+            problems.push(format!(
+                "A level {level} heading follows a level {previous_level} heading."
+            ));
+        }
+        previous_level = level;
+    }
+    problems
+}
@@ -0,0 +1,322 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `tunnel.rs` -- Reach a Server on a remote machine over SSH
+///
+/// The Server only listens on the loopback address, so a Server running on a
+/// remote machine (for example, one reached using VSCode's Remote - SSH
+/// extension) can't be reached directly from a browser on the local machine.
+/// An SSH tunnel forwards a local port to the Server's port on the remote
+/// machine instead. The `tunnel` command, run on the local machine, prints the
+/// `ssh` command which does this or, with `--spawn`, runs it then pings the
+/// Server through the tunnel to verify that it works.
+///
+/// Requests through the tunnel reach the Server from the loopback address, as
+/// if they came from the remote machine itself, so the Server can't tell them
+/// apart from its other users. A Server started with `serve --tunnel` therefore
+/// mints a one-time token, printed when it starts, and requires it of every
+/// request (except pings), including those from the loopback address. The first
+/// page opened must provide this token in its `token` query parameter; the
+/// Server then invalidates the token, so that a copy of this URL is useless,
+/// and instead stores a session in a cookie. A session ends after a time (see
+/// [`Sessions`](webserver/lan_share.rs)) or on logout; the `token` command
+/// mints a new token, for another browser or to start a new session. A reverse proxy (see
+/// `serve --base-path`) has the same problem, so the Server then does this
+/// too. Pass the token to the `tunnel` command, which prints the URL to open.
+///
+/// Programs on the remote machine, such as the `stop` command and the VSCode
+/// extension, aren't browsers; instead, they prove they run on this machine by
+/// sending the local secret, which the Server writes to a file that only its
+/// user may read (see `local_secret_path`), in the `LOCAL_SECRET_HEADER`
+/// header.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs,
+    io::Write,
+    net::TcpListener,
+    path::PathBuf,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+
+// ### Local
+use crate::webserver::{is_token_match, mint_token, ping_server, Sessions, IP_ADDRESS};
+
+// ## Globals
+/// How long to wait for the tunnel to reach the Server. This includes the time
+/// spent logging in to the remote machine, which may ask for a password.
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// True if browsers reach the Server through an SSH tunnel.
+static TUNNEL: AtomicBool = AtomicBool::new(false);
+
+/// The one-time token which a browser exchanges for a session; `None` once
+/// it's used, or if the Server doesn't require it.
+static ACCESS_TOKEN: RwLock<Option<String>> = RwLock::new(None);
+
+lazy_static! {
+    /// The sessions created by using a one-time token.
+    static ref SESSIONS: Sessions = Sessions::default();
+}
+
+/// The secret which programs on this machine provide, or `None` if the Server
+/// doesn't require it.
+static LOCAL_SECRET: RwLock<Option<String>> = RwLock::new(None);
+
+/// The query parameter providing the one-time token.
+pub const TOKEN_NAME: &str = "token";
+
+/// The cookie providing the session.
+pub const SESSION_NAME: &str = "session";
+
+/// The header providing the local secret.
+pub const LOCAL_SECRET_HEADER: &str = "X-CodeChat-Local-Secret";
+
+// ## Code
+/// Specify if browsers reach the Server through an SSH tunnel.
+pub fn set_tunnel(enabled: bool) {
+    TUNNEL.store(enabled, Ordering::Relaxed);
+}
+
+/// Return true if browsers reach the Server through an SSH tunnel.
+pub fn is_tunneled() -> bool {
+    TUNNEL.load(Ordering::Relaxed)
+}
+
+/// Mint a new one-time token, replacing any unused token, then return it.
+/// Existing sessions continue.
+pub fn start_tunnel_access() -> Result<String, String> {
+    let token = mint_token()?;
+    *ACCESS_TOKEN.write().unwrap() = Some(token.clone());
+    Ok(token)
+}
+
+/// If `token` is the one-time token, invalidate it, then return a new session
+/// for the browser which provided it.
+pub fn redeem_token(token: &str) -> Option<String> {
+    let mut access_token = ACCESS_TOKEN.write().unwrap();
+    match &*access_token {
+        Some(expected_token) if is_token_match(token, expected_token) => {
+            // Without a session, the browser couldn't use the token anyway.
+            let session = SESSIONS.start().ok()?;
+            *access_token = None;
+            Some(session)
+        }
+        _ => None,
+    }
+}
+
+/// Return true if `session` is an unexpired session created by a one-time
+/// token; if so, restart its idle timeout.
+pub fn is_session(session: &str) -> bool {
+    SESSIONS.is_valid(session)
+}
+
+/// End `session`, if it was created by a one-time token.
+pub fn end_session(session: &str) {
+    SESSIONS.end(session);
+}
+
+/// Return the path to the file holding the local secret of the Server using
+/// `port`, if the OS provides a configuration directory.
+pub fn local_secret_path(port: u16) -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| {
+        config_dir
+            .join("codechat-editor")
+            .join(format!("local-secret-{port}"))
+    })
+}
+
+/// Mint the local secret for the Server using `port`, then write it to the
+/// file given by `local_secret_path`, which only this user may read.
+pub fn start_local_secret(port: u16) -> Result<(), String> {
+    let path = local_secret_path(port)
+        .ok_or("Unable to find the configuration directory for the local secret.")?;
+    let secret = mint_token()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("Unable to create {}: {err}.", parent.display()))?;
+    }
+    // Remove any old file, which may be readable by others, then create it.
+    let _ = fs::remove_file(&path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(secret.as_bytes()))
+        .map_err(|err| format!("Unable to write {}: {err}.", path.display()))?;
+    *LOCAL_SECRET.write().unwrap() = Some(secret);
+    Ok(())
+}
+
+/// Return the local secret of the Server using `port`, if it requires one.
+pub fn read_local_secret(port: u16) -> Option<String> {
+    local_secret_path(port)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|secret| secret.trim().to_string())
+}
+
+/// Return true if `secret` is the Server's local secret.
+pub fn is_local_secret(secret: &str) -> bool {
+    LOCAL_SECRET
+        .read()
+        .unwrap()
+        .as_deref()
+        .is_some_and(|expected_secret| is_token_match(secret, expected_secret))
+}
+
+/// Return the URL which a browser on this machine opens to reach the Server
+/// through the tunnel, including the one-time `token` if provided.
+pub fn tunnel_url(local_port: u16, token: Option<&str>) -> String {
+    let query = token.map_or(String::new(), |token| format!("?{TOKEN_NAME}={token}"));
+    format!("http://{IP_ADDRESS}:{local_port}/{query}")
+}
+
+/// Return the arguments of the `ssh` command which forwards `local_port` on
+/// this machine to the Server listening on `remote_port` of `host`.
+pub fn ssh_args(host: &str, local_port: u16, remote_port: u16) -> Vec<String> {
+    vec![
+        "ssh".to_string(),
+        // Only forward the port; don't run a remote command.
+        "-N".to_string(),
+        "-L".to_string(),
+        format!("{local_port}:{IP_ADDRESS}:{remote_port}"),
+        host.to_string(),
+    ]
+}
+
+/// Run the `ssh` command from `ssh_args`, verify that the Server responds
+/// through the tunnel, then wait until the tunnel closes. `token` is the
+/// Server's one-time token, if provided.
+pub fn run_tunnel(
+    host: &str,
+    local_port: u16,
+    remote_port: u16,
+    token: Option<&str>,
+) -> Result<(), String> {
+    // Otherwise, a ping could reach another program using this port instead
+    // of the tunnel.
+    TcpListener::bind((IP_ADDRESS, local_port))
+        .map_err(|err| format!("Local port {local_port} is unavailable: {err}."))?;
+    let args = ssh_args(host, local_port, remote_port);
+    // Inherit stdin and stdout, so that `ssh` can ask for a password.
+    let mut process = Command::new(&args[0])
+        .args(&args[1..])
+        .spawn()
+        .map_err(|err| format!("Unable to run ssh: {err}."))?;
+
+    let start = Instant::now();
+    loop {
+        if ping_server(local_port, 1).is_ok() {
+            break;
+        }
+        if let Some(status) = process
+            .try_wait()
+            .map_err(|err| format!("Unable to check on ssh: {err}."))?
+        {
+            return Err(format!("ssh exited before the tunnel worked: {status}."));
+        }
+        if start.elapsed() > TUNNEL_TIMEOUT {
+            let _ = process.kill();
+            return Err(format!(
+                "No CodeChat Editor Server responded through the tunnel after {} seconds. Is it running on {host} using port {remote_port}?",
+                TUNNEL_TIMEOUT.as_secs()
+            ));
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    println!(
+        "Tunnel verified. Open {} in a browser; press Ctrl+C to close the tunnel.",
+        tunnel_url(local_port, token)
+    );
+
+    let status = process
+        .wait()
+        .map_err(|err| format!("Unable to wait for ssh: {err}."))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("ssh exited: {status}."))
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{
+        end_session, is_local_secret, is_session, redeem_token, ssh_args, start_tunnel_access,
+        tunnel_url, ACCESS_TOKEN, LOCAL_SECRET,
+    };
+
+    #[test]
+    fn test_ssh_args() {
+        assert_eq!(
+            ssh_args("user@example.com", 9000, 8080),
+            vec!["ssh", "-N", "-L", "9000:127.0.0.1:8080", "user@example.com"]
+        );
+    }
+
+    #[test]
+    fn test_tunnel_url() {
+        assert_eq!(tunnel_url(9000, None), "http://127.0.0.1:9000/");
+        assert_eq!(
+            tunnel_url(9000, Some("abc")),
+            "http://127.0.0.1:9000/?token=abc"
+        );
+    }
+
+    #[test]
+    fn test_redeem_token() {
+        let token = start_tunnel_access().unwrap();
+        assert!(redeem_token("abc").is_none());
+        let session = redeem_token(&token).unwrap();
+        assert!(is_session(&session));
+        assert!(!is_session(&token));
+        // The token works only once.
+        assert!(ACCESS_TOKEN.read().unwrap().is_none());
+        assert!(redeem_token(&token).is_none());
+        assert!(is_session(&session));
+        // A new token starts another session, leaving this one.
+        let token = start_tunnel_access().unwrap();
+        let session2 = redeem_token(&token).unwrap();
+        assert!(is_session(&session2));
+        assert!(is_session(&session));
+        // Logging out ends only that session.
+        end_session(&session);
+        assert!(!is_session(&session));
+        assert!(is_session(&session2));
+    }
+
+    #[test]
+    fn test_is_local_secret() {
+        assert!(!is_local_secret("abc"));
+        *LOCAL_SECRET.write().unwrap() = Some("abc".to_string());
+        assert!(is_local_secret("abc"));
+        assert!(!is_local_secret("abd"));
+        assert!(!is_local_secret(""));
+    }
+}
@@ -15,21 +15,45 @@
 // [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
 /// # `webserver.rs` -- Serve CodeChat Editor Client webpages
 // ## Submodules
+mod base_path;
+mod cache_control;
+mod capabilities;
+mod classroom;
+mod csp;
+mod events;
+mod external;
 mod filewatcher;
+mod keymap;
+mod lan_share;
+mod log_forward;
+mod open_files;
+mod outside_project;
+mod preferences;
+mod proxy;
+mod revision;
+mod sandbox;
+mod scroll;
 #[cfg(test)]
 pub mod tests;
+mod tls;
+mod todos;
+mod translation_queue;
 mod vscode;
 
 // ## Imports
 //
 // ### Standard library
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     env, fs,
     path::{self, Path, PathBuf, MAIN_SEPARATOR_STR},
     str::FromStr,
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 // ### Third-party
@@ -38,14 +62,18 @@ use actix_web::{
     dev::{ServerHandle, ServiceFactory, ServiceRequest},
     error::Error,
     get,
-    http::header::ContentType,
-    web, App, HttpRequest, HttpResponse, HttpServer,
+    http::{
+        header::{self, ContentType},
+        KeepAlive,
+    },
+    middleware::from_fn,
+    post, web, App, HttpRequest, HttpResponse, HttpServer,
 };
 use actix_ws::AggregatedMessage;
 use bytes::Bytes;
 use dunce::simplified;
 use futures_util::StreamExt;
-use indoc::{formatdoc, indoc};
+use indoc::formatdoc;
 use lazy_static::lazy_static;
 use log::{error, info, warn, LevelFilter};
 use log4rs;
@@ -72,13 +100,63 @@ use vscode::{
 
 // ### Local
 //use crate::capture::EventCapture;
+use crate::export::{codechat_for_web_to_html, export_file, export_project, ExportFormat};
+use crate::lexer::{source_lexer_explained, LexerTraceEvent};
+use crate::mobile::{codechat_for_web_to_mobile, is_mobile_user_agent};
+use crate::perf::{self, Operation};
 use crate::processing::{
-    source_to_codechat_for_web_string, CodeChatForWeb, TranslationResultsString,
+    cache::persist_if_idle,
+    conditions::set_file_conditions,
+    copy_markdown::doc_blocks_to_markdown,
+    definitions::Definition,
+    diagnostics::{check_file, Diagnostic},
+    escape::{escape, EscapeContext},
+    find_file_lexer, find_path_to_toc,
+    media::is_media_file,
+    outline::{outline, OutlineEntry},
+    project_config::{project_root, RenderTarget, TexMacro},
+    project_config_for_file,
+    snippets::{snippet, snippet_names, Snippet},
+    source_to_codechat_for_web_string,
+    workspace::WorkspaceRoot,
+    CodeChatForWeb, CodeMirrorDiff, TranslationResultsString,
 };
+use crate::slides::codechat_for_web_to_slides;
+use crate::tunnel::{
+    is_local_secret, is_tunneled, start_local_secret, start_tunnel_access, LOCAL_SECRET_HEADER,
+    TOKEN_NAME,
+};
+pub use base_path::set_base_path;
+use base_path::{base_path, strip_base_path, with_base_path};
+use cache_control::cache_control;
+use capabilities::{capabilities, Capabilities};
+pub use capabilities::{set_disabled_features, Feature};
+pub use classroom::set_instructor_token;
+use classroom::{classroom_endpoint, classroom_fs_endpoint, classroom_websocket, Classroom};
+use csp::{content_security_policy, external_script};
+pub use csp::{inline_script, set_frame_ancestors};
+use events::ServerEvent;
+use external::external_endpoint;
 use filewatcher::{
     filewatcher_browser_endpoint, filewatcher_client_endpoint, filewatcher_root_fs_redirect,
     filewatcher_websocket,
 };
+use keymap::{keymap, keymap_tag};
+use lan_share::{is_lan_sharing, lan_access, lan_url, logout, qr_code, start_lan_sharing};
+pub use lan_share::{is_token_match, mint_token, set_share_lan, Sessions};
+use log_forward::LogRecord;
+use outside_project::{outside_project, refused_message};
+pub use outside_project::{set_outside_project_policy, OutsideProjectPolicy};
+use preferences::{load_preferences, update_preferences, Preferences};
+use proxy::proxy_endpoint;
+pub use proxy::set_proxy_hosts;
+use revision::revision_endpoint;
+use sandbox::iframe_attributes;
+pub use sandbox::{set_iframe_settings, IframeSettings};
+use scroll::ScrollPosition;
+use tls::{load_server_config, tls_settings};
+pub use tls::{set_tls, TlsSettings};
+use todos::{todo_events_endpoint, todos_endpoint};
 
 // ## Data structures
 //
@@ -100,6 +178,10 @@ struct ProcessingTaskHttpRequest {
     file_path: PathBuf,
     /// True if this file is a TOC.
     is_toc: bool,
+    /// True if this file should be shown as a slide deck.
+    is_slides: bool,
+    /// True if this file should be shown using the read-only mobile layout.
+    is_mobile: bool,
     /// True if test mode is enabled.
     is_test_mode: bool,
     /// A queue to send the response back to the HTTP task.
@@ -124,6 +206,12 @@ enum SimpleHttpResponse {
 
 /// Define the data structure used to pass data between the CodeChat Editor
 /// Client, the IDE, and the CodeChat Editor Server.
+///
+/// Messages are delivered in order: the Client receives messages from the IDE
+/// in the order the IDE sent them, and vice versa. (The Server drops an IDE
+/// `Update` superseded by a later `Update` to the same file, replying to the
+/// IDE itself.) `Result` messages reply to earlier messages, so they may
+/// interleave with other messages; match them to their request using `id`.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 struct EditorMessage {
     /// A value unique to this message; it's used to report results
@@ -141,16 +229,106 @@ enum EditorMessageContents {
     /// This sends an update; any missing fields are unchanged. Valid
     /// destinations: IDE, Client.
     Update(UpdateMessageContents),
-    /// Specify the current file to edit. Valid destinations: IDE, Client.
-    CurrentFile(String),
+    /// Specify the current file to edit, and optionally where to show it.
+    /// Valid destinations: IDE, Client.
+    CurrentFile(CurrentFileContents),
+    /// Select the lexer used for a file, overriding the lexer chosen by its
+    /// extension and contents. If this is the current file, the Server then
+    /// reloads it in the Client. Valid destinations: Server.
+    SetFileLanguage(SetFileLanguageContents),
+    /// Request the outline of the provided file: the headings in its doc
+    /// blocks, found when the Server last translated it. The `Result` provides
+    /// these headings; it's empty if the Server hasn't translated this file.
+    /// Valid destinations: Server.
+    Outline(String),
+    /// List the snippets available when editing the provided file; the
+    /// `Result` provides their names. Valid destinations: Server.
+    ListSnippets(String),
+    /// Request the named snippet, prepared for insertion into the provided
+    /// file. The `Result` provides both its source code, for the IDE, and its
+    /// HTML, for the Client. Valid destinations: Server.
+    InsertSnippet(InsertSnippetContents),
+    /// Request the user's preferences, or, if provided, update each preference
+    /// given. The `Result` provides the user's preferences. Valid
+    /// destinations: Server.
+    Preferences(Option<Preferences>),
+    /// Report the folded (collapsed) regions of a file, replacing those
+    /// previously reported. The Server records these, then forwards them to
+    /// the IDE or Client; after sending the Client a file's contents, it also
+    /// sends that file's folded regions, so that they survive reloading the
+    /// Client. Valid destinations: IDE, Client.
+    FoldRanges(FoldRangesContents),
+
+    // #### These messages may only be sent by the Client.
+    /// List the documents opened during this connection, from least to most
+    /// recently used; the `Result` provides their paths. Valid destinations:
+    /// Server.
+    ListOpenFiles,
+    /// Make the provided document, which must be open (see `ListOpenFiles`),
+    /// the current file. Unlike `CurrentFile`, the Server then sends its most
+    /// recent contents in an `Update`, so the Client doesn't need to reload it.
+    /// If these contents aren't available, the `Result` is an error; use
+    /// `CurrentFile` instead. Valid destinations: Server.
+    SwitchFile(String),
+    /// Show the file at the provided URL in an additional view, alongside the
+    /// current file; the Client then loads this URL. `Update`s for this file
+    /// are sent to the Client like those for the current file; use its
+    /// `file_path` to determine which view to update. Valid destinations:
+    /// Server.
+    OpenView(String),
+    /// Stop showing the file at the provided URL in an additional view. Valid
+    /// destinations: Server.
+    CloseView(String),
+    /// Show the definition of an identifier named in a doc block. The Server
+    /// looks for it in the project's `tags` file, then forwards this message
+    /// to the IDE, which shows the definition; if the Server didn't find it,
+    /// the IDE finds it instead. The IDE's `Result` is returned to the Client.
+    /// Valid destinations: Server, IDE.
+    GotoDefinition(GotoDefinitionContents),
+    /// Open the provided `http` or `https` URL outside the Client, in the
+    /// system's web browser; see `external.rs`. The Server forwards this to
+    /// the IDE, which opens it; the File Watcher opens it itself. Valid
+    /// destinations: Server, IDE.
+    OpenUrl(String),
+    /// Save the provided file, or the current file if `None`, to disk. The
+    /// Client sends any unsaved changes in an `Update` first. The Server
+    /// forwards this to the IDE, naming the file; the IDE's `Result` is
+    /// returned to the Client. The File Watcher already saved each `Update`,
+    /// so it simply replies. Valid destinations: Server, IDE.
+    Save(Option<String>),
+    /// Save all files open in the IDE, like `Save`. Valid destinations:
+    /// Server, IDE.
+    SaveAll,
+    /// Export the provided file, or its whole project, to a PDF; see
+    /// `export.rs`. A file's PDF is placed beside it, while a project's is
+    /// placed in its root, named for its directory. The `Result` provides the
+    /// path to the PDF. Valid destinations: Server.
+    Export(ExportContents),
 
     // #### These messages may only be sent by the IDE.
     /// This is the first message sent when the IDE starts up. It may only be
     /// sent at startup. Valid destinations: Server.
     Opened(IdeType),
-    /// Request the Client to save any unsaved data then close. Valid
-    /// destinations: Client.
+    /// Request the Client to save any unsaved data then close. The Client sends
+    /// its `Result` once this data was saved, so the IDE should wait for it
+    /// before closing. Valid destinations: Client.
     RequestClose,
+    /// Forward the Server's log records at or above the provided level to the
+    /// IDE in `Log` messages; `Off` stops forwarding. See `log_forward.rs`.
+    /// Valid destinations: Server.
+    SubscribeLogs(LevelFilter),
+    /// Provide the root folders of the IDE's workspace and whether the user
+    /// trusts each, replacing any previously provided; send this again when
+    /// they change. See `workspace.rs`. Valid destinations: Server.
+    WorkspaceRoots(Vec<WorkspaceRoot>),
+    /// Start (`true`) or stop sending `Event` messages to the IDE when the
+    /// Server's state changes. See `events.rs`. Valid destinations: Server.
+    SubscribeEvents(bool),
+    /// Report whether the provided file has unsaved changes, so that the
+    /// Client can show this. The IDE sends this when the file's state changes;
+    /// the File Watcher sends it after saving an `Update` from the Client.
+    /// Valid destinations: Client.
+    SavedState(SavedStateContents),
 
     // #### These messages may only be sent by the Server.
     /// Ask the IDE if the provided file is loaded. If so, the IDE should
@@ -162,6 +340,32 @@ enum EditorMessageContents {
     /// HTML for the CodeChat Editor Client to display in its built-in browser.
     /// Valid destinations: IDE.
     ClientHtml(String),
+    /// Change a file's contents, like an `Update`, by sending the changes to
+    /// the contents the Client last received rather than the new contents.
+    /// If the Client no longer has those contents, it replies with an error;
+    /// the Server then sends the new contents in an `Update`. Valid
+    /// destinations: Client.
+    UpdateDiff(UpdateDiffContents),
+    /// Report non-fatal problems found in the file being edited, such as lint
+    /// warnings or HTML removed by the sanitizer. This is sent after an
+    /// `Update` only if problems were found; an `Update`
+    /// which provides contents clears any previously-reported problems. Valid
+    /// destinations: Client.
+    Diagnostics(Vec<Diagnostic>),
+    /// List the optional features enabled for this connection, so that the
+    /// Client shows only their UI; see `capabilities.rs`. This is sent after
+    /// the IDE's `Opened` message, or when a File Watcher connection starts.
+    /// Valid destinations: Client.
+    Capabilities(Capabilities),
+    /// A log record produced by the Server, sent after the IDE's
+    /// `SubscribeLogs` message. If the IDE doesn't acknowledge it, the Server
+    /// stops forwarding records. Valid destinations: IDE.
+    Log(LogRecord),
+    /// A change in the Server's state, such as a translation's progress, sent
+    /// after the IDE's `SubscribeEvents` message. If the IDE doesn't
+    /// acknowledge it, the Server stops sending events. Valid destinations:
+    /// IDE.
+    Event(ServerEvent),
     /// Sent when the IDE or Client websocket was closed, indicating that the
     /// unclosed websocket should be closed as well. Therefore, this message
     /// will never be received by the IDE or Client. Valid destinations: Server.
@@ -188,6 +392,25 @@ enum ResultOkTypes {
     /// The `LoadFile` message provides file contents, if available. This
     /// message may only be sent from the IDE to the Server.
     LoadFile(Option<String>),
+    /// The `ListOpenFiles` message provides the paths of open documents. This
+    /// message may only be sent from the Server to the Client.
+    OpenFiles(Vec<String>),
+    /// The `Outline` message provides the headings in a file. This message
+    /// may only be sent from the Server to the IDE or Client.
+    Outline(Vec<OutlineEntry>),
+    /// The `ListSnippets` message provides the names of the available
+    /// snippets. This message may only be sent from the Server to the IDE or
+    /// Client.
+    Snippets(Vec<String>),
+    /// The `InsertSnippet` message provides the requested snippet. This
+    /// message may only be sent from the Server to the IDE or Client.
+    Snippet(Snippet),
+    /// The `Preferences` message provides the user's preferences. This
+    /// message may only be sent from the Server to the IDE or Client.
+    Preferences(Preferences),
+    /// The result of an `Export` message: the path to the exported PDF. This
+    /// message may only be sent from the Server to the Client.
+    Exported(String),
 }
 
 /// Specify the type of IDE that this client represents.
@@ -200,6 +423,109 @@ enum IdeType {
     DeleteMe,
 }
 
+/// Contents of the `CurrentFile` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct CurrentFileContents {
+    /// The filesystem path to the file when sent to the IDE; its URL when sent
+    /// to the Client.
+    file_path: String,
+    /// Where the IDE should show this file. The Client ignores this, since it
+    /// shows only one file.
+    #[serde(default)]
+    view_target: ViewTarget,
+}
+
+/// Where the IDE shows a file, when it splits its editors into groups (VSCode
+/// calls these view columns).
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+enum ViewTarget {
+    /// In the active group.
+    #[default]
+    Active,
+    /// In a group beside the active group, creating one if needed.
+    Beside,
+    /// In the numbered group, starting from 1.
+    Group(u8),
+}
+
+/// Contents of the `SetFileLanguage` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SetFileLanguageContents {
+    /// The filesystem path to the file.
+    file_path: String,
+    /// The name of the lexer to use for this file, such as `c_cpp`; `None`
+    /// returns to the lexer chosen by the file's extension and contents.
+    language: Option<String>,
+}
+
+/// Contents of the `GotoDefinition` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct GotoDefinitionContents {
+    /// The filesystem path to the file whose doc block names the identifier.
+    file_path: String,
+    /// The identifier to find.
+    identifier: String,
+    /// The definition found by the Server, if any. The Client always sends
+    /// `None`.
+    definition: Option<Definition>,
+}
+
+/// Contents of the `InsertSnippet` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct InsertSnippetContents {
+    /// The filesystem path to the file which the snippet will be inserted
+    /// into.
+    file_path: String,
+    /// The name of the snippet (see `ListSnippets`).
+    name: String,
+}
+
+/// Contents of the `Export` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct ExportContents {
+    /// The filesystem path to the file.
+    file_path: String,
+    /// True to export the file's whole project, rather than only this file.
+    project: bool,
+}
+
+/// Contents of the `SavedState` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SavedStateContents {
+    /// The filesystem path to the file.
+    file_path: String,
+    /// Whether this file has unsaved changes.
+    state: SaveState,
+}
+
+/// Whether a file's contents match those saved on disk.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+enum SaveState {
+    /// The file is saved.
+    Clean,
+    /// The file has unsaved changes.
+    Dirty,
+}
+
+/// Contents of the `FoldRanges` message.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+struct FoldRangesContents {
+    /// The filesystem path to the file.
+    file_path: String,
+    /// The folded regions of this file.
+    ranges: Vec<FoldRange>,
+}
+
+/// A folded region of a file. Since the Client shows each line of a file on a
+/// line of its own, lines are the same in the IDE and the Client.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+struct FoldRange {
+    /// The first line of the region, numbered from 0.
+    start: u32,
+    /// The last line of the region.
+    end: u32,
+}
+
 /// Contents of the `Update` message.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 struct UpdateMessageContents {
@@ -217,9 +543,23 @@ struct UpdateMessageContents {
     /// in the file. TODO: Selections are not yet supported. TODO: how to get a
     /// cursor location from within a doc block in the Client?
     cursor_position: Option<u32>,
-    /// The normalized vertical scroll position in the file, where 0 = top and 1
-    /// = bottom.
-    scroll_position: Option<f32>,
+    /// The vertical scroll position in the file, in the units of the sender;
+    /// the Server translates this to the units of the receiver. See
+    /// `scroll.rs`.
+    scroll_position: Option<ScrollPosition>,
+}
+
+/// Contents of the `UpdateDiff` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct UpdateDiffContents {
+    /// The filesystem path to this file; see `UpdateMessageContents`.
+    file_path: String,
+    /// The ID of the message which sent the contents these changes apply to.
+    base_id: f64,
+    /// The changes to these contents. The metadata is unchanged.
+    diff: CodeMirrorDiff,
+    /// The vertical scroll position in the file; see `UpdateMessageContents`.
+    scroll_position: Option<ScrollPosition>,
 }
 
 /// ### Data structures used by the webserver
@@ -244,6 +584,8 @@ pub struct AppState {
     vscode_client_queues: Arc<Mutex<HashMap<String, WebsocketQueues>>>,
     // Connection IDs that are currently in use.
     vscode_connection_id: Arc<Mutex<HashSet<String>>>,
+    // The instructor's position, shared with students in classroom mode.
+    classroom: Arc<Classroom>,
 }
 
 // ## Macros
@@ -290,10 +632,45 @@ const REPLY_TIMEOUT: Duration = if cfg!(test) {
     Duration::from_millis(15000)
 };
 
+/// The start of the error produced when a websocket doesn't reply in time.
+const TIMEOUT_PREFIX: &str = "Timeout:";
+
+/// By default, how many times to resend a `LoadFile` request which the IDE
+/// didn't answer before reading the file from disk instead.
+pub const DEFAULT_LOAD_FILE_RETRIES: u32 = 2;
+
+/// The delay before the first resend of an unanswered `LoadFile` request; this
+/// doubles with each later resend.
+const LOAD_FILE_RETRY_DELAY: Duration = if cfg!(test) {
+    Duration::from_millis(10)
+} else {
+    Duration::from_millis(250)
+};
+
+/// The number of times to resend an unanswered `LoadFile` request; see
+/// `set_load_file_retries`.
+static LOAD_FILE_RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_LOAD_FILE_RETRIES);
+
 /// The time to wait for a pong from the websocket in response to a ping sent by
 /// this server.
 const WEBSOCKET_PING_DELAY: Duration = Duration::from_secs(2);
 
+/// How often to check if the Server is idle, and therefore may save cached
+/// translations to disk; it's idle if nothing was translated during this time.
+const CACHE_PERSIST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to keep an idle connection open. Loading the Client requests many
+/// static files; keeping connections open avoids reconnecting for each one.
+const KEEP_ALIVE: Duration = Duration::from_secs(75);
+
+/// The minimum number of worker threads, so that static files are served in
+/// parallel even on machines with few cores.
+const MIN_WORKERS: usize = 4;
+
+/// Query parameters of a file's page which the Server uses; the rest give the
+/// values of conditions in the file.
+const RESERVED_QUERY_PARAMS: [&str; 5] = ["mode", "test", "mobile", "instructor", "token"];
+
 /// The initial value for a message ID.
 const INITIAL_MESSAGE_ID: f64 = if cfg!(test) {
     // A simpler value when testing.
@@ -346,11 +723,7 @@ lazy_static! {
 
     // Read in the hashed names of files bundled by esbuild.
     static ref BUNDLED_FILES_MAP: HashMap<String, String> = {
-        let mut hl = ROOT_PATH.clone();
-        #[cfg(debug_assertions)]
-        hl.push("server");
-        hl.push("hashLocations.json");
-        let json = fs::read_to_string(hl).unwrap();
+        let json = fs::read_to_string(server_file_path("hashLocations.json")).unwrap();
         let hmm: HashMap<String, String> = serde_json::from_str(&json).unwrap();
         hmm
     };
@@ -375,6 +748,156 @@ async fn stop(app_state: web::Data<AppState>) -> HttpResponse {
     HttpResponse::NoContent().finish()
 }
 
+/// Mint a new one-time token for a browser reaching the Server through a
+/// tunnel or proxy, returning the path to open. Only programs on this machine,
+/// which provide the local secret, may do this.
+#[get("/tunnel-token")]
+async fn tunnel_token(req: HttpRequest) -> HttpResponse {
+    let has_local_secret = req
+        .headers()
+        .get(LOCAL_SECRET_HEADER)
+        .and_then(|secret| secret.to_str().ok())
+        .is_some_and(is_local_secret);
+    if !has_local_secret {
+        return html_forbidden("<p>Only programs on the Server's machine may mint a token.</p>");
+    }
+    match start_tunnel_access() {
+        Ok(token) => HttpResponse::Ok().body(with_base_path(&format!("/?{TOKEN_NAME}={token}"))),
+        Err(err) => HttpResponse::InternalServerError().body(err),
+    }
+}
+
+/// The range of lines requested from the `/markdown` endpoint.
+#[derive(Deserialize)]
+struct MarkdownLineRange {
+    /// The first line (1-based) to include; if omitted, start at the beginning
+    /// of the file.
+    from: Option<usize>,
+    /// The last line to include; if omitted, continue to the end of the file.
+    to: Option<usize>,
+}
+
+/// Return the doc blocks of the requested file which overlap the requested
+/// range of lines as Markdown, with relative links resolved. This supports
+/// "Copy as Markdown" in the Client. For example,
+/// `/markdown/path/to/foo.py?from=10&to=20`. This reads the file as saved on
+/// disk; to include unsaved changes, `POST` the IDE's contents of the file to
+/// the same URL instead.
+#[get("/markdown/{file_path:.*}")]
+async fn markdown_endpoint(
+    orig_path: web::Path<String>,
+    line_range: web::Query<MarkdownLineRange>,
+) -> HttpResponse {
+    let file_path = markdown_file_path(&orig_path);
+    let file_contents = match fs::read_to_string(&file_path) {
+        Ok(v) => v,
+        Err(err) => {
+            return html_not_found(&format!(
+                "<p>Unable to read <code>{}</code>: {}.</p>",
+                path_display(&file_path),
+                escape(&err.to_string(), EscapeContext::Text)
+            ))
+        }
+    };
+    markdown_response(&file_contents, &file_path, &line_range)
+}
+
+/// Like `markdown_endpoint`, but use the contents of the file provided in the
+/// body of the request, such as an IDE's unsaved buffer, rather than reading
+/// the file from disk. The file's path is still used to find its lexer and to
+/// resolve relative links.
+#[post("/markdown/{file_path:.*}")]
+async fn markdown_buffer_endpoint(
+    orig_path: web::Path<String>,
+    line_range: web::Query<MarkdownLineRange>,
+    file_contents: String,
+) -> HttpResponse {
+    markdown_response(&file_contents, &markdown_file_path(&orig_path), &line_range)
+}
+
+// Translate the path requested from the `/markdown` endpoints to a file path.
+fn markdown_file_path(orig_path: &str) -> PathBuf {
+    // For Linux/OS X, prepend a slash, so that `a/path/to/file.txt` becomes
+    // `/a/path/to/file.txt`.
+    #[cfg(not(target_os = "windows"))]
+    let file_path = PathBuf::from("/".to_string() + orig_path);
+    #[cfg(target_os = "windows")]
+    let file_path = PathBuf::from(orig_path);
+    file_path
+}
+
+// Respond with the doc blocks of the provided file as Markdown.
+fn markdown_response(
+    file_contents: &str,
+    file_path: &Path,
+    line_range: &MarkdownLineRange,
+) -> HttpResponse {
+    match doc_blocks_to_markdown(file_contents, file_path, line_range.from, line_range.to) {
+        Ok(markdown) => HttpResponse::Ok()
+            .content_type("text/markdown; charset=utf-8")
+            .body(markdown),
+        Err(err) => HttpResponse::BadRequest()
+            .content_type(ContentType::plaintext())
+            .body(err),
+    }
+}
+
+/// The file requested from the `/debug/lex` endpoint.
+#[derive(Deserialize)]
+struct DebugLexQuery {
+    /// The path to the file to lex.
+    path: PathBuf,
+}
+
+/// The response from the `/debug/lex` endpoint.
+#[derive(Serialize)]
+struct LexerExplanation {
+    /// The name of the lexer used for this file.
+    lexer_name: String,
+    /// Each step the lexer took.
+    trace: Vec<LexerTraceEvent>,
+}
+
+/// Return, as JSON, each step the lexer took to split the requested file into
+/// code and doc blocks. This shows why a comment was or wasn't classified as a
+/// doc block. For example, `/debug/lex?path=/path/to/foo.py`.
+#[get("/debug/lex")]
+async fn debug_lex_endpoint(query: web::Query<DebugLexQuery>) -> HttpResponse {
+    let file_path = &query.path;
+    let file_contents = match fs::read_to_string(file_path) {
+        Ok(v) => v,
+        Err(err) => {
+            return html_not_found(&format!(
+                "<p>Unable to read <code>{}</code>: {}.</p>",
+                path_display(file_path),
+                escape(&err.to_string(), EscapeContext::Text)
+            ))
+        }
+    };
+    let project_config = project_config_for_file(file_path, find_path_to_toc(file_path).as_deref());
+    match find_file_lexer(&file_contents, file_path, &project_config) {
+        Ok(lexer) => HttpResponse::Ok().json(LexerExplanation {
+            lexer_name: lexer.language_lexer.lexer_name.to_string(),
+            trace: source_lexer_explained(&file_contents, lexer).1,
+        }),
+        Err(err) => HttpResponse::BadRequest()
+            .content_type(ContentType::plaintext())
+            .body(err),
+    }
+}
+
+/// Return, as JSON, the durations recorded by a Server built with the `perf`
+/// feature.
+#[get("/metrics")]
+async fn metrics_endpoint() -> HttpResponse {
+    if !cfg!(feature = "perf") {
+        return HttpResponse::NotFound()
+            .content_type(ContentType::plaintext())
+            .body("Build the Server with the perf feature to record metrics.");
+    }
+    HttpResponse::Ok().json(perf::metrics())
+}
+
 /// Assign an ID to a new connection.
 #[get("/id")]
 async fn connection_id_endpoint(
@@ -415,6 +938,23 @@ pub fn get_test_mode(req: &HttpRequest) -> bool {
     }
 }
 
+// Determine if the read-only mobile layout should be used: the `mobile` query
+// parameter selects it (or, if it's `0`, the Client); otherwise, use it for a
+// mobile browser.
+fn get_mobile_mode(req: &HttpRequest) -> bool {
+    let query_params = web::Query::<HashMap<String, String>>::from_query(req.query_string());
+    if let Some(mobile) = query_params
+        .ok()
+        .and_then(|query| query.get("mobile").cloned())
+    {
+        return mobile != "0";
+    }
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .is_some_and(is_mobile_user_agent)
+}
+
 // Return an instance of the Client.
 fn get_client_framework(
     // True if the page should enable test mode for Clients it loads.
@@ -425,17 +965,27 @@ fn get_client_framework(
     connection_id: &str,
     // This returns a response (the Client, or an error).
 ) -> Result<String, String> {
-    // Provide the pathname to the websocket connection. Quote the string using
-    // JSON to handle any necessary escapes.
-    let ws_url = match serde_json::to_string(&format!("{ide_path}/{connection_id}")) {
-        Ok(v) => v,
-        Err(err) => {
-            return Err(format!(
-                "Unable to encode websocket URL for {ide_path}, id {connection_id}: {err}"
-            ))
-        }
-    };
+    // Provide the pathname to the websocket connection. This differs for
+    // each connection, so provide it as data rather than as part of the
+    // script.
+    let ws_path = with_base_path(&format!("/{ide_path}/{connection_id}"));
     let codechat_editor_framework_js = BUNDLED_FILES_MAP.get("CodeChatEditorFramework.js").unwrap();
+    let iframe_attributes = iframe_attributes();
+    let base_path = base_path();
+    let framework_data_tag = json_data_tag(
+        "CodeChat-framework-data",
+        &(ws_path, is_test_mode, &base_path),
+    );
+    let framework_script = inline_script(
+        r#" type="module""#,
+        &formatdoc!(
+            r#"
+            import {{ page_init }} from "{base_path}/{codechat_editor_framework_js}"
+            const [ws_url, is_test_mode, base_path] = JSON.parse(document.getElementById("CodeChat-framework-data").textContent)
+            page_init(ws_url, is_test_mode, base_path)
+            "#
+        ),
+    );
 
     // Build and return the webpage.
     Ok(formatdoc!(
@@ -446,13 +996,11 @@ fn get_client_framework(
                 <meta charset="UTF-8">
                 <meta name="viewport" content="width=device-width, initial-scale=1">
                 <title>The CodeChat Editor</title>
-                <script type="module">
-                    import {{ page_init }} from "/{codechat_editor_framework_js}"
-                    page_init({ws_url}, {is_test_mode})
-                </script>
+                {framework_data_tag}
+                {framework_script}
             </head>
             <body style="margin: 0px; padding: 0px; overflow: hidden">
-                <iframe id="CodeChat-iframe"
+                <iframe id="CodeChat-iframe"{iframe_attributes}
                     style="width:100%; height:100vh; border:none;"
                     srcdoc="<!DOCTYPE html>
                     <html lang='en'>
@@ -501,14 +1049,28 @@ pub async fn filesystem_endpoint(
         }
     };
 
-    // Get the `mode` query parameter to determine `is_toc`; default to `false`.
+    // Get the `mode` query parameter to determine `is_toc` and `is_slides`;
+    // both default to `false`.
     let query_params: Result<
         web::Query<HashMap<String, String>>,
         actix_web::error::QueryPayloadError,
     > = web::Query::<HashMap<String, String>>::from_query(req.query_string());
-    let is_toc = query_params.map_or(false, |query| {
-        query.get("mode").map_or(false, |mode| mode == "toc")
-    });
+    let query_params = query_params
+        .map(|query| query.into_inner())
+        .unwrap_or_default();
+    let mode = query_params.get("mode").cloned();
+    // The remaining query parameters, such as `audience=instructor`, give the
+    // values of conditions in this file; see `conditions.rs`.
+    set_file_conditions(
+        &file_path,
+        query_params
+            .into_iter()
+            .filter(|(name, _)| !RESERVED_QUERY_PARAMS.contains(&name.as_str()))
+            .collect(),
+    );
+    let is_toc = mode.as_deref() == Some("toc");
+    let is_slides = mode.as_deref() == Some("slides");
+    let is_mobile = get_mobile_mode(req);
     let is_test_mode = get_test_mode(req);
 
     // Create a one-shot channel used by the processing task to provide a
@@ -534,6 +1096,8 @@ pub async fn filesystem_endpoint(
         .send(ProcessingTaskHttpRequest {
             file_path,
             is_toc,
+            is_slides,
+            is_mobile,
             is_test_mode,
             response_queue: tx,
         })
@@ -575,18 +1139,25 @@ async fn make_simple_http_response(
 ) -> (
     // The response to send back to the HTTP endpoint.
     SimpleHttpResponse,
-    // If this file is currently being edited, this is the body of an `Update`
-    // message to send.
-    Option<EditorMessageContents>,
+    // If this file is currently being edited, the bodies of the messages to
+    // send: an `Update`, followed by a `Diagnostics` if its doc blocks contain
+    // problems.
+    Vec<EditorMessageContents>,
 ) {
     // Convert the provided URL back into a file name.
     let file_path = &http_request.file_path;
 
+    // Serve audio and video directly from the filesystem, which supports range
+    // requests, rather than reading (potentially large) recordings into memory.
+    if is_media_file(file_path) {
+        return (SimpleHttpResponse::Bin(file_path.clone()), vec![]);
+    }
+
     // Read the file
     match File::open(file_path).await {
         Err(err) => (
             SimpleHttpResponse::Err(format!("<p>Error opening file {file_path:?}: {err}.")),
-            None,
+            vec![],
         ),
         Ok(mut fc) => {
             let mut file_contents = String::new();
@@ -594,7 +1165,7 @@ async fn make_simple_http_response(
                 // If this is a binary file (meaning we can't read the contents
                 // as UTF-8), just serve it raw; assume this is an
                 // image/video/etc.
-                Err(_) => (SimpleHttpResponse::Bin(file_path.clone()), None),
+                Err(_) => (SimpleHttpResponse::Bin(file_path.clone()), vec![]),
                 Ok(_) => {
                     text_file_to_response(http_request, current_filepath, file_path, &file_contents)
                         .await
@@ -614,42 +1185,195 @@ async fn text_file_to_response(
 ) -> (
     // The response to send back to the HTTP endpoint.
     SimpleHttpResponse,
-    // If this file is currently being edited, this is the body of an `Update`
-    // message to send.
-    Option<EditorMessageContents>,
+    // If this file is currently being edited, the bodies of the messages to
+    // send: an `Update`, followed by a `Diagnostics` if its doc blocks contain
+    // problems.
+    Vec<EditorMessageContents>,
 ) {
     // Compare using the canonical path first, then the absolute path if this
     // fails. This is necessary because the file may not exist on the filesystem
     // (only in the IDE).
     let is_current = match file_path.canonicalize() {
         Ok(fp) => simplified(&fp) == current_filepath,
-        Err(_) => path::absolute(file_path).map_or(false, |fp| fp == current_filepath),
+        Err(_) => path::absolute(file_path).is_ok_and(|fp| fp == current_filepath),
+    };
+    // Show a document outside the current project read-only, unless the
+    // policy allows editing it; see `outside_project.rs`.
+    let policy = if is_current {
+        None
+    } else {
+        outside_project(current_filepath, file_path)
     };
     let (simple_http_response, option_codechat_for_web) = serve_file(
         file_path,
         file_contents,
         http_request.is_toc,
+        http_request.is_slides,
+        http_request.is_mobile || policy.is_some(),
         is_current,
         http_request.is_test_mode,
     )
     .await;
-    let Some(file_path) = file_path.to_str() else {
+    // Refuse to show this document, while still serving resources such as
+    // images or stylesheets.
+    if policy == Some(OutsideProjectPolicy::Refuse) {
+        if let SimpleHttpResponse::Ok(_) = simple_http_response {
+            return (
+                SimpleHttpResponse::Err(format!(
+                    "<p>{}</p>",
+                    escape(&refused_message(file_path), EscapeContext::Text)
+                )),
+                vec![],
+            );
+        }
+    }
+    let Some(file_path_str) = file_path.to_str() else {
         let msg = format!("Error: unable to convert path {file_path:?} to a string.");
         error!("{msg}");
-        return (SimpleHttpResponse::Err(msg), None);
+        return (SimpleHttpResponse::Err(msg), vec![]);
     };
-    // If this file is editable and is the main file, send an `Update`. The
-    // `simple_http_response` contains the Client.
-    (
-        simple_http_response,
-        option_codechat_for_web.map(|codechat_for_web| {
-            EditorMessageContents::Update(UpdateMessageContents {
-                file_path: file_path.to_string(),
-                contents: Some(codechat_for_web),
-                cursor_position: None,
-                scroll_position: None,
+    // If this file is editable and is the main file, send an `Update`, then
+    // any problems in its doc blocks. The `simple_http_response` contains the
+    // Client.
+    let mut messages = vec![];
+    if let Some(codechat_for_web) = option_codechat_for_web {
+        messages.push(EditorMessageContents::Update(UpdateMessageContents {
+            file_path: file_path_str.to_string(),
+            contents: Some(codechat_for_web),
+            cursor_position: None,
+            scroll_position: None,
+        }));
+        messages.extend(diagnostics_message(file_contents, file_path));
+    }
+    (simple_http_response, messages)
+}
+
+/// Check the provided file for problems, returning a `Diagnostics` message if
+/// any were found.
+fn diagnostics_message(file_contents: &str, file_path: &Path) -> Option<EditorMessageContents> {
+    match check_file(file_contents, file_path) {
+        Ok(diagnostics) if !diagnostics.is_empty() => {
+            Some(EditorMessageContents::Diagnostics(diagnostics))
+        }
+        Ok(_) => None,
+        Err(err) => {
+            warn!("Unable to check {file_path:?}: {err}");
+            None
+        }
+    }
+}
+
+/// Find the outline of the provided file, returning an empty outline if this
+/// fails.
+fn outline_entries(file_contents: &str, file_path: &Path) -> Vec<OutlineEntry> {
+    outline(file_contents, file_path).unwrap_or_else(|err| {
+        warn!("Unable to find the outline of {file_path:?}: {err}");
+        vec![]
+    })
+}
+
+/// Respond to a `ListSnippets` message.
+fn list_snippets(file_path: &str) -> MessageResult {
+    Ok(ResultOkTypes::Snippets(snippet_names(Path::new(file_path))))
+}
+
+/// Respond to an `InsertSnippet` message.
+fn insert_snippet(insert_snippet_contents: &InsertSnippetContents) -> MessageResult {
+    snippet(
+        &insert_snippet_contents.name,
+        Path::new(&insert_snippet_contents.file_path),
+    )
+    .map(ResultOkTypes::Snippet)
+}
+
+/// Respond to a `Preferences` message.
+fn preferences_message(update: Option<Preferences>) -> MessageResult {
+    match update {
+        None => Ok(ResultOkTypes::Preferences(load_preferences())),
+        Some(update) => update_preferences(update).map(ResultOkTypes::Preferences),
+    }
+}
+
+/// Respond to an `Export` message. Since this runs Chromium, which is slow,
+/// export on another thread, then send the `Result` using `tx`.
+fn export_message(export_contents: ExportContents, tx: Sender<EditorMessage>, id: f64) {
+    actix_rt::spawn(async move {
+        let result = actix_rt::task::spawn_blocking(move || export_pdf(&export_contents))
+            .await
+            .unwrap_or_else(|err| Err(format!("Unable to export: {err}")));
+        send_response(&tx, id, result).await;
+    });
+}
+
+// Export a file or its project to a PDF, returning the path to this PDF.
+fn export_pdf(export_contents: &ExportContents) -> MessageResult {
+    let file_path = Path::new(&export_contents.file_path);
+    let output_path = if export_contents.project {
+        let path_to_toc = find_path_to_toc(file_path)
+            .ok_or_else(|| format!("{file_path:?} isn't part of a project."))?;
+        let root = project_root(file_path, &path_to_toc);
+        let name = root
+            .canonicalize()
+            .ok()
+            .and_then(|root| {
+                root.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
             })
-        }),
+            .unwrap_or_else(|| "project".to_string());
+        let output_path = root.join(format!("{name}.pdf"));
+        export_project(&root.join("toc.md"), &output_path, ExportFormat::Pdf)?;
+        output_path
+    } else {
+        let output_path = file_path.with_extension("pdf");
+        export_file(file_path, &output_path, ExportFormat::Pdf)?;
+        output_path
+    };
+    Ok(ResultOkTypes::Exported(
+        output_path.to_string_lossy().to_string(),
+    ))
+}
+
+/// Return the tags which configure then load MathJax, defining the project's
+/// TeX macros.
+fn mathjax_tags(tex_macros: &BTreeMap<String, TexMacro>) -> String {
+    // The macros differ between projects, so provide them as data rather than
+    // as part of the script.
+    let macros_tag = json_data_tag("CodeChat-tex-macros", tex_macros);
+    let base_path = base_path();
+    let config_script = inline_script(
+        "",
+        &formatdoc!(
+            r#"
+            MathJax = {{
+                // See the [docs](https://docs.mathjax.org/en/latest/options/output/chtml.html#option-descriptions).
+                chtml: {{
+                    fontURL: "{base_path}/static/mathjax-modern-font/chtml/woff",
+                }},
+                tex: {{
+                    inlineMath: [['$', '$'], ['\\(', '\\)']],
+                    macros: JSON.parse(document.getElementById("CodeChat-tex-macros").textContent),
+                }},
+            }};
+            "#
+        ),
+    );
+    formatdoc!(
+        r#"
+        {macros_tag}
+        {config_script}
+        <script defer src="{base_path}/static/mathjax/tex-chtml.js"></script>
+        "#
+    )
+}
+
+/// Return a `<script type="application/json">` element with the provided `id`
+/// containing `data`, for a page's scripts to read. Since this isn't run, the
+/// page's Content Security Policy doesn't need to allow it.
+fn json_data_tag(id: &str, data: &impl Serialize) -> String {
+    // Escape `<`, so that the data can't end this script.
+    format!(
+        r#"<script type="application/json" id="{id}">{}</script>"#,
+        serde_json::to_string(data).unwrap().replace('<', "\\u003c")
     )
 }
 
@@ -657,6 +1381,8 @@ async fn serve_file(
     file_path: &Path,
     file_contents: &str,
     is_toc: bool,
+    is_slides: bool,
+    is_mobile: bool,
     is_current_file: bool,
     is_test_mode: bool,
 ) -> (SimpleHttpResponse, Option<CodeChatForWeb>) {
@@ -664,9 +1390,13 @@ async fn serve_file(
     let raw_dir = file_path.parent().unwrap();
     // Use a lossy conversion, since this is UI display, not filesystem access.
     let dir = path_display(raw_dir);
-    let name = escape_html(&file_path.file_name().unwrap().to_string_lossy());
+    let name = escape(
+        &file_path.file_name().unwrap().to_string_lossy(),
+        EscapeContext::Text,
+    );
 
     // Get the locations for bundled files.
+    let base_path = base_path();
     let js_test_suffix = if is_test_mode { "-test" } else { "" };
     let codechat_editor_js = BUNDLED_FILES_MAP
         .get(&format!("CodeChatEditor{js_test_suffix}.js"))
@@ -674,31 +1404,20 @@ async fn serve_file(
     let codehat_editor_css = BUNDLED_FILES_MAP
         .get(&format!("CodeChatEditor{js_test_suffix}.css"))
         .unwrap();
-    let mathjax_tags = indoc!(
-        r#"
-        <script>
-            MathJax = {
-                // See the [docs](https://docs.mathjax.org/en/latest/options/output/chtml.html#option-descriptions).
-                chtml: {
-                    fontURL: "/static/mathjax-modern-font/chtml/woff",
-                },
-                tex: {
-                    inlineMath: [['$', '$'], ['\\(', '\\)']]
-                },
-            };
-        </script>
-        <script defer src="/static/mathjax/tex-chtml.js"></script>
-        "#
-    );
-
     // See if this is a CodeChat Editor file.
-    let (translation_results_string, path_to_toc) = if is_current_file || is_toc {
-        source_to_codechat_for_web_string(file_contents, file_path, is_toc)
+    let (translation_results_string, path_to_toc) = if is_current_file
+        || is_toc
+        || is_slides
+        || is_mobile
+    {
+        source_to_codechat_for_web_string(file_contents, file_path, is_toc, RenderTarget::Editor)
     } else {
         // If this isn't the current file, then don't parse it.
         (TranslationResultsString::Unknown, None)
     };
     let is_project = path_to_toc.is_some();
+    let project_config = project_config_for_file(file_path, path_to_toc.as_deref());
+    let mathjax_tags = mathjax_tags(&project_config.tex_macros);
     let codechat_for_web = match translation_results_string {
         // The file type is unknown. Serve it raw.
         TranslationResultsString::Unknown => {
@@ -716,6 +1435,41 @@ async fn serve_file(
         }
         // This is a CodeChat file. The following code wraps the CodeChat for
         // web results in a CodeChat Editor Client webpage.
+        // Show a slide deck, which isn't edited, rather than the Client.
+        TranslationResultsString::CodeChat(codechat_for_web) if is_slides => {
+            return (
+                SimpleHttpResponse::Ok(codechat_for_web_to_slides(
+                    &codechat_for_web,
+                    &file_path.file_name().unwrap().to_string_lossy(),
+                    &formatdoc!(
+                        r#"
+                        {mathjax_tags}
+                        <link rel="stylesheet" href="{base_path}/{codehat_editor_css}">"#
+                    ),
+                )),
+                None,
+            );
+        }
+        // Likewise, show a read-only page on mobile devices.
+        TranslationResultsString::CodeChat(codechat_for_web) if is_mobile => {
+            let toc = path_to_toc
+                .as_deref()
+                .and_then(|path_to_toc| toc_html(raw_dir, path_to_toc));
+            return (
+                SimpleHttpResponse::Ok(codechat_for_web_to_mobile(
+                    &codechat_for_web,
+                    &file_path.file_name().unwrap().to_string_lossy(),
+                    &formatdoc!(
+                        r#"
+                        {mathjax_tags}
+                        <link rel="stylesheet" href="{base_path}/{codehat_editor_css}">"#
+                    ),
+                    toc.as_ref()
+                        .map(|(html, toc_dir)| (html.as_str(), toc_dir.as_str())),
+                )),
+                None,
+            );
+        }
         TranslationResultsString::CodeChat(codechat_for_web) => codechat_for_web,
         TranslationResultsString::Toc(html) => {
             // The TOC is a simplified web page which requires no additional
@@ -730,12 +1484,14 @@ async fn serve_file(
                             <meta name="viewport" content="width=device-width, initial-scale=1">
                             <title>{name} - The CodeChat Editor</title>
                             {mathjax_tags}
-                            <link rel="stylesheet" href="/{codehat_editor_css}">
+                            <link rel="stylesheet" href="{base_path}/{codehat_editor_css}">
                         </head>
                         <body class="CodeChat-theme-light">
-                            <div class="CodeChat-TOC">
-                                {html}
-                            </div>
+                            <main>
+                                <div class="CodeChat-TOC" role="navigation" aria-label="Table of contents">
+                                    {html}
+                                </div>
+                            </main>
                         </body>
                     </html>"#,
                 )),
@@ -749,11 +1505,11 @@ async fn serve_file(
     let (sidebar_iframe, sidebar_css) = if is_project {
         (
             format!(
-                r#"<iframe src="{}?mode=toc" id="CodeChat-sidebar"></iframe>"#,
+                r#"<iframe src="{}?mode=toc" id="CodeChat-sidebar" title="Table of contents"></iframe>"#,
                 path_to_toc.unwrap().to_slash_lossy()
             ),
             format!(
-                r#"<link rel="stylesheet" href="/{}">"#,
+                r#"<link rel="stylesheet" href="{base_path}/{}">"#,
                 BUNDLED_FILES_MAP.get("CodeChatEditorProject.css").unwrap()
             ),
         )
@@ -761,20 +1517,44 @@ async fn serve_file(
         ("".to_string(), "".to_string())
     };
 
+    // Include a read-only rendering of the file, so that it's readable before
+    // the Client's scripts load, or if they're blocked. The Client replaces
+    // this with the editable contents.
+    let initial_html = codechat_for_web_to_html(&codechat_for_web);
+    // Likewise, embed the contents which the Client edits, so that it shows
+    // them without waiting for the `Update` sent over the websocket.
+    let initial_contents_tag = initial_contents_tag(&codechat_for_web);
+
+    // Provide the Client's keyboard shortcuts.
+    let keymap_tag = keymap_tag(&keymap(&project_config.keymap, &load_preferences().keymap));
+
     // Add testing mode scripts if requested.
     let testing_src = if is_test_mode {
-        r#"
-        <link rel="stylesheet" href="https://unpkg.com/mocha/mocha.css" />
-        <script src="https://unpkg.com/mocha/mocha.js"></script>
-        "#
+        formatdoc!(
+            r#"
+            <link rel="stylesheet" href="https://unpkg.com/mocha/mocha.css" />
+            {}
+            "#,
+            external_script("https://unpkg.com/mocha/mocha.js")
+        )
     } else {
-        ""
+        "".to_string()
     };
 
+    let client_script = inline_script(
+        r#" type="module""#,
+        &formatdoc!(
+            r#"
+            import {{ page_init }} from "{base_path}/{codechat_editor_js}"
+            page_init()
+            "#
+        ),
+    );
+
     // Build and return the webpage.
     (
         SimpleHttpResponse::Ok(formatdoc!(
-            r#"
+            r##"
             <!DOCTYPE html>
             <html lang="en">
                 <head>
@@ -782,36 +1562,109 @@ async fn serve_file(
                     <meta name="viewport" content="width=device-width, initial-scale=1">
                     <title>{name} - The CodeChat Editor</title>
                     {mathjax_tags}
-                    <script type="module">
-                        import {{ page_init }} from "/{codechat_editor_js}"
-                        page_init()
-                    </script>
-                    <link rel="stylesheet" href="/{codehat_editor_css}">
+                    {keymap_tag}
+                    {initial_contents_tag}
+                    {client_script}
+                    <link rel="stylesheet" href="{base_path}/{codehat_editor_css}">
                     {testing_src}
                     {sidebar_css}
                 </head>
                 <body class="CodeChat-theme-light">
+                    <a class="CodeChat-skip-link" href="#CodeChat-body">Skip to content</a>
                     {sidebar_iframe}
                     <div id="CodeChat-contents">
-                        <div id="CodeChat-top">
+                        <div id="CodeChat-top" role="banner">
                             <div id="CodeChat-filename">
-                                <p>
+                                <p role="heading" aria-level="1">
                                     {name} - {dir}
                                 </p>
                             </div>
                             <div id="CodeChat-menu"></div>
                         </div>
-                        <div id="CodeChat-body"></div>
+                        <div id="CodeChat-body" role="main">
+                            <noscript>
+                                <p>Editing this file requires JavaScript; it's shown read-only below.</p>
+                            </noscript>
+                            <div class="CodeChat-doc-contents">
+            {initial_html}
+                            </div>
+                        </div>
                         <div id="CodeChat-bottom"></div>
                         <div id="mocha"></div>
                     </div>
                 </body>
-            </html>"#
+            </html>"##
         )),
         Some(codechat_for_web),
     )
 }
 
+// Return the tag which embeds the contents of the file in the Client's page.
+fn initial_contents_tag(codechat_for_web: &CodeChatForWeb) -> String {
+    json_data_tag("CodeChat-initial-contents", codechat_for_web)
+}
+
+// Return the HTML of the project's TOC, along with the URL of its directory
+// relative to `dir`, or `None` if the TOC can't be read.
+fn toc_html(dir: &Path, path_to_toc: &Path) -> Option<(String, String)> {
+    let toc_path = dir.join(path_to_toc);
+    let toc_contents = fs::read_to_string(&toc_path)
+        .inspect_err(|err| warn!("Unable to read {toc_path:?}: {err}"))
+        .ok()?;
+    let TranslationResultsString::Toc(html) =
+        source_to_codechat_for_web_string(&toc_contents, &toc_path, true, RenderTarget::Editor).0
+    else {
+        return None;
+    };
+    // For example, `../toc.md` produces `../`, while `toc.md` produces an
+    // empty string.
+    let toc_dir = match path_to_toc.parent() {
+        Some(toc_dir) if !toc_dir.as_os_str().is_empty() => {
+            format!("{}/", toc_dir.to_slash_lossy())
+        }
+        _ => "".to_string(),
+    };
+    Some((html, toc_dir))
+}
+
+// Render the provided file as a read-only web page, or return `None` if this
+// isn't a CodeChat Editor file.
+fn read_only_page(file_path: &Path, file_contents: &str) -> Option<String> {
+    let (translation_results_string, path_to_toc) =
+        source_to_codechat_for_web_string(file_contents, file_path, false, RenderTarget::ReadOnly);
+    let TranslationResultsString::CodeChat(codechat_for_web) = translation_results_string else {
+        return None;
+    };
+    let mathjax_tags =
+        mathjax_tags(&project_config_for_file(file_path, path_to_toc.as_deref()).tex_macros);
+    let codechat_editor_css = BUNDLED_FILES_MAP.get("CodeChatEditor.css").unwrap();
+    let base_path = base_path();
+    let name = escape(
+        &file_path.file_name()?.to_string_lossy(),
+        EscapeContext::Text,
+    );
+    let body = codechat_for_web_to_html(&codechat_for_web);
+    Some(formatdoc!(
+        r##"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <title>{name} - The CodeChat Editor</title>
+                {mathjax_tags}
+                <link rel="stylesheet" href="{base_path}/{codechat_editor_css}">
+            </head>
+            <body class="CodeChat-theme-light">
+                <a class="CodeChat-skip-link" href="#CodeChat-body">Skip to content</a>
+                <main id="CodeChat-body" class="CodeChat-doc-contents">
+        {body}
+                </main>
+            </body>
+        </html>"##
+    ))
+}
+
 /// ## Websockets
 ///
 /// Each CodeChat Editor IDE instance pairs with a CodeChat Editor Client
@@ -882,6 +1735,8 @@ async fn client_websocket(
         let mut is_closing = false;
         // True if a ping was sent, but a matching pong wasn't yet received.
         let mut sent_ping = false;
+        // When the most recent ping was sent.
+        let mut ping_time = Instant::now();
 
         loop {
             select! {
@@ -901,6 +1756,7 @@ async fn client_websocket(
                         break;
                     }
                     sent_ping = true;
+                    ping_time = Instant::now();
                 }
 
                 // Process a message received from the websocket.
@@ -920,6 +1776,7 @@ async fn client_websocket(
                                     // Acknowledge the matching pong to the ping
                                     // that was most recently sent.
                                     sent_ping = false;
+                                    perf::record(Operation::WebsocketRtt, 0, ping_time.elapsed());
                                 }
 
                                 // Decode text messages as JSON then dispatch
@@ -949,6 +1806,10 @@ async fn client_websocket(
                                                 // Check for an invalid message.
                                                 EditorMessageContents::LoadFile(_) |
                                                 EditorMessageContents::ClientHtml(_) |
+                                                EditorMessageContents::Diagnostics(_) |
+                                                EditorMessageContents::Capabilities(_) |
+                                                EditorMessageContents::Log(_) |
+                                                EditorMessageContents::Event(_) |
                                                 EditorMessageContents::Closed => {
                                                     let msg = format!("Invalid message {joint_message:?}");
                                                     error!("{msg}");
@@ -1011,7 +1872,7 @@ async fn client_websocket(
                             let timeout_tx = from_websocket_tx.clone();
                             let waiting_task = actix_rt::spawn(async move {
                                 sleep(REPLY_TIMEOUT).await;
-                                let msg = format!("Timeout: message id {} unacknowledged.", m.id);
+                                let msg = format!("{TIMEOUT_PREFIX} message id {} unacknowledged.", m.id);
                                 error!("{msg}");
                                 // Since the websocket failed to send a
                                 // `Result`, produce a timeout `Result` for it.
@@ -1074,6 +1935,13 @@ async fn client_websocket(
     Ok(response)
 }
 
+/// Set the number of times to resend a `LoadFile` request which the IDE
+/// didn't answer, waiting twice as long before each resend. After this, the
+/// Server reads the file from disk.
+pub fn set_load_file_retries(retries: u32) {
+    LOAD_FILE_RETRIES.store(retries, Ordering::Relaxed);
+}
+
 // ## Webserver core
 #[actix_web::main]
 pub async fn main(port: u16) -> std::io::Result<()> {
@@ -1088,30 +1956,197 @@ pub async fn run_server(port: u16) -> std::io::Result<()> {
     let _ = &*BUNDLED_FILES_MAP;
     let app_data = make_app_data(port);
     let app_data_server = app_data.clone();
-    let server = match HttpServer::new(move || configure_app(App::new(), &app_data_server))
-        .bind((IP_ADDRESS, port))
+    let workers = thread::available_parallelism()
+        .map_or(MIN_WORKERS, |workers| workers.get().max(MIN_WORKERS));
+    let server = match HttpServer::new(move || {
+        configure_app(App::new(), &app_data_server)
+            .wrap(from_fn(lan_access))
+            .wrap(from_fn(cache_control))
+            .wrap(from_fn(content_security_policy))
+    })
+    .keep_alive(KeepAlive::Timeout(KEEP_ALIVE))
+    .workers(workers)
+    .bind((IP_ADDRESS, port))
     {
-        Ok(server) => server.run(),
+        Ok(server) => server,
         Err(err) => {
             error!("Unable to bind to {IP_ADDRESS}:{port} - {err}");
             return Err(err);
         }
     };
+    // Also serve HTTP/2 over TLS, if requested.
+    let tls = match tls_settings() {
+        Some(tls_settings) => {
+            let server_config = load_server_config(&tls_settings).map_err(|err| {
+                error!("{err}");
+                std::io::Error::other(err)
+            })?;
+            Some((tls_settings.port, server_config))
+        }
+        None => None,
+    };
+    let server = match &tls {
+        Some((tls_port, server_config)) => {
+            let server = server
+                .bind_rustls_0_23((IP_ADDRESS, *tls_port), server_config.clone())
+                .inspect_err(|err| {
+                    error!("Unable to bind to {IP_ADDRESS}:{tls_port} - {err}");
+                })?;
+            println!("Serving HTTPS at https://{IP_ADDRESS}:{tls_port}/");
+            server
+        }
+        None => server,
+    };
+    // Behind a tunnel or proxy, browsers must first provide a one-time token.
+    if is_tunneled() || !base_path().is_empty() {
+        let token = start_tunnel_access().map_err(std::io::Error::other)?;
+        // Let the `stop` command and the IDE reach the Server.
+        start_local_secret(port).map_err(std::io::Error::other)?;
+        println!(
+            "Browsers reaching this Server through a tunnel or proxy must first open {}/?{TOKEN_NAME}={token}; this token works only once.",
+            base_path()
+        );
+    }
+    let server = if is_lan_sharing() {
+        let (lan_address, token) = start_lan_sharing().map_err(std::io::Error::other)?;
+        // Devices on the LAN use HTTPS if it's available.
+        let (server, url) = match &tls {
+            Some((tls_port, server_config)) => (
+                server
+                    .bind_rustls_0_23((lan_address, *tls_port), server_config.clone())
+                    .inspect_err(|err| {
+                        error!("Unable to bind to {lan_address}:{tls_port} - {err}");
+                    })?,
+                lan_url(lan_address, *tls_port, &token, true),
+            ),
+            None => (
+                server.bind((lan_address, port)).inspect_err(|err| {
+                    error!("Unable to bind to {lan_address}:{port} - {err}");
+                })?,
+                lan_url(lan_address, port, &token, false),
+            ),
+        };
+        println!("To view the rendered docs on another device on this network, open {url}");
+        match qr_code(&url) {
+            Ok(qr_code) => println!("{qr_code}"),
+            Err(err) => warn!("{err}"),
+        }
+        server.run()
+    } else {
+        server.run()
+    };
     // Store the server handle in the global state.
     *(app_data.server_handle.lock().unwrap()) = Some(server.handle());
+    // Save cached translations to disk when the Server is idle.
+    actix_rt::spawn(async {
+        let mut interval = actix_rt::time::interval(CACHE_PERSIST_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Writing files blocks; do this on a separate thread.
+            if let Err(err) =
+                actix_rt::task::spawn_blocking(|| persist_if_idle(CACHE_PERSIST_INTERVAL)).await
+            {
+                error!("Unable to save cached translations: {err}");
+            }
+        }
+    });
     // Start the server.
-    server.await
+    let result = server.await;
+    if cfg!(feature = "perf") {
+        info!(
+            "Performance metrics: {}",
+            serde_json::to_string(&perf::metrics()).unwrap_or_default()
+        );
+    }
+    result
 }
 
 pub fn configure_logger(level: LevelFilter) {
+    let config =
+        log4rs::config::load_config_file(server_file_path("log4rs.yml"), Default::default())
+            .unwrap();
+    // Also forward log records to subscribed IDEs.
+    log_forward::init(log4rs::Logger::new(config));
+    log::set_max_level(level);
+}
+
+// Return the path to a file installed with the Server, such as `log4rs.yml`.
+fn server_file_path(file_name: &str) -> PathBuf {
     #[cfg(not(debug_assertions))]
-    let l4rs = ROOT_PATH.clone();
+    let path = ROOT_PATH.clone();
     #[cfg(debug_assertions)]
-    let mut l4rs = ROOT_PATH.clone();
+    let mut path = ROOT_PATH.clone();
     #[cfg(debug_assertions)]
-    l4rs.push("server");
-    log4rs::init_file(l4rs.join("log4rs.yml"), Default::default()).unwrap();
-    log::set_max_level(level);
+    path.push("server");
+    path.join(file_name)
+}
+
+/// Check that the files the Server needs, which are installed with it, are
+/// present, returning a description of the first missing file.
+pub fn check_installed_files() -> Result<(), String> {
+    if !CLIENT_STATIC_PATH.is_dir() {
+        return Err(format!(
+            "The Client's static files directory {} is missing.",
+            CLIENT_STATIC_PATH.display()
+        ));
+    }
+    let log4rs_path = server_file_path("log4rs.yml");
+    if !log4rs_path.is_file() {
+        return Err(format!(
+            "The logging configuration {} is missing.",
+            log4rs_path.display()
+        ));
+    }
+    let hash_locations_path = server_file_path("hashLocations.json");
+    let bundled_files_map: HashMap<String, String> = fs::read_to_string(&hash_locations_path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()))
+        .map_err(|err| {
+            format!(
+                "Unable to read the bundled files list {}: {err}.",
+                hash_locations_path.display()
+            )
+        })?;
+    // Bundled files are relative to the Client's directory.
+    let client_path = CLIENT_STATIC_PATH.parent().unwrap_or(Path::new(""));
+    for name in [
+        "CodeChatEditorFramework.js",
+        "CodeChatEditor.js",
+        "CodeChatEditor.css",
+        "CodeChatEditorProject.css",
+    ] {
+        let Some(bundled_path) = bundled_files_map.get(name) else {
+            return Err(format!(
+                "The bundled files list {} doesn't include {name}.",
+                hash_locations_path.display()
+            ));
+        };
+        if !client_path.join(bundled_path).is_file() {
+            return Err(format!(
+                "The bundled file {} is missing.",
+                client_path.join(bundled_path).display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that a CodeChat Editor Server responds on `port`, waiting at most
+/// `timeout` seconds for its reply.
+pub fn ping_server(port: u16, timeout: u64) -> Result<(), String> {
+    let response = minreq::get(format!("http://{IP_ADDRESS}:{port}/ping"))
+        .with_timeout(timeout)
+        .send()
+        .map_err(|err| err.to_string())?;
+    let status_code = response.status_code;
+    let body = response.as_str().unwrap_or("Non-text body");
+    if status_code == 200 && body == "pong" {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unexpected response from server: {body}, status code = {status_code}"
+        ))
+    }
 }
 
 // Quoting the [docs](https://actix.rs/docs/application#shared-mutable-state),
@@ -1129,6 +2164,7 @@ fn make_app_data(port: u16) -> web::Data<AppState> {
         vscode_ide_queues: Arc::new(Mutex::new(HashMap::new())),
         vscode_client_queues: Arc::new(Mutex::new(HashMap::new())),
         vscode_connection_id: Arc::new(Mutex::new(HashSet::new())),
+        classroom: Arc::new(Classroom::new()),
     })
 }
 
@@ -1156,8 +2192,22 @@ where
         .service(vscode_ide_websocket)
         .service(vscode_client_websocket)
         .service(vscode_client_framework)
+        .service(classroom_endpoint)
+        .service(classroom_websocket)
+        .service(classroom_fs_endpoint)
+        .service(revision_endpoint)
+        .service(external_endpoint)
+        .service(proxy_endpoint)
+        .service(todos_endpoint)
+        .service(todo_events_endpoint)
+        .service(markdown_endpoint)
+        .service(markdown_buffer_endpoint)
+        .service(debug_lex_endpoint)
+        .service(metrics_endpoint)
         .service(ping)
         .service(stop)
+        .service(tunnel_token)
+        .service(logout)
         // Reroute to the filewatcher filesystem for typical user-requested
         // URLs.
         .route("/", web::get().to(filewatcher_root_fs_redirect))
@@ -1186,11 +2236,13 @@ fn url_to_path(url_string: &str, expected_prefix: &[&str]) -> Result<PathBuf, St
         Ok(url) => match url.path_segments() {
             None => Err(format!("Error: URL {url} cannot be a base.")),
             Some(path_segments) => {
-                // Make sure the path segments start with the `expected_prefix`.
+                // Make sure the path segments start with the `expected_prefix`,
+                // following the base path, if any.
                 let path_segments_vec: Vec<_> = path_segments.collect();
+                let path_segments_vec = strip_base_path(&path_segments_vec);
                 let prefix_equal = expected_prefix
                     .iter()
-                    .zip(&path_segments_vec)
+                    .zip(path_segments_vec)
                     .all(|(a, b)| a == b);
                 // The URL should have at least the expected prefix plus one
                 // more element (the connection ID).
@@ -1257,7 +2309,7 @@ fn path_to_url(prefix: &str, connection_id: &str, file_path: &Path) -> String {
 // Given a `Path`, transform it into a displayable HTML string (with any
 // necessary escaping).
 fn path_display(p: &Path) -> String {
-    escape_html(&simplified(p).to_string_lossy())
+    escape(&simplified(p).to_string_lossy(), EscapeContext::Text)
 }
 
 // Return a Not Found (404) error with the provided HTML body.
@@ -1267,6 +2319,20 @@ fn html_not_found(msg: &str) -> HttpResponse {
         .body(html_wrapper(msg))
 }
 
+// Return a Bad Request (400) error with the provided HTML body.
+fn html_bad_request(msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest()
+        .content_type(ContentType::html())
+        .body(html_wrapper(msg))
+}
+
+// Return a Forbidden (403) error with the provided HTML body.
+fn html_forbidden(msg: &str) -> HttpResponse {
+    HttpResponse::Forbidden()
+        .content_type(ContentType::html())
+        .body(html_wrapper(msg))
+}
+
 // Wrap the provided HTML body in DOCTYPE/html/head tags.
 fn html_wrapper(body: &str) -> String {
     formatdoc!(
@@ -1279,17 +2345,10 @@ fn html_wrapper(body: &str) -> String {
                 <title>The CodeChat Editor</title>
             </head>
             <body>
-                {body}
+                <main>
+                    {body}
+                </main>
             </body>
         </html>"#
     )
 }
-
-// Given text, escape it so it formats correctly as HTML. This is a translation
-// of Python's `html.escape` function.
-fn escape_html(unsafe_text: &str) -> String {
-    unsafe_text
-        .replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-}
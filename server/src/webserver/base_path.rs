@@ -0,0 +1,89 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `base_path.rs` -- Serve the CodeChat Editor below a path
+///
+/// A reverse proxy, such as nginx, may make the Server available below a path
+/// such as `/codechat`, removing this path from each request before passing
+/// it to the Server. So, the Server's endpoints don't change, but every
+/// absolute URL it provides -- for bundled and static files, the websockets,
+/// and the files the Client loads -- must start with this path, which
+/// `serve --base-path` provides. URLs the Client sends back, such as in a
+/// `CurrentFile` message, start with it as well; `url_to_path` removes it.
+// ## Imports
+//
+// ### Standard library
+use std::sync::RwLock;
+
+// ## Globals
+/// The path below which the proxy serves the Server, without a trailing slash;
+/// empty if there's no proxy.
+static BASE_PATH: RwLock<String> = RwLock::new(String::new());
+
+// ## Code
+/// Set the path below which a reverse proxy serves the Server, such as
+/// `/codechat`.
+pub fn set_base_path(base_path: &str) {
+    *BASE_PATH.write().unwrap() = normalize(base_path);
+}
+
+/// Return the base path: empty, or a path such as `/codechat`.
+pub fn base_path() -> String {
+    BASE_PATH.read().unwrap().clone()
+}
+
+/// Prepend the base path to the provided absolute path, such as `/static`.
+pub fn with_base_path(path: &str) -> String {
+    format!("{}{path}", base_path())
+}
+
+/// Remove the segments of the base path from the start of the provided path
+/// segments, if present.
+pub fn strip_base_path<'a>(path_segments: &'a [&'a str]) -> &'a [&'a str] {
+    let base_path = base_path();
+    let base_segments: Vec<&str> = base_path.split('/').filter(|s| !s.is_empty()).collect();
+    let has_base_path = path_segments.len() >= base_segments.len()
+        && base_segments.iter().zip(path_segments).all(|(a, b)| a == b);
+    if has_base_path {
+        &path_segments[base_segments.len()..]
+    } else {
+        path_segments
+    }
+}
+
+// Provide a leading slash but no trailing slash, so that `codechat/` becomes
+// `/codechat`; `/` becomes empty.
+fn normalize(base_path: &str) -> String {
+    let base_path = base_path.trim_matches('/');
+    if base_path.is_empty() {
+        String::new()
+    } else {
+        format!("/{base_path}")
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(""), "");
+        assert_eq!(normalize("/"), "");
+        assert_eq!(normalize("codechat/"), "/codechat");
+        assert_eq!(normalize("/a/b"), "/a/b");
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `cache_control.rs` -- Tell browsers how long to cache responses
+///
+/// esbuild names each file it bundles (those listed in `BUNDLED_FILES_MAP`,
+/// along with the chunks they import) using a hash of its contents, so a
+/// changed file has a new name. Browsers may therefore cache these files
+/// forever. In contrast, pages showing documents change whenever their file
+/// does, so browsers must check with the Server before reusing them. Other
+/// static files keep the browser's default caching, revalidated using their
+/// modification time.
+// ## Imports
+//
+// ### Third-party
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, CacheControl, CacheDirective, TryIntoHeaderPair},
+    middleware::Next,
+    Error,
+};
+
+// ## Globals
+/// The URL prefix of the files bundled by esbuild.
+const BUNDLED_PREFIX: &str = "/static/bundled/";
+
+/// How long browsers may cache bundled files: one year, the longest period
+/// which browsers reliably honor.
+const BUNDLED_MAX_AGE: u32 = 365 * 24 * 60 * 60;
+
+// ## Code
+/// ### Middleware
+///
+/// Add a `Cache-Control` header to responses which don't have one.
+pub async fn cache_control(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let path = req.path().to_string();
+    let mut res = next
+        .call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)?;
+    let headers = res.headers();
+    if headers.contains_key(header::CACHE_CONTROL) {
+        return Ok(res);
+    }
+    let is_html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if let Some(cache_control) = cache_control_for(&path, is_html) {
+        if let Ok((name, value)) = cache_control.try_into_pair() {
+            res.headers_mut().insert(name, value);
+        }
+    }
+    Ok(res)
+}
+
+// Return the `Cache-Control` header for a response to a request for `path`,
+// or `None` to use the browser's default.
+fn cache_control_for(path: &str, is_html: bool) -> Option<CacheControl> {
+    if path.starts_with(BUNDLED_PREFIX) {
+        Some(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(BUNDLED_MAX_AGE),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ]))
+    } else if is_html {
+        Some(CacheControl(vec![CacheDirective::NoCache]))
+    } else {
+        None
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::cache_control_for;
+
+    #[test]
+    fn test_cache_control_for() {
+        assert_eq!(
+            cache_control_for("/static/bundled/CodeChatEditor-ABCD1234.js", false)
+                .unwrap()
+                .to_string(),
+            "public, max-age=31536000, immutable"
+        );
+        // Document pages must be revalidated.
+        assert_eq!(
+            cache_control_for("/fw/fsb/home/me/test.py", true)
+                .unwrap()
+                .to_string(),
+            "no-cache"
+        );
+        // Other files use the browser's default.
+        assert_eq!(
+            cache_control_for("/static/mathjax/tex-chtml.js", false),
+            None
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `capabilities.rs` -- Tell the Client which optional features are enabled
+///
+/// Some of the Client's features are optional: the Server may not provide
+/// them, or the user may disable them using `serve --disable-feature`. When a
+/// connection starts, the Server sends a `Capabilities` message listing the
+/// features enabled for it, so that the Client shows only the UI for these
+/// features. Handlers for a feature's messages use `is_enabled` to make the
+/// same decision.
+// ## Imports
+//
+// ### Standard library
+use std::sync::RwLock;
+
+// ### Third-party
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use super::classroom;
+
+// ## Data structures
+/// An optional feature of the Client.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Feature {
+    /// Check the spelling of doc blocks, using the browser's spellchecker.
+    Spellcheck,
+    /// Run code blocks.
+    RunCode,
+    /// Comment on doc blocks.
+    Comments,
+    /// Share the current file and position with others, as in classroom mode.
+    Collaboration,
+}
+
+/// The features enabled for a connection, sent to the Client in the
+/// `Capabilities` message.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Capabilities {
+    pub spellcheck: bool,
+    pub run_code: bool,
+    pub comments: bool,
+    pub collaboration: bool,
+}
+
+// ## Globals
+/// The features the user disabled.
+static DISABLED_FEATURES: RwLock<Vec<Feature>> = RwLock::new(Vec::new());
+
+// ## Code
+/// Disable the provided features for all connections.
+pub fn set_disabled_features(features: Vec<Feature>) {
+    *DISABLED_FEATURES.write().unwrap() = features;
+}
+
+/// Return true if the provided feature is enabled.
+pub fn is_enabled(feature: Feature) -> bool {
+    is_available(feature) && !DISABLED_FEATURES.read().unwrap().contains(&feature)
+}
+
+/// Return the features enabled for a new connection.
+pub fn capabilities() -> Capabilities {
+    capabilities_for(&DISABLED_FEATURES.read().unwrap())
+}
+
+// Return the features enabled when the provided features are disabled.
+fn capabilities_for(disabled: &[Feature]) -> Capabilities {
+    let enabled = |feature| is_available(feature) && !disabled.contains(&feature);
+    Capabilities {
+        spellcheck: enabled(Feature::Spellcheck),
+        run_code: enabled(Feature::RunCode),
+        comments: enabled(Feature::Comments),
+        collaboration: enabled(Feature::Collaboration),
+    }
+}
+
+// Return true if the Server provides the given feature. Running code and
+// comments aren't provided yet.
+fn is_available(feature: Feature) -> bool {
+    match feature {
+        Feature::Spellcheck => true,
+        Feature::RunCode | Feature::Comments => false,
+        Feature::Collaboration => classroom::is_enabled(),
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{capabilities_for, Feature};
+
+    #[test]
+    fn test_capabilities_for() {
+        assert!(capabilities_for(&[]).spellcheck);
+        let capabilities = capabilities_for(&[Feature::Spellcheck]);
+        assert!(!capabilities.spellcheck);
+        // Features the Server doesn't provide are never enabled.
+        assert!(!capabilities.run_code);
+        assert!(!capabilities.comments);
+    }
+}
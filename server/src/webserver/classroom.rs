@@ -0,0 +1,486 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `classroom.rs` -- Let students follow an instructor's editor
+///
+/// In classroom mode, one instructor connection broadcasts the file it shows
+/// and its scroll position to any number of students, so that a class can
+/// follow along live. Starting the Server with `--instructor-token <token>`
+/// enables classroom mode; this requires `--share-lan`, since students can't
+/// otherwise reach the Server. A connection becomes the instructor's by providing
+/// this token as the `instructor` query parameter: either when opening a file
+/// in the browser (`/fw/fsb/path/to/file?instructor=<token>`) or when the IDE
+/// connects (for VSCode, using the extension's Instructor Token setting). The
+/// most recent such connection is the instructor.
+///
+/// Students open `/classroom`, which shows a read-only rendering of the
+/// instructor's current file, as last saved, and follows the instructor's
+/// scrolling. Students may only view this file, along with the other files
+/// (such as images) in its project, or in its directory if it isn't part of a
+/// project.
+///
+/// Sharing on the LAN (see [`lan_share.rs`](lan_share.rs)) also uses these pages.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashMap,
+    path::{self, Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, RwLock},
+};
+
+// ### Third-party
+use actix_web::{
+    error::{Error, ErrorNotFound},
+    get,
+    http::header::ContentType,
+    web, HttpRequest, HttpResponse,
+};
+use actix_ws::Message;
+use futures_util::StreamExt;
+use indoc::formatdoc;
+use log::{info, warn};
+use serde::Serialize;
+use tokio::{select, sync::broadcast};
+
+// ### Local
+use super::{
+    base_path::with_base_path,
+    capabilities::{self, Feature},
+    csp::inline_script,
+    html_not_found,
+    lan_share::{is_lan_sharing, is_token_match},
+    path_display, path_to_url, read_only_page, AppState,
+};
+use crate::processing::{
+    escape::{escape, EscapeContext},
+    find_project_root,
+};
+
+// ## Globals
+/// The token which identifies the instructor; classroom mode is disabled if
+/// this isn't set.
+static INSTRUCTOR_TOKEN: RwLock<Option<String>> = RwLock::new(None);
+
+/// The number of positions buffered for each student; a student who falls
+/// further behind skips to more recent positions.
+const BROADCAST_CAPACITY: usize = 16;
+
+/// The URL prefix for files shown to students.
+const CLASSROOM_FS_PREFIX: &str = "/classroom";
+
+/// The script which shows the instructor's file in the students' page.
+const CLASSROOM_SCRIPT: &str = r#"
+const iframe = document.getElementById("CodeChat-classroom");
+const status = document.getElementById("CodeChat-classroom-status");
+let url = null;
+let scroll_position = null;
+const scroll = () => {
+    const root = iframe.contentDocument?.documentElement;
+    if (root && scroll_position !== null) {
+        root.scrollTop = scroll_position * (root.scrollHeight - root.clientHeight);
+    }
+};
+iframe.addEventListener("load", scroll);
+const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+const websocket = new WebSocket(`${protocol}//${location.host}/classroom/ws`);
+websocket.addEventListener("message", (event) => {
+    const message = JSON.parse(event.data);
+    scroll_position = message.scroll_position;
+    if (message.url !== null && message.url !== url) {
+        url = message.url;
+        status.hidden = true;
+        iframe.src = url;
+    } else {
+        scroll();
+    }
+});
+websocket.addEventListener("close", () => {
+    status.textContent = "The connection to the Server closed; reload this page to reconnect.";
+    status.hidden = false;
+});
+"#;
+
+// ## Data structures
+/// What the instructor is showing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClassroomPosition {
+    /// The file the instructor is viewing.
+    pub file_path: Option<PathBuf>,
+    /// The normalized vertical scroll position in this file, where 0 = top and
+    /// 1 = bottom.
+    pub scroll_position: Option<f32>,
+}
+
+/// The message sent to students, in JSON, when the instructor's position
+/// changes.
+#[derive(Debug, Serialize, PartialEq)]
+struct StudentMessage {
+    /// The URL of the instructor's current file.
+    url: Option<String>,
+    scroll_position: Option<f32>,
+}
+
+/// The classroom shared by all connections.
+pub struct Classroom {
+    /// Sends each change in the instructor's position to the students.
+    tx: broadcast::Sender<ClassroomPosition>,
+    state: Mutex<ClassroomState>,
+}
+
+#[derive(Default)]
+struct ClassroomState {
+    /// The connection ID of the instructor.
+    instructor: Option<String>,
+    /// The instructor's most recent position, for students who join later.
+    position: ClassroomPosition,
+}
+
+// ## Code
+/// Enable classroom mode, using `token` to identify the instructor, or disable
+/// it if `token` is `None`.
+pub fn set_instructor_token(token: Option<String>) {
+    *INSTRUCTOR_TOKEN.write().unwrap() = token;
+}
+
+/// Return true if classroom mode is enabled. Sharing on the LAN also uses the
+/// students' pages. The user may still disable it; see `capabilities.rs`.
+pub(super) fn is_enabled() -> bool {
+    INSTRUCTOR_TOKEN.read().unwrap().is_some() || is_lan_sharing()
+}
+
+/// Determine if the provided request comes from the instructor, based on its
+/// `instructor` query parameter. Return an error if this parameter is provided
+/// but doesn't match the instructor's token. When sharing on the LAN without an
+/// instructor token, every request is the instructor's, so that the shared
+/// screen follows the file most recently opened.
+pub fn is_instructor_request(req: &HttpRequest) -> Result<bool, String> {
+    let query_params = web::Query::<HashMap<String, String>>::from_query(req.query_string());
+    let Some(token) = query_params
+        .ok()
+        .and_then(|query| query.get("instructor").cloned())
+    else {
+        return Ok(INSTRUCTOR_TOKEN.read().unwrap().is_none() && is_lan_sharing());
+    };
+    match &*INSTRUCTOR_TOKEN.read().unwrap() {
+        Some(instructor_token) if is_token_match(&token, instructor_token) => Ok(true),
+        Some(_) => Err("Invalid instructor token.".to_string()),
+        None => Err(
+            "Classroom mode is disabled; start the Server with --instructor-token to enable it."
+                .to_string(),
+        ),
+    }
+}
+
+impl Default for Classroom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Classroom {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Classroom {
+            tx,
+            state: Mutex::new(ClassroomState::default()),
+        }
+    }
+
+    /// Make the provided connection the instructor.
+    pub fn set_instructor(&self, connection_id: &str) {
+        info!("Connection {connection_id} is now the instructor.");
+        self.state.lock().unwrap().instructor = Some(connection_id.to_string());
+    }
+
+    /// Record that the provided connection now shows `file_path`; if it's the
+    /// instructor, the students follow.
+    pub fn set_current_file(&self, connection_id: &str, file_path: &Path) {
+        // Store a canonical path, so that students' requests can be compared
+        // with it.
+        let file_path = file_path
+            .canonicalize()
+            .unwrap_or_else(|_| file_path.to_path_buf());
+        self.update(connection_id, |position| {
+            position.file_path = Some(file_path);
+            position.scroll_position = None;
+        });
+    }
+
+    /// Record that the provided connection scrolled `file_path`; if it's the
+    /// instructor's current file, the students follow.
+    pub fn set_scroll_position(&self, connection_id: &str, file_path: &Path, scroll_position: f32) {
+        let file_path = file_path
+            .canonicalize()
+            .unwrap_or_else(|_| file_path.to_path_buf());
+        self.update(connection_id, |position| {
+            if position.file_path.as_ref() == Some(&file_path) {
+                position.scroll_position = Some(scroll_position);
+            }
+        });
+    }
+
+    /// Return the instructor's current position, along with a receiver for
+    /// later changes.
+    pub fn subscribe(&self) -> (ClassroomPosition, broadcast::Receiver<ClassroomPosition>) {
+        // Subscribe while holding the lock, so that no change is missed.
+        let state = self.state.lock().unwrap();
+        (state.position.clone(), self.tx.subscribe())
+    }
+
+    /// Return the instructor's current file.
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.state.lock().unwrap().position.file_path.clone()
+    }
+
+    /// Return true if students may view `file_path`, which must be canonical.
+    pub fn is_shared(&self, file_path: &Path) -> bool {
+        let Some(current_file) = self.current_file() else {
+            return false;
+        };
+        // Share the current file's project, or its directory if it's not in a
+        // project.
+        let shared_dir = find_project_root(&current_file).unwrap_or_else(|| {
+            current_file
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        });
+        let shared_dir = shared_dir.canonicalize().unwrap_or(shared_dir);
+        file_path == current_file
+            || (!shared_dir.as_os_str().is_empty() && file_path.starts_with(shared_dir))
+    }
+
+    // Apply `change` to the position if `connection_id` is the instructor,
+    // then send the students the new position.
+    fn update(&self, connection_id: &str, change: impl FnOnce(&mut ClassroomPosition)) {
+        let mut state = self.state.lock().unwrap();
+        if state.instructor.as_deref() != Some(connection_id) {
+            return;
+        }
+        let old_position = state.position.clone();
+        change(&mut state.position);
+        if state.position != old_position {
+            // This fails only if no students are connected, which is fine.
+            let _ = self.tx.send(state.position.clone());
+        }
+    }
+}
+
+// Convert a position to the message sent to students.
+fn student_message(position: &ClassroomPosition) -> StudentMessage {
+    StudentMessage {
+        url: position
+            .file_path
+            .as_ref()
+            .map(|file_path| path_to_url(&with_base_path(CLASSROOM_FS_PREFIX), "fs", file_path)),
+        scroll_position: position.scroll_position,
+    }
+}
+
+/// ### Endpoints
+///
+/// Serve the students' page, which follows the instructor.
+#[get("/classroom")]
+pub async fn classroom_endpoint() -> HttpResponse {
+    if !capabilities::is_enabled(Feature::Collaboration) {
+        return html_not_found("<p>Classroom mode is disabled.</p>");
+    }
+    let classroom_script = inline_script("", CLASSROOM_SCRIPT);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(formatdoc!(
+            r#"
+            <!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta charset="UTF-8">
+                    <meta name="viewport" content="width=device-width, initial-scale=1">
+                    <title>Classroom - The CodeChat Editor</title>
+                </head>
+                <body style="margin: 0px; padding: 0px; overflow: hidden">
+                    <p id="CodeChat-classroom-status" role="status" style="position: fixed; margin: 1em;">Waiting for the instructor...</p>
+                    <main>
+                        <iframe id="CodeChat-classroom" title="The instructor's file" style="width:100%; height:100vh; border:none;"></iframe>
+                    </main>
+                    {classroom_script}
+                </body>
+            </html>"#
+        ))
+}
+
+/// Send students each change in the instructor's position. Students can't
+/// send anything; messages they send are ignored.
+#[get("/classroom/ws")]
+pub async fn classroom_websocket(
+    req: HttpRequest,
+    body: web::Payload,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    if !capabilities::is_enabled(Feature::Collaboration) {
+        return Err(ErrorNotFound("Classroom mode is disabled."));
+    }
+    let (response, mut session, mut msg_stream) = actix_ws::handle(&req, body)?;
+    let (position, mut rx) = app_state.classroom.subscribe();
+    actix_rt::spawn(async move {
+        let mut position = position;
+        loop {
+            let text = serde_json::to_string(&student_message(&position)).unwrap();
+            if session.text(text).await.is_err() {
+                return;
+            }
+            position = loop {
+                select! {
+                    received = rx.recv() => match received {
+                        Ok(position) => break position,
+                        // This student fell behind; the next position received
+                        // is more recent.
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    msg = msg_stream.next() => match msg {
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(Message::Close(reason))) => {
+                            let _ = session.close(reason).await;
+                            return;
+                        }
+                        Some(Ok(_)) => (),
+                        Some(Err(_)) | None => return,
+                    },
+                }
+            };
+        }
+    });
+    Ok(response)
+}
+
+/// Serve a file to students: a read-only rendering of the instructor's current
+/// file, or another file it refers to, such as an image.
+#[get("/classroom/fs/{file_path:.*}")]
+pub async fn classroom_fs_endpoint(
+    file_path: web::Path<String>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> HttpResponse {
+    if !capabilities::is_enabled(Feature::Collaboration) {
+        return html_not_found("<p>Classroom mode is disabled.</p>");
+    }
+    // Only serve files which exist, using their canonical path to prevent
+    // requests such as `shared/dir/../../secret.txt` from escaping the shared
+    // directory.
+    let Some(file_path) = PathBuf::from_str(&file_path)
+        .ok()
+        .and_then(|file_path| path::absolute(file_path).ok())
+        .and_then(|file_path| file_path.canonicalize().ok())
+    else {
+        return html_not_found(&format!(
+            "<p>The requested path <code>{}</code> is not valid.</p>",
+            escape(&file_path, EscapeContext::Text)
+        ));
+    };
+    if !app_state.classroom.is_shared(&file_path) {
+        warn!("A student requested {file_path:?}, which isn't shared.");
+        return html_not_found(&format!(
+            "<p>The file {} isn't shared with students.</p>",
+            path_display(&file_path)
+        ));
+    }
+
+    if app_state.classroom.current_file().as_ref() == Some(&file_path) {
+        if let Ok(file_contents) = tokio::fs::read_to_string(&file_path).await {
+            if let Some(page) = read_only_page(&file_path, &file_contents) {
+                return HttpResponse::Ok()
+                    .content_type(ContentType::html())
+                    .body(page);
+            }
+        }
+    }
+    match actix_files::NamedFile::open_async(&file_path).await {
+        Ok(named_file) => named_file.into_response(&req),
+        Err(err) => html_not_found(&format!(
+            "<p>Error opening file {}: {}.</p>",
+            path_display(&file_path),
+            escape(&err.to_string(), EscapeContext::Text)
+        )),
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{student_message, Classroom, ClassroomPosition, StudentMessage};
+
+    #[test]
+    fn test_classroom() {
+        let classroom = Classroom::new();
+        let (position, mut rx) = classroom.subscribe();
+        assert_eq!(position, ClassroomPosition::default());
+
+        // Only the instructor's changes are broadcast.
+        classroom.set_current_file("1", Path::new("/a/b.py"));
+        assert!(rx.try_recv().is_err());
+        classroom.set_instructor("1");
+        classroom.set_current_file("2", Path::new("/a/c.py"));
+        assert!(rx.try_recv().is_err());
+        classroom.set_current_file("1", Path::new("/a/b.py"));
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ClassroomPosition {
+                file_path: Some(PathBuf::from("/a/b.py")),
+                scroll_position: None,
+            }
+        );
+
+        // Scrolling applies only to the current file.
+        classroom.set_scroll_position("1", Path::new("/a/other.py"), 0.5);
+        assert!(rx.try_recv().is_err());
+        classroom.set_scroll_position("1", Path::new("/a/b.py"), 0.5);
+        let position = rx.try_recv().unwrap();
+        assert_eq!(position.scroll_position, Some(0.5));
+        // An unchanged position isn't sent again.
+        classroom.set_scroll_position("1", Path::new("/a/b.py"), 0.5);
+        assert!(rx.try_recv().is_err());
+
+        // Students who join later receive the current position.
+        assert_eq!(classroom.subscribe().0, position);
+
+        // Students may only view files near the current file.
+        assert!(classroom.is_shared(Path::new("/a/b.py")));
+        assert!(classroom.is_shared(Path::new("/a/images/c.png")));
+        assert!(!classroom.is_shared(Path::new("/etc/passwd")));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_student_message() {
+        assert_eq!(
+            student_message(&ClassroomPosition {
+                file_path: Some(PathBuf::from("/a/b c.py")),
+                scroll_position: Some(0.25),
+            }),
+            StudentMessage {
+                url: Some("/classroom/fs//a/b%20c.py".to_string()),
+                scroll_position: Some(0.25),
+            }
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `csp.rs` -- Add a Content Security Policy to the Server's pages
+///
+/// A [Content Security Policy](https://developer.mozilla.org/en-US/docs/Web/HTTP/CSP)
+/// (CSP) tells the browser which scripts, frames, and other content a page may
+/// load, limiting the damage done by HTML injected into it. The
+/// `content_security_policy` middleware adds a CSP to each HTML page the Server
+/// sends. Pages create the inline scripts which bootstrap them using
+/// `inline_script`, which allows each by its hash, and load scripts from other
+/// sites using `external_script`, which allows that site. The policy is never
+/// derived from the page itself: a script in a doc block, even one rendered
+/// without sanitizing it, isn't allowed to run. Other pages may only show these
+/// pages in a frame if they come from the Server or from VSCode's webview; use
+/// `serve --frame-ancestor <source>` to embed the Client elsewhere.
+///
+/// The page VSCode's webview shows (the simple viewer, which contains only an
+/// iframe holding the Client) isn't served over HTTP, so it provides its
+/// policy in a `<meta>` element instead; see `simple_viewer_policy`.
+// ## Imports
+//
+// ### Standard library
+use std::{collections::BTreeSet, sync::RwLock};
+
+// ### Third-party
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+// ## Globals
+/// The sources which may show the Server's pages in a frame, used when none
+/// are provided.
+const DEFAULT_FRAME_ANCESTORS: [&str; 2] = ["'self'", "vscode-webview:"];
+
+/// The sources which may show the Server's pages in a frame; if empty, use
+/// `DEFAULT_FRAME_ANCESTORS`.
+static FRAME_ANCESTORS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// The sources of the scripts the Server's pages may run: the hashes of the
+/// inline scripts created by `inline_script`, and the sites of the scripts
+/// loaded by `external_script`.
+static SCRIPT_SOURCES: RwLock<BTreeSet<String>> = RwLock::new(BTreeSet::new());
+
+// ## Code
+/// Let the provided sources, such as `https://example.com`, show the Server's
+/// pages in a frame; if empty, allow the Server and VSCode's webview.
+pub fn set_frame_ancestors(frame_ancestors: Vec<String>) {
+    *FRAME_ANCESTORS.write().unwrap() = frame_ancestors;
+}
+
+/// Return a script element with the provided attributes, such as
+/// ` type="module"`, which runs `contents`; allow it in the policy of every
+/// page. Since each distinct script remains allowed, `contents` must not vary
+/// between requests; provide data such as a connection's ID in a
+/// `<script type="application/json">` element instead.
+pub fn inline_script(attributes: &str, contents: &str) -> String {
+    SCRIPT_SOURCES.write().unwrap().insert(format!(
+        "'sha256-{}'",
+        STANDARD.encode(Sha256::digest(contents.as_bytes()))
+    ));
+    format!("<script{attributes}>{contents}</script>")
+}
+
+/// Return a script element which loads the script at `src` from another
+/// site; allow scripts from that site in the policy of every page.
+pub fn external_script(src: &str) -> String {
+    if let Ok(url) = Url::parse(src) {
+        SCRIPT_SOURCES
+            .write()
+            .unwrap()
+            .insert(url.origin().ascii_serialization());
+    }
+    format!(r#"<script src="{src}"></script>"#)
+}
+
+/// Return the policy for the simple viewer, which shows the Client from
+/// `client_origin` in an iframe.
+pub fn simple_viewer_policy(client_origin: &str) -> String {
+    format!("default-src 'none'; style-src 'unsafe-inline'; frame-src {client_origin}")
+}
+
+/// ### Middleware
+///
+/// Add a `Content-Security-Policy` header to HTML responses which don't have
+/// one.
+pub async fn content_security_policy(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let host = req.connection_info().host().to_string();
+    let res = next.call(req).await?;
+    let headers = res.headers();
+    let is_html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if !is_html || headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let policy = policy(&SCRIPT_SOURCES.read().unwrap(), &host, &frame_ancestors());
+    let mut res = res.map_into_boxed_body();
+    if let Ok(value) = HeaderValue::from_str(&policy) {
+        res.headers_mut()
+            .insert(header::CONTENT_SECURITY_POLICY, value);
+    }
+    Ok(res)
+}
+
+// Return the sources which may show the Server's pages in a frame.
+fn frame_ancestors() -> Vec<String> {
+    let frame_ancestors = FRAME_ANCESTORS.read().unwrap();
+    if frame_ancestors.is_empty() {
+        DEFAULT_FRAME_ANCESTORS.map(str::to_string).to_vec()
+    } else {
+        frame_ancestors.clone()
+    }
+}
+
+// Return the policy for a page served from `host` which may run scripts from
+// `script_sources`, in addition to those from the Server.
+fn policy(script_sources: &BTreeSet<String>, host: &str, frame_ancestors: &[String]) -> String {
+    // WebAssembly, used to render Graphviz diagrams and to preview files,
+    // requires `wasm-unsafe-eval`.
+    let script_sources: Vec<&str> = ["'self'", "'wasm-unsafe-eval'"]
+        .into_iter()
+        .chain(script_sources.iter().map(String::as_str))
+        .collect();
+    [
+        "default-src 'self'".to_string(),
+        format!("script-src {}", script_sources.join(" ")),
+        // CodeMirror and TinyMCE add inline styles.
+        "style-src * 'unsafe-inline'".to_string(),
+        // Doc blocks may show images, media, and pages from anywhere.
+        "img-src * data: blob:".to_string(),
+        "media-src * data: blob:".to_string(),
+        "frame-src * data: blob:".to_string(),
+        "font-src 'self' data:".to_string(),
+        format!("connect-src 'self' ws://{host} wss://{host}"),
+        "object-src 'none'".to_string(),
+        "base-uri 'self'".to_string(),
+        format!("frame-ancestors {}", frame_ancestors.join(" ")),
+    ]
+    .join("; ")
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{external_script, inline_script, policy, simple_viewer_policy, SCRIPT_SOURCES};
+
+    #[test]
+    fn test_policy() {
+        assert_eq!(
+            inline_script(r#" type="module""#, ""),
+            r#"<script type="module"></script>"#
+        );
+        assert_eq!(
+            external_script("https://unpkg.com/mocha/mocha.js"),
+            r#"<script src="https://unpkg.com/mocha/mocha.js"></script>"#
+        );
+        let script_sources = SCRIPT_SOURCES.read().unwrap();
+        assert!(script_sources.contains("'sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU='"));
+        assert!(script_sources.contains("https://unpkg.com"));
+
+        let policy = policy(
+            &BTreeSet::from([
+                "'sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU='".to_string(),
+                "https://unpkg.com".to_string(),
+            ]),
+            "127.0.0.1:8080",
+            &["'self'".to_string()],
+        );
+        assert!(policy.contains(
+            "script-src 'self' 'wasm-unsafe-eval' 'sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=' https://unpkg.com;"
+        ));
+        assert!(policy.contains("connect-src 'self' ws://127.0.0.1:8080 wss://127.0.0.1:8080;"));
+        assert!(policy.ends_with("frame-ancestors 'self'"));
+    }
+
+    #[test]
+    fn test_simple_viewer_policy() {
+        assert_eq!(
+            simple_viewer_policy("http://127.0.0.1:8080"),
+            "default-src 'none'; style-src 'unsafe-inline'; frame-src http://127.0.0.1:8080"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `events.rs` -- Notify the IDE of changes in the Server's state
+///
+/// An IDE may show the Server's state, such as a status bar item indicating
+/// that a translation is in progress. Rather than asking for this state, the
+/// IDE sends a `SubscribeEvents` message; the Server then sends it an `Event`
+/// message when this state changes:
+///
+/// *   the number of files being translated (progress);
+/// *   whether the Client is connected;
+/// *   the problems found in a file when it was translated (diagnostics).
+///
+/// On subscribing, the IDE receives the current translation and connection
+/// state.
+// ## Imports
+//
+// ### Standard library
+use std::collections::{HashMap, HashSet};
+
+// ### Third-party
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+// ### Local
+use super::{EditorMessage, EditorMessageContents, MessageResult, MESSAGE_ID_INCREMENT};
+use crate::processing::diagnostics::Diagnostic;
+
+// ## Data structures
+/// A change in the Server's state, sent to the IDE in an `Event` message.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ServerEvent {
+    /// The number of files being translated; 0 once all translations
+    /// finished.
+    Translating(usize),
+    /// True when the Client connected; false when it disconnected.
+    ClientConnected(bool),
+    /// The problems found when the provided file was translated; an empty list
+    /// means none were found.
+    Diagnostics {
+        file_path: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+/// The events sent to one IDE, along with the state they report, so that only
+/// changes are sent.
+pub struct EventPublisher {
+    subscribed: bool,
+    // The IDs of `Event` messages the IDE hasn't yet acknowledged.
+    message_ids: HashSet<u64>,
+    translating: usize,
+    client_connected: bool,
+    // The most recently sent diagnostics for each file.
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+}
+
+// ## Code
+impl EventPublisher {
+    pub fn new() -> Self {
+        EventPublisher {
+            subscribed: false,
+            message_ids: HashSet::new(),
+            translating: 0,
+            client_connected: false,
+            diagnostics: HashMap::new(),
+        }
+    }
+
+    /// Start or stop sending events to the IDE. On subscribing, send the
+    /// current state.
+    pub async fn subscribe(
+        &mut self,
+        to_ide_tx: &Sender<EditorMessage>,
+        id: &mut f64,
+        subscribed: bool,
+    ) {
+        self.subscribed = subscribed;
+        self.diagnostics.clear();
+        self.send(to_ide_tx, id, ServerEvent::Translating(self.translating))
+            .await;
+        self.send(
+            to_ide_tx,
+            id,
+            ServerEvent::ClientConnected(self.client_connected),
+        )
+        .await;
+    }
+
+    /// Return true if the IDE's `Result` with the provided ID acknowledges an
+    /// `Event` message. If the IDE reports an error, stop sending events.
+    pub fn acknowledged(&mut self, id: f64, result: &MessageResult) -> bool {
+        if !self.message_ids.remove(&id.to_bits()) {
+            return false;
+        }
+        if result.is_err() {
+            self.subscribed = false;
+            self.message_ids.clear();
+        }
+        true
+    }
+
+    /// Report the number of files being translated.
+    pub async fn translating(
+        &mut self,
+        to_ide_tx: &Sender<EditorMessage>,
+        id: &mut f64,
+        count: usize,
+    ) {
+        if count != self.translating {
+            self.translating = count;
+            self.send(to_ide_tx, id, ServerEvent::Translating(count))
+                .await;
+        }
+    }
+
+    /// Report whether the Client is connected.
+    pub async fn client_connected(
+        &mut self,
+        to_ide_tx: &Sender<EditorMessage>,
+        id: &mut f64,
+        connected: bool,
+    ) {
+        if connected != self.client_connected {
+            self.client_connected = connected;
+            self.send(to_ide_tx, id, ServerEvent::ClientConnected(connected))
+                .await;
+        }
+    }
+
+    /// Report the problems found when the provided file was translated.
+    pub async fn diagnostics(
+        &mut self,
+        to_ide_tx: &Sender<EditorMessage>,
+        id: &mut f64,
+        file_path: &str,
+        diagnostics: &[Diagnostic],
+    ) {
+        if !self.subscribed
+            || self.diagnostics.get(file_path).map(Vec::as_slice) == Some(diagnostics)
+            || (diagnostics.is_empty() && !self.diagnostics.contains_key(file_path))
+        {
+            return;
+        }
+        self.diagnostics
+            .insert(file_path.to_string(), diagnostics.to_vec());
+        self.send(
+            to_ide_tx,
+            id,
+            ServerEvent::Diagnostics {
+                file_path: file_path.to_string(),
+                diagnostics: diagnostics.to_vec(),
+            },
+        )
+        .await;
+    }
+
+    // Send an event to the IDE, if it subscribed.
+    async fn send(&mut self, to_ide_tx: &Sender<EditorMessage>, id: &mut f64, event: ServerEvent) {
+        if !self.subscribed {
+            return;
+        }
+        self.message_ids.insert(id.to_bits());
+        if let Err(err) = to_ide_tx
+            .send(EditorMessage {
+                id: *id,
+                message: EditorMessageContents::Event(event),
+            })
+            .await
+        {
+            error!("Unable to enqueue: {err}");
+        }
+        *id += MESSAGE_ID_INCREMENT;
+    }
+}
+
+impl Default for EventPublisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::{EventPublisher, ServerEvent};
+    use crate::cast;
+    use crate::processing::diagnostics::{Diagnostic, Severity};
+    use crate::webserver::{EditorMessageContents, ResultOkTypes, MESSAGE_ID_INCREMENT};
+
+    #[actix_web::test]
+    async fn test_event_publisher() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let mut id = 0.0;
+        let mut events = EventPublisher::new();
+        // Nothing is sent before subscribing.
+        events.translating(&tx, &mut id, 1).await;
+        assert!(rx.try_recv().is_err());
+
+        // Subscribing sends the current state.
+        events.subscribe(&tx, &mut id, true).await;
+        let mut next_event = || {
+            let em = rx.try_recv().unwrap();
+            (em.id, cast!(em.message, EditorMessageContents::Event))
+        };
+        assert_eq!(next_event(), (0.0, ServerEvent::Translating(1)));
+        assert_eq!(
+            next_event(),
+            (MESSAGE_ID_INCREMENT, ServerEvent::ClientConnected(false))
+        );
+        assert_eq!(id, 2.0 * MESSAGE_ID_INCREMENT);
+
+        // Only changes are sent.
+        events.translating(&tx, &mut id, 1).await;
+        events.client_connected(&tx, &mut id, true).await;
+        assert_eq!(next_event().1, ServerEvent::ClientConnected(true));
+        let diagnostics = vec![Diagnostic::on_line(
+            Severity::Warning,
+            1,
+            "problem".to_string(),
+            "test",
+        )];
+        // A file without problems isn't reported, unless it had problems.
+        events.diagnostics(&tx, &mut id, "a.py", &[]).await;
+        events.diagnostics(&tx, &mut id, "a.py", &diagnostics).await;
+        events.diagnostics(&tx, &mut id, "a.py", &diagnostics).await;
+        assert_eq!(
+            next_event().1,
+            ServerEvent::Diagnostics {
+                file_path: "a.py".to_string(),
+                diagnostics: diagnostics.clone()
+            }
+        );
+        events.diagnostics(&tx, &mut id, "a.py", &[]).await;
+        let (last_id, event) = next_event();
+        assert_eq!(
+            event,
+            ServerEvent::Diagnostics {
+                file_path: "a.py".to_string(),
+                diagnostics: vec![]
+            }
+        );
+
+        // The IDE's acknowledgements are recognized; an error unsubscribes.
+        assert!(!events.acknowledged(id, &Ok(ResultOkTypes::Void)));
+        assert!(events.acknowledged(last_id, &Err("error".to_string())));
+        events.translating(&tx, &mut id, 0).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
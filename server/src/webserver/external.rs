@@ -0,0 +1,180 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `external.rs` -- Show external pages with a navigation bar
+///
+/// When the user follows a link to another site, the Client loads it in its
+/// iframe, since hosts such as VSCode's webview may block popups. This leaves
+/// the user without the browser's usual controls. Instead, the Client loads
+/// `/ext?url=<url>&home=<url>`, which shows the external page below a
+/// navigation bar providing back, forward, and home (returning to the file the
+/// user was editing) buttons, along with a button which opens the page outside
+/// the Client using an `OpenUrl` message: the IDE opens it in the system's
+/// browser, as does the File Watcher.
+///
+/// Only `http` and `https` URLs are shown or opened, so that a page can't ask
+/// the IDE to open a URL which runs a command or reads a local file.
+// ## Imports
+//
+// ### Third-party
+use actix_web::{get, http::header::ContentType, web, HttpResponse};
+use indoc::formatdoc;
+use serde::Deserialize;
+use url::Url;
+
+// ### Local
+use super::{html_bad_request, inline_script, json_data_tag};
+use crate::processing::escape::{escape, EscapeContext};
+
+// ## Globals
+/// The script run by the external page. The page's URL and home are provided
+/// as data, so that this script is the same for every page.
+const EXTERNAL_SCRIPT: &str = r#"
+const [url, home] = JSON.parse(document.getElementById("CodeChat-external-data").textContent);
+// This page is loaded in the Client's iframe, whose parent
+// is the Client framework. If it's opened by itself,
+// navigate directly.
+const webSocketComm = parent.window.CodeChatEditorFramework?.webSocketComm;
+document.getElementById("CodeChat-external-back").addEventListener("click", () => history.back());
+document.getElementById("CodeChat-external-forward").addEventListener("click", () => history.forward());
+document.getElementById("CodeChat-external-home").addEventListener("click", () => {
+    if (webSocketComm === undefined) {
+        window.location.href = home;
+    } else {
+        webSocketComm.current_file(new URL(home));
+    }
+});
+document.getElementById("CodeChat-external-open").addEventListener("click", () => {
+    if (webSocketComm === undefined) {
+        window.open(url, "_blank", "noopener");
+    } else {
+        webSocketComm.open_url(url);
+    }
+});
+"#;
+
+// ## Data structures
+/// The query parameters of the `/ext` endpoint.
+#[derive(Deserialize)]
+pub struct ExternalQuery {
+    /// The URL of the external page.
+    url: String,
+    /// The URL of the Client page to return to.
+    home: Option<String>,
+}
+
+// ## Code
+/// Return the provided URL if it's an `http` or `https` URL, or an error
+/// otherwise.
+pub fn check_external_url(url: &str) -> Result<Url, String> {
+    let parsed_url = Url::parse(url).map_err(|err| format!("Invalid URL {url}: {err}"))?;
+    match parsed_url.scheme() {
+        "http" | "https" => Ok(parsed_url),
+        scheme => Err(format!(
+            "Only http and https URLs may be opened, not {scheme} URLs."
+        )),
+    }
+}
+
+/// ### Endpoints
+///
+/// Show the external page at the provided URL below a navigation bar.
+#[get("/ext")]
+pub async fn external_endpoint(query: web::Query<ExternalQuery>) -> HttpResponse {
+    match check_external_url(&query.url) {
+        Ok(url) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(external_page(&url, query.home.as_deref())),
+        Err(err) => html_bad_request(&format!("<p>{}</p>", escape(&err, EscapeContext::Text))),
+    }
+}
+
+// Return a page showing `url` below a navigation bar; its home button returns
+// to `home`, if provided.
+fn external_page(url: &Url, home: Option<&str>) -> String {
+    let url_text = escape(url.as_str(), EscapeContext::Text);
+    let url_attribute = escape(url.as_str(), EscapeContext::Attribute);
+    let external_data = json_data_tag("CodeChat-external-data", &(url.as_str(), home));
+    let external_script = inline_script("", EXTERNAL_SCRIPT);
+    let home_hidden = if home.is_none() { " hidden" } else { "" };
+    formatdoc!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <title>{url_text}</title>
+                <style>
+                    body {{ margin: 0; display: flex; flex-direction: column; height: 100vh; }}
+                    nav {{ display: flex; gap: 0.25em; align-items: center; padding: 0.25em; border-bottom: 1px solid #ccc; font-family: sans-serif; }}
+                    nav span {{ flex: 1; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }}
+                    main {{ flex: 1; display: flex; }}
+                    iframe {{ flex: 1; border: none; }}
+                </style>
+            </head>
+            <body>
+                <nav aria-label="External page">
+                    <button type="button" id="CodeChat-external-back" title="Back" aria-label="Back">&larr;</button>
+                    <button type="button" id="CodeChat-external-forward" title="Forward" aria-label="Forward">&rarr;</button>
+                    <button type="button" id="CodeChat-external-home" title="Return to the CodeChat Editor"{home_hidden}>Home</button>
+                    <span>{url_text}</span>
+                    <button type="button" id="CodeChat-external-open" title="Open this page in a web browser">Open externally</button>
+                </nav>
+                <main>
+                    <iframe id="CodeChat-external-page" src="{url_attribute}" title="{url_attribute}"></iframe>
+                </main>
+                {external_data}
+                {external_script}
+            </body>
+        </html>"#
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{check_external_url, external_page};
+    use crate::test_utils::accessibility_problems;
+
+    #[test]
+    fn test_check_external_url() {
+        assert!(check_external_url("https://example.com/a?b=c").is_ok());
+        assert!(check_external_url("http://example.com").is_ok());
+        assert_eq!(
+            check_external_url("command:workbench.action.quit").unwrap_err(),
+            "Only http and https URLs may be opened, not command URLs."
+        );
+        assert!(check_external_url("file:///etc/passwd").is_err());
+        assert!(check_external_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_external_page() {
+        let url = check_external_url("https://example.com/a?b=</script>").unwrap();
+        let html = external_page(&url, Some("http://127.0.0.1:8080/fw/fsb/test.py"));
+        assert!(html.contains(r#"src="https://example.com/a?b=%3C/script%3E""#));
+        assert!(html.contains(
+            r#"id="CodeChat-external-data">["https://example.com/a?b=%3C/script%3E","http://127.0.0.1:8080/fw/fsb/test.py"]</script>"#
+        ));
+        assert!(!html.contains(" hidden>Home"));
+        assert_eq!(accessibility_problems(&html), Vec::<String>::new());
+
+        // Without a home page, hide the home button.
+        let html = external_page(&url, None);
+        assert!(html.contains(r#"/script%3E",null]</script>"#));
+        assert!(html.contains(" hidden>Home"));
+    }
+}
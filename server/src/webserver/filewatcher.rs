@@ -35,8 +35,8 @@ use lazy_static::lazy_static;
 use log::{error, info, warn};
 use notify_debouncer_full::{
     new_debouncer,
-    notify::{EventKind, RecursiveMode},
-    DebounceEventResult,
+    notify::{EventKind, RecursiveMode, Watcher},
+    DebounceEventResult, Debouncer, FileIdCache,
 };
 use regex::Regex;
 use tokio::{
@@ -52,19 +52,35 @@ use win_partitions::win_api::get_logical_drive;
 
 // ### Local
 use super::{
-    client_websocket, escape_html, get_client_framework, get_connection_id, html_not_found,
-    html_wrapper, path_display, send_response, AppState, EditorMessage, EditorMessageContents,
-    UpdateMessageContents, WebsocketQueues,
+    base_path::{base_path, with_base_path},
+    classroom::is_instructor_request,
+    client_websocket, export_message,
+    external::check_external_url,
+    get_client_framework, get_connection_id, html_forbidden, html_not_found, html_wrapper,
+    outside_project::{outside_project, refused_message, OutsideProjectPolicy},
+    path_display, send_response, AppState, CurrentFileContents, EditorMessage,
+    EditorMessageContents, FoldRange, FoldRangesContents, SaveState, SavedStateContents,
+    UpdateMessageContents, ViewTarget, WebsocketQueues,
 };
 use crate::{
+    browser_open::open_url,
     oneshot_send,
     processing::{
-        codechat_for_web_to_source, source_to_codechat_for_web_string, TranslationResultsString,
+        anchors::record_anchors,
+        codechat_for_web_to_source,
+        definitions::find_definition,
+        escape::{escape, EscapeContext},
+        project_config::RenderTarget,
+        render_cache::invalidate_render_cache,
+        set_file_language, source_to_codechat_for_web_string,
+        transclusion::transcluded_files,
+        TranslationResultsString,
     },
     queue_send,
     webserver::{
-        filesystem_endpoint, get_test_mode, make_simple_http_response, path_to_url, url_to_path,
-        ResultOkTypes,
+        capabilities, diagnostics_message, filesystem_endpoint, get_test_mode, insert_snippet,
+        list_snippets, make_simple_http_response, outline_entries, path_to_url,
+        preferences_message, url_to_path, ResultOkTypes,
     },
 };
 
@@ -85,7 +101,7 @@ pub const FILEWATCHER_PATH_PREFIX: &[&str] = &["fw", "fsc"];
 /// Redirect from the root of the filesystem to the actual root path on this OS.
 pub async fn filewatcher_root_fs_redirect() -> impl Responder {
     HttpResponse::TemporaryRedirect()
-        .insert_header((header::LOCATION, "/fw/fsb/"))
+        .insert_header((header::LOCATION, with_base_path("/fw/fsb/")))
         .finish()
 }
 
@@ -119,6 +135,14 @@ async fn filewatcher_browser_endpoint(
     }
     // All other cases (for example, `C:\a\path\to\file.txt`) are OK.
 
+    // In classroom mode, determine if the instructor is opening this file.
+    let is_instructor = match is_instructor_request(&req) {
+        Ok(is_instructor) => is_instructor,
+        Err(err) => {
+            return html_forbidden(&format!("<p>{}</p>", escape(&err, EscapeContext::Text)))
+        }
+    };
+
     // For Linux/OS X, prepend a slash, so that `a/path/to/file.txt` becomes
     // `/a/path/to/file.txt`.
     #[cfg(not(target_os = "windows"))]
@@ -139,13 +163,18 @@ async fn filewatcher_browser_endpoint(
     } else if canon_path.is_file() {
         // Get an ID for this connection.
         let connection_id = get_connection_id(&app_state);
+        if is_instructor {
+            app_state
+                .classroom
+                .set_instructor(&connection_id.to_string());
+        }
         actix_rt::spawn(async move {
             processing_task(&canon_path, app_state, connection_id).await;
         });
         return match get_client_framework(get_test_mode(&req), "fw/ws", &connection_id.to_string())
         {
             Ok(s) => HttpResponse::Ok().content_type(ContentType::html()).body(s),
-            Err(err) => html_not_found(&format!("<p>{}</p>", escape_html(&err))),
+            Err(err) => html_not_found(&format!("<p>{}</p>", escape(&err, EscapeContext::Text))),
         };
     }
 
@@ -166,6 +195,7 @@ async fn filewatcher_browser_endpoint(
 /// replaces this.
 #[cfg(not(tarpaulin_include))]
 async fn dir_listing(web_path: &str, dir_path: &Path) -> HttpResponse {
+    let base_path = base_path();
     // Special case on Windows: list drive letters.
     #[cfg(target_os = "windows")]
     if dir_path == Path::new("") {
@@ -177,7 +207,7 @@ async fn dir_listing(web_path: &str, dir_path: &Path) -> HttpResponse {
         };
         for drive_letter in logical_drives {
             drive_html.push_str(&format!(
-                "<li><a href='/fw/fsb/{drive_letter}:/'>{drive_letter}:</a></li>\n"
+                "<li><a href='{base_path}/fw/fsb/{drive_letter}:/'>{drive_letter}:</a></li>\n"
             ));
         }
 
@@ -258,7 +288,7 @@ async fn dir_listing(web_path: &str, dir_path: &Path) -> HttpResponse {
         };
         let encoded_dir = urlencoding::encode(&dir_name);
         dir_html += &format!(
-            "<li><a href='/fw/fsb/{web_path}{}{encoded_dir}'>{dir_name}</a></li>\n",
+            "<li><a href='{base_path}/fw/fsb/{web_path}{}{encoded_dir}'>{dir_name}</a></li>\n",
             // If this is a raw drive letter, then the path already ends with a
             // slash, such as `C:/`. Don't add a second slash in this case.
             // Otherwise, add a slash to make `C:/foo` into `C:/foo/`.
@@ -286,7 +316,7 @@ async fn dir_listing(web_path: &str, dir_path: &Path) -> HttpResponse {
         let encoded_file = urlencoding::encode(&file_name);
         file_html += &formatdoc!(
             r#"
-            <li><a href="/fw/fsb/{web_path}/{encoded_file}" target="_blank">{file_name}</a></li>
+            <li><a href="{base_path}/fw/fsb/{web_path}/{encoded_file}" target="_blank">{file_name}</a></li>
             "#
         );
     }
@@ -374,6 +404,13 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                     break 'task;
                 };
             }
+            // Also watch the files the current file quotes.
+            let mut transclusions = Vec::new();
+            watch_transclusions(
+                &mut debounced_watcher,
+                &mut transclusions,
+                current_filepath.as_deref(),
+            );
 
             // Create the queues for the websocket connection to communicate
             // with this task.
@@ -387,14 +424,28 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                 },
             );
 
-            // Provide it a file to open.
+            // Tell the Client which optional features are enabled.
             let mut id: f64 = 0.0;
+            queue_send!(to_websocket_tx.send(EditorMessage {
+                id,
+                message: EditorMessageContents::Capabilities(capabilities())
+            }), 'task);
+            id += 1.0;
+
+            // Provide it a file to open.
             if let Some(cfp) = &current_filepath {
-                let url_pathbuf = path_to_url("/fw/fsc", &connection_id.to_string(), cfp);
+                let url_pathbuf =
+                    path_to_url(&with_base_path("/fw/fsc"), &connection_id.to_string(), cfp);
                 queue_send!(to_websocket_tx.send(EditorMessage {
                     id,
-                    message: EditorMessageContents::CurrentFile(url_pathbuf)
+                    message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                        file_path: url_pathbuf,
+                        view_target: ViewTarget::Active,
+                    })
                 }), 'task);
+                app_state
+                    .classroom
+                    .set_current_file(&connection_id.to_string(), cfp);
                 // Note: it's OK to postpone the increment to here; if the
                 // `queue_send` exits before this runs, the message didn't get sent,
                 // so the ID wasn't used.
@@ -409,6 +460,10 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                 .unwrap()
                 .insert(connection_id.to_string(), from_http_tx);
 
+            // The folded regions of the current file, restored after reloading
+            // it.
+            let mut fold_ranges: Vec<FoldRange> = Vec::new();
+
             loop {
                 select! {
                     // Process results produced by the file watcher.
@@ -443,8 +498,17 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                         }
                                     };
                                     if is_modify {
+                                        // Discard cached renderings of
+                                        // directives, such as quoted code,
+                                        // which depend on the changed files.
+                                        for path in &debounced_event.event.paths {
+                                            invalidate_render_cache(path);
+                                        }
+                                        // A change to a quoted file also
+                                        // changes the current file's rendering.
                                         if debounced_event.event.paths.len() != 1 ||
-                                            current_filepath.as_ref().map_or(true, |cfp| cfp != &debounced_event.event.paths[0])
+                                            (current_filepath.as_ref().is_none_or(|cfp| cfp != &debounced_event.event.paths[0]) &&
+                                            !transclusions.contains(&debounced_event.event.paths[0]))
                                         {
                                             warn!("Modification to different file {}.", debounced_event.event.paths[0].to_string_lossy());
                                         } else {
@@ -466,36 +530,45 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
 
                                                 // Close the file if it can't be read as
                                                 // Unicode text.
-                                                if read_ret.is_err() {
-                                                    error!("Unable to read '{}': {}", cfp.to_string_lossy(), read_ret.unwrap_err());
+                                                if let Err(err) = read_ret {
+                                                    error!("Unable to read '{}': {}", cfp.to_string_lossy(), err);
                                                     break 'process Err(());
                                                 }
 
                                                 // Translate the file.
                                                 let (translation_results_string, _path_to_toc) =
-                                                source_to_codechat_for_web_string(&file_contents, cfp, false);
+                                                source_to_codechat_for_web_string(&file_contents, cfp, false, RenderTarget::Editor);
                                                 if let TranslationResultsString::CodeChat(cc) = translation_results_string {
                                                     let Some(current_filepath_str) = cfp.to_str() else {
                                                         error!("Unable to convert path {cfp:?} to string.");
                                                         break 'process Err(());
                                                     };
-                                                    // Send the new contents.
-                                                    Ok(EditorMessage {
-                                                            id,
-                                                            message: EditorMessageContents::Update(UpdateMessageContents {
-                                                                file_path: current_filepath_str.to_string(),
-                                                                contents: Some(cc),
-                                                                cursor_position: None,
-                                                                scroll_position: None,
-                                                            }),
-                                                        })
+                                                    // Send the new contents, then any problems
+                                                    // in its doc blocks.
+                                                    let mut messages = vec![EditorMessageContents::Update(UpdateMessageContents {
+                                                        file_path: current_filepath_str.to_string(),
+                                                        contents: Some(cc),
+                                                        cursor_position: None,
+                                                        scroll_position: None,
+                                                    })];
+                                                    messages.extend(diagnostics_message(&file_contents, cfp));
+                                                    if !fold_ranges.is_empty() {
+                                                        messages.push(EditorMessageContents::FoldRanges(FoldRangesContents {
+                                                            file_path: current_filepath_str.to_string(),
+                                                            ranges: fold_ranges.clone(),
+                                                        }));
+                                                    }
+                                                    Ok(messages)
                                                 } else {
                                                     break 'process Err(());
                                                 }
                                             };
-                                            if let Ok(editor_message) = result {
-                                                queue_send!(to_websocket_tx.send(editor_message));
-                                                id += 1.0;
+                                            if let Ok(messages) = result {
+                                                for message in messages {
+                                                    queue_send!(to_websocket_tx.send(EditorMessage { id, message }));
+                                                    id += 1.0;
+                                                }
+                                                watch_transclusions(&mut debounced_watcher, &mut transclusions, current_filepath.as_deref());
                                             } else {
                                                 // We can't open the file -- it's been
                                                 // moved or deleted. Close the file.
@@ -513,6 +586,7 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                                     );
                                                 }
                                                 current_filepath = None;
+                                                watch_transclusions(&mut debounced_watcher, &mut transclusions, None);
                                                 continue;
                                             }
                                         }
@@ -526,10 +600,10 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                         // If there's no current file, replace it with an empty file, which will still produce an error.
                         let empty_path = PathBuf::new();
                         let cfp = current_filepath.as_ref().unwrap_or(&empty_path);
-                        let (simple_http_response, option_update) = make_simple_http_response(&http_request, cfp).await;
-                        if let Some(update) = option_update {
+                        let (simple_http_response, messages) = make_simple_http_response(&http_request, cfp).await;
+                        for message in messages {
                             // Send the update to the client.
-                            queue_send!(to_websocket_tx.send(EditorMessage { id, message: update }));
+                            queue_send!(to_websocket_tx.send(EditorMessage { id, message }));
                             id += 1.0;
                         }
                         oneshot_send!(http_request.response_queue.send(simple_http_response));
@@ -538,6 +612,7 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                     Some(m) = from_websocket_rx.recv() => {
                         match m.message {
                             EditorMessageContents::Update(update_message_contents) => {
+                                let has_contents = update_message_contents.contents.is_some();
                                 let result = 'process: {
                                     // Check that the file path matches the current file. If `canonicalize` fails, then the files don't match.
                                     if Some(Path::new(&update_message_contents.file_path).to_path_buf()) != current_filepath {
@@ -546,6 +621,9 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                             update_message_contents.file_path
                                         ));
                                     }
+                                    if let (Some(scroll_position), Some(cfw)) = (update_message_contents.scroll_position, &update_message_contents.contents) {
+                                        app_state.classroom.set_scroll_position(&connection_id.to_string(), Path::new(&update_message_contents.file_path), scroll_position.to_normalized(&cfw.source));
+                                    }
                                     // With code or a path, there's nothing to
                                     // save.
                                     let codechat_for_web = match update_message_contents.contents {
@@ -567,6 +645,9 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                     };
 
                                     let cfp = current_filepath.as_ref().unwrap();
+                                    if let Err(err) = record_anchors(cfp, &file_contents) {
+                                        warn!("Unable to record anchors for {cfp:?}: {err}");
+                                    }
                                     // Unwrap the file, write to it, then rewatch it, in order to avoid a watch notification from this write.
                                     if let Err(err) = debounced_watcher.unwatch(cfp) {
                                         let msg = format!(
@@ -590,14 +671,37 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                         );
                                         break 'process Err(msg);
                                     }
+                                    watch_transclusions(&mut debounced_watcher, &mut transclusions, current_filepath.as_deref());
                                     Ok(ResultOkTypes::Void)
                                 };
+                                let is_ok = result.is_ok();
                                 send_response(&to_websocket_tx, m.id, result).await;
+                                // The file on disk now matches the Client.
+                                if is_ok && has_contents {
+                                    queue_send!(to_websocket_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::SavedState(SavedStateContents {
+                                            file_path: update_message_contents.file_path,
+                                            state: SaveState::Clean,
+                                        })
+                                    }));
+                                    id += 1.0;
+                                }
                             }
 
-                            EditorMessageContents::CurrentFile(url_string) => {
-                                let result = match url_to_path(&url_string, FILEWATCHER_PATH_PREFIX) {
+                            EditorMessageContents::CurrentFile(current_file_contents) => {
+                                let file_path = url_to_path(&current_file_contents.file_path, FILEWATCHER_PATH_PREFIX);
+                                // A file outside the current project doesn't
+                                // become the current file; see
+                                // `outside_project.rs`.
+                                let policy = match (&file_path, &current_filepath) {
+                                    (Ok(file_path), Some(cfp)) => outside_project(cfp, file_path),
+                                    _ => None,
+                                };
+                                let result = match file_path {
                                     Err(err) => Err(err),
+                                    Ok(ref file_path) if policy == Some(OutsideProjectPolicy::Refuse) => Err(refused_message(file_path)),
+                                    Ok(_) if policy.is_some() => Ok(ResultOkTypes::Void),
                                     Ok(ref file_path) => 'err_exit: {
                                         // We finally have the desired path! First,
                                         // unwatch the old path.
@@ -611,6 +715,7 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                         };
                                         // Update to the new path.
                                         current_filepath = Some(file_path.to_path_buf());
+                                        fold_ranges.clear();
 
                                         // Watch the new file.
                                         if let Err(err) = debounced_watcher.watch(file_path, RecursiveMode::NonRecursive) {
@@ -619,6 +724,8 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                                 file_path.to_string_lossy()
                                             ));
                                         }
+                                        app_state.classroom.set_current_file(&connection_id.to_string(), file_path);
+                                        watch_transclusions(&mut debounced_watcher, &mut transclusions, current_filepath.as_deref());
 
                                         // Indicate there was no error in the
                                         // `Result` message.
@@ -628,6 +735,24 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                 send_response(&to_websocket_tx, m.id, result).await;
                             },
 
+                            EditorMessageContents::SetFileLanguage(set_file_language_contents) => {
+                                let file_path = PathBuf::from(&set_file_language_contents.file_path);
+                                let result = set_file_language(&file_path, set_file_language_contents.language).map(|_| ResultOkTypes::Void);
+                                let is_ok = result.is_ok();
+                                send_response(&to_websocket_tx, m.id, result).await;
+                                // Reload the current file using its new lexer.
+                                if is_ok && current_filepath.as_ref() == Some(&file_path) {
+                                    queue_send!(to_websocket_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                            file_path: path_to_url(&with_base_path("/fw/fsc"), &connection_id.to_string(), &file_path),
+                                            view_target: ViewTarget::Active,
+                                        })
+                                    }));
+                                    id += 1.0;
+                                }
+                            }
+
                             // Process a result, the respond to a message we
                             // sent.
                             EditorMessageContents::Result(message_result) => {
@@ -642,12 +767,97 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
                                 break;
                             }
 
-                            EditorMessageContents::Opened(_) | EditorMessageContents::ClientHtml(_) | EditorMessageContents::RequestClose => {
+                            EditorMessageContents::Opened(_) | EditorMessageContents::ClientHtml(_) | EditorMessageContents::UpdateDiff(_) | EditorMessageContents::Diagnostics(_) | EditorMessageContents::Capabilities(_) | EditorMessageContents::Log(_) | EditorMessageContents::Event(_) | EditorMessageContents::RequestClose | EditorMessageContents::SubscribeLogs(_) | EditorMessageContents::WorkspaceRoots(_) | EditorMessageContents::SubscribeEvents(_) | EditorMessageContents::SavedState(_) => {
                                 let msg = format!("Client sent unsupported message type {m:?}");
                                 error!("{msg}");
                                 send_response(&to_websocket_tx, m.id, Err(msg)).await;
                             }
 
+                            // The File Watcher tracks only the current file.
+                            EditorMessageContents::ListOpenFiles => {
+                                let paths = current_filepath.iter().filter_map(|cfp| cfp.to_str().map(str::to_string)).collect();
+                                send_response(&to_websocket_tx, m.id, Ok(ResultOkTypes::OpenFiles(paths))).await;
+                            }
+                            EditorMessageContents::SwitchFile(file_path) => {
+                                let msg = format!("No contents available for {file_path}; use CurrentFile instead.");
+                                send_response(&to_websocket_tx, m.id, Err(msg)).await;
+                            }
+                            EditorMessageContents::OpenView(_) | EditorMessageContents::CloseView(_) => {
+                                let msg = "The File Watcher supports only one view.".to_string();
+                                send_response(&to_websocket_tx, m.id, Err(msg)).await;
+                            }
+
+                            // Without an IDE, only definitions in the project's
+                            // `tags` file can be shown, by loading their file.
+                            EditorMessageContents::GotoDefinition(goto_definition_contents) => {
+                                let definition = find_definition(&goto_definition_contents.identifier, Path::new(&goto_definition_contents.file_path));
+                                let result = match &definition {
+                                    Ok(Some(_)) => Ok(ResultOkTypes::Void),
+                                    Ok(None) => Err(format!("No definition found for {}; the File Watcher finds definitions only in the project's tags file.", goto_definition_contents.identifier)),
+                                    Err(err) => Err(err.clone()),
+                                };
+                                send_response(&to_websocket_tx, m.id, result).await;
+                                if let Ok(Some(definition)) = definition {
+                                    queue_send!(to_websocket_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                            file_path: path_to_url(&with_base_path("/fw/fsc"), &connection_id.to_string(), Path::new(&definition.file_path)),
+                                            view_target: ViewTarget::Active,
+                                        })
+                                    }));
+                                    id += 1.0;
+                                }
+                            }
+
+                            // The file on disk is the file being edited, so
+                            // find its outline there.
+                            EditorMessageContents::Outline(file_path) => {
+                                let result = fs::read_to_string(&file_path).await
+                                    .map(|file_contents| ResultOkTypes::Outline(outline_entries(&file_contents, Path::new(&file_path))))
+                                    .map_err(|err| format!("Unable to read {file_path}: {err}"));
+                                send_response(&to_websocket_tx, m.id, result).await;
+                            }
+
+                            EditorMessageContents::ListSnippets(file_path) => {
+                                send_response(&to_websocket_tx, m.id, list_snippets(&file_path)).await;
+                            }
+
+                            EditorMessageContents::InsertSnippet(insert_snippet_contents) => {
+                                send_response(&to_websocket_tx, m.id, insert_snippet(&insert_snippet_contents)).await;
+                            }
+
+                            EditorMessageContents::Preferences(update) => {
+                                send_response(&to_websocket_tx, m.id, preferences_message(update)).await;
+                            }
+
+                            // Without an IDE, only remember the folded regions
+                            // of the current file.
+                            EditorMessageContents::FoldRanges(fold_ranges_contents) => {
+                                if current_filepath.as_deref() == Some(Path::new(&fold_ranges_contents.file_path)) {
+                                    fold_ranges = fold_ranges_contents.ranges;
+                                }
+                                send_response(&to_websocket_tx, m.id, Ok(ResultOkTypes::Void)).await;
+                            }
+
+                            // Each `Update` was saved when it arrived, so
+                            // there's nothing left to save.
+                            EditorMessageContents::Save(_) | EditorMessageContents::SaveAll => {
+                                send_response(&to_websocket_tx, m.id, Ok(ResultOkTypes::Void)).await;
+                            }
+
+                            EditorMessageContents::Export(export_contents) => {
+                                export_message(export_contents, to_websocket_tx.clone(), m.id);
+                            }
+
+                            // Without an IDE, open the URL in a browser on
+                            // this machine.
+                            EditorMessageContents::OpenUrl(url) => {
+                                let result = check_external_url(&url)
+                                    .and_then(|url| open_url(url.as_str()))
+                                    .map(|_| ResultOkTypes::Void);
+                                send_response(&to_websocket_tx, m.id, result).await;
+                            }
+
                             other => {
                                 warn!("Unhandled message {other:?}");
                             }
@@ -680,6 +890,45 @@ async fn processing_task(file_path: &Path, app_state: web::Data<AppState>, conne
     });
 }
 
+// Watch the files quoted by `include` fences in the current file, recorded in
+// `transclusions`, so that changes to them update the Client; stop watching
+// files which are no longer quoted.
+fn watch_transclusions<W: Watcher, C: FileIdCache>(
+    debounced_watcher: &mut Debouncer<W, C>,
+    transclusions: &mut Vec<PathBuf>,
+    current_filepath: Option<&Path>,
+) {
+    let new_transclusions: Vec<PathBuf> = current_filepath
+        .and_then(|cfp| {
+            std::fs::read_to_string(cfp)
+                .ok()
+                .map(|file_contents| transcluded_files(cfp, &file_contents))
+        })
+        .unwrap_or_default()
+        .into_iter()
+        // Ignore missing files; use the same form of path as the current
+        // file, so that watcher events match.
+        .filter_map(|path| path.canonicalize().ok())
+        .map(|path| PathBuf::from(simplified(&path)))
+        .filter(|path| Some(path.as_path()) != current_filepath)
+        .collect();
+    for path in transclusions.iter() {
+        if !new_transclusions.contains(path) {
+            if let Err(err) = debounced_watcher.unwatch(path) {
+                warn!("Unable to unwatch quoted file {path:?}: {err}.");
+            }
+        }
+    }
+    for path in new_transclusions.iter() {
+        if !transclusions.contains(path) {
+            if let Err(err) = debounced_watcher.watch(path, RecursiveMode::NonRecursive) {
+                warn!("Unable to watch quoted file {path:?}: {err}.");
+            }
+        }
+    }
+    *transclusions = new_transclusions;
+}
+
 /// Define a websocket handler for the CodeChat Editor Client.
 #[get("/fw/ws/{connection_id}")]
 pub async fn filewatcher_websocket(
@@ -721,13 +970,14 @@ mod tests {
 
     use super::{
         super::{configure_app, make_app_data, WebsocketQueues},
-        send_response, AppState, EditorMessage, EditorMessageContents, UpdateMessageContents,
+        send_response, AppState, CurrentFileContents, EditorMessage, EditorMessageContents,
+        SaveState, SavedStateContents, UpdateMessageContents, ViewTarget,
     };
     use crate::{
         cast, prep_test_dir,
         processing::{
-            source_to_codechat_for_web, CodeChatForWeb, CodeMirror, SourceFileMetadata,
-            TranslationResults,
+            project_config::ProjectConfig, source_to_codechat_for_web, CodeChatForWeb, CodeMirror,
+            SourceFileMetadata, TranslationResults,
         },
         test_utils::{check_logger_errors, configure_testing_logger},
         webserver::{tests::IP_PORT, IdeType, ResultOkTypes},
@@ -791,10 +1041,16 @@ mod tests {
         let ide_tx_queue = je.from_websocket_tx;
         let mut client_rx = je.to_websocket_rx;
 
+        // The Client first receives its capabilities.
+        let (id, _) = get_message_as!(client_rx, EditorMessageContents::Capabilities);
+        assert_eq!(id, 0.0);
+        send_response(&ide_tx_queue, id, Ok(ResultOkTypes::Void)).await;
+
         // The initial web request for the Client framework produces a
         // `CurrentFile`.
-        let (id, url_string) = get_message_as!(client_rx, EditorMessageContents::CurrentFile);
-        assert_eq!(id, 0.0);
+        let (id, current_file_contents) =
+            get_message_as!(client_rx, EditorMessageContents::CurrentFile);
+        assert_eq!(id, 1.0);
 
         // Compute the path this message should contain.
         let mut test_path = test_dir.clone();
@@ -802,7 +1058,11 @@ mod tests {
         // The comparison below fails without this.
         let test_path = test_path.canonicalize().unwrap();
         // The URL parser requires a valid origin.
-        let url = Url::parse(&format!("http://foo.com{url_string}")).unwrap();
+        let url = Url::parse(&format!(
+            "http://foo.com{}",
+            current_file_contents.file_path
+        ))
+        .unwrap();
         let url_segs: Vec<_> = url
             .path_segments()
             .unwrap()
@@ -821,11 +1081,17 @@ mod tests {
         let resp = test::call_service(&app, req).await;
         assert!(resp.status().is_success());
         let (id, umc) = get_message_as!(client_rx, EditorMessageContents::Update);
-        assert_eq!(id, 1.0);
+        assert_eq!(id, 2.0);
         send_response(&ide_tx_queue, id, Ok(ResultOkTypes::Void)).await;
 
         // Check the contents.
-        let translation_results = source_to_codechat_for_web("", &"py".to_string(), false, false);
+        let translation_results = source_to_codechat_for_web(
+            "",
+            &"py".to_string(),
+            false,
+            false,
+            &ProjectConfig::default(),
+        );
         let codechat_for_web = cast!(translation_results, TranslationResults::CodeChat);
         assert_eq!(umc.contents, Some(codechat_for_web));
 
@@ -842,11 +1108,16 @@ mod tests {
         let ide_tx_queue = je.from_websocket_tx;
         let mut client_rx = je.to_websocket_rx;
 
+        // The Client first receives its capabilities.
+        let (id, _) = get_message_as!(client_rx, EditorMessageContents::Capabilities);
+        assert_eq!(id, 0.0);
+        send_response(&ide_tx_queue, id, Ok(ResultOkTypes::Void)).await;
+
         // The initial web request for the Client framework produces a
         // `CurrentFile`.
         let (id, _) = get_message_as!(client_rx, EditorMessageContents::CurrentFile);
-        assert_eq!(id, 0.0);
-        send_response(&ide_tx_queue, 0.0, Ok(ResultOkTypes::Void)).await;
+        assert_eq!(id, 1.0);
+        send_response(&ide_tx_queue, 1.0, Ok(ResultOkTypes::Void)).await;
 
         // The follow-up web request for the file produces an `Update`.
         let mut file_path = test_dir.clone();
@@ -860,8 +1131,8 @@ mod tests {
         let resp = test::call_service(&app, req).await;
         assert!(resp.status().is_success());
         let (id, _) = get_message_as!(client_rx, EditorMessageContents::Update);
-        assert_eq!(id, 1.0);
-        send_response(&ide_tx_queue, 1.0, Ok(ResultOkTypes::Void)).await;
+        assert_eq!(id, 2.0);
+        send_response(&ide_tx_queue, 2.0, Ok(ResultOkTypes::Void)).await;
 
         // 1.  Send an update message with no contents.
         ide_tx_queue
@@ -907,6 +1178,7 @@ mod tests {
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "".to_string(),
@@ -937,6 +1209,7 @@ mod tests {
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "nope".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "testing".to_string(),
@@ -968,6 +1241,7 @@ mod tests {
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "testing()".to_string(),
@@ -984,6 +1258,18 @@ mod tests {
             get_message_as!(client_rx, EditorMessageContents::Result),
             (6.0, Ok(ResultOkTypes::Void))
         );
+        // The Client is told that its changes are saved.
+        assert_eq!(
+            get_message_as!(client_rx, EditorMessageContents::SavedState),
+            (
+                3.0,
+                SavedStateContents {
+                    file_path: file_path.clone(),
+                    state: SaveState::Clean,
+                }
+            )
+        );
+        send_response(&ide_tx_queue, 3.0, Ok(ResultOkTypes::Void)).await;
 
         // Check that the requested file is written.
         let mut s = fs::read_to_string(&file_path).unwrap();
@@ -997,12 +1283,13 @@ mod tests {
         assert_eq!(
             get_message_as!(client_rx, EditorMessageContents::Update),
             (
-                2.0,
+                4.0,
                 UpdateMessageContents {
                     file_path: file_path.clone(),
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "testing()123".to_string(),
@@ -1015,7 +1302,7 @@ mod tests {
             )
         );
         // Acknowledge this message.
-        send_response(&ide_tx_queue, 2.0, Ok(ResultOkTypes::Void)).await;
+        send_response(&ide_tx_queue, 4.0, Ok(ResultOkTypes::Void)).await;
 
         // 7.  Rename it and check for an close (the file watcher can't detect
         //     the destination file, so it's treated as the file is deleted).
@@ -1025,11 +1312,11 @@ mod tests {
         assert_eq!(
             client_rx.recv().await.unwrap(),
             EditorMessage {
-                id: 3.0,
+                id: 5.0,
                 message: EditorMessageContents::Closed
             }
         );
-        send_response(&ide_tx_queue, 3.0, Ok(ResultOkTypes::Void)).await;
+        send_response(&ide_tx_queue, 5.0, Ok(ResultOkTypes::Void)).await;
 
         // 8.  Load another file from the Client.
         let mut new_file_path = test_dir.clone();
@@ -1041,7 +1328,10 @@ mod tests {
         ide_tx_queue
             .send(EditorMessage {
                 id: 7.0,
-                message: EditorMessageContents::CurrentFile(new_uri.clone()),
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: new_uri.clone(),
+                    view_target: ViewTarget::Active,
+                }),
             })
             .await
             .unwrap();
@@ -1055,8 +1345,8 @@ mod tests {
         let new_resp = test::call_service(&app, new_req).await;
         assert!(new_resp.status().is_success());
         let (id, _) = get_message_as!(client_rx, EditorMessageContents::Update);
-        assert_eq!(id, 4.0);
-        send_response(&ide_tx_queue, 4.0, Ok(ResultOkTypes::Void)).await;
+        assert_eq!(id, 6.0);
+        send_response(&ide_tx_queue, 6.0, Ok(ResultOkTypes::Void)).await;
 
         // 9.  Writes to this file should produce an update.
         fs::write(&new_file_path, "testing 1").unwrap();
@@ -0,0 +1,123 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `keymap.rs` -- Provide the Client's keyboard shortcuts
+///
+/// The Client's keyboard shortcuts are given by a keymap, which maps each
+/// action, such as `save`, to a shortcut, such as `Mod-s`. A shortcut names
+/// its modifiers (`Mod`, which is Cmd on a Mac and Ctrl elsewhere, `Ctrl`,
+/// `Alt`, `Shift`, and `Meta`) followed by its key, separated by `-`; an empty
+/// shortcut disables the action. The keymap starts with the default
+/// shortcuts, which the project's configuration then the user's preferences
+/// may change. The Server places the keymap in each Client page it serves.
+// ## Imports
+//
+// ### Standard library
+use std::collections::BTreeMap;
+
+// ## Globals
+/// The default keymap, as (action, shortcut) pairs.
+pub const DEFAULT_KEYMAP: &[(&str, &str)] = &[
+    // Save the current file.
+    ("save", "Mod-s"),
+    // Save all files open in the IDE.
+    ("save_all", "Mod-Alt-s"),
+    // Export the current file to a PDF.
+    ("export_pdf", "Mod-Alt-p"),
+    // Export the current file's project to a PDF; there's no default
+    // shortcut.
+    ("export_project_pdf", ""),
+    // Move the focus from a doc block to the code, or from the code to the
+    // next doc block.
+    ("toggle_focus", "Mod-Shift-e"),
+];
+
+// ## Code
+/// Return the keymap: the defaults, updated by the project's keymap, then by
+/// the user's keymap.
+pub fn keymap(
+    project_keymap: &BTreeMap<String, String>,
+    user_keymap: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut keymap: BTreeMap<String, String> = DEFAULT_KEYMAP
+        .iter()
+        .map(|(action, shortcut)| (action.to_string(), shortcut.to_string()))
+        .collect();
+    keymap.extend(project_keymap.clone());
+    keymap.extend(user_keymap.clone());
+    keymap
+}
+
+/// Return the tag which provides the keymap to the Client.
+pub fn keymap_tag(keymap: &BTreeMap<String, String>) -> String {
+    // Escape `<`, so that a shortcut can't end this script.
+    format!(
+        r#"<script type="application/json" id="CodeChat-keymap">{}</script>"#,
+        serde_json::to_string(keymap)
+            .unwrap()
+            .replace('<', "\\u003c")
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{keymap, keymap_tag};
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(action, shortcut)| (action.to_string(), shortcut.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_keymap() {
+        assert_eq!(
+            keymap(&BTreeMap::new(), &BTreeMap::new()),
+            map(&[
+                ("export_pdf", "Mod-Alt-p"),
+                ("export_project_pdf", ""),
+                ("save", "Mod-s"),
+                ("save_all", "Mod-Alt-s"),
+                ("toggle_focus", "Mod-Shift-e")
+            ])
+        );
+        // The user's keymap takes priority over the project's.
+        assert_eq!(
+            keymap(
+                &map(&[("save", "Ctrl-s"), ("toggle_focus", "")]),
+                &map(&[("save", "Alt-s")])
+            ),
+            map(&[
+                ("export_pdf", "Mod-Alt-p"),
+                ("export_project_pdf", ""),
+                ("save", "Alt-s"),
+                ("save_all", "Mod-Alt-s"),
+                ("toggle_focus", "")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_keymap_tag() {
+        assert_eq!(
+            keymap_tag(&map(&[("save", "</script>")])),
+            r#"<script type="application/json" id="CodeChat-keymap">{"save":"\u003c/script>"}</script>"#
+        );
+    }
+}
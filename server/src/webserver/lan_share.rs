@@ -0,0 +1,518 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `lan_share.rs` -- Share the rendered docs with devices on the LAN
+///
+/// Starting the Server with `serve --share-lan` lets another device on the
+/// local network, such as a tablet, act as a second screen showing the
+/// rendered docs. The Server then also listens on this machine's LAN address,
+/// and prints a URL (along with a QR code of it, for easy scanning) containing
+/// a random access token minted when the Server starts.
+///
+/// Requests from other machines may only view the read-only
+/// [classroom](classroom.rs) pages, which follow the file most recently opened
+/// on this machine, and only if they provide this token. The first request
+/// provides it in the URL's `token` query parameter; the Server then starts a
+/// session stored in a cookie, so that the pages and websocket this page loads
+/// are also allowed.
+///
+/// When the Server is reached through an SSH tunnel or a reverse proxy,
+/// requests from this machine must also provide a session or the local secret;
+/// see [`tunnel.rs`](../tunnel.rs).
+///
+/// A session ends after `SESSION_IDLE_TIMEOUT` without use,
+/// `SESSION_LIFETIME` after it began, or when the browser opens `/logout`; the
+/// browser then needs a new token.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::HashMap,
+    net::{IpAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+// ### Third-party
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    cookie::{self, Cookie, SameSite},
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    http::header::ContentType,
+    middleware::Next,
+    web, Error, HttpRequest, HttpResponse,
+};
+use lazy_static::lazy_static;
+use log::warn;
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use subtle::ConstantTimeEq;
+
+// ### Local
+use super::{base_path::base_path, html_forbidden, html_wrapper};
+use crate::tunnel::{
+    end_session, is_local_secret, is_session, is_tunneled, redeem_token, LOCAL_SECRET_HEADER,
+    SESSION_NAME,
+};
+
+// ## Globals
+/// True if the Server should share the rendered docs on the LAN.
+static SHARE_LAN: AtomicBool = AtomicBool::new(false);
+
+/// The token other machines must provide; requests from other machines are
+/// refused if this isn't set.
+static LAN_TOKEN: RwLock<Option<String>> = RwLock::new(None);
+
+lazy_static! {
+    /// The sessions of other machines which provided the token.
+    static ref LAN_SESSIONS: Sessions = Sessions::default();
+}
+
+/// The query parameter providing the token.
+const TOKEN_NAME: &str = "token";
+
+/// The cookie providing a session started using the token.
+const LAN_SESSION_NAME: &str = "lan-session";
+
+/// The length of the token, in bytes.
+const TOKEN_LENGTH: usize = 16;
+
+/// How long a session lasts without being used.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// How long a session lasts, even if it's used.
+const SESSION_LIFETIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// ## Data structures
+/// The sessions of browsers which provided a token, each identified by a
+/// random value stored in a cookie.
+#[derive(Default)]
+pub struct Sessions(RwLock<HashMap<String, Session>>);
+
+// When a session ends.
+struct Session {
+    // The end of its idle timeout, which restarts each time it's used.
+    idle_end: Instant,
+    // The end of its lifetime.
+    end: Instant,
+}
+
+// ## Code
+/// Enable or disable sharing the rendered docs on the LAN when the Server
+/// starts.
+pub fn set_share_lan(enabled: bool) {
+    SHARE_LAN.store(enabled, Ordering::Relaxed);
+}
+
+/// Return true if the Server should share the rendered docs on the LAN.
+pub fn is_lan_sharing() -> bool {
+    SHARE_LAN.load(Ordering::Relaxed)
+}
+
+/// Start sharing on the LAN: mint a new access token, then return this
+/// machine's LAN address and the token.
+pub fn start_lan_sharing() -> Result<(IpAddr, String), String> {
+    let address = lan_address()?;
+    let token = mint_token()?;
+    *LAN_TOKEN.write().unwrap() = Some(token.clone());
+    Ok((address, token))
+}
+
+/// Return a new random access token, in hex.
+pub fn mint_token() -> Result<String, String> {
+    let mut bytes = [0u8; TOKEN_LENGTH];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|err| format!("Unable to create an access token: {err}."))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+impl Sessions {
+    /// Start a new session, then return it.
+    pub fn start(&self) -> Result<String, String> {
+        let session = mint_token()?;
+        let now = Instant::now();
+        let mut sessions = self.0.write().unwrap();
+        sessions.retain(|_, session| session.is_live(now));
+        sessions.insert(
+            session.clone(),
+            Session {
+                idle_end: now + SESSION_IDLE_TIMEOUT,
+                end: now + SESSION_LIFETIME,
+            },
+        );
+        Ok(session)
+    }
+
+    /// Return true if `session` hasn't ended; if so, restart its idle timeout.
+    pub fn is_valid(&self, session: &str) -> bool {
+        let mut sessions = self.0.write().unwrap();
+        let Some(key) = find_session(&sessions, session) else {
+            return false;
+        };
+        let now = Instant::now();
+        let entry = sessions.get_mut(&key).unwrap();
+        if entry.is_live(now) {
+            entry.idle_end = now + SESSION_IDLE_TIMEOUT;
+            true
+        } else {
+            sessions.remove(&key);
+            false
+        }
+    }
+
+    /// End `session`, if it exists.
+    pub fn end(&self, session: &str) {
+        let mut sessions = self.0.write().unwrap();
+        if let Some(key) = find_session(&sessions, session) {
+            sessions.remove(&key);
+        }
+    }
+}
+
+impl Session {
+    fn is_live(&self, now: Instant) -> bool {
+        now < self.idle_end && now < self.end
+    }
+}
+
+// Return the key of `session` in `sessions`. Compare with each session, so that
+// the time taken doesn't depend on which one matches.
+fn find_session(sessions: &HashMap<String, Session>, session: &str) -> Option<String> {
+    sessions.keys().fold(None, |matched, expected_session| {
+        if is_token_match(session, expected_session) {
+            Some(expected_session.clone())
+        } else {
+            matched
+        }
+    })
+}
+
+// Return a cookie storing `session`, which the browser keeps for the session's
+// lifetime.
+fn session_cookie(name: &'static str, session: String) -> Cookie<'static> {
+    Cookie::build(name, session)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(cookie::time::Duration::seconds(
+            SESSION_LIFETIME.as_secs() as i64
+        ))
+        .finish()
+}
+
+/// Return true if `token` matches `expected_token`. This takes the same time
+/// wherever they differ, so that the time taken doesn't reveal how much of a
+/// guessed token is correct.
+pub fn is_token_match(token: &str, expected_token: &str) -> bool {
+    token.as_bytes().ct_eq(expected_token.as_bytes()).into()
+}
+
+/// Return the URL a device on the LAN opens to view the rendered docs, using
+/// HTTPS if `is_tls`.
+pub fn lan_url(address: IpAddr, port: u16, token: &str, is_tls: bool) -> String {
+    let host = match address {
+        IpAddr::V4(address) => address.to_string(),
+        IpAddr::V6(address) => format!("[{address}]"),
+    };
+    let scheme = if is_tls { "https" } else { "http" };
+    format!("{scheme}://{host}:{port}/classroom?{TOKEN_NAME}={token}")
+}
+
+/// Return a QR code of `url`, drawn using text which can be printed to a
+/// terminal.
+pub fn qr_code(url: &str) -> Result<String, String> {
+    let code =
+        QrCode::new(url.as_bytes()).map_err(|err| format!("Unable to create a QR code: {err}."))?;
+    // Terminals usually show light text on a dark background; invert the
+    // colors so that the code scans as dark on light.
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+// Return the address of the network interface used to reach the LAN. Connecting
+// a UDP socket sends nothing; it only selects the interface used for this
+// destination.
+fn lan_address() -> Result<IpAddr, String> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))
+        .map_err(|err| format!("Unable to find the LAN address: {err}."))?;
+    socket
+        .connect(("192.0.2.1", 80))
+        .and_then(|()| socket.local_addr())
+        .map(|address| address.ip())
+        .map_err(|err| {
+            format!("Unable to find the LAN address; is this machine on a network? {err}.")
+        })
+}
+
+/// ### Middleware
+///
+/// Allow requests from this machine; refuse requests from other machines
+/// unless they provide the token and ask for a shared page. Behind an SSH
+/// tunnel or a reverse proxy, requests from elsewhere also come from this
+/// machine, so these must instead provide a tunnel session or the local secret
+/// (see [`tunnel.rs`](../tunnel.rs)); pings are still allowed, so that the
+/// `tunnel` and `start` commands can check on the Server.
+pub async fn lan_access(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let is_loopback = req
+        .peer_addr()
+        .is_some_and(|peer_addr| peer_addr.ip().is_loopback());
+    let is_remote_access = is_tunneled() || !base_path().is_empty();
+    let has_local_secret = req
+        .headers()
+        .get(LOCAL_SECRET_HEADER)
+        .and_then(|secret| secret.to_str().ok())
+        .is_some_and(is_local_secret);
+    if is_exempt(req.path(), is_loopback, is_remote_access, has_local_secret) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    }
+    let query_token = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.get(TOKEN_NAME).cloned());
+    if is_remote_access {
+        if req
+            .cookie(SESSION_NAME)
+            .is_some_and(|cookie| is_session(cookie.value()))
+        {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_boxed_body);
+        }
+        if let Some(session) = query_token.as_deref().and_then(redeem_token) {
+            let mut res = next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_boxed_body)?;
+            if let Err(err) = res
+                .response_mut()
+                .add_cookie(&session_cookie(SESSION_NAME, session))
+            {
+                warn!("Unable to set the tunnel session cookie: {err}");
+            }
+            return Ok(res);
+        }
+    }
+    if is_shared_path(req.path())
+        && req
+            .cookie(LAN_SESSION_NAME)
+            .is_some_and(|cookie| LAN_SESSIONS.is_valid(cookie.value()))
+    {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    }
+    let lan_token = LAN_TOKEN.read().unwrap().clone();
+    if !is_allowed(req.path(), query_token.as_deref(), lan_token.as_deref()) {
+        return Ok(req.into_response(html_forbidden(
+            "<p>This page isn't shared with other machines; open the URL the Server printed, including its token.</p>",
+        )));
+    }
+    let mut res = next
+        .call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)?;
+    // Start a session, so that the page's requests need no token.
+    match LAN_SESSIONS.start() {
+        Ok(session) => {
+            if let Err(err) = res
+                .response_mut()
+                .add_cookie(&session_cookie(LAN_SESSION_NAME, session))
+            {
+                warn!("Unable to set the LAN session cookie: {err}");
+            }
+        }
+        Err(err) => warn!("Unable to start a LAN session: {err}"),
+    }
+    Ok(res)
+}
+
+/// End the sessions of the browser making this request, removing their
+/// cookies.
+#[get("/logout")]
+pub async fn logout(req: HttpRequest) -> HttpResponse {
+    let mut res = HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_wrapper(
+        "<p>You're logged out. To return, open a URL containing a new token from the Server.</p>",
+    ));
+    for name in [SESSION_NAME, LAN_SESSION_NAME] {
+        if let Some(cookie) = req.cookie(name) {
+            if name == SESSION_NAME {
+                end_session(cookie.value());
+            } else {
+                LAN_SESSIONS.end(cookie.value());
+            }
+            if let Err(err) = res.add_removal_cookie(&session_cookie(name, String::new())) {
+                warn!("Unable to remove the {name} cookie: {err}");
+            }
+        }
+    }
+    res
+}
+
+// Return true if a request for `path` needs no token: pings and logouts, along
+// with requests from this machine. When the Server is reached through a tunnel
+// or proxy, requests from this machine must also provide the local secret.
+fn is_exempt(
+    path: &str,
+    is_loopback: bool,
+    is_remote_access: bool,
+    has_local_secret: bool,
+) -> bool {
+    path == "/ping" || path == "/logout" || (is_loopback && (!is_remote_access || has_local_secret))
+}
+
+// Return true if another machine providing `token` may request `path`, given
+// the Server's `lan_token`.
+fn is_allowed(path: &str, token: Option<&str>, lan_token: Option<&str>) -> bool {
+    match (token, lan_token) {
+        (Some(token), Some(lan_token)) => is_shared_path(path) && is_token_match(token, lan_token),
+        _ => false,
+    }
+}
+
+// Return true if other machines may view `path`.
+fn is_shared_path(path: &str) -> bool {
+    path == "/classroom"
+        || path.starts_with("/classroom/")
+        // The read-only pages use the Client's styles.
+        || path.starts_with("/static/")
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        time::Instant,
+    };
+
+    use super::{is_allowed, is_exempt, is_token_match, lan_url, mint_token, qr_code, Sessions};
+
+    #[test]
+    fn test_is_allowed() {
+        let lan_token = Some("abc");
+        assert!(is_allowed("/classroom", Some("abc"), lan_token));
+        assert!(is_allowed("/classroom/ws", Some("abc"), lan_token));
+        assert!(is_allowed("/classroom/fs/a/b.py", Some("abc"), lan_token));
+        assert!(is_allowed("/static/bundled/a.css", Some("abc"), lan_token));
+        // Other pages, missing tokens, or wrong tokens are refused.
+        assert!(!is_allowed("/fw/fsb/a/b.py", Some("abc"), lan_token));
+        assert!(!is_allowed("/stop", Some("abc"), lan_token));
+        assert!(!is_allowed("/classroomx", Some("abc"), lan_token));
+        assert!(!is_allowed("/classroom", None, lan_token));
+        assert!(!is_allowed("/classroom", Some("abd"), lan_token));
+        assert!(!is_allowed("/classroom", Some("abc"), None));
+    }
+
+    #[test]
+    fn test_is_exempt() {
+        assert!(is_exempt("/fw/fsb/a/b.py", true, false, false));
+        // Behind a tunnel or proxy, requests from this machine need a token,
+        // unless they come from a program which knows the local secret.
+        assert!(!is_exempt("/fw/fsb/a/b.py", true, true, false));
+        assert!(!is_exempt("/stop", true, true, false));
+        assert!(is_exempt("/stop", true, true, true));
+        assert!(is_exempt("/vsc/ws-ide/1", true, true, true));
+        assert!(!is_exempt("/fw/fsb/a/b.py", false, false, false));
+        assert!(!is_exempt("/stop", false, true, true));
+        // Pings and logouts are always allowed.
+        assert!(is_exempt("/ping", true, true, false));
+        assert!(is_exempt("/ping", false, false, false));
+        assert!(is_exempt("/logout", false, true, false));
+    }
+
+    #[test]
+    fn test_sessions() {
+        let sessions = Sessions::default();
+        let session = sessions.start().unwrap();
+        let session2 = sessions.start().unwrap();
+        assert!(sessions.is_valid(&session));
+        assert!(sessions.is_valid(&session2));
+        assert!(!sessions.is_valid("abc"));
+
+        // A session ends on logout.
+        sessions.end(&session2);
+        assert!(!sessions.is_valid(&session2));
+        assert!(sessions.is_valid(&session));
+
+        // A session ends after going unused, or at the end of its lifetime,
+        // even if it's used.
+        sessions
+            .0
+            .write()
+            .unwrap()
+            .get_mut(&session)
+            .unwrap()
+            .idle_end = Instant::now();
+        assert!(!sessions.is_valid(&session));
+        assert!(sessions.0.read().unwrap().is_empty());
+        let session = sessions.start().unwrap();
+        sessions.0.write().unwrap().get_mut(&session).unwrap().end = Instant::now();
+        assert!(!sessions.is_valid(&session));
+    }
+
+    #[test]
+    fn test_token() {
+        let token = mint_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, mint_token().unwrap());
+        assert!(is_token_match(&token, &token));
+        assert!(!is_token_match(&token[1..], &token));
+        assert!(!is_token_match("", &token));
+    }
+
+    #[test]
+    fn test_lan_url() {
+        assert_eq!(
+            lan_url(
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5)),
+                8080,
+                "abc",
+                false
+            ),
+            "http://192.168.1.5:8080/classroom?token=abc"
+        );
+        assert_eq!(
+            lan_url(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080, "abc", false),
+            "http://[::1]:8080/classroom?token=abc"
+        );
+        assert_eq!(
+            lan_url(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5)), 8443, "abc", true),
+            "https://192.168.1.5:8443/classroom?token=abc"
+        );
+    }
+
+    #[test]
+    fn test_qr_code() {
+        let code = qr_code("http://192.168.1.5:8080/classroom?token=abc").unwrap();
+        assert!(code.lines().count() > 10);
+    }
+}
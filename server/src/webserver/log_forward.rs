@@ -0,0 +1,181 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `log_forward.rs` -- Forward the Server's log records to the IDE
+///
+/// The Server's log is written to files which log4rs places in the Server's
+/// directory; when the Server is installed with an IDE extension, users rarely
+/// find them. Instead, an IDE may send a `SubscribeLogs` message giving the
+/// least severe level it wants; the Server then sends it each record at or
+/// above this level in a `Log` message, so that the IDE can show them (VSCode
+/// shows them in an Output channel). Records below the level given by `serve
+/// --log` are never produced, so they can't be forwarded.
+///
+/// To do this, the logger installed by `configure_logger` wraps the log4rs
+/// logger, also broadcasting each record to all subscribers.
+// ## Imports
+//
+// ### Standard library
+use std::future::pending;
+
+// ### Third-party
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// ## Data structures
+/// A log record, sent to the IDE in a `Log` message.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LogRecord {
+    pub level: Level,
+    /// The module which produced this record.
+    pub target: String,
+    pub message: String,
+}
+
+/// The records an IDE subscribed to; see `SubscribeLogs`.
+pub struct LogSubscription {
+    rx: broadcast::Receiver<LogRecord>,
+    level: LevelFilter,
+}
+
+// The logger installed by `init`.
+struct ForwardingLogger {
+    logger: log4rs::Logger,
+}
+
+// ## Globals
+/// The number of records kept for a subscriber which hasn't received them yet.
+/// Beyond this, the oldest records are dropped.
+const LOG_CHANNEL_CAPACITY: usize = 256;
+
+lazy_static! {
+    // Broadcast each log record to all subscribers.
+    static ref LOG_TX: broadcast::Sender<LogRecord> = broadcast::channel(LOG_CHANNEL_CAPACITY).0;
+}
+
+// ## Code
+/// Install the provided logger as the global logger, forwarding the records it
+/// receives to subscribers.
+pub fn init(logger: log4rs::Logger) {
+    log::set_boxed_logger(Box::new(ForwardingLogger { logger })).unwrap();
+}
+
+impl Log for ForwardingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.logger.enabled(metadata) || LOG_TX.receiver_count() > 0
+    }
+
+    fn log(&self, record: &Record) {
+        // Avoid formatting records no one will receive.
+        if LOG_TX.receiver_count() > 0 {
+            forward(LogRecord {
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+        self.logger.log(record);
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
+
+// Send a record to all subscribers. This fails only when there are none, so
+// ignore the result.
+fn forward(record: LogRecord) {
+    let _ = LOG_TX.send(record);
+}
+
+impl LogSubscription {
+    /// Subscribe to records at or above the provided level.
+    pub fn new(level: LevelFilter) -> LogSubscription {
+        LogSubscription {
+            rx: LOG_TX.subscribe(),
+            level,
+        }
+    }
+
+    /// Wait for the next record at or above this subscription's level.
+    pub async fn recv(&mut self) -> LogRecord {
+        loop {
+            match self.rx.recv().await {
+                Ok(record) => {
+                    if record.level <= self.level {
+                        return record;
+                    }
+                }
+                // Tell the subscriber about dropped records. This can't be
+                // logged, since logging it would produce yet another record.
+                Err(RecvError::Lagged(count)) => {
+                    return LogRecord {
+                        level: Level::Warn,
+                        target: module_path!().to_string(),
+                        message: format!("{count} log records were dropped."),
+                    }
+                }
+                // `LOG_TX` is never dropped, so this can't happen.
+                Err(RecvError::Closed) => pending().await,
+            }
+        }
+    }
+}
+
+/// Wait for the next record of the provided subscription; without one, wait
+/// forever. This allows use of an optional subscription in a `select!`.
+pub async fn next_log_record(subscription: &mut Option<LogSubscription>) -> LogRecord {
+    match subscription {
+        Some(subscription) => subscription.recv().await,
+        None => pending().await,
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter};
+
+    use super::{forward, LogRecord, LogSubscription};
+
+    fn record(level: Level, message: &str) -> LogRecord {
+        LogRecord {
+            level,
+            target: "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_log_subscription() {
+        let mut warn_subscription = LogSubscription::new(LevelFilter::Warn);
+        let mut info_subscription = LogSubscription::new(LevelFilter::Info);
+        forward(record(Level::Debug, "debug"));
+        forward(record(Level::Info, "info"));
+        forward(record(Level::Error, "error"));
+        // Each subscriber receives only records at or above its level.
+        assert_eq!(info_subscription.recv().await, record(Level::Info, "info"));
+        assert_eq!(
+            info_subscription.recv().await,
+            record(Level::Error, "error")
+        );
+        assert_eq!(
+            warn_subscription.recv().await,
+            record(Level::Error, "error")
+        );
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `open_files.rs` -- Track the documents opened during a connection
+///
+/// The Client may show each document opened during a connection as a tab. To
+/// support this, the Server records these documents, along with the contents
+/// most recently sent to or received from the Client for each. Switching to a
+/// document with known contents then needs only an `Update`, instead of
+/// reloading the Client. Likewise, when the IDE changes a document whose
+/// contents the Client received from the Server, the Server sends only the
+/// changes to these contents, in an `UpdateDiff` message. The Server also
+/// records the folded regions of each document, so that they survive reloading
+/// it.
+// ## Imports
+//
+// ### Local
+use super::FoldRange;
+use crate::processing::CodeChatForWeb;
+
+// ## Globals
+//
+/// The maximum number of documents to track; opening another document forgets
+/// the least recently used one.
+const MAX_OPEN_FILES: usize = 20;
+
+// ## Data structures
+//
+/// The documents opened during a connection.
+#[derive(Default)]
+pub struct OpenFiles {
+    // Each document, from least to most recently used.
+    files: Vec<OpenFile>,
+}
+
+// A document opened during a connection.
+struct OpenFile {
+    path: String,
+    // Its most recent contents, if known.
+    contents: Option<CodeChatForWeb>,
+    // The ID of the message which sent these contents to the Client, or `None`
+    // if they came from the Client.
+    sent_id: Option<f64>,
+    // Its folded regions.
+    fold_ranges: Vec<FoldRange>,
+}
+
+// ## Code
+impl OpenFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `file_path` is now the current document.
+    pub fn open(&mut self, file_path: &str) {
+        let entry = match self.files.iter().position(|file| file.path == file_path) {
+            Some(index) => self.files.remove(index),
+            None => OpenFile {
+                path: file_path.to_string(),
+                contents: None,
+                sent_id: None,
+                fold_ranges: Vec::new(),
+            },
+        };
+        self.files.push(entry);
+        if self.files.len() > MAX_OPEN_FILES {
+            self.files.remove(0);
+        }
+    }
+
+    /// Record the most recent contents of `file_path`, if it's open. `sent_id`
+    /// is the ID of the message which sends these contents to the Client, or
+    /// `None` if the Client sent them.
+    pub fn set_contents(
+        &mut self,
+        file_path: &str,
+        codechat_for_web: &CodeChatForWeb,
+        sent_id: Option<f64>,
+    ) {
+        if let Some(file) = self.file_mut(file_path) {
+            file.contents = Some(codechat_for_web.clone());
+            file.sent_id = sent_id;
+        }
+    }
+
+    /// Return the most recent contents of `file_path`, if known.
+    pub fn contents(&self, file_path: &str) -> Option<&CodeChatForWeb> {
+        self.file(file_path).and_then(|file| file.contents.as_ref())
+    }
+
+    /// Return the most recent contents of `file_path` and the ID of the message
+    /// which sent them to the Client, if the Server sent them.
+    pub fn sent_contents(&self, file_path: &str) -> Option<(&CodeChatForWeb, f64)> {
+        self.file(file_path)
+            .and_then(|file| file.contents.as_ref().zip(file.sent_id))
+    }
+
+    /// Record the folded regions of `file_path`, if it's open.
+    pub fn set_fold_ranges(&mut self, file_path: &str, fold_ranges: Vec<FoldRange>) {
+        if let Some(file) = self.file_mut(file_path) {
+            file.fold_ranges = fold_ranges;
+        }
+    }
+
+    /// Return the folded regions of `file_path`; these are empty if it isn't
+    /// open.
+    pub fn fold_ranges(&self, file_path: &str) -> &[FoldRange] {
+        self.file(file_path)
+            .map(|file| file.fold_ranges.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Return the paths of all open documents, from least to most recently
+    /// used.
+    pub fn paths(&self) -> Vec<String> {
+        self.files.iter().map(|file| file.path.clone()).collect()
+    }
+
+    fn file(&self, file_path: &str) -> Option<&OpenFile> {
+        self.files.iter().find(|file| file.path == file_path)
+    }
+
+    fn file_mut(&mut self, file_path: &str) -> Option<&mut OpenFile> {
+        self.files.iter_mut().find(|file| file.path == file_path)
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{OpenFiles, MAX_OPEN_FILES};
+    use crate::processing::{CodeChatForWeb, CodeMirror, SourceFileMetadata};
+    use crate::webserver::FoldRange;
+
+    fn codechat_for_web(doc: &str) -> CodeChatForWeb {
+        CodeChatForWeb {
+            metadata: SourceFileMetadata {
+                mode: "python".to_string(),
+                notebook: None,
+            },
+            source: CodeMirror {
+                doc: doc.to_string(),
+                doc_blocks: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_open_files() {
+        let mut open_files = OpenFiles::new();
+        open_files.open("a.py");
+        open_files.open("b.py");
+        assert_eq!(open_files.paths(), vec!["a.py", "b.py"]);
+
+        // Reopening a file makes it the most recently used.
+        open_files.open("a.py");
+        assert_eq!(open_files.paths(), vec!["b.py", "a.py"]);
+
+        // Contents are only recorded for open files.
+        assert_eq!(open_files.contents("a.py"), None);
+        open_files.set_contents("a.py", &codechat_for_web("a = 1\n"), Some(3.0));
+        open_files.set_contents("c.py", &codechat_for_web("c = 1\n"), None);
+        assert_eq!(
+            open_files.contents("a.py"),
+            Some(&codechat_for_web("a = 1\n"))
+        );
+        assert_eq!(open_files.contents("c.py"), None);
+        assert_eq!(
+            open_files.sent_contents("a.py"),
+            Some((&codechat_for_web("a = 1\n"), 3.0))
+        );
+        // Contents from the Client weren't sent to it.
+        open_files.set_contents("a.py", &codechat_for_web("a = 2\n"), None);
+        assert_eq!(open_files.sent_contents("a.py"), None);
+
+        // Likewise, folded regions are only recorded for open files, and are
+        // kept when a file is reopened.
+        let fold_ranges = vec![FoldRange { start: 1, end: 3 }];
+        open_files.set_fold_ranges("a.py", fold_ranges.clone());
+        open_files.set_fold_ranges("c.py", fold_ranges.clone());
+        open_files.open("b.py");
+        open_files.open("a.py");
+        assert_eq!(open_files.fold_ranges("a.py"), fold_ranges.as_slice());
+        assert!(open_files.fold_ranges("c.py").is_empty());
+
+        // Opening too many files forgets the least recently used.
+        for i in 0..MAX_OPEN_FILES {
+            open_files.open(&format!("{i}.py"));
+        }
+        assert_eq!(open_files.paths().len(), MAX_OPEN_FILES);
+        assert!(!open_files.paths().contains(&"a.py".to_string()));
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `outside_project.rs` -- Open files outside the current project
+///
+/// A link in a doc block may refer to a file outside the project containing
+/// the file being edited, such as an absolute path to a file in another
+/// project. This file has a different table of contents and configuration, if
+/// any. The `serve --outside-project` option selects how the Client opens these
+/// files:
+///
+/// *   `new-project` (the default) edits the file in the context of its own
+///     project, if any, as though it had been opened in the IDE;
+/// *   `read-only` shows the file as a read-only page, without changing the
+///     file being edited;
+/// *   `refuse` shows a page explaining why the file wasn't opened.
+///
+/// This applies only to documents opened from the Client: files opened in the
+/// IDE, along with images and other resources a document refers to, are always
+/// served. A file which isn't part of a project has no outside.
+// ## Imports
+//
+// ### Standard library
+use std::{path::Path, sync::RwLock};
+
+// ### Third-party
+use clap::ValueEnum;
+
+// ### Local
+use crate::processing::find_project_root;
+
+// ## Data structures
+/// How the Client opens a document outside the current project.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutsideProjectPolicy {
+    /// Edit the document in the context of its own project.
+    #[default]
+    NewProject,
+    /// Show the document as a read-only page.
+    ReadOnly,
+    /// Don't open the document.
+    Refuse,
+}
+
+// ## Globals
+/// The policy for all connections.
+static OUTSIDE_PROJECT_POLICY: RwLock<OutsideProjectPolicy> =
+    RwLock::new(OutsideProjectPolicy::NewProject);
+
+// ## Code
+/// Select the policy for all connections.
+pub fn set_outside_project_policy(policy: OutsideProjectPolicy) {
+    *OUTSIDE_PROJECT_POLICY.write().unwrap() = policy;
+}
+
+/// Return the policy which applies when the Client opens `file_path` while
+/// editing `current_file`, or `None` if `file_path` may be edited as usual.
+pub fn outside_project(current_file: &Path, file_path: &Path) -> Option<OutsideProjectPolicy> {
+    let policy = *OUTSIDE_PROJECT_POLICY.read().unwrap();
+    (policy != OutsideProjectPolicy::NewProject && is_outside_project(current_file, file_path))
+        .then_some(policy)
+}
+
+/// Explain why a document outside the current project wasn't opened.
+pub fn refused_message(file_path: &Path) -> String {
+    format!(
+        "{file_path:?} is outside the current project, so it wasn't opened. Open it in the IDE instead, or restart the Server with `--outside-project new-project`."
+    )
+}
+
+// Return true if `file_path` is outside the project containing
+// `current_file`.
+fn is_outside_project(current_file: &Path, file_path: &Path) -> bool {
+    let Some(project_root) = find_project_root(current_file) else {
+        return false;
+    };
+    // Compare canonical paths where possible, since a link may contain `..`.
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    !canonical(file_path).starts_with(canonical(&project_root))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::is_outside_project;
+    use crate::prep_test_dir;
+
+    #[test]
+    fn test_is_outside_project() {
+        let (temp_dir, test_dir) = prep_test_dir!();
+        let current_file = test_dir.join("project/a.py");
+        assert!(!is_outside_project(
+            &current_file,
+            &test_dir.join("project/docs/b.md")
+        ));
+        assert!(!is_outside_project(
+            &current_file,
+            &test_dir.join("other/../project/docs/b.md")
+        ));
+        assert!(is_outside_project(
+            &current_file,
+            &test_dir.join("other/c.py")
+        ));
+        // A file which isn't part of a project has no outside.
+        assert!(!is_outside_project(
+            &test_dir.join("other/c.py"),
+            &current_file
+        ));
+
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+}
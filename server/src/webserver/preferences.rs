@@ -0,0 +1,181 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `preferences.rs` -- Store the user's preferences
+///
+/// The user's preferences, such as the Client's theme, are stored in
+/// `preferences.json` in the CodeChat Editor's directory in the OS's
+/// configuration directory (for example, `~/.config/codechat-editor` on Linux),
+/// so that they apply to every session. The Client requests them when it
+/// starts, then sends any changes, using the `Preferences` message.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// ### Third-party
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+// ## Data structures
+/// The user's preferences. A missing preference uses the Client's default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Preferences {
+    /// The Client's theme, such as `light`.
+    pub theme: Option<String>,
+    /// The font size of the Client's text, in pixels.
+    pub font_size: Option<u32>,
+    /// The time to wait after an edit before saving the file, in milliseconds.
+    pub autosave_delay: Option<u32>,
+    /// The path to the project most recently opened.
+    pub last_project: Option<String>,
+    /// A map from a Client action, such as `save`, to its keyboard shortcut.
+    /// See `keymap.rs`.
+    pub keymap: BTreeMap<String, String>,
+}
+
+impl Preferences {
+    /// Replace each preference with the one provided in `update`, if any.
+    fn update(&mut self, update: Preferences) {
+        if update.theme.is_some() {
+            self.theme = update.theme;
+        }
+        if update.font_size.is_some() {
+            self.font_size = update.font_size;
+        }
+        if update.autosave_delay.is_some() {
+            self.autosave_delay = update.autosave_delay;
+        }
+        if update.last_project.is_some() {
+            self.last_project = update.last_project;
+        }
+        self.keymap.extend(update.keymap);
+    }
+}
+
+// ## Globals
+lazy_static! {
+    /// Serialize reads and writes of the preferences file, since several
+    /// connections may update it.
+    static ref PREFERENCES_LOCK: Mutex<()> = Mutex::new(());
+}
+
+// ## Code
+/// Return the path to the user's preferences file, if the OS provides a
+/// configuration directory.
+pub fn preferences_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("codechat-editor").join("preferences.json"))
+}
+
+/// Return the user's preferences, or the defaults if they can't be read.
+pub fn load_preferences() -> Preferences {
+    let _lock = PREFERENCES_LOCK.lock().unwrap();
+    preferences_path()
+        .map(|path| load_preferences_from(&path))
+        .unwrap_or_default()
+}
+
+/// Update the user's preferences with each preference provided in `update`,
+/// then save them. Return the updated preferences.
+pub fn update_preferences(update: Preferences) -> Result<Preferences, String> {
+    let _lock = PREFERENCES_LOCK.lock().unwrap();
+    let path = preferences_path().ok_or("The OS provides no configuration directory.")?;
+    let mut preferences = load_preferences_from(&path);
+    preferences.update(update);
+    save_preferences_to(&path, &preferences)?;
+    Ok(preferences)
+}
+
+// Read the preferences from `path`; a missing file provides the defaults.
+fn load_preferences_from(path: &Path) -> Preferences {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+            warn!("Ignoring invalid preferences in {path:?}: {err}");
+            Preferences::default()
+        }),
+        Err(_) => Preferences::default(),
+    }
+}
+
+// Write the preferences to `path`, creating its directory if necessary.
+fn save_preferences_to(path: &Path, preferences: &Preferences) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("Unable to create {dir:?}: {err}"))?;
+    }
+    let json = serde_json::to_string_pretty(preferences).map_err(|err| err.to_string())?;
+    fs::write(path, json).map_err(|err| format!("Unable to write {path:?}: {err}"))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+
+    use assert_fs::TempDir;
+
+    use super::{load_preferences_from, save_preferences_to, Preferences};
+
+    #[test]
+    fn test_load_save() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("codechat-editor/preferences.json");
+        // A missing file provides the defaults.
+        assert_eq!(load_preferences_from(&path), Preferences::default());
+
+        let preferences = Preferences {
+            theme: Some("light".to_string()),
+            font_size: Some(16),
+            ..Default::default()
+        };
+        save_preferences_to(&path, &preferences).unwrap();
+        assert_eq!(load_preferences_from(&path), preferences);
+
+        // Invalid files provide the defaults.
+        fs::write(&path, "not JSON").unwrap();
+        assert_eq!(load_preferences_from(&path), Preferences::default());
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_update() {
+        let mut preferences = Preferences {
+            theme: Some("light".to_string()),
+            font_size: Some(16),
+            ..Default::default()
+        };
+        preferences.update(Preferences {
+            font_size: Some(20),
+            last_project: Some("/projects/book".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            preferences,
+            Preferences {
+                theme: Some("light".to_string()),
+                font_size: Some(20),
+                autosave_delay: None,
+                last_project: Some("/projects/book".to_string()),
+                keymap: BTreeMap::new(),
+            }
+        );
+    }
+}
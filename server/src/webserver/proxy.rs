@@ -0,0 +1,365 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `proxy.rs` -- Load external images and pages through the Server
+///
+/// VSCode's webview may block the Client from loading images and pages from
+/// other sites. Starting the Server with `serve --proxy-allow <host>` (repeat
+/// for several hosts) enables the `/proxy?url=<url>` endpoint, which fetches
+/// the provided URL from an allowed host then serves it. When the Client runs
+/// in VSCode's webview, the Server points the `src` attribute of images, iframes, and
+/// other embedded content in doc blocks with URLs on an allowed host to this
+/// endpoint, then restores the original URLs in doc blocks the Client sends
+/// back, so the source file is unchanged. The Client sends doc blocks as
+/// Markdown, so this restores both `src` attributes in raw HTML and the
+/// destinations of Markdown images and links.
+///
+/// Proxied responses are served with a `sandbox` content security policy, so
+/// that an external page can't use the Server's origin to access the Server.
+/// Relative links in a proxied page don't work, since they refer to the
+/// Server rather than the external host.
+// ## Imports
+//
+// ### Standard library
+use std::sync::RwLock;
+
+// ### Third-party
+use actix_web::{
+    get,
+    http::header::{self, ContentType},
+    web, HttpResponse,
+};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use url::Url;
+
+// ### Local
+use super::{
+    base_path::with_base_path, external::check_external_url, html_forbidden, html_wrapper,
+};
+use crate::processing::{
+    escape::{escape, unescape, EscapeContext},
+    CodeChatForWeb,
+};
+
+// ## Data structures
+/// The query parameters of the `/proxy` endpoint.
+#[derive(Deserialize)]
+pub struct ProxyQuery {
+    /// The URL to fetch.
+    url: String,
+}
+
+// ## Globals
+/// The path of the proxy endpoint, before adding the base path.
+const PROXY_PATH: &str = "/proxy";
+
+/// The largest response the proxy serves, in bytes.
+const MAX_PROXY_SIZE: usize = 20 * 1024 * 1024;
+
+/// How long to wait for an external host to respond, in seconds.
+const PROXY_TIMEOUT: u64 = 30;
+
+/// The hosts the proxy may fetch from; if empty, the proxy is disabled.
+static PROXY_HOSTS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+lazy_static! {
+    /// Match a `src` attribute whose value is an `http` or `https` URL.
+    static ref EXTERNAL_SRC_REGEX: Regex = Regex::new(r#"(\ssrc=")(https?://[^"]*)""#).unwrap();
+    /// Match a `src` attribute whose value may point to the proxy, capturing
+    /// the path and the proxied URL. The Client may make this path relative to
+    /// the current file, such as `../../proxy?url=...`.
+    static ref PROXIED_SRC_REGEX: Regex =
+        Regex::new(r#"(\ssrc=")([^"?]*)\?url=([^"]*)""#).unwrap();
+    /// Likewise, match the destination of a Markdown image or link, such as
+    /// `![A](../../proxy?url=...)`.
+    static ref PROXIED_DESTINATION_REGEX: Regex =
+        Regex::new(r#"(\]\()([^\s()<>"?]*)\?url=([^\s()<>"]*)"#).unwrap();
+}
+
+// ## Code
+/// Let the proxy fetch from the provided hosts, such as `example.com`; if
+/// empty, disable it.
+pub fn set_proxy_hosts(hosts: Vec<String>) {
+    *PROXY_HOSTS.write().unwrap() = hosts.into_iter().map(|host| host.to_lowercase()).collect();
+}
+
+/// Point the `src` attributes in the doc blocks of `codechat_for_web` with
+/// URLs on an allowed host to the proxy.
+pub fn proxy_doc_blocks(codechat_for_web: &mut CodeChatForWeb) {
+    let hosts = PROXY_HOSTS.read().unwrap();
+    if !hosts.is_empty() {
+        let proxy_path = with_base_path(PROXY_PATH);
+        map_doc_blocks(codechat_for_web, |html| {
+            proxy_urls(html, &hosts, &proxy_path)
+        });
+    }
+}
+
+/// Restore the original URLs in the doc blocks of `codechat_for_web` which
+/// point to the proxy.
+pub fn unproxy_doc_blocks(codechat_for_web: &mut CodeChatForWeb) {
+    if !PROXY_HOSTS.read().unwrap().is_empty() {
+        let proxy_path = with_base_path(PROXY_PATH);
+        map_doc_blocks(codechat_for_web, |markdown| {
+            unproxy_urls(markdown, &proxy_path)
+        });
+    }
+}
+
+// Apply `f` to the contents of each doc block.
+fn map_doc_blocks(codechat_for_web: &mut CodeChatForWeb, f: impl Fn(&str) -> String) {
+    // Document-only files are entirely HTML.
+    if codechat_for_web.metadata.mode == "markdown" {
+        codechat_for_web.source.doc = f(&codechat_for_web.source.doc);
+    }
+    for doc_block in &mut codechat_for_web.source.doc_blocks {
+        doc_block.4 = f(&doc_block.4);
+    }
+}
+
+// Point `src` attributes in `html` with URLs on one of `hosts` to the proxy,
+// which is at `proxy_path`.
+fn proxy_urls(html: &str, hosts: &[String], proxy_path: &str) -> String {
+    EXTERNAL_SRC_REGEX
+        .replace_all(html, |captures: &Captures| {
+            let url = unescape(&captures[2], EscapeContext::Attribute);
+            match Url::parse(&url) {
+                Ok(parsed_url) if is_allowed(&parsed_url, hosts) => format!(
+                    "{}{proxy_path}?url={}\"",
+                    &captures[1],
+                    urlencoding::encode(&url)
+                ),
+                _ => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+// Restore the original URLs of `src` attributes and Markdown destinations in
+// `markdown` which point to the proxy at `proxy_path`.
+fn unproxy_urls(markdown: &str, proxy_path: &str) -> String {
+    let markdown =
+        PROXIED_SRC_REGEX.replace_all(markdown, |captures: &Captures| {
+            match proxied_url(&captures[2], &captures[3], proxy_path) {
+                Some(url) => format!(
+                    "{}{}\"",
+                    &captures[1],
+                    escape(&url, EscapeContext::Attribute)
+                ),
+                None => captures[0].to_string(),
+            }
+        });
+    PROXIED_DESTINATION_REGEX
+        .replace_all(&markdown, |captures: &Captures| {
+            match proxied_url(&captures[2], &captures[3], proxy_path) {
+                // Use a pointy-bracketed destination if the URL contains
+                // characters which would end a bare one.
+                Some(url) if url.contains(|c: char| c.is_whitespace() || "()<>".contains(c)) => {
+                    format!("{}<{url}>", &captures[1])
+                }
+                Some(url) => format!("{}{url}", &captures[1]),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+// Given the path and the `url` query parameter of a URL, return the proxied
+// URL if the path refers to the proxy at `proxy_path`: either this path, or a
+// path relative to the current file such as `../../proxy`.
+fn proxied_url(path: &str, encoded_url: &str, proxy_path: &str) -> Option<String> {
+    let relative_path = path.trim_start_matches("../");
+    let is_proxy = path == proxy_path || (relative_path == "proxy" && path != relative_path);
+    if !is_proxy {
+        return None;
+    }
+    urlencoding::decode(encoded_url)
+        .ok()
+        .map(|url| url.into_owned())
+}
+
+// Return true if `url` is on one of `hosts`.
+fn is_allowed(url: &Url, hosts: &[String]) -> bool {
+    url.host_str()
+        .is_some_and(|host| hosts.iter().any(|allowed| allowed == host))
+}
+
+// Return the provided URL if the proxy may fetch it, or an error otherwise.
+fn check_proxy_url(url: &str) -> Result<Url, String> {
+    let hosts = PROXY_HOSTS.read().unwrap();
+    if hosts.is_empty() {
+        return Err("The proxy is disabled; enable it using serve --proxy-allow.".to_string());
+    }
+    let url = check_external_url(url)?;
+    if is_allowed(&url, &hosts) {
+        Ok(url)
+    } else {
+        Err(format!(
+            "The proxy may not fetch from {}.",
+            url.host_str().unwrap_or_default()
+        ))
+    }
+}
+
+// Fetch `url`, returning its content type and body.
+fn fetch(url: &Url) -> Result<(String, Vec<u8>), String> {
+    // Don't follow redirects, which may lead to a host which isn't allowed.
+    let response = minreq::get(url.as_str())
+        .with_header("User-Agent", "CodeChat-Editor")
+        .with_max_redirects(0)
+        .with_timeout(PROXY_TIMEOUT)
+        .send_lazy()
+        .map_err(|err| format!("Unable to fetch {url}: {err}"))?;
+    if response.status_code != 200 {
+        return Err(format!(
+            "Fetching {url} returned status code {}.",
+            response.status_code
+        ));
+    }
+    let content_type = response
+        .headers
+        .get("content-type")
+        .cloned()
+        .unwrap_or_else(|| {
+            mime_guess::from_path(url.path())
+                .first_or_octet_stream()
+                .to_string()
+        });
+    let mut body = Vec::new();
+    for byte in response {
+        let (byte, _) = byte.map_err(|err| format!("Unable to fetch {url}: {err}"))?;
+        if body.len() == MAX_PROXY_SIZE {
+            return Err(format!(
+                "{url} is larger than {} MB.",
+                MAX_PROXY_SIZE / (1024 * 1024)
+            ));
+        }
+        body.push(byte);
+    }
+    Ok((content_type, body))
+}
+
+/// ### Endpoints
+///
+/// Fetch then serve the provided URL.
+#[get("/proxy")]
+pub async fn proxy_endpoint(query: web::Query<ProxyQuery>) -> HttpResponse {
+    let url = match check_proxy_url(&query.url) {
+        Ok(url) => url,
+        Err(err) => {
+            return html_forbidden(&format!("<p>{}</p>", escape(&err, EscapeContext::Text)))
+        }
+    };
+    // Fetching blocks; do this on a separate thread.
+    let result = web::block(move || fetch(&url))
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|result| result);
+    match result {
+        Ok((content_type, body)) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox"))
+            .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+            .body(body),
+        Err(err) => HttpResponse::BadGateway()
+            .content_type(ContentType::html())
+            .body(html_wrapper(&format!(
+                "<p>{}</p>",
+                escape(&err, EscapeContext::Text)
+            ))),
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use url::Url;
+
+    use super::{is_allowed, proxy_urls, unproxy_urls};
+
+    #[test]
+    fn test_is_allowed() {
+        let hosts = ["example.com".to_string()];
+        assert!(is_allowed(
+            &Url::parse("https://example.com/a.png").unwrap(),
+            &hosts
+        ));
+        assert!(!is_allowed(
+            &Url::parse("https://example.org/a.png").unwrap(),
+            &hosts
+        ));
+        assert!(!is_allowed(
+            &Url::parse("https://sub.example.com/a.png").unwrap(),
+            &hosts
+        ));
+    }
+
+    #[test]
+    fn test_proxy_urls() {
+        let hosts = ["example.com".to_string()];
+        let html = r#"<p><img src="https://example.com/a.png?b=1&amp;c=2" alt="A"><img src="https://example.org/b.png" alt="B"><img src="local.png" alt="C"></p>"#;
+        let proxied = proxy_urls(html, &hosts, "/proxy");
+        assert_eq!(
+            proxied,
+            r#"<p><img src="/proxy?url=https%3A%2F%2Fexample.com%2Fa.png%3Fb%3D1%26c%3D2" alt="A"><img src="https://example.org/b.png" alt="B"><img src="local.png" alt="C"></p>"#
+        );
+        // Restoring the URLs produces the original HTML, even if the Client
+        // made the proxy's path relative.
+        assert_eq!(unproxy_urls(&proxied, "/proxy"), html);
+        assert_eq!(
+            unproxy_urls(&proxied.replace("\"/proxy", "\"../../proxy"), "/proxy"),
+            html
+        );
+
+        // The proxy's path includes the base path.
+        let proxied = proxy_urls(html, &hosts, "/codechat/proxy");
+        assert!(proxied.contains(r#"src="/codechat/proxy?url=https%3A%2F%2Fexample.com"#));
+        assert_eq!(unproxy_urls(&proxied, "/codechat/proxy"), html);
+        // Other paths with a `url` query parameter are unchanged.
+        assert_eq!(unproxy_urls(&proxied, "/proxy"), proxied);
+    }
+
+    // The Client converts the HTML it receives to Markdown before sending it
+    // back.
+    #[test]
+    fn test_unproxy_markdown() {
+        let markdown = indoc!(
+            r#"
+            An image: ![A](../../proxy?url=https%3A%2F%2Fexample.com%2Fa.png%3Fb%3D1%26c%3D2 "Title").
+
+            A [link](../../../proxy?url=https%3A%2F%2Fexample.com%2Fa%20b.png) and
+            <img src="../../proxy?url=https%3A%2F%2Fexample.com%2Fc.png" alt="C">.
+
+            Unchanged: ![B](docs/proxy?url=x) and ![C](https://example.com/d.png?url=y).
+            "#
+        );
+        assert_eq!(
+            unproxy_urls(markdown, "/proxy"),
+            indoc!(
+                r#"
+                An image: ![A](https://example.com/a.png?b=1&c=2 "Title").
+
+                A [link](<https://example.com/a b.png>) and
+                <img src="https://example.com/c.png" alt="C">.
+
+                Unchanged: ![B](docs/proxy?url=x) and ![C](https://example.com/d.png?url=y).
+                "#
+            )
+        );
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `revision.rs` -- Show a file as of a git revision
+///
+/// To review the rendered docs of a branch, such as a pull request, alongside
+/// those of `main`, open `/rev/<revision>/path/to/file`. This shows a
+/// read-only rendering of the file as committed in `<revision>`, which is any
+/// revision git understands, such as a branch name, a tag, or a commit hash;
+/// URL-encode any slashes it contains (`feature%2Fdocs`). The file's contents
+/// are read from the repository's objects, so the working tree isn't changed,
+/// and any number of revisions may be viewed at once.
+///
+/// Links in the rendered file, such as to images or other files, are relative,
+/// so they also show these files as of the same revision.
+// ## Imports
+//
+// ### Standard library
+use std::path::{self, Path, PathBuf};
+
+// ### Third-party
+use actix_web::{get, http::header::ContentType, web, HttpResponse};
+use git2::Repository;
+
+// ### Local
+use super::{html_not_found, path_display, read_only_page};
+use crate::processing::escape::{escape, EscapeContext};
+
+// ## Code
+/// Return the contents of `file_path`, an absolute path in a git repository's
+/// working tree, as of `revision`.
+pub fn read_revision(revision: &str, file_path: &Path) -> Result<Vec<u8>, String> {
+    // The file, or even its directory, may not exist in the working tree; find
+    // the repository from the closest directory which does.
+    let existing_dir = file_path
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| format!("No directory containing {file_path:?} exists."))?;
+    let repo = Repository::discover(existing_dir)
+        .map_err(|err| format!("{file_path:?} isn't in a git repository: {err}"))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| format!("The repository containing {file_path:?} has no working tree."))?;
+    // Git reports the working tree's path with symbolic links resolved, so do
+    // the same for the existing part of the file's path.
+    let relative_path = existing_dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| {
+            let workdir = workdir.canonicalize().ok()?;
+            let rest = file_path.strip_prefix(existing_dir).ok()?;
+            Some(dir.strip_prefix(workdir).ok()?.join(rest))
+        })
+        .ok_or_else(|| format!("{file_path:?} isn't in the repository's working tree."))?;
+
+    let commit = repo
+        .revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|err| format!("Unknown revision {revision}: {err}"))?;
+    let entry = commit
+        .tree()
+        .and_then(|tree| tree.get_path(&relative_path))
+        .map_err(|err| format!("{relative_path:?} doesn't exist in revision {revision}: {err}"))?;
+    let blob = entry
+        .to_object(&repo)
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|err| format!("{relative_path:?} isn't a file in revision {revision}: {err}"))?;
+    Ok(blob.content().to_vec())
+}
+
+/// ### Endpoints
+///
+/// Serve a file as of the provided revision.
+#[get("/rev/{revision}/{file_path:.*}")]
+pub async fn revision_endpoint(path: web::Path<(String, String)>) -> HttpResponse {
+    // Both are already URL-decoded, including any slashes in the revision,
+    // which must be URL-encoded to keep the revision in one segment of the
+    // path. Decoding them again would change a revision or path containing a
+    // `%`.
+    let (revision, file_path) = path.into_inner();
+    // For Linux/OS X, prepend a slash, so that `a/path/to/file.txt` becomes
+    // `/a/path/to/file.txt`.
+    #[cfg(not(target_os = "windows"))]
+    let file_path = if file_path.starts_with('/') {
+        file_path
+    } else {
+        format!("/{file_path}")
+    };
+    let Ok(file_path) = path::absolute(PathBuf::from(&file_path)) else {
+        return html_not_found(&format!(
+            "<p>The requested path <code>{}</code> is not valid.</p>",
+            escape(&file_path, EscapeContext::Text)
+        ));
+    };
+
+    // Reading from git blocks; do this on a separate thread.
+    let (revision_clone, file_path_clone) = (revision.clone(), file_path.clone());
+    let contents = match web::block(move || read_revision(&revision_clone, &file_path_clone)).await
+    {
+        Ok(Ok(contents)) => contents,
+        Ok(Err(err)) => {
+            return html_not_found(&format!("<p>{}</p>", escape(&err, EscapeContext::Text)))
+        }
+        Err(err) => {
+            return html_not_found(&format!(
+                "<p>Unable to read {} as of {}: {err}.</p>",
+                path_display(&file_path),
+                escape(&revision, EscapeContext::Text)
+            ))
+        }
+    };
+
+    // Render CodeChat Editor files; serve everything else, such as images,
+    // as is.
+    if let Ok(file_contents) = std::str::from_utf8(&contents) {
+        if let Some(page) = read_only_page(&file_path, file_contents) {
+            return HttpResponse::Ok()
+                .content_type(ContentType::html())
+                .body(page);
+        }
+    }
+    HttpResponse::Ok()
+        .content_type(mime_guess::from_path(&file_path).first_or_octet_stream())
+        .body(contents)
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use actix_web::{
+        test::{call_service, init_service, read_body, TestRequest},
+        App,
+    };
+    use assert_fs::TempDir;
+    use git2::{Oid, Repository, Signature};
+
+    use super::{read_revision, revision_endpoint};
+
+    // Commit the provided file, returning the new commit's hash.
+    fn commit_file(repo: &Repository, relative_path: &Path, message: &str) -> String {
+        let mut index = repo.index().unwrap();
+        index.add_path(relative_path).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parents: Vec<_> = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+        .to_string()
+    }
+
+    #[test]
+    fn test_read_revision() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let relative_path = Path::new("docs/a.py");
+        let file_path = temp_dir.path().join(relative_path);
+        fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        fs::write(&file_path, "# First\n").unwrap();
+        let first = commit_file(&repo, relative_path, "First");
+        fs::write(&file_path, "# Second\n").unwrap();
+        commit_file(&repo, relative_path, "Second");
+        // Change the working tree, which shouldn't be read.
+        fs::write(&file_path, "# Uncommitted\n").unwrap();
+
+        assert_eq!(read_revision(&first, &file_path).unwrap(), b"# First\n");
+        assert_eq!(read_revision("HEAD", &file_path).unwrap(), b"# Second\n");
+        assert_eq!(read_revision("HEAD~1", &file_path).unwrap(), b"# First\n");
+        // The working tree isn't changed.
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "# Uncommitted\n");
+
+        // A file which doesn't exist in the working tree can still be read.
+        fs::remove_dir_all(temp_dir.path().join("docs")).unwrap();
+        assert_eq!(read_revision("HEAD", &file_path).unwrap(), b"# Second\n");
+
+        // Report unknown revisions and files.
+        assert!(read_revision("no-such-branch", &file_path).is_err());
+        assert!(read_revision("HEAD", &temp_dir.path().join("missing.py")).is_err());
+        // A directory isn't a file.
+        assert!(read_revision("HEAD", &temp_dir.path().join("docs")).is_err());
+        temp_dir.close().unwrap();
+    }
+
+    // The revision and path in the URL are decoded once.
+    #[actix_web::test]
+    async fn test_revision_endpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let relative_path = Path::new("100%25 a.py");
+        let file_path = temp_dir.path().join(relative_path);
+        fs::write(&file_path, "# First\n").unwrap();
+        let first = commit_file(&repo, relative_path, "First");
+        repo.branch(
+            "feature/50%41",
+            &repo.find_commit(Oid::from_str(&first).unwrap()).unwrap(),
+            false,
+        )
+        .unwrap();
+        fs::write(&file_path, "# Second\n").unwrap();
+        commit_file(&repo, relative_path, "Second");
+
+        let app = init_service(App::new().service(revision_endpoint)).await;
+        for (revision, contents) in [("HEAD", "Second"), ("feature%2F50%2541", "First")] {
+            let url = format!(
+                "/rev/{revision}/{}",
+                urlencoding::encode(&file_path.to_string_lossy()).replace("%2F", "/")
+            );
+            let response = call_service(&app, TestRequest::get().uri(&url).to_request()).await;
+            assert!(response.status().is_success(), "{url}");
+            let body = read_body(response).await;
+            assert!(
+                std::str::from_utf8(&body).unwrap().contains(contents),
+                "{url}"
+            );
+        }
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `sandbox.rs` -- Configure the permissions of the Client's iframes
+///
+/// Both the Client framework page and the page shown in the IDE's webview load
+/// the Client in an iframe. By default, these iframes have neither a `sandbox`
+/// nor an `allow` attribute, so they receive the permissions of the page
+/// containing them. Some hosts require an iframe to request a permission
+/// explicitly; for example, VSCode's webview blocks popups, such as links
+/// opened in a new window, unless the iframe's sandbox includes
+/// `allow-popups`. The `serve --iframe-sandbox` and `serve --iframe-allow`
+/// options set these attributes, letting integrators opt into the permissions
+/// their host allows.
+// ## Imports
+//
+// ### Standard library
+use std::sync::RwLock;
+
+// ### Local
+use crate::processing::escape::{escape, EscapeContext};
+
+// ## Data structures
+/// The attributes added to the Client's iframes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IframeSettings {
+    /// The value of the
+    /// [`sandbox`](https://developer.mozilla.org/en-US/docs/Web/HTML/Element/iframe#sandbox)
+    /// attribute, such as `allow-scripts allow-same-origin allow-popups`, or
+    /// `None` to omit it.
+    pub sandbox: Option<String>,
+    /// The value of the
+    /// [`allow`](https://developer.mozilla.org/en-US/docs/Web/HTML/Element/iframe#allow)
+    /// attribute (a permissions policy), such as `clipboard-write`, or `None`
+    /// to omit it.
+    pub allow: Option<String>,
+}
+
+// ## Globals
+/// The attributes to add to the Client's iframes.
+static IFRAME_SETTINGS: RwLock<IframeSettings> = RwLock::new(IframeSettings {
+    sandbox: None,
+    allow: None,
+});
+
+// ## Code
+/// Add the provided attributes to the Client's iframes.
+pub fn set_iframe_settings(iframe_settings: IframeSettings) {
+    *IFRAME_SETTINGS.write().unwrap() = iframe_settings;
+}
+
+/// Return the attributes to add to an iframe containing the Client, each
+/// preceded by a space.
+pub fn iframe_attributes() -> String {
+    attributes_for(&IFRAME_SETTINGS.read().unwrap())
+}
+
+// Format the provided settings as attributes.
+fn attributes_for(iframe_settings: &IframeSettings) -> String {
+    let mut attributes = String::new();
+    for (name, value) in [
+        ("sandbox", &iframe_settings.sandbox),
+        ("allow", &iframe_settings.allow),
+    ] {
+        if let Some(value) = value {
+            attributes.push_str(&format!(
+                " {name}=\"{}\"",
+                escape(value, EscapeContext::Attribute)
+            ));
+        }
+    }
+    attributes
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{attributes_for, IframeSettings};
+
+    #[test]
+    fn test_attributes_for() {
+        assert_eq!(attributes_for(&IframeSettings::default()), "");
+        assert_eq!(
+            attributes_for(&IframeSettings {
+                sandbox: Some("allow-scripts allow-popups".to_string()),
+                allow: Some("clipboard-write \"self\"".to_string()),
+            }),
+            " sandbox=\"allow-scripts allow-popups\" allow=\"clipboard-write &quot;self&quot;\""
+        );
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `scroll.rs` -- Translate scroll positions between the IDE and the Client
+///
+/// The IDE and the Client show a file differently: the IDE shows its lines,
+/// while the Client renders each doc block, whose height rarely matches the
+/// lines of the comment it came from. So, each describes the top of its view
+/// in its own units:
+///
+/// *   the IDE provides a (fractional) line of the file;
+/// *   the Client provides a code or doc block, numbered from 0 in the order
+///     they appear in the file, and how far through this block the top of the
+///     view is, from 0 (its top) to 1 (its bottom). For a doc block, this is a
+///     fraction of its rendered height.
+///
+/// The Server translates between these, using the lines of the file each
+/// block occupies. Older IDEs and Clients send a normalized position instead;
+/// the Server translates this to the units of the receiver, too.
+// ## Imports
+//
+// ### Standard library
+use std::ops::Range;
+
+// ### Third-party
+use serde::{Deserialize, Serialize};
+
+// ### Local
+use crate::processing::CodeMirror;
+
+// ## Data structures
+/// <a id="ScrollPosition"></a>The position of the top of the view of a file.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ScrollPosition {
+    /// A position in the Client: a fraction `offset` of the way through the
+    /// code or doc block numbered `block`.
+    Block { block: usize, offset: f32 },
+    /// A position in the IDE: the line of the file, numbered from 0.
+    Line { line: f32 },
+    /// The position sent by older IDEs and Clients: 0 is the top of the file
+    /// and 1 is the bottom.
+    Normalized(f32),
+}
+
+// ## Code
+impl ScrollPosition {
+    /// Return this position as a line of the provided document.
+    pub fn to_line(self, code_mirror: &CodeMirror) -> f32 {
+        let line_count = line_count(code_mirror) as f32;
+        match self {
+            ScrollPosition::Block { block, offset } => match block_lines(code_mirror).get(block) {
+                Some(lines) => lines.start as f32 + offset.clamp(0.0, 1.0) * lines.len() as f32,
+                None => line_count,
+            },
+            ScrollPosition::Line { line } => line.clamp(0.0, line_count),
+            ScrollPosition::Normalized(position) => position.clamp(0.0, 1.0) * line_count,
+        }
+    }
+
+    /// Return this position as a block of the provided document.
+    pub fn to_block(self, code_mirror: &CodeMirror) -> ScrollPosition {
+        if let ScrollPosition::Block { .. } = self {
+            return self;
+        }
+        let line = self.to_line(code_mirror);
+        let block_lines = block_lines(code_mirror);
+        // Look for the block containing this line; past the end, use the last
+        // block.
+        let block = block_lines
+            .iter()
+            .position(|lines| line < lines.end as f32)
+            .unwrap_or(block_lines.len().saturating_sub(1));
+        let offset = match block_lines.get(block) {
+            Some(lines) => ((line - lines.start as f32) / lines.len() as f32).clamp(0.0, 1.0),
+            None => 0.0,
+        };
+        ScrollPosition::Block { block, offset }
+    }
+
+    /// Return this position as a normalized position in the provided
+    /// document.
+    pub fn to_normalized(self, code_mirror: &CodeMirror) -> f32 {
+        self.to_line(code_mirror) / line_count(code_mirror) as f32
+    }
+}
+
+// Return the number of lines in the provided document.
+fn line_count(code_mirror: &CodeMirror) -> usize {
+    code_mirror.doc.matches('\n').count() + 1
+}
+
+// Return the lines occupied by each code and doc block of the provided
+// document, in order. Each doc block occupies the lines from its `from` through
+// its `to` character; code blocks occupy the lines between them.
+fn block_lines(code_mirror: &CodeMirror) -> Vec<Range<usize>> {
+    // Doc block locations are given in characters, not bytes.
+    let newlines: Vec<usize> = code_mirror
+        .doc
+        .chars()
+        .enumerate()
+        .filter_map(|(index, c)| (c == '\n').then_some(index))
+        .collect();
+    let line_of = |index: usize| newlines.partition_point(|newline| *newline < index);
+
+    let mut block_lines = Vec::new();
+    let mut line = 0;
+    for (from, to, ..) in &code_mirror.doc_blocks {
+        let lines = line_of(*from)..line_of(*to) + 1;
+        if lines.start > line {
+            block_lines.push(line..lines.start);
+        }
+        line = lines.end;
+        block_lines.push(lines);
+    }
+    let line_count = newlines.len() + 1;
+    if line_count > line {
+        block_lines.push(line..line_count);
+    }
+    block_lines
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{block_lines, ScrollPosition};
+    use crate::processing::CodeMirror;
+
+    #[test]
+    fn test_scroll_position() {
+        // Two lines of code, a three-line doc block, then a line of code.
+        let code_mirror = CodeMirror {
+            doc: "a = 1\nb = 2\n\n\n\nc = 3".to_string(),
+            doc_blocks: vec![(12, 14, "".to_string(), "#".to_string(), "".to_string())],
+        };
+        assert_eq!(block_lines(&code_mirror), vec![0..2, 2..5, 5..6]);
+
+        // The IDE's lines become the Client's blocks...
+        let block = ScrollPosition::Line { line: 3.5 }.to_block(&code_mirror);
+        assert_eq!(
+            block,
+            ScrollPosition::Block {
+                block: 1,
+                offset: 0.5
+            }
+        );
+        // ...and back again.
+        assert_eq!(block.to_line(&code_mirror), 3.5);
+        assert_eq!(
+            ScrollPosition::Line { line: 1.0 }.to_block(&code_mirror),
+            ScrollPosition::Block {
+                block: 0,
+                offset: 0.5
+            }
+        );
+        // Positions past the end are at the end.
+        assert_eq!(
+            ScrollPosition::Line { line: 10.0 }.to_block(&code_mirror),
+            ScrollPosition::Block {
+                block: 2,
+                offset: 1.0
+            }
+        );
+
+        // Normalized positions from older IDEs and Clients are translated too.
+        assert_eq!(ScrollPosition::Normalized(0.5).to_line(&code_mirror), 3.0);
+        assert_eq!(
+            ScrollPosition::Block {
+                block: 2,
+                offset: 0.0
+            }
+            .to_normalized(&code_mirror),
+            5.0 / 6.0
+        );
+
+        // Each form is recognized when received.
+        for (json, position) in [
+            (
+                r#"{"block":1,"offset":0.5}"#,
+                ScrollPosition::Block {
+                    block: 1,
+                    offset: 0.5,
+                },
+            ),
+            (r#"{"line":2.0}"#, ScrollPosition::Line { line: 2.0 }),
+            ("0.25", ScrollPosition::Normalized(0.25)),
+        ] {
+            assert_eq!(
+                serde_json::from_str::<ScrollPosition>(json).unwrap(),
+                position
+            );
+        }
+    }
+}
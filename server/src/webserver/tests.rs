@@ -16,16 +16,29 @@
 /// # `test.rs` -- Unit tests for the webserver
 // ## Imports
 use std::{
+    collections::BTreeMap,
+    fs,
     path::{PathBuf, MAIN_SEPARATOR_STR},
     thread::{self, sleep},
     time::Duration,
 };
 
+use actix_web::{
+    test::{call_service, init_service, read_body, TestRequest},
+    App,
+};
 use assert_cmd::Command;
+use assert_fs::TempDir;
 use assertables::{assert_ends_with, assert_starts_with};
 
-use super::{filewatcher::FILEWATCHER_PATH_PREFIX, path_to_url, url_to_path};
-use crate::prep_test_dir;
+use super::{
+    filewatcher::FILEWATCHER_PATH_PREFIX, html_wrapper, markdown_buffer_endpoint,
+    markdown_endpoint, mathjax_tags, path_to_url, read_only_page, serve_file, url_to_path,
+    SimpleHttpResponse,
+};
+use crate::{
+    cast, prep_test_dir, processing::project_config::TexMacro, test_utils::accessibility_problems,
+};
 
 // ## Constants
 /// The default port on which the server listens for incoming connections.
@@ -90,6 +103,139 @@ fn test_path_to_url() {
     temp_dir.close().unwrap();
 }
 
+#[test]
+fn test_mathjax_tags() {
+    assert!(mathjax_tags(&BTreeMap::new())
+        .contains(r#"<script type="application/json" id="CodeChat-tex-macros">{}</script>"#));
+    let tex_macros = BTreeMap::from([
+        ("R".to_string(), TexMacro::Simple("\\mathbb{R}".to_string())),
+        (
+            "vec".to_string(),
+            TexMacro::WithArguments("\\mathbf{#1}".to_string(), 1),
+        ),
+        // A macro can't end the script.
+        ("bad".to_string(), TexMacro::Simple("</script>".to_string())),
+    ]);
+    assert!(mathjax_tags(&tex_macros).contains(
+        r#"<script type="application/json" id="CodeChat-tex-macros">{"R":"\\mathbb{R}","bad":"\u003c/script>","vec":["\\mathbf{#1}",1]}</script>"#
+    ));
+}
+
+// Check the pages the Server generates for accessibility problems.
+#[actix_web::test]
+async fn test_accessibility() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.py");
+    let file_contents = "# # Title\n#\n# ![A logo](logo.png)\nprint(1)\n";
+    fs::write(&file_path, file_contents).unwrap();
+    let toc_path = temp_dir.path().join("toc.md");
+    let toc_contents = "# Contents\n\n[Test](test.py)\n";
+    fs::write(&toc_path, toc_contents).unwrap();
+
+    // Check the Client, slides, and mobile pages, then the TOC.
+    for (file_path, file_contents, is_toc, is_slides, is_mobile) in [
+        (&file_path, file_contents, false, false, false),
+        (&file_path, file_contents, false, true, false),
+        (&file_path, file_contents, false, false, true),
+        (&toc_path, toc_contents, true, false, false),
+    ] {
+        let (simple_http_response, _) = serve_file(
+            file_path,
+            file_contents,
+            is_toc,
+            is_slides,
+            is_mobile,
+            true,
+            false,
+        )
+        .await;
+        let html = cast!(simple_http_response, SimpleHttpResponse::Ok);
+        assert_eq!(
+            accessibility_problems(&html),
+            Vec::<String>::new(),
+            "is_toc = {is_toc}, is_slides = {is_slides}, is_mobile = {is_mobile}"
+        );
+    }
+    // Check the page used for errors and directory listings.
+    assert!(accessibility_problems(&html_wrapper("<p>Not found.</p>")).is_empty());
+
+    temp_dir.close().unwrap();
+}
+
+// Check that the Client's page includes a rendering of the file, for use
+// without JavaScript.
+#[actix_web::test]
+async fn test_initial_contents() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.py");
+    let file_contents = "# A *doc* block.\nprint('</script>')\n";
+    fs::write(&file_path, file_contents).unwrap();
+    let (simple_http_response, _) =
+        serve_file(&file_path, file_contents, false, false, false, true, false).await;
+    let html = cast!(simple_http_response, SimpleHttpResponse::Ok);
+    assert!(html.contains("<noscript>"));
+    assert!(html.contains("<p>A <em>doc</em> block.</p>"));
+    assert!(html.contains("print('&lt;/script&gt;')"));
+    assert!(html.contains(r#"<script type="application/json" id="CodeChat-initial-contents">{"metadata":{"mode":"python"},"#));
+    // The contents can't end their script.
+    assert!(html.contains(r"print('\u003c/script>')"));
+
+    temp_dir.close().unwrap();
+}
+
+// Check that a read-only page, such as a student's view of a classroom, omits
+// content meant for other audiences.
+#[test]
+fn test_read_only_page() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.py");
+    let file_contents =
+        "# Everyone.\n#\n# :::if audience=instructor\n# The answer is 42.\n# :::\nprint(1)\n";
+    fs::write(&file_path, file_contents).unwrap();
+    let page = read_only_page(&file_path, file_contents).unwrap();
+    assert!(page.contains("<p>Everyone.</p>"));
+    assert!(!page.contains("The answer is 42."));
+    assert!(!page.contains("CodeChat-if"));
+
+    temp_dir.close().unwrap();
+}
+
+// Check that the `/markdown` endpoints return the doc blocks of the file on
+// disk, or of the provided contents.
+#[actix_web::test]
+async fn test_markdown_endpoints() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.py");
+    fs::write(&file_path, "# Saved.\nprint(1)\n").unwrap();
+    let url = format!(
+        "/markdown/{}",
+        file_path.to_str().unwrap().trim_start_matches('/')
+    );
+    let app = init_service(
+        App::new()
+            .service(markdown_endpoint)
+            .service(markdown_buffer_endpoint),
+    )
+    .await;
+
+    let response = call_service(&app, TestRequest::get().uri(&url).to_request()).await;
+    assert!(response.status().is_success());
+    assert_eq!(read_body(response).await, "Saved.\n");
+
+    let response = call_service(
+        &app,
+        TestRequest::post()
+            .uri(&url)
+            .set_payload("# Unsaved.\nprint(1)\n")
+            .to_request(),
+    )
+    .await;
+    assert!(response.status().is_success());
+    assert_eq!(read_body(response).await, "Unsaved.\n");
+
+    temp_dir.close().unwrap();
+}
+
 // Test startup outside the repo path.
 #[test]
 fn test_other_path() {
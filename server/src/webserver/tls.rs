@@ -0,0 +1,122 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `tls.rs` -- Serve HTTP/2 over TLS
+///
+/// Browsers only use HTTP/2 over TLS. When given a certificate and its private
+/// key (`serve --tls-cert <cert.pem> --tls-key <key.pem>`), the Server also
+/// listens for HTTPS connections on a second port, where HTTP/2 lets the
+/// browser fetch the Client's many static files (MathJax fonts, CodeMirror,
+/// and so on) over one connection, rather than one at a time over a few HTTP/1.1
+/// connections. The IDE extension and the Server's own commands continue to
+/// use the unencrypted port.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    fs::File,
+    io::BufReader,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+// ### Third-party
+use rustls::{crypto::ring::default_provider, ServerConfig};
+
+// ## Data structures
+/// Where to find the certificate, and which port to serve HTTPS on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsSettings {
+    /// The port for HTTPS connections.
+    pub port: u16,
+    /// A PEM file containing the certificate chain.
+    pub cert_path: PathBuf,
+    /// A PEM file containing the certificate's private key.
+    pub key_path: PathBuf,
+}
+
+// ## Globals
+/// The TLS settings, or `None` to serve only unencrypted HTTP.
+static TLS_SETTINGS: RwLock<Option<TlsSettings>> = RwLock::new(None);
+
+// ## Code
+/// Serve HTTPS using the provided settings, or only HTTP if these are `None`.
+pub fn set_tls(tls_settings: Option<TlsSettings>) {
+    *TLS_SETTINGS.write().unwrap() = tls_settings;
+}
+
+/// Return the TLS settings, if the Server should serve HTTPS.
+pub fn tls_settings() -> Option<TlsSettings> {
+    TLS_SETTINGS.read().unwrap().clone()
+}
+
+/// Load the certificate and key named in the provided settings.
+pub fn load_server_config(tls_settings: &TlsSettings) -> Result<ServerConfig, String> {
+    let cert_path = &tls_settings.cert_path;
+    let key_path = &tls_settings.key_path;
+    let mut cert_reader = BufReader::new(
+        File::open(cert_path).map_err(|err| format!("Unable to open {cert_path:?}: {err}"))?,
+    );
+    let certs = rustls_pemfile::certs(&mut cert_reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Unable to read certificates from {cert_path:?}: {err}"))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {cert_path:?}."));
+    }
+    let mut key_reader = BufReader::new(
+        File::open(key_path).map_err(|err| format!("Unable to open {key_path:?}: {err}"))?,
+    );
+    let key = rustls_pemfile::private_key(&mut key_reader)
+        .map_err(|err| format!("Unable to read the private key from {key_path:?}: {err}"))?
+        .ok_or_else(|| format!("No private key found in {key_path:?}."))?;
+    ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|err| format!("Invalid certificate or key: {err}"))
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use assert_fs::TempDir;
+
+    use super::{load_server_config, TlsSettings};
+
+    #[test]
+    fn test_load_server_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let tls_settings = TlsSettings {
+            port: 8443,
+            cert_path: temp_dir.path().join("cert.pem"),
+            key_path: temp_dir.path().join("key.pem"),
+        };
+        // Missing files produce an error.
+        assert!(load_server_config(&tls_settings)
+            .unwrap_err()
+            .starts_with("Unable to open"));
+
+        // So do files without a certificate.
+        fs::write(&tls_settings.cert_path, "not a certificate").unwrap();
+        fs::write(&tls_settings.key_path, "not a key").unwrap();
+        assert!(load_server_config(&tls_settings)
+            .unwrap_err()
+            .starts_with("No certificates found"));
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `todos.rs` -- List the TODOs in a project
+///
+/// `/todos/path/to/project` lists the [TODO, FIXME, and HACK
+/// markers](../processing/todos.rs) in all files in this directory and its
+/// subdirectories, grouped by file or by owner, with a link to each marker's
+/// file. The query parameters `marker`, `owner`, and `text` filter the list;
+/// `group=owner` groups it by owner; `format=json` returns the list as JSON
+/// instead.
+///
+/// The page stays current: a file watcher on the project's directory notifies
+/// the page, using [server-sent
+/// events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events),
+/// when a file changes; the page then reloads.
+// ## Imports
+//
+// ### Standard library
+use std::{
+    collections::BTreeMap,
+    path::{self, Path, PathBuf},
+    time::Duration,
+};
+
+// ### Third-party
+use actix_web::{
+    get,
+    http::header::{CacheControl, CacheDirective, ContentType},
+    web, HttpResponse,
+};
+use bytes::Bytes;
+use futures_util::stream;
+use indoc::formatdoc;
+use log::error;
+use notify_debouncer_full::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+// ### Local
+use super::{
+    base_path::with_base_path, html_not_found, inline_script, json_data_tag, path_display,
+    path_to_url,
+};
+use crate::processing::{
+    escape::{escape, EscapeContext},
+    todos::{find_project_todos, Todo},
+};
+
+// ## Globals
+/// Reload the page when a file in its directory changes.
+const TODOS_SCRIPT: &str = r#"new EventSource(JSON.parse(document.getElementById("CodeChat-todo-events").textContent)).onmessage = () => location.reload();"#;
+
+// ## Data structures
+/// The query parameters of the `/todos` endpoint.
+#[derive(Debug, Default, Deserialize)]
+pub struct TodoQuery {
+    /// Only list this kind of marker, such as `FIXME`.
+    marker: Option<String>,
+    /// Only list markers with this owner.
+    owner: Option<String>,
+    /// Only list markers whose text contains this, ignoring case.
+    text: Option<String>,
+    /// Group by `file` (the default) or `owner`.
+    group: Option<String>,
+    /// `html` (the default) or `json`.
+    format: Option<String>,
+}
+
+// ## Code
+/// ### Endpoints
+///
+/// List the markers in the requested directory.
+#[get("/todos/{dir_path:.*}")]
+pub async fn todos_endpoint(
+    dir_path: web::Path<String>,
+    query: web::Query<TodoQuery>,
+) -> HttpResponse {
+    let dir_path = match project_dir(&dir_path) {
+        Ok(dir_path) => dir_path,
+        Err(err) => return html_not_found(&format!("<p>{err}</p>")),
+    };
+    // Searching reads every file; do this on a separate thread.
+    let search_dir = dir_path.clone();
+    let todos = match web::block(move || find_project_todos(&search_dir)).await {
+        Ok(todos) => todos,
+        Err(err) => {
+            return html_not_found(&format!(
+                "<p>Unable to search {}: {err}.</p>",
+                path_display(&dir_path)
+            ))
+        }
+    };
+    let todos = filter_todos(todos, &query);
+    if query.format.as_deref() == Some("json") {
+        HttpResponse::Ok().json(todos)
+    } else {
+        HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(todos_page(&dir_path, &todos, &query))
+    }
+}
+
+/// Send an event each time a file in the requested directory changes, so that
+/// the TODO page can reload.
+#[get("/todo-events/{dir_path:.*}")]
+pub async fn todo_events_endpoint(dir_path: web::Path<String>) -> HttpResponse {
+    let dir_path = match project_dir(&dir_path) {
+        Ok(dir_path) => dir_path,
+        Err(err) => return html_not_found(&format!("<p>{err}</p>")),
+    };
+    let (watcher_tx, watcher_rx) = mpsc::channel(10);
+    let mut debounced_watcher = match new_debouncer(
+        Duration::from_secs(1),
+        None,
+        // This runs in a thread created by the watcher. Don't wait to send:
+        // if the queue is full, the page is already about to reload. Once the
+        // page disconnects, the receiver is dropped and sends fail, which is
+        // expected.
+        move |result: DebounceEventResult| {
+            if result.is_ok_and(|events| !events.is_empty()) {
+                let _ = watcher_tx.try_send(());
+            }
+        },
+    ) {
+        Ok(debounced_watcher) => debounced_watcher,
+        Err(err) => {
+            error!("Unable to create debouncer: {err}");
+            return html_not_found(&format!("<p>Unable to watch for changes: {err}.</p>"));
+        }
+    };
+    if let Err(err) = debounced_watcher.watch(&dir_path, RecursiveMode::Recursive) {
+        return html_not_found(&format!(
+            "<p>Unable to watch {}: {err}.</p>",
+            path_display(&dir_path)
+        ));
+    }
+    // Keep the watcher alive for as long as the page is connected; it's dropped
+    // along with the stream.
+    let events = stream::unfold(
+        (watcher_rx, debounced_watcher),
+        |(mut watcher_rx, debounced_watcher)| async move {
+            watcher_rx.recv().await.map(|()| {
+                (
+                    Ok::<_, actix_web::Error>(Bytes::from_static(b"data: changed\n\n")),
+                    (watcher_rx, debounced_watcher),
+                )
+            })
+        },
+    );
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(events)
+}
+
+// Convert the path from a URL to a directory to search; for a file, search its
+// directory.
+fn project_dir(dir_path: &str) -> Result<PathBuf, String> {
+    // For Linux/OS X, prepend a slash, so that `a/path/to/dir` becomes
+    // `/a/path/to/dir`.
+    #[cfg(not(target_os = "windows"))]
+    let dir_path = format!("/{}", dir_path.trim_start_matches('/'));
+    let path = path::absolute(PathBuf::from(&dir_path)).map_err(|err| {
+        format!(
+            "The path {} is not valid: {err}.",
+            path_display(Path::new(&dir_path))
+        )
+    })?;
+    if path.is_dir() {
+        Ok(path)
+    } else if path.is_file() {
+        Ok(path.parent().map(Path::to_path_buf).unwrap_or_default())
+    } else {
+        Err(format!("{} is not a directory.", path_display(&path)))
+    }
+}
+
+// Return the URL-encoded path, without a leading slash, as the endpoints
+// expect.
+fn url_path(path: &Path) -> String {
+    path_to_url("", "", path)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+// Keep only the markers selected by the query.
+fn filter_todos(todos: Vec<Todo>, query: &TodoQuery) -> Vec<Todo> {
+    let is_selected = |filter: &Option<String>, value: Option<&str>| match filter
+        .as_deref()
+        .filter(|filter| !filter.is_empty())
+    {
+        None => true,
+        Some(filter) => value.is_some_and(|value| value.eq_ignore_ascii_case(filter)),
+    };
+    let text = query.text.as_deref().unwrap_or("").to_lowercase();
+    todos
+        .into_iter()
+        .filter(|todo| {
+            is_selected(&query.marker, Some(&todo.marker))
+                && is_selected(&query.owner, todo.owner.as_deref())
+                && todo.text.to_lowercase().contains(&text)
+        })
+        .collect()
+}
+
+// Return the page listing the provided markers.
+fn todos_page(dir_path: &Path, todos: &[Todo], query: &TodoQuery) -> String {
+    let group_by_owner = query.group.as_deref() == Some("owner");
+    let mut groups: BTreeMap<String, Vec<&Todo>> = BTreeMap::new();
+    for todo in todos {
+        let key = if group_by_owner {
+            todo.owner
+                .clone()
+                .unwrap_or_else(|| "(No owner)".to_string())
+        } else {
+            todo.file_path.clone()
+        };
+        groups.entry(key).or_default().push(todo);
+    }
+
+    let mut list_html = String::new();
+    for (group, todos) in &groups {
+        list_html.push_str(&format!(
+            "<section><h2>{}</h2><ul>\n",
+            escape(group, EscapeContext::Text)
+        ));
+        for todo in todos {
+            // Link to the file in the File Watcher.
+            let file_url =
+                with_base_path(&format!("/fw/fsb/{}", url_path(Path::new(&todo.file_path))));
+            let location = if group_by_owner {
+                format!("{}:{}", todo.file_path, todo.line)
+            } else {
+                format!(
+                    "line {}{}",
+                    todo.line,
+                    todo.owner
+                        .as_ref()
+                        .map(|owner| format!(", {owner}"))
+                        .unwrap_or_default()
+                )
+            };
+            list_html.push_str(&format!(
+                "<li><strong class=\"CodeChat-todo-{}\">{}</strong> {} (<a href=\"{}\">{}</a>)</li>\n",
+                todo.marker.to_lowercase(),
+                escape(&todo.marker, EscapeContext::Text),
+                escape(&todo.text, EscapeContext::Text),
+                escape(&file_url, EscapeContext::Attribute),
+                escape(&location, EscapeContext::Text)
+            ));
+        }
+        list_html.push_str("</ul></section>\n");
+    }
+    if groups.is_empty() {
+        list_html.push_str("<p>No markers found.</p>\n");
+    }
+
+    let selected = |value: &str, current: Option<&str>| {
+        if current.unwrap_or("") == value {
+            " selected"
+        } else {
+            ""
+        }
+    };
+    let marker_options: String = ["", "TODO", "FIXME", "HACK"]
+        .iter()
+        .map(|&marker| {
+            format!(
+                "<option value=\"{marker}\"{}>{}</option>",
+                selected(marker, query.marker.as_deref()),
+                if marker.is_empty() { "All" } else { marker }
+            )
+        })
+        .collect();
+    let attribute =
+        |value: &Option<String>| escape(value.as_deref().unwrap_or(""), EscapeContext::Attribute);
+    formatdoc!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <title>TODOs - The CodeChat Editor</title>
+                <style>
+                    .CodeChat-todo-fixme {{ color: #cf222e; }}
+                    .CodeChat-todo-hack {{ color: #9a6700; }}
+                </style>
+            </head>
+            <body>
+                <main>
+                    <h1>TODOs in {dir}</h1>
+                    <form method="get" role="search">
+                        <label>Marker <select name="marker">{marker_options}</select></label>
+                        <label>Owner <input name="owner" value="{owner}"></label>
+                        <label>Text <input name="text" value="{text}"></label>
+                        <label>Group by <select name="group">
+                            <option value="file"{by_file}>File</option>
+                            <option value="owner"{by_owner}>Owner</option>
+                        </select></label>
+                        <button type="submit">Filter</button>
+                    </form>
+                    <p>{count} marker(s).</p>
+                    {list_html}
+                </main>
+                {events_data}
+                {events_script}
+            </body>
+        </html>
+        "#,
+        dir = path_display(dir_path),
+        owner = attribute(&query.owner),
+        text = attribute(&query.text),
+        by_file = selected("file", Some(query.group.as_deref().unwrap_or("file"))),
+        by_owner = selected("owner", query.group.as_deref()),
+        count = todos.len(),
+        events_data = json_data_tag(
+            "CodeChat-todo-events",
+            &with_base_path(&format!("/todo-events/{}", url_path(dir_path)))
+        ),
+        events_script = inline_script("", TODOS_SCRIPT),
+    )
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{filter_todos, todos_page, TodoQuery};
+    use crate::processing::todos::Todo;
+
+    fn todos() -> Vec<Todo> {
+        vec![
+            Todo {
+                file_path: "/a.py".to_string(),
+                line: 1,
+                marker: "TODO".to_string(),
+                owner: Some("alice".to_string()),
+                text: "Handle errors.".to_string(),
+            },
+            Todo {
+                file_path: "/b.py".to_string(),
+                line: 2,
+                marker: "FIXME".to_string(),
+                owner: None,
+                text: "Off by one.".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_filter_todos() {
+        let filter = |query: TodoQuery| -> Vec<usize> {
+            filter_todos(todos(), &query)
+                .iter()
+                .map(|todo| todo.line)
+                .collect()
+        };
+        assert_eq!(filter(TodoQuery::default()), vec![1, 2]);
+        assert_eq!(
+            filter(TodoQuery {
+                marker: Some("fixme".to_string()),
+                ..Default::default()
+            }),
+            vec![2]
+        );
+        assert_eq!(
+            filter(TodoQuery {
+                owner: Some("Alice".to_string()),
+                ..Default::default()
+            }),
+            vec![1]
+        );
+        assert_eq!(
+            filter(TodoQuery {
+                text: Some("ERRORS".to_string()),
+                ..Default::default()
+            }),
+            vec![1]
+        );
+        // An empty filter selects everything.
+        assert_eq!(
+            filter(TodoQuery {
+                marker: Some("".to_string()),
+                ..Default::default()
+            }),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_todos_page() {
+        let page = todos_page(Path::new("/project"), &todos(), &TodoQuery::default());
+        assert!(page.contains("<h2>/a.py</h2>"));
+        assert!(page.contains("Handle errors."));
+        assert!(page.contains("line 1, alice"));
+        assert!(page.contains("2 marker(s)."));
+
+        let page = todos_page(
+            Path::new("/project"),
+            &todos(),
+            &TodoQuery {
+                group: Some("owner".to_string()),
+                ..Default::default()
+            },
+        );
+        assert!(page.contains("<h2>alice</h2>"));
+        assert!(page.contains("<h2>(No owner)</h2>"));
+        assert!(page.contains("/b.py:2"));
+        assert!(
+            todos_page(Path::new("/project"), &[], &TodoQuery::default())
+                .contains("No markers found.")
+        );
+    }
+}
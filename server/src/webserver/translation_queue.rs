@@ -0,0 +1,168 @@
+// Copyright (C) 2023 Bryan A. Jones.
+//
+// This file is part of the CodeChat Editor. The CodeChat Editor is free
+// software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+//
+// The CodeChat Editor is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// the CodeChat Editor. If not, see
+// [http://www.gnu.org/licenses](http://www.gnu.org/licenses).
+/// # `translation_queue.rs` -- Serialize translations of each file
+///
+/// The IDE may send several updates to a file while the Server is still
+/// translating an earlier one. Translating each in turn wastes time on results
+/// which are stale before they're finished; translating them concurrently may
+/// deliver results out of order. Instead, this queue allows only one
+/// translation per file at a time, and keeps only the newest request which
+/// arrives during that translation; any older waiting request is superseded.
+// ## Imports
+//
+// ### Standard library
+use std::collections::HashMap;
+
+// ## Data structures
+//
+// The state of translations for one file.
+struct FileQueue<T> {
+    // The request to translate once the running translation finishes.
+    next: Option<T>,
+}
+
+/// What to do with a newly-requested translation.
+#[derive(Debug, PartialEq)]
+pub enum Queued<T> {
+    /// No translation of this file is running; start this one.
+    Start(T),
+    /// A translation of this file is running; this request will be returned
+    /// by `finished`. It replaced the request returned here, if any, which
+    /// won't be translated.
+    Waiting { superseded: Option<T> },
+}
+
+/// The translations in progress, indexed by file path.
+pub struct TranslationQueue<T> {
+    files: HashMap<String, FileQueue<T>>,
+}
+
+// ## Code
+impl<T> TranslationQueue<T> {
+    pub fn new() -> Self {
+        TranslationQueue {
+            files: HashMap::new(),
+        }
+    }
+
+    /// Request a translation of `file_path`.
+    pub fn request(&mut self, file_path: &str, request: T) -> Queued<T> {
+        match self.files.get_mut(file_path) {
+            Some(file_queue) => Queued::Waiting {
+                superseded: file_queue.next.replace(request),
+            },
+            None => {
+                self.files
+                    .insert(file_path.to_string(), FileQueue { next: None });
+                Queued::Start(request)
+            }
+        }
+    }
+
+    /// Report that the running translation of `file_path` finished. Returns
+    /// `None` if its result is current; otherwise, the result is stale and
+    /// this returns the newer request to translate next.
+    pub fn finished(&mut self, file_path: &str) -> Option<T> {
+        let next = self
+            .files
+            .get_mut(file_path)
+            .and_then(|file_queue| file_queue.next.take());
+        if next.is_none() {
+            self.files.remove(file_path);
+        }
+        next
+    }
+
+    /// True if a translation of `file_path` is running.
+    pub fn is_translating(&self, file_path: &str) -> bool {
+        self.files.contains_key(file_path)
+    }
+
+    /// The number of files being translated.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// True if no translations are running.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl<T> Default for TranslationQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ## Tests
+#[cfg(test)]
+mod tests {
+    use super::{Queued, TranslationQueue};
+
+    #[test]
+    fn test_single_update() {
+        let mut tq = TranslationQueue::new();
+        assert!(tq.is_empty());
+        assert_eq!(tq.request("a.py", 1), Queued::Start(1));
+        assert!(!tq.is_empty());
+        assert!(tq.is_translating("a.py"));
+        assert!(!tq.is_translating("b.py"));
+        assert_eq!(tq.finished("a.py"), None);
+        assert!(tq.is_empty());
+        assert!(!tq.is_translating("a.py"));
+        // Once finished, the next request starts immediately.
+        assert_eq!(tq.request("a.py", 2), Queued::Start(2));
+        assert_eq!(tq.finished("a.py"), None);
+    }
+
+    #[test]
+    fn test_rapid_updates() {
+        let mut tq = TranslationQueue::new();
+        assert_eq!(tq.request("a.py", 1), Queued::Start(1));
+        // Updates arriving during a translation wait; each supersedes the one
+        // before it.
+        assert_eq!(tq.request("a.py", 2), Queued::Waiting { superseded: None });
+        assert_eq!(
+            tq.request("a.py", 3),
+            Queued::Waiting {
+                superseded: Some(2)
+            }
+        );
+        assert_eq!(
+            tq.request("a.py", 4),
+            Queued::Waiting {
+                superseded: Some(3)
+            }
+        );
+        // The result of the first translation is stale; only the newest
+        // request remains.
+        assert_eq!(tq.finished("a.py"), Some(4));
+        assert_eq!(tq.finished("a.py"), None);
+        assert_eq!(tq.request("a.py", 5), Queued::Start(5));
+    }
+
+    #[test]
+    fn test_independent_files() {
+        let mut tq = TranslationQueue::new();
+        assert_eq!(tq.request("a.py", 1), Queued::Start(1));
+        assert_eq!(tq.request("b.py", 2), Queued::Start(2));
+        assert_eq!(tq.request("a.py", 3), Queued::Waiting { superseded: None });
+        assert_eq!(tq.finished("b.py"), None);
+        assert_eq!(tq.finished("a.py"), Some(3));
+        assert_eq!(tq.finished("a.py"), None);
+    }
+}
@@ -20,36 +20,66 @@
 // ### Standard library
 use std::{
     cmp::min,
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
+    sync::atomic::Ordering,
 };
 
 // ### Third-party
 use actix_web::{
-    error::{Error, ErrorBadRequest},
+    error::{Error, ErrorBadRequest, ErrorForbidden},
     get, web, HttpRequest, HttpResponse,
 };
 use indoc::formatdoc;
-use log::{debug, error, warn};
-use open;
-use tokio::{select, sync::mpsc};
+use log::{debug, error, warn, LevelFilter};
+use tokio::{fs, select, sync::mpsc, time::sleep};
 
 // ### Local
 use super::{
-    client_websocket, get_client_framework, send_response, AppState, EditorMessage,
-    EditorMessageContents, IdeType, WebsocketQueues, IP_ADDRESS,
+    base_path::with_base_path,
+    classroom::is_instructor_request,
+    client_websocket,
+    csp::simple_viewer_policy,
+    events::EventPublisher,
+    export_message,
+    external::check_external_url,
+    get_client_framework,
+    log_forward::{next_log_record, LogSubscription},
+    open_files::OpenFiles,
+    outside_project::{outside_project, refused_message, OutsideProjectPolicy},
+    proxy::{proxy_doc_blocks, unproxy_doc_blocks},
+    sandbox::iframe_attributes,
+    scroll::ScrollPosition,
+    send_response,
+    translation_queue::{Queued, TranslationQueue},
+    AppState, EditorMessage, EditorMessageContents, IdeType, WebsocketQueues, IP_ADDRESS,
+    LOAD_FILE_RETRIES, LOAD_FILE_RETRY_DELAY, TIMEOUT_PREFIX,
 };
 use crate::{
+    browser_open::{open_url, server_url},
     oneshot_send,
     processing::{
-        codechat_for_web_to_source, source_to_codechat_for_web_string, CodeChatForWeb, CodeMirror,
-        TranslationResultsString,
+        anchors::record_anchors,
+        codechat_for_web_to_source,
+        definitions::find_definition,
+        diagnostics::{Diagnostic, Severity},
+        diff::DiffGranularity,
+        diff_code_mirror,
+        escape::{escape, EscapeContext},
+        outline::OutlineEntry,
+        project_config::RenderTarget,
+        set_file_language, source_to_codechat_for_web_string,
+        workspace::set_workspace_roots,
+        CodeChatForWeb, CodeMirror, CodeMirrorDiff, TranslationResultsString,
     },
     queue_send,
     webserver::{
-        escape_html, filesystem_endpoint, html_wrapper, make_simple_http_response, path_to_url,
-        text_file_to_response, url_to_path, ProcessingTaskHttpRequest, ResultOkTypes,
-        UpdateMessageContents, INITIAL_MESSAGE_ID, MESSAGE_ID_INCREMENT,
+        capabilities, diagnostics_message, filesystem_endpoint, html_wrapper, insert_snippet,
+        list_snippets, make_simple_http_response, outline_entries, path_to_url,
+        preferences_message, text_file_to_response, url_to_path, CurrentFileContents,
+        FoldRangesContents, GotoDefinitionContents, ProcessingTaskHttpRequest, ResultOkTypes,
+        UpdateDiffContents, UpdateMessageContents, ViewTarget, INITIAL_MESSAGE_ID,
+        MESSAGE_ID_INCREMENT,
     },
 };
 
@@ -70,6 +100,11 @@ pub async fn vscode_ide_websocket(
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
     let connection_id_str = connection_id.to_string();
+    // In classroom mode, determine if this is the instructor's IDE.
+    let is_instructor = is_instructor_request(&req).map_err(|err| {
+        error!("{err}");
+        ErrorForbidden(err)
+    })?;
 
     // There are three cases for this `connection_id`:
     //
@@ -91,6 +126,9 @@ pub async fn vscode_ide_websocket(
         error!("{msg}");
         return Err(ErrorBadRequest(msg));
     }
+    if is_instructor {
+        app_state.classroom.set_instructor(&connection_id_str);
+    }
 
     // Now case 2.
     if app_state
@@ -155,7 +193,22 @@ pub async fn vscode_ide_websocket(
         // to provide a way to exit the current task.
         'task: {
             let mut current_file = PathBuf::new();
-            let mut load_file_requests: HashMap<u64, ProcessingTaskHttpRequest> = HashMap::new();
+            let mut load_file_requests: HashMap<u64, LoadFileRequest> = HashMap::new();
+            // The file changed by each `UpdateDiff` awaiting the Client's
+            // `Result`, indexed by the message's ID.
+            let mut pending_diffs: HashMap<u64, String> = HashMap::new();
+            // Files which the IDE reported aren't open; the Server reads and
+            // saves these directly on disk.
+            let mut disk_files: HashSet<PathBuf> = HashSet::new();
+            // The documents opened during this connection.
+            let mut open_files = OpenFiles::new();
+            // Files the Client shows in additional views, besides the current
+            // file.
+            let mut views: HashSet<PathBuf> = HashSet::new();
+            // The outline of each file, found when it was last translated.
+            let mut outlines: HashMap<String, Vec<OutlineEntry>> = HashMap::new();
+            // Requests which the IDE didn't answer are resent via this queue.
+            let (load_file_retry_tx, mut load_file_retry_rx) = mpsc::channel(10);
             debug!("VSCode processing task started.");
 
             // Get the first message sent by the IDE.
@@ -189,14 +242,20 @@ pub async fn vscode_ide_websocket(
 
                         // Send the HTML for the internal browser.
                         let port = app_state_task.port;
+                        let iframe_attributes = iframe_attributes();
+                        let policy = escape(
+                            &simple_viewer_policy(&format!("http://{IP_ADDRESS}:{port}")),
+                            EscapeContext::Attribute,
+                        );
                         let client_html = formatdoc!(
                             r#"
                             <!DOCTYPE html>
                             <html>
                                 <head>
+                                    <meta http-equiv="Content-Security-Policy" content="{policy}">
                                 </head>
                                 <body style="margin: 0px; padding: 0px; overflow: hidden">
-                                    <iframe src="http://{IP_ADDRESS}:{port}/vsc/cf/{connection_id_task}" style="width: 100%; height: 100vh; border: none"></iframe>
+                                    <iframe src="http://{IP_ADDRESS}:{port}/vsc/cf/{connection_id_task}"{iframe_attributes} style="width: 100%; height: 100vh; border: none"></iframe>
                                 </body>
                             </html>"#
                         );
@@ -244,11 +303,10 @@ pub async fn vscode_ide_websocket(
                         };
                     } else {
                         // Open the Client in an external browser.
-                        if let Err(err) = open::that_detached(format!(
-                            "http://{IP_ADDRESS}:{}/vsc/cf/{connection_id_task}",
-                            app_state_task.port
+                        if let Err(msg) = open_url(&server_url(
+                            app_state_task.port,
+                            &format!("/vsc/cf/{connection_id_task}"),
                         )) {
-                            let msg = format!("Unable to open web browser: {err}");
                             error!("{msg}");
                             send_response(&to_ide_tx, message.id, Err(msg)).await;
 
@@ -275,6 +333,16 @@ pub async fn vscode_ide_websocket(
                 }
             }
 
+            // Tell the Client which optional features are enabled. This uses
+            // the same ID as `ClientHtml`, which was sent to the IDE instead.
+            queue_send!(to_client_tx.send(EditorMessage {
+                id: INITIAL_MESSAGE_ID,
+                message: EditorMessageContents::Capabilities(capabilities())
+            }), 'task);
+            // When the Client runs in VSCode's webview, load allowed external
+            // content through the proxy; see `proxy.rs`.
+            let use_proxy = matches!(ide_type, IdeType::VSCode(true));
+
             // Create a queue for HTTP requests fo communicate with this task.
             let (from_http_tx, mut from_http_rx) = mpsc::channel(10);
             app_state_task
@@ -285,17 +353,78 @@ pub async fn vscode_ide_websocket(
 
             // All further messages are handled in the main loop.
             let mut id: f64 = INITIAL_MESSAGE_ID + MESSAGE_ID_INCREMENT;
+            let mut translation_queue = TranslationQueue::new();
+            let (translation_done_tx, mut translation_done_rx) = mpsc::channel(10);
+            // IDE messages whose processing waits for translations to finish;
+            // see `next_ide_message`.
+            let mut deferred_ide_messages = VecDeque::new();
+            // The log records the IDE subscribed to, and the IDs of `Log`
+            // messages it hasn't yet acknowledged.
+            let mut log_subscription: Option<LogSubscription> = None;
+            let mut log_message_ids = HashSet::new();
+            // The `Event` messages sent to the IDE; see `events.rs`.
+            let mut events = EventPublisher::new();
             loop {
                 select! {
                     // Look for messages from the IDE.
-                    Some(ide_message) = from_ide_rx.recv() => {
+                    Some(ide_message) = next_ide_message(&mut deferred_ide_messages, &mut from_ide_rx, translation_queue.is_empty()) => {
+                        // Handle the IDE's acknowledgement of a `Log` message
+                        // before logging anything, since logging would
+                        // produce yet another `Log` message. If the IDE didn't
+                        // acknowledge it, stop forwarding records.
+                        if let EditorMessageContents::Result(ref result) = ide_message.message {
+                            if log_message_ids.remove(&ide_message.id.to_bits()) {
+                                if result.is_err() {
+                                    log_subscription = None;
+                                    log_message_ids.clear();
+                                }
+                                continue;
+                            }
+                            // Likewise, handle acknowledgements of `Event`
+                            // messages here.
+                            if events.acknowledged(ide_message.id, result) {
+                                continue;
+                            }
+                        }
                         let msg = format!("{:?}", ide_message.message);
                         debug!("Received IDE message id = {}, message = {}", ide_message.id, &msg[..min(MAX_MESSAGE_LENGTH, msg.len())]);
+                        // Messages from the IDE must reach the Client in the
+                        // order the IDE sent them. Since an `Update` reaches
+                        // the Client only after its translation finishes,
+                        // defer all other messages until then, including any
+                        // `Update` for another file. Only an `Update` with
+                        // new contents for the file being translated may wait
+                        // in the translation queue, since translations of
+                        // one file finish in order. Once a message is
+                        // deferred, defer everything after it as well.
+                        let is_deferred = match &ide_message.message {
+                            EditorMessageContents::Update(update) => update.contents.is_none() || !translation_queue.is_translating(&update.file_path),
+                            _ => true,
+                        };
+                        if !translation_queue.is_empty() && (!deferred_ide_messages.is_empty() || is_deferred) {
+                            debug!("Deferring it until translation finishes.");
+                            deferred_ide_messages.push_back(ide_message);
+                            continue;
+                        }
                         match ide_message.message {
                             // Handle messages that the IDE must not send.
                             EditorMessageContents::Opened(_) |
                             EditorMessageContents::LoadFile(_) |
-                            EditorMessageContents::ClientHtml(_) => {
+                            EditorMessageContents::ClientHtml(_) |
+                            EditorMessageContents::UpdateDiff(_) |
+                            EditorMessageContents::Diagnostics(_) |
+                            EditorMessageContents::Capabilities(_) |
+                            EditorMessageContents::Log(_) |
+                            EditorMessageContents::Event(_) |
+                            EditorMessageContents::ListOpenFiles |
+                            EditorMessageContents::SwitchFile(_) |
+                            EditorMessageContents::OpenView(_) |
+                            EditorMessageContents::CloseView(_) |
+                            EditorMessageContents::GotoDefinition(_) |
+                            EditorMessageContents::OpenUrl(_) |
+                            EditorMessageContents::Save(_) |
+                            EditorMessageContents::SaveAll |
+                            EditorMessageContents::Export(_) => {
                                 let msg = "IDE must not send this message.";
                                 error!("{msg}");
                                 send_response(&to_ide_tx, ide_message.id, Err(msg.to_string())).await;
@@ -303,7 +432,8 @@ pub async fn vscode_ide_websocket(
 
                             // Handle messages that are simply passed through.
                             EditorMessageContents::Closed |
-                            EditorMessageContents::RequestClose => {
+                            EditorMessageContents::RequestClose |
+                            EditorMessageContents::SavedState(_) => {
                                 debug!("Forwarding it to the Client.");
                                 queue_send!(to_client_tx.send(ide_message))
                             },
@@ -316,7 +446,7 @@ pub async fn vscode_ide_websocket(
                                     // `LoadFile` result.
                                     Err(_) => load_file_requests.contains_key(&ide_message.id.to_bits()),
                                     Ok(result_ok) => match result_ok {
-                                        ResultOkTypes::Void => false,
+                                        ResultOkTypes::Void | ResultOkTypes::OpenFiles(_) | ResultOkTypes::Outline(_) | ResultOkTypes::Snippets(_) | ResultOkTypes::Snippet(_) | ResultOkTypes::Preferences(_) | ResultOkTypes::Exported(_) => false,
                                         ResultOkTypes::LoadFile(_) => true,
                                     }
                                 };
@@ -330,11 +460,35 @@ pub async fn vscode_ide_websocket(
                                 }
                                 // Ensure there's an HTTP request for this
                                 // `LoadFile` result.
-                                let Some(http_request) = load_file_requests.remove(&ide_message.id.to_bits()) else {
-                                    error!("Error: no HTTP request found for LoadFile result ID {}.", ide_message.id);
-                                    break 'task;
+                                let Some(load_file_request) = load_file_requests.remove(&ide_message.id.to_bits()) else {
+                                    // This is a late reply to a request which
+                                    // timed out, then was resent.
+                                    warn!("No HTTP request found for LoadFile result ID {}.", ide_message.id);
+                                    continue;
                                 };
 
+                                // If the IDE didn't reply, resend the request,
+                                // waiting twice as long before each resend.
+                                let is_timeout = matches!(result, Err(err) if err.starts_with(TIMEOUT_PREFIX));
+                                if is_timeout && load_file_request.retries < LOAD_FILE_RETRIES.load(Ordering::Relaxed) {
+                                    let delay = LOAD_FILE_RETRY_DELAY * 2u32.pow(load_file_request.retries);
+                                    warn!("No reply to LoadFile for {:?}; resending in {delay:?}.", load_file_request.http_request.file_path);
+                                    let load_file_retry_tx = load_file_retry_tx.clone();
+                                    actix_rt::spawn(async move {
+                                        sleep(delay).await;
+                                        // This fails only if the processing
+                                        // task already exited.
+                                        if load_file_retry_tx.send(LoadFileRequest {
+                                            http_request: load_file_request.http_request,
+                                            retries: load_file_request.retries + 1,
+                                        }).await.is_err() {
+                                            warn!("Unable to resend LoadFile; the processing task exited.");
+                                        }
+                                    });
+                                    continue;
+                                }
+                                let http_request = load_file_request.http_request;
+
                                 // Get the file contents from a `LoadFile`
                                 // result; otherwise, this is None.
                                 let file_contents_option = match result {
@@ -343,81 +497,286 @@ pub async fn vscode_ide_websocket(
                                         &None
                                     },
                                     Ok(result_ok) => match result_ok {
-                                        ResultOkTypes::Void => panic!("LoadFile result should not be void."),
+                                        ResultOkTypes::Void | ResultOkTypes::OpenFiles(_) | ResultOkTypes::Outline(_) | ResultOkTypes::Snippets(_) | ResultOkTypes::Snippet(_) | ResultOkTypes::Preferences(_) | ResultOkTypes::Exported(_) => panic!("LoadFile result should not be void."),
                                         ResultOkTypes::LoadFile(file_contents) => file_contents,
                                     }
                                 };
 
-                                // Process the file contents.
-                                let (simple_http_response, option_update) = match file_contents_option {
-                                    Some(file_contents) =>
-                                        text_file_to_response(&http_request, &current_file, &http_request.file_path, file_contents).await,
+                                // Process the file contents. A file shown in a
+                                // view is edited like the current file.
+                                let editing_file = if views.contains(&http_request.file_path) {
+                                    &http_request.file_path
+                                } else {
+                                    &current_file
+                                };
+                                let (simple_http_response, mut messages) = match file_contents_option {
+                                    Some(file_contents) => {
+                                        let response = text_file_to_response(&http_request, editing_file, &http_request.file_path, file_contents).await;
+                                        // Find the outline of a file being
+                                        // edited.
+                                        if !response.1.is_empty() {
+                                            outlines.insert(http_request.file_path.to_string_lossy().to_string(), outline_entries(file_contents, editing_file));
+                                        }
+                                        response
+                                    }
                                     None => {
                                         // The file wasn't available in the IDE.
-                                        // Look for it in the filesystem.
+                                        // Look for it in the filesystem. If the
+                                        // IDE reported that it's not open, then
+                                        // also save changes to it on disk.
+                                        if result.is_ok() {
+                                            disk_files.insert(http_request.file_path.clone());
+                                        }
                                         debug!("Sending HTTP response.");
-                                        make_simple_http_response(&http_request, &current_file).await
+                                        let response = make_simple_http_response(&http_request, editing_file).await;
+                                        if !response.1.is_empty() {
+                                            if let Ok(file_contents) = fs::read_to_string(&http_request.file_path).await {
+                                                outlines.insert(http_request.file_path.to_string_lossy().to_string(), outline_entries(&file_contents, editing_file));
+                                            }
+                                        }
+                                        response
                                     }
                                 };
-                                if let Some(update) = option_update {
+                                // Tell the user when the IDE never replied, since
+                                // the file on disk may lack unsaved edits.
+                                if is_timeout {
+                                    add_diagnostic(&mut messages, Diagnostic::on_line(
+                                        Severity::Warning,
+                                        1,
+                                        "The IDE didn't provide this file; showing its contents on disk instead, which may not include unsaved changes.".to_string(),
+                                        "ide",
+                                    ));
+                                }
+                                for message in messages {
+                                    if let EditorMessageContents::Update(UpdateMessageContents { file_path, contents: Some(codechat_for_web), .. }) = &message {
+                                        open_files.set_contents(file_path, codechat_for_web, Some(id));
+                                    }
                                     // Send the update to the client.
                                     debug!("Sending Update to Client, id = {id}.");
-                                    queue_send!(to_client_tx.send(EditorMessage { id, message: update }));
+                                    queue_send!(to_client_tx.send(EditorMessage { id, message }));
                                     id += MESSAGE_ID_INCREMENT;
                                 }
                                 oneshot_send!(http_request.response_queue.send(simple_http_response));
                             }
 
-                            // Handle the `Update` message.
+                            // Handle the `Update` message. Translate it in the
+                            // background, allowing only one translation per
+                            // file at a time; a newer update replaces one
+                            // waiting for translation.
                             EditorMessageContents::Update(update) => {
-                                if let Some(contents) = &update.contents {
-                                // Translate the file.
-                                let (translation_results_string, _path_to_toc) =
-                                source_to_codechat_for_web_string(&contents.source.doc, &current_file, false);
-                                if let TranslationResultsString::CodeChat(cc) = translation_results_string {
-                                    // Send the new contents
-                                    debug!("Sending translated contents to Client.");
-                                    queue_send!(to_client_tx.send(EditorMessage {
-                                            id: ide_message.id,
-                                            message: EditorMessageContents::Update(UpdateMessageContents {
-                                                file_path: update.file_path,
-                                                contents: Some(cc),
-                                                cursor_position: None,
-                                                scroll_position: None,
-                                            }),
-                                        }));
-                                    } else {
-                                        error!("Error translating source to CodeChat.");
+                                // The IDE has this file open.
+                                disk_files.remove(Path::new(&update.file_path));
+                                // Translate the scroll position using these
+                                // contents or, without them, the most recent
+                                // contents.
+                                let code_mirror = update.contents.as_ref().or(open_files.contents(&update.file_path)).map(|cfw| &cfw.source);
+                                if let (Some(scroll_position), Some(code_mirror)) = (update.scroll_position, code_mirror) {
+                                    app_state_task.classroom.set_scroll_position(&connection_id_task, Path::new(&update.file_path), scroll_position.to_normalized(code_mirror));
+                                }
+                                if update.contents.is_none() {
+                                    // Send just a scroll position to the Client
+                                    // in its units.
+                                    match (update.scroll_position, code_mirror) {
+                                        (Some(scroll_position), Some(code_mirror)) => {
+                                            let scroll_position = Some(scroll_position.to_block(code_mirror));
+                                            queue_send!(to_client_tx.send(EditorMessage {
+                                                id: ide_message.id,
+                                                message: EditorMessageContents::Update(UpdateMessageContents {
+                                                    scroll_position,
+                                                    ..update
+                                                }),
+                                            }));
+                                        }
+                                        _ => send_response(&to_ide_tx, ide_message.id, Ok(ResultOkTypes::Void)).await,
+                                    }
+                                    continue;
+                                }
+                                let file_path = update.file_path.clone();
+                                // Translate a file shown in a view using its own
+                                // path; otherwise, use the current file.
+                                let view_path = PathBuf::from(&file_path);
+                                let translation_path = if views.contains(&view_path) { view_path } else { current_file.clone() };
+                                match translation_queue.request(&file_path, PendingTranslation { id: ide_message.id, update, file_path: translation_path }) {
+                                    Queued::Start(pending) => start_translation(pending, translation_done_tx.clone()),
+                                    Queued::Waiting { superseded } => if let Some(superseded) = superseded {
+                                        // The Client will never see this update,
+                                        // so acknowledge it here.
+                                        debug!("Dropping superseded update, id = {}.", superseded.id);
+                                        send_response(&to_ide_tx, superseded.id, Ok(ResultOkTypes::Void)).await;
                                     }
                                 }
+                                events.translating(&to_ide_tx, &mut id, translation_queue.len()).await;
                             }
 
                             // Update the current file; translate it to a URL
                             // then pass it to the Client.
-                            EditorMessageContents::CurrentFile(file_path) => {
+                            EditorMessageContents::CurrentFile(current_file_contents) => {
                                 debug!("Translating and forwarding it to the Client.");
+                                let file_path = current_file_contents.file_path;
+                                // The IDE has this file open.
+                                disk_files.remove(Path::new(&file_path));
+                                open_files.open(&file_path);
                                 queue_send!(to_client_tx.send(EditorMessage {
                                     id: ide_message.id,
-                                    message: EditorMessageContents::CurrentFile(
-                                        path_to_url("/vsc/fs", &connection_id_task, Path::new(&file_path))
-                                    )
+                                    message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                        file_path: path_to_url(&with_base_path("/vsc/fs"), &connection_id_task, Path::new(&file_path)),
+                                        view_target: current_file_contents.view_target,
+                                    })
                                 }));
+                                app_state_task.classroom.set_current_file(&connection_id_task, Path::new(&file_path));
                                 current_file = file_path.into();
                             }
+
+                            // Record the folded regions, then pass them to the
+                            // Client.
+                            EditorMessageContents::FoldRanges(fold_ranges_contents) => {
+                                open_files.set_fold_ranges(&fold_ranges_contents.file_path, fold_ranges_contents.ranges.clone());
+                                queue_send!(to_client_tx.send(EditorMessage {
+                                    id: ide_message.id,
+                                    message: EditorMessageContents::FoldRanges(fold_ranges_contents)
+                                }));
+                            }
+
+                            EditorMessageContents::Outline(file_path) => {
+                                let entries = outlines.get(&file_path).cloned().unwrap_or_default();
+                                send_response(&to_ide_tx, ide_message.id, Ok(ResultOkTypes::Outline(entries))).await;
+                            }
+
+                            EditorMessageContents::ListSnippets(file_path) => {
+                                send_response(&to_ide_tx, ide_message.id, list_snippets(&file_path)).await;
+                            }
+
+                            EditorMessageContents::InsertSnippet(insert_snippet_contents) => {
+                                send_response(&to_ide_tx, ide_message.id, insert_snippet(&insert_snippet_contents)).await;
+                            }
+
+                            EditorMessageContents::Preferences(update) => {
+                                send_response(&to_ide_tx, ide_message.id, preferences_message(update)).await;
+                            }
+
+                            EditorMessageContents::SubscribeLogs(level) => {
+                                log_subscription = (level != LevelFilter::Off).then(|| LogSubscription::new(level));
+                                send_response(&to_ide_tx, ide_message.id, Ok(ResultOkTypes::Void)).await;
+                            }
+
+                            EditorMessageContents::WorkspaceRoots(roots) => {
+                                set_workspace_roots(&connection_id_task, roots);
+                                send_response(&to_ide_tx, ide_message.id, Ok(ResultOkTypes::Void)).await;
+                            }
+
+                            EditorMessageContents::SubscribeEvents(subscribed) => {
+                                send_response(&to_ide_tx, ide_message.id, Ok(ResultOkTypes::Void)).await;
+                                events.subscribe(&to_ide_tx, &mut id, subscribed).await;
+                            }
+
+                            EditorMessageContents::SetFileLanguage(set_file_language_contents) => {
+                                let file_path = PathBuf::from(&set_file_language_contents.file_path);
+                                let result = set_file_language(&file_path, set_file_language_contents.language).map(|_| ResultOkTypes::Void);
+                                let is_ok = result.is_ok();
+                                send_response(&to_ide_tx, ide_message.id, result).await;
+                                // Reload the current file using its new lexer.
+                                if is_ok && file_path == current_file {
+                                    queue_send!(to_client_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                            file_path: path_to_url(&with_base_path("/vsc/fs"), &connection_id_task, &file_path),
+                                            view_target: ViewTarget::Active,
+                                        })
+                                    }));
+                                    id += MESSAGE_ID_INCREMENT;
+                                }
+                            }
                         }
                     },
 
-                    // Handle HTTP requests.
-                    Some(http_request) = from_http_rx.recv() => {
-                        debug!("Received HTTP request for {:?} and sending LoadFile to IDE, id = {id}.", http_request.file_path);
+                    // Handle a finished translation.
+                    Some(translation_done) = translation_done_rx.recv() => {
+                        let TranslationDone { id: update_id, file_path, translation_results_string, diagnostics, outline, scroll_position } = translation_done;
+                        // If the file changed during this translation, drop
+                        // this stale result and translate the newest update
+                        // instead.
+                        if let Some(next) = translation_queue.finished(&file_path) {
+                            debug!("Dropping stale translation, id = {update_id}.");
+                            send_response(&to_ide_tx, update_id, Ok(ResultOkTypes::Void)).await;
+                            start_translation(next, translation_done_tx.clone());
+                            continue;
+                        }
+                        events.translating(&to_ide_tx, &mut id, translation_queue.len()).await;
+                        if let TranslationResultsString::CodeChat(mut cc) = translation_results_string {
+                            // Send the new contents or, if smaller, the
+                            // changes to the contents the Client has.
+                            debug!("Sending translated contents to Client.");
+                            let sent = open_files.sent_contents(&file_path).map(|(sent, base_id)| (sent.clone(), base_id));
+                            open_files.set_contents(&file_path, &cc, Some(update_id));
+                            let scroll_position = scroll_position.map(|scroll_position| scroll_position.to_block(&cc.source));
+                            if use_proxy {
+                                proxy_doc_blocks(&mut cc);
+                            }
+                            let diff = sent.and_then(|(mut sent, base_id)| {
+                                // The Client has the proxied contents.
+                                if use_proxy {
+                                    proxy_doc_blocks(&mut sent);
+                                }
+                                contents_diff(&sent, &cc).map(|diff| (diff, base_id))
+                            });
+                            outlines.insert(file_path.clone(), outline);
+                            let fold_ranges = fold_ranges_message(&open_files, &file_path);
+                            let problems = match &diagnostics {
+                                Some(EditorMessageContents::Diagnostics(problems)) => problems.as_slice(),
+                                _ => &[],
+                            };
+                            events.diagnostics(&to_ide_tx, &mut id, &file_path, problems).await;
+                            let message = match diff {
+                                Some((diff, base_id)) => {
+                                    pending_diffs.insert(update_id.to_bits(), file_path.clone());
+                                    EditorMessageContents::UpdateDiff(UpdateDiffContents {
+                                        file_path,
+                                        base_id,
+                                        diff,
+                                        scroll_position,
+                                    })
+                                }
+                                None => EditorMessageContents::Update(UpdateMessageContents {
+                                    file_path,
+                                    contents: Some(cc),
+                                    cursor_position: None,
+                                    scroll_position,
+                                }),
+                            };
+                            queue_send!(to_client_tx.send(EditorMessage { id: update_id, message }));
+                            // Report any problems in its doc blocks, then
+                            // restore its folded regions.
+                            for message in diagnostics.into_iter().chain(fold_ranges) {
+                                queue_send!(to_client_tx.send(EditorMessage { id, message }));
+                                id += MESSAGE_ID_INCREMENT;
+                            }
+                        } else {
+                            error!("Error translating source to CodeChat.");
+                        }
+                    }
+
+                    // Handle HTTP requests, along with resent requests which
+                    // the IDE didn't answer.
+                    Some(load_file_request) = next_load_file_request(&mut from_http_rx, &mut load_file_retry_rx) => {
+                        debug!("Received HTTP request for {:?} and sending LoadFile to IDE, id = {id}.", load_file_request.http_request.file_path);
                         // Convert the request into a `LoadFile` message.
                         queue_send!(to_ide_tx.send(EditorMessage {
                             id,
-                            message: EditorMessageContents::LoadFile(http_request.file_path.clone())
+                            message: EditorMessageContents::LoadFile(load_file_request.http_request.file_path.clone())
                         }));
                         // Store the ID and request, which are needed to send a
                         // response when the `LoadFile` result is received.
-                        load_file_requests.insert(id.to_bits(), http_request);
+                        load_file_requests.insert(id.to_bits(), load_file_request);
+                        id += MESSAGE_ID_INCREMENT;
+                    }
+
+                    // Forward a log record to the IDE.
+                    record = next_log_record(&mut log_subscription) => {
+                        log_message_ids.insert(id.to_bits());
+                        queue_send!(to_ide_tx.send(EditorMessage {
+                            id,
+                            message: EditorMessageContents::Log(record)
+                        }));
                         id += MESSAGE_ID_INCREMENT;
                     }
 
@@ -425,12 +784,24 @@ pub async fn vscode_ide_websocket(
                     Some(client_message) = from_client_rx.recv() => {
                         let msg = format!("{:?}", client_message.message);
                         debug!("Received Client message id = {}, message = {}", client_message.id, &msg[..min(MAX_MESSAGE_LENGTH, msg.len())]);
+                        // The Client sends a `Closed` message when it
+                        // disconnects; any other message shows it's connected.
+                        events.client_connected(&to_ide_tx, &mut id, !matches!(client_message.message, EditorMessageContents::Closed)).await;
                         match client_message.message {
                             // Handle messages that the client must not send.
                             EditorMessageContents::Opened(_) |
                             EditorMessageContents::LoadFile(_) |
                             EditorMessageContents::RequestClose |
-                            EditorMessageContents::ClientHtml(_) => {
+                            EditorMessageContents::ClientHtml(_) |
+                            EditorMessageContents::UpdateDiff(_) |
+                            EditorMessageContents::Diagnostics(_) |
+                            EditorMessageContents::Capabilities(_) |
+                            EditorMessageContents::SubscribeLogs(_) |
+                            EditorMessageContents::WorkspaceRoots(_) |
+                            EditorMessageContents::SubscribeEvents(_) |
+                            EditorMessageContents::Log(_) |
+                            EditorMessageContents::Event(_) |
+                            EditorMessageContents::SavedState(_) => {
                                 let msg = "Client must not send this message.";
                                 error!("{msg}");
                                 send_response(&to_client_tx, client_message.id, Err(msg.to_string())).await;
@@ -438,26 +809,80 @@ pub async fn vscode_ide_websocket(
 
                             // Handle messages that are simply passed through.
                             EditorMessageContents::Closed |
-                            EditorMessageContents::Result(_) => {
+                            EditorMessageContents::SaveAll => {
                                 debug!("Forwarding it to the IDE.");
                                 queue_send!(to_ide_tx.send(client_message))
                             },
 
+                            // If the Client no longer has the contents an
+                            // `UpdateDiff` changed, send it the new contents,
+                            // unless it sent newer contents itself. Pass other
+                            // results to the IDE.
+                            EditorMessageContents::Result(result) => {
+                                if let (Some(file_path), Err(err)) = (pending_diffs.remove(&client_message.id.to_bits()), &result) {
+                                    debug!("Sending the contents of {file_path} instead of changes: {err}");
+                                    if let Some((contents, _)) = open_files.sent_contents(&file_path) {
+                                        let contents = contents.clone();
+                                        open_files.set_contents(&file_path, &contents, Some(id));
+                                        queue_send!(to_client_tx.send(EditorMessage {
+                                            id,
+                                            message: EditorMessageContents::Update(UpdateMessageContents {
+                                                file_path,
+                                                contents: Some(contents),
+                                                cursor_position: None,
+                                                scroll_position: None,
+                                            }),
+                                        }));
+                                        id += MESSAGE_ID_INCREMENT;
+                                    }
+                                    send_response(&to_ide_tx, client_message.id, Ok(ResultOkTypes::Void)).await;
+                                } else {
+                                    queue_send!(to_ide_tx.send(EditorMessage {
+                                        id: client_message.id,
+                                        message: EditorMessageContents::Result(result),
+                                    }));
+                                }
+                            },
+
                             // Handle the `Update` message.
-                            EditorMessageContents::Update(update_message_contents) => {
+                            EditorMessageContents::Update(mut update_message_contents) => {
                                 debug!("Forwarding translation of it to the IDE.");
+                                if let (true, Some(cfw)) = (use_proxy, &mut update_message_contents.contents) {
+                                    unproxy_doc_blocks(cfw);
+                                }
+                                if let Some(cfw) = &update_message_contents.contents {
+                                    open_files.set_contents(&update_message_contents.file_path, cfw, None);
+                                }
+                                // Translate the scroll position to the IDE's
+                                // units using these contents or, without them,
+                                // the most recent contents.
+                                let code_mirror = update_message_contents.contents.as_ref().or(open_files.contents(&update_message_contents.file_path)).map(|cfw| &cfw.source);
+                                let scroll_position = match (update_message_contents.scroll_position, code_mirror) {
+                                    (Some(scroll_position), Some(code_mirror)) => {
+                                        app_state_task.classroom.set_scroll_position(&connection_id_task, Path::new(&update_message_contents.file_path), scroll_position.to_normalized(code_mirror));
+                                        Some(ScrollPosition::Line { line: scroll_position.to_line(code_mirror) })
+                                    }
+                                    (scroll_position, _) => scroll_position,
+                                };
                                 let codechat_for_web = match update_message_contents.contents {
                                     None => None,
                                     Some(cfw) => match codechat_for_web_to_source(
                                         &cfw)
                                     {
-                                        Ok(result) => Some(CodeChatForWeb {
-                                            metadata: cfw.metadata,
-                                            source: CodeMirror {
-                                                doc: result,
-                                                doc_blocks: vec![],
-                                            },
-                                        }),
+                                        Ok(result) => {
+                                            let file_path = Path::new(&update_message_contents.file_path);
+                                            outlines.insert(update_message_contents.file_path.clone(), outline_entries(&result, file_path));
+                                            if let Err(err) = record_anchors(file_path, &result) {
+                                                warn!("Unable to record anchors for {file_path:?}: {err}");
+                                            }
+                                            Some(CodeChatForWeb {
+                                                metadata: cfw.metadata,
+                                                source: CodeMirror {
+                                                    doc: result,
+                                                    doc_blocks: vec![],
+                                                },
+                                            })
+                                        }
                                         Err(message) => {
                                             let msg = format!(
                                                 "Unable to translate to source: {message}"
@@ -468,31 +893,63 @@ pub async fn vscode_ide_websocket(
                                         }
                                     },
                                 };
+                                // Save changes to a file which isn't open in the
+                                // IDE directly to disk.
+                                if disk_files.contains(Path::new(&update_message_contents.file_path)) {
+                                    let result = match codechat_for_web {
+                                        None => Ok(ResultOkTypes::Void),
+                                        Some(cfw) => fs::write(&update_message_contents.file_path, cfw.source.doc).await
+                                            .map(|_| ResultOkTypes::Void)
+                                            .map_err(|err| format!("Unable to save {}: {err}", update_message_contents.file_path)),
+                                    };
+                                    if let Err(msg) = &result {
+                                        error!("{msg}");
+                                    }
+                                    send_response(&to_client_tx, client_message.id, result).await;
+                                    continue;
+                                }
                                 queue_send!(to_ide_tx.send(EditorMessage {
                                     id: client_message.id,
                                     message: EditorMessageContents::Update(UpdateMessageContents {
                                         file_path: update_message_contents.file_path,
                                         contents: codechat_for_web,
                                         cursor_position: update_message_contents.cursor_position,
-                                        scroll_position: update_message_contents.scroll_position,
+                                        scroll_position,
                                     })
                                 }));
                             },
 
                             // Update the current file; translate it to a URL
                             // then pass it to the IDE.
-                            EditorMessageContents::CurrentFile(url_string) => {
+                            EditorMessageContents::CurrentFile(current_file_contents) => {
                                 debug!("Forwarding translated path to IDE.");
-                                let result = match url_to_path(&url_string, VSCODE_PATH_PREFIX) {
+                                let result = match url_to_path(&current_file_contents.file_path, VSCODE_PATH_PREFIX) {
                                     Err(err) => Err(format!("Unable to convert URL to path: {err}")),
                                     Ok(file_path) => {
-                                        match file_path.to_str() {
-                                            None => Err("Unable to convert path to string.".to_string()),
-                                            Some(file_path_string) => {
+                                        match (file_path.to_str(), outside_project(&current_file, &file_path)) {
+                                            (None, _) => Err("Unable to convert path to string.".to_string()),
+                                            // A file outside the current project
+                                            // doesn't become the current file;
+                                            // see `outside_project.rs`.
+                                            (Some(_), Some(policy)) => {
+                                                let result = if policy == OutsideProjectPolicy::Refuse {
+                                                    Err(refused_message(&file_path))
+                                                } else {
+                                                    Ok(ResultOkTypes::Void)
+                                                };
+                                                send_response(&to_client_tx, client_message.id, result).await;
+                                                Ok(())
+                                            }
+                                            (Some(file_path_string), None) => {
                                                 queue_send!(to_ide_tx.send(EditorMessage {
                                                     id: client_message.id,
-                                                    message: EditorMessageContents::CurrentFile(file_path_string.to_string())
+                                                    message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                                        file_path: file_path_string.to_string(),
+                                                        view_target: current_file_contents.view_target,
+                                                    })
                                                 }));
+                                                open_files.open(file_path_string);
+                                                app_state_task.classroom.set_current_file(&connection_id_task, &file_path);
                                                 current_file = file_path;
                                                 Ok(())
                                             }
@@ -504,6 +961,172 @@ pub async fn vscode_ide_websocket(
                                     send_response(&to_client_tx, client_message.id, Err(msg)).await;
                                 }
                             }
+
+                            // Show or stop showing a file in an additional view.
+                            // The IDE isn't involved, since its current file
+                            // doesn't change.
+                            EditorMessageContents::OpenView(ref url_string) |
+                            EditorMessageContents::CloseView(ref url_string) => {
+                                let result = match url_to_path(url_string, VSCODE_PATH_PREFIX) {
+                                    Err(err) => Err(format!("Unable to convert URL to path: {err}")),
+                                    Ok(file_path) => {
+                                        if let EditorMessageContents::OpenView(_) = client_message.message {
+                                            views.insert(file_path);
+                                        } else {
+                                            views.remove(&file_path);
+                                        }
+                                        Ok(ResultOkTypes::Void)
+                                    }
+                                };
+                                if let Err(msg) = &result {
+                                    error!("{msg}");
+                                }
+                                send_response(&to_client_tx, client_message.id, result).await;
+                            }
+
+                            EditorMessageContents::ListOpenFiles => {
+                                send_response(&to_client_tx, client_message.id, Ok(ResultOkTypes::OpenFiles(open_files.paths()))).await;
+                            }
+
+                            // Record the folded regions, then pass them to the
+                            // IDE.
+                            EditorMessageContents::FoldRanges(fold_ranges_contents) => {
+                                open_files.set_fold_ranges(&fold_ranges_contents.file_path, fold_ranges_contents.ranges.clone());
+                                queue_send!(to_ide_tx.send(EditorMessage {
+                                    id: client_message.id,
+                                    message: EditorMessageContents::FoldRanges(fold_ranges_contents)
+                                }));
+                            }
+
+                            EditorMessageContents::Outline(file_path) => {
+                                let entries = outlines.get(&file_path).cloned().unwrap_or_default();
+                                send_response(&to_client_tx, client_message.id, Ok(ResultOkTypes::Outline(entries))).await;
+                            }
+
+                            EditorMessageContents::ListSnippets(file_path) => {
+                                send_response(&to_client_tx, client_message.id, list_snippets(&file_path)).await;
+                            }
+
+                            EditorMessageContents::InsertSnippet(insert_snippet_contents) => {
+                                send_response(&to_client_tx, client_message.id, insert_snippet(&insert_snippet_contents)).await;
+                            }
+
+                            EditorMessageContents::Preferences(update) => {
+                                send_response(&to_client_tx, client_message.id, preferences_message(update)).await;
+                            }
+
+                            // Look for the definition in the project's `tags`
+                            // file, then ask the IDE to show it. The IDE's
+                            // `Result` for this message answers the Client.
+                            EditorMessageContents::GotoDefinition(goto_definition_contents) => {
+                                let definition = find_definition(&goto_definition_contents.identifier, Path::new(&goto_definition_contents.file_path))
+                                    .unwrap_or_else(|err| {
+                                        warn!("{err}");
+                                        None
+                                    });
+                                queue_send!(to_ide_tx.send(EditorMessage {
+                                    id: client_message.id,
+                                    message: EditorMessageContents::GotoDefinition(GotoDefinitionContents {
+                                        definition,
+                                        ..goto_definition_contents
+                                    })
+                                }));
+                            }
+
+                            // Ask the IDE to open the URL; the IDE's `Result`
+                            // for this message answers the Client.
+                            EditorMessageContents::OpenUrl(url) => {
+                                match check_external_url(&url) {
+                                    Ok(_) => queue_send!(to_ide_tx.send(EditorMessage {
+                                        id: client_message.id,
+                                        message: EditorMessageContents::OpenUrl(url)
+                                    })),
+                                    Err(msg) => {
+                                        warn!("{msg}");
+                                        send_response(&to_client_tx, client_message.id, Err(msg)).await;
+                                    }
+                                }
+                            }
+
+                            // Ask the IDE to save the file, naming the current
+                            // file if the Client didn't; the IDE's `Result`
+                            // answers the Client. Changes to a file which isn't
+                            // open in the IDE were already saved to disk.
+                            EditorMessageContents::Save(file_path) => {
+                                let file_path = file_path.unwrap_or_else(|| current_file.to_string_lossy().to_string());
+                                if disk_files.contains(Path::new(&file_path)) {
+                                    send_response(&to_client_tx, client_message.id, Ok(ResultOkTypes::Void)).await;
+                                } else {
+                                    queue_send!(to_ide_tx.send(EditorMessage {
+                                        id: client_message.id,
+                                        message: EditorMessageContents::Save(Some(file_path))
+                                    }));
+                                }
+                            }
+
+                            EditorMessageContents::Export(export_contents) => {
+                                export_message(export_contents, to_client_tx.clone(), client_message.id);
+                            }
+
+                            // Switch to an open file by sending its most recent
+                            // contents, rather than reloading the Client.
+                            EditorMessageContents::SwitchFile(file_path) => {
+                                let Some(mut codechat_for_web) = open_files.contents(&file_path).cloned() else {
+                                    let msg = format!("No contents available for {file_path}; use CurrentFile instead.");
+                                    warn!("{msg}");
+                                    send_response(&to_client_tx, client_message.id, Err(msg)).await;
+                                    continue;
+                                };
+                                debug!("Forwarding it to the IDE as a CurrentFile message.");
+                                open_files.set_contents(&file_path, &codechat_for_web, Some(id));
+                                if use_proxy {
+                                    proxy_doc_blocks(&mut codechat_for_web);
+                                }
+                                open_files.open(&file_path);
+                                current_file = PathBuf::from(&file_path);
+                                // The IDE's `Result` for this message answers the
+                                // Client's `SwitchFile` message.
+                                queue_send!(to_ide_tx.send(EditorMessage {
+                                    id: client_message.id,
+                                    message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                        file_path: file_path.clone(),
+                                        view_target: ViewTarget::Active,
+                                    })
+                                }));
+                                let fold_ranges = fold_ranges_message(&open_files, &file_path);
+                                queue_send!(to_client_tx.send(EditorMessage {
+                                    id,
+                                    message: EditorMessageContents::Update(UpdateMessageContents {
+                                        file_path,
+                                        contents: Some(codechat_for_web),
+                                        cursor_position: None,
+                                        scroll_position: None,
+                                    }),
+                                }));
+                                id += MESSAGE_ID_INCREMENT;
+                                if let Some(message) = fold_ranges {
+                                    queue_send!(to_client_tx.send(EditorMessage { id, message }));
+                                    id += MESSAGE_ID_INCREMENT;
+                                }
+                            }
+
+                            EditorMessageContents::SetFileLanguage(set_file_language_contents) => {
+                                let file_path = PathBuf::from(&set_file_language_contents.file_path);
+                                let result = set_file_language(&file_path, set_file_language_contents.language).map(|_| ResultOkTypes::Void);
+                                let is_ok = result.is_ok();
+                                send_response(&to_client_tx, client_message.id, result).await;
+                                // Reload the current file using its new lexer.
+                                if is_ok && file_path == current_file {
+                                    queue_send!(to_client_tx.send(EditorMessage {
+                                        id,
+                                        message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                                            file_path: path_to_url(&with_base_path("/vsc/fs"), &connection_id_task, &file_path),
+                                            view_target: ViewTarget::Active,
+                                        })
+                                    }));
+                                    id += MESSAGE_ID_INCREMENT;
+                                }
+                            }
                         }
                     },
 
@@ -512,6 +1135,7 @@ pub async fn vscode_ide_websocket(
             }
 
             debug!("VSCode processing task shutting down.");
+            set_workspace_roots(&connection_id_task, Vec::new());
             if app_state_task
                 .processing_task_queue_tx
                 .lock()
@@ -566,13 +1190,161 @@ pub async fn vscode_ide_websocket(
     .await
 }
 
+// A request for a file, sent to the IDE as a `LoadFile` message.
+struct LoadFileRequest {
+    http_request: ProcessingTaskHttpRequest,
+    // The number of times this request was resent.
+    retries: u32,
+}
+
+// Return the next HTTP request or resent request.
+async fn next_load_file_request(
+    from_http_rx: &mut mpsc::Receiver<ProcessingTaskHttpRequest>,
+    load_file_retry_rx: &mut mpsc::Receiver<LoadFileRequest>,
+) -> Option<LoadFileRequest> {
+    select! {
+        Some(http_request) = from_http_rx.recv() => Some(LoadFileRequest { http_request, retries: 0 }),
+        Some(load_file_request) = load_file_retry_rx.recv() => Some(load_file_request),
+        else => None,
+    }
+}
+
+// Add a diagnostic to the `Diagnostics` message in `messages`, adding this
+// message if needed. Do nothing if there are no messages, since the file isn't
+// being edited.
+fn add_diagnostic(messages: &mut Vec<EditorMessageContents>, diagnostic: Diagnostic) {
+    if messages.is_empty() {
+        return;
+    }
+    for message in messages.iter_mut() {
+        if let EditorMessageContents::Diagnostics(diagnostics) = message {
+            diagnostics.push(diagnostic);
+            return;
+        }
+    }
+    messages.push(EditorMessageContents::Diagnostics(vec![diagnostic]));
+}
+
+// Return the changes which transform `sent`, the contents the Client has, into
+// `contents`, if sending these is smaller than sending `contents`.
+fn contents_diff(sent: &CodeChatForWeb, contents: &CodeChatForWeb) -> Option<CodeMirrorDiff> {
+    if sent.metadata != contents.metadata {
+        return None;
+    }
+    let diff = diff_code_mirror(&sent.source, &contents.source, DiffGranularity::default());
+    let diff_length = serde_json::to_string(&diff).ok()?.len();
+    let contents_length = serde_json::to_string(contents).ok()?.len();
+    (diff_length < contents_length).then_some(diff)
+}
+
+// Return a `FoldRanges` message restoring the folded regions of the provided
+// file, or `None` if it has none.
+fn fold_ranges_message(open_files: &OpenFiles, file_path: &str) -> Option<EditorMessageContents> {
+    let ranges = open_files.fold_ranges(file_path);
+    (!ranges.is_empty()).then(|| {
+        EditorMessageContents::FoldRanges(FoldRangesContents {
+            file_path: file_path.to_string(),
+            ranges: ranges.to_vec(),
+        })
+    })
+}
+
+// Return the next IDE message to process: the oldest deferred message once no
+// translations are running, or else the next message from the IDE.
+async fn next_ide_message(
+    deferred_ide_messages: &mut VecDeque<EditorMessage>,
+    from_ide_rx: &mut mpsc::Receiver<EditorMessage>,
+    is_translation_done: bool,
+) -> Option<EditorMessage> {
+    if is_translation_done {
+        if let Some(ide_message) = deferred_ide_messages.pop_front() {
+            return Some(ide_message);
+        }
+    }
+    from_ide_rx.recv().await
+}
+
+// An IDE update waiting for or undergoing translation.
+struct PendingTranslation {
+    // The ID of the IDE's `Update` message.
+    id: f64,
+    update: UpdateMessageContents,
+    // The path of the file to translate.
+    file_path: PathBuf,
+}
+
+// The results of translating a `PendingTranslation`.
+struct TranslationDone {
+    // The ID of the IDE's `Update` message.
+    id: f64,
+    // The file path from the IDE's `Update` message.
+    file_path: String,
+    translation_results_string: TranslationResultsString,
+    diagnostics: Option<EditorMessageContents>,
+    outline: Vec<OutlineEntry>,
+    // The scroll position from the IDE's `Update` message.
+    scroll_position: Option<ScrollPosition>,
+}
+
+// Translate an update on a separate thread, since translation blocks; send the
+// results to `translation_done_tx`. This always sends results, even if the
+// translation panics, so that the translation queue never stalls.
+fn start_translation(
+    pending: PendingTranslation,
+    translation_done_tx: mpsc::Sender<TranslationDone>,
+) {
+    let id = pending.id;
+    let file_path = pending.update.file_path.clone();
+    let scroll_position = pending.update.scroll_position;
+    actix_rt::spawn(async move {
+        let results = actix_rt::task::spawn_blocking(move || {
+            // `Update` messages are only queued when they have contents.
+            let doc = &pending.update.contents.as_ref().unwrap().source.doc;
+            let (translation_results_string, _path_to_toc) = source_to_codechat_for_web_string(
+                doc,
+                &pending.file_path,
+                false,
+                RenderTarget::Editor,
+            );
+            (
+                translation_results_string,
+                diagnostics_message(doc, &pending.file_path),
+                outline_entries(doc, &pending.file_path),
+            )
+        })
+        .await;
+        let (translation_results_string, diagnostics, outline) = results.unwrap_or_else(|err| {
+            (
+                TranslationResultsString::Err(format!("Unable to translate: {err}")),
+                None,
+                vec![],
+            )
+        });
+        // This fails only if the processing task already exited.
+        if translation_done_tx
+            .send(TranslationDone {
+                id,
+                file_path,
+                translation_results_string,
+                diagnostics,
+                outline,
+                scroll_position,
+            })
+            .await
+            .is_err()
+        {
+            warn!("Unable to deliver a translation; the processing task exited.");
+        }
+    });
+}
+
 pub fn get_vscode_client_framework(connection_id: &str) -> String {
     // Send the HTML for the internal browser.
     match get_client_framework(false, "vsc/ws-client", connection_id) {
         Ok(web_page) => web_page,
         Err(html_string) => {
             error!("{html_string}");
-            html_wrapper(&escape_html(&html_string))
+            html_wrapper(&escape(&html_string, EscapeContext::Text))
         }
     }
 }
@@ -646,9 +1418,13 @@ mod test {
     };
     use crate::{
         cast,
-        processing::{CodeChatForWeb, CodeMirror, SourceFileMetadata},
+        processing::{outline::OutlineEntry, CodeChatForWeb, CodeMirror, SourceFileMetadata},
         test_utils::{_prep_test_dir, check_logger_errors, configure_testing_logger},
-        webserver::{ResultOkTypes, UpdateMessageContents},
+        webserver::{
+            scroll::ScrollPosition, CurrentFileContents, ExportContents, FoldRange,
+            FoldRangesContents, GotoDefinitionContents, InsertSnippetContents, ResultOkTypes,
+            SaveState, SavedStateContents, UpdateMessageContents, ViewTarget,
+        },
     };
 
     lazy_static! {
@@ -716,7 +1492,10 @@ mod test {
     // isn't opened at all.)
     //
     // Message ids at function end: IDE - 4, Server - 3, Client - 2.
-    async fn open_client<S: AsyncRead + AsyncWrite + Unpin>(ws_ide: &mut WebSocketStream<S>) {
+    async fn open_client<S: AsyncRead + AsyncWrite + Unpin>(
+        ws_ide: &mut WebSocketStream<S>,
+        ws_client: &mut WebSocketStream<S>,
+    ) {
         // 1.  Send the `Opened` message.
         //
         // Message ids: IDE - 1->4, Server - 0, Client - 2.
@@ -757,6 +1536,28 @@ mod test {
             },
         )
         .await;
+
+        // 3.  The Client then receives its capabilities. Respond, as the
+        //     Client does; otherwise, this times out in longer tests. The
+        //     Server forwards this response to the IDE.
+        let em = read_message(ws_client).await;
+        cast!(&em.message, EditorMessageContents::Capabilities);
+        assert_eq!(em.id, 0.0);
+        send_message(
+            ws_client,
+            &EditorMessage {
+                id: 0.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(ws_ide).await,
+            EditorMessage {
+                id: 0.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            }
+        );
     }
 
     // Perform all the setup for testing the Server via IDE and Client
@@ -877,8 +1678,8 @@ mod test {
     #[actix_web::test]
     async fn test_vscode_ide_websocket3() {
         let connection_id = "test-connection-id3";
-        let (temp_dir, test_dir, mut ws_ide, _) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
 
         // Do this is a thread, since the request generates a message that
         // requires a response in order to complete.
@@ -934,7 +1735,7 @@ mod test {
     async fn test_vscode_ide_websocket8() {
         let connection_id = "test-connection-id8";
         let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
 
         // Message ids: IDE - 4->7, Server - 3, Client - 2.
         let file_path = format!(
@@ -945,7 +1746,10 @@ mod test {
             &mut ws_ide,
             &EditorMessage {
                 id: 4.0,
-                message: EditorMessageContents::CurrentFile(file_path.clone()),
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                }),
             },
         )
         .await;
@@ -954,7 +1758,7 @@ mod test {
         let em = read_message(&mut ws_client).await;
         assert_eq!(em.id, 4.0);
         assert_ends_with!(
-            cast!(&em.message, EditorMessageContents::CurrentFile),
+            cast!(&em.message, EditorMessageContents::CurrentFile).file_path,
             "/only-in-ide.py"
         );
 
@@ -1028,6 +1832,7 @@ mod test {
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "\n".to_string(),
@@ -1074,7 +1879,7 @@ mod test {
     async fn test_vscode_ide_websocket7() {
         let connection_id = "test-connection-id7";
         let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
 
         // Set the current file, so a subsequent `Update` message can be
         // translated.
@@ -1085,17 +1890,22 @@ mod test {
             &mut ws_client,
             &EditorMessage {
                 id: 2.0,
-                message: EditorMessageContents::CurrentFile(format!(
-                    "http://localhost:8080/vsc/fs/{connection_id}/{}",
-                    &file_path
-                )),
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: format!(
+                        "http://localhost:8080/vsc/fs/{connection_id}/{}",
+                        &file_path
+                    ),
+                    view_target: ViewTarget::Beside,
+                }),
             },
         )
         .await;
         let em = read_message(&mut ws_ide).await;
         let cf = cast!(em.message, EditorMessageContents::CurrentFile);
+        // The IDE receives the Client's view target.
+        assert_eq!(cf.view_target, ViewTarget::Beside);
         assert_eq!(
-            path::absolute(Path::new(&cf)).unwrap(),
+            path::absolute(Path::new(&cf.file_path)).unwrap(),
             path::absolute(Path::new(&format!(
                 "{}/test.py",
                 test_dir.to_str().unwrap()
@@ -1132,6 +1942,7 @@ mod test {
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "# more".to_string(),
@@ -1153,6 +1964,7 @@ mod test {
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
                             doc: "\n".to_string(),
@@ -1191,64 +2003,1197 @@ mod test {
         temp_dir.close().unwrap();
     }
 
-    // Send an `Update` message from the Client.
+    // Send `CurrentFile`, `Update`, then `CurrentFile` messages from the IDE
+    // without waiting; the Client must receive them in the same order, even
+    // though the `Update` must first be translated.
     #[actix_web::test]
-    async fn test_vscode_ide_websocket6() {
-        let connection_id = "test-connection-id6";
-        let (temp_dir, _, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
+    async fn test_vscode_ide_websocket10() {
+        let connection_id = "test-connection-id10";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
 
-        // Message ids: IDE - 4, Server - 3, Client - 2->5.
-        let file_path = "foo.py".to_string();
+        // Message ids: IDE - 4->13, Server - 3, Client - 2.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
         send_message(
-            &mut ws_client,
+            &mut ws_ide,
             &EditorMessage {
-                id: 2.0,
-                message: EditorMessageContents::Update(UpdateMessageContents {
+                id: 4.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
                     file_path: file_path.clone(),
-                    contents: Some(CodeChatForWeb {
-                        metadata: SourceFileMetadata {
-                            mode: "python".to_string(),
-                        },
-                        source: CodeMirror {
-                            doc: "\n".to_string(),
-                            doc_blocks: vec![(
-                                0,
-                                0,
-                                "".to_string(),
-                                "#".to_string(),
-                                "less\n".to_string(),
-                            )],
-                        },
-                    }),
-                    cursor_position: None,
-                    scroll_position: None,
+                    view_target: ViewTarget::Active,
                 }),
             },
         )
         .await;
-        assert_eq!(
-            read_message(&mut ws_ide).await,
-            EditorMessage {
-                id: 2.0,
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 7.0,
                 message: EditorMessageContents::Update(UpdateMessageContents {
-                    file_path,
+                    file_path: file_path.clone(),
                     contents: Some(CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
-                            doc: "# less\n".to_string(),
+                            doc: "# more".to_string(),
                             doc_blocks: vec![],
                         },
                     }),
                     cursor_position: None,
                     scroll_position: None,
-                })
-            }
-        );
-        send_message(
-            &mut ws_ide,
+                }),
+            },
+        )
+        .await;
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 10.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 4.0);
+        cast!(em.message, EditorMessageContents::CurrentFile);
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 7.0);
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::Update).file_path,
+            file_path
+        );
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 10.0);
+        cast!(em.message, EditorMessageContents::CurrentFile);
+
+        for id in [4.0, 7.0, 10.0] {
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // List then switch between open files.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket12() {
+        let connection_id = "test-connection-id12";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Open a file in the IDE, then edit it.
+        //
+        // Message ids: IDE - 4->10, Server - 3, Client - 2.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 7.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents: Some(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            notebook: None,
+                        },
+                        source: CodeMirror {
+                            doc: "# more".to_string(),
+                            doc_blocks: vec![],
+                        },
+                    }),
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 4.0);
+        cast!(em.message, EditorMessageContents::CurrentFile);
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 7.0);
+        let contents = cast!(em.message, EditorMessageContents::Update).contents;
+        for id in [4.0, 7.0] {
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
+
+        // List the open files.
+        //
+        // Message ids: IDE - 10, Server - 3, Client - 2->5.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::ListOpenFiles,
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::OpenFiles(vec![
+                    file_path.clone()
+                ])))
+            }
+        );
+
+        // Switch to this file. The IDE receives a `CurrentFile`, while the
+        // Client receives its most recent contents.
+        //
+        // Message ids: IDE - 10, Server - 3->6, Client - 5->8.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::SwitchFile(file_path.clone()),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                })
+            }
+        );
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 3.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents,
+                    cursor_position: None,
+                    scroll_position: None,
+                })
+            }
+        );
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 3.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 3.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        // Switching to a file which isn't open fails.
+        //
+        // Message ids: IDE - 10, Server - 6, Client - 8->11.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 8.0,
+                message: EditorMessageContents::SwitchFile("unknown.py".to_string()),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 8.0);
+        assert!(cast!(em.message, EditorMessageContents::Result).is_err());
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Show a file in an additional view.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket13() {
+        let connection_id = "test-connection-id13";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        let file_path = fs::canonicalize(test_dir.join("test.py")).unwrap();
+        let url = format!(
+            "http://localhost:8080/vsc/fs/{connection_id}/{}",
+            file_path.to_str().unwrap()
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::OpenView(url.clone()),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        // Loading this file provides its contents, as if it were the current
+        // file.
+        let url_thread = url.clone();
+        let join_handle = thread::spawn(move || {
+            assert_eq!(minreq::get(url_thread).send().unwrap().status_code, 200)
+        });
+
+        // Message ids: IDE - 4, Server - 3->6, Client - 5.
+        let em = read_message(&mut ws_ide).await;
+        cast!(em.message, EditorMessageContents::LoadFile);
+        assert_eq!(em.id, 3.0);
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 3.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::LoadFile(Some(
+                    "# view\n".to_string(),
+                )))),
+            },
+        )
+        .await;
+        join_handle.join().unwrap();
+
+        // Message ids: IDE - 4, Server - 6->9, Client - 5.
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 6.0);
+        let umc = cast!(em.message, EditorMessageContents::Update);
+        assert_eq!(umc.file_path, file_path.to_str().unwrap());
+        assert_eq!(
+            umc.contents.unwrap().source.doc_blocks[0].4,
+            "<p>view</p>\n"
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 6.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 6.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        // Close the view.
+        //
+        // Message ids: IDE - 4, Server - 9, Client - 5->8.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::CloseView(url),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Send an `Update` message from the Client.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket6() {
+        let connection_id = "test-connection-id6";
+        let (temp_dir, _, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        let file_path = "foo.py".to_string();
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents: Some(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            notebook: None,
+                        },
+                        source: CodeMirror {
+                            doc: "\n".to_string(),
+                            doc_blocks: vec![(
+                                0,
+                                0,
+                                "".to_string(),
+                                "#".to_string(),
+                                "less\n".to_string(),
+                            )],
+                        },
+                    }),
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path,
+                    contents: Some(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            notebook: None,
+                        },
+                        source: CodeMirror {
+                            doc: "# less\n".to_string(),
+                            doc_blocks: vec![],
+                        },
+                    }),
+                    cursor_position: None,
+                    scroll_position: None,
+                })
+            }
+        );
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Send a `CurrentFile` message from the Client, requesting a file that
+    // exists on disk, but not in the IDE.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket4() {
+        let connection_id = "test-connection-id4";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        let file_path = format!("{}{MAIN_SEPARATOR_STR}test.py", test_dir.to_str().unwrap());
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: format!(
+                        "http://localhost:8080/vsc/fs/{connection_id}/{}",
+                        &file_path
+                    ),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+
+        let em = read_message(&mut ws_ide).await;
+        let cf = cast!(em.message, EditorMessageContents::CurrentFile);
+        assert_eq!(
+            fs::canonicalize(Path::new(&cf.file_path)).unwrap(),
+            fs::canonicalize(Path::new(&format!(
+                "{}/test.py",
+                test_dir.to_str().unwrap()
+            )))
+            .unwrap()
+        );
+        assert_eq!(em.id, 2.0);
+
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        // The Client should send a GET request for this file.
+        let test_dir_thread = test_dir.clone();
+        let join_handle = thread::spawn(move || {
+            assert_eq!(
+                minreq::get(format!(
+                    "http://localhost:8080/vsc/fs/{connection_id}/{}/test.py",
+                    test_dir_thread.to_str().unwrap()
+                ))
+                .send()
+                .unwrap()
+                .status_code,
+                200
+            )
+        });
+
+        // This should produce a `LoadFile` message.
+        //
+        // Message ids: IDE - 4, Server - 3->6, Client - 5.
+        let em = read_message(&mut ws_ide).await;
+        let msg = cast!(em.message, EditorMessageContents::LoadFile);
+        assert_eq!(
+            path::absolute(Path::new(&msg)).unwrap(),
+            path::absolute(format!("{}/test.py", test_dir.to_str().unwrap())).unwrap()
+        );
+        assert_eq!(em.id, 3.0);
+
+        // Reply to the `LoadFile` message: the IDE doesn't have the file.
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 3.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::LoadFile(None))),
+            },
+        )
+        .await;
+        join_handle.join().unwrap();
+
+        // This should also produce an `Update` message sent from the Server.
+        //
+        // Message ids: IDE - 4, Server - 6->9, Client - 5.
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 6.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents: Some(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            notebook: None,
+                        },
+                        source: CodeMirror {
+                            doc: "\n".to_string(),
+                            doc_blocks: vec![(
+                                0,
+                                0,
+                                "".to_string(),
+                                "#".to_string(),
+                                "<p>test.py</p>\n".to_string()
+                            )],
+                        },
+                    }),
+                    cursor_position: None,
+                    scroll_position: None,
+                })
+            }
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 6.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 6.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            }
+        );
+
+        // Since the IDE doesn't have this file, the Server saves changes to it
+        // on disk.
+        //
+        // Message ids: IDE - 4, Server - 9, Client - 5->8.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents: Some(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            notebook: None,
+                        },
+                        source: CodeMirror {
+                            doc: "\n".to_string(),
+                            doc_blocks: vec![(
+                                0,
+                                0,
+                                "".to_string(),
+                                "#".to_string(),
+                                "saved\n".to_string(),
+                            )],
+                        },
+                    }),
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "# saved\n");
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Request a file which the IDE never provides; after resending the
+    // `LoadFile` request, the Server reads it from disk.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket11() {
+        let connection_id = "test-connection-id11";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        let file_path = format!("{}{MAIN_SEPARATOR_STR}test.py", test_dir.to_str().unwrap());
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: format!(
+                        "http://localhost:8080/vsc/fs/{connection_id}/{}",
+                        &file_path
+                    ),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_ide).await;
+        cast!(em.message, EditorMessageContents::CurrentFile);
+        assert_eq!(em.id, 2.0);
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        // The Client should send a GET request for this file.
+        let test_dir_thread = test_dir.clone();
+        let join_handle = thread::spawn(move || {
+            assert_eq!(
+                minreq::get(format!(
+                    "http://localhost:8080/vsc/fs/{connection_id}/{}/test.py",
+                    test_dir_thread.to_str().unwrap()
+                ))
+                .send()
+                .unwrap()
+                .status_code,
+                200
+            )
+        });
+
+        // Ignore the `LoadFile` message, then each resend of it.
+        //
+        // Message ids: IDE - 4, Server - 3->12, Client - 5.
+        for id in [3.0, 6.0, 9.0] {
+            let em = read_message(&mut ws_ide).await;
+            cast!(em.message, EditorMessageContents::LoadFile);
+            assert_eq!(em.id, id);
+        }
+        join_handle.join().unwrap();
+
+        // The Server then reads the file from disk, warning that it may be
+        // out of date.
+        //
+        // Message ids: IDE - 4, Server - 12->18, Client - 5.
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 12.0);
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::Update).file_path,
+            file_path
+        );
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 15.0);
+        let diagnostics = cast!(em.message, EditorMessageContents::Diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source, "ide");
+
+        for id in [12.0, 15.0] {
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
+
+        // Each of the three `LoadFile` messages timed out; the last timeout is
+        // also reported by the processing task.
+        check_logger_errors(4);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Send a `RequestClose` message to the Client, then close the Client.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket5() {
+        let connection_id = "test-connection-id5";
+        let (temp_dir, _, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4->7, Server - 3, Client - 2.
+        //
+        // Send the `RequestClose` message.
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::RequestClose,
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::RequestClose
+            }
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            }
+        );
+
+        // Close the Client websocket.
+        ws_client.close(None).await.unwrap();
+        loop {
+            match ws_ide.next().await.unwrap().unwrap() {
+                Message::Ping(_) => ws_ide.send(Message::Pong(vec![].into())).await.unwrap(),
+                Message::Close(_) => break,
+                _ => panic!("Unexpected message."),
+            }
+        }
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Close the IDE.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket9() {
+        let connection_id = "test-connection-id9";
+        let (temp_dir, _, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        ws_ide.close(None).await.unwrap();
+        loop {
+            match ws_client.next().await.unwrap().unwrap() {
+                Message::Ping(_) => ws_client.send(Message::Pong(vec![].into())).await.unwrap(),
+                Message::Close(_) => break,
+                _ => panic!("Unexpected message."),
+            }
+        }
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Request the outline of a file from the IDE and the Client.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket14() {
+        let connection_id = "test-connection-id14";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Open a file in the IDE, edit it, then request its outline. The
+        // `Outline` waits for the translation of this edit.
+        //
+        // Message ids: IDE - 4->13, Server - 3, Client - 2.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 7.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents: Some(CodeChatForWeb {
+                        metadata: SourceFileMetadata {
+                            mode: "python".to_string(),
+                            notebook: None,
+                        },
+                        source: CodeMirror {
+                            doc: "a = 1\n# ## Setup\n".to_string(),
+                            doc_blocks: vec![],
+                        },
+                    }),
+                    cursor_position: None,
+                    scroll_position: None,
+                }),
+            },
+        )
+        .await;
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 10.0,
+                message: EditorMessageContents::Outline(file_path.clone()),
+            },
+        )
+        .await;
+        let outline = vec![OutlineEntry {
+            level: 2,
+            title: "Setup".to_string(),
+            line: 2,
+        }];
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 10.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Outline(outline.clone())))
+            }
+        );
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 4.0);
+        cast!(em.message, EditorMessageContents::CurrentFile);
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 7.0);
+        cast!(em.message, EditorMessageContents::Update);
+
+        // The Client receives the same outline; a file which hasn't been
+        // translated has an empty outline.
+        //
+        // Message ids: IDE - 13, Server - 3, Client - 2->8.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Outline(file_path.clone()),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Outline(outline)))
+            }
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Outline("other.py".to_string()),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Outline(vec![])))
+            }
+        );
+
+        for id in [4.0, 7.0] {
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Jump to a definition named in a doc block.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket15() {
+        let connection_id = "test-connection-id15";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Without a `tags` file, the Server forwards this to the IDE without a
+        // definition.
+        //
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        let goto_definition_contents = GotoDefinitionContents {
+            file_path: format!("{}/test.py", test_dir.to_str().unwrap()),
+            identifier: "parse_args".to_string(),
+            definition: None,
+        };
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::GotoDefinition(goto_definition_contents),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_ide).await;
+        assert_eq!(em.id, 2.0);
+        let goto_definition_contents = cast!(em.message, EditorMessageContents::GotoDefinition);
+        assert_eq!(goto_definition_contents.identifier, "parse_args");
+        assert_eq!(goto_definition_contents.definition, None);
+
+        // The IDE's result goes to the Client.
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Provide snippets to both the IDE and the Client.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket16() {
+        let connection_id = "test-connection-id16";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // The IDE lists the snippets, then inserts one as source code.
+        //
+        // Message ids: IDE - 4->10, Server - 3, Client - 2.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::ListSnippets(file_path.clone()),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_ide).await;
+        assert_eq!(em.id, 4.0);
+        let names = cast!(
+            cast!(cast!(em.message, EditorMessageContents::Result), Ok),
+            ResultOkTypes::Snippets
+        );
+        assert!(names.contains(&"admonition".to_string()));
+
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 7.0,
+                message: EditorMessageContents::InsertSnippet(InsertSnippetContents {
+                    file_path: file_path.clone(),
+                    name: "admonition".to_string(),
+                }),
+            },
+        )
+        .await;
+        let em = read_message(&mut ws_ide).await;
+        assert_eq!(em.id, 7.0);
+        let snippet = cast!(
+            cast!(cast!(em.message, EditorMessageContents::Result), Ok),
+            ResultOkTypes::Snippet
+        );
+        assert_eq!(snippet.source, "# :::note Title\n# Text.\n# :::\n");
+
+        // The Client inserts the same snippet as HTML; an unknown snippet
+        // produces an error.
+        //
+        // Message ids: IDE - 10, Server - 3, Client - 2->8.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::InsertSnippet(InsertSnippetContents {
+                    file_path: file_path.clone(),
+                    name: "admonition".to_string(),
+                }),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Snippet(snippet)))
+            }
+        );
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::InsertSnippet(InsertSnippetContents {
+                    file_path,
+                    name: "unknown".to_string(),
+                }),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Err(
+                    "Unknown snippet 'unknown'.".to_string()
+                ))
+            }
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Open external URLs using the IDE.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket17() {
+        let connection_id = "test-connection-id17";
+        let (temp_dir, _test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // The Server forwards this to the IDE, whose result goes to the
+        // Client.
+        //
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::OpenUrl("https://example.com/".to_string()),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::OpenUrl("https://example.com/".to_string()),
+            }
+        );
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 2.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+            }
+        );
+
+        // Other schemes are rejected by the Server.
+        //
+        // Message ids: IDE - 4, Server - 3, Client - 5->8.
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::OpenUrl(
+                    "command:workbench.action.quit".to_string(),
+                ),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 5.0,
+                message: EditorMessageContents::Result(Err(
+                    "Only http and https URLs may be opened, not command URLs.".to_string()
+                ))
+            }
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Forward folded regions from the Client to the IDE.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket18() {
+        let connection_id = "test-connection-id18";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4, Server - 3, Client - 2->5.
+        let fold_ranges = || {
+            EditorMessageContents::FoldRanges(FoldRangesContents {
+                file_path: format!("{}/test.py", test_dir.to_str().unwrap()),
+                ranges: vec![FoldRange { start: 2, end: 5 }],
+            })
+        };
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 2.0,
+                message: fold_ranges(),
+            },
+        )
+        .await;
+        assert_eq!(
+            read_message(&mut ws_ide).await,
+            EditorMessage {
+                id: 2.0,
+                message: fold_ranges(),
+            }
+        );
+        send_message(
+            &mut ws_ide,
             &EditorMessage {
                 id: 2.0,
                 message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
@@ -1268,213 +3213,446 @@ mod test {
         temp_dir.close().unwrap();
     }
 
-    // Send a `CurrentFile` message from the Client, requesting a file that
-    // exists on disk, but not in the IDE.
+    // Forward the saved state of a file from the IDE to the Client.
     #[actix_web::test]
-    async fn test_vscode_ide_websocket4() {
-        let connection_id = "test-connection-id4";
+    async fn test_vscode_ide_websocket19() {
+        let connection_id = "test-connection-id19";
         let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
 
-        // Message ids: IDE - 4, Server - 3, Client - 2->5.
-        let file_path = format!("{}{MAIN_SEPARATOR_STR}test.py", test_dir.to_str().unwrap());
+        // Message ids: IDE - 4->7, Server - 3, Client - 2.
+        let saved_state = EditorMessageContents::SavedState(SavedStateContents {
+            file_path: format!("{}/test.py", test_dir.to_str().unwrap()),
+            state: SaveState::Dirty,
+        });
         send_message(
-            &mut ws_client,
+            &mut ws_ide,
             &EditorMessage {
-                id: 2.0,
-                message: EditorMessageContents::CurrentFile(format!(
-                    "http://localhost:8080/vsc/fs/{connection_id}/{}",
-                    &file_path
-                )),
+                id: 4.0,
+                message: saved_state,
             },
         )
         .await;
-
-        let em = read_message(&mut ws_ide).await;
-        let cf = cast!(em.message, EditorMessageContents::CurrentFile);
         assert_eq!(
-            fs::canonicalize(Path::new(&cf)).unwrap(),
-            fs::canonicalize(Path::new(&format!(
-                "{}/test.py",
-                test_dir.to_str().unwrap()
-            )))
-            .unwrap()
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::SavedState(SavedStateContents {
+                    file_path: format!("{}/test.py", test_dir.to_str().unwrap()),
+                    state: SaveState::Dirty,
+                }),
+            }
         );
-        assert_eq!(em.id, 2.0);
-
         send_message(
-            &mut ws_ide,
+            &mut ws_client,
             &EditorMessage {
-                id: 2.0,
+                id: 4.0,
                 message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
             },
         )
         .await;
         assert_eq!(
-            read_message(&mut ws_client).await,
+            read_message(&mut ws_ide).await,
             EditorMessage {
-                id: 2.0,
+                id: 4.0,
                 message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
             }
         );
 
-        // The Client should send a GET request for this file.
-        let test_dir_thread = test_dir.clone();
-        let join_handle = thread::spawn(move || {
-            assert_eq!(
-                minreq::get(format!(
-                    "http://localhost:8080/vsc/fs/{connection_id}/{}/test.py",
-                    test_dir_thread.to_str().unwrap()
-                ))
-                .send()
-                .unwrap()
-                .status_code,
-                200
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Forward requests to save from the Client to the IDE.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket20() {
+        let connection_id = "test-connection-id20";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4, Server - 3, Client - 2->5->8.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
+        for (id, message) in [
+            (2.0, EditorMessageContents::Save(Some(file_path))),
+            (5.0, EditorMessageContents::SaveAll),
+        ] {
+            let message = EditorMessage { id, message };
+            send_message(&mut ws_client, &message).await;
+            assert_eq!(read_message(&mut ws_ide).await, message);
+            send_message(
+                &mut ws_ide,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
             )
-        });
+            .await;
+            assert_eq!(
+                read_message(&mut ws_client).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
 
-        // This should produce a `LoadFile` message.
-        //
-        // Message ids: IDE - 4, Server - 3->6, Client - 5.
-        let em = read_message(&mut ws_ide).await;
-        let msg = cast!(em.message, EditorMessageContents::LoadFile);
-        assert_eq!(
-            path::absolute(Path::new(&msg)).unwrap(),
-            path::absolute(format!("{}/test.py", test_dir.to_str().unwrap())).unwrap()
-        );
-        assert_eq!(em.id, 3.0);
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
 
-        // Reply to the `LoadFile` message: the IDE doesn't have the file.
+    // Test exporting a PDF.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket21() {
+        let connection_id = "test-connection-id21";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // The Server, not the IDE, replies. A file outside a project has no
+        // project to export.
+        //
+        // Message ids: IDE - 4, Server - 3, Client - 2.
+        let file_path = test_dir.join("test.py");
         send_message(
-            &mut ws_ide,
+            &mut ws_client,
             &EditorMessage {
-                id: 3.0,
-                message: EditorMessageContents::Result(Ok(ResultOkTypes::LoadFile(None))),
+                id: 2.0,
+                message: EditorMessageContents::Export(ExportContents {
+                    file_path: file_path.to_str().unwrap().to_string(),
+                    project: true,
+                }),
             },
         )
         .await;
-        join_handle.join().unwrap();
-
-        // This should also produce an `Update` message sent from the Server.
-        //
-        // Message ids: IDE - 4, Server - 6->9, Client - 5.
         assert_eq!(
             read_message(&mut ws_client).await,
             EditorMessage {
-                id: 6.0,
+                id: 2.0,
+                message: EditorMessageContents::Result(Err(format!(
+                    "{file_path:?} isn't part of a project."
+                )))
+            }
+        );
+
+        check_logger_errors(0);
+        // Report any errors produced when removing the temporary directory.
+        temp_dir.close().unwrap();
+    }
+
+    // Send an `Update` with contents, then one with only a scroll position,
+    // without waiting; the scroll position must not overtake the contents.
+    #[actix_web::test]
+    async fn test_vscode_ide_websocket22() {
+        let connection_id = "test-connection-id22";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4->16, Server - 3, Client - 2.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
+        let update =
+            |id: f64, doc: Option<&str>, scroll_position: Option<ScrollPosition>| EditorMessage {
+                id,
                 message: EditorMessageContents::Update(UpdateMessageContents {
-                    file_path,
-                    contents: Some(CodeChatForWeb {
+                    file_path: file_path.clone(),
+                    contents: doc.map(|doc| CodeChatForWeb {
                         metadata: SourceFileMetadata {
                             mode: "python".to_string(),
+                            notebook: None,
                         },
                         source: CodeMirror {
-                            doc: "\n".to_string(),
-                            doc_blocks: vec![(
-                                0,
-                                0,
-                                "".to_string(),
-                                "#".to_string(),
-                                "<p>test.py</p>\n".to_string()
-                            )],
+                            doc: doc.to_string(),
+                            doc_blocks: vec![],
                         },
                     }),
                     cursor_position: None,
-                    scroll_position: None,
-                })
-            }
-        );
+                    scroll_position,
+                }),
+            };
+
+        // Open the file and provide its contents, so that a scroll position
+        // can be translated.
         send_message(
-            &mut ws_client,
+            &mut ws_ide,
             &EditorMessage {
-                id: 6.0,
-                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                id: 4.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                }),
             },
         )
         .await;
-        assert_eq!(
-            read_message(&mut ws_ide).await,
-            EditorMessage {
-                id: 6.0,
-                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
-            }
-        );
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 4.0);
+        cast!(em.message, EditorMessageContents::CurrentFile);
+        send_message(&mut ws_ide, &update(7.0, Some("# one"), None)).await;
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 7.0);
+        cast!(em.message, EditorMessageContents::Update);
+
+        // Now change the contents, then scroll before their translation
+        // finishes.
+        send_message(&mut ws_ide, &update(10.0, Some("# one\n# two"), None)).await;
+        send_message(
+            &mut ws_ide,
+            &update(13.0, None, Some(ScrollPosition::Line { line: 1.0 })),
+        )
+        .await;
+
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 10.0);
+        assert!(cast!(em.message, EditorMessageContents::Update)
+            .contents
+            .is_some());
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 13.0);
+        let scroll_update = cast!(em.message, EditorMessageContents::Update);
+        assert!(scroll_update.contents.is_none());
+        assert!(scroll_update.scroll_position.is_some());
+
+        for id in [4.0, 7.0, 10.0, 13.0] {
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
 
         check_logger_errors(0);
         // Report any errors produced when removing the temporary directory.
         temp_dir.close().unwrap();
     }
 
-    // Send a `RequestClose` message to the Client, then close the Client.
+    // Send changes to the Client's contents, rather than new contents; if the
+    // Client no longer has those contents, send new contents.
     #[actix_web::test]
-    async fn test_vscode_ide_websocket5() {
-        let connection_id = "test-connection-id5";
-        let (temp_dir, _, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
+    async fn test_vscode_ide_websocket23() {
+        let connection_id = "test-connection-id23";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
 
-        // Message ids: IDE - 4->7, Server - 3, Client - 2.
-        //
-        // Send the `RequestClose` message.
+        // Message ids: IDE - 4->13, Server - 3->6, Client - 2.
+        let file_path = format!("{}/test.py", test_dir.to_str().unwrap());
+        let code = "y = 2\n".repeat(20);
+        let update = |id: f64, doc: String| EditorMessage {
+            id,
+            message: EditorMessageContents::Update(UpdateMessageContents {
+                file_path: file_path.clone(),
+                contents: Some(CodeChatForWeb {
+                    metadata: SourceFileMetadata {
+                        mode: "python".to_string(),
+                        notebook: None,
+                    },
+                    source: CodeMirror {
+                        doc,
+                        doc_blocks: vec![],
+                    },
+                }),
+                cursor_position: None,
+                scroll_position: None,
+            }),
+        };
         send_message(
             &mut ws_ide,
             &EditorMessage {
                 id: 4.0,
-                message: EditorMessageContents::RequestClose,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path.clone(),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+        send_message(&mut ws_ide, &update(7.0, format!("# Doc\nx = 1\n{code}"))).await;
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 4.0);
+        cast!(em.message, EditorMessageContents::CurrentFile);
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 7.0);
+        let contents = cast!(em.message, EditorMessageContents::Update)
+            .contents
+            .unwrap();
+        for id in [4.0, 7.0] {
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
+        }
+
+        // A small change is sent as changes to the contents of message 7.
+        send_message(&mut ws_ide, &update(10.0, format!("# Doc\nx = 3\n{code}"))).await;
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 10.0);
+        let update_diff = cast!(em.message, EditorMessageContents::UpdateDiff);
+        assert_eq!(update_diff.file_path, file_path);
+        assert_eq!(update_diff.base_id, 7.0);
+        assert_eq!(update_diff.diff.doc.len(), 1);
+        assert_eq!(update_diff.diff.doc[0].insert, "3");
+        assert!(update_diff.diff.doc_blocks.is_empty());
+        send_message(
+            &mut ws_client,
+            &EditorMessage {
+                id: 10.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
             },
         )
         .await;
         assert_eq!(
-            read_message(&mut ws_client).await,
+            read_message(&mut ws_ide).await,
             EditorMessage {
-                id: 4.0,
-                message: EditorMessageContents::RequestClose
+                id: 10.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
             }
         );
+
+        // If the Client can't apply these changes, it receives the new
+        // contents instead.
+        send_message(&mut ws_ide, &update(13.0, format!("# Doc\nx = 1\n{code}"))).await;
+        let em = read_message(&mut ws_client).await;
+        assert_eq!(em.id, 13.0);
+        assert_eq!(
+            cast!(em.message, EditorMessageContents::UpdateDiff).base_id,
+            10.0
+        );
         send_message(
             &mut ws_client,
             &EditorMessage {
-                id: 4.0,
-                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                id: 13.0,
+                message: EditorMessageContents::Result(Err("Out of date.".to_string())),
             },
         )
         .await;
         assert_eq!(
             read_message(&mut ws_ide).await,
             EditorMessage {
-                id: 4.0,
-                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                id: 13.0,
+                message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
             }
         );
-
-        // Close the Client websocket.
-        ws_client.close(None).await.unwrap();
-        loop {
-            match ws_ide.next().await.unwrap().unwrap() {
-                Message::Ping(_) => ws_ide.send(Message::Pong(vec![].into())).await.unwrap(),
-                Message::Close(_) => break,
-                _ => panic!("Unexpected message."),
+        assert_eq!(
+            read_message(&mut ws_client).await,
+            EditorMessage {
+                id: 3.0,
+                message: EditorMessageContents::Update(UpdateMessageContents {
+                    file_path: file_path.clone(),
+                    contents: Some(contents),
+                    cursor_position: None,
+                    scroll_position: None,
+                })
             }
-        }
+        );
 
         check_logger_errors(0);
         // Report any errors produced when removing the temporary directory.
         temp_dir.close().unwrap();
     }
 
-    // Close the IDE.
+    // Updates to two files reach the Client in the order the IDE sent them,
+    // even if the second file's translation finishes first.
     #[actix_web::test]
-    async fn test_vscode_ide_websocket9() {
-        let connection_id = "test-connection-id9";
-        let (temp_dir, _, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
-        open_client(&mut ws_ide).await;
-
-        ws_ide.close(None).await.unwrap();
-        loop {
-            match ws_client.next().await.unwrap().unwrap() {
-                Message::Ping(_) => ws_client.send(Message::Pong(vec![].into())).await.unwrap(),
-                Message::Close(_) => break,
-                _ => panic!("Unexpected message."),
+    async fn test_vscode_ide_websocket24() {
+        let connection_id = "test-connection-id24";
+        let (temp_dir, test_dir, mut ws_ide, mut ws_client) = prep_test!(connection_id).await;
+        open_client(&mut ws_ide, &mut ws_client).await;
+
+        // Message ids: IDE - 4->13, Server - 3, Client - 2.
+        let file_path_a = format!("{}/a.py", test_dir.to_str().unwrap());
+        let file_path_b = format!("{}/b.py", test_dir.to_str().unwrap());
+        let update = |id: f64, file_path: &str, doc: String| EditorMessage {
+            id,
+            message: EditorMessageContents::Update(UpdateMessageContents {
+                file_path: file_path.to_string(),
+                contents: Some(CodeChatForWeb {
+                    metadata: SourceFileMetadata {
+                        mode: "python".to_string(),
+                        notebook: None,
+                    },
+                    source: CodeMirror {
+                        doc,
+                        doc_blocks: vec![],
+                    },
+                }),
+                cursor_position: None,
+                scroll_position: None,
+            }),
+        };
+        send_message(
+            &mut ws_ide,
+            &EditorMessage {
+                id: 4.0,
+                message: EditorMessageContents::CurrentFile(CurrentFileContents {
+                    file_path: file_path_a.clone(),
+                    view_target: ViewTarget::Active,
+                }),
+            },
+        )
+        .await;
+        // The first file takes much longer to translate than the second.
+        send_message(
+            &mut ws_ide,
+            &update(
+                7.0,
+                &file_path_a,
+                format!("# Doc\n{}", "x = 1\n".repeat(20000)),
+            ),
+        )
+        .await;
+        send_message(&mut ws_ide, &update(10.0, &file_path_b, "# b".to_string())).await;
+
+        // Acknowledge each message as it arrives.
+        for (id, file_path) in [
+            (4.0, None),
+            (7.0, Some(&file_path_a)),
+            (10.0, Some(&file_path_b)),
+        ] {
+            let em = read_message(&mut ws_client).await;
+            assert_eq!(em.id, id);
+            match file_path {
+                None => {
+                    cast!(em.message, EditorMessageContents::CurrentFile);
+                }
+                Some(file_path) => assert_eq!(
+                    &cast!(em.message, EditorMessageContents::Update).file_path,
+                    file_path
+                ),
             }
+            send_message(
+                &mut ws_client,
+                &EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void)),
+                },
+            )
+            .await;
+            assert_eq!(
+                read_message(&mut ws_ide).await,
+                EditorMessage {
+                    id,
+                    message: EditorMessageContents::Result(Ok(ResultOkTypes::Void))
+                }
+            );
         }
 
         check_logger_errors(0);
@@ -41,6 +41,17 @@ fn test_start_no_start() {
         .stderr(contains("Server failed to start after 5 seconds."));
 }
 
+#[test]
+fn test_serve_instructor_token_requires_share_lan() {
+    // Students couldn't reach a Server which only listens on the loopback
+    // address.
+    let mut cmd = get_server();
+    let assert = cmd
+        .args(["--port", "8083", "serve", "--instructor-token", "abc"])
+        .assert();
+    assert.failure().stderr(contains("--share-lan"));
+}
+
 #[actix_web::test]
 async fn test_start_no_response() {
     // Run a dummy webserver that doesn't respond to the `/stop` endpoint.
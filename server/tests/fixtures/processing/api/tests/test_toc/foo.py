@@ -0,0 +1,2 @@
+# Foo.
+foo = 1
@@ -0,0 +1 @@
+# Other
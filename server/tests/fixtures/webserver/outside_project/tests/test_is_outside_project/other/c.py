@@ -0,0 +1 @@
+# C file.
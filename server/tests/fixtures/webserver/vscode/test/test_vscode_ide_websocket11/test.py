@@ -0,0 +1 @@
+# test.py
\ No newline at end of file